@@ -0,0 +1,266 @@
+//! Журнал аудита вызовов API (только добавление записей; устаревшие записи
+//! удаляются по сроку хранения)
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::events::DEFAULT_TENANT;
+use crate::retention::{is_expired, replace_file};
+
+#[cfg(feature = "postgres")]
+pub use self::postgres::PostgresAuditLog;
+
+/// Запись о вызове API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: String, // RFC 3339
+    /// Заголовок `X-User-Id`, иначе адрес клиента
+    pub caller: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub request_bytes: usize,
+    pub response_bytes: usize,
+    pub duration_ms: u64,
+    /// Версии моделей (`ModelInfo::model_version`), построивших ответ
+    #[serde(default)]
+    pub model_versions: Vec<String>,
+    /// Изменение корректировок обучения (`/api/learn`)
+    #[serde(default)]
+    pub learning_update: Option<serde_json::Value>,
+    /// Арендатор запроса (из токена или `X-Tenant-Id`); записи арендатора
+    /// удаляются вместе с его данными
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+}
+
+/// Записи до разделения по арендаторам относятся к арендатору по умолчанию
+fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+/// Фильтр выборки; все поля необязательны
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditQuery {
+    pub tenant: Option<String>,
+    pub caller: Option<String>,
+    pub path: Option<String>,
+    /// Нижняя граница `timestamp` (RFC 3339, включительно)
+    pub since: Option<String>,
+    /// Верхняя граница `timestamp` (RFC 3339, не включительно)
+    pub until: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub const DEFAULT_LIMIT: usize = 100;
+
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        self.tenant.as_ref().is_none_or(|t| &record.tenant == t)
+            && self
+                .caller
+                .as_ref()
+                .is_none_or(|c| record.caller.as_ref() == Some(c))
+            && self.path.as_ref().is_none_or(|p| &record.path == p)
+            && self
+                .since
+                .as_ref()
+                .is_none_or(|since| timestamp_cmp(&record.timestamp, since).is_ge())
+            && self
+                .until
+                .as_ref()
+                .is_none_or(|until| timestamp_cmp(&record.timestamp, until).is_lt())
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(Self::DEFAULT_LIMIT)
+    }
+}
+
+#[async_trait]
+pub trait AuditLog: Send + Sync {
+    async fn append(&self, record: &AuditRecord) -> Result<(), String>;
+
+    /// Последние `query.limit()` подходящих записей в порядке поступления
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, String>;
+
+    /// Удаляет записи раньше `before`; возвращает их число
+    async fn prune(&self, before: DateTime<Utc>) -> Result<usize, String>;
+
+    /// Удаляет записи арендатора; возвращает их число
+    async fn delete_tenant(&self, tenant: &str) -> Result<usize, String>;
+}
+
+/// Журнал в памяти процесса; хранит последние `capacity` записей
+pub struct MemoryAuditLog {
+    capacity: usize,
+    records: Mutex<VecDeque<AuditRecord>>,
+}
+
+impl MemoryAuditLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<AuditRecord>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for MemoryAuditLog {
+    fn default() -> Self {
+        Self::new(10_000)
+    }
+}
+
+#[async_trait]
+impl AuditLog for MemoryAuditLog {
+    async fn append(&self, record: &AuditRecord) -> Result<(), String> {
+        let mut records = self.lock();
+        records.push_back(record.clone());
+        while records.len() > self.capacity {
+            records.pop_front();
+        }
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, String> {
+        Ok(select(self.lock().iter().cloned(), query))
+    }
+
+    async fn prune(&self, before: DateTime<Utc>) -> Result<usize, String> {
+        let mut records = self.lock();
+        let count = records.len();
+        records.retain(|r| !is_expired(&r.timestamp, before));
+        Ok(count - records.len())
+    }
+
+    async fn delete_tenant(&self, tenant: &str) -> Result<usize, String> {
+        let mut records = self.lock();
+        let count = records.len();
+        records.retain(|r| r.tenant != tenant);
+        Ok(count - records.len())
+    }
+}
+
+/// Журнал в файле JSON Lines (одна запись на строку)
+pub struct FileAuditLog {
+    path: PathBuf,
+    /// Сериализует запись, чтобы строки разных запросов не перемешивались
+    writer: tokio::sync::Mutex<()>,
+}
+
+impl FileAuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            writer: tokio::sync::Mutex::new(()),
+        }
+    }
+}
+
+#[async_trait]
+impl AuditLog for FileAuditLog {
+    async fn append(&self, record: &AuditRecord) -> Result<(), String> {
+        let mut line =
+            serde_json::to_vec(record).map_err(|e| format!("Serialization error: {}", e))?;
+        line.push(b'\n');
+
+        let _guard = self.writer.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        file.write_all(&line)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, String> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.path.display(), e)),
+        };
+
+        // Поврежденные строки (например, оборванная последняя) пропускаются
+        let records = content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok());
+        Ok(select(records, query))
+    }
+
+    async fn prune(&self, before: DateTime<Utc>) -> Result<usize, String> {
+        self.remove_where(|record| is_expired(&record.timestamp, before))
+            .await
+    }
+
+    async fn delete_tenant(&self, tenant: &str) -> Result<usize, String> {
+        self.remove_where(|record| record.tenant == tenant).await
+    }
+}
+
+impl FileAuditLog {
+    /// Удаляет записи, для которых `remove` - `true`. Файл переписывается через
+    /// временный; поврежденные строки сохраняются
+    async fn remove_where(&self, remove: impl Fn(&AuditRecord) -> bool) -> Result<usize, String> {
+        let _guard = self.writer.lock().await;
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.path.display(), e)),
+        };
+        let mut kept = String::with_capacity(content.len());
+        let mut removed = 0;
+        for line in content.lines() {
+            let matched = serde_json::from_str::<AuditRecord>(line).is_ok_and(|r| remove(&r));
+            if matched {
+                removed += 1;
+            } else {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+        if removed > 0 {
+            replace_file(&self.path, kept).await?;
+        }
+        Ok(removed)
+    }
+}
+
+fn select(records: impl Iterator<Item = AuditRecord>, query: &AuditQuery) -> Vec<AuditRecord> {
+    let mut selected: VecDeque<AuditRecord> = VecDeque::new();
+    for record in records.filter(|r| query.matches(r)) {
+        selected.push_back(record);
+        if selected.len() > query.limit() {
+            selected.pop_front();
+        }
+    }
+    selected.into()
+}
+
+/// Сравнение меток времени RFC 3339 с учетом часового пояса; некорректные
+/// значения сравниваются как строки
+fn timestamp_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    match (
+        chrono::DateTime::parse_from_rfc3339(a),
+        chrono::DateTime::parse_from_rfc3339(b),
+    ) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
@@ -0,0 +1,158 @@
+//! Журнал аудита в PostgreSQL
+
+use async_trait::async_trait;
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::Row;
+
+use super::{AuditLog, AuditQuery, AuditRecord};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS ml_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    timestamp TIMESTAMPTZ NOT NULL,
+    caller TEXT,
+    method TEXT NOT NULL,
+    path TEXT NOT NULL,
+    status SMALLINT NOT NULL,
+    request_bytes BIGINT NOT NULL,
+    response_bytes BIGINT NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    model_versions JSONB NOT NULL,
+    learning_update JSONB
+)";
+
+/// Записи до разделения по арендаторам относятся к арендатору по умолчанию
+const TENANT_COLUMN: &str =
+    "ALTER TABLE ml_audit_log ADD COLUMN IF NOT EXISTS tenant TEXT NOT NULL DEFAULT 'default'";
+
+pub struct PostgresAuditLog {
+    pool: PgPool,
+}
+
+impl PostgresAuditLog {
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        let pool = PgPoolOptions::new()
+            .max_connections(4)
+            .connect(url)
+            .await
+            .map_err(db_error)?;
+        for statement in [SCHEMA, TENANT_COLUMN] {
+            sqlx::query(statement)
+                .execute(&pool)
+                .await
+                .map_err(db_error)?;
+        }
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl AuditLog for PostgresAuditLog {
+    async fn append(&self, record: &AuditRecord) -> Result<(), String> {
+        let timestamp = chrono::DateTime::parse_from_rfc3339(&record.timestamp)
+            .map_err(|e| format!("Invalid timestamp: {}", e))?;
+        sqlx::query(
+            "INSERT INTO ml_audit_log (timestamp, caller, method, path, status, request_bytes,
+                response_bytes, duration_ms, model_versions, learning_update, tenant)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+        )
+        .bind(timestamp)
+        .bind(&record.caller)
+        .bind(&record.method)
+        .bind(&record.path)
+        .bind(record.status as i16)
+        .bind(record.request_bytes as i64)
+        .bind(record.response_bytes as i64)
+        .bind(record.duration_ms as i64)
+        .bind(serde_json::json!(record.model_versions))
+        .bind(&record.learning_update)
+        .bind(&record.tenant)
+        .execute(&self.pool)
+        .await
+        .map_err(db_error)?;
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, String> {
+        let since = parse_bound(query.since.as_deref())?;
+        let until = parse_bound(query.until.as_deref())?;
+
+        let rows = sqlx::query(
+            "SELECT * FROM (
+                SELECT * FROM ml_audit_log
+                WHERE ($1::TEXT IS NULL OR caller = $1)
+                  AND ($2::TEXT IS NULL OR path = $2)
+                  AND ($3::TIMESTAMPTZ IS NULL OR timestamp >= $3)
+                  AND ($4::TIMESTAMPTZ IS NULL OR timestamp < $4)
+                  AND ($6::TEXT IS NULL OR tenant = $6)
+                ORDER BY id DESC LIMIT $5
+             ) recent ORDER BY id",
+        )
+        .bind(&query.caller)
+        .bind(&query.path)
+        .bind(since)
+        .bind(until)
+        .bind(query.limit() as i64)
+        .bind(&query.tenant)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        rows.iter()
+            .map(|row| {
+                let timestamp: chrono::DateTime<chrono::Utc> =
+                    row.try_get("timestamp").map_err(db_error)?;
+                let status: i16 = row.try_get("status").map_err(db_error)?;
+                let request_bytes: i64 = row.try_get("request_bytes").map_err(db_error)?;
+                let response_bytes: i64 = row.try_get("response_bytes").map_err(db_error)?;
+                let duration_ms: i64 = row.try_get("duration_ms").map_err(db_error)?;
+                let model_versions: serde_json::Value =
+                    row.try_get("model_versions").map_err(db_error)?;
+                Ok(AuditRecord {
+                    timestamp: timestamp.to_rfc3339(),
+                    caller: row.try_get("caller").map_err(db_error)?,
+                    method: row.try_get("method").map_err(db_error)?,
+                    path: row.try_get("path").map_err(db_error)?,
+                    status: status as u16,
+                    request_bytes: request_bytes as usize,
+                    response_bytes: response_bytes as usize,
+                    duration_ms: duration_ms as u64,
+                    model_versions: serde_json::from_value(model_versions).unwrap_or_default(),
+                    learning_update: row.try_get("learning_update").map_err(db_error)?,
+                    tenant: row.try_get("tenant").map_err(db_error)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn prune(&self, before: chrono::DateTime<chrono::Utc>) -> Result<usize, String> {
+        let result = sqlx::query("DELETE FROM ml_audit_log WHERE timestamp < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(result.rows_affected() as usize)
+    }
+
+    async fn delete_tenant(&self, tenant: &str) -> Result<usize, String> {
+        let result = sqlx::query("DELETE FROM ml_audit_log WHERE tenant = $1")
+            .bind(tenant)
+            .execute(&self.pool)
+            .await
+            .map_err(db_error)?;
+        Ok(result.rows_affected() as usize)
+    }
+}
+
+fn parse_bound(value: Option<&str>) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+    value
+        .map(|v| {
+            chrono::DateTime::parse_from_rfc3339(v)
+                .map(|t| t.with_timezone(&chrono::Utc))
+                .map_err(|e| format!("Invalid timestamp {}: {}", v, e))
+        })
+        .transpose()
+}
+
+fn db_error(e: sqlx::Error) -> String {
+    format!("Database error: {}", e)
+}
@@ -0,0 +1,267 @@
+//! Аутентификация запросов по JWT (`Authorization: Bearer`).
+//!
+//! Токены HS256/384/512 проверяются общим секретом (`JWT_SECRET`), остальные -
+//! ключами JWKS (`JWT_JWKS_URL`) по `kid`. Арендатор берется из утверждения
+//! `JWT_TENANT_CLAIM` (`tenant_id` по умолчанию), пользователь - из `sub`
+
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value as JsonValue};
+use tokio::sync::RwLock;
+
+/// Утверждение с арендатором по умолчанию
+pub const DEFAULT_TENANT_CLAIM: &str = "tenant_id";
+
+/// Время жизни загруженного набора ключей JWKS
+const JWKS_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Незнакомый `kid` перезагружает JWKS не чаще этого интервала
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(60);
+
+/// Арендатор и пользователь проверенного токена
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub tenant: String,
+    pub user: Option<String>,
+}
+
+/// Набор ключей JWKS с кэшем
+struct Jwks {
+    url: String,
+    client: reqwest::Client,
+    cache: RwLock<Option<(Instant, JwkSet)>>,
+}
+
+impl Jwks {
+    async fn key(&self, kid: Option<&str>) -> Result<DecodingKey, String> {
+        let kid = kid.ok_or("Token header has no kid")?;
+        let stale = |fetched: &Instant, max_age: Duration| fetched.elapsed() >= max_age;
+
+        if let Some((fetched, keys)) = self.cache.read().await.as_ref() {
+            if !stale(fetched, JWKS_TTL) {
+                if let Some(jwk) = keys.find(kid) {
+                    return DecodingKey::from_jwk(jwk)
+                        .map_err(|e| format!("Invalid JWK {}: {}", kid, e));
+                }
+                if !stale(fetched, JWKS_MIN_REFRESH) {
+                    return Err(format!("Unknown key id: {}", kid));
+                }
+            }
+        }
+
+        let keys = self.fetch().await?;
+        let key = keys
+            .find(kid)
+            .ok_or_else(|| format!("Unknown key id: {}", kid))
+            .and_then(|jwk| {
+                DecodingKey::from_jwk(jwk).map_err(|e| format!("Invalid JWK {}: {}", kid, e))
+            });
+        *self.cache.write().await = Some((Instant::now(), keys));
+        key
+    }
+
+    async fn fetch(&self) -> Result<JwkSet, String> {
+        self.client
+            .get(&self.url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| format!("Failed to fetch JWKS {}: {}", self.url, e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid JWKS {}: {}", self.url, e))
+    }
+}
+
+/// Проверка токенов и извлечение арендатора
+pub struct JwtAuth {
+    secret: Option<DecodingKey>,
+    jwks: Option<Jwks>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    tenant_claim: String,
+    /// Арендаторы с доступом к `/api/admin/*` и журналу аудита
+    admin_tenants: HashSet<String>,
+}
+
+impl JwtAuth {
+    /// Настройки из `JWT_SECRET`, `JWT_JWKS_URL`, `JWT_ISSUER`, `JWT_AUDIENCE`,
+    /// `JWT_TENANT_CLAIM` и `JWT_ADMIN_TENANTS` (через запятую); `None`, если не
+    /// задан ни секрет, ни JWKS
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+        let secret = var("JWT_SECRET");
+        let jwks_url = var("JWT_JWKS_URL");
+        if secret.is_none() && jwks_url.is_none() {
+            return None;
+        }
+
+        Some(
+            Self {
+                secret: secret.map(|s| DecodingKey::from_secret(s.as_bytes())),
+                jwks: jwks_url.map(|url| Jwks {
+                    url,
+                    client: reqwest::Client::new(),
+                    cache: RwLock::new(None),
+                }),
+                issuer: var("JWT_ISSUER"),
+                audience: var("JWT_AUDIENCE"),
+                tenant_claim: var("JWT_TENANT_CLAIM")
+                    .unwrap_or_else(|| DEFAULT_TENANT_CLAIM.to_string()),
+                admin_tenants: HashSet::new(),
+            }
+            .admin_tenants(
+                var("JWT_ADMIN_TENANTS")
+                    .as_deref()
+                    .unwrap_or_default()
+                    .split(','),
+            ),
+        )
+    }
+
+    /// Проверка токенов HS256/384/512 секретом `secret` без издателя и аудитории
+    pub fn with_secret(secret: &str) -> Self {
+        Self {
+            secret: Some(DecodingKey::from_secret(secret.as_bytes())),
+            jwks: None,
+            issuer: None,
+            audience: None,
+            tenant_claim: DEFAULT_TENANT_CLAIM.to_string(),
+            admin_tenants: HashSet::new(),
+        }
+    }
+
+    /// Арендаторы с доступом к `/api/admin/*` и журналу аудита
+    pub fn admin_tenants<'a>(mut self, tenants: impl IntoIterator<Item = &'a str>) -> Self {
+        self.admin_tenants = tenants
+            .into_iter()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect();
+        self
+    }
+
+    pub fn is_admin(&self, identity: &Identity) -> bool {
+        self.admin_tenants.contains(&identity.tenant)
+    }
+
+    /// Проверяет подпись, срок действия, издателя и аудиторию токена
+    pub async fn authenticate(&self, token: &str) -> Result<Identity, String> {
+        let header = decode_header(token).map_err(|e| format!("Invalid token: {}", e))?;
+        let key = match header.alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => self
+                .secret
+                .clone()
+                .ok_or("HMAC-signed tokens are not accepted")?,
+            _ => match &self.jwks {
+                Some(jwks) => jwks.key(header.kid.as_deref()).await?,
+                None => return Err(format!("{:?} tokens are not accepted", header.alg)),
+            },
+        };
+
+        let mut validation = Validation::new(header.alg);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        let claims = decode::<Map<String, JsonValue>>(token, &key, &validation)
+            .map_err(|e| format!("Invalid token: {}", e))?
+            .claims;
+
+        let tenant = match claims.get(&self.tenant_claim) {
+            Some(JsonValue::String(tenant)) if !tenant.is_empty() => tenant.clone(),
+            Some(JsonValue::Number(tenant)) => tenant.to_string(),
+            _ => return Err(format!("Token has no {} claim", self.tenant_claim)),
+        };
+        Ok(Identity {
+            tenant,
+            user: claims
+                .get("sub")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    fn token(secret: &str, claims: JsonValue) -> String {
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn expires() -> i64 {
+        chrono::Utc::now().timestamp() + 600
+    }
+
+    #[tokio::test]
+    async fn tenant_and_user_come_from_a_valid_token() {
+        let auth = JwtAuth::with_secret("secret");
+        let claims = serde_json::json!({"tenant_id": "acme", "sub": "user-1", "exp": expires()});
+        assert_eq!(
+            auth.authenticate(&token("secret", claims)).await,
+            Ok(Identity {
+                tenant: "acme".to_string(),
+                user: Some("user-1".to_string()),
+            })
+        );
+
+        let numeric = serde_json::json!({"tenant_id": 42, "exp": expires()});
+        let identity = auth.authenticate(&token("secret", numeric)).await.unwrap();
+        assert_eq!(identity.tenant, "42");
+        assert_eq!(identity.user, None);
+    }
+
+    #[tokio::test]
+    async fn invalid_tokens_are_rejected() {
+        let auth = JwtAuth::with_secret("secret");
+        let valid = serde_json::json!({"tenant_id": "acme", "exp": expires()});
+        let rejected = [
+            token("other", valid),
+            token(
+                "secret",
+                serde_json::json!({"tenant_id": "acme", "exp": expires() - 3600}),
+            ),
+            token(
+                "secret",
+                serde_json::json!({"tenant_id": "", "exp": expires()}),
+            ),
+            token(
+                "secret",
+                serde_json::json!({"sub": "user-1", "exp": expires()}),
+            ),
+            token("secret", serde_json::json!({"tenant_id": "acme"})),
+            "not-a-token".to_string(),
+        ];
+        for token in rejected {
+            assert!(auth.authenticate(&token).await.is_err(), "{}", token);
+        }
+    }
+
+    #[test]
+    fn only_listed_tenants_are_administrators() {
+        let auth = JwtAuth::with_secret("secret").admin_tenants(" ops ,,root".split(','));
+        let identity = |tenant: &str| Identity {
+            tenant: tenant.to_string(),
+            user: None,
+        };
+        assert!(auth.is_admin(&identity("ops")));
+        assert!(auth.is_admin(&identity("root")));
+        assert!(!auth.is_admin(&identity("acme")));
+        assert!(!auth.is_admin(&identity("")));
+    }
+}
@@ -0,0 +1,123 @@
+//! Кэш матриц признаков аномалий между запросами `/api/detect-anomalies`.
+//!
+//! Клиент часто повторяет почти тот же большой запрос; признаки тех же записей
+//! не извлекаются заново. Кэш локален для процесса: матрицы не сериализуются.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use ndarray::Array2;
+use sha2::{Digest, Sha256};
+
+use crate::float::Float;
+use crate::preprocessing::FeatureEngineer;
+use crate::types::TimesheetEntry;
+
+/// Наборов записей одного арендатора в кэше: обучающие записи и окно проверки
+/// (`options.evaluation_days`) с запасом на чередование запросов
+const SETS_PER_TENANT: usize = 4;
+
+/// Матрицы признаков по арендатору и SHA-256 набора записей. Изменилась любая
+/// запись - изменился ключ, а старая матрица вытесняется новыми наборами
+pub struct AnomalyFeatureCache {
+    /// Арендаторов в кэше; 0 - кэш выключен
+    capacity: usize,
+    tenants: Mutex<HashMap<String, TenantFeatures>>,
+}
+
+struct TenantFeatures {
+    used_at: Instant,
+    /// Сначала последние использованные
+    sets: Vec<(String, Arc<Array2<Float>>)>,
+}
+
+impl AnomalyFeatureCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tenants: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, TenantFeatures>> {
+        self.tenants.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Признаки `entries` из кэша или только что извлеченные
+    pub fn features(&self, tenant: &str, entries: &[TimesheetEntry]) -> Arc<Array2<Float>> {
+        if self.capacity == 0 {
+            return Arc::new(FeatureEngineer::extract_anomaly_features(entries));
+        }
+        let key = entries_hash(entries);
+        if let Some(features) = self.get(tenant, &key) {
+            return features;
+        }
+
+        // Извлечение без блокировки: другие арендаторы не ждут
+        let features = Arc::new(FeatureEngineer::extract_anomaly_features(entries));
+        self.put(tenant, key, features.clone());
+        features
+    }
+
+    /// Забывает матрицы арендатора (удаление его данных)
+    pub fn remove(&self, tenant: &str) {
+        self.lock().remove(tenant);
+    }
+
+    fn get(&self, tenant: &str, key: &str) -> Option<Arc<Array2<Float>>> {
+        let mut tenants = self.lock();
+        let cached = tenants.get_mut(tenant)?;
+        let position = cached.sets.iter().position(|(k, _)| k == key)?;
+        let set = cached.sets.remove(position);
+        let features = set.1.clone();
+        cached.sets.insert(0, set);
+        cached.used_at = Instant::now();
+        Some(features)
+    }
+
+    fn put(&self, tenant: &str, key: String, features: Arc<Array2<Float>>) {
+        let mut tenants = self.lock();
+        // Кэш полон: вытесняем арендатора, дольше всех не обращавшегося к кэшу
+        if tenants.len() >= self.capacity && !tenants.contains_key(tenant) {
+            let oldest = tenants
+                .iter()
+                .min_by_key(|(_, cached)| cached.used_at)
+                .map(|(t, _)| t.clone());
+            if let Some(oldest) = oldest {
+                tenants.remove(&oldest);
+            }
+        }
+
+        let cached = tenants
+            .entry(tenant.to_string())
+            .or_insert_with(|| TenantFeatures {
+                used_at: Instant::now(),
+                sets: Vec::new(),
+            });
+        cached.sets.retain(|(k, _)| *k != key);
+        cached.sets.insert(0, (key, features));
+        cached.sets.truncate(SETS_PER_TENANT);
+        cached.used_at = Instant::now();
+    }
+}
+
+impl Default for AnomalyFeatureCache {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+/// SHA-256 набора записей в их порядке: признаки зависят от всех полей записей
+/// и средних по проектам всего набора
+fn entries_hash(entries: &[TimesheetEntry]) -> String {
+    let mut hasher = Sha256::new();
+    for entry in entries {
+        // Сериализация записи без ошибок: только строки, числа и списки строк
+        if let Ok(json) = serde_json::to_vec(entry) {
+            hasher.update(&json);
+        }
+        hasher.update([0u8]);
+    }
+    format!("{:x}", hasher.finalize())
+}
@@ -0,0 +1,181 @@
+//! Кэш результатов анализа.
+//!
+//! По умолчанию кэш локален для процесса; с feature `redis` реплики
+//! разделяют общий кэш.
+
+pub mod features;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+pub use self::features::AnomalyFeatureCache;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisCache;
+
+#[async_trait]
+pub trait PredictionCache: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<String>, String>;
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String>;
+
+    /// Сбрасывает все записи (например, после изменения корректировок обучения)
+    async fn clear(&self) -> Result<(), String>;
+
+    /// Сбрасывает записи арендатора (ключи `tenant_cache_key`)
+    async fn clear_tenant(&self, tenant: &str) -> Result<(), String>;
+}
+
+/// Ключ кэша: SHA-256 от области (`predict`, ...) и JSON запроса
+pub fn cache_key<T: Serialize>(scope: &str, request: &T) -> Result<String, String> {
+    let json = serde_json::to_vec(request).map_err(|e| format!("Serialization error: {}", e))?;
+    let mut hasher = Sha256::new();
+    hasher.update(scope.as_bytes());
+    hasher.update([0u8]);
+    hasher.update(&json);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Ключ кэша ответа арендатора: `<хэш арендатора>:<cache_key>`. Арендатор
+/// входит и в область ключа, а по префиксу `clear_tenant` находит его записи
+pub fn tenant_cache_key<T: Serialize>(
+    tenant: &str,
+    scope: &str,
+    request: &T,
+) -> Result<String, String> {
+    let key = cache_key(&format!("{}:{}", scope, tenant), request)?;
+    Ok(format!("{}{}", tenant_prefix(tenant), key))
+}
+
+/// Префикс ключей арендатора; хэш - чтобы идентификатор арендатора не
+/// попадал в шаблоны поиска ключей Redis
+pub(crate) fn tenant_prefix(tenant: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(tenant.as_bytes()));
+    format!("{}:", &hash[..16])
+}
+
+/// ETag ответа: SHA-256 от частей запроса (путь, тело, версии моделей, ...) в кавычках
+pub fn etag(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+        hasher.update([0u8]);
+    }
+    format!("\"{:x}\"", hasher.finalize())
+}
+
+/// Кэш в памяти процесса с ограничением по времени жизни и числу записей
+pub struct MemoryCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, (Instant, String)>>,
+}
+
+impl MemoryCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, (Instant, String)>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(300), 1000)
+    }
+}
+
+#[async_trait]
+impl PredictionCache for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let mut entries = self.lock();
+        match entries.get(key) {
+            Some((stored_at, value)) if stored_at.elapsed() < self.ttl => Ok(Some(value.clone())),
+            Some(_) => {
+                entries.remove(key);
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+
+        let mut entries = self.lock();
+        if entries.len() >= self.capacity && !entries.contains_key(key) {
+            let ttl = self.ttl;
+            entries.retain(|_, (stored_at, _)| stored_at.elapsed() < ttl);
+        }
+        // Кэш все еще полон: вытесняем самую старую запись
+        if entries.len() >= self.capacity && !entries.contains_key(key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (stored_at, _))| *stored_at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(key.to_string(), (Instant::now(), value.to_string()));
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.lock().clear();
+        Ok(())
+    }
+
+    async fn clear_tenant(&self, tenant: &str) -> Result<(), String> {
+        let prefix = tenant_prefix(tenant);
+        self.lock().retain(|key, _| !key.starts_with(&prefix));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tenant_entries_are_separate_and_cleared_per_tenant() {
+        let cache = MemoryCache::default();
+        let request = serde_json::json!({"weeks": [1, 2, 3]});
+        let acme = tenant_cache_key("acme", "predict", &request).unwrap();
+        let globex = tenant_cache_key("globex", "predict", &request).unwrap();
+        assert_ne!(acme, globex);
+
+        cache.put(&acme, "acme forecast").await.unwrap();
+        cache.put(&globex, "globex forecast").await.unwrap();
+        assert_eq!(
+            cache.get(&acme).await.unwrap().as_deref(),
+            Some("acme forecast")
+        );
+        assert_eq!(
+            cache.get(&globex).await.unwrap().as_deref(),
+            Some("globex forecast")
+        );
+
+        cache.clear_tenant("acme").await.unwrap();
+        assert_eq!(cache.get(&acme).await.unwrap(), None);
+        assert_eq!(
+            cache.get(&globex).await.unwrap().as_deref(),
+            Some("globex forecast")
+        );
+    }
+}
@@ -0,0 +1,98 @@
+//! Общий кэш результатов в Redis
+
+use async_trait::async_trait;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+
+use super::{tenant_prefix, PredictionCache};
+
+const KEY_PREFIX: &str = "kimai-ml:cache";
+
+/// Записи хранятся под ключами `kimai-ml:cache:{поколение}:{ключ}` с TTL;
+/// `clear` увеличивает поколение, и старые записи истекают сами.
+pub struct RedisCache {
+    connection: ConnectionManager,
+    ttl_secs: u64,
+}
+
+impl RedisCache {
+    pub async fn connect(url: &str, ttl_secs: u64) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        let connection = client.get_connection_manager().await.map_err(redis_error)?;
+        Ok(Self {
+            connection,
+            ttl_secs,
+        })
+    }
+
+    async fn generation(&self) -> Result<u64, String> {
+        let generation: Option<u64> = self
+            .connection
+            .clone()
+            .get(format!("{}:generation", KEY_PREFIX))
+            .await
+            .map_err(redis_error)?;
+        Ok(generation.unwrap_or(0))
+    }
+
+    async fn entry_key(&self, key: &str) -> Result<String, String> {
+        Ok(format!(
+            "{}:{}:{}",
+            KEY_PREFIX,
+            self.generation().await?,
+            key
+        ))
+    }
+}
+
+#[async_trait]
+impl PredictionCache for RedisCache {
+    async fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let entry_key = self.entry_key(key).await?;
+        self.connection
+            .clone()
+            .get(entry_key)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), String> {
+        let entry_key = self.entry_key(key).await?;
+        self.connection
+            .clone()
+            .set_ex(entry_key, value, self.ttl_secs)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn clear(&self) -> Result<(), String> {
+        self.connection
+            .clone()
+            .incr(format!("{}:generation", KEY_PREFIX), 1)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn clear_tenant(&self, tenant: &str) -> Result<(), String> {
+        // Записи всех поколений: старые еще не истекли
+        let pattern = format!("{}:*:{}*", KEY_PREFIX, tenant_prefix(tenant));
+        let mut connection = self.connection.clone();
+        let keys: Vec<String> = {
+            let mut iter: redis::AsyncIter<'_, String> =
+                connection.scan_match(&pattern).await.map_err(redis_error)?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        if keys.is_empty() {
+            return Ok(());
+        }
+        connection.del(keys).await.map_err(redis_error)
+    }
+}
+
+pub(crate) fn redis_error(e: redis::RedisError) -> String {
+    format!("Redis error: {}", e)
+}
@@ -0,0 +1,153 @@
+//! Экспорт плана недели в iCalendar (RFC 5545).
+//!
+//! Шаги `ActionPlan` раскладываются по порядку в рабочие часы пользователя
+//! (`OptimalWorkHours`): с начала окна первого рабочего дня недели плана, шаг,
+//! не помещающийся в остаток дня, переносится на следующий рабочий день;
+//! праздники недели (`ActionPlan::holidays`) пропускаются. Время
+//! событий - местное без часового пояса, календарь показывает его как есть
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday};
+
+use crate::types::{ActionPlan, OptimalWorkHours};
+
+/// MIME-тип ответа с календарем
+pub const ICS_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+/// Длина строки iCalendar в октетах, дальше - перенос
+const LINE_LIMIT: usize = 75;
+
+/// Календарь с событием на каждый шаг плана; план без недели - ошибка
+pub fn action_plan_ics(plan: &ActionPlan, hours: &OptimalWorkHours) -> Result<String, String> {
+    let period = plan.period.as_deref().ok_or("Action plan has no week")?;
+    let monday = period
+        .split_once("-W")
+        .and_then(|(year, week)| {
+            NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, Weekday::Mon)
+        })
+        .ok_or_else(|| format!("Invalid plan week: {}", period))?;
+
+    // Дни окна - в порядке недели с понедельника (`days`: 0 - воскресенье)
+    let mut days: Vec<NaiveDate> = hours
+        .days
+        .iter()
+        .filter(|day| (0..7).contains(*day))
+        .map(|&day| monday + Duration::days(((day + 6) % 7) as i64))
+        .collect();
+    days.sort();
+    days.dedup();
+    days.retain(|day| !plan.holidays.contains(&day.to_string()));
+    if days.is_empty() {
+        days = (0..5)
+            .map(|offset| monday + Duration::days(offset))
+            .collect();
+    }
+    let start = hours.start.clamp(0, 23) as u32;
+    let end = (hours.end.clamp(0, 23) as u32 + 1).max(start + 1);
+    let window_minutes = i64::from(end - start) * 60;
+
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//kimai-ml//Action plan//RU".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!(
+            "X-WR-CALNAME:{}",
+            escape(&format!("План недели {}", period))
+        ),
+    ];
+    let mut day = 0;
+    let mut used_minutes = 0;
+    for step in &plan.steps {
+        let minutes = ((step.time_cost_hours * 60.0).round() as i64).max(1);
+        // Шаг дольше всего окна начинается с начала дня и выходит за окно
+        if used_minutes > 0 && used_minutes + minutes > window_minutes {
+            day += 1;
+            used_minutes = 0;
+        }
+        let date = days.get(day).copied().unwrap_or_else(|| {
+            days[days.len() - 1] + Duration::days((day - days.len() + 1) as i64)
+        });
+        let begin = NaiveDateTime::new(
+            date,
+            NaiveTime::from_hms_opt(start, 0, 0).unwrap_or_default(),
+        ) + Duration::minutes(used_minutes);
+        used_minutes += minutes;
+
+        let mut description = step
+            .checklist
+            .iter()
+            .map(|item| format!("- {}", item))
+            .collect::<Vec<_>>();
+        let benefit = &step.expected_benefit;
+        if let Some(delta) = benefit.estimated_delta {
+            description.push(format!(
+                "Ожидаемый эффект: {:+.1} {} ({})",
+                delta, benefit.unit, benefit.metric
+            ));
+        }
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}-{}@kimai-ml", step.recommendation_id, period),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART:{}", local_time(begin)),
+            format!("DTEND:{}", local_time(begin + Duration::minutes(minutes))),
+            format!("SUMMARY:{}", escape(&step.title)),
+            format!("DESCRIPTION:{}", escape(&description.join("\n"))),
+            format!("CATEGORIES:{}", escape(&step.recommendation_type)),
+            format!("PRIORITY:{}", priority(&step.priority)),
+            "TRANSP:OPAQUE".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    Ok(lines.iter().map(|line| fold(line)).collect())
+}
+
+fn local_time(time: NaiveDateTime) -> String {
+    time.format("%Y%m%dT%H%M%S").to_string()
+}
+
+/// Приоритет iCalendar: 1 - высший, 9 - низший
+fn priority(priority: &str) -> u8 {
+    match priority {
+        "high" => 1,
+        "medium" => 5,
+        _ => 9,
+    }
+}
+
+/// Экранирование текстового значения
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Строка с переносами по `LINE_LIMIT` октетов (не разрывая символы UTF-8) и CRLF
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            // Пробел продолжения входит в длину строки
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
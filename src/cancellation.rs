@@ -0,0 +1,39 @@
+//! Кооперативная отмена долгих операций обучения
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Текст ошибки, которой завершается отмененное обучение
+pub const TRAINING_CANCELLED: &str = "Training cancelled";
+
+/// Флаг отмены, разделяемый между сервером и обучающим кодом.
+///
+/// Модели проверяют его между шагами (узлы деревьев, деревья леса), поэтому
+/// отмена срабатывает без ожидания завершения всего обучения.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `Err(TRAINING_CANCELLED)`, если операция отменена
+    pub fn check(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err(TRAINING_CANCELLED.to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
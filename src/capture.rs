@@ -0,0 +1,350 @@
+//! Запись запросов анализа для воспроизведения (`kimai-ml replay`).
+//!
+//! Запись включается `CAPTURE_PATH`: тела запросов, ответы и версии моделей
+//! добавляются строками JSON в файл. Названия проектов и активностей, описания,
+//! теги и арендатор заменяются псевдонимами (`anon-<хэш>`); те же псевдонимы
+//! подставляются в ответ, поэтому воспроизведение обезличенного запроса дает
+//! сравнимый результат. Названия, совпадающие с шаблонами встреч, сохраняют
+//! шаблон: встречи распознаются и после обезличивания
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+use crate::models::productivity::DEFAULT_MEETING_PATTERNS;
+
+/// Поля ответа, которые меняются от запуска к запуску и не сравниваются
+pub const VOLATILE_FIELDS: [&str; 3] = ["trained_at", "first_seen", "last_seen"];
+
+/// Записанный запрос
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    pub timestamp: String, // RFC 3339
+    /// Псевдоним арендатора
+    pub tenant: String,
+    pub path: String,
+    pub status: u16,
+    /// Обезличенное тело запроса
+    pub input: JsonValue,
+    /// Ответ с теми же псевдонимами
+    pub output: JsonValue,
+    /// Версии моделей (`ModelInfo::model_version`), построивших ответ
+    #[serde(default)]
+    pub model_versions: Vec<String>,
+}
+
+/// Расхождение воспроизведенного ответа с записанным
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Путь JSON Pointer
+    pub path: String,
+    pub expected: JsonValue,
+    pub actual: JsonValue,
+}
+
+/// Замена личных строк запроса псевдонимами
+pub struct Anonymizer {
+    salt: String,
+    meeting_patterns: Vec<String>,
+    /// Исходная строка - псевдоним, для подстановки в ответ
+    replaced: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    /// `salt` делает псевдонимы необратимыми подбором; с одной солью одинаковые
+    /// строки получают одинаковые псевдонимы во всех записях
+    pub fn new(salt: &str) -> Self {
+        Self {
+            salt: salt.to_string(),
+            meeting_patterns: DEFAULT_MEETING_PATTERNS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            replaced: HashMap::new(),
+        }
+    }
+
+    pub fn pseudonym(&self, text: &str) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.salt.as_bytes())
+            .chain_update([0])
+            .chain_update(text.as_bytes())
+            .finalize();
+        let hash: String = digest[..6].iter().map(|b| format!("{:02x}", b)).collect();
+
+        let lower = text.to_lowercase();
+        match self
+            .meeting_patterns
+            .iter()
+            .find(|p| lower.contains(p.as_str()))
+        {
+            Some(pattern) => format!("{} anon-{}", pattern, hash),
+            None => format!("anon-{}", hash),
+        }
+    }
+
+    /// Обезличивает тело `MLInputData` (объект JSON)
+    pub fn anonymize_input(&mut self, input: &mut JsonValue) {
+        let patterns = input
+            .pointer("/settings/meeting_patterns")
+            .and_then(|v| v.as_array())
+            .filter(|patterns| !patterns.is_empty());
+        if let Some(patterns) = patterns {
+            self.meeting_patterns = patterns
+                .iter()
+                .filter_map(|p| p.as_str())
+                .map(|p| p.to_lowercase())
+                .collect();
+        }
+
+        if let Some(entries) = input.get_mut("timesheets").and_then(|v| v.as_array_mut()) {
+            for entry in entries {
+                for field in ["project_name", "activity_name", "description"] {
+                    self.replace(entry.get_mut(field));
+                }
+                if let Some(tags) = entry.get_mut("tags").and_then(|v| v.as_array_mut()) {
+                    for tag in tags {
+                        self.replace(Some(tag));
+                    }
+                }
+            }
+        }
+        if let Some(projects) = input.get_mut("projects").and_then(|v| v.as_array_mut()) {
+            for project in projects {
+                self.replace(project.get_mut("name"));
+            }
+        }
+    }
+
+    /// Подставляет псевдонимы вместо исходных строк во все строки ответа
+    pub fn anonymize_output(&self, output: &mut JsonValue) {
+        // Длинные названия раньше: название, содержащее другое, заменяется целиком
+        let mut replaced: Vec<(&String, &String)> = self.replaced.iter().collect();
+        replaced.sort_by_key(|(original, _)| std::cmp::Reverse(original.len()));
+        replace_strings(output, &replaced);
+    }
+
+    fn replace(&mut self, value: Option<&mut JsonValue>) {
+        let Some(JsonValue::String(text)) = value else {
+            return;
+        };
+        if text.is_empty() {
+            return;
+        }
+        let pseudonym = self.pseudonym(text);
+        self.replaced.insert(text.clone(), pseudonym.clone());
+        *text = pseudonym;
+    }
+}
+
+fn replace_strings(value: &mut JsonValue, replaced: &[(&String, &String)]) {
+    match value {
+        JsonValue::String(text) => *text = replace_names(text, replaced),
+        JsonValue::Array(items) => {
+            for item in items {
+                replace_strings(item, replaced);
+            }
+        }
+        JsonValue::Object(map) => {
+            // Ключи тоже бывают названиями (например, активностей)
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut item)| {
+                    replace_strings(&mut item, replaced);
+                    (replace_names(&key, replaced), item)
+                })
+                .collect();
+        }
+        _ => {}
+    }
+}
+
+/// Заменяет вхождения названий, отделенные от соседних букв и цифр: короткое
+/// название не заменяется внутри слова
+fn replace_names(text: &str, replaced: &[(&String, &String)]) -> String {
+    let mut text = text.to_string();
+    for (original, pseudonym) in replaced {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text.as_str();
+        while let Some(index) = rest.find(original.as_str()) {
+            let end = index + original.len();
+            let bounded = !rest[..index].chars().next_back().is_some_and(is_word_char)
+                && !rest[end..].chars().next().is_some_and(is_word_char);
+            if bounded {
+                result.push_str(&rest[..index]);
+                result.push_str(pseudonym);
+                rest = &rest[end..];
+            } else {
+                let next = index + rest[index..].chars().next().map_or(1, char::len_utf8);
+                result.push_str(&rest[..next]);
+                rest = &rest[next..];
+            }
+        }
+        result.push_str(rest);
+        text = result;
+    }
+    text
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Файл записей: по одной `CaptureRecord` на строку
+pub struct CaptureLog {
+    path: PathBuf,
+    salt: String,
+    /// Доля записываемых запросов
+    sample_rate: f64,
+    /// Сериализует запись, чтобы строки разных запросов не перемешивались
+    writer: tokio::sync::Mutex<()>,
+}
+
+impl CaptureLog {
+    pub fn new(path: impl Into<PathBuf>, salt: &str) -> Self {
+        Self {
+            path: path.into(),
+            salt: salt.to_string(),
+            sample_rate: 1.0,
+            writer: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Доля записываемых запросов в (0, 1]
+    pub fn sample_rate(mut self, rate: f64) -> Result<Self, String> {
+        if !(rate > 0.0 && rate <= 1.0) {
+            return Err(format!(
+                "Capture sample rate must be in (0, 1], got {}",
+                rate
+            ));
+        }
+        self.sample_rate = rate;
+        Ok(self)
+    }
+
+    /// Записывать ли очередной запрос
+    pub fn sampled(&self) -> bool {
+        self.sample_rate >= 1.0 || rand::random::<f64>() < self.sample_rate
+    }
+
+    pub fn anonymizer(&self) -> Anonymizer {
+        Anonymizer::new(&self.salt)
+    }
+
+    /// Оставляет только записи, для которых `keep` верно; поврежденные строки
+    /// сохраняются. Возвращает число удаленных записей
+    pub async fn retain(&self, keep: impl Fn(&CaptureRecord) -> bool) -> Result<usize, String> {
+        let _guard = self.writer.lock().await;
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.path.display(), e)),
+        };
+        let mut kept = String::with_capacity(content.len());
+        let mut removed = 0;
+        for line in content.lines() {
+            match serde_json::from_str::<CaptureRecord>(line) {
+                Ok(record) if !keep(&record) => removed += 1,
+                _ => {
+                    kept.push_str(line);
+                    kept.push('\n');
+                }
+            }
+        }
+        if removed > 0 {
+            crate::retention::replace_file(&self.path, kept).await?;
+        }
+        Ok(removed)
+    }
+
+    pub async fn append(&self, record: &CaptureRecord) -> Result<(), String> {
+        let mut line =
+            serde_json::to_vec(record).map_err(|e| format!("Serialization error: {}", e))?;
+        line.push(b'\n');
+
+        let _guard = self.writer.lock().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", self.path.display(), e))?;
+        file.write_all(&line)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+/// Записи файла в порядке поступления; поврежденные строки - `Err` с номером
+pub fn read_captures(path: &str) -> Result<Vec<CaptureRecord>, String> {
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).map_err(|e| format!("{}:{}: {}", path, index + 1, e))
+        })
+        .collect()
+}
+
+/// Расхождения `actual` с `expected`. Числа совпадают, если отличаются не больше
+/// чем на `tolerance` от `max(|expected|, 1)`; поля `VOLATILE_FIELDS` пропускаются
+pub fn compare(expected: &JsonValue, actual: &JsonValue, tolerance: f64) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    compare_at(expected, actual, tolerance, String::new(), &mut differences);
+    differences
+}
+
+fn compare_at(
+    expected: &JsonValue,
+    actual: &JsonValue,
+    tolerance: f64,
+    path: String,
+    differences: &mut Vec<Difference>,
+) {
+    match (expected, actual) {
+        (JsonValue::Number(e), JsonValue::Number(a)) => {
+            let (e, a) = (e.as_f64().unwrap_or(0.0), a.as_f64().unwrap_or(0.0));
+            if (a - e).abs() <= tolerance * e.abs().max(1.0) {
+                return;
+            }
+        }
+        (JsonValue::Object(e), JsonValue::Object(a)) => {
+            let mut keys: Vec<&String> = e.keys().chain(a.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                if VOLATILE_FIELDS.contains(&key.as_str()) {
+                    continue;
+                }
+                let e = e.get(key).unwrap_or(&JsonValue::Null);
+                let a = a.get(key).unwrap_or(&JsonValue::Null);
+                compare_at(e, a, tolerance, pointer(&path, key), differences);
+            }
+            return;
+        }
+        (JsonValue::Array(e), JsonValue::Array(a)) if e.len() == a.len() => {
+            for (index, (e, a)) in e.iter().zip(a).enumerate() {
+                compare_at(e, a, tolerance, format!("{}/{}", path, index), differences);
+            }
+            return;
+        }
+        (e, a) if e == a => return,
+        _ => {}
+    }
+    differences.push(Difference {
+        path,
+        expected: expected.clone(),
+        actual: actual.clone(),
+    });
+}
+
+fn pointer(path: &str, key: &str) -> String {
+    format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"))
+}
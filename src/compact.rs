@@ -0,0 +1,105 @@
+//! Компактный двоичный формат снимков моделей.
+//!
+//! Снимок - заголовок (`KMLC`, версия формата, вид модели) и тело bincode с
+//! целыми переменной длины. Деревья Isolation Forest хранятся обходом в прямом
+//! порядке: узел - номер признака + 1 (varint, 0 - лист) и порог в `f32`.
+//! Пороги теряют точность после седьмой значащей цифры, чего для разбиения по
+//! признакам в [0, 1] достаточно. Такие артефакты в несколько раз меньше JSON
+//! и подходят для реестра и поставки в WASM
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Сигнатура компактного снимка
+pub const COMPACT_MAGIC: [u8; 4] = *b"KMLC";
+
+/// Версия компактного формата (заголовок и раскладка деревьев)
+pub const COMPACT_FORMAT_VERSION: u8 = 1;
+
+/// Сравнение размеров снимка модели в JSON и компактном формате
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeReport {
+    pub json_bytes: usize,
+    pub compact_bytes: usize,
+}
+
+impl SizeReport {
+    /// Во сколько раз компактный снимок меньше JSON
+    pub fn ratio(&self) -> f64 {
+        self.json_bytes as f64 / self.compact_bytes.max(1) as f64
+    }
+}
+
+/// Является ли `data` компактным снимком (по сигнатуре)
+pub fn is_compact(data: &[u8]) -> bool {
+    data.starts_with(&COMPACT_MAGIC)
+}
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().with_varint_encoding()
+}
+
+/// Снимок вида `kind` (`anomaly`, `forecasting`) с заголовком
+pub fn encode<T: Serialize>(kind: &str, value: &T) -> Result<Vec<u8>, String> {
+    let mut data = COMPACT_MAGIC.to_vec();
+    data.push(COMPACT_FORMAT_VERSION);
+    write_varint(kind.len() as u64, &mut data);
+    data.extend_from_slice(kind.as_bytes());
+    options()
+        .serialize_into(&mut data, value)
+        .map_err(|e| format!("Serialization error: {}", e))?;
+    Ok(data)
+}
+
+/// Тело снимка вида `kind`; другой вид или версия формата - ошибка
+pub fn decode<T: DeserializeOwned>(kind: &str, data: &[u8]) -> Result<T, String> {
+    let rest = data
+        .strip_prefix(&COMPACT_MAGIC)
+        .ok_or("Not a compact model snapshot")?;
+    let (&version, mut rest) = rest.split_first().ok_or("Truncated compact snapshot")?;
+    if version != COMPACT_FORMAT_VERSION {
+        return Err(format!(
+            "Unsupported compact format version: {} (expected {})",
+            version, COMPACT_FORMAT_VERSION
+        ));
+    }
+    let len = read_varint(&mut rest)? as usize;
+    let found = rest.get(..len).ok_or("Truncated compact snapshot")?;
+    if found != kind.as_bytes() {
+        return Err(format!(
+            "Compact snapshot holds {} model, expected {}",
+            String::from_utf8_lossy(found),
+            kind
+        ));
+    }
+    options()
+        .deserialize(&rest[len..])
+        .map_err(|e| format!("Deserialization error: {}", e))
+}
+
+/// Целое без знака LEB128: по 7 бит в байте, старший бит - продолжение
+pub fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Читает LEB128 из начала `data` и сдвигает срез за него
+pub fn read_varint(data: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0u64;
+    for (index, &byte) in data.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * index);
+        if byte & 0x80 == 0 {
+            *data = &data[index + 1..];
+            return Ok(value);
+        }
+    }
+    Err("Invalid varint".to_string())
+}
@@ -0,0 +1,244 @@
+//! Длительности записей во входных данных.
+//!
+//! Модели считают `TimesheetEntry::duration` в минутах. API Kimai отдает секунды,
+//! другие источники - строки ISO-8601 (`PT1H30M`), поэтому длительности приводятся
+//! к минутам до разбора `MLInputData`: числа - по `settings.duration_unit`,
+//! строки ISO-8601 - всегда по их собственным единицам.
+
+use chrono::DateTime;
+use serde_json::Value as JsonValue;
+
+use crate::types::DurationUnit;
+
+/// Длительность в минутах больше суток без `begin`/`end` для сравнения
+/// считается записанной в секундах (`duration_unit: "auto"`)
+const AUTO_SECONDS_ABOVE_MINUTES: f64 = 24.0 * 60.0;
+
+/// Допустимое расхождение длительности с интервалом записи при распознавании единиц, минуты
+const AUTO_TOLERANCE_MINUTES: f64 = 1.0;
+
+/// Длительность ISO-8601 (`PnDTnHnMnS`, `PnW`) в минутах. Годы и месяцы
+/// не принимаются: их длина зависит от даты
+pub fn parse_iso8601_minutes(text: &str) -> Result<f64, String> {
+    let invalid = || format!("Invalid ISO-8601 duration: {}", text);
+    let rest = text.strip_prefix('P').ok_or_else(invalid)?;
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut minutes = 0.0;
+    let mut in_time = false;
+    let mut number = String::new();
+    let mut components = 0;
+    for c in rest.chars() {
+        match c {
+            'T' if !in_time && number.is_empty() => in_time = true,
+            '0'..='9' | '.' | ',' => number.push(if c == ',' { '.' } else { c }),
+            _ => {
+                let value: f64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                minutes += value
+                    * match (c, in_time) {
+                        ('W', false) => 7.0 * 24.0 * 60.0,
+                        ('D', false) => 24.0 * 60.0,
+                        ('H', true) => 60.0,
+                        ('M', true) => 1.0,
+                        ('S', true) => 1.0 / 60.0,
+                        _ => return Err(invalid()),
+                    };
+                components += 1;
+            }
+        }
+    }
+    if !number.is_empty() || components == 0 {
+        return Err(invalid());
+    }
+    Ok(minutes)
+}
+
+/// Приведение длительностей записей к минутам по мере поступления (NDJSON).
+///
+/// Для `auto` единицы определяются по первой записи с `begin` и `end` и
+/// дальше не меняются; до нее секундами считаются длительности больше суток
+pub struct DurationNormalizer {
+    unit: DurationUnit,
+}
+
+impl DurationNormalizer {
+    pub fn new(unit: DurationUnit) -> Self {
+        Self { unit }
+    }
+
+    /// Переписывает `duration` записи (объект JSON) в целые минуты
+    pub fn normalize_entry(&mut self, entry: &mut JsonValue) -> Result<(), String> {
+        if self.unit == DurationUnit::Auto {
+            if let Some(unit) = entry_unit(entry) {
+                self.unit = unit;
+            }
+        }
+        normalize_duration(entry, self.unit)
+    }
+}
+
+/// Приводит `timesheets[].duration` тела запроса к минутам по
+/// `settings.duration_unit` и отмечает, что единицы уже минуты.
+///
+/// Для `auto` единицы выбираются большинством записей, где длительность
+/// совпадает с интервалом `begin`-`end` в минутах или в секундах
+pub fn normalize_input(input: &mut JsonValue) -> Result<(), String> {
+    let unit = match input.pointer("/settings/duration_unit") {
+        None | Some(JsonValue::Null) => DurationUnit::default(),
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|e| format!("Invalid settings.duration_unit: {}", e))?,
+    };
+    let Some(entries) = input.get_mut("timesheets").and_then(|v| v.as_array_mut()) else {
+        return Ok(());
+    };
+
+    let unit = match unit {
+        DurationUnit::Auto => {
+            let (seconds, minutes) =
+                entries
+                    .iter()
+                    .filter_map(entry_unit)
+                    .fold((0, 0), |(seconds, minutes), unit| match unit {
+                        DurationUnit::Seconds => (seconds + 1, minutes),
+                        _ => (seconds, minutes + 1),
+                    });
+            if seconds + minutes > 0 {
+                if seconds > minutes {
+                    DurationUnit::Seconds
+                } else {
+                    DurationUnit::Minutes
+                }
+            } else {
+                DurationUnit::Auto
+            }
+        }
+        unit => unit,
+    };
+    for (index, entry) in entries.iter_mut().enumerate() {
+        normalize_duration(entry, unit).map_err(|e| format!("timesheets[{}]: {}", index, e))?;
+    }
+
+    if let Some(settings) = input.get_mut("settings").and_then(|v| v.as_object_mut()) {
+        settings.insert(
+            "duration_unit".to_string(),
+            JsonValue::String("minutes".to_string()),
+        );
+    }
+    Ok(())
+}
+
+/// Единицы числовой длительности записи по ее интервалу `begin`-`end`
+fn entry_unit(entry: &JsonValue) -> Option<DurationUnit> {
+    let duration = entry.get("duration")?.as_f64()?;
+    let begin = DateTime::parse_from_rfc3339(entry.get("begin")?.as_str()?).ok()?;
+    let end = DateTime::parse_from_rfc3339(entry.get("end")?.as_str()?).ok()?;
+    let interval = (end - begin).num_seconds() as f64 / 60.0;
+    if interval <= 0.0 {
+        return None;
+    }
+
+    let as_minutes = (duration - interval).abs() <= AUTO_TOLERANCE_MINUTES;
+    let as_seconds = (duration / 60.0 - interval).abs() <= AUTO_TOLERANCE_MINUTES;
+    match (as_minutes, as_seconds) {
+        (true, false) => Some(DurationUnit::Minutes),
+        (false, true) => Some(DurationUnit::Seconds),
+        _ => None,
+    }
+}
+
+fn normalize_duration(entry: &mut JsonValue, unit: DurationUnit) -> Result<(), String> {
+    let Some(duration) = entry.get_mut("duration") else {
+        return Ok(());
+    };
+    let minutes = match &*duration {
+        JsonValue::String(text) => parse_iso8601_minutes(text)?,
+        JsonValue::Number(number) => {
+            let value = number
+                .as_f64()
+                .ok_or_else(|| format!("Invalid duration: {}", number))?;
+            match unit {
+                DurationUnit::Minutes => value,
+                DurationUnit::Seconds => value / 60.0,
+                DurationUnit::Auto if value > AUTO_SECONDS_ABOVE_MINUTES => value / 60.0,
+                DurationUnit::Auto => value,
+            }
+        }
+        _ => return Ok(()),
+    };
+    if !minutes.is_finite() || minutes.abs() > i32::MAX as f64 {
+        return Err(format!("Duration out of range: {}", duration));
+    }
+    *duration = JsonValue::from(minutes.round() as i32);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn iso8601_durations_are_converted_to_minutes() {
+        for (text, minutes) in [
+            ("PT1H30M", 90.0),
+            ("P1DT2H", 26.0 * 60.0),
+            ("PT0.5H", 30.0),
+            ("PT0,5H", 30.0),
+            ("P1W", 7.0 * 24.0 * 60.0),
+            ("PT90S", 1.5),
+        ] {
+            assert_eq!(parse_iso8601_minutes(text), Ok(minutes), "{}", text);
+        }
+        // Месяцы, пустые длительности и минуты без `T`
+        for text in ["P1M", "PT", "P", "", "1H", "PT1.5", "P1Y", "PT1H1D"] {
+            assert!(parse_iso8601_minutes(text).is_err(), "{}", text);
+        }
+    }
+
+    fn entry(duration: f64, minutes: i64) -> JsonValue {
+        let begin = DateTime::parse_from_rfc3339("2024-03-04T09:00:00+00:00").unwrap();
+        let end = begin + chrono::Duration::minutes(minutes);
+        json!({"duration": duration, "begin": begin.to_rfc3339(), "end": end.to_rfc3339()})
+    }
+
+    fn durations(timesheets: Vec<JsonValue>) -> Vec<JsonValue> {
+        let mut input = json!({
+            "timesheets": timesheets,
+            "settings": {"duration_unit": "auto"},
+        });
+        normalize_input(&mut input).unwrap();
+        assert_eq!(input["settings"]["duration_unit"], "minutes");
+        input["timesheets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["duration"].clone())
+            .collect()
+    }
+
+    #[test]
+    fn auto_unit_follows_begin_end_intervals() {
+        // Интервалы совпадают с длительностями в секундах
+        let seconds = durations(vec![
+            entry(5400.0, 90),
+            entry(1800.0, 30),
+            json!({"duration": 600}),
+        ]);
+        assert_eq!(seconds, vec![json!(90), json!(30), json!(10)]);
+
+        // Интервалы совпадают с длительностями в минутах
+        let minutes = durations(vec![
+            entry(90.0, 90),
+            entry(30.0, 30),
+            json!({"duration": 600}),
+        ]);
+        assert_eq!(minutes, vec![json!(90), json!(30), json!(600)]);
+
+        // Без интервалов секундами считаются только длительности больше суток
+        let unknown = durations(vec![json!({"duration": 90}), json!({"duration": 7200})]);
+        assert_eq!(unknown, vec![json!(90), json!(120)]);
+    }
+}
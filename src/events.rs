@@ -0,0 +1,81 @@
+//! Оповещения клиентов о результатах анализа (канал `/ws`)
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::types::{AnomalyOutput, ModelInfo};
+
+/// Арендатор запросов без явного идентификатора
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Размер буфера событий; отстающие подписчики пропускают старые события
+const EVENTS_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisEvent {
+    pub tenant: String,
+    pub timestamp: String, // RFC 3339
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// `status`: "completed" | "failed" | "cancelled"
+    TrainingFinished {
+        model: String,
+        status: String,
+        model_info: Option<ModelInfo>,
+    },
+    AnomaliesDetected {
+        anomalies: Vec<AnomalyOutput>,
+    },
+    /// Корректирующий фактор по ошибкам прогнозов вышел за допустимые пределы:
+    /// модель систематически ошибается на новых данных
+    DriftAlert {
+        prediction_type: String,
+        horizon: u32,
+        correction_factor: f64,
+        previous_correction_factor: f64,
+    },
+    /// Скользящая MAPE прогнозов держится выше порога; `job_id` - запущенная
+    /// задача переобучения, если модель можно переобучить
+    RetrainTriggered {
+        prediction_type: String,
+        horizon: u32,
+        rolling_mape: f64,
+        job_id: Option<String>,
+    },
+}
+
+/// Рассылка событий всем подписчикам; фильтрация по арендатору на их стороне
+pub struct EventHub {
+    sender: broadcast::Sender<AnalysisEvent>,
+}
+
+impl EventHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENTS_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, tenant: &str, kind: EventKind) {
+        // Ошибка означает лишь отсутствие подписчиков
+        let _ = self.sender.send(AnalysisEvent {
+            tenant: tenant.to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            kind,
+        });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AnalysisEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,21 @@
+//! Тип с плавающей точкой для матриц признаков и параметров моделей
+//!
+//! По умолчанию `f64`; с feature `f32` матрицы и обученные модели занимают вдвое
+//! меньше памяти. Публичные типы API (`types`) всегда остаются в `f64`.
+
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+
+/// Перевод значения модели в `f64` для типов API
+#[inline]
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f64(value: Float) -> f64 {
+    value as f64
+}
@@ -0,0 +1,247 @@
+//! GraphQL API (`POST /graphql`): те же анализы, что и REST, но клиент выбирает
+//! нужные поля, и вычисляются только запрошенные части.
+//!
+//! ```graphql
+//! query ($input: JSON!) {
+//!   analysis(input: $input) {
+//!     forecast { weeklyHours trend }
+//!     goals { projectId goalHours progress }
+//!   }
+//!   models { name trained info { modelVersion } }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+
+use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Extension, Router};
+
+use kimai_ml::types::{
+    ActionPlan, AnomalyOutput, ForecastingOutput, MLInputData, ModelInfo, ProductivityOutput,
+    RecommendationOutput,
+};
+
+use crate::{AnalysisInput, AppState, Tenant, WeeklyInput};
+
+pub type AnalyticsSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn schema(state: AppState) -> AnalyticsSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(state)
+        .finish()
+}
+
+/// `POST /graphql` выполняет запрос, `GET /graphql` возвращает схему (SDL)
+pub fn routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/graphql", get(sdl).post(execute))
+        .layer(Extension(schema(state)))
+}
+
+async fn execute(
+    Extension(schema): Extension<AnalyticsSchema>,
+    tenant: Tenant,
+    axum::Json(request): axum::Json<async_graphql::Request>,
+) -> axum::Json<async_graphql::Response> {
+    axum::Json(schema.execute(request.data(tenant)).await)
+}
+
+async fn sdl(Extension(schema): Extension<AnalyticsSchema>) -> String {
+    schema.sdl()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Анализ данных в формате тела REST-запросов (`MLInputData`)
+    async fn analysis(
+        &self,
+        input: async_graphql::Json<serde_json::Value>,
+    ) -> async_graphql::Result<Analysis> {
+        // Через serde_json: ключи-числа (`project_goals`) приходят строками
+        let mut input = input.0;
+        kimai_ml::duration::normalize_input(&mut input)?;
+        let input =
+            serde_json::from_value(input).map_err(|e| format!("Invalid analysis input: {}", e))?;
+        Ok(Analysis { input })
+    }
+
+    /// Состояние моделей арендатора
+    async fn models(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<ModelStatus>> {
+        let state = ctx.data::<AppState>()?;
+        let tenant = ctx.data::<Tenant>()?;
+        let forecasting = crate::forecasting_model(state, &tenant.0)
+            .await
+            .lock()
+            .await
+            .model_info();
+        let anomaly = crate::anomaly_detector(state, &tenant.0)
+            .await
+            .lock()
+            .await
+            .model_info();
+        Ok(vec![
+            ModelStatus::new("forecasting", forecasting),
+            ModelStatus::new("anomaly", anomaly),
+        ])
+    }
+}
+
+pub struct Analysis {
+    input: MLInputData,
+}
+
+#[Object]
+impl Analysis {
+    /// Прогноз часов (`/api/predict`)
+    async fn forecast(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Option<ForecastingOutput>> {
+        let state = ctx.data::<AppState>()?.clone();
+        let tenant = ctx.data::<Tenant>()?.clone();
+        let axum::Json(output) =
+            crate::predict(State(state), tenant, WeeklyInput(self.input.clone())).await?;
+        Ok(output.forecasting)
+    }
+
+    /// Аномалии в записях времени (`/api/detect-anomalies`)
+    async fn anomalies(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<AnomalyOutput>> {
+        let state = ctx.data::<AppState>()?.clone();
+        let tenant = ctx.data::<Tenant>()?.clone();
+        let axum::Json(output) =
+            crate::detect_anomalies(State(state), tenant, AnalysisInput(self.input.clone()))
+                .await?;
+        Ok(output.anomalies.unwrap_or_default())
+    }
+
+    /// Анализ продуктивности (`/api/productivity`)
+    async fn productivity(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Option<ProductivityOutput>> {
+        let state = ctx.data::<AppState>()?.clone();
+        let axum::Json(output) =
+            crate::analyze_productivity(State(state), AnalysisInput(self.input.clone())).await?;
+        Ok(output.productivity)
+    }
+
+    /// Рекомендации (`/api/recommendations`)
+    async fn recommendations(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<RecommendationOutput>> {
+        let state = ctx.data::<AppState>()?.clone();
+        let tenant = ctx.data::<Tenant>()?.clone();
+        let axum::Json(output) =
+            crate::get_recommendations(State(state), tenant, AnalysisInput(self.input.clone()))
+                .await?;
+        Ok(output.recommendations.unwrap_or_default())
+    }
+
+    /// План действий на следующую неделю по рекомендациям (`/api/recommendations`)
+    async fn action_plan(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Option<ActionPlan>> {
+        let state = ctx.data::<AppState>()?.clone();
+        let tenant = ctx.data::<Tenant>()?.clone();
+        let axum::Json(output) =
+            crate::get_recommendations(State(state), tenant, AnalysisInput(self.input.clone()))
+                .await?;
+        Ok(output.action_plan)
+    }
+
+    /// Недельные цели по проектам: `user_preferences.project_goals`, иначе
+    /// `weekly_goal_hours` из настроек включенных проектов
+    async fn goals(&self) -> Vec<GoalProgress> {
+        let settings = &self.input.settings;
+        let mut goals: BTreeMap<i32, f64> = settings
+            .project_settings
+            .iter()
+            .filter(|(_, s)| s.enabled && !s.archived)
+            .filter_map(|(id, s)| s.weekly_goal_hours.map(|hours| (*id, hours)))
+            .collect();
+        if let Some(prefs) = &settings.user_preferences {
+            goals.extend(prefs.project_goals.iter().map(|(id, hours)| (*id, *hours)));
+        }
+
+        let weeks = &self.input.weeks;
+        let project_hours = |week: &kimai_ml::types::WeekData, project_id: i32| -> f64 {
+            week.project_stats
+                .iter()
+                .filter(|s| s.project_id == project_id)
+                .map(|s| s.hours)
+                .sum()
+        };
+
+        goals
+            .into_iter()
+            .map(|(project_id, goal_hours)| {
+                let last_week_hours = weeks
+                    .last()
+                    .map(|w| project_hours(w, project_id))
+                    .unwrap_or(0.0);
+                let average_hours = if weeks.is_empty() {
+                    0.0
+                } else {
+                    weeks
+                        .iter()
+                        .map(|w| project_hours(w, project_id))
+                        .sum::<f64>()
+                        / weeks.len() as f64
+                };
+                GoalProgress {
+                    project_id,
+                    goal_hours,
+                    last_week_hours,
+                    average_hours,
+                    progress: if goal_hours > 0.0 {
+                        last_week_hours / goal_hours
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// Выполнение недельной цели проекта
+#[derive(SimpleObject)]
+pub struct GoalProgress {
+    pub project_id: i32,
+    pub goal_hours: f64,
+    /// Часы за последнюю неделю из `weeks`
+    pub last_week_hours: f64,
+    /// Средние часы в неделю по всем `weeks`
+    pub average_hours: f64,
+    /// `last_week_hours / goal_hours`
+    pub progress: f64,
+}
+
+#[derive(SimpleObject)]
+pub struct ModelStatus {
+    pub name: String,
+    pub trained: bool,
+    pub info: Option<ModelInfo>,
+}
+
+impl ModelStatus {
+    fn new(name: &str, info: Option<ModelInfo>) -> Self {
+        Self {
+            name: name.to_string(),
+            trained: info.is_some(),
+            info,
+        }
+    }
+}
@@ -0,0 +1,363 @@
+//! Встроенные календари государственных праздников (`settings.holiday_country`).
+//!
+//! Праздники заданы правилами: фиксированная дата, смещение от Пасхи (западной
+//! или православной) и n-й день недели месяца. Переносы выходных и праздники
+//! отдельных регионов не учитываются: календарь нужен моделям, чтобы отличать
+//! недели с меньшим числом рабочих дней, а не для учета рабочего времени.
+//! Даты из календаря дополняют `options.holidays` прогноза, уменьшают рабочее
+//! время плана действий и пропускаются при раскладке плана по дням
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// Правило даты праздника
+#[derive(Debug, Clone, Copy)]
+enum Rule {
+    /// Месяц и день
+    Fixed(u32, u32),
+    /// Дней от западной Пасхи
+    Easter(i64),
+    /// Дней от православной Пасхи (по григорианскому календарю)
+    OrthodoxEaster(i64),
+    /// n-й (с 1) день недели месяца; 0 - последний
+    NthWeekday(u32, Weekday, u8),
+    /// Последний день недели строго до месяца и дня
+    WeekdayBefore(u32, u32, Weekday),
+}
+
+use Rule::*;
+
+type Calendar = &'static [(Rule, &'static str)];
+
+/// Календари по коду страны ISO 3166-1 alpha-2
+const CALENDARS: &[(&str, Calendar)] = &[
+    (
+        "BY",
+        &[
+            (Fixed(1, 1), "Новый год"),
+            (Fixed(1, 2), "Новый год"),
+            (Fixed(1, 7), "Рождество (православное)"),
+            (Fixed(3, 8), "День женщин"),
+            (OrthodoxEaster(9), "Радуница"),
+            (Fixed(5, 1), "Праздник труда"),
+            (Fixed(5, 9), "День Победы"),
+            (Fixed(7, 3), "День Независимости"),
+            (Fixed(11, 7), "День Октябрьской революции"),
+            (Fixed(12, 25), "Рождество (католическое)"),
+        ],
+    ),
+    (
+        "CA",
+        &[
+            (Fixed(1, 1), "New Year's Day"),
+            (Easter(-2), "Good Friday"),
+            (WeekdayBefore(5, 25, Weekday::Mon), "Victoria Day"),
+            (Fixed(7, 1), "Canada Day"),
+            (NthWeekday(9, Weekday::Mon, 1), "Labour Day"),
+            (Fixed(9, 30), "National Day for Truth and Reconciliation"),
+            (NthWeekday(10, Weekday::Mon, 2), "Thanksgiving"),
+            (Fixed(11, 11), "Remembrance Day"),
+            (Fixed(12, 25), "Christmas Day"),
+            (Fixed(12, 26), "Boxing Day"),
+        ],
+    ),
+    (
+        "DE",
+        &[
+            (Fixed(1, 1), "Neujahr"),
+            (Easter(-2), "Karfreitag"),
+            (Easter(1), "Ostermontag"),
+            (Fixed(5, 1), "Tag der Arbeit"),
+            (Easter(39), "Christi Himmelfahrt"),
+            (Easter(50), "Pfingstmontag"),
+            (Fixed(10, 3), "Tag der Deutschen Einheit"),
+            (Fixed(12, 25), "1. Weihnachtstag"),
+            (Fixed(12, 26), "2. Weihnachtstag"),
+        ],
+    ),
+    (
+        "ES",
+        &[
+            (Fixed(1, 1), "Año Nuevo"),
+            (Fixed(1, 6), "Epifanía del Señor"),
+            (Easter(-2), "Viernes Santo"),
+            (Fixed(5, 1), "Fiesta del Trabajo"),
+            (Fixed(8, 15), "Asunción de la Virgen"),
+            (Fixed(10, 12), "Fiesta Nacional de España"),
+            (Fixed(11, 1), "Todos los Santos"),
+            (Fixed(12, 6), "Día de la Constitución"),
+            (Fixed(12, 8), "Inmaculada Concepción"),
+            (Fixed(12, 25), "Navidad"),
+        ],
+    ),
+    (
+        "FR",
+        &[
+            (Fixed(1, 1), "Jour de l'an"),
+            (Easter(1), "Lundi de Pâques"),
+            (Fixed(5, 1), "Fête du Travail"),
+            (Fixed(5, 8), "Victoire 1945"),
+            (Easter(39), "Ascension"),
+            (Easter(50), "Lundi de Pentecôte"),
+            (Fixed(7, 14), "Fête nationale"),
+            (Fixed(8, 15), "Assomption"),
+            (Fixed(11, 1), "Toussaint"),
+            (Fixed(11, 11), "Armistice 1918"),
+            (Fixed(12, 25), "Noël"),
+        ],
+    ),
+    (
+        "GB",
+        &[
+            (Fixed(1, 1), "New Year's Day"),
+            (Easter(-2), "Good Friday"),
+            (Easter(1), "Easter Monday"),
+            (NthWeekday(5, Weekday::Mon, 1), "Early May bank holiday"),
+            (NthWeekday(5, Weekday::Mon, 0), "Spring bank holiday"),
+            (NthWeekday(8, Weekday::Mon, 0), "Summer bank holiday"),
+            (Fixed(12, 25), "Christmas Day"),
+            (Fixed(12, 26), "Boxing Day"),
+        ],
+    ),
+    (
+        "IT",
+        &[
+            (Fixed(1, 1), "Capodanno"),
+            (Fixed(1, 6), "Epifania"),
+            (Easter(1), "Lunedì dell'Angelo"),
+            (Fixed(4, 25), "Festa della Liberazione"),
+            (Fixed(5, 1), "Festa del Lavoro"),
+            (Fixed(6, 2), "Festa della Repubblica"),
+            (Fixed(8, 15), "Ferragosto"),
+            (Fixed(11, 1), "Ognissanti"),
+            (Fixed(12, 8), "Immacolata Concezione"),
+            (Fixed(12, 25), "Natale"),
+            (Fixed(12, 26), "Santo Stefano"),
+        ],
+    ),
+    (
+        "KZ",
+        &[
+            (Fixed(1, 1), "Новый год"),
+            (Fixed(1, 2), "Новый год"),
+            (Fixed(1, 7), "Рождество Христово"),
+            (Fixed(3, 8), "Международный женский день"),
+            (Fixed(3, 21), "Наурыз мейрамы"),
+            (Fixed(3, 22), "Наурыз мейрамы"),
+            (Fixed(3, 23), "Наурыз мейрамы"),
+            (Fixed(5, 1), "Праздник единства народа Казахстана"),
+            (Fixed(5, 7), "День защитника Отечества"),
+            (Fixed(5, 9), "День Победы"),
+            (Fixed(7, 6), "День столицы"),
+            (Fixed(8, 30), "День Конституции"),
+            (Fixed(10, 25), "День Республики"),
+            (Fixed(12, 16), "День Независимости"),
+        ],
+    ),
+    (
+        "NL",
+        &[
+            (Fixed(1, 1), "Nieuwjaarsdag"),
+            (Easter(1), "Tweede Paasdag"),
+            (Fixed(4, 27), "Koningsdag"),
+            (Easter(39), "Hemelvaartsdag"),
+            (Easter(50), "Tweede Pinksterdag"),
+            (Fixed(12, 25), "Eerste Kerstdag"),
+            (Fixed(12, 26), "Tweede Kerstdag"),
+        ],
+    ),
+    (
+        "PL",
+        &[
+            (Fixed(1, 1), "Nowy Rok"),
+            (Fixed(1, 6), "Święto Trzech Króli"),
+            (Easter(1), "Poniedziałek Wielkanocny"),
+            (Fixed(5, 1), "Święto Pracy"),
+            (Fixed(5, 3), "Święto Konstytucji 3 Maja"),
+            (Easter(60), "Boże Ciało"),
+            (Fixed(8, 15), "Wniebowzięcie Najświętszej Maryi Panny"),
+            (Fixed(11, 1), "Wszystkich Świętych"),
+            (Fixed(11, 11), "Narodowe Święto Niepodległości"),
+            (Fixed(12, 25), "Boże Narodzenie"),
+            (Fixed(12, 26), "Drugi dzień Bożego Narodzenia"),
+        ],
+    ),
+    (
+        "RU",
+        &[
+            (Fixed(1, 1), "Новогодние каникулы"),
+            (Fixed(1, 2), "Новогодние каникулы"),
+            (Fixed(1, 3), "Новогодние каникулы"),
+            (Fixed(1, 4), "Новогодние каникулы"),
+            (Fixed(1, 5), "Новогодние каникулы"),
+            (Fixed(1, 6), "Новогодние каникулы"),
+            (Fixed(1, 7), "Рождество Христово"),
+            (Fixed(1, 8), "Новогодние каникулы"),
+            (Fixed(2, 23), "День защитника Отечества"),
+            (Fixed(3, 8), "Международный женский день"),
+            (Fixed(5, 1), "Праздник Весны и Труда"),
+            (Fixed(5, 9), "День Победы"),
+            (Fixed(6, 12), "День России"),
+            (Fixed(11, 4), "День народного единства"),
+        ],
+    ),
+    (
+        "US",
+        &[
+            (Fixed(1, 1), "New Year's Day"),
+            (NthWeekday(1, Weekday::Mon, 3), "Martin Luther King Jr. Day"),
+            (NthWeekday(2, Weekday::Mon, 3), "Washington's Birthday"),
+            (NthWeekday(5, Weekday::Mon, 0), "Memorial Day"),
+            (Fixed(6, 19), "Juneteenth"),
+            (Fixed(7, 4), "Independence Day"),
+            (NthWeekday(9, Weekday::Mon, 1), "Labor Day"),
+            (NthWeekday(10, Weekday::Mon, 2), "Columbus Day"),
+            (Fixed(11, 11), "Veterans Day"),
+            (NthWeekday(11, Weekday::Thu, 4), "Thanksgiving Day"),
+            (Fixed(12, 25), "Christmas Day"),
+        ],
+    ),
+];
+
+/// Праздник календаря
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: &'static str,
+}
+
+/// Коды стран со встроенным календарем
+pub fn supported_countries() -> Vec<&'static str> {
+    CALENDARS.iter().map(|(code, _)| *code).collect()
+}
+
+fn calendar(country: &str) -> Result<Calendar, String> {
+    CALENDARS
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(country.trim()))
+        .map(|(_, calendar)| *calendar)
+        .ok_or_else(|| {
+            format!(
+                "Unknown holiday country: {} (supported: {})",
+                country,
+                supported_countries().join(", ")
+            )
+        })
+}
+
+/// Код страны из настроек: известный календарь, в верхнем регистре
+pub fn deserialize_country<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let Some(country) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    calendar(&country).map_err(serde::de::Error::custom)?;
+    Ok(Some(country.trim().to_ascii_uppercase()))
+}
+
+/// Праздники страны за год по дате
+pub fn holidays(country: &str, year: i32) -> Result<Vec<Holiday>, String> {
+    let mut holidays: Vec<Holiday> = calendar(country)?
+        .iter()
+        .filter_map(|(rule, name)| {
+            Some(Holiday {
+                date: date(*rule, year)?,
+                name,
+            })
+        })
+        .collect();
+    holidays.sort_by_key(|h| h.date);
+    Ok(holidays)
+}
+
+/// Даты праздников страны с `from` по `to` включительно
+pub fn holiday_dates(
+    country: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<NaiveDate>, String> {
+    let mut dates = Vec::new();
+    for year in from.year()..=to.year() {
+        dates.extend(
+            holidays(country, year)?
+                .into_iter()
+                .map(|h| h.date)
+                .filter(|d| (from..=to).contains(d)),
+        );
+    }
+    dates.dedup();
+    Ok(dates)
+}
+
+/// Праздники страны, выпадающие на будни ISO-недели
+pub fn weekday_holidays(country: &str, year: i32, week: u32) -> Result<Vec<NaiveDate>, String> {
+    let Some(monday) = NaiveDate::from_isoywd_opt(year, week, Weekday::Mon) else {
+        return Ok(Vec::new());
+    };
+    holiday_dates(country, monday, monday + Duration::days(4))
+}
+
+fn date(rule: Rule, year: i32) -> Option<NaiveDate> {
+    match rule {
+        Fixed(month, day) => NaiveDate::from_ymd_opt(year, month, day),
+        Easter(offset) => Some(western_easter(year)? + Duration::days(offset)),
+        OrthodoxEaster(offset) => Some(orthodox_easter(year)? + Duration::days(offset)),
+        NthWeekday(month, weekday, 0) => {
+            let next_month = if month == 12 {
+                NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+            } else {
+                NaiveDate::from_ymd_opt(year, month + 1, 1)?
+            };
+            last_weekday_before(next_month, weekday)
+        }
+        NthWeekday(month, weekday, n) => {
+            NaiveDate::from_weekday_of_month_opt(year, month, weekday, n)
+        }
+        WeekdayBefore(month, day, weekday) => {
+            last_weekday_before(NaiveDate::from_ymd_opt(year, month, day)?, weekday)
+        }
+    }
+}
+
+/// Последний `weekday` строго до `date`
+fn last_weekday_before(date: NaiveDate, weekday: Weekday) -> Option<NaiveDate> {
+    let back = (date.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+    Some(date - Duration::days(if back == 0 { 7 } else { back as i64 }))
+}
+
+/// Западная Пасха (алгоритм Мееуса-Джонса-Бутчера)
+fn western_easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)
+}
+
+/// Православная Пасха: по юлианскому календарю (алгоритм Меуса), переведенная
+/// в григорианский
+fn orthodox_easter(year: i32) -> Option<NaiveDate> {
+    let a = year % 4;
+    let b = year % 7;
+    let c = year % 19;
+    let d = (19 * c + 15) % 30;
+    let e = (2 * a + 4 * b - d + 34) % 7;
+    let month = (d + e + 114) / 31;
+    let day = (d + e + 114) % 31 + 1;
+    let julian = NaiveDate::from_ymd_opt(year, month as u32, day as u32)?;
+    // Разница календарей: 13 дней с 1900 по 2099 год
+    let shift = year / 100 - year / 400 - 2;
+    Some(julian + Duration::days(shift as i64))
+}
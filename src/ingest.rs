@@ -0,0 +1,229 @@
+//! Потоковый прием больших историй в формате NDJSON (`application/x-ndjson`).
+//!
+//! Первая строка - заголовок: объект `MLInputData`, в котором `timesheets`
+//! не передаются, а `projects` и `weeks` можно опустить. Каждая следующая
+//! строка - одна запись `TimesheetEntry`. Тело разбирается по мере поступления:
+//! в памяти находятся одна строка и недельные агрегаты (и, если нужны модели,
+//! сами записи, не больше `max_entries`). Длительности записей приводятся к
+//! минутам по `settings.duration_unit` заголовка.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::duration::DurationNormalizer;
+use crate::types::{
+    Context, DurationUnit, MLInputData, Project, ProjectStats, Settings, TimesheetEntry, WeekData,
+};
+
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Заголовок потока: `MLInputData` без записей
+#[derive(Deserialize)]
+struct Header {
+    #[serde(default)]
+    projects: Vec<Project>,
+    #[serde(default)]
+    weeks: Vec<WeekData>,
+    settings: Settings,
+    #[serde(default)]
+    context: Option<Context>,
+    #[serde(default)]
+    options: Option<JsonValue>,
+}
+
+/// Недельные агрегаты (`WeekData`) из записей времени
+pub struct WeeklyAggregator {
+    rate_per_minute: f64,
+    weeks: BTreeMap<(i32, i32), WeekTotals>,
+}
+
+#[derive(Default)]
+struct WeekTotals {
+    minutes: i64,
+    project_minutes: BTreeMap<i32, i64>,
+}
+
+impl WeeklyAggregator {
+    pub fn new(rate_per_minute: f64) -> Self {
+        Self {
+            rate_per_minute,
+            weeks: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, entry: &TimesheetEntry) {
+        let totals = self.weeks.entry(entry.iso_week()).or_default();
+        totals.minutes += entry.duration as i64;
+        if let Some(project_id) = entry.project_id {
+            *totals.project_minutes.entry(project_id).or_insert(0) += entry.duration as i64;
+        }
+    }
+
+    /// Недели в хронологическом порядке
+    pub fn finish(self) -> Vec<WeekData> {
+        self.weeks
+            .into_iter()
+            .map(|((year, week), totals)| WeekData {
+                year,
+                week,
+                total_minutes: totals.minutes as i32,
+                total_hours: totals.minutes as f64 / 60.0,
+                total_amount: totals.minutes as f64 * self.rate_per_minute,
+                project_stats: totals
+                    .project_minutes
+                    .into_iter()
+                    .map(|(project_id, minutes)| ProjectStats {
+                        project_id,
+                        minutes: minutes as i32,
+                        hours: minutes as f64 / 60.0,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// Разбор NDJSON по частям тела запроса
+pub struct NdjsonReader {
+    keep_entries: bool,
+    max_entries: usize,
+    max_line_bytes: usize,
+    buffer: Vec<u8>,
+    line: usize,
+    header: Option<Header>,
+    aggregator: Option<WeeklyAggregator>,
+    durations: DurationNormalizer,
+    entries: Vec<TimesheetEntry>,
+}
+
+impl NdjsonReader {
+    pub const DEFAULT_MAX_ENTRIES: usize = 1_000_000;
+    pub const DEFAULT_MAX_LINE_BYTES: usize = 8 * 1024 * 1024;
+
+    /// `keep_entries: false` - записи только агрегируются по неделям и не
+    /// попадают в `timesheets` (достаточно для прогноза)
+    pub fn new(keep_entries: bool) -> Self {
+        Self {
+            keep_entries,
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+            max_line_bytes: Self::DEFAULT_MAX_LINE_BYTES,
+            buffer: Vec::new(),
+            line: 0,
+            header: None,
+            aggregator: None,
+            durations: DurationNormalizer::new(DurationUnit::Minutes),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Предел числа сохраняемых записей (при `keep_entries`)
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Предел длины одной строки
+    pub fn max_line_bytes(mut self, max_line_bytes: usize) -> Self {
+        self.max_line_bytes = max_line_bytes;
+        self
+    }
+
+    pub fn push(&mut self, mut chunk: &[u8]) -> Result<(), String> {
+        while let Some(pos) = chunk.iter().position(|&b| b == b'\n') {
+            self.check_line_length(self.buffer.len() + pos)?;
+            if self.buffer.is_empty() {
+                self.process_line(&chunk[..pos])?;
+            } else {
+                // Строка началась в предыдущей части тела
+                let mut line = std::mem::take(&mut self.buffer);
+                line.extend_from_slice(&chunk[..pos]);
+                self.process_line(&line)?;
+                line.clear();
+                self.buffer = line;
+            }
+            chunk = &chunk[pos + 1..];
+        }
+        self.check_line_length(self.buffer.len() + chunk.len())?;
+        self.buffer.extend_from_slice(chunk);
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<MLInputData, String> {
+        if !self.buffer.is_empty() {
+            let line = std::mem::take(&mut self.buffer);
+            self.process_line(&line)?;
+        }
+        let header = self
+            .header
+            .ok_or("Empty NDJSON body: header line expected")?;
+
+        let weeks = match self.aggregator {
+            Some(aggregator) => aggregator.finish(),
+            None => header.weeks,
+        };
+        Ok(MLInputData {
+            timesheets: self.entries,
+            projects: header.projects,
+            weeks,
+            settings: header.settings,
+            context: header.context,
+            options: header.options,
+        })
+    }
+
+    fn check_line_length(&self, len: usize) -> Result<(), String> {
+        if len > self.max_line_bytes {
+            return Err(format!(
+                "NDJSON line {} exceeds {} bytes",
+                self.line + 1,
+                self.max_line_bytes
+            ));
+        }
+        Ok(())
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Result<(), String> {
+        self.line += 1;
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            return Ok(());
+        }
+
+        if self.header.is_none() {
+            let mut header: Header = serde_json::from_slice(line)
+                .map_err(|e| format!("Invalid NDJSON header (line {}): {}", self.line, e))?;
+            self.durations = DurationNormalizer::new(header.settings.duration_unit);
+            header.settings.duration_unit = DurationUnit::Minutes;
+            // Переданные недели важнее восстановленных по записям
+            self.aggregator = header
+                .weeks
+                .is_empty()
+                .then(|| WeeklyAggregator::new(header.settings.rate_per_minute));
+            self.header = Some(header);
+            return Ok(());
+        }
+
+        let invalid = |e: String| format!("Invalid timesheet entry (line {}): {}", self.line, e);
+        let mut entry: JsonValue =
+            serde_json::from_slice(line).map_err(|e| invalid(e.to_string()))?;
+        self.durations
+            .normalize_entry(&mut entry)
+            .map_err(invalid)?;
+        let entry: TimesheetEntry =
+            serde_json::from_value(entry).map_err(|e| invalid(e.to_string()))?;
+        if let Some(aggregator) = self.aggregator.as_mut() {
+            aggregator.add(&entry);
+        }
+        if self.keep_entries {
+            if self.entries.len() >= self.max_entries {
+                return Err(format!(
+                    "Too many timesheet entries (limit {})",
+                    self.max_entries
+                ));
+            }
+            self.entries.push(entry);
+        }
+        Ok(())
+    }
+}
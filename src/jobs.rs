@@ -0,0 +1,272 @@
+//! Реестр выполняющихся задач обучения.
+//!
+//! Задачи и подписки на них принадлежат арендатору: другой арендатор не видит,
+//! не отменяет и не слушает их, даже зная идентификатор
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::cancellation::CancellationToken;
+use crate::progress::TrainingProgress;
+
+/// Размер буфера событий одной задачи; отстающие подписчики пропускают старые события
+const EVENTS_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: String,
+    pub started_at: String,
+    /// Арендатор, запустивший задачу
+    #[serde(default)]
+    pub tenant: String,
+}
+
+/// Событие задачи для подписчиков (`GET /api/jobs/{id}/events`)
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JobEvent {
+    Progress(TrainingProgress),
+    /// `status`: "completed" | "failed" | "cancelled"
+    Finished {
+        status: String,
+    },
+}
+
+impl JobEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Progress(_) => "progress",
+            JobEvent::Finished { .. } => "finished",
+        }
+    }
+}
+
+struct JobEntry {
+    /// `None`, пока на задачу только подписались, но она еще не запущена
+    info: Option<JobInfo>,
+    token: CancellationToken,
+    events: broadcast::Sender<JobEvent>,
+}
+
+impl JobEntry {
+    fn pending() -> Self {
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        Self {
+            info: None,
+            token: CancellationToken::new(),
+            events,
+        }
+    }
+}
+
+/// Задача арендатора: (арендатор, идентификатор)
+type JobKey = (String, String);
+
+fn job_key(tenant: &str, id: &str) -> JobKey {
+    (tenant.to_string(), id.to_string())
+}
+
+/// Активные задачи обучения, их токены отмены и каналы событий
+#[derive(Default)]
+pub struct JobRegistry {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<JobKey, JobEntry>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Регистрирует задачу; она снимается с учета при сбросе `JobGuard`.
+    ///
+    /// `requested_id` позволяет клиенту заранее подписаться на события задачи.
+    pub fn start(
+        self: &Arc<Self>,
+        kind: &str,
+        tenant: &str,
+        requested_id: Option<&str>,
+    ) -> Result<JobGuard, String> {
+        let id = match requested_id {
+            Some(id) => id.to_string(),
+            None => format!("job-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1),
+        };
+
+        let mut jobs = self.lock();
+        Self::prune(&mut jobs);
+
+        let key = job_key(tenant, &id);
+        let entry = jobs.entry(key.clone()).or_insert_with(JobEntry::pending);
+        if entry.info.is_some() {
+            return Err(format!("Job {} is already running", id));
+        }
+        entry.info = Some(JobInfo {
+            id,
+            kind: kind.to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            tenant: tenant.to_string(),
+        });
+
+        Ok(JobGuard {
+            registry: Arc::clone(self),
+            key,
+            token: entry.token.clone(),
+            events: entry.events.clone(),
+            finished: false,
+        })
+    }
+
+    /// Подписка на события задачи арендатора; допускается до ее запуска
+    pub fn subscribe(&self, tenant: &str, id: &str) -> broadcast::Receiver<JobEvent> {
+        let mut jobs = self.lock();
+        Self::prune(&mut jobs);
+        jobs.entry(job_key(tenant, id))
+            .or_insert_with(JobEntry::pending)
+            .events
+            .subscribe()
+    }
+
+    /// Отмена задачи арендатора; `false`, если задача не найдена
+    pub fn cancel(&self, tenant: &str, id: &str) -> bool {
+        match self.lock().get(&job_key(tenant, id)) {
+            Some(entry) if entry.info.is_some() => {
+                entry.token.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Задача с идентификатором `id` запущена только другими арендаторами
+    pub fn is_foreign(&self, tenant: &str, id: &str) -> bool {
+        let jobs = self.lock();
+        let running = |owner: &str| {
+            jobs.get(&job_key(owner, id))
+                .is_some_and(|e| e.info.is_some())
+        };
+        !running(tenant) && jobs.keys().any(|(owner, job)| job == id && running(owner))
+    }
+
+    /// Задачи арендатора
+    pub fn list(&self, tenant: &str) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self
+            .lock()
+            .iter()
+            .filter(|((owner, _), _)| owner == tenant)
+            .filter_map(|(_, e)| e.info.clone())
+            .collect();
+        jobs.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        jobs
+    }
+
+    /// Удаляет подписки на незапущенные задачи, от которых все отключились
+    fn prune(jobs: &mut HashMap<JobKey, JobEntry>) {
+        jobs.retain(|_, e| e.info.is_some() || e.events.receiver_count() > 0);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<JobKey, JobEntry>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Дескриптор выполняющейся задачи.
+///
+/// При сбросе (в том числе когда клиент отключился и future обработчика
+/// уничтожен) задача отменяется и удаляется из реестра.
+pub struct JobGuard {
+    registry: Arc<JobRegistry>,
+    key: JobKey,
+    token: CancellationToken,
+    events: broadcast::Sender<JobEvent>,
+    finished: bool,
+}
+
+impl JobGuard {
+    pub fn id(&self) -> &str {
+        &self.key.1
+    }
+
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Сведения о задаче (для общего хранилища)
+    pub fn info(&self) -> Option<JobInfo> {
+        self.registry
+            .lock()
+            .get(&self.key)
+            .and_then(|e| e.info.clone())
+    }
+
+    /// Колбэк прогресса для передачи в обучение (в том числе в другой поток)
+    pub fn progress_reporter(&self) -> impl Fn(TrainingProgress) + Send + 'static {
+        let events = self.events.clone();
+        move |progress| {
+            // Ошибка означает лишь отсутствие подписчиков
+            let _ = events.send(JobEvent::Progress(progress));
+        }
+    }
+
+    /// Завершение задачи с итоговым статусом для подписчиков
+    pub fn finish(mut self, status: &str) {
+        self.send_finished(status);
+    }
+
+    fn send_finished(&mut self, status: &str) {
+        if !self.finished {
+            self.finished = true;
+            let _ = self.events.send(JobEvent::Finished {
+                status: status.to_string(),
+            });
+        }
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        self.token.cancel();
+        self.send_finished("cancelled");
+        self.registry.lock().remove(&self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jobs_are_visible_only_to_their_tenant() {
+        let registry = Arc::new(JobRegistry::new());
+        let job = registry
+            .start("forecasting", "acme", Some("train"))
+            .unwrap();
+        // Тот же идентификатор у другого арендатора - другая задача
+        let other = registry
+            .start("forecasting", "globex", Some("train"))
+            .unwrap();
+
+        assert_eq!(registry.list("acme").len(), 1);
+        assert_eq!(registry.list("acme")[0].tenant, "acme");
+        assert!(registry.list("initech").is_empty());
+
+        let mut acme_events = registry.subscribe("acme", "train");
+        let mut intruder_events = registry.subscribe("initech", "train");
+        job.finish("completed");
+        assert!(matches!(
+            acme_events.try_recv(),
+            Ok(JobEvent::Finished { .. })
+        ));
+        assert!(intruder_events.try_recv().is_err());
+
+        assert!(registry.is_foreign("initech", "train"));
+        assert!(!registry.is_foreign("globex", "train"));
+        assert!(!registry.cancel("initech", "train"));
+        assert!(registry.cancel("globex", "train"));
+        assert!(other.token().is_cancelled());
+    }
+}
@@ -1,12 +1,47 @@
 //! Kimai ML - Rust библиотека
 
+// Линкует провайдер BLAS, которым `ndarray` выполняет `dot` (feature `blas`)
+#[cfg(feature = "blas")]
+extern crate blas_src;
+
+pub mod audit;
+pub mod auth;
+pub mod cache;
+pub mod calendar;
+pub mod cancellation;
+pub mod capture;
+pub mod compact;
+pub mod duration;
+pub mod events;
+pub mod float;
+pub mod grpc_server;
+pub mod holidays;
+pub mod ingest;
+pub mod jobs;
 pub mod models;
 pub mod preprocessing;
+pub mod progress;
+pub mod projection;
+pub mod registry;
+pub mod retention;
+pub mod schema;
+pub mod segments;
+pub mod selftest;
+pub mod signing;
+pub mod storage;
+pub mod synthetic;
+pub mod tenants;
+pub mod testing;
+pub mod text;
 pub mod types;
-pub mod grpc_server;
+pub mod usage;
 
+pub use cancellation::CancellationToken;
+pub use float::Float;
 pub use models::*;
 pub use preprocessing::*;
+pub use progress::TrainingProgress;
+pub use storage::{MemoryStorage, Storage};
 pub use types::*;
 
 // Re-export для удобства
@@ -1,26 +1,253 @@
 //! API сервер для ML моделей
 
 use axum::{
-    extract::State,
-    http::{Method, StatusCode},
-    response::Json,
-    routing::{get, post},
+    body::Body,
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, FromRequest, FromRequestParts, Path, Query, Request, State,
+    },
+    http::{header, request::Parts, Method, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Json, Response,
+    },
+    routing::{delete, get, post},
     Router,
 };
-use serde::Deserialize;
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
 use tower_http::cors::{Any, CorsLayer};
 
+#[cfg(feature = "graphql")]
+mod graphql;
+
 use kimai_ml::{
-    types::{MLInputData, MLOutputData},
-    AnomalyDetector, ForecastingModel, LearningModule, RecommendationEngine,
+    audit::{AuditLog, AuditQuery, AuditRecord, FileAuditLog, MemoryAuditLog},
+    auth::{Identity, JwtAuth},
+    cache::{cache_key, tenant_cache_key, AnomalyFeatureCache, MemoryCache, PredictionCache},
+    cancellation::TRAINING_CANCELLED,
+    capture::{self, CaptureLog, CaptureRecord},
+    compact::SizeReport,
+    events::{AnalysisEvent, EventHub, EventKind, DEFAULT_TENANT},
+    ingest::{NdjsonReader, NDJSON_CONTENT_TYPE},
+    jobs::{JobEvent, JobGuard, JobInfo, JobRegistry},
+    models::anomaly_detection::{split_evaluation_window, ScoringMode, MIN_TRAINING_ENTRIES},
+    models::anomaly_filter::AnomalyFilter,
+    models::baseline::{self, BaselineProfile},
+    models::forecast_ledger::ForecastLedger,
+    models::forecasting::ForecastBounds,
+    models::recommendations::{
+        RecommendationHistory, RecommendationTracker, RecommendationTypeStats,
+    },
+    preprocessing::{sanitize_weeks, NonFinitePolicy},
+    projection::FieldProjection,
+    registry::{
+        Artifact, ArtifactFormat, ArtifactVersion, LocalArtifactStore, ModelRegistry, Promotion,
+    },
+    retention::{self, CleanupReport, RetentionPolicy, TenantDataDeletion},
+    selftest::{self, ReadinessCheck},
+    signing::{SignedStorage, SnapshotSigner},
+    storage::{MemoryStorage, Storage},
+    synthetic::{self, Profile},
+    tenants::{self, TenantMap},
+    types::{
+        AbsenceImpact, AnomalyFeedback, CapacityGap, CohortAnalysis, DataSufficiency, DryRunReport,
+        EntryExplanation, ForecastHistory, ForecastRevisions, InsufficientData, MLInputData,
+        MLOutputData, PatternDiagnostics, PlannedAbsence, ProjectLifetime, ProjectUsage,
+        ProposedSchedule, RecommendationFeedback, ScheduleComparison, SeasonalityDiagnostics,
+        SegmentOutput, TeamBalance, TeamInput, TeamUtilization, UtilizationThresholds, WeekData,
+    },
+    usage::{QuotaConfig, UsageReport, UsageTracker},
+    AnomalyDetector, AnomalyVerdict, ClassificationStats, ForecastingModel, LearningModule,
+    RecommendationEngine, RetrainSignal,
 };
 
 #[derive(Clone)]
 struct AppState {
-    forecasting_model: std::sync::Arc<tokio::sync::Mutex<ForecastingModel>>,
-    anomaly_detector: std::sync::Arc<tokio::sync::Mutex<AnomalyDetector>>,
+    /// Модели прогноза арендаторов, загружаются при первом обращении (`forecasting_model`)
+    forecasting_models: std::sync::Arc<TenantMap<ForecastingModel>>,
+    /// Детекторы аномалий арендаторов (`anomaly_detector`)
+    anomaly_detectors: std::sync::Arc<TenantMap<AnomalyDetector>>,
+    /// Детектор нового арендатора с параметрами из окружения
+    anomaly_template: std::sync::Arc<AnomalyDetector>,
     recommendation_engine: std::sync::Arc<tokio::sync::Mutex<RecommendationEngine>>,
-    learning_module: std::sync::Arc<tokio::sync::Mutex<LearningModule>>,
+    /// Модули обучения арендаторов (`sync_learning`)
+    learning_modules: std::sync::Arc<TenantMap<LearningModule>>,
+    /// Модуль обучения нового арендатора с порогами переобучения из окружения
+    learning_template: std::sync::Arc<LearningModule>,
+    jobs: std::sync::Arc<JobRegistry>,
+    storage: std::sync::Arc<dyn Storage>,
+    cache: std::sync::Arc<dyn PredictionCache>,
+    /// Признаки аномалий последних наборов записей арендаторов (`ANOMALY_FEATURE_CACHE`)
+    anomaly_features: std::sync::Arc<AnomalyFeatureCache>,
+    registry: Option<std::sync::Arc<ModelRegistry>>,
+    audit: std::sync::Arc<dyn AuditLog>,
+    events: std::sync::Arc<EventHub>,
+    /// Последнее успешное обучение прогноза по арендаторам: на этих данных
+    /// модель арендатора переобучается по сигналу модуля обучения
+    retrain_inputs:
+        std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, TrainingInput>>>,
+    /// Ревизия ответов для ETag: растет после обучения на ошибках, отзывов и
+    /// переобучения, когда ответы на те же данные меняются без смены модели
+    output_revision: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    /// Проверка JWT; `None` - аутентификация выключена (`JWT_SECRET`, `JWT_JWKS_URL`)
+    auth: Option<std::sync::Arc<JwtAuth>>,
+    /// Потребление арендаторов и квоты (`TENANT_QUOTAS`)
+    usage: std::sync::Arc<UsageTracker>,
+    /// Запись запросов анализа для `kimai-ml replay` (`CAPTURE_PATH`)
+    capture: Option<std::sync::Arc<CaptureLog>>,
+    /// SHA-256 снимков моделей арендаторов (`forecasting:<арендатор>`,
+    /// `anomaly:<арендатор>`), из которых модели загружены или которые
+    /// сохранены последними (проверка целостности в `/ready`)
+    model_checksums: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, String>>>,
+}
+
+#[derive(Clone)]
+struct TrainingInput {
+    weeks: Vec<WeekData>,
+    options: Option<serde_json::Value>,
+}
+
+/// Арендатор запроса: из JWT при включенной аутентификации, иначе из заголовка
+/// `X-Tenant-Id` (`default`, если не задан)
+#[derive(Clone)]
+struct Tenant(String);
+
+impl Tenant {
+    fn of(parts: &Parts) -> Self {
+        if let Some(identity) = parts.extensions.get::<Identity>() {
+            return Tenant(identity.tenant.clone());
+        }
+        let tenant = parts
+            .headers
+            .get("x-tenant-id")
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty())
+            .unwrap_or(DEFAULT_TENANT);
+        Tenant(tenant.to_string())
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Tenant {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Tenant::of(parts))
+    }
+}
+
+/// Тело запроса анализа: JSON (`MLInputData`) или поток NDJSON (`application/x-ndjson`)
+struct AnalysisInput(MLInputData);
+
+/// То же для прогноза: записи из NDJSON только сворачиваются в недели и не хранятся
+struct WeeklyInput(MLInputData);
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequest<S> for AnalysisInput {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        read_input(request, state, true).await.map(AnalysisInput)
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequest<S> for WeeklyInput {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        read_input(request, state, false).await.map(WeeklyInput)
+    }
+}
+
+async fn read_input<S: Send + Sync>(
+    request: Request,
+    state: &S,
+    keep_entries: bool,
+) -> Result<MLInputData, Response> {
+    let is_ndjson = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(NDJSON_CONTENT_TYPE));
+    if !is_ndjson {
+        // Длительности в секундах и ISO-8601 приводятся к минутам до разбора
+        let Json(mut data) = Json::<serde_json::Value>::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        kimai_ml::duration::normalize_input(&mut data)
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e).into_response())?;
+        return serde_json::from_value(data).map_err(|e| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "Failed to deserialize the JSON body into the target type: {}",
+                    e
+                ),
+            )
+                .into_response()
+        });
+    }
+
+    // Тело читается по частям, без буферизации целиком
+    let mut reader = NdjsonReader::new(keep_entries);
+    if let Some(max) = std::env::var("NDJSON_MAX_ENTRIES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        reader = reader.max_entries(max);
+    }
+    let mut body = request.into_body().into_data_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())?;
+        reader
+            .push(&chunk)
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e).into_response())?;
+    }
+    reader
+        .finish()
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e).into_response())
+}
+
+/// Тело запроса аналитики команды (`TeamInput`, только JSON); длительности
+/// записей каждого участника приводятся к минутам по его настройкам
+struct TeamBody(TeamInput);
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequest<S> for TeamBody {
+    type Rejection = Response;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(mut data) = Json::<serde_json::Value>::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        if let Some(members) = data.get_mut("members").and_then(|m| m.as_array_mut()) {
+            for member in members {
+                kimai_ml::duration::normalize_input(member)
+                    .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e).into_response())?;
+            }
+        }
+        let mut team: TeamInput = serde_json::from_value(data).map_err(|e| {
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "Failed to deserialize the JSON body into the target type: {}",
+                    e
+                ),
+            )
+                .into_response()
+        })?;
+        if team.members.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "members must not be empty").into_response());
+        }
+        for member in &mut team.members {
+            member.data.exclude_projects(ProjectUsage::Analysis);
+        }
+        Ok(TeamBody(team))
+    }
 }
 
 #[tokio::main]
@@ -30,44 +257,233 @@ async fn main() {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("replay") {
+        std::process::exit(replay(&args[1..]).await);
+    }
+
     let state = AppState {
-        forecasting_model: std::sync::Arc::new(tokio::sync::Mutex::new(ForecastingModel::new())),
-        anomaly_detector: std::sync::Arc::new(tokio::sync::Mutex::new(AnomalyDetector::new(0.1))),
+        registry: open_registry(),
+        auth: JwtAuth::from_env().map(std::sync::Arc::new),
+        usage: std::sync::Arc::new(open_usage_tracker()),
+        capture: open_capture(),
+        ..new_state(
+            open_storage().await,
+            open_cache().await,
+            open_audit_log().await,
+        )
+    };
+    for check in self_test(&state).await {
+        if check.ok {
+            tracing::info!("Self-test {}: {}", check.name, check.detail);
+        } else {
+            tracing::warn!("Self-test {} failed: {}", check.name, check.detail);
+        }
+    }
+    tokio::spawn(watch_model_updates(state.clone()));
+    let retention = RetentionPolicy::from_env().unwrap_or_else(|e| panic!("{}", e));
+    if retention.is_enabled() {
+        tokio::spawn(enforce_retention(state.clone(), retention));
+    }
+
+    let app = router(state);
+    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 8000));
+    // Start gRPC server in background (addr: 50051)
+    let grpc_addr = std::net::SocketAddr::from(([0, 0, 0, 0], 50051));
+    let _grpc = tokio::spawn(async move {
+        if let Err(e) = kimai_ml::grpc_server::start_grpc_server(grpc_addr).await {
+            tracing::error!("gRPC server error: {}", e);
+        }
+    });
+
+    let app = app.into_make_service_with_connect_info::<std::net::SocketAddr>();
+    if let Some((cert_path, key_path)) = tls_paths() {
+        serve_tls(addr, app, &cert_path, &key_path).await;
+        return;
+    }
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    tracing::info!("Server listening on http://0.0.0.0:8000");
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// Модели и модуль обучения по настройкам окружения с переданными хранилищем,
+/// кэшем и журналом аудита; без реестра, аутентификации, квот и записи запросов
+fn new_state(
+    storage: std::sync::Arc<dyn Storage>,
+    cache: std::sync::Arc<dyn PredictionCache>,
+    audit: std::sync::Arc<dyn AuditLog>,
+) -> AppState {
+    let mut anomaly_detector = AnomalyDetector::builder().contamination(0.1);
+    if let Some(trees) = env_parse("ANOMALY_TREES") {
+        anomaly_detector = anomaly_detector.trees(trees);
+    }
+    if let Some(samples) = env_parse("ANOMALY_MAX_SAMPLES") {
+        anomaly_detector = anomaly_detector.max_samples(samples);
+    }
+    let anomaly_detector = configure_anomaly_detector(
+        anomaly_detector
+            .build()
+            .expect("valid anomaly detector parameters"),
+    );
+    let mut learning_module = LearningModule::builder().max_errors(1000);
+    if let Some(threshold) = env_parse("RETRAIN_MAPE_THRESHOLD") {
+        learning_module = learning_module.retrain_mape_threshold(threshold);
+    }
+    if let Some(consecutive) = env_parse("RETRAIN_AFTER_ERRORS") {
+        learning_module = learning_module.retrain_after(consecutive);
+    }
+    let learning_module = learning_module
+        .build()
+        .expect("valid learning module parameters");
+
+    let tenant_capacity =
+        env_parse("TENANT_STATE_CAPACITY").unwrap_or(tenants::DEFAULT_TENANT_CAPACITY);
+
+    AppState {
+        forecasting_models: std::sync::Arc::new(TenantMap::with_capacity(tenant_capacity)),
+        anomaly_detectors: std::sync::Arc::new(TenantMap::with_capacity(tenant_capacity)),
+        anomaly_template: std::sync::Arc::new(anomaly_detector),
         recommendation_engine: std::sync::Arc::new(tokio::sync::Mutex::new(
-            RecommendationEngine::new(),
+            open_recommendation_engine(),
         )),
-        learning_module: std::sync::Arc::new(tokio::sync::Mutex::new(LearningModule::new(1000))),
-    };
+        learning_modules: std::sync::Arc::new(TenantMap::with_capacity(tenant_capacity)),
+        learning_template: std::sync::Arc::new(learning_module),
+        jobs: std::sync::Arc::new(JobRegistry::new()),
+        storage,
+        cache,
+        anomaly_features: std::sync::Arc::new(
+            env_parse("ANOMALY_FEATURE_CACHE")
+                .map_or_else(AnomalyFeatureCache::default, AnomalyFeatureCache::new),
+        ),
+        registry: None,
+        audit,
+        events: std::sync::Arc::new(EventHub::new()),
+        retrain_inputs: std::sync::Arc::default(),
+        output_revision: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
+        model_checksums: std::sync::Arc::default(),
+        auth: None,
+        usage: std::sync::Arc::default(),
+        capture: None,
+    }
+}
 
+fn router(state: AppState) -> Router {
     // CORS
     let cors = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers(Any);
+        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
+        .allow_headers(Any)
+        .expose_headers([header::ETAG]);
 
     let app = Router::new()
         .route("/", get(root))
         .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route("/api/schema", get(api_schema))
+        .route("/api/schema/:name", get(api_schema_by_name))
         .route("/api/predict", post(predict))
         .route("/api/detect-anomalies", post(detect_anomalies))
-        .route("/api/recommendations", post(get_recommendations))
+        .route("/api/explain-entry", post(explain_entry))
+        .route("/api/recommendations", post(recommendations))
+        .route(
+            "/api/recommendations/feedback",
+            post(recommendation_feedback),
+        )
         .route("/api/productivity", post(analyze_productivity))
+        .route("/api/demo", get(demo_analysis))
+        .route(
+            "/api/diagnostics/seasonality",
+            post(seasonality_diagnostics),
+        )
+        .route("/api/compare-schedules", post(compare_schedules))
+        .route("/api/simulate-absence", post(simulate_absence))
+        .route("/api/team/cohorts", post(team_cohorts))
+        .route("/api/team/balance", post(team_balance))
+        .route("/api/team/capacity-gap", post(team_capacity_gap))
+        .route("/api/team/utilization", post(team_utilization))
+        .route("/api/diagnostics/patterns", post(pattern_diagnostics))
+        .route(
+            "/api/diagnostics/project-lifetime",
+            post(project_lifetime_diagnostics),
+        )
+        .route("/api/forecasts/history", get(forecast_history))
+        .route("/api/forecasts/revisions", get(forecast_revisions))
         .route("/api/learn", post(learn_from_error))
-        .layer(cors)
-        .with_state(state);
+        .route("/api/anomalies/feedback", post(anomaly_feedback))
+        .route("/api/learning/stats", get(learning_stats))
+        .route("/api/models/:name/versions", get(list_model_versions))
+        .route("/api/models/:name/register", post(register_model))
+        .route("/api/models/:name/promote", post(promote_model))
+        .route("/api/jobs", get(list_jobs))
+        .route("/api/jobs/:id/cancel", post(cancel_job))
+        .route("/api/jobs/:id/events", get(job_events))
+        .route("/api/audit", get(query_audit_log))
+        .route("/api/admin/usage", get(tenant_usage))
+        .route("/api/tenants/:id/data", delete(delete_tenant_data))
+        .route("/ws", get(ws_events));
+    #[cfg(feature = "graphql")]
+    let app = app.merge(graphql::routes(state.clone()));
+    app.layer(middleware::from_fn_with_state(
+        state.clone(),
+        capture_requests,
+    ))
+    .layer(middleware::from_fn(project_fields))
+    .layer(middleware::from_fn_with_state(
+        state.clone(),
+        conditional_responses,
+    ))
+    .layer(middleware::from_fn_with_state(
+        state.clone(),
+        enforce_quotas,
+    ))
+    .layer(middleware::from_fn_with_state(
+        state.clone(),
+        audit_requests,
+    ))
+    .layer(middleware::from_fn_with_state(
+        state.clone(),
+        authenticate_requests,
+    ))
+    .layer(cors)
+    .with_state(state)
+}
 
-    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 8000));
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    tracing::info!("Server listening on http://0.0.0.0:8000");
-        // Start gRPC server in background (addr: 50051)
-        let grpc_addr = std::net::SocketAddr::from(([0, 0, 0, 0], 50051));
-        let _grpc = tokio::spawn(async move {
-            if let Err(e) = kimai_ml::grpc_server::start_grpc_server(grpc_addr).await {
-                tracing::error!("gRPC server error: {}", e);
-            }
-        });
+type AppService =
+    axum::extract::connect_info::IntoMakeServiceWithConnectInfo<Router, std::net::SocketAddr>;
+
+/// Сертификат и ключ PEM из `TLS_CERT_PATH` и `TLS_KEY_PATH`; без них сервер
+/// работает по HTTP
+fn tls_paths() -> Option<(String, String)> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+    match (var("TLS_CERT_PATH"), var("TLS_KEY_PATH")) {
+        (Some(cert_path), Some(key_path)) => Some((cert_path, key_path)),
+        (None, None) => None,
+        _ => panic!("TLS_CERT_PATH and TLS_KEY_PATH must be set together"),
+    }
+}
 
-        axum::serve(listener, app).await.unwrap();
+#[cfg(feature = "tls")]
+async fn serve_tls(addr: std::net::SocketAddr, app: AppService, cert_path: &str, key_path: &str) {
+    // В дереве зависимостей rustls может быть собран с несколькими провайдерами
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let config = axum_server::tls_rustls::RustlsConfig::from_pem_file(cert_path, key_path)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to load TLS certificate {}: {}", cert_path, e));
+    tracing::info!("Server listening on https://0.0.0.0:{}", addr.port());
+    axum_server::bind_rustls(addr, config)
+        .serve(app)
+        .await
+        .unwrap();
+}
+
+#[cfg(not(feature = "tls"))]
+async fn serve_tls(
+    _addr: std::net::SocketAddr,
+    _app: AppService,
+    _cert_path: &str,
+    _key_path: &str,
+) {
+    panic!("TLS_CERT_PATH is set, but the server is built without the tls feature")
 }
 
 async fn root() -> Json<serde_json::Value> {
@@ -81,31 +497,175 @@ async fn health() -> Json<serde_json::Value> {
     Json(serde_json::json!({ "status": "ok" }))
 }
 
+/// Готовность принимать запросы: 503, если не прошла хотя бы одна проверка `self_test`
+async fn ready(State(state): State<AppState>) -> (StatusCode, Json<serde_json::Value>) {
+    let checks = self_test(&state).await;
+    let ready = checks.iter().all(|c| c.ok);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(serde_json::json!({
+            "status": if ready { "ready" } else { "not_ready" },
+            "checks": checks,
+        })),
+    )
+}
+
+/// Доступность хранилища, контрольные суммы обученных моделей и пробные
+/// прогноз и поиск аномалий на синтетических данных (на копиях моделей)
+async fn self_test(state: &AppState) -> Vec<ReadinessCheck> {
+    let mut checks = vec![ReadinessCheck::new(
+        "storage",
+        state
+            .storage
+            .load_model(&tenants::model_name("forecasting", DEFAULT_TENANT))
+            .await
+            .map(|_| "reachable".to_string()),
+    )];
+
+    // Снимки обученных моделей, загруженных арендаторами
+    let mut forecasting_snapshots = Vec::new();
+    for tenant in state.forecasting_models.tenants() {
+        if let Some(model) = state.forecasting_models.get(&tenant) {
+            let model = model.lock().await;
+            if model.model_info().is_some() {
+                forecasting_snapshots
+                    .push((tenants::model_name("forecasting", &tenant), model.to_json()));
+            }
+        }
+    }
+    let mut anomaly_snapshots = Vec::new();
+    for tenant in state.anomaly_detectors.tenants() {
+        if let Some(detector) = state.anomaly_detectors.get(&tenant) {
+            let detector = detector.lock().await;
+            if detector.model_info().is_some() {
+                anomaly_snapshots
+                    .push((tenants::model_name("anomaly", &tenant), detector.to_json()));
+            }
+        }
+    }
+    let checksums = state
+        .model_checksums
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    for (check, snapshots) in [
+        ("forecasting_checksum", forecasting_snapshots),
+        ("anomaly_checksum", anomaly_snapshots),
+    ] {
+        if snapshots.is_empty() {
+            continue;
+        }
+        let count = snapshots.len();
+        let failures: Vec<String> = snapshots
+            .into_iter()
+            .filter_map(|(name, snapshot)| {
+                selftest::verify_checksum(snapshot, checksums.get(&name).map(String::as_str))
+                    .err()
+                    .map(|e| format!("{}: {}", name, e))
+            })
+            .collect();
+        let result = if failures.is_empty() {
+            Ok(format!("{} models verified", count))
+        } else {
+            Err(failures.join("; "))
+        };
+        checks.push(ReadinessCheck::new(check, result));
+    }
+
+    // Пробные прогноз и поиск аномалий - на копиях моделей арендатора по умолчанию
+    let forecasting = forecasting_model(state, DEFAULT_TENANT)
+        .await
+        .lock()
+        .await
+        .clone();
+    let anomaly = anomaly_detector(state, DEFAULT_TENANT)
+        .await
+        .lock()
+        .await
+        .clone();
+
+    let smoke = tokio::task::spawn_blocking(move || {
+        [
+            ReadinessCheck::new("forecasting_smoke", selftest::smoke_forecast(&forecasting)),
+            ReadinessCheck::new("anomaly_smoke", selftest::smoke_anomalies(&anomaly)),
+        ]
+    })
+    .await;
+    match smoke {
+        Ok(smoke) => checks.extend(smoke),
+        Err(e) => checks.push(ReadinessCheck::new(
+            "smoke",
+            Err(format!("Smoke test task failed: {}", e)),
+        )),
+    }
+    checks
+}
+
+/// JSON Schema входа, выхода и отзывов с версией контракта
+async fn api_schema() -> Json<serde_json::Value> {
+    Json(kimai_ml::schema::api_schema())
+}
+
+async fn api_schema_by_name(
+    Path(name): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    kimai_ml::schema::schema(&name).map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!(
+                "Unknown schema: {} (expected one of: {})",
+                name,
+                kimai_ml::schema::SCHEMA_NAMES.join(", ")
+            ),
+        )
+    })
+}
+
 async fn predict(
     State(state): State<AppState>,
-    Json(data): Json<MLInputData>,
+    tenant: Tenant,
+    WeeklyInput(data): WeeklyInput,
 ) -> Result<Json<MLOutputData>, String> {
-    tracing::info!(
-        "Predict request: {} weeks, {} entries",
-        data.weeks.len(),
-        data.timesheets.len()
-    );
-
-    // Read optional model choice and other options from payload options
-    let model_choice = data
-        .options
-        .as_ref()
-        .and_then(|o| o.get("model"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
+    let key = output_cache_key("predict", &tenant.0, &data);
+    if is_dry_run(&data) {
+        let cache_hit = cached_output(&state, key.as_deref()).await.is_some();
+        let Json(mut output) = run_predict(state, tenant, data).await?;
+        if let Some(report) = output.dry_run.as_mut() {
+            report.cache_hit = cache_hit;
+            if key.is_some() {
+                report.skipped.push("cache output".to_string());
+            }
+        }
+        return Ok(Json(output));
+    }
+    if let Some(output) = cached_output(&state, key.as_deref()).await {
+        // Ответ из кэша - тоже выданный прогноз: ревизия записывается, как при расчете
+        if let Some(forecast) = output.forecasting.as_ref() {
+            let mut data = data;
+            data.exclude_projects(ProjectUsage::Forecasting);
+            match forecast_weeks(&data) {
+                Ok((weeks, _)) => record_forecast(&state, &tenant, &weeks, forecast).await,
+                Err(e) => tracing::warn!("Cached forecast not recorded: {}", e),
+            }
+        }
+        return Ok(Json(output));
+    }
 
-    let _include_weekends = data
-        .options
-        .as_ref()
-        .and_then(|o| o.get("include_weekends"))
-        .and_then(|v| v.as_bool())
-        .unwrap_or(true);
+    let Json(output) = run_predict(state.clone(), tenant, data).await?;
+    store_output(&state, key.as_deref(), &output).await;
+    Ok(Json(output))
+}
 
+/// Недели прогноза из запроса: по порядку без повторов, в окне `window_size`,
+/// без NaN и бесконечностей; вместе с предупреждениями об исправлениях
+fn forecast_weeks(
+    data: &MLInputData,
+) -> Result<(Vec<kimai_ml::types::WeekData>, Vec<String>), String> {
     let window_size_opt = data
         .options
         .as_ref()
@@ -113,13 +673,6 @@ async fn predict(
         .and_then(|v| v.as_i64())
         .map(|v| v as usize);
 
-    let _confidence_threshold = data
-        .options
-        .as_ref()
-        .and_then(|o| o.get("confidence_threshold"))
-        .and_then(|v| v.as_f64())
-        .unwrap_or(0.0);
-
     // Build weeks vector and apply window_size if present
     let mut weeks: Vec<kimai_ml::types::WeekData> = data
         .weeks
@@ -142,51 +695,152 @@ async fn predict(
         })
         .collect();
 
+    // Окно - последние недели по порядку, без повторов
+    let (canonical, week_report) =
+        kimai_ml::preprocessing::FeatureEngineer::canonical_weeks(&weeks);
+    if !week_report.is_clean() {
+        tracing::warn!("Week input corrected: {:?}", week_report);
+        weeks = canonical.into_owned();
+    }
+    let week_warnings = week_report.warnings();
+
     if let Some(ws) = window_size_opt {
         if weeks.len() > ws {
             weeks = weeks.split_off(weeks.len() - ws);
         }
     }
 
-    let mut model = state.forecasting_model.lock().await;
+    // NaN и бесконечности в неделях: замена (по умолчанию) или ошибка запроса
+    let non_finite = NonFinitePolicy::from_options(data.options.as_ref())?.unwrap_or_default();
+    let weeks = sanitize_weeks(&weeks, non_finite)?.into_owned();
+    Ok((weeks, week_warnings))
+}
 
-    if weeks.len() < 8 {
-        let avg_hours = if weeks.is_empty() {
-            0.0
-        } else {
-            weeks.iter().map(|w| w.total_hours).sum::<f64>() / weeks.len() as f64
-        };
+async fn run_predict(
+    state: AppState,
+    tenant: Tenant,
+    mut data: MLInputData,
+) -> Result<Json<MLOutputData>, String> {
+    data.exclude_projects(ProjectUsage::Forecasting);
+    tracing::info!(
+        "Predict request: {} weeks, {} entries",
+        data.weeks.len(),
+        data.timesheets.len()
+    );
+    add_country_holidays(&mut data)?;
 
-        // Учитываем цели по проектам
-        let mut weekly_hours_by_project = std::collections::HashMap::new();
-        if let Some(prefs) = &data.settings.user_preferences {
-            let total_goals: f64 = prefs.project_goals.values().sum();
-            if total_goals > 0.0 {
-                for (project_id, goal_hours) in &prefs.project_goals {
-                    let ratio = goal_hours / total_goals;
-                    weekly_hours_by_project.insert(*project_id, avg_hours * ratio);
-                }
-            }
-        }
+    // Read optional model choice and other options from payload options
+    let model_choice = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("model"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    let _include_weekends = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("include_weekends"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
+    let dry_run = is_dry_run(&data);
+    let bounds = ForecastBounds::from_options(data.options.as_ref())?;
+
+    // Доля оплачиваемой работы последних недель для прогноза оплачиваемых часов
+    let billable_share = kimai_ml::ProductivityAnalyzer::new()
+        .billable_tags(data.settings.billable_tags.clone())
+        .billable_share(&data.timesheets)
+        .map(|share| share.recent_share);
+
+    let _confidence_threshold = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("confidence_threshold"))
+        .and_then(|v| v.as_f64())
+        .unwrap_or(0.0);
 
+    let (weeks, week_warnings) = forecast_weeks(&data)?;
+
+    // Короткая история: прогноз без обучения дает сама модель
+    // (`ForecastingModel::fallback_forecast`), здесь - только причина
+    let sufficient = kimai_ml::types::DataSufficiency::from_weeks(weeks.len())
+        == kimai_ml::types::DataSufficiency::Full;
+    if !sufficient && data.settings.feature_enabled("insufficient_data", false) {
+        // Вместо среднего с низкой уверенностью - сколько недель не хватает
         return Ok(Json(MLOutputData {
-            forecasting: Some(kimai_ml::types::ForecastingOutput {
-                weekly_hours: avg_hours,
-                weekly_hours_by_project,
-                monthly_hours: avg_hours * 4.0,
-                confidence: 0.3,
-                trend: "stable".to_string(),
-            }),
-            anomalies: None,
-            recommendations: None,
-            productivity: None,
+            dry_run: dry_run_report(dry_run, weeks.len()),
+            segments: forecast_segments(&state, &tenant, &data).await?,
+            insufficient_data: InsufficientData::forecasting(weeks.len()).map(|gap| vec![gap]),
+            ..MLOutputData::default()
         }));
     }
 
-    // Обучение (если еще не обучена)
-    if let Err(e) = model.train_with_options(&weeks, data.options.as_ref()) {
-        tracing::warn!("Training failed: {}", e);
-    }
+    let mut dry_run_report = dry_run_report(dry_run, weeks.len());
+    let shared_model = forecasting_model(&state, &tenant.0).await;
+    let (model, weeks) = if !sufficient {
+        (shared_model.lock_owned().await, weeks)
+    } else {
+        // Обучение (если еще не обучена) в отдельном потоке: при отключении клиента
+        // или отмене через /api/jobs задача прерывается
+        let job = state
+            .jobs
+            .start("forecasting", &tenant.0, requested_job_id(&data).as_deref())?;
+        let _stored_job = StoredJob::save(&state, &job).await;
+        let token = job.token();
+        let report = job.progress_reporter();
+        let options = data.options.clone();
+        let mut model = if dry_run {
+            // Обучается копия: модель арендатора остается прежней
+            let copy = shared_model.lock().await.clone();
+            std::sync::Arc::new(tokio::sync::Mutex::new(copy))
+                .lock_owned()
+                .await
+        } else {
+            shared_model.lock_owned().await
+        };
+        let (model, weeks, train_result, elapsed) = tokio::task::spawn_blocking(move || {
+            let started = std::time::Instant::now();
+            let result = model.train_with_progress(&weeks, options.as_ref(), &token, &report);
+            (model, weeks, result, started.elapsed())
+        })
+        .await
+        .map_err(|e| format!("Training task failed: {}", e))?;
+        state.usage.record_training(&tenant.0, elapsed);
+        job.finish(job_status(&train_result));
+        if !dry_run {
+            publish_training(
+                &state,
+                &tenant,
+                "forecasting",
+                &train_result,
+                model.model_info(),
+            );
+        }
+
+        match (train_result, dry_run_report.as_mut()) {
+            (Err(e), _) if e == TRAINING_CANCELLED => return Err(e),
+            (Err(e), Some(report)) => report.training_error = Some(e),
+            (Err(e), None) => tracing::warn!("Training failed: {}", e),
+            (Ok(()), Some(report)) => report.skip(&["train forecasting", "store forecasting"]),
+            (Ok(()), None) => {
+                let name = tenants::model_name("forecasting", &tenant.0);
+                persist_model(&state, &tenant, &name, model.to_json()).await;
+                state
+                    .retrain_inputs
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(
+                        tenant.0.clone(),
+                        TrainingInput {
+                            weeks: weeks.clone(),
+                            options: data.options.clone(),
+                        },
+                    );
+            }
+        }
+        (model, weeks)
+    };
 
     // Прогнозирование
     let mut forecasting_result = if let Some(ref mc) = model_choice {
@@ -195,27 +849,49 @@ async fn predict(
         model.predict(&weeks)?
     };
 
-    // Применяем корректирующий фактор из модуля обучения
-    let learning = state.learning_module.lock().await;
-    let correction_factor = learning.get_correction_factor("forecasting");
-    let confidence_adjustment = learning.get_confidence_adjustment("forecasting");
+    // Применяем корректирующий фактор из модуля обучения; ошибки модели к
+    // прогнозу по короткой истории не относятся
+    let mut correction_factor = 1.0;
+    if sufficient {
+        let learning = sync_learning(&state, &tenant.0).await;
+        correction_factor = learning.get_correction_factor("forecasting");
+        let confidence_adjustment = learning.get_confidence_adjustment("forecasting");
 
-    forecasting_result.weekly_hours *= correction_factor;
-    forecasting_result.monthly_hours *= correction_factor;
-    forecasting_result.confidence *= confidence_adjustment;
+        forecasting_result.weekly_hours *= correction_factor;
+        forecasting_result.monthly_hours *= correction_factor;
+        forecasting_result.confidence = learning.calibrate_confidence(
+            "forecasting",
+            kimai_ml::models::learning::DEFAULT_HORIZON,
+            forecasting_result.confidence * confidence_adjustment,
+        );
+        if let Some(info) = forecasting_result.model_info.as_mut() {
+            info.correction_factor = Some(correction_factor);
+        }
+    }
+    forecasting_result.billable_hours =
+        billable_share.map(|share| forecasting_result.weekly_hours * share);
+    if data.settings.feature_enabled("explanations", false) {
+        let mut explanation = model.explain(&weeks, model_choice.as_deref())?;
+        explanation.correction_factor = correction_factor;
+        forecasting_result.explanation = Some(explanation);
+    }
 
-    // Учитываем цели по проектам при распределении
+    // Смешиваем прогноз по проектам с целями с учетом вероятности их достижения
     if let Some(prefs) = &data.settings.user_preferences {
-        let total_goals: f64 = prefs.project_goals.values().sum();
-        if total_goals > 0.0 {
-            forecasting_result.weekly_hours_by_project.clear();
-            for (project_id, goal_hours) in &prefs.project_goals {
-                let ratio = goal_hours / total_goals;
-                forecasting_result
-                    .weekly_hours_by_project
-                    .insert(*project_id, forecasting_result.weekly_hours * ratio);
-            }
-        }
+        forecasting_result.goal_assessments = kimai_ml::models::forecasting::blend_with_goals(
+            &weeks,
+            &mut forecasting_result.weekly_hours_by_project,
+            &prefs.project_goals,
+        );
+    }
+
+    forecasting_result.warnings = week_warnings;
+    if bounds.apply(&mut forecasting_result) {
+        tracing::warn!("Forecast clamped to {:?}", bounds);
+    }
+
+    if dry_run_report.is_none() {
+        record_forecast(&state, &tenant, &weeks, &forecasting_result).await;
     }
 
     // No further structural filtering for forecasting; return
@@ -224,24 +900,202 @@ async fn predict(
         anomalies: None,
         recommendations: None,
         productivity: None,
+        anomaly_model_info: None,
+        dry_run: dry_run_report,
+        anomaly_page: None,
+        anomaly_summary: None,
+        resolved_recommendations: None,
+        action_plan: None,
+        segments: forecast_segments(&state, &tenant, &data).await?,
+        insufficient_data: None,
     }))
 }
 
+/// Праздники `settings.holiday_country` от первой недели истории до недели
+/// прогноза - в `options.holidays` к датам клиента
+fn add_country_holidays(data: &mut MLInputData) -> Result<(), String> {
+    let Some(country) = &data.settings.holiday_country else {
+        return Ok(());
+    };
+    let monday = |week: &kimai_ml::types::WeekData| {
+        chrono::NaiveDate::from_isoywd_opt(week.year, week.week.max(1) as u32, chrono::Weekday::Mon)
+    };
+    let (Some(from), Some(last)) = (
+        data.weeks.first().and_then(monday),
+        data.weeks.last().and_then(monday),
+    ) else {
+        return Ok(());
+    };
+    let dates =
+        kimai_ml::holidays::holiday_dates(country, from, last + chrono::Duration::days(13))?;
+
+    let options = data
+        .options
+        .get_or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .ok_or("options must be an object")?;
+    let holidays = options
+        .entry("holidays")
+        .or_insert_with(|| serde_json::json!([]));
+    if let Some(holidays) = holidays.as_array_mut() {
+        holidays.extend(
+            dates
+                .iter()
+                .map(|d| serde_json::Value::String(d.to_string())),
+        );
+    }
+    Ok(())
+}
+
+/// Прогноз недели после `weeks` в журнал прогнозов арендатора и снимки на
+/// ближайшие недели; недели запроса сверяются с прежними прогнозами
+async fn record_forecast(
+    state: &AppState,
+    tenant: &Tenant,
+    weeks: &[kimai_ml::types::WeekData],
+    forecast: &kimai_ml::types::ForecastingOutput,
+) {
+    let Some(last) = weeks.last() else {
+        return;
+    };
+    let name = ForecastLedger::storage_name(&tenant.0);
+    let mut ledger: ForecastLedger =
+        load_tenant_state(state, &name, ForecastLedger::from_json).await;
+    ledger.sync_actuals(weeks);
+    let (year, week) =
+        kimai_ml::models::forecasting::next_iso_week(last.year, last.week.max(1) as u32);
+    let model_version = forecast
+        .model_info
+        .as_ref()
+        .map(|info| info.model_version.clone());
+    let now = chrono::Utc::now();
+    ledger.record_forecast(
+        ForecastLedger::period(year, week),
+        forecast.weekly_hours,
+        forecast.confidence,
+        model_version.clone(),
+        now,
+    );
+    ledger.record_snapshots(
+        year,
+        week,
+        forecast.weekly_hours,
+        forecast.confidence,
+        model_version,
+        now,
+    );
+    persist_model(state, tenant, &name, ledger.to_json()).await;
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastHistoryParams {
+    limit: Option<usize>,
+}
+
+/// Недель журнала прогнозов в ответе по умолчанию
+const DEFAULT_FORECAST_HISTORY: usize = 52;
+
+/// Выданные прогнозы арендатора с фактом и точностью по неделям
+async fn forecast_history(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<ForecastHistoryParams>,
+) -> Json<ForecastHistory> {
+    let name = ForecastLedger::storage_name(&tenant.0);
+    let ledger: ForecastLedger = load_tenant_state(&state, &name, ForecastLedger::from_json).await;
+    Json(ledger.history(params.limit.unwrap_or(DEFAULT_FORECAST_HISTORY)))
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastRevisionsParams {
+    period: String,
+}
+
+/// Как менялся прогноз недели `period` от первого снимка к последнему
+async fn forecast_revisions(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<ForecastRevisionsParams>,
+) -> Result<Json<ForecastRevisions>, (StatusCode, String)> {
+    let name = ForecastLedger::storage_name(&tenant.0);
+    let ledger: ForecastLedger = load_tenant_state(&state, &name, ForecastLedger::from_json).await;
+    ledger.revisions(&params.period).map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            format!("No forecast snapshots for {}", params.period),
+        )
+    })
+}
+
 async fn detect_anomalies(
     State(state): State<AppState>,
-    Json(data): Json<MLInputData>,
+    tenant: Tenant,
+    AnalysisInput(mut data): AnalysisInput,
 ) -> Result<Json<MLOutputData>, String> {
+    data.exclude_projects(ProjectUsage::Anomalies);
     tracing::info!(
         "Detect anomalies request: {} entries",
         data.timesheets.len()
     );
 
+    if let Some(backend) = data.settings.feature_str("anomaly_backend") {
+        if !AnomalyDetector::BACKENDS.contains(&backend) {
+            return Err(format!(
+                "Unsupported anomaly backend '{}' (available: {})",
+                backend,
+                AnomalyDetector::BACKENDS.join(", ")
+            ));
+        }
+    }
+
+    let filter = AnomalyFilter::from_options(data.options.as_ref())?;
+
+    // Аномалии недель целиком - без обучения модели: расхождения выручки и
+    // часов и недоучтенные недели (только с договорными часами)
+    let mut week_anomalies = if data.settings.feature_enabled("billing_anomalies", true) {
+        let tolerance = data
+            .options
+            .as_ref()
+            .and_then(|o| o.get("billing_tolerance"))
+            .and_then(|v| v.as_f64())
+            .unwrap_or(kimai_ml::models::billing::DEFAULT_BILLING_TOLERANCE);
+        kimai_ml::models::billing::billing_anomalies(&data.weeks, &data.settings, tolerance)
+    } else {
+        Vec::new()
+    };
+    if data
+        .settings
+        .feature_enabled("under_tracking_anomalies", true)
+    {
+        let tolerance = data
+            .options
+            .as_ref()
+            .and_then(|o| o.get("under_tracking_tolerance"))
+            .and_then(|v| v.as_f64())
+            .unwrap_or(kimai_ml::models::contract::DEFAULT_UNDER_TRACKING_TOLERANCE);
+        week_anomalies.extend(kimai_ml::models::contract::under_tracking_anomalies(
+            &data.weeks,
+            &data.settings,
+            tolerance,
+        ));
+    }
+
     if data.timesheets.is_empty() {
+        let summary = filter.summary(&week_anomalies, &data.timesheets);
+        let (week_anomalies, page) = filter.apply(week_anomalies, &data.timesheets);
         return Ok(Json(MLOutputData {
             forecasting: None,
-            anomalies: Some(Vec::new()),
+            anomalies: Some(week_anomalies),
             recommendations: None,
             productivity: None,
+            anomaly_model_info: None,
+            dry_run: dry_run_report(is_dry_run(&data), 0),
+            anomaly_page: Some(page),
+            anomaly_summary: Some(summary),
+            resolved_recommendations: None,
+            action_plan: None,
+            segments: None,
+            insufficient_data: None,
         }));
     }
 
@@ -260,7 +1114,7 @@ async fn detect_anomalies(
         .and_then(|v| v.as_f64())
         .unwrap_or(0.0);
 
-    let entries: Vec<kimai_ml::types::TimesheetEntry> = data
+    let mut entries: Vec<kimai_ml::types::TimesheetEntry> = data
         .timesheets
         .iter()
         .map(|e| kimai_ml::types::TimesheetEntry {
@@ -279,6 +1133,7 @@ async fn detect_anomalies(
             week_of_year: e.week_of_year,
             month: e.month,
             year: e.year,
+            billable: e.billable,
         })
         .filter(|e| {
             if include_weekends {
@@ -289,62 +1144,514 @@ async fn detect_anomalies(
         })
         .collect();
 
-    let mut detector = state.anomaly_detector.lock().await;
+    // Невозможные записи отмечаются сразу и не попадают в обучение
+    let invalid = kimai_ml::models::validation::invalid_entries(&entries, chrono::Utc::now());
+    let invalid_ids: std::collections::HashSet<i32> = invalid.iter().map(|a| a.entry_id).collect();
+    entries.retain(|e| !invalid_ids.contains(&e.id));
 
-    if entries.len() >= 20 {
-        if let Err(e) = detector.train(&entries) {
-            tracing::warn!("Training failed: {}", e);
-        }
+    // Пороги по типам аномалий, сдвинутые по отзывам пользователей, и модель
+    // важности, обученная на подтвержденных и отклоненных аномалиях
+    let (threshold_shifts, severity_model) = {
+        let learning = sync_learning(&state, &tenant.0).await;
+        (learning.threshold_shifts(), learning.severity_model())
+    };
+
+    // Окно проверки (`options.evaluation_days`): детектор обучается на записях до
+    // него, а записи окна получают абсолютную оценку изоляции
+    let evaluation_days = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("evaluation_days"))
+        .and_then(|v| v.as_i64())
+        .filter(|days| *days > 0);
+    let mut evaluation = None;
+    if let Some(days) = evaluation_days {
+        let (training, window) = split_evaluation_window(&entries, days);
+        entries = training;
+        evaluation = Some(window);
     }
 
-    match detector.detect(&entries) {
-        Ok(mut anomalies) => {
-            if confidence_threshold > 0.0 {
-                anomalies.retain(|a| a.score >= confidence_threshold);
-            }
-            Ok(Json(MLOutputData {
-                forecasting: None,
-                anomalies: Some(anomalies),
-                recommendations: None,
-                productivity: None,
-            }))
+    let dry_run = is_dry_run(&data);
+    let mut dry_run_report = dry_run_report(dry_run, entries.len());
+
+    // Оценка по профилю арендатора до того, как он учтет записи этого запроса
+    let baseline_name = BaselineProfile::storage_name(&tenant.0);
+    let mut baseline: BaselineProfile =
+        load_tenant_state(&state, &baseline_name, BaselineProfile::from_json).await;
+    let baseline_anomalies = baseline.score(evaluation.as_deref().unwrap_or(&entries));
+    let learned = baseline.update(&entries)
+        + evaluation
+            .as_deref()
+            .map_or(0, |window| baseline.update(window));
+    if learned > 0 {
+        match dry_run_report.as_mut() {
+            Some(report) => report.skip(&["store baseline"]),
+            None => persist_model(&state, &tenant, &baseline_name, baseline.to_json()).await,
         }
-        Err(e) => Err(format!("Detection error: {}", e)),
     }
-}
 
-async fn get_recommendations(
-    State(state): State<AppState>,
-    Json(data): Json<MLInputData>,
-) -> Result<Json<MLOutputData>, String> {
-    tracing::info!("Recommendations request: {} projects", data.projects.len());
+    let shared_detector = anomaly_detector(&state, &tenant.0).await;
+    let mut detector = if dry_run {
+        // Обучается копия: детектор арендатора остается прежним
+        let copy = shared_detector.lock().await.clone();
+        std::sync::Arc::new(tokio::sync::Mutex::new(copy))
+            .lock_owned()
+            .await
+    } else {
+        shared_detector.lock_owned().await
+    };
 
-    let mut engine = state.recommendation_engine.lock().await;
-    let mut recommendations = engine.generate_recommendations(&data);
+    // В режиме скользящего окна (`ANOMALY_WINDOW`) каждый запрос пополняет окно
+    // и перестраивает часть деревьев; иначе детектор обучается заново
+    let rolling = detector.window_len().is_some();
+    let (detector, entries) = if rolling || entries.len() >= MIN_TRAINING_ENTRIES {
+        let job = state.jobs.start(
+            "anomaly_detection",
+            &tenant.0,
+            requested_job_id(&data).as_deref(),
+        )?;
+        let _stored_job = StoredJob::save(&state, &job).await;
+        let token = job.token();
+        let report = job.progress_reporter();
+        let features = state.anomaly_features.clone();
+        let tenant_id = tenant.0.clone();
+        let (detector, entries, train_result, rebuilt, elapsed) =
+            tokio::task::spawn_blocking(move || {
+                let started = std::time::Instant::now();
+                let (result, rebuilt) = if rolling {
+                    match detector.update_window(&entries, &token, &report) {
+                        Ok(rebuilt) => (Ok(()), rebuilt),
+                        Err(e) => (Err(e), 0),
+                    }
+                } else {
+                    let features = features.features(&tenant_id, &entries);
+                    let result = detector.train_on_features(&features, &token, &report);
+                    (result, 1)
+                };
+                (detector, entries, result, rebuilt, started.elapsed())
+            })
+            .await
+            .map_err(|e| format!("Training task failed: {}", e))?;
+        state.usage.record_training(&tenant.0, elapsed);
+        job.finish(job_status(&train_result));
+        // Окно без новых записей или пока мало для обучения: модель не менялась
+        let unchanged = train_result.is_ok() && rebuilt == 0;
+        if !dry_run && !unchanged {
+            publish_training(
+                &state,
+                &tenant,
+                "anomaly",
+                &train_result,
+                detector.model_info(),
+            );
+        }
 
-    let confidence_threshold = data
-        .options
-        .as_ref()
-        .and_then(|o| o.get("confidence_threshold"))
-        .and_then(|v| v.as_f64())
-        .unwrap_or(0.0);
+        match (train_result, dry_run_report.as_mut()) {
+            (Err(e), _) if e == TRAINING_CANCELLED => return Err(e),
+            (Err(e), Some(report)) => report.training_error = Some(e),
+            (Err(e), None) => tracing::warn!("Training failed: {}", e),
+            (Ok(()), _) if unchanged => {}
+            (Ok(()), Some(report)) => report.skip(&["train anomaly", "store anomaly"]),
+            (Ok(()), None) => {
+                let name = tenants::model_name("anomaly", &tenant.0);
+                persist_model(&state, &tenant, &name, detector.to_json()).await
+            }
+        }
+        (detector, entries)
+    } else {
+        (detector, entries)
+    };
+
+    // Детектор без модели и записей для обучения: аномалии только по правилам
+    let insufficient = (data.settings.feature_enabled("insufficient_data", false)
+        && !rolling
+        && detector.model_info().is_none())
+    .then(|| InsufficientData::check("anomalies", "entries", MIN_TRAINING_ENTRIES, entries.len()))
+    .flatten();
+    let (entries, mode) = match evaluation {
+        Some(window) => (window, ScoringMode::Absolute),
+        None => (entries, ScoringMode::Batch),
+    };
+    // Признаки тех же записей повторного запроса берутся из кэша
+    let features = state.anomaly_features.features(&tenant.0, &entries);
+    let mut anomalies =
+        match detector.detect_with_features(&entries, &features, &threshold_shifts, mode) {
+            Ok(anomalies) => anomalies,
+            Err(_) if insufficient.is_some() => Vec::new(),
+            // Без обученного детектора небольшой запрос оценивается только по профилю;
+            // в режиме окна детектор обучится, когда окно наберет записи
+            Err(_)
+                if detector.model_info().is_none()
+                    && (rolling || baseline.entries_seen() >= baseline::MIN_BASELINE_SAMPLES) =>
+            {
+                Vec::new()
+            }
+            Err(e) => return Err(format!("Detection error: {}", e)),
+        };
+
+    // Профиль дополняет записи, которые детектор не отметил
+    let flagged: std::collections::HashSet<i32> = anomalies.iter().map(|a| a.entry_id).collect();
+    anomalies.extend(
+        baseline_anomalies
+            .into_iter()
+            .filter(|a| !flagged.contains(&a.entry_id)),
+    );
+    anomalies.extend(week_anomalies);
+    if let Some(model) = &severity_model {
+        for anomaly in &mut anomalies {
+            anomaly.severity = model.severity(&anomaly.r#type, anomaly.score).to_string();
+        }
+    }
+    anomalies.extend(invalid);
+    if confidence_threshold > 0.0 {
+        anomalies.retain(|a| a.score >= confidence_threshold);
+    }
+    kimai_ml::models::anomaly_context::add_context(&mut anomalies, &data.timesheets);
+    if !dry_run && !anomalies.is_empty() {
+        state.events.publish(
+            &tenant.0,
+            EventKind::AnomaliesDetected {
+                anomalies: anomalies.clone(),
+            },
+        );
+    }
+    let summary = filter.summary(&anomalies, &data.timesheets);
+    let (anomalies, page) = filter.apply(anomalies, &data.timesheets);
+    Ok(Json(MLOutputData {
+        forecasting: None,
+        anomalies: Some(anomalies),
+        recommendations: None,
+        productivity: None,
+        anomaly_model_info: detector.model_info(),
+        dry_run: dry_run_report,
+        anomaly_page: Some(page),
+        anomaly_summary: Some(summary),
+        resolved_recommendations: None,
+        action_plan: None,
+        segments: None,
+        insufficient_data: insufficient.map(|gap| vec![gap]),
+    }))
+}
+
+/// Состояние арендатора (профиль, трекер рекомендаций) из хранилища моделей;
+/// пустое, если его нет или оно несовместимо
+async fn load_tenant_state<T: Default>(
+    state: &AppState,
+    name: &str,
+    from_json: fn(&str) -> Result<T, String>,
+) -> T {
+    match state.storage.load_model(name).await {
+        Ok(Some(json)) => from_json(&json).unwrap_or_else(|e| {
+            tracing::warn!("Stored {} is not usable: {}", name, e);
+            T::default()
+        }),
+        Ok(None) => T::default(),
+        Err(e) => {
+            tracing::warn!("Failed to load {}: {}", name, e);
+            T::default()
+        }
+    }
+}
+
+/// Формат ответа рекомендаций: `ics` - только план действий в iCalendar
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PlanFormat {
+    #[default]
+    Json,
+    Ics,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecommendationParams {
+    #[serde(default)]
+    format: PlanFormat,
+}
+
+/// Рекомендации; `?format=ics` - план действий недели как календарь, шаги
+/// разложены по рабочим часам пользователя
+async fn recommendations(
+    state: State<AppState>,
+    tenant: Tenant,
+    Query(params): Query<RecommendationParams>,
+    AnalysisInput(mut data): AnalysisInput,
+) -> Result<Response, String> {
+    if params.format == PlanFormat::Json {
+        return get_recommendations(state, tenant, AnalysisInput(data))
+            .await
+            .map(IntoResponse::into_response);
+    }
+
+    data.settings
+        .features
+        .insert("action_plan".to_string(), serde_json::Value::Bool(true));
+    let hours = kimai_ml::ProductivityAnalyzer::with_preferences(
+        data.settings.user_preferences.clone(),
+        data.settings.analyzer.clone(),
+    )
+    .analyze(&data.timesheets)
+    .optimal_work_hours;
+    let Json(output) = get_recommendations(state, tenant, AnalysisInput(data)).await?;
+    let plan = output.action_plan.unwrap_or_default();
+    let calendar = kimai_ml::calendar::action_plan_ics(&plan, &hours)?;
+    let disposition = format!(
+        "attachment; filename=\"action-plan-{}.ics\"",
+        plan.period.as_deref().unwrap_or("week")
+    );
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                kimai_ml::calendar::ICS_CONTENT_TYPE.to_string(),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        calendar,
+    )
+        .into_response())
+}
+
+async fn get_recommendations(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    AnalysisInput(mut data): AnalysisInput,
+) -> Result<Json<MLOutputData>, String> {
+    data.exclude_projects(ProjectUsage::Analysis);
+    tracing::info!("Recommendations request: {} projects", data.projects.len());
+
+    let mut recommendations = state
+        .recommendation_engine
+        .lock()
+        .await
+        .generate_recommendations(&data);
+
+    // Новые, открытые и решенные с прошлого запроса рекомендации арендатора
+    let tracker_name = RecommendationTracker::storage_name(&tenant.0);
+    let mut tracker: RecommendationTracker =
+        load_tenant_state(&state, &tracker_name, RecommendationTracker::from_json).await;
+    let resolved = tracker.track(&mut recommendations, chrono::Utc::now());
+    let mut dry_run_report = dry_run_report(is_dry_run(&data), data.projects.len());
+    match dry_run_report.as_mut() {
+        Some(report) => report.skip(&["store recommendation tracking"]),
+        None => persist_model(&state, &tenant, &tracker_name, tracker.to_json()).await,
+    }
+
+    // Без отклоненных и отложенных; уверенность - с поправкой на отзывы по типам
+    let mut recommendations = recommendation_history(&state, &tenant)
+        .await
+        .apply(recommendations, chrono::Utc::now());
+
+    let confidence_threshold = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("confidence_threshold"))
+        .and_then(|v| v.as_f64())
+        .unwrap_or(0.0);
 
     if confidence_threshold > 0.0 {
         recommendations.retain(|r| r.confidence >= confidence_threshold);
     }
 
+    // Рекомендации сегментов не отслеживаются: решенными считаются только общие
+    let segments = kimai_ml::segments::segment_inputs(&data);
+    let segments = if segments.is_empty() {
+        None
+    } else {
+        let history = recommendation_history(&state, &tenant).await;
+        let mut engine = state.recommendation_engine.lock().await;
+        Some(
+            segments
+                .into_iter()
+                .map(|(tag, segment)| {
+                    let mut recommendations = history.apply(
+                        engine.generate_recommendations(&segment),
+                        chrono::Utc::now(),
+                    );
+                    recommendations.retain(|r| r.confidence >= confidence_threshold);
+                    SegmentOutput {
+                        tag,
+                        entries: segment.timesheets.len(),
+                        forecasting: None,
+                        recommendations: Some(recommendations),
+                        productivity: None,
+                        insufficient_data: None,
+                    }
+                })
+                .collect(),
+        )
+    };
+
+    let action_plan = if data.settings.feature_enabled("action_plan", true) {
+        let (capacity_hours, capacity_source) = plan_capacity(&state, &tenant, &data).await;
+        Some(kimai_ml::models::recommendations::action_plan(
+            &recommendations,
+            &data,
+            capacity_hours,
+            capacity_source,
+        ))
+    } else {
+        None
+    };
+
     Ok(Json(MLOutputData {
         forecasting: None,
         anomalies: None,
         recommendations: Some(recommendations),
         productivity: None,
+        anomaly_model_info: None,
+        dry_run: dry_run_report,
+        anomaly_page: None,
+        anomaly_summary: None,
+        resolved_recommendations: Some(resolved),
+        action_plan,
+        segments,
+        insufficient_data: None,
     }))
 }
 
+/// Прогнозы сегментов `settings.segment_by_tags`. Модель обучается на каждом
+/// сегменте заново и не сохраняется; поправки обучения на ошибках не применяются
+async fn forecast_segments(
+    state: &AppState,
+    tenant: &Tenant,
+    data: &MLInputData,
+) -> Result<Option<Vec<SegmentOutput>>, String> {
+    let segments = kimai_ml::segments::segment_inputs(data);
+    if segments.is_empty() {
+        return Ok(None);
+    }
+    let gated = data.settings.feature_enabled("insufficient_data", false);
+    let bounds = ForecastBounds::from_options(data.options.as_ref())?;
+    let (segments, elapsed) = tokio::task::spawn_blocking(move || {
+        let started = std::time::Instant::now();
+        let segments = segments
+            .into_iter()
+            .map(|(tag, segment)| {
+                let entries = segment.timesheets.len();
+                if let Some(gap) = InsufficientData::forecasting(segment.weeks.len()) {
+                    if gated {
+                        return Ok(SegmentOutput {
+                            tag,
+                            entries,
+                            forecasting: None,
+                            recommendations: None,
+                            productivity: None,
+                            insufficient_data: Some(vec![gap]),
+                        });
+                    }
+                }
+                let mut model = ForecastingModel::new();
+                if DataSufficiency::from_weeks(segment.weeks.len()) == DataSufficiency::Full {
+                    model.train_with_options(&segment.weeks, segment.options.as_ref())?;
+                }
+                let mut forecast = model.predict(&segment.weeks)?;
+                bounds.apply(&mut forecast);
+                Ok(SegmentOutput {
+                    tag,
+                    entries,
+                    forecasting: Some(forecast),
+                    recommendations: None,
+                    productivity: None,
+                    insufficient_data: None,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>();
+        (segments, started.elapsed())
+    })
+    .await
+    .map_err(|e| format!("Training task failed: {}", e))?;
+    state.usage.record_training(&tenant.0, elapsed);
+    segments.map(Some)
+}
+
+/// Сколько последних недель дают емкость плана без обученной модели прогноза
+const PLAN_HISTORY_WEEKS: usize = 4;
+
+/// Рабочие часы следующей недели для плана действий: прогноз обученной модели
+/// или среднее последних недель
+async fn plan_capacity(
+    state: &AppState,
+    tenant: &Tenant,
+    data: &MLInputData,
+) -> (f64, &'static str) {
+    if let Some(hours) = kimai_ml::models::contract::contracted_hours(&data.settings) {
+        return (hours, "contract");
+    }
+    let mut data = data.clone();
+    data.exclude_projects(ProjectUsage::Forecasting);
+    let forecast = forecasting_model(state, &tenant.0)
+        .await
+        .lock()
+        .await
+        .predict(&data.weeks);
+    match forecast {
+        Ok(forecast) if forecast.weekly_hours.is_finite() => (forecast.weekly_hours, "forecast"),
+        _ => {
+            let recent = &data.weeks[data.weeks.len().saturating_sub(PLAN_HISTORY_WEEKS)..];
+            let hours = if recent.is_empty() {
+                0.0
+            } else {
+                recent.iter().map(|w| w.total_hours).sum::<f64>() / recent.len() as f64
+            };
+            (hours, "history")
+        }
+    }
+}
+
+/// Столько последних отзывов арендатора о рекомендациях учитывается
+const MAX_RECOMMENDATION_FEEDBACK: usize = 1000;
+
+async fn recommendation_history(state: &AppState, tenant: &Tenant) -> RecommendationHistory {
+    match state
+        .storage
+        .recommendation_feedback(&tenant.0, MAX_RECOMMENDATION_FEEDBACK)
+        .await
+    {
+        Ok(feedback) => RecommendationHistory::from_feedback(&feedback),
+        Err(e) => {
+            tracing::warn!("Failed to load recommendation feedback: {}", e);
+            RecommendationHistory::default()
+        }
+    }
+}
+
+/// Отзыв о рекомендации: отклоненные больше не показываются, отложенные -
+/// до конца срока; доля принятых по типу меняет уверенность рекомендаций
+async fn recommendation_feedback(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(mut feedback): Json<RecommendationFeedback>,
+) -> Result<Json<RecommendationTypeStats>, (StatusCode, String)> {
+    tracing::info!(
+        "Recommendation feedback: {} {} ({})",
+        feedback.recommendation_id,
+        feedback.action.as_str(),
+        feedback.recommendation_type
+    );
+    if feedback.recommendation_id.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "recommendation_id is required".to_string(),
+        ));
+    }
+
+    feedback.tenant = tenant.0.clone();
+    feedback.recorded_at = chrono::Utc::now().to_rfc3339();
+    state
+        .storage
+        .record_recommendation_feedback(&feedback)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    bump_output_revision(&state);
+    let history = recommendation_history(&state, &tenant).await;
+    Ok(Json(history.type_stats(&feedback.recommendation_type)))
+}
+
 async fn analyze_productivity(
     State(_state): State<AppState>,
-    Json(data): Json<MLInputData>,
+    AnalysisInput(mut data): AnalysisInput,
 ) -> Result<Json<MLOutputData>, String> {
+    data.exclude_projects(ProjectUsage::Analysis);
     tracing::info!(
         "Productivity analysis request: {} entries",
         data.timesheets.len()
@@ -380,6 +1687,7 @@ async fn analyze_productivity(
             week_of_year: e.week_of_year,
             month: e.month,
             year: e.year,
+            billable: e.billable,
         })
         .filter(|e| {
             if include_weekends {
@@ -392,27 +1700,512 @@ async fn analyze_productivity(
 
     // Создаем анализатор с предпочтениями пользователя
     let preferences = data.settings.user_preferences.clone();
-    let analyzer = kimai_ml::ProductivityAnalyzer::with_preferences(preferences);
-    let productivity = analyzer.analyze(&entries);
+    let project_rates = data
+        .settings
+        .project_settings
+        .iter()
+        .filter_map(|(id, s)| s.rate_per_minute.map(|rate| (*id, rate)))
+        .collect();
+    let analyzer = kimai_ml::ProductivityAnalyzer::with_preferences(
+        preferences,
+        data.settings.analyzer.clone(),
+    )
+    .meeting_patterns(data.settings.meeting_patterns.clone())
+    .billable_tags(data.settings.billable_tags.clone())
+    .rates(data.settings.rate_per_minute, project_rates);
+    let mut productivity = analyzer.analyze(&entries);
+    productivity.contract = kimai_ml::models::contract::utilization(&data.weeks, &data.settings);
+    kimai_ml::models::utilization::validate(&data.settings.utilization)?;
+    productivity.utilization = productivity
+        .billable
+        .as_ref()
+        .and_then(|billable| kimai_ml::models::utilization::analyze(billable, &data.settings));
+    productivity.profitability =
+        kimai_ml::models::unit_economics::profitability(&data.weeks, &data.settings);
+
+    let segments = kimai_ml::segments::segment_inputs(&data);
+    let segments = (!segments.is_empty()).then(|| {
+        segments
+            .into_iter()
+            .map(|(tag, segment)| {
+                let entries: Vec<_> = segment
+                    .timesheets
+                    .into_iter()
+                    .filter(|e| include_weekends || !(e.day_of_week == 0 || e.day_of_week == 6))
+                    .collect();
+                SegmentOutput {
+                    tag,
+                    entries: entries.len(),
+                    forecasting: None,
+                    recommendations: None,
+                    productivity: (!entries.is_empty()).then(|| analyzer.analyze(&entries)),
+                    insufficient_data: None,
+                }
+            })
+            .collect()
+    });
 
     Ok(Json(MLOutputData {
         forecasting: None,
         anomalies: None,
         recommendations: None,
         productivity: Some(productivity),
+        anomaly_model_info: None,
+        dry_run: dry_run_report(is_dry_run(&data), entries.len()),
+        anomaly_page: None,
+        anomaly_summary: None,
+        resolved_recommendations: None,
+        action_plan: None,
+        segments,
+        insufficient_data: None,
+    }))
+}
+
+/// Недель истории демонстрации по умолчанию и наибольшее
+const DEMO_WEEKS: usize = 12;
+const DEMO_MAX_WEEKS: usize = 104;
+
+/// Зерно демонстрационных данных по умолчанию
+const DEMO_SEED: u64 = 1;
+
+#[derive(Debug, Deserialize)]
+struct DemoParams {
+    profile: Option<Profile>,
+    weeks: Option<usize>,
+    seed: Option<u64>,
+}
+
+/// Синтетические данные демонстрации и их полный анализ
+#[derive(Debug, Serialize)]
+struct DemoOutput {
+    profile: Profile,
+    seed: u64,
+    data: MLInputData,
+    analysis: MLOutputData,
+}
+
+/// Полный анализ (прогноз, аномалии, рекомендации, продуктивность) синтетических
+/// данных профиля за `weeks` недель, заканчивающихся текущей. Модели обучаются
+/// заново в отдельном состоянии: модели сервера и состояние арендаторов не меняются
+async fn demo_analysis(
+    State(state): State<AppState>,
+    Query(params): Query<DemoParams>,
+) -> Result<Json<DemoOutput>, (StatusCode, String)> {
+    let profile = params.profile.unwrap_or(Profile::SeasonalFreelancer);
+    let weeks = params.weeks.unwrap_or(DEMO_WEEKS);
+    if weeks == 0 || weeks > DEMO_MAX_WEEKS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("weeks must be between 1 and {}", DEMO_MAX_WEEKS),
+        ));
+    }
+    let seed = params.seed.unwrap_or(DEMO_SEED);
+    let start = chrono::Utc::now().date_naive() - chrono::Duration::weeks(weeks as i64 - 1);
+    let data = synthetic::generate_from(profile, weeks, seed, start).input;
+
+    let key = cache_key("demo", &(profile, weeks, seed, start)).ok();
+    if let Some(analysis) = cached_output(&state, key.as_deref()).await {
+        return Ok(Json(DemoOutput {
+            profile,
+            seed,
+            data,
+            analysis,
+        }));
+    }
+
+    let demo = new_state(
+        std::sync::Arc::new(MemoryStorage::new()),
+        std::sync::Arc::new(MemoryCache::new(std::time::Duration::ZERO, 0)),
+        std::sync::Arc::new(MemoryAuditLog::new(0)),
+    );
+    let tenant = Tenant(DEFAULT_TENANT.to_string());
+    let failed = |e: String| (StatusCode::INTERNAL_SERVER_ERROR, e);
+    let Json(forecast) = predict(
+        State(demo.clone()),
+        tenant.clone(),
+        WeeklyInput(data.clone()),
+    )
+    .await
+    .map_err(failed)?;
+    let Json(anomalies) = detect_anomalies(
+        State(demo.clone()),
+        tenant.clone(),
+        AnalysisInput(data.clone()),
+    )
+    .await
+    .map_err(failed)?;
+    let Json(recommendations) =
+        get_recommendations(State(demo.clone()), tenant, AnalysisInput(data.clone()))
+            .await
+            .map_err(failed)?;
+    let Json(productivity) = analyze_productivity(State(demo), AnalysisInput(data.clone()))
+        .await
+        .map_err(failed)?;
+
+    let analysis = MLOutputData {
+        forecasting: forecast.forecasting,
+        anomalies: anomalies.anomalies,
+        recommendations: recommendations.recommendations,
+        productivity: productivity.productivity,
+        anomaly_model_info: anomalies.anomaly_model_info,
+        dry_run: None,
+        anomaly_page: anomalies.anomaly_page,
+        anomaly_summary: anomalies.anomaly_summary,
+        resolved_recommendations: None,
+        action_plan: recommendations.action_plan,
+        segments: None,
+        insufficient_data: forecast.insufficient_data,
+    };
+    store_output(&state, key.as_deref(), &analysis).await;
+    Ok(Json(DemoOutput {
+        profile,
+        seed,
+        data,
+        analysis,
     }))
 }
 
+/// Автокорреляция недельных часов и найденные сезонные периоды;
+/// `options.max_lag` ограничивает лаг (26 недель по умолчанию)
+async fn seasonality_diagnostics(
+    WeeklyInput(mut data): WeeklyInput,
+) -> Result<Json<SeasonalityDiagnostics>, (StatusCode, String)> {
+    data.exclude_projects(ProjectUsage::Forecasting);
+    tracing::info!(
+        "Seasonality diagnostics request: {} weeks",
+        data.weeks.len()
+    );
+
+    let max_lag = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("max_lag"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize);
+    kimai_ml::models::seasonality::diagnose(&data.weeks, max_lag)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Мотивы и диссонансы матричного профиля часов по дням; `options.window`
+/// (7 дней) и `options.top` (3) задают длину окна и число результатов
+async fn pattern_diagnostics(
+    AnalysisInput(mut data): AnalysisInput,
+) -> Result<Json<PatternDiagnostics>, (StatusCode, String)> {
+    data.exclude_projects(ProjectUsage::Analysis);
+    tracing::info!(
+        "Pattern diagnostics request: {} entries",
+        data.timesheets.len()
+    );
+
+    let option = |name: &str| {
+        data.options
+            .as_ref()
+            .and_then(|o| o.get(name))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+    };
+    kimai_ml::models::matrix_profile::diagnose(&data.timesheets, option("window"), option("top"))
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Подробное объяснение записи `options.entry_id` из `timesheets`: текущие
+/// детектор и профиль арендатора только оценивают записи, без обучения
+async fn explain_entry(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    AnalysisInput(mut data): AnalysisInput,
+) -> Result<Json<EntryExplanation>, (StatusCode, String)> {
+    data.exclude_projects(ProjectUsage::Anomalies);
+    let entry_id = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("entry_id"))
+        .and_then(|v| v.as_i64())
+        .and_then(|v| i32::try_from(v).ok())
+        .ok_or((
+            StatusCode::BAD_REQUEST,
+            "options.entry_id is required".to_string(),
+        ))?;
+    tracing::info!(
+        "Explain entry request: entry {} of {} entries",
+        entry_id,
+        data.timesheets.len()
+    );
+
+    let threshold_shifts = sync_learning(&state, &tenant.0).await.threshold_shifts();
+    let baseline: BaselineProfile = load_tenant_state(
+        &state,
+        &BaselineProfile::storage_name(&tenant.0),
+        BaselineProfile::from_json,
+    )
+    .await;
+    let detector = anomaly_detector(&state, &tenant.0).await;
+    let detector = detector.lock().await;
+    kimai_ml::models::entry_explanation::explain_entry(
+        entry_id,
+        &data.timesheets,
+        &detector,
+        &baseline,
+        &threshold_shifts,
+        chrono::Utc::now(),
+    )
+    .map(Json)
+    .map_err(|e| (StatusCode::NOT_FOUND, e))
+}
+
+/// Когорты команды по ключу метаданных `options.cohort_by` (`role`) со скрытием
+/// когорт меньше `options.min_cohort_size` (3)
+async fn team_cohorts(
+    TeamBody(team): TeamBody,
+) -> Result<Json<CohortAnalysis>, (StatusCode, String)> {
+    tracing::info!("Team cohorts request: {} members", team.members.len());
+
+    let options = team.options.as_ref();
+    let cohort_by = options
+        .and_then(|o| o.get("cohort_by"))
+        .and_then(|v| v.as_str())
+        .unwrap_or(kimai_ml::models::team::DEFAULT_COHORT_KEY)
+        .to_string();
+    let min_cohort_size = match options.and_then(|o| o.get("min_cohort_size")) {
+        Some(value) => value.as_u64().filter(|size| *size >= 1).ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid min_cohort_size: {}", value),
+            )
+        })? as usize,
+        None => kimai_ml::models::team::DEFAULT_MIN_COHORT_SIZE,
+    };
+    Ok(Json(kimai_ml::models::team::cohort_analysis(
+        &team,
+        &cohort_by,
+        min_cohort_size,
+    )))
+}
+
+/// Переносы часов проектов от перегруженных участников к недогруженным; с
+/// `options.allow_new_projects` - и участникам, не работавшим над проектом.
+/// Прогнозы участников обучаются вне потоков запросов, время - за арендатором
+async fn team_balance(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    TeamBody(team): TeamBody,
+) -> Result<Json<TeamBalance>, (StatusCode, String)> {
+    tracing::info!("Team balance request: {} members", team.members.len());
+
+    let allow_new_projects = team
+        .options
+        .as_ref()
+        .and_then(|o| o.get("allow_new_projects"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    let (balance, elapsed) = tokio::task::spawn_blocking(move || {
+        let started = std::time::Instant::now();
+        let balance =
+            kimai_ml::models::workload_balance::balance_workload(&team, allow_new_projects);
+        (balance, started.elapsed())
+    })
+    .await
+    .map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Training task failed: {}", e),
+        )
+    })?;
+    state.usage.record_training(&tenant.0, elapsed);
+    Ok(Json(balance))
+}
+
+/// Разрыв обязательств `options.commitments` (`{"<project_id>": <часов в неделю>}`)
+/// и емкости команды на `options.horizon_weeks` (12) недель
+async fn team_capacity_gap(
+    TeamBody(team): TeamBody,
+) -> Result<Json<CapacityGap>, (StatusCode, String)> {
+    use kimai_ml::models::capacity_gap::{DEFAULT_HORIZON_WEEKS, MAX_HORIZON_WEEKS};
+    tracing::info!("Team capacity gap request: {} members", team.members.len());
+
+    let options = team.options.as_ref();
+    let commitments: std::collections::HashMap<i32, f64> = options
+        .and_then(|o| o.get("commitments"))
+        .cloned()
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid commitments: {}", e),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "options.commitments is required".to_string(),
+            )
+        })?;
+    if let Some((project_id, hours)) = commitments
+        .iter()
+        .find(|(_, &hours)| !hours.is_finite() || hours < 0.0)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid commitment {} for project {}", hours, project_id),
+        ));
+    }
+    let horizon_weeks = match options.and_then(|o| o.get("horizon_weeks")) {
+        Some(value) => value
+            .as_u64()
+            .map(|weeks| weeks as usize)
+            .filter(|weeks| (1..=MAX_HORIZON_WEEKS).contains(weeks))
+            .ok_or_else(|| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid horizon_weeks: {}", value),
+                )
+            })?,
+        None => DEFAULT_HORIZON_WEEKS,
+    };
+    Ok(Json(kimai_ml::models::capacity_gap::capacity_gap(
+        &team,
+        &commitments,
+        horizon_weeks,
+    )))
+}
+
+/// Загрузка оплачиваемой работой участников и команды; пороги команды -
+/// `options.utilization` (`{"low": 0.6, "high": 0.9}`)
+async fn team_utilization(
+    TeamBody(team): TeamBody,
+) -> Result<Json<TeamUtilization>, (StatusCode, String)> {
+    tracing::info!("Team utilization request: {} members", team.members.len());
+
+    let thresholds: UtilizationThresholds = team
+        .options
+        .as_ref()
+        .and_then(|o| o.get("utilization"))
+        .cloned()
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid utilization thresholds: {}", e),
+            )
+        })?
+        .unwrap_or_default();
+    kimai_ml::models::utilization::validate(&thresholds)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(kimai_ml::models::utilization::team_utilization(
+        &team, thresholds,
+    )))
+}
+
+/// Кривая выживаемости проектов и вероятность засыпания активных проектов
+/// в ближайшие `options.horizon_weeks` (4) недель
+async fn project_lifetime_diagnostics(WeeklyInput(data): WeeklyInput) -> Json<ProjectLifetime> {
+    tracing::info!("Project lifetime request: {} weeks", data.weeks.len());
+
+    let horizon = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("horizon_weeks"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+        .unwrap_or(kimai_ml::models::survival::DEFAULT_DORMANCY_HORIZON);
+    Json(kimai_ml::models::survival::project_lifetime(
+        &data.weeks,
+        horizon,
+    ))
+}
+
+/// Сравнение двух недельных распределений часов `options.schedules`
+/// (`[{"name": ..., "allocations": {"<project_id>": <часы>}}, ...]`) по истории `weeks`
+async fn compare_schedules(
+    WeeklyInput(mut data): WeeklyInput,
+) -> Result<Json<ScheduleComparison>, (StatusCode, String)> {
+    data.exclude_projects(ProjectUsage::Forecasting);
+    tracing::info!("Schedule comparison request: {} weeks", data.weeks.len());
+
+    let schedules: Vec<ProposedSchedule> = data
+        .options
+        .as_ref()
+        .and_then(|o| o.get("schedules"))
+        .cloned()
+        .map(serde_json::from_value)
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid schedules: {}", e)))?
+        .unwrap_or_default();
+    let [first, second] = schedules.as_slice() else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "options.schedules must hold exactly 2 schedules, got {}",
+                schedules.len()
+            ),
+        ));
+    };
+    for schedule in [first, second] {
+        kimai_ml::models::schedule::validate(schedule).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    let simulator = kimai_ml::models::schedule::ScheduleSimulator::new(&data.weeks, &data.settings);
+    Ok(Json(simulator.compare(first, second)))
+}
+
+/// Влияние отсутствия `options.absence` (`{"from", "to"}`) на часы и цели
+/// проектов и что отработать заранее за `options.preparation_weeks` (2) недель
+async fn simulate_absence(
+    WeeklyInput(mut data): WeeklyInput,
+) -> Result<Json<AbsenceImpact>, (StatusCode, String)> {
+    data.exclude_projects(ProjectUsage::Forecasting);
+    tracing::info!("Absence simulation request: {} weeks", data.weeks.len());
+
+    let options = data.options.as_ref();
+    let absence: PlannedAbsence = options
+        .and_then(|o| o.get("absence"))
+        .cloned()
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "options.absence is required".to_string(),
+            )
+        })
+        .and_then(|v| {
+            serde_json::from_value(v)
+                .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid absence: {}", e)))
+        })?;
+    let preparation_weeks = options
+        .and_then(|o| o.get("preparation_weeks"))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+        .unwrap_or(kimai_ml::models::absence::DEFAULT_PREPARATION_WEEKS);
+    kimai_ml::models::absence::simulate_absence(&data, &absence, preparation_weeks)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
 #[derive(Debug, Deserialize)]
 struct LearnRequest {
     prediction_type: String,
     predicted_value: f64,
     actual_value: f64,
     context: Option<serde_json::Value>,
+    /// На сколько недель вперед был прогноз (1 по умолчанию)
+    #[serde(default)]
+    horizon: Option<u32>,
+    /// Уверенность, с которой был выдан прогноз
+    #[serde(default)]
+    confidence: Option<f64>,
+    /// Только вычислить новые корректировки, не записывая ошибку
+    #[serde(default)]
+    dry_run: bool,
+    /// Неделя прогноза (`YYYY-Www`): `actual_value` - ее факт в журнале прогнозов
+    #[serde(default)]
+    period: Option<String>,
 }
 
 async fn learn_from_error(
     State(_state): State<AppState>,
+    tenant: Tenant,
     Json(req): Json<LearnRequest>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     tracing::info!(
@@ -422,23 +2215,2292 @@ async fn learn_from_error(
         req.actual_value
     );
 
-    let error = req.predicted_value - req.actual_value;
-
-    let mut learning = _state.learning_module.lock().await;
-    learning.record_error(kimai_ml::PredictionError {
+    let horizon = req
+        .horizon
+        .unwrap_or(kimai_ml::models::learning::DEFAULT_HORIZON);
+    let error = kimai_ml::PredictionError {
         prediction_type: req.prediction_type.clone(),
         predicted_value: req.predicted_value,
         actual_value: req.actual_value,
-        error,
+        error: req.predicted_value - req.actual_value,
         context: req.context.unwrap_or(serde_json::json!({})),
-    });
+        horizon,
+        confidence: req.confidence,
+        recorded_at: Some(chrono::Utc::now().to_rfc3339()),
+        tenant: tenant.0.clone(),
+    };
 
-    let correction_factor = learning.get_correction_factor(&req.prediction_type);
-    let confidence_adjustment = learning.get_confidence_adjustment(&req.prediction_type);
+    if req.dry_run {
+        // Корректировки считаются на копии модуля обучения
+        let mut learning = sync_learning(&_state, &tenant.0).await.clone();
+        let current_factor =
+            learning.get_correction_factor_for_horizon(&req.prediction_type, horizon);
+        learning.record_error(error);
+        return Ok(Json(serde_json::json!({
+            "status": "dry_run",
+            "prediction_type": req.prediction_type,
+            "horizon": horizon,
+            "correction_factor": learning.get_correction_factor_for_horizon(&req.prediction_type, horizon),
+            "confidence_adjustment": learning.get_confidence_adjustment_for_horizon(&req.prediction_type, horizon),
+            "current_correction_factor": current_factor,
+            "skipped": ["record learning error", "clear prediction cache"],
+        })));
+    }
+
+    let (previous_factor, retrain_pending) = {
+        let learning = sync_learning(&_state, &tenant.0).await;
+        (
+            learning.get_correction_factor_for_horizon(&req.prediction_type, horizon),
+            learning
+                .retrain_signal(&req.prediction_type, horizon)
+                .is_some(),
+        )
+    };
+
+    // Ошибка записывается в общее хранилище, чтобы ее учли все реплики
+    _state
+        .storage
+        .record_learning_error(&error)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if let Some(period) = req
+        .period
+        .as_deref()
+        .filter(|_| req.prediction_type == "forecasting")
+    {
+        let name = ForecastLedger::storage_name(&tenant.0);
+        let mut ledger: ForecastLedger =
+            load_tenant_state(&_state, &name, ForecastLedger::from_json).await;
+        if ledger.record_actual(period, req.actual_value, "learn") {
+            persist_model(&_state, &tenant, &name, ledger.to_json()).await;
+        }
+    }
+    // Корректировки изменились — кэшированные прогнозы арендатора устарели
+    if let Err(e) = _state.cache.clear_tenant(&tenant.0).await {
+        tracing::warn!("Failed to clear prediction cache: {}", e);
+    }
+    bump_output_revision(&_state);
+    let learning = sync_learning(&_state, &tenant.0).await;
+
+    let correction_factor =
+        learning.get_correction_factor_for_horizon(&req.prediction_type, horizon);
+    let confidence_adjustment =
+        learning.get_confidence_adjustment_for_horizon(&req.prediction_type, horizon);
+
+    if is_drifted(correction_factor) && !is_drifted(previous_factor) {
+        tracing::warn!(
+            "Drift of {} predictions: correction factor {:.3}",
+            req.prediction_type,
+            correction_factor
+        );
+        _state.events.publish(
+            &tenant.0,
+            EventKind::DriftAlert {
+                prediction_type: req.prediction_type.clone(),
+                horizon,
+                correction_factor,
+                previous_correction_factor: previous_factor,
+            },
+        );
+    }
+
+    // Сигнал переобучения срабатывает один раз, пока ошибки не вернутся в норму
+    let mut retrain_job = None;
+    if let Some(signal) = learning
+        .retrain_signal(&req.prediction_type, horizon)
+        .filter(|_| !retrain_pending)
+    {
+        tracing::warn!(
+            "{} predictions degraded: rolling MAPE {:.3}, retraining",
+            signal.prediction_type,
+            signal.rolling_mape
+        );
+        retrain_job = start_retrain(&_state, &tenant, &signal).await;
+        _state.events.publish(
+            &tenant.0,
+            EventKind::RetrainTriggered {
+                prediction_type: signal.prediction_type,
+                horizon,
+                rolling_mape: signal.rolling_mape,
+                job_id: retrain_job.clone(),
+            },
+        );
+    }
 
     Ok(Json(serde_json::json!({
         "status": "recorded",
+        "prediction_type": req.prediction_type,
+        "horizon": horizon,
         "correction_factor": correction_factor,
         "confidence_adjustment": confidence_adjustment,
+        "retrain_job": retrain_job,
     })))
 }
+
+/// Переобучение модели по сигналу модуля обучения в фоновой задаче
+/// (`retrain_forecasting` в `/api/jobs`); `None`, если переобучать нечего
+async fn start_retrain(
+    state: &AppState,
+    tenant: &Tenant,
+    signal: &RetrainSignal,
+) -> Option<String> {
+    if signal.prediction_type != "forecasting" {
+        return None;
+    }
+    let retrain_input = state
+        .retrain_inputs
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&tenant.0)
+        .cloned();
+    let Some(TrainingInput { weeks, options }) = retrain_input else {
+        tracing::warn!("No training data to retrain forecasting on");
+        return None;
+    };
+    let job = match state.jobs.start("retrain_forecasting", &tenant.0, None) {
+        Ok(job) => job,
+        Err(e) => {
+            tracing::warn!("Failed to start retraining: {}", e);
+            return None;
+        }
+    };
+    let job_id = job.id().to_string();
+
+    let state = state.clone();
+    let tenant = tenant.clone();
+    tokio::spawn(async move {
+        let _stored_job = StoredJob::save(&state, &job).await;
+        let token = job.token();
+        let report = job.progress_reporter();
+        let mut model = forecasting_model(&state, &tenant.0)
+            .await
+            .lock_owned()
+            .await;
+        let result = tokio::task::spawn_blocking(move || {
+            let started = std::time::Instant::now();
+            let result = model.train_with_progress(&weeks, options.as_ref(), &token, &report);
+            (model, result, started.elapsed())
+        })
+        .await;
+        let (model, train_result) = match result {
+            Ok((model, train_result, elapsed)) => {
+                state.usage.record_training(&tenant.0, elapsed);
+                (model, train_result)
+            }
+            Err(e) => {
+                tracing::warn!("Retraining task failed: {}", e);
+                job.finish("failed");
+                return;
+            }
+        };
+        job.finish(job_status(&train_result));
+        publish_training(
+            &state,
+            &tenant,
+            "forecasting",
+            &train_result,
+            model.model_info(),
+        );
+        match train_result {
+            Ok(()) => {
+                let name = tenants::model_name("forecasting", &tenant.0);
+                persist_model(&state, &tenant, &name, model.to_json()).await;
+                if let Err(e) = state.cache.clear_tenant(&tenant.0).await {
+                    tracing::warn!("Failed to clear prediction cache: {}", e);
+                }
+                bump_output_revision(&state);
+            }
+            Err(e) => tracing::warn!("Retraining failed: {}", e),
+        }
+    });
+    Some(job_id)
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok().and_then(|v| v.parse().ok())
+}
+
+/// Состояние обучения на ошибках по каждому типу прогноза и горизонту:
+/// поправки, число ошибок и диаграмма надежности уверенности
+async fn learning_stats(State(state): State<AppState>, tenant: Tenant) -> Json<serde_json::Value> {
+    let learning = sync_learning(&state, &tenant.0).await;
+    let stats: Vec<serde_json::Value> = learning
+        .tracked()
+        .into_iter()
+        .map(|(prediction_type, horizon)| {
+            serde_json::json!({
+                "prediction_type": prediction_type,
+                "horizon": horizon,
+                "errors": learning.error_count(&prediction_type, horizon),
+                "correction_factor":
+                    learning.get_correction_factor_for_horizon(&prediction_type, horizon),
+                "confidence_adjustment":
+                    learning.get_confidence_adjustment_for_horizon(&prediction_type, horizon),
+                "calibration": learning.calibration_curve(&prediction_type, horizon),
+            })
+        })
+        .collect();
+    let anomalies: Vec<ClassificationStats> = learning
+        .anomaly_types()
+        .iter()
+        .map(|anomaly_type| learning.classification_stats(anomaly_type))
+        .collect();
+    Json(serde_json::json!({
+        "accuracy_tolerance": kimai_ml::models::learning::ACCURACY_TOLERANCE,
+        "stats": stats,
+        "anomalies": anomalies,
+        "severity_model": learning.severity_model(),
+    }))
+}
+
+/// Отзыв о найденной или пропущенной аномалии: пополняет точность и полноту
+/// детектора по типу аномалии и сдвигает его порог для этого типа
+async fn anomaly_feedback(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Json(mut feedback): Json<AnomalyFeedback>,
+) -> Result<Json<ClassificationStats>, (StatusCode, String)> {
+    feedback.tenant = tenant.0.clone();
+    tracing::info!(
+        "Anomaly feedback: entry {} is_anomaly={}, detected={}",
+        feedback.entry_id,
+        feedback.is_anomaly,
+        feedback.detected
+    );
+
+    // Отзыв записывается в общее хранилище, чтобы пороги изменились на всех репликах
+    state
+        .storage
+        .record_anomaly_feedback(&feedback)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    bump_output_revision(&state);
+    let learning = sync_learning(&state, &tenant.0).await;
+    Ok(Json(learning.classification_stats(
+        &AnomalyVerdict::from(&feedback).anomaly_type,
+    )))
+}
+
+/// Допустимое отклонение корректирующего фактора от 1 (сам фактор ограничен
+/// 1 ± 0.2); при большем прогнозы систематически смещены и клиентам
+/// отправляется `drift_alert`
+const DRIFT_THRESHOLD: f64 = 0.15;
+
+fn is_drifted(correction_factor: f64) -> bool {
+    (correction_factor - 1.0).abs() > DRIFT_THRESHOLD
+}
+
+/// Пробный запрос (`options.dry_run`): конвейер выполняется полностью, но общие
+/// модели, хранилище и кэш не меняются
+fn is_dry_run(data: &MLInputData) -> bool {
+    data.options
+        .as_ref()
+        .and_then(|o| o.get("dry_run"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn dry_run_report(dry_run: bool, samples: usize) -> Option<DryRunReport> {
+    dry_run.then(|| DryRunReport {
+        samples,
+        ..DryRunReport::default()
+    })
+}
+
+/// Идентификатор задачи, заданный клиентом (`options.job_id`), для подписки на прогресс
+fn requested_job_id(data: &MLInputData) -> Option<String> {
+    data.options
+        .as_ref()
+        .and_then(|o| o.get("job_id"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+/// Хранилище из `DATABASE_URL` (feature `postgres`), иначе память процесса
+/// Подпись снимков моделей ключом `MODEL_SIGNING_KEY`; без ключа - только
+/// контрольные суммы
+fn open_signer() -> SnapshotSigner {
+    let key = std::env::var("MODEL_SIGNING_KEY").ok();
+    SnapshotSigner::new(key.as_deref().map(str::as_bytes))
+}
+
+/// Хранилище из `open_backend` с подписью снимков моделей (`open_signer`)
+async fn open_storage() -> std::sync::Arc<dyn Storage> {
+    let signer = open_signer();
+    if signer.has_key() {
+        tracing::info!("Model snapshots are signed with MODEL_SIGNING_KEY");
+    }
+    std::sync::Arc::new(SignedStorage::new(open_backend().await, signer))
+}
+
+async fn open_backend() -> std::sync::Arc<dyn Storage> {
+    match std::env::var("DATABASE_URL") {
+        #[cfg(feature = "postgres")]
+        Ok(url) => match kimai_ml::storage::PostgresStorage::connect(&url).await {
+            Ok(storage) => {
+                tracing::info!("Using PostgreSQL storage");
+                std::sync::Arc::new(storage)
+            }
+            Err(e) => panic!("Failed to open storage: {}", e),
+        },
+        #[cfg(not(feature = "postgres"))]
+        Ok(_) => {
+            tracing::warn!("DATABASE_URL is set, but the server is built without the postgres feature; using in-memory storage");
+            std::sync::Arc::new(MemoryStorage::new())
+        }
+        Err(_) => match std::env::var("REDIS_URL") {
+            #[cfg(feature = "redis")]
+            Ok(url) => match kimai_ml::storage::RedisStorage::connect(&url).await {
+                Ok(storage) => {
+                    tracing::info!("Using Redis storage");
+                    std::sync::Arc::new(storage)
+                }
+                Err(e) => panic!("Failed to open storage: {}", e),
+            },
+            _ => std::sync::Arc::new(MemoryStorage::new()),
+        },
+    }
+}
+
+/// Кэш прогнозов: общий в Redis (`REDIS_URL`, feature `redis`), иначе в памяти.
+/// Время жизни записей задается `CACHE_TTL_SECS` (по умолчанию 300).
+async fn open_cache() -> std::sync::Arc<dyn PredictionCache> {
+    let ttl_secs = std::env::var("CACHE_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(300);
+
+    match std::env::var("REDIS_URL") {
+        #[cfg(feature = "redis")]
+        Ok(url) => match kimai_ml::cache::RedisCache::connect(&url, ttl_secs).await {
+            Ok(cache) => std::sync::Arc::new(cache),
+            Err(e) => panic!("Failed to open cache: {}", e),
+        },
+        #[cfg(not(feature = "redis"))]
+        Ok(_) => {
+            tracing::warn!("REDIS_URL is set, but the server is built without the redis feature; using in-memory cache");
+            std::sync::Arc::new(MemoryCache::new(
+                std::time::Duration::from_secs(ttl_secs),
+                1000,
+            ))
+        }
+        Err(_) => std::sync::Arc::new(MemoryCache::new(
+            std::time::Duration::from_secs(ttl_secs),
+            1000,
+        )),
+    }
+}
+
+/// Генератор рекомендаций с формулировками и порогами из файла
+/// `RECOMMENDATION_TEMPLATES` (JSON с шаблонами Handlebars), если он задан
+/// Настройки детектора аномалий из окружения: пределы размера леса
+/// `ANOMALY_MAX_MODEL_BYTES` и `ANOMALY_PRUNE_CORRELATION`, режим скользящего
+/// окна `ANOMALY_WINDOW` (записей в окне) и `ANOMALY_REFRESH_FRACTION` (доля
+/// деревьев на обновление, 0.1); без `ANOMALY_WINDOW` детектор переобучается на
+/// каждом запросе целиком
+fn configure_anomaly_detector(detector: AnomalyDetector) -> AnomalyDetector {
+    let detector = detector
+        .with_limits(
+            env_parse("ANOMALY_MAX_MODEL_BYTES"),
+            env_parse("ANOMALY_PRUNE_CORRELATION"),
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+    let Some(capacity) = env_parse::<usize>("ANOMALY_WINDOW") else {
+        return detector.without_rolling_window();
+    };
+    let fraction = env_parse("ANOMALY_REFRESH_FRACTION").unwrap_or(0.1);
+    detector
+        .with_rolling_window(capacity, fraction)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+fn open_recommendation_engine() -> RecommendationEngine {
+    let Ok(path) = std::env::var("RECOMMENDATION_TEMPLATES") else {
+        return RecommendationEngine::new();
+    };
+    match kimai_ml::models::recommendation_templates::RecommendationTemplates::load(&path) {
+        Ok(templates) => {
+            tracing::info!("Using recommendation templates from {}", path);
+            RecommendationEngine::with_templates(templates)
+        }
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Реестр моделей из `MODEL_REGISTRY_URL`: каталог (`/data/models`, `file:///data/models`)
+/// или `s3://bucket/prefix` (feature `s3`)
+fn open_registry() -> Option<std::sync::Arc<ModelRegistry>> {
+    let url = std::env::var("MODEL_REGISTRY_URL").ok()?;
+    let store: Box<dyn kimai_ml::registry::ArtifactStore> = if url.starts_with("s3://") {
+        #[cfg(feature = "s3")]
+        match kimai_ml::registry::S3ArtifactStore::from_url(&url) {
+            Ok(store) => Box::new(store),
+            Err(e) => panic!("Failed to open model registry: {}", e),
+        }
+        #[cfg(not(feature = "s3"))]
+        panic!(
+            "MODEL_REGISTRY_URL is an s3:// URL, but the server is built without the s3 feature"
+        );
+    } else {
+        Box::new(LocalArtifactStore::new(
+            url.strip_prefix("file://").unwrap_or(&url),
+        ))
+    };
+    tracing::info!("Using model registry at {}", url);
+    Some(std::sync::Arc::new(
+        ModelRegistry::new(store).with_signer(open_signer()),
+    ))
+}
+
+/// Снимок модели `model` арендатора для загрузки при первом обращении: версия
+/// реестра, назначенная окружению `MODEL_ENVIRONMENT`, иначе снимок хранилища.
+/// Снимки без арендатора в имени (сохраненные до разделения по арендаторам)
+/// достаются арендатору по умолчанию
+async fn initial_snapshot(state: &AppState, model: &str, tenant: &str) -> Option<Artifact> {
+    let mut names = vec![tenants::model_name(model, tenant)];
+    if tenant == DEFAULT_TENANT {
+        names.push(model.to_string());
+    }
+
+    if let (Some(registry), Ok(environment)) = (&state.registry, std::env::var("MODEL_ENVIRONMENT"))
+    {
+        for name in &names {
+            match registry.load(name, &environment).await {
+                Ok(Some(artifact)) => return Some(artifact),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("Failed to load promoted {} model: {}", name, e),
+            }
+        }
+    }
+    for name in &names {
+        match state.storage.load_model(name).await {
+            Ok(Some(json)) => {
+                // Снимки, сохраненные до перезапуска, тоже числятся за арендатором
+                state.usage.record_snapshot(tenant, name, json.len() as u64);
+                return Some(Artifact::Json(json));
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to load {} model: {}", name, e),
+        }
+    }
+    None
+}
+
+/// Модель прогноза арендатора; при первом обращении загружается из реестра
+/// или хранилища, без снимка - необученная
+async fn forecasting_model(
+    state: &AppState,
+    tenant: &str,
+) -> std::sync::Arc<tokio::sync::Mutex<ForecastingModel>> {
+    if let Some(model) = state.forecasting_models.get(tenant) {
+        return model;
+    }
+    let name = tenants::model_name("forecasting", tenant);
+    let model = match initial_snapshot(state, "forecasting", tenant).await {
+        Some(artifact) => match forecasting_from_artifact(&artifact) {
+            Ok(model) => {
+                record_checksum(state, &name, model.to_json());
+                model
+            }
+            Err(e) => {
+                tracing::warn!("Stored {} model is not usable: {}", name, e);
+                ForecastingModel::new()
+            }
+        },
+        None => ForecastingModel::new(),
+    };
+    state
+        .forecasting_models
+        .get_or_insert_with(tenant, || model)
+}
+
+/// Детектор аномалий арендатора; загружается как `forecasting_model`
+async fn anomaly_detector(
+    state: &AppState,
+    tenant: &str,
+) -> std::sync::Arc<tokio::sync::Mutex<AnomalyDetector>> {
+    if let Some(detector) = state.anomaly_detectors.get(tenant) {
+        return detector;
+    }
+    let name = tenants::model_name("anomaly", tenant);
+    let detector = match initial_snapshot(state, "anomaly", tenant).await {
+        Some(artifact) => match anomaly_from_artifact(&artifact) {
+            Ok(detector) => {
+                let detector = configure_anomaly_detector(detector);
+                record_checksum(state, &name, detector.to_json());
+                detector
+            }
+            Err(e) => {
+                tracing::warn!("Stored {} model is not usable: {}", name, e);
+                (*state.anomaly_template).clone()
+            }
+        },
+        None => (*state.anomaly_template).clone(),
+    };
+    state
+        .anomaly_detectors
+        .get_or_insert_with(tenant, || detector)
+}
+
+fn forecasting_from_artifact(artifact: &Artifact) -> Result<ForecastingModel, String> {
+    match artifact {
+        Artifact::Json(json) => ForecastingModel::from_json(json),
+        Artifact::Compact(data) => ForecastingModel::from_compact(data),
+    }
+}
+
+fn anomaly_from_artifact(artifact: &Artifact) -> Result<AnomalyDetector, String> {
+    match artifact {
+        Artifact::Json(json) => AnomalyDetector::from_json(json),
+        Artifact::Compact(data) => AnomalyDetector::from_compact(data),
+    }
+}
+
+fn registry(state: &AppState) -> Result<&ModelRegistry, (StatusCode, String)> {
+    state.registry.as_deref().ok_or((
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Model registry is not configured".to_string(),
+    ))
+}
+
+async fn list_model_versions(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<ArtifactVersion>>, (StatusCode, String)> {
+    registry(&state)?
+        .versions(&tenants::model_name(&name, &tenant.0))
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// Регистрация текущей обученной модели арендатора (`forecasting` или `anomaly`)
+/// как новой версии
+#[derive(Debug, Deserialize)]
+struct RegisterParams {
+    #[serde(default)]
+    format: ArtifactFormat,
+}
+
+async fn register_model(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(name): Path<String>,
+    Query(params): Query<RegisterParams>,
+) -> Result<Json<ArtifactVersion>, (StatusCode, String)> {
+    let registry = registry(&state)?;
+    let (json, compact, model_info) = match name.as_str() {
+        "forecasting" => {
+            let model = forecasting_model(&state, &tenant.0).await;
+            let model = model.lock().await;
+            (model.to_json(), model.to_compact(), model.model_info())
+        }
+        "anomaly" => {
+            let detector = anomaly_detector(&state, &tenant.0).await;
+            let detector = detector.lock().await;
+            (
+                detector.to_json(),
+                detector.to_compact(),
+                detector.model_info(),
+            )
+        }
+        _ => return Err((StatusCode::NOT_FOUND, format!("Unknown model {}", name))),
+    };
+    let json = json.map_err(|e| (StatusCode::CONFLICT, e))?;
+    let compact = compact.map_err(|e| (StatusCode::CONFLICT, e))?;
+    let size = SizeReport {
+        json_bytes: json.len(),
+        compact_bytes: compact.len(),
+    };
+    let artifact = match params.format {
+        ArtifactFormat::Json => Artifact::Json(json),
+        ArtifactFormat::Compact => Artifact::Compact(compact),
+    };
+
+    let name = tenants::model_name(&name, &tenant.0);
+    let version = registry
+        .register(&name, &artifact, model_info, Some(size))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    tracing::info!("Registered {} model version {}", name, version.version);
+    Ok(Json(version))
+}
+
+#[derive(Debug, Deserialize)]
+struct PromoteRequest {
+    version: String,
+    environment: String,
+}
+
+async fn promote_model(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(name): Path<String>,
+    Json(req): Json<PromoteRequest>,
+) -> Result<Json<Promotion>, (StatusCode, String)> {
+    let name = tenants::model_name(&name, &tenant.0);
+    let promotion = registry(&state)?
+        .promote(&name, &req.version, &req.environment)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    tracing::info!(
+        "Promoted {} model version {} to {}",
+        name,
+        promotion.version,
+        promotion.environment
+    );
+    Ok(Json(promotion))
+}
+
+/// Журнал аудита из `AUDIT_LOG`: путь к файлу JSON Lines или `postgres`
+/// (таблица в `DATABASE_URL`, feature `postgres`); по умолчанию — в памяти
+async fn open_audit_log() -> std::sync::Arc<dyn AuditLog> {
+    match std::env::var("AUDIT_LOG").ok().as_deref() {
+        None | Some("") | Some("memory") => std::sync::Arc::new(MemoryAuditLog::default()),
+        #[cfg(feature = "postgres")]
+        Some("postgres") => {
+            let url =
+                std::env::var("DATABASE_URL").expect("AUDIT_LOG=postgres requires DATABASE_URL");
+            match kimai_ml::audit::PostgresAuditLog::connect(&url).await {
+                Ok(log) => std::sync::Arc::new(log),
+                Err(e) => panic!("Failed to open audit log: {}", e),
+            }
+        }
+        #[cfg(not(feature = "postgres"))]
+        Some("postgres") => {
+            panic!("AUDIT_LOG=postgres, but the server is built without the postgres feature")
+        }
+        Some(path) => {
+            tracing::info!("Writing audit log to {}", path);
+            std::sync::Arc::new(FileAuditLog::new(path))
+        }
+    }
+}
+
+/// Пути, доступные без токена
+const PUBLIC_PATHS: [&str; 3] = ["/", "/health", "/ready"];
+
+/// Проверка `Authorization: Bearer` (для `/ws` - и `?access_token=`, браузер не
+/// задает заголовки WebSocket). Арендатор и пользователь токена передаются
+/// обработчикам через `Identity` в расширениях запроса
+async fn authenticate_requests(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(auth) = &state.auth else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    if PUBLIC_PATHS.contains(&path.as_str()) || request.method() == Method::OPTIONS {
+        return next.run(request).await;
+    }
+
+    let token = bearer_token(&request, path == "/ws");
+    let result = match token {
+        Some(token) => auth.authenticate(&token).await,
+        None => Err("Missing bearer token".to_string()),
+    };
+    match result {
+        Ok(identity) => {
+            request.extensions_mut().insert(identity);
+            next.run(request).await
+        }
+        Err(e) => {
+            tracing::warn!("Rejected {} {}: {}", request.method(), path, e);
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                e,
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WsAuthParams {
+    access_token: Option<String>,
+}
+
+fn bearer_token(request: &Request, allow_query: bool) -> Option<String> {
+    let header_token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string());
+    header_token.or_else(|| {
+        allow_query
+            .then(|| Query::<WsAuthParams>::try_from_uri(request.uri()).ok())
+            .flatten()
+            .and_then(|Query(params)| params.access_token)
+    })
+}
+
+/// Запись каждого вызова API в журнал аудита (кроме `/health` и `/ready`)
+async fn audit_requests(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<std::net::SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    if path == "/health" || path == "/ready" {
+        return next.run(request).await;
+    }
+
+    let started = std::time::Instant::now();
+    let method = request.method().to_string();
+    let caller = match request.extensions().get::<Identity>() {
+        Some(identity) => identity.user.clone(),
+        None => request
+            .headers()
+            .get("x-user-id")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string()),
+    }
+    .unwrap_or_else(|| peer.ip().to_string());
+    // Тело запроса не буферизуется: размер берется из Content-Length
+    let request_bytes = content_length(request.headers());
+    let (parts, body) = request.into_parts();
+    let Tenant(tenant) = Tenant::of(&parts);
+    let request = Request::from_parts(parts, body);
+
+    let response = next.run(request).await;
+    let status = response.status().as_u16();
+
+    // JSON-ответы уже целиком в памяти; потоки (SSE) не читаются
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let (response, response_bytes, body) = if is_json {
+        let (parts, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX)
+            .await
+            .unwrap_or_default();
+        let json = serde_json::from_slice::<serde_json::Value>(&bytes).ok();
+        let len = bytes.len();
+        (Response::from_parts(parts, Body::from(bytes)), len, json)
+    } else {
+        let len = content_length(response.headers());
+        (response, len, None)
+    };
+
+    let mut model_versions = Vec::new();
+    if let Some(body) = &body {
+        collect_model_versions(body, &mut model_versions);
+    }
+    let learning_update =
+        body.filter(|b| path == "/api/learn" && status < 400 && b["status"] == "recorded");
+
+    let record = AuditRecord {
+        timestamp: chrono::Utc::now().to_rfc3339(),
+        caller: Some(caller),
+        method,
+        path,
+        status,
+        request_bytes,
+        response_bytes,
+        duration_ms: started.elapsed().as_millis() as u64,
+        model_versions,
+        learning_update,
+        tenant,
+    };
+    if let Err(e) = state.audit.append(&record).await {
+        tracing::error!("Failed to write audit record: {}", e);
+    }
+
+    response
+}
+
+/// Ответы, для которых выдается ETag и проверяется `If-None-Match`
+const CONDITIONAL_PATHS: [&str; 7] = [
+    "/api/predict",
+    "/api/detect-anomalies",
+    "/api/recommendations",
+    "/api/productivity",
+    "/api/diagnostics/seasonality",
+    "/api/diagnostics/patterns",
+    "/api/diagnostics/project-lifetime",
+];
+
+/// Размер тела JSON, которое буферизуется для ETag (как у `Json`)
+const CONDITIONAL_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+fn bump_output_revision(state: &AppState) {
+    state
+        .output_revision
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Версии и время обучения моделей арендатора и ревизия ответов: то, что
+/// кроме тела запроса определяет ответ. Не загружает модели: незагруженная
+/// модель - `untrained`
+async fn model_fingerprint(state: &AppState, tenant: &str) -> String {
+    let forecasting = match state.forecasting_models.get(tenant) {
+        Some(model) => model.lock().await.model_info(),
+        None => None,
+    };
+    let anomaly = match state.anomaly_detectors.get(tenant) {
+        Some(detector) => detector.lock().await.model_info(),
+        None => None,
+    };
+    let models: Vec<String> = [forecasting, anomaly]
+        .into_iter()
+        .map(|info| match info {
+            Some(info) => format!(
+                "{}@{}",
+                info.model_version,
+                info.trained_at.unwrap_or_default()
+            ),
+            None => "untrained".to_string(),
+        })
+        .collect();
+    format!(
+        "{};{}",
+        models.join(";"),
+        state
+            .output_revision
+            .load(std::sync::atomic::Ordering::Relaxed)
+    )
+}
+
+/// ETag анализа по хэшу тела запроса, арендатору, параметрам запроса и
+/// `model_fingerprint`. Совпавший `If-None-Match` - ответ 304 без анализа.
+/// Тела NDJSON не буферизуются и ETag не получают
+async fn conditional_responses(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let is_ndjson = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(NDJSON_CONTENT_TYPE));
+    if request.method() != Method::POST || !CONDITIONAL_PATHS.contains(&path.as_str()) || is_ndjson
+    {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, CONDITIONAL_BODY_LIMIT).await else {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+    };
+    let body_hash = kimai_ml::cache::etag(&[&bytes]);
+    let Tenant(tenant) = Tenant::of(&parts);
+    let query = parts.uri.query().unwrap_or_default().to_string();
+    let etag_for = |fingerprint: &str| {
+        kimai_ml::cache::etag(&[
+            path.as_bytes(),
+            tenant.as_bytes(),
+            query.as_bytes(),
+            body_hash.as_bytes(),
+            fingerprint.as_bytes(),
+        ])
+    };
+
+    let etag = etag_for(&model_fingerprint(&state, &tenant).await);
+    let not_modified = parts
+        .headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| {
+            v.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag)
+        });
+    if not_modified {
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response();
+    }
+
+    let mut response = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+    if response.status().is_success() {
+        // Запрос мог обучить модели: ETag - по их состоянию после ответа
+        let etag = etag_for(&model_fingerprint(&state, &tenant).await);
+        if let Ok(value) = etag.parse() {
+            response.headers_mut().insert(header::ETAG, value);
+        }
+    }
+    response
+}
+
+#[derive(Debug, Deserialize)]
+struct FieldsQuery {
+    fields: Option<String>,
+}
+
+/// Отбор полей успешного JSON-ответа по `?fields=` (`FieldProjection`)
+async fn project_fields(
+    Query(query): Query<FieldsQuery>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(fields) = query.fields else {
+        return next.run(request).await;
+    };
+    let projection = match FieldProjection::parse(&fields) {
+        Ok(projection) => projection,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !response.status().is_success() || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .unwrap_or_default();
+    let Ok(json) = serde_json::from_slice::<serde_json::Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    // Длина тела меняется; hyper выставит ее заново
+    parts.headers.remove(header::CONTENT_LENGTH);
+    (parts, Json(projection.apply(json))).into_response()
+}
+
+fn content_length(headers: &axum::http::HeaderMap) -> usize {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Все значения `model_version` из вложенных `model_info`
+fn collect_model_versions(value: &serde_json::Value, versions: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("model_version", serde_json::Value::String(version)) => {
+                        if !versions.contains(version) {
+                            versions.push(version.clone());
+                        }
+                    }
+                    _ => collect_model_versions(value, versions),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                collect_model_versions(item, versions);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Квоты из `TENANT_QUOTAS` (файл JSON); без него потребление только учитывается
+fn open_usage_tracker() -> UsageTracker {
+    match std::env::var("TENANT_QUOTAS")
+        .ok()
+        .filter(|v| !v.is_empty())
+    {
+        Some(path) => match QuotaConfig::load(&path) {
+            Ok(quotas) => {
+                tracing::info!("Loaded tenant quotas from {}", path);
+                UsageTracker::new(quotas)
+            }
+            Err(e) => panic!("{}", e),
+        },
+        None => UsageTracker::default(),
+    }
+}
+
+/// Запросы, которые могут обучать модели и сохранять снимки: для них
+/// проверяются и квоты обучения и хранения
+const TRAINING_PATHS: [&str; 5] = [
+    "/api/predict",
+    "/api/detect-anomalies",
+    "/api/recommendations",
+    "/api/learn",
+    "/api/team/balance",
+];
+
+/// Учет запросов арендатора и ответ 429 при исчерпанной квоте
+async fn enforce_quotas(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if PUBLIC_PATHS.contains(&path) || path.starts_with("/api/admin/") {
+        return next.run(request).await;
+    }
+    let trains = request.method() == Method::POST && TRAINING_PATHS.contains(&path);
+
+    let (parts, body) = request.into_parts();
+    let Tenant(tenant) = Tenant::of(&parts);
+    if let Err(exceeded) = state.usage.admit(&tenant, trains) {
+        tracing::warn!("Tenant {}: {}", tenant, exceeded);
+        let mut response = (StatusCode::TOO_MANY_REQUESTS, exceeded.to_string()).into_response();
+        if exceeded.daily {
+            // Суточные квоты восстанавливаются в полночь UTC
+            let now = chrono::Utc::now();
+            let midnight = (now.date_naive() + chrono::Duration::days(1))
+                .and_hms_opt(0, 0, 0)
+                .unwrap_or_default()
+                .and_utc();
+            let seconds = (midnight - now).num_seconds().max(1);
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, seconds.into());
+        }
+        return response;
+    }
+    next.run(Request::from_parts(parts, body)).await
+}
+
+/// Потребление и квоты всех арендаторов. С JWT - только для арендаторов из
+/// `JWT_ADMIN_TENANTS`
+async fn tenant_usage(
+    State(state): State<AppState>,
+    identity: Option<axum::Extension<Identity>>,
+) -> Result<Json<UsageReport>, (StatusCode, String)> {
+    require_admin(&state, identity)?;
+    Ok(Json(state.usage.report()))
+}
+
+/// С JWT - только арендаторы из `JWT_ADMIN_TENANTS`: данные всех арендаторов
+fn require_admin(
+    state: &AppState,
+    identity: Option<axum::Extension<Identity>>,
+) -> Result<(), (StatusCode, String)> {
+    if let (Some(auth), Some(axum::Extension(identity))) = (&state.auth, identity) {
+        if !auth.is_admin(&identity) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!("Tenant {} is not an administrator", identity.tenant),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Удаление данных арендатора `id`: моделей (снимки, версии в реестре и
+/// загруженные в память), снимков его состояния (профиль, трекер рекомендаций,
+/// журнал прогнозов), ошибок прогнозов, отзывов об аномалиях и рекомендациях,
+/// данных переобучения, учета снимков, записей журнала аудита, записанных
+/// запросов и кэшей. Остаются суточные счетчики квот (до полуночи UTC) и
+/// запись аудита о самом удалении. С JWT - только для самого арендатора или
+/// арендаторов из `JWT_ADMIN_TENANTS`
+async fn delete_tenant_data(
+    State(state): State<AppState>,
+    identity: Option<axum::Extension<Identity>>,
+    Path(tenant): Path<String>,
+) -> Result<Json<TenantDataDeletion>, (StatusCode, String)> {
+    if let (Some(auth), Some(axum::Extension(identity))) = (&state.auth, identity) {
+        if identity.tenant != tenant && !auth.is_admin(&identity) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                format!(
+                    "Tenant {} cannot delete data of {}",
+                    identity.tenant, tenant
+                ),
+            ));
+        }
+    }
+    let internal = |e: String| (StatusCode::INTERNAL_SERVER_ERROR, e);
+
+    let mut deletion = TenantDataDeletion {
+        tenant: tenant.clone(),
+        ..TenantDataDeletion::default()
+    };
+    let models = [
+        tenants::model_name("forecasting", &tenant),
+        tenants::model_name("anomaly", &tenant),
+    ];
+    let mut snapshots = vec![
+        BaselineProfile::storage_name(&tenant),
+        RecommendationTracker::storage_name(&tenant),
+        ForecastLedger::storage_name(&tenant),
+    ];
+    snapshots.extend(models.iter().cloned());
+    if tenant == DEFAULT_TENANT {
+        // Снимки, сохраненные до разделения моделей по арендаторам
+        snapshots.extend(["forecasting".to_string(), "anomaly".to_string()]);
+    }
+    for name in snapshots {
+        if state.storage.delete_model(&name).await.map_err(internal)? {
+            deletion.snapshots.push(name);
+        }
+    }
+    if let Some(registry) = &state.registry {
+        for name in &models {
+            deletion.model_versions += registry.delete(name).await.map_err(internal)?.len();
+        }
+    }
+
+    // Модели и обучение арендатора в памяти
+    state.forecasting_models.remove(&tenant);
+    state.anomaly_detectors.remove(&tenant);
+    state.learning_modules.remove(&tenant);
+    state
+        .retrain_inputs
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&tenant);
+    state
+        .model_checksums
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|name, _| !models.contains(name));
+    state.usage.remove_snapshots(&tenant);
+
+    deletion.learning_errors = state
+        .storage
+        .delete_learning_errors(&tenant)
+        .await
+        .map_err(internal)?;
+    deletion.anomaly_feedback = state
+        .storage
+        .delete_anomaly_feedback(&tenant)
+        .await
+        .map_err(internal)?;
+    deletion.recommendation_feedback = state
+        .storage
+        .delete_recommendation_feedback(&tenant)
+        .await
+        .map_err(internal)?;
+    if let Some(capture) = &state.capture {
+        let pseudonym = capture.anonymizer().pseudonym(&tenant);
+        deletion.captures = capture
+            .retain(|record| record.tenant != pseudonym)
+            .await
+            .map_err(internal)?;
+    }
+
+    deletion.audit_records = state.audit.delete_tenant(&tenant).await.map_err(internal)?;
+
+    // Кэшированные ответы и признаки могли быть построены по данным арендатора
+    state.anomaly_features.remove(&tenant);
+    if let Err(e) = state.cache.clear_tenant(&tenant).await {
+        tracing::warn!("Failed to clear prediction cache: {}", e);
+    }
+    bump_output_revision(&state);
+    tracing::info!(
+        "Deleted data of tenant {}: {} snapshots, {} model versions, {} learning errors, \
+         {} anomaly feedback, {} recommendation feedback, {} captures, {} audit records",
+        tenant,
+        deletion.snapshots.len(),
+        deletion.model_versions,
+        deletion.learning_errors,
+        deletion.anomaly_feedback,
+        deletion.recommendation_feedback,
+        deletion.captures,
+        deletion.audit_records
+    );
+    Ok(Json(deletion))
+}
+
+/// Фоновая очистка данных старше сроков хранения
+async fn enforce_retention(state: AppState, policy: RetentionPolicy) {
+    tracing::info!(
+        "Retention cleanup every {:?}: {:?}",
+        policy.interval,
+        policy
+    );
+    let mut interval = tokio::time::interval(policy.interval);
+    loop {
+        interval.tick().await;
+        let report = cleanup(&state, &policy, chrono::Utc::now()).await;
+        if report.total() > 0 {
+            tracing::info!(
+                "Retention cleanup: {} learning errors, {} captures, {} audit records, {} model versions",
+                report.learning_errors,
+                report.captures,
+                report.audit_records,
+                report.artifacts
+            );
+        }
+    }
+}
+
+/// Удаление данных старше сроков `policy`; ошибки одного вида данных не мешают
+/// очистке остальных
+async fn cleanup(
+    state: &AppState,
+    policy: &RetentionPolicy,
+    now: chrono::DateTime<chrono::Utc>,
+) -> CleanupReport {
+    let mut report = CleanupReport::default();
+    if let Some(days) = policy.learning_errors_days {
+        match state
+            .storage
+            .prune_learning_errors(retention::cutoff(days, now))
+            .await
+        {
+            Ok(0) => {}
+            Ok(removed) => {
+                report.learning_errors = removed;
+                // Поправки пересчитываются без удаленных ошибок
+                for tenant in state.learning_modules.tenants() {
+                    drop(sync_learning(state, &tenant).await);
+                }
+                if let Err(e) = state.cache.clear().await {
+                    tracing::warn!("Failed to clear prediction cache: {}", e);
+                }
+                bump_output_revision(state);
+            }
+            Err(e) => tracing::warn!("Failed to prune learning errors: {}", e),
+        }
+    }
+    if let (Some(days), Some(capture)) = (policy.captures_days, &state.capture) {
+        let before = retention::cutoff(days, now);
+        match capture
+            .retain(|record| !retention::is_expired(&record.timestamp, before))
+            .await
+        {
+            Ok(removed) => report.captures = removed,
+            Err(e) => tracing::warn!("Failed to prune captured requests: {}", e),
+        }
+    }
+    if let Some(days) = policy.audit_days {
+        match state.audit.prune(retention::cutoff(days, now)).await {
+            Ok(removed) => report.audit_records = removed,
+            Err(e) => tracing::warn!("Failed to prune audit log: {}", e),
+        }
+    }
+    if let (Some(days), Some(registry)) = (policy.artifacts_days, &state.registry) {
+        match registry.prune(retention::cutoff(days, now)).await {
+            Ok(removed) => {
+                for version in &removed {
+                    tracing::info!("Removed {} model version {}", version.name, version.version);
+                }
+                report.artifacts = removed.len();
+            }
+            Err(e) => tracing::warn!("Failed to prune model registry: {}", e),
+        }
+    }
+    report
+}
+
+/// Запись запросов из `CAPTURE_PATH`; соль псевдонимов - `CAPTURE_SALT` (без нее
+/// случайная, псевдонимы разных запусков не совпадают), доля записываемых
+/// запросов - `CAPTURE_SAMPLE_RATE` (1 по умолчанию)
+fn open_capture() -> Option<std::sync::Arc<CaptureLog>> {
+    let path = std::env::var("CAPTURE_PATH")
+        .ok()
+        .filter(|v| !v.is_empty())?;
+    let salt = std::env::var("CAPTURE_SALT")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| format!("{:032x}", rand::random::<u128>()));
+    let mut capture = CaptureLog::new(&path, &salt);
+    if let Some(rate) = env_parse("CAPTURE_SAMPLE_RATE") {
+        capture = capture
+            .sample_rate(rate)
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+    tracing::info!("Capturing analysis requests to {}", path);
+    Some(std::sync::Arc::new(capture))
+}
+
+/// Запись обезличенных запросов анализа (`CONDITIONAL_PATHS`) с ответами и
+/// версиями моделей. Записываются только успешные ответы на тела JSON
+async fn capture_requests(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(capture) = state.capture.clone() else {
+        return next.run(request).await;
+    };
+    let path = request.uri().path().to_string();
+    let is_ndjson = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(NDJSON_CONTENT_TYPE));
+    if request.method() != Method::POST
+        || !CONDITIONAL_PATHS.contains(&path.as_str())
+        || is_ndjson
+        || !capture.sampled()
+    {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(input) = axum::body::to_bytes(body, CONDITIONAL_BODY_LIMIT).await else {
+        return (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response();
+    };
+    let Tenant(tenant) = Tenant::of(&parts);
+    let response = next
+        .run(Request::from_parts(parts, Body::from(input.clone())))
+        .await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !response.status().is_success() || !is_json {
+        return response;
+    }
+
+    let status = response.status().as_u16();
+    let (parts, body) = response.into_parts();
+    let output = axum::body::to_bytes(body, usize::MAX)
+        .await
+        .unwrap_or_default();
+    if let (Ok(mut input), Ok(mut output)) = (
+        serde_json::from_slice::<serde_json::Value>(&input),
+        serde_json::from_slice::<serde_json::Value>(&output),
+    ) {
+        let mut model_versions = Vec::new();
+        collect_model_versions(&output, &mut model_versions);
+        let mut anonymizer = capture.anonymizer();
+        anonymizer.anonymize_input(&mut input);
+        anonymizer.anonymize_output(&mut output);
+
+        let record = CaptureRecord {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            tenant: anonymizer.pseudonym(&tenant),
+            path,
+            status,
+            input,
+            output,
+            model_versions,
+        };
+        if let Err(e) = capture.append(&record).await {
+            tracing::error!("Failed to write capture record: {}", e);
+        }
+    }
+    Response::from_parts(parts, Body::from(output))
+}
+
+/// Различий на запрос в отчете `replay`
+const REPLAY_SHOWN_DIFFERENCES: usize = 10;
+
+/// `kimai-ml replay <файл> [--tolerance <доля>]`: повторяет записанные запросы
+/// по порядку на моделях этой сборки и сравнивает ответы с записанными.
+/// Модели начинают с чистого состояния в памяти; хранилище, реестр и журнал
+/// аудита сервера не используются. Код выхода 1 - есть расхождения
+async fn replay(args: &[String]) -> i32 {
+    let mut path = None;
+    let mut tolerance = 0.05;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tolerance" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(value) => tolerance = value,
+                None => {
+                    eprintln!("--tolerance requires a number");
+                    return 2;
+                }
+            },
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => {
+                eprintln!("Unexpected argument: {}", arg);
+                return 2;
+            }
+        }
+    }
+    let Some(path) = path else {
+        eprintln!("Usage: kimai-ml replay <capture file> [--tolerance <fraction>]");
+        return 2;
+    };
+    let records = match capture::read_captures(&path) {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    let ttl = std::time::Duration::from_secs(300);
+    let state = new_state(
+        std::sync::Arc::new(MemoryStorage::new()),
+        std::sync::Arc::new(MemoryCache::new(ttl, 1000)),
+        std::sync::Arc::new(MemoryAuditLog::default()),
+    );
+    let app = router(state).layer(axum::extract::connect_info::MockConnectInfo(
+        std::net::SocketAddr::from(([127, 0, 0, 1], 0)),
+    ));
+
+    let mut mismatched = 0;
+    for (index, record) in records.iter().enumerate() {
+        let request = Request::post(&record.path)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("x-tenant-id", &record.tenant)
+            .body(Body::from(record.input.to_string()))
+            .expect("valid replay request");
+        let response = match tower::ServiceExt::oneshot(app.clone(), request).await {
+            Ok(response) => response,
+            Err(infallible) => match infallible {},
+        };
+        let status = response.status().as_u16();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap_or_default();
+        let output = serde_json::from_slice(&bytes).unwrap_or_else(|_| {
+            serde_json::Value::String(String::from_utf8_lossy(&bytes).into_owned())
+        });
+        let mut model_versions = Vec::new();
+        collect_model_versions(&output, &mut model_versions);
+
+        let differences = if status == record.status {
+            capture::compare(&record.output, &output, tolerance)
+        } else {
+            vec![capture::Difference {
+                path: "status".to_string(),
+                expected: record.status.into(),
+                actual: status.into(),
+            }]
+        };
+        println!(
+            "[{}/{}] {} {} ({} -> {}): {}",
+            index + 1,
+            records.len(),
+            record.timestamp,
+            record.path,
+            record.model_versions.join(", "),
+            model_versions.join(", "),
+            if differences.is_empty() {
+                "match".to_string()
+            } else {
+                format!("{} differences", differences.len())
+            }
+        );
+        for difference in differences.iter().take(REPLAY_SHOWN_DIFFERENCES) {
+            println!(
+                "    {}: {} -> {}",
+                difference.path, difference.expected, difference.actual
+            );
+        }
+        if !differences.is_empty() {
+            mismatched += 1;
+        }
+    }
+
+    println!(
+        "{} requests replayed, {} with differences (tolerance {})",
+        records.len(),
+        mismatched,
+        tolerance
+    );
+    i32::from(mismatched > 0)
+}
+
+/// Журнал аудита всех арендаторов; с JWT - только для `JWT_ADMIN_TENANTS`
+async fn query_audit_log(
+    State(state): State<AppState>,
+    identity: Option<axum::Extension<Identity>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditRecord>>, (StatusCode, String)> {
+    require_admin(&state, identity)?;
+    state
+        .audit
+        .query(&query)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
+/// Ключ кэша для запроса; `None`, если кэш отключен (`options.cache: false`)
+/// или клиент ждет событий задачи (`options.job_id`)
+fn output_cache_key(scope: &str, tenant: &str, data: &MLInputData) -> Option<String> {
+    let options = data.options.as_ref();
+    let enabled = options
+        .and_then(|o| o.get("cache"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+    if !enabled || requested_job_id(data).is_some() {
+        return None;
+    }
+    // Пробный запрос проверяет ключ обычного
+    if is_dry_run(data) {
+        let mut data = data.clone();
+        if let Some(serde_json::Value::Object(options)) = data.options.as_mut() {
+            options.remove("dry_run");
+        }
+        return tenant_cache_key(tenant, scope, &data).ok();
+    }
+    tenant_cache_key(tenant, scope, data).ok()
+}
+
+async fn cached_output(state: &AppState, key: Option<&str>) -> Option<MLOutputData> {
+    match state.cache.get(key?).await {
+        Ok(json) => json.and_then(|json| serde_json::from_str(&json).ok()),
+        Err(e) => {
+            tracing::warn!("Prediction cache unavailable: {}", e);
+            None
+        }
+    }
+}
+
+async fn store_output(state: &AppState, key: Option<&str>, output: &MLOutputData) {
+    let Some(key) = key else {
+        return;
+    };
+    let result = match serde_json::to_string(output) {
+        Ok(json) => state.cache.put(key, &json).await,
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = result {
+        tracing::warn!("Failed to cache output: {}", e);
+    }
+}
+
+/// Перезагрузка моделей, переобученных другими репликами
+async fn watch_model_updates(state: AppState) {
+    let mut updates = match state.storage.model_updates().await {
+        Ok(Some(updates)) => updates,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Model update notifications unavailable: {}", e);
+            return;
+        }
+    };
+
+    while let Some(name) = updates.next().await {
+        tracing::info!("Model {} updated by another instance", name);
+        reload_model(&state, &name).await;
+    }
+}
+
+/// Замена загруженной модели арендатора снимком `name` (`forecasting:acme`);
+/// модели, которые еще не загружены, прочитаются из хранилища при первом обращении
+async fn reload_model(state: &AppState, name: &str) {
+    let Some((model, tenant)) = tenants::parse_model_name(name) else {
+        return;
+    };
+    let loaded = match model {
+        "forecasting" => state.forecasting_models.get(tenant).is_some(),
+        "anomaly" => state.anomaly_detectors.get(tenant).is_some(),
+        _ => false,
+    };
+    if !loaded {
+        return;
+    }
+    let json = match state.storage.load_model(name).await {
+        Ok(Some(json)) => json,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!("Failed to load {} model: {}", name, e);
+            return;
+        }
+    };
+
+    match model {
+        "forecasting" => match ForecastingModel::from_json(&json) {
+            Ok(model) => {
+                record_checksum(state, name, model.to_json());
+                if let Some(slot) = state.forecasting_models.get(tenant) {
+                    *slot.lock().await = model;
+                }
+            }
+            Err(e) => tracing::warn!("Stored {} model is not usable: {}", name, e),
+        },
+        "anomaly" => match AnomalyDetector::from_json(&json) {
+            Ok(detector) => {
+                let detector = configure_anomaly_detector(detector);
+                record_checksum(state, name, detector.to_json());
+                if let Some(slot) = state.anomaly_detectors.get(tenant) {
+                    *slot.lock().await = detector;
+                }
+            }
+            Err(e) => tracing::warn!("Stored {} model is not usable: {}", name, e),
+        },
+        _ => {}
+    }
+}
+
+/// Модуль обучения арендатора с его ошибками и отзывами, записанными в
+/// хранилище (в том числе другими репликами)
+async fn sync_learning(
+    state: &AppState,
+    tenant: &str,
+) -> tokio::sync::OwnedMutexGuard<LearningModule> {
+    let mut learning = state
+        .learning_modules
+        .get_or_insert_with(tenant, || (*state.learning_template).clone())
+        .lock_owned()
+        .await;
+    match state
+        .storage
+        .learning_errors(tenant, learning.max_errors())
+        .await
+    {
+        Ok(errors) => learning.load_errors(errors),
+        Err(e) => tracing::warn!("Failed to load learning errors: {}", e),
+    }
+    match state
+        .storage
+        .anomaly_feedback(tenant, learning.max_errors())
+        .await
+    {
+        Ok(feedback) => learning.load_verdicts(feedback.iter().map(AnomalyVerdict::from).collect()),
+        Err(e) => tracing::warn!("Failed to load anomaly feedback: {}", e),
+    }
+    learning
+}
+
+/// Сохранение снимка в хранилище; размер учитывается за арендатором
+async fn persist_model(
+    state: &AppState,
+    tenant: &Tenant,
+    name: &str,
+    snapshot: Result<String, String>,
+) {
+    if let Ok(json) = &snapshot {
+        let model = tenants::parse_model_name(name).map(|(model, _)| model);
+        if matches!(model, Some("forecasting" | "anomaly")) {
+            record_checksum(state, name, Ok(json.clone()));
+        }
+    }
+    let result = match snapshot {
+        Ok(json) => state
+            .storage
+            .save_model(name, &json)
+            .await
+            .map(|()| json.len()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(bytes) => state.usage.record_snapshot(&tenant.0, name, bytes as u64),
+        Err(e) => tracing::warn!("Failed to store {} model: {}", name, e),
+    }
+}
+
+/// Контрольная сумма снимка модели для проверки целостности в `/ready`. Снимок
+/// берется из `to_json` загруженной модели: JSON хранилища может отличаться
+/// порядком полей
+fn record_checksum(state: &AppState, name: &str, snapshot: Result<String, String>) {
+    match snapshot {
+        Ok(json) => {
+            state
+                .model_checksums
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(name.to_string(), selftest::snapshot_checksum(&json));
+        }
+        Err(e) => tracing::warn!("Failed to checksum {} model: {}", name, e),
+    }
+}
+
+/// Запись о задаче в хранилище на время ее выполнения
+struct StoredJob {
+    storage: std::sync::Arc<dyn Storage>,
+    tenant: String,
+    id: String,
+}
+
+impl StoredJob {
+    async fn save(state: &AppState, job: &JobGuard) -> Self {
+        if let Some(info) = job.info() {
+            if let Err(e) = state.storage.save_job(&info).await {
+                tracing::warn!("Failed to store job {}: {}", info.id, e);
+            }
+        }
+        Self {
+            storage: state.storage.clone(),
+            tenant: job.info().map(|info| info.tenant).unwrap_or_default(),
+            id: job.id().to_string(),
+        }
+    }
+}
+
+impl Drop for StoredJob {
+    fn drop(&mut self) {
+        let storage = self.storage.clone();
+        let tenant = std::mem::take(&mut self.tenant);
+        let id = std::mem::take(&mut self.id);
+        tokio::spawn(async move {
+            if let Err(e) = storage.remove_job(&tenant, &id).await {
+                tracing::warn!("Failed to remove stored job {}: {}", id, e);
+            }
+        });
+    }
+}
+
+/// Оповещение арендатора о завершении обучения модели
+fn publish_training(
+    state: &AppState,
+    tenant: &Tenant,
+    model: &str,
+    result: &Result<(), String>,
+    model_info: Option<kimai_ml::types::ModelInfo>,
+) {
+    state.events.publish(
+        &tenant.0,
+        EventKind::TrainingFinished {
+            model: model.to_string(),
+            status: job_status(result).to_string(),
+            model_info: model_info.filter(|_| result.is_ok()),
+        },
+    );
+}
+
+fn job_status(result: &Result<(), String>) -> &'static str {
+    match result {
+        Ok(()) => "completed",
+        Err(e) if e == TRAINING_CANCELLED => "cancelled",
+        Err(_) => "failed",
+    }
+}
+
+/// Задачи арендатора на всех репликах из общего хранилища (при его
+/// недоступности — только локальные). С JWT - арендатора токена
+async fn list_jobs(State(state): State<AppState>, tenant: Tenant) -> Json<Vec<JobInfo>> {
+    match state.storage.jobs(&tenant.0).await {
+        Ok(jobs) => Json(jobs),
+        Err(e) => {
+            tracing::warn!("Failed to list stored jobs: {}", e);
+            Json(state.jobs.list(&tenant.0))
+        }
+    }
+}
+
+/// Отмена задачи арендатора; задачи других арендаторов - 404
+async fn cancel_job(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if state.jobs.cancel(&tenant.0, &id) {
+        tracing::info!("Job {} cancelled", id);
+        Ok(Json(serde_json::json!({ "status": "cancelled", "id": id })))
+    } else {
+        Err((StatusCode::NOT_FOUND, format!("Job {} not found", id)))
+    }
+}
+
+/// Server-sent events с прогрессом задачи арендатора; поток закрывается после
+/// события `finished`. Задача другого арендатора - 404
+async fn job_events(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, std::convert::Infallible>>>, (StatusCode, String)>
+{
+    if state.jobs.is_foreign(&tenant.0, &id) {
+        return Err((StatusCode::NOT_FOUND, format!("Job {} not found", id)));
+    }
+    let receiver = state.jobs.subscribe(&tenant.0, &id);
+
+    let stream = futures_util::stream::unfold(Some(receiver), |receiver| async move {
+        let mut receiver = receiver?;
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let sse_event = Event::default()
+                        .event(event.name())
+                        .json_data(&event)
+                        .unwrap_or_default();
+                    let next = match event {
+                        JobEvent::Finished { .. } => None,
+                        _ => Some(receiver),
+                    };
+                    return Some((Ok(sse_event), next));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[derive(Debug, Deserialize)]
+struct WsParams {
+    tenant: Option<String>,
+}
+
+/// Команды клиента `/ws`: `{"subscribe": "tenant"}`, `{"unsubscribe": "tenant"}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum WsCommand {
+    Subscribe(String),
+    Unsubscribe(String),
+}
+
+/// Push-канал событий анализа (обучение, аномалии, дрейф) для арендатора из
+/// `?tenant=` или `X-Tenant-Id`. С JWT - только для арендатора токена
+async fn ws_events(
+    State(state): State<AppState>,
+    tenant: Tenant,
+    identity: Option<axum::Extension<Identity>>,
+    Query(params): Query<WsParams>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let (tenant, locked) = match identity {
+        Some(_) => (tenant.0, true),
+        None => (params.tenant.unwrap_or(tenant.0), false),
+    };
+    let events = state.events.subscribe();
+    ws.on_upgrade(move |socket| push_events(socket, events, tenant, locked))
+}
+
+/// `locked` - подписка на других арендаторов запрещена
+async fn push_events(
+    mut socket: WebSocket,
+    mut events: tokio::sync::broadcast::Receiver<AnalysisEvent>,
+    tenant: String,
+    locked: bool,
+) {
+    let own_tenant = tenant.clone();
+    let mut tenants = std::collections::HashSet::from([tenant]);
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) if tenants.contains(&event.tenant) => {
+                    let Ok(json) = serde_json::to_string(&event) else {
+                        continue;
+                    };
+                    if socket.send(Message::Text(json)).await.is_err() {
+                        return;
+                    }
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+                    Ok(WsCommand::Subscribe(tenant)) if !locked || tenant == own_tenant => {
+                        tenants.insert(tenant);
+                    }
+                    Ok(WsCommand::Subscribe(tenant)) => {
+                        tracing::debug!("Ignoring subscription to tenant {}", tenant);
+                    }
+                    Ok(WsCommand::Unsubscribe(tenant)) => {
+                        tenants.remove(&tenant);
+                    }
+                    Err(e) => tracing::debug!("Ignoring WebSocket message: {}", e),
+                },
+                Some(Ok(_)) => {}
+                Some(Err(_)) | None => return,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kimai_ml::usage::UsageLimits;
+
+    fn input(hours: f64) -> MLInputData {
+        let weeks: Vec<serde_json::Value> = (1..=20)
+            .map(|week| {
+                serde_json::json!({
+                    "year": 2024,
+                    "week": week,
+                    "total_minutes": (hours * 60.0) as i32,
+                    "total_hours": hours + (week % 3) as f64,
+                    "total_amount": 0.0,
+                    "project_stats": [],
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "weeks": weeks,
+            "timesheets": [],
+            "projects": [],
+            "settings": {"rate_per_minute": 1.0, "project_settings": {}},
+        }))
+        .unwrap()
+    }
+
+    fn memory_state(cache: std::sync::Arc<dyn PredictionCache>) -> AppState {
+        new_state(
+            std::sync::Arc::new(MemoryStorage::new()),
+            cache,
+            std::sync::Arc::new(MemoryAuditLog::default()),
+        )
+    }
+
+    /// Маршруты сервера с адресом клиента, как у `serve`
+    fn test_router(state: &AppState) -> Router {
+        router(state.clone()).layer(axum::extract::connect_info::MockConnectInfo(
+            std::net::SocketAddr::from(([127, 0, 0, 1], 0)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn audit_log_requires_an_administrator() {
+        let state = AppState {
+            auth: Some(std::sync::Arc::new(
+                JwtAuth::with_secret("secret").admin_tenants(["ops"]),
+            )),
+            ..memory_state(std::sync::Arc::new(MemoryCache::default()))
+        };
+        let identity = |tenant: &str| {
+            Some(axum::Extension(Identity {
+                tenant: tenant.to_string(),
+                user: None,
+            }))
+        };
+        let query = || Query(AuditQuery::default());
+
+        let denied = query_audit_log(State(state.clone()), identity("acme"), query()).await;
+        assert_eq!(denied.unwrap_err().0, StatusCode::FORBIDDEN);
+        assert!(
+            query_audit_log(State(state.clone()), identity("ops"), query())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn same_request_is_cached_per_tenant() {
+        let state = memory_state(std::sync::Arc::new(MemoryCache::default()));
+        let data = input(40.0);
+        let mut trained_at = Vec::new();
+        for tenant in ["acme", "globex"] {
+            let Json(output) = predict(
+                State(state.clone()),
+                Tenant(tenant.to_string()),
+                WeeklyInput(data.clone()),
+            )
+            .await
+            .unwrap();
+            // Второй арендатор обучает свою модель, а не получает ответ первого
+            let model_info = output.forecasting.unwrap().model_info.unwrap();
+            trained_at.push(model_info.trained_at);
+        }
+        assert_ne!(trained_at[0], trained_at[1]);
+
+        let key = |tenant: &str| output_cache_key("predict", tenant, &data).unwrap();
+        assert_ne!(key("acme"), key("globex"));
+        assert!(state.cache.get(&key("acme")).await.unwrap().is_some());
+        assert!(state.cache.get(&key("globex")).await.unwrap().is_some());
+
+        let Json(deletion) =
+            delete_tenant_data(State(state.clone()), None, Path("acme".to_string()))
+                .await
+                .unwrap();
+        assert_eq!(deletion.tenant, "acme");
+        assert!(state.cache.get(&key("acme")).await.unwrap().is_none());
+        assert!(state.cache.get(&key("globex")).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn tenant_deletion_purges_every_tenant_store() {
+        let storage: std::sync::Arc<dyn Storage> = std::sync::Arc::new(MemoryStorage::new());
+        let registry_dir = std::env::temp_dir().join(format!(
+            "kimai-ml-tenant-deletion-{}",
+            rand::random::<u32>()
+        ));
+        let state = AppState {
+            registry: Some(std::sync::Arc::new(ModelRegistry::new(Box::new(
+                LocalArtifactStore::new(&registry_dir),
+            )))),
+            ..new_state(
+                storage.clone(),
+                std::sync::Arc::new(MemoryCache::new(std::time::Duration::ZERO, 0)),
+                std::sync::Arc::new(MemoryAuditLog::default()),
+            )
+        };
+
+        for (tenant, hours) in [("acme", 40.0), ("globex", 10.0)] {
+            let id = Tenant(tenant.to_string());
+            let Json(output) = predict(State(state.clone()), id.clone(), WeeklyInput(input(hours)))
+                .await
+                .unwrap();
+            assert!(output.forecasting.is_some_and(|f| f.model_info.is_some()));
+            let Json(version) = register_model(
+                State(state.clone()),
+                id.clone(),
+                Path("forecasting".to_string()),
+                Query(RegisterParams {
+                    format: ArtifactFormat::Json,
+                }),
+            )
+            .await
+            .unwrap();
+            assert_eq!(version.name, tenants::model_name("forecasting", tenant));
+            storage
+                .save_model(&BaselineProfile::storage_name(tenant), "{}")
+                .await
+                .unwrap();
+            storage
+                .record_learning_error(&kimai_ml::PredictionError {
+                    prediction_type: "weekly_hours".to_string(),
+                    predicted_value: hours,
+                    actual_value: hours + 2.0,
+                    error: 2.0,
+                    context: serde_json::Value::Null,
+                    horizon: 1,
+                    confidence: None,
+                    recorded_at: Some(chrono::Utc::now().to_rfc3339()),
+                    tenant: tenant.to_string(),
+                })
+                .await
+                .unwrap();
+            storage
+                .record_anomaly_feedback(&AnomalyFeedback {
+                    entry_id: 1,
+                    is_anomaly: true,
+                    anomaly_type: None,
+                    comment: None,
+                    detected: true,
+                    score: None,
+                    tenant: tenant.to_string(),
+                })
+                .await
+                .unwrap();
+            storage
+                .record_recommendation_feedback(&RecommendationFeedback {
+                    recommendation_id: "r1".to_string(),
+                    recommendation_type: "workload".to_string(),
+                    action: kimai_ml::types::RecommendationAction::Accepted,
+                    snooze_weeks: None,
+                    tenant: tenant.to_string(),
+                    recorded_at: chrono::Utc::now().to_rfc3339(),
+                })
+                .await
+                .unwrap();
+            drop(sync_learning(&state, tenant).await);
+            state
+                .audit
+                .append(&AuditRecord {
+                    timestamp: chrono::Utc::now().to_rfc3339(),
+                    caller: Some("user-1".to_string()),
+                    method: "POST".to_string(),
+                    path: "/api/predict".to_string(),
+                    status: 200,
+                    request_bytes: 0,
+                    response_bytes: 0,
+                    duration_ms: 1,
+                    model_versions: Default::default(),
+                    learning_update: None,
+                    tenant: tenant.to_string(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let Json(deletion) =
+            delete_tenant_data(State(state.clone()), None, Path("acme".to_string()))
+                .await
+                .unwrap();
+        assert_eq!(deletion.model_versions, 1);
+        assert_eq!(deletion.learning_errors, 1);
+        assert_eq!(deletion.anomaly_feedback, 1);
+        assert_eq!(deletion.recommendation_feedback, 1);
+        assert_eq!(deletion.audit_records, 1);
+        assert!(deletion
+            .snapshots
+            .contains(&tenants::model_name("forecasting", "acme")));
+
+        let registry = state.registry.as_deref().unwrap();
+        for (tenant, remaining) in [("acme", 0), ("globex", 1)] {
+            for name in [
+                tenants::model_name("forecasting", tenant),
+                BaselineProfile::storage_name(tenant),
+                ForecastLedger::storage_name(tenant),
+            ] {
+                let stored = storage.load_model(&name).await.unwrap();
+                assert_eq!(stored.is_some(), remaining > 0, "{}", name);
+            }
+            let name = tenants::model_name("forecasting", tenant);
+            assert_eq!(registry.versions(&name).await.unwrap().len(), remaining);
+            assert_eq!(
+                storage.learning_errors(tenant, 100).await.unwrap().len(),
+                remaining
+            );
+            assert_eq!(
+                storage.anomaly_feedback(tenant, 100).await.unwrap().len(),
+                remaining
+            );
+            assert_eq!(
+                storage
+                    .recommendation_feedback(tenant, 100)
+                    .await
+                    .unwrap()
+                    .len(),
+                remaining
+            );
+            assert_eq!(
+                state.forecasting_models.get(tenant).is_some(),
+                remaining > 0
+            );
+            assert_eq!(state.learning_modules.get(tenant).is_some(), remaining > 0);
+            let audit = state
+                .audit
+                .query(&AuditQuery {
+                    tenant: Some(tenant.to_string()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            assert_eq!(audit.len(), remaining);
+            let retrain_input = state.retrain_inputs.lock().unwrap().contains_key(tenant);
+            assert_eq!(retrain_input, remaining > 0);
+            let usage = &state.usage.report().tenants[tenant].usage;
+            assert_eq!(usage.snapshots.is_empty(), remaining == 0);
+            assert_eq!(usage.model_bytes > 0, remaining > 0);
+        }
+        assert!(!state
+            .model_checksums
+            .lock()
+            .unwrap()
+            .contains_key(&tenants::model_name("forecasting", "acme")));
+
+        // После удаления арендатор начинает с необученной модели
+        let model = forecasting_model(&state, "acme").await;
+        assert!(model.lock().await.model_info().is_none());
+
+        std::fs::remove_dir_all(&registry_dir).ok();
+    }
+
+    #[tokio::test]
+    async fn token_tenant_isolates_models_and_learning() {
+        let state = AppState {
+            auth: Some(std::sync::Arc::new(JwtAuth::with_secret("secret"))),
+            ..memory_state(std::sync::Arc::new(MemoryCache::default()))
+        };
+        let app = test_router(&state);
+        let bearer = |tenant: &str, secret: &str| {
+            let claims = serde_json::json!({
+                "tenant_id": tenant,
+                "sub": "user-1",
+                "exp": chrono::Utc::now().timestamp() + 600,
+            });
+            let token = jsonwebtoken::encode(
+                &jsonwebtoken::Header::default(),
+                &claims,
+                &jsonwebtoken::EncodingKey::from_secret(secret.as_bytes()),
+            )
+            .unwrap();
+            format!("Bearer {}", token)
+        };
+        // Заголовок `X-Tenant-Id` не подменяет арендатора токена
+        let call = |authorization: Option<String>, method: Method, path: &str, body: Body| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri(path)
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("x-tenant-id", "globex");
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+            tower::ServiceExt::oneshot(app.clone(), request.body(body).unwrap())
+        };
+        let json = |value: serde_json::Value| Body::from(value.to_string());
+
+        let missing = call(None, Method::GET, "/api/learning/stats", Body::empty()).await;
+        assert_eq!(missing.unwrap().status(), StatusCode::UNAUTHORIZED);
+        let forged = bearer("acme", "other");
+        let forged = call(
+            Some(forged),
+            Method::GET,
+            "/api/learning/stats",
+            Body::empty(),
+        )
+        .await;
+        assert_eq!(forged.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+        let acme = bearer("acme", "secret");
+        let input = serde_json::to_value(input(40.0)).unwrap();
+        let predicted = call(
+            Some(acme.clone()),
+            Method::POST,
+            "/api/predict",
+            json(input),
+        )
+        .await;
+        assert_eq!(predicted.unwrap().status(), StatusCode::OK);
+        let learned = json(serde_json::json!({
+            "prediction_type": "weekly_hours",
+            "predicted_value": 40.0,
+            "actual_value": 50.0,
+        }));
+        let learned = call(Some(acme.clone()), Method::POST, "/api/learn", learned).await;
+        assert_eq!(learned.unwrap().status(), StatusCode::OK);
+        let feedback = json(serde_json::json!({"entry_id": 1, "is_anomaly": true}));
+        let feedback = call(
+            Some(acme.clone()),
+            Method::POST,
+            "/api/anomalies/feedback",
+            feedback,
+        );
+        assert_eq!(feedback.await.unwrap().status(), StatusCode::OK);
+
+        for (tenant, expected) in [("acme", 1), ("globex", 0)] {
+            let trained = match state.forecasting_models.get(tenant) {
+                Some(model) => model.lock().await.model_info().is_some(),
+                None => false,
+            };
+            assert_eq!(trained, expected > 0, "{}", tenant);
+            let errors = state.storage.learning_errors(tenant, 100).await.unwrap();
+            assert_eq!(errors.len(), expected, "{}", tenant);
+            let feedback = state.storage.anomaly_feedback(tenant, 100).await.unwrap();
+            assert_eq!(feedback.len(), expected, "{}", tenant);
+
+            let response = call(
+                Some(bearer(tenant, "secret")),
+                Method::GET,
+                "/api/learning/stats",
+                Body::empty(),
+            )
+            .await
+            .unwrap();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let stats: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+            assert_eq!(
+                stats["stats"].as_array().unwrap().len(),
+                expected,
+                "{}",
+                tenant
+            );
+            assert_eq!(
+                stats["anomalies"].as_array().unwrap().len(),
+                expected,
+                "{}",
+                tenant
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn request_over_quota_is_rejected_for_its_tenant_only() {
+        let quotas = QuotaConfig {
+            default: UsageLimits {
+                requests_per_day: Some(2),
+                ..UsageLimits::default()
+            },
+            tenants: std::collections::HashMap::new(),
+        };
+        let state = AppState {
+            usage: std::sync::Arc::new(UsageTracker::new(quotas)),
+            ..memory_state(std::sync::Arc::new(MemoryCache::default()))
+        };
+        let app = test_router(&state);
+        let call = |tenant: &str| {
+            let request = Request::get("/api/learning/stats")
+                .header("x-tenant-id", tenant)
+                .body(Body::empty())
+                .unwrap();
+            tower::ServiceExt::oneshot(app.clone(), request)
+        };
+
+        for _ in 0..2 {
+            assert_eq!(call("acme").await.unwrap().status(), StatusCode::OK);
+        }
+        let rejected = call("acme").await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: i64 = rejected.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=86_400).contains(&retry_after), "{}", retry_after);
+
+        // Квота другого арендатора не тронута
+        assert_eq!(call("globex").await.unwrap().status(), StatusCode::OK);
+        let report = state.usage.report();
+        assert_eq!(report.tenants["acme"].usage.requests, 2);
+        assert_eq!(report.tenants["globex"].usage.requests, 1);
+    }
+}
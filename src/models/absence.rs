@@ -0,0 +1,213 @@
+//! Плановое отсутствие (`/api/simulate-absence`).
+//!
+//! Часы проекта в неделю отсутствия - средние часы последних
+//! `SCHEDULE_HISTORY_WEEKS` недель, уменьшенные пропорционально пропущенным
+//! рабочим дням и праздникам. Цели проектов оцениваются тем же моделированием, что и
+//! распределения часов (`ScheduleSimulator`). Выпадающие часы сначала
+//! предлагается отработать за `preparation_weeks` недель до отсутствия в пределах
+//! устойчивой нагрузки, остальное - передать коллегам
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use super::contract;
+use super::schedule::{ScheduleSimulator, SCHEDULE_HISTORY_WEEKS};
+use crate::holidays;
+use crate::types::{
+    AbsenceImpact, AbsenceWeek, MLInputData, PlannedAbsence, ProjectAbsenceImpact, ProposedSchedule,
+};
+
+/// Недель подготовки по умолчанию (`options.preparation_weeks`)
+pub const DEFAULT_PREPARATION_WEEKS: usize = 2;
+
+/// Самое длинное моделируемое отсутствие, дней
+pub const MAX_ABSENCE_DAYS: i64 = 366;
+
+/// Даты отсутствия из запроса; ошибка - неверная дата, конец раньше начала или
+/// отсутствие дольше `MAX_ABSENCE_DAYS`
+pub fn validate(absence: &PlannedAbsence) -> Result<(NaiveDate, NaiveDate), String> {
+    let parse = |date: &str| {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|e| format!("Invalid absence date {}: {}", date, e))
+    };
+    let (from, to) = (parse(&absence.from)?, parse(&absence.to)?);
+    if to < from {
+        return Err(format!(
+            "Absence ends ({}) before it starts ({})",
+            absence.to, absence.from
+        ));
+    }
+    if (to - from).num_days() >= MAX_ABSENCE_DAYS {
+        return Err(format!("Absence is longer than {} days", MAX_ABSENCE_DAYS));
+    }
+    Ok((from, to))
+}
+
+/// Влияние отсутствия `absence` на недели и проекты пользователя
+pub fn simulate_absence(
+    data: &MLInputData,
+    absence: &PlannedAbsence,
+    preparation_weeks: usize,
+) -> Result<AbsenceImpact, String> {
+    let (from, to) = validate(absence)?;
+    let settings = &data.settings;
+    let simulator = ScheduleSimulator::new(&data.weeks, settings);
+    let baseline = project_hours(data);
+    let baseline_total: f64 = {
+        let recent = &data.weeks[data.weeks.len().saturating_sub(SCHEDULE_HISTORY_WEEKS)..];
+        recent.iter().map(|w| w.total_hours).sum::<f64>() / recent.len().max(1) as f64
+    };
+    let baseline_outcome = simulator.simulate(&ProposedSchedule {
+        name: "baseline".to_string(),
+        allocations: baseline.iter().map(|(&id, &hours)| (id, hours)).collect(),
+    });
+
+    let mut weeks = Vec::new();
+    let mut lost: BTreeMap<i32, f64> = baseline.keys().map(|&id| (id, 0.0)).collect();
+    let mut worst_attainment: HashMap<i32, f64> = HashMap::new();
+    let country = settings.holiday_country.as_deref();
+    for ((year, week), absent_days) in absent_days_by_week(from, to, country) {
+        let workdays = workdays(year, week, country);
+        // Праздники сокращают и обычную неделю, в потери входят только дни отсутствия
+        let available_share = workdays.saturating_sub(absent_days) as f64 / 5.0;
+        let absent_share = absent_days as f64 / 5.0;
+        let allocations: HashMap<i32, f64> = baseline
+            .iter()
+            .map(|(&id, &hours)| (id, hours * available_share))
+            .collect();
+        for (id, hours) in &baseline {
+            *lost.entry(*id).or_default() += hours * absent_share;
+        }
+        let outcome = simulator.simulate(&ProposedSchedule {
+            name: format!("{}-W{:02}", year, week),
+            allocations,
+        });
+        for goal in &outcome.goals {
+            let worst = worst_attainment.entry(goal.project_id).or_insert(1.0);
+            *worst = worst.min(goal.attainment_probability);
+        }
+        weeks.push(AbsenceWeek {
+            period: outcome.name,
+            absent_days,
+            available_share,
+            baseline_hours: baseline_total,
+            expected_hours: baseline_total * available_share,
+            goal_attainment_probability: outcome.goal_attainment_probability,
+        });
+    }
+
+    // Проекты с целями отрабатываются заранее первыми, затем - по выпадающим часам
+    let frontload_capacity_hours = preparation_weeks as f64
+        * (contract::sustainable_hours(settings) - baseline_total).max(0.0);
+    let mut order: Vec<i32> = lost.keys().copied().collect();
+    order.sort_by(|a, b| {
+        let goal = |id: &i32| baseline_outcome.goals.iter().any(|g| g.project_id == *id);
+        goal(b).cmp(&goal(a)).then(lost[b].total_cmp(&lost[a]))
+    });
+    let mut remaining_capacity = frontload_capacity_hours;
+    let mut projects = Vec::new();
+    let mut suggestions = Vec::new();
+    for project_id in order {
+        let lost_hours = lost[&project_id];
+        let frontload_hours = lost_hours.min(remaining_capacity);
+        remaining_capacity -= frontload_hours;
+        let handover_hours = lost_hours - frontload_hours;
+        let project_name = project_name(data, project_id);
+        if frontload_hours > 0.0 {
+            suggestions.push(format!(
+                "Отработайте заранее {:.1} ч по проекту '{}' за {} нед. до отсутствия",
+                frontload_hours, project_name, preparation_weeks
+            ));
+        }
+        if handover_hours > 0.0 {
+            suggestions.push(format!(
+                "Передайте {:.1} ч проекта '{}' на время отсутствия",
+                handover_hours, project_name
+            ));
+        }
+        let goal = baseline_outcome
+            .goals
+            .iter()
+            .find(|g| g.project_id == project_id);
+        projects.push(ProjectAbsenceImpact {
+            project_id,
+            project_name,
+            baseline_weekly_hours: baseline[&project_id],
+            lost_hours,
+            goal_hours: goal.map(|g| g.goal_hours),
+            baseline_attainment: goal.map(|g| g.attainment_probability),
+            absence_attainment: goal.and(worst_attainment.get(&project_id).copied()),
+            frontload_hours,
+            handover_hours,
+        });
+    }
+
+    Ok(AbsenceImpact {
+        from: absence.from.clone(),
+        to: absence.to.clone(),
+        absent_days: weeks.iter().map(|w| w.absent_days).sum(),
+        weeks,
+        projects,
+        preparation_weeks,
+        frontload_capacity_hours,
+        suggestions,
+    })
+}
+
+/// Средние часы проектов за последние `SCHEDULE_HISTORY_WEEKS` недель, недели
+/// без работы по проекту - с нулем часов
+fn project_hours(data: &MLInputData) -> BTreeMap<i32, f64> {
+    let recent = &data.weeks[data.weeks.len().saturating_sub(SCHEDULE_HISTORY_WEEKS)..];
+    let mut hours: BTreeMap<i32, f64> = BTreeMap::new();
+    for stats in recent.iter().flat_map(|w| &w.project_stats) {
+        if stats.hours.is_finite() {
+            *hours.entry(stats.project_id).or_default() += stats.hours / recent.len() as f64;
+        }
+    }
+    hours
+}
+
+/// Рабочие дни отсутствия по ISO-неделям; недели только с выходными и
+/// праздниками не входят
+fn absent_days_by_week(
+    from: NaiveDate,
+    to: NaiveDate,
+    country: Option<&str>,
+) -> BTreeMap<(i32, u32), usize> {
+    let holidays: Vec<NaiveDate> = country
+        .and_then(|c| holidays::holiday_dates(c, from, to).ok())
+        .unwrap_or_default();
+    let mut weeks: BTreeMap<(i32, u32), usize> = BTreeMap::new();
+    let mut date = from;
+    while date <= to {
+        let week = date.iso_week();
+        let days = weeks.entry((week.year(), week.week())).or_default();
+        if is_weekday(date) && !holidays.contains(&date) {
+            *days += 1;
+        }
+        date += Duration::days(1);
+    }
+    weeks.retain(|_, days| *days > 0);
+    weeks
+}
+
+/// Будни ISO-недели без праздников
+fn workdays(year: i32, week: u32, country: Option<&str>) -> usize {
+    let holidays = country
+        .and_then(|c| holidays::weekday_holidays(c, year, week).ok())
+        .map_or(0, |dates| dates.len());
+    5usize.saturating_sub(holidays)
+}
+
+fn is_weekday(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+fn project_name(data: &MLInputData, project_id: i32) -> String {
+    data.projects
+        .iter()
+        .find(|p| p.id == project_id)
+        .map(|p| p.name.clone())
+        .unwrap_or_else(|| format!("Проект {}", project_id))
+}
@@ -0,0 +1,119 @@
+//! Контекст отмеченных записей: обычные для проекта, активности и пользователя
+//! длительности и час начала работы по записям запроса, чтобы клиент мог показать
+//! «в 3.2 раза дольше обычной записи по проекту» без собственных расчетов
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::types::{AnomalyContext, AnomalyOutput, TimesheetEntry};
+
+/// Меньше записей в группе - обычное значение группы не приводится
+pub const MIN_CONTEXT_ENTRIES: usize = 3;
+
+/// Обычные значения по записям запроса
+pub struct ContextBaselines {
+    /// Медианная длительность записи (минуты) по проектам и активностям
+    project_durations: HashMap<i32, (f64, usize)>,
+    activity_durations: HashMap<i32, (f64, usize)>,
+    user_duration: Option<f64>,
+    /// Медианный час первой записи дня
+    typical_start_hour: Option<f64>,
+}
+
+impl ContextBaselines {
+    pub fn from_entries(entries: &[TimesheetEntry]) -> Self {
+        let mut projects: HashMap<i32, Vec<f64>> = HashMap::new();
+        let mut activities: HashMap<i32, Vec<f64>> = HashMap::new();
+        let mut durations = Vec::with_capacity(entries.len());
+        // Час первой записи по дням (дата начала записи)
+        let mut day_starts: BTreeMap<&str, i32> = BTreeMap::new();
+        for entry in entries.iter().filter(|e| e.duration > 0) {
+            let duration = entry.duration as f64;
+            durations.push(duration);
+            if let Some(project_id) = entry.project_id {
+                projects.entry(project_id).or_default().push(duration);
+            }
+            if let Some(activity_id) = entry.activity_id {
+                activities.entry(activity_id).or_default().push(duration);
+            }
+            if let Some(day) = entry.begin.get(..10) {
+                let start = day_starts.entry(day).or_insert(entry.hour_of_day);
+                *start = (*start).min(entry.hour_of_day);
+            }
+        }
+
+        let group_medians = |groups: HashMap<i32, Vec<f64>>| {
+            groups
+                .into_iter()
+                .filter(|(_, values)| values.len() >= MIN_CONTEXT_ENTRIES)
+                .filter_map(|(id, mut values)| {
+                    let count = values.len();
+                    median(&mut values).map(|m| (id, (m, count)))
+                })
+                .collect()
+        };
+        let mut starts: Vec<f64> = day_starts.into_values().map(|h| h as f64).collect();
+        Self {
+            project_durations: group_medians(projects),
+            activity_durations: group_medians(activities),
+            user_duration: (durations.len() >= MIN_CONTEXT_ENTRIES)
+                .then(|| median(&mut durations))
+                .flatten(),
+            typical_start_hour: (starts.len() >= MIN_CONTEXT_ENTRIES)
+                .then(|| median(&mut starts))
+                .flatten(),
+        }
+    }
+
+    pub fn context(&self, entry: &TimesheetEntry) -> AnomalyContext {
+        let duration = entry.duration as f64;
+        let ratio = |typical: f64| (typical > 0.0).then(|| duration / typical);
+        let project = entry
+            .project_id
+            .and_then(|id| self.project_durations.get(&id));
+        let activity = entry
+            .activity_id
+            .and_then(|id| self.activity_durations.get(&id));
+        AnomalyContext {
+            duration_minutes: duration,
+            project_median_duration: project.map(|(m, _)| *m),
+            project_duration_ratio: project.and_then(|(m, _)| ratio(*m)),
+            project_entries: project.map_or(0, |(_, count)| *count),
+            activity_median_duration: activity.map(|(m, _)| *m),
+            activity_duration_ratio: activity.and_then(|(m, _)| ratio(*m)),
+            user_median_duration: self.user_duration,
+            user_duration_ratio: self.user_duration.and_then(ratio),
+            start_hour: entry.hour_of_day,
+            typical_start_hour: self.typical_start_hour,
+            // Разница по кругу суток: 23 ч против обычных 1 ч - на 2 ч раньше
+            start_hour_difference: self
+                .typical_start_hour
+                .map(|typical| (entry.hour_of_day as f64 - typical + 12.0).rem_euclid(24.0) - 12.0),
+        }
+    }
+}
+
+/// Добавляет контекст к аномалиям записей (`entry_id` из `entries`); аномалии
+/// недель (`entry_id` 0) и записей не из `entries` остаются без контекста
+pub fn add_context(anomalies: &mut [AnomalyOutput], entries: &[TimesheetEntry]) {
+    let baselines = ContextBaselines::from_entries(entries);
+    let by_id: HashMap<i32, &TimesheetEntry> = entries.iter().map(|e| (e.id, e)).collect();
+    for anomaly in anomalies {
+        if let Some(entry) = by_id.get(&anomaly.entry_id) {
+            anomaly.context = Some(baselines.context(entry));
+        }
+    }
+}
+
+/// Медиана; `None` для пустого набора
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
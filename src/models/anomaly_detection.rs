@@ -1,23 +1,177 @@
 //! Обнаружение аномалий в записях времени
 
 use ndarray::Array2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 
-use crate::preprocessing::FeatureEngineer;
-use crate::types::{AnomalyOutput, TimesheetEntry};
+use crate::cancellation::CancellationToken;
+use crate::compact;
+use crate::float::{to_f64, Float};
+use crate::models::matrix_profile::{discord_days, DEFAULT_PATTERN_WINDOW};
+use crate::preprocessing::feature_engineering::ANOMALY_FEATURES;
+use crate::preprocessing::{FeatureEngineer, FeatureLayout};
+use crate::progress::{no_progress, TrainingProgress};
+use crate::types::{AnomalyOutput, ModelInfo, ModelSize, TimesheetEntry};
+
+/// Версия формата сохраненного детектора аномалий
+pub const ANOMALY_SNAPSHOT_VERSION: u32 = 1;
+
+/// Меньше записей - детектор не обучается
+pub const MIN_TRAINING_ENTRIES: usize = 20;
+
+/// Отбор избыточных деревьев по корреляции оставляет не меньше деревьев
+pub const MIN_PRUNED_TREES: usize = 10;
+
+/// Строк выборки, на которых сравниваются оценки деревьев при отборе
+const PRUNE_SAMPLE_ROWS: usize = 256;
+
+/// Сколько диссонансов матричного профиля подкрепляют аномалии типа `pattern`
+const PATTERN_DISCORDS: usize = 3;
+
+/// Запись длиннее (минуты) - аномалия длительности
+const LONG_SESSION_MINUTES: i32 = 8 * 60;
+
+/// Запись длиннее (минуты) повышает важность аномалии
+const VERY_LONG_SESSION_MINUTES: i32 = 10 * 60;
+
+/// Запись короче (минуты) - аномалия длительности
+const SHORT_SESSION_MINUTES: i32 = 5;
+
+/// Строк в выборке каждого дерева по умолчанию, как в исходной статье
+/// Isolation Forest: большие выборки замедляют обучение и хуже изолируют аномалии
+pub const DEFAULT_MAX_SAMPLES: usize = 256;
+
+/// Пределы числа деревьев при автоподборе по размеру данных
+const MIN_AUTO_TREES: usize = 100;
+const MAX_AUTO_TREES: usize = 300;
+
+/// Сколько выборок деревьев в среднем покрывают каждую запись при автоподборе
+const AUTO_TREE_COVERAGE: usize = 3;
+
+/// Абсолютная оценка изоляции, начиная с которой запись - аномалия
+/// (`ScoringMode::Absolute`); около 0.5 - обычные записи
+pub const DEFAULT_SCORE_THRESHOLD: Float = 0.6;
+
+/// Постоянная Эйлера-Маскерони для средней длины пути
+const EULER_GAMMA: f64 = 0.577_215_664_9;
+
+/// Средняя длина пути неудачного поиска в двоичном дереве из `n` элементов:
+/// нормирующий множитель оценки изоляции
+fn average_path_length(n: usize) -> Float {
+    match n {
+        0 | 1 => 0.0,
+        2 => 1.0,
+        n => {
+            let n = n as Float;
+            2.0 * ((n - 1.0).ln() + EULER_GAMMA as Float) - 2.0 * (n - 1.0) / n
+        }
+    }
+}
+
+/// Размер выборки каждого дерева
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleSize {
+    /// Не больше заданного числа записей
+    Count(usize),
+    /// Доля (0, 1] записей обучения
+    Ratio(Float),
+}
+
+impl Default for SampleSize {
+    fn default() -> Self {
+        Self::Count(DEFAULT_MAX_SAMPLES)
+    }
+}
+
+impl SampleSize {
+    /// Строк в выборке для `rows` записей обучения
+    pub fn resolve(self, rows: usize) -> usize {
+        match self {
+            Self::Count(count) => count.min(rows),
+            Self::Ratio(ratio) => ((rows as Float * ratio) as usize).max(1),
+        }
+    }
+}
+
+/// Число деревьев по размеру данных: каждая запись в среднем попадает в
+/// `AUTO_TREE_COVERAGE` выборок, в пределах `MIN_AUTO_TREES`..=`MAX_AUTO_TREES`
+pub fn auto_tree_count(rows: usize, subsample: usize) -> usize {
+    (AUTO_TREE_COVERAGE * rows)
+        .div_ceil(subsample.max(1))
+        .clamp(MIN_AUTO_TREES, MAX_AUTO_TREES)
+}
+
+/// Предел глубины дерева ceil(log2 ψ): средняя глубина дерева из ψ строк, глубже
+/// изолируются только обычные записи
+pub fn auto_max_depth(subsample: usize) -> usize {
+    (subsample.max(2) as Float).log2().ceil() as usize
+}
+
+/// Как оценки леса превращаются в аномалии
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScoringMode {
+    /// Оценки нормализуются min-max по проверяемым записям, отмечается доля
+    /// `contamination` записей с наибольшими оценками: аномальность относительно
+    /// остальных записей того же запроса
+    #[default]
+    Batch,
+    /// Оценка изоляции 2^(-E[h]/c(ψ)) каждой записи против постоянного порога:
+    /// результат не зависит от соседей по запросу. Для записей, не участвовавших
+    /// в обучении (`split_evaluation_window`)
+    Absolute,
+}
+
+/// Делит записи на обучающие и окно проверки - последние `evaluation_days`
+/// дней до самой поздней даты начала. Записи без даты начала - в обучающих
+pub fn split_evaluation_window(
+    entries: &[TimesheetEntry],
+    evaluation_days: i64,
+) -> (Vec<TimesheetEntry>, Vec<TimesheetEntry>) {
+    let date = |entry: &TimesheetEntry| {
+        entry
+            .begin
+            .get(..10)
+            .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+    };
+    let Some(last) = entries.iter().filter_map(date).max() else {
+        return (entries.to_vec(), Vec::new());
+    };
+    let cutoff = last - chrono::Duration::days(evaluation_days);
+    entries
+        .iter()
+        .cloned()
+        .partition(|entry| date(entry).is_none_or(|d| d <= cutoff))
+}
 
 /// Упрощенный Isolation Forest
+#[derive(Clone, Serialize, Deserialize)]
 pub struct IsolationForest {
     n_trees: usize,
     max_samples: usize,
     max_depth: usize,
     trees: Vec<IsolationTree>,
+    /// Строк в выборке каждого дерева при последнем построении (ψ оценки
+    /// изоляции); 0 у снимков без него - берется `max_samples`
+    #[serde(default)]
+    subsample: usize,
+    /// Зерно генератора; не сохраняется вместе с деревьями
+    #[serde(skip)]
+    seed: Option<u64>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 enum IsolationTree {
     Leaf,
+    /// Лист с несколькими строками выборки (предел глубины или одинаковые
+    /// строки): к длине пути добавляется средняя длина пути дерева из них
+    Bucket {
+        size: usize,
+    },
     Split {
         feature: usize,
-        threshold: f64,
+        threshold: Float,
         left: Box<IsolationTree>,
         right: Box<IsolationTree>,
     },
@@ -30,47 +184,229 @@ impl IsolationForest {
             max_samples,
             max_depth,
             trees: Vec::new(),
+            subsample: 0,
+            seed: None,
         }
     }
 
-    pub fn fit(&mut self, features: &Array2<f64>) {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+    /// Фиксирует зерно генератора для воспроизводимого построения
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn fit(&mut self, features: &Array2<Float>) {
+        // Токен без возможности отмены: ошибка здесь невозможна
+        let _ = self.fit_with_cancellation(features, &CancellationToken::new());
+    }
 
-        for _ in 0..self.n_trees {
-            // Случайная выборка
-            let mut indices: Vec<usize> = (0..features.nrows()).collect();
-            for _ in 0..(features.nrows().saturating_sub(self.max_samples)) {
-                if !indices.is_empty() {
-                    let idx = rng.gen_range(0..indices.len());
-                    indices.remove(idx);
+    /// Построение леса с проверкой токена отмены на каждом узле.
+    /// При отмене ранее построенные деревья сохраняются.
+    pub fn fit_with_cancellation(
+        &mut self,
+        features: &Array2<Float>,
+        token: &CancellationToken,
+    ) -> Result<(), String> {
+        self.fit_with_progress(features, token, &no_progress)
+    }
+
+    /// Построение леса с отчетом о числе построенных деревьев
+    pub fn fit_with_progress(
+        &mut self,
+        features: &Array2<Float>,
+        token: &CancellationToken,
+        progress: &dyn Fn(TrainingProgress),
+    ) -> Result<(), String> {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut trees = Vec::with_capacity(self.n_trees);
+
+        for built in 0..self.n_trees {
+            trees.push(self.sample_tree(features, &mut rng, token)?);
+            progress(TrainingProgress::new("trees", built + 1, self.n_trees));
+        }
+
+        self.trees = trees;
+        self.subsample = self.max_samples.min(features.nrows());
+        Ok(())
+    }
+
+    /// Перестраивает `count` деревьев подряд начиная с `start` (по кругу) на
+    /// `features`; остальные деревья не меняются. При отмене лес прежний
+    pub fn refit_trees(
+        &mut self,
+        features: &Array2<Float>,
+        start: usize,
+        count: usize,
+        token: &CancellationToken,
+        progress: &dyn Fn(TrainingProgress),
+    ) -> Result<(), String> {
+        if self.trees.is_empty() {
+            return Err("Forest not fitted".to_string());
+        }
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(start as u64)),
+            None => StdRng::from_entropy(),
+        };
+        let size = self.trees.len();
+        let count = count.min(size);
+        let mut trees = Vec::with_capacity(count);
+        for built in 0..count {
+            trees.push(self.sample_tree(features, &mut rng, token)?);
+            progress(TrainingProgress::new("trees", built + 1, count));
+        }
+
+        for (offset, tree) in trees.into_iter().enumerate() {
+            self.trees[(start + offset) % size] = tree;
+        }
+        self.subsample = self.max_samples.min(features.nrows());
+        Ok(())
+    }
+
+    /// Деревьев в лесу; после отбора и ограничения памяти может быть меньше
+    /// заданного при создании
+    pub fn tree_count(&self) -> usize {
+        self.trees.len()
+    }
+
+    /// Число узлов, глубина листьев и оценка памяти леса
+    pub fn size(&self) -> ModelSize {
+        let mut nodes = 0;
+        let mut leaves = 0;
+        let mut depth_sum = 0;
+        let mut max_depth = 0;
+        let mut stack: Vec<(&IsolationTree, usize)> = self.trees.iter().map(|t| (t, 0)).collect();
+        while let Some((node, depth)) = stack.pop() {
+            nodes += 1;
+            match node {
+                IsolationTree::Leaf | IsolationTree::Bucket { .. } => {
+                    leaves += 1;
+                    depth_sum += depth;
+                    max_depth = max_depth.max(depth);
+                }
+                IsolationTree::Split { left, right, .. } => {
+                    stack.push((left, depth + 1));
+                    stack.push((right, depth + 1));
                 }
             }
+        }
+        ModelSize {
+            trees: self.trees.len(),
+            nodes,
+            max_depth,
+            mean_depth: if leaves > 0 {
+                depth_sum as f64 / leaves as f64
+            } else {
+                0.0
+            },
+            // Каждый узел - отдельное значение `IsolationTree` (корни - в векторе)
+            memory_bytes: nodes * std::mem::size_of::<IsolationTree>(),
+        }
+    }
 
-            // Построение дерева
-            let tree = self.build_tree(features, &indices, 0);
-            self.trees.push(IsolationTree::Split {
-                feature: 0,
-                threshold: 0.0,
-                left: Box::new(tree),
-                right: Box::new(IsolationTree::Leaf),
-            });
+    /// Отбрасывает деревья, оценки которых на `features` коррелируют с оценками
+    /// уже оставленного дерева не слабее `max_correlation`: такие деревья почти
+    /// не меняют итоговую оценку. Остается не меньше `MIN_PRUNED_TREES`
+    /// деревьев. Возвращает число отброшенных
+    pub fn prune_correlated(&mut self, features: &Array2<Float>, max_correlation: Float) -> usize {
+        if self.trees.len() <= MIN_PRUNED_TREES {
+            return 0;
         }
+        let rows = features.nrows().min(PRUNE_SAMPLE_ROWS);
+        let paths: Vec<Vec<Float>> = self
+            .trees
+            .iter()
+            .map(|tree| {
+                features
+                    .rows()
+                    .into_iter()
+                    .take(rows)
+                    .map(|row| self.path_length(tree, &row.to_owned(), 0))
+                    .collect()
+            })
+            .collect();
+
+        let mut kept: Vec<usize> = Vec::with_capacity(self.trees.len());
+        let mut redundant = Vec::new();
+        for (index, path) in paths.iter().enumerate() {
+            let duplicate = kept
+                .iter()
+                .any(|&k| correlation(&paths[k], path) >= max_correlation);
+            if duplicate {
+                redundant.push(index);
+            } else {
+                kept.push(index);
+            }
+        }
+        // Не меньше MIN_PRUNED_TREES: часть избыточных деревьев возвращается
+        let restore = MIN_PRUNED_TREES.saturating_sub(kept.len());
+        kept.extend(redundant.iter().take(restore));
+
+        let mut keep = vec![false; self.trees.len()];
+        for index in kept {
+            keep[index] = true;
+        }
+        let pruned = keep.iter().filter(|k| !**k).count();
+        let mut keep = keep.into_iter();
+        self.trees.retain(|_| keep.next().unwrap_or(false));
+        pruned
+    }
+
+    /// Отбрасывает деревья с конца, пока оценка памяти больше `max_bytes`;
+    /// остается хотя бы одно дерево. Возвращает число отброшенных
+    pub fn truncate_to_memory(&mut self, max_bytes: usize) -> usize {
+        let node_size = std::mem::size_of::<IsolationTree>();
+        let mut used = 0;
+        let mut keep = 0;
+        for tree in &self.trees {
+            used += tree_nodes(tree) * node_size;
+            if used > max_bytes && keep > 0 {
+                break;
+            }
+            keep += 1;
+        }
+        let dropped = self.trees.len() - keep;
+        self.trees.truncate(keep);
+        dropped
+    }
+
+    /// Дерево на случайной выборке из `max_samples` строк
+    fn sample_tree(
+        &self,
+        features: &Array2<Float>,
+        rng: &mut StdRng,
+        token: &CancellationToken,
+    ) -> Result<IsolationTree, String> {
+        token.check()?;
+
+        // Случайная выборка без повторов за O(max_samples)
+        let rows = features.nrows();
+        let indices = rand::seq::index::sample(rng, rows, self.max_samples.min(rows)).into_vec();
+
+        self.build_tree(features, &indices, 0, rng, token)
     }
 
-    fn build_tree(&self, features: &Array2<f64>, indices: &[usize], depth: usize) -> IsolationTree {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
+    fn build_tree(
+        &self,
+        features: &Array2<Float>,
+        indices: &[usize],
+        depth: usize,
+        rng: &mut StdRng,
+        token: &CancellationToken,
+    ) -> Result<IsolationTree, String> {
+        token.check()?;
 
         if depth >= self.max_depth || indices.len() <= 1 {
-            return IsolationTree::Leaf;
+            return Ok(leaf(indices.len()));
         }
 
         let feature = rng.gen_range(0..features.ncols());
 
         // Случайный порог
-        let mut min_val = f64::INFINITY;
-        let mut max_val = f64::NEG_INFINITY;
+        let mut min_val = Float::INFINITY;
+        let mut max_val = Float::NEG_INFINITY;
         for &idx in indices {
             let val = features[[idx, feature]];
             min_val = min_val.min(val);
@@ -84,18 +420,18 @@ impl IsolationForest {
             .partition(|&&i| features[[i, feature]] < threshold);
 
         if left_indices.is_empty() || right_indices.is_empty() {
-            return IsolationTree::Leaf;
+            return Ok(leaf(indices.len()));
         }
 
-        IsolationTree::Split {
+        Ok(IsolationTree::Split {
             feature,
             threshold,
-            left: Box::new(self.build_tree(features, &left_indices, depth + 1)),
-            right: Box::new(self.build_tree(features, &right_indices, depth + 1)),
-        }
+            left: Box::new(self.build_tree(features, &left_indices, depth + 1, rng, token)?),
+            right: Box::new(self.build_tree(features, &right_indices, depth + 1, rng, token)?),
+        })
     }
 
-    pub fn predict(&self, features: &Array2<f64>) -> Vec<f64> {
+    pub fn predict(&self, features: &Array2<Float>) -> Vec<Float> {
         let mut scores = vec![0.0; features.nrows()];
 
         for tree in &self.trees {
@@ -106,7 +442,7 @@ impl IsolationForest {
         }
 
         // Нормализация
-        let n_trees = self.n_trees as f64;
+        let n_trees = self.trees.len().max(1) as Float;
         for score in &mut scores {
             *score /= n_trees;
         }
@@ -115,14 +451,77 @@ impl IsolationForest {
         scores.iter().map(|s| (-s).exp()).collect()
     }
 
+    /// Оценка изоляции 2^(-E[h]/c(ψ)) в (0, 1]: средняя длина пути, нормированная
+    /// на среднюю длину пути в дереве из ψ строк. Близко к 1 - запись изолируется
+    /// сразу, около 0.5 и ниже - обычная
+    pub fn score_samples(&self, features: &Array2<Float>) -> Vec<Float> {
+        let subsample = if self.subsample > 0 {
+            self.subsample
+        } else {
+            self.max_samples
+        };
+        let normalizer = average_path_length(subsample).max(Float::EPSILON);
+        let n_trees = self.trees.len().max(1) as Float;
+        features
+            .rows()
+            .into_iter()
+            .map(|row| {
+                let row = row.to_owned();
+                let mean_path = self
+                    .trees
+                    .iter()
+                    .map(|tree| self.path_length(tree, &row, 0))
+                    .sum::<Float>()
+                    / n_trees;
+                (2.0 as Float).powf(-mean_path / normalizer)
+            })
+            .collect()
+    }
+
+    /// Доли признаков в изоляции строки: разбиения на пути строки в каждом дереве
+    /// с весом 1/глубина (раннее разбиение отделяет строку от большей части
+    /// выборки). Сумма долей 1; без разбиений на пути - все доли 0
+    pub fn feature_contributions(&self, sample: &ndarray::Array1<Float>) -> Vec<Float> {
+        let mut contributions = vec![0.0; sample.len()];
+        for tree in &self.trees {
+            let mut node = tree;
+            let mut depth = 1;
+            while let IsolationTree::Split {
+                feature,
+                threshold,
+                left,
+                right,
+            } = node
+            {
+                if let Some(contribution) = contributions.get_mut(*feature) {
+                    *contribution += 1.0 / depth as Float;
+                }
+                node = if sample[*feature] < *threshold {
+                    left
+                } else {
+                    right
+                };
+                depth += 1;
+            }
+        }
+        let total: Float = contributions.iter().sum();
+        if total > 0.0 {
+            for contribution in &mut contributions {
+                *contribution /= total;
+            }
+        }
+        contributions
+    }
+
     fn path_length(
         &self,
         node: &IsolationTree,
-        sample: &ndarray::Array1<f64>,
+        sample: &ndarray::Array1<Float>,
         current_depth: usize,
-    ) -> f64 {
+    ) -> Float {
         match node {
-            IsolationTree::Leaf => current_depth as f64,
+            IsolationTree::Leaf => current_depth as Float,
+            IsolationTree::Bucket { size } => current_depth as Float + average_path_length(*size),
             IsolationTree::Split {
                 feature,
                 threshold,
@@ -139,39 +538,403 @@ impl IsolationForest {
     }
 }
 
+/// Скользящее окно последних записей для постепенного переобучения
+#[derive(Clone, Serialize, Deserialize)]
+struct RollingWindow {
+    capacity: usize,
+    /// Доля деревьев, перестраиваемых при каждом обновлении
+    refresh_fraction: Float,
+    entries: VecDeque<TimesheetEntry>,
+    /// Следующее перестраиваемое дерево: деревья обновляются по кругу, от старых
+    next_tree: usize,
+}
+
+/// Оценки леса, нормализованные min-max по записям к [0, 1] (1 - самая аномальная)
+fn batch_scores(scores: &[Float]) -> Vec<Float> {
+    let min_score = scores.iter().copied().fold(Float::INFINITY, Float::min);
+    let max_score = scores.iter().copied().fold(Float::NEG_INFINITY, Float::max);
+    let score_range = max_score - min_score;
+
+    if score_range.abs() < 1e-12 {
+        // All scores equal — treat as non-anomalous (uniform)
+        scores.iter().map(|_| 0.0).collect()
+    } else {
+        scores
+            .iter()
+            .map(|s| {
+                // exp(-E[h]) больше у записей с коротким путем изоляции
+                let v = (s - min_score) / score_range;
+                // clamp
+                v.clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+}
+
+/// Оценка, выше которой остается доля `contamination` записей с наибольшими оценками
+/// (round(contamination * n) записей; при равных оценках на границе - меньше)
+fn contamination_cutoff(scores: &[Float], contamination: Float) -> Float {
+    let mut sorted = scores.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+    let flagged = (contamination * sorted.len() as Float).round() as usize;
+    match sorted.get(flagged) {
+        Some(score) => *score,
+        // Все записи аномальны: порог ниже любой нормализованной оценки
+        None => Float::NEG_INFINITY,
+    }
+}
+
+fn leaf(size: usize) -> IsolationTree {
+    if size > 1 {
+        IsolationTree::Bucket { size }
+    } else {
+        IsolationTree::Leaf
+    }
+}
+
+fn tree_nodes(tree: &IsolationTree) -> usize {
+    match tree {
+        IsolationTree::Leaf | IsolationTree::Bucket { .. } => 1,
+        IsolationTree::Split { left, right, .. } => 1 + tree_nodes(left) + tree_nodes(right),
+    }
+}
+
+/// Корреляция Пирсона; два постоянных ряда одинаковы (1), постоянный и
+/// меняющийся - не связаны (0)
+fn correlation(a: &[Float], b: &[Float]) -> Float {
+    let n = a.len().min(b.len());
+    if n == 0 {
+        return 1.0;
+    }
+    let mean_a = a[..n].iter().sum::<Float>() / n as Float;
+    let mean_b = b[..n].iter().sum::<Float>() / n as Float;
+    let (mut cov, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a[..n].iter().zip(&b[..n]) {
+        cov += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    match (var_a < 1e-12, var_b < 1e-12) {
+        (true, true) => 1.0,
+        (true, false) | (false, true) => 0.0,
+        _ => cov / (var_a * var_b).sqrt(),
+    }
+}
+
+#[derive(Clone)]
 pub struct AnomalyDetector {
     isolation_forest: Option<IsolationForest>,
-    contamination: f64,
+    contamination: Float,
+    /// Число деревьев; `None` - `auto_tree_count`
+    n_trees: Option<usize>,
+    /// Выборка каждого дерева
+    sample_size: SampleSize,
+    /// Предел глубины дерева; `None` - `auto_max_depth`
+    max_depth: Option<usize>,
+    seed: Option<u64>,
     is_trained: bool,
+    trained_at: Option<String>,
+    training_samples: usize,
+    window: Option<RollingWindow>,
+    /// Предел оценки памяти леса, байты
+    memory_limit: Option<usize>,
+    /// Порог корреляции оценок для отбора избыточных деревьев после обучения
+    prune_correlation: Option<Float>,
+    /// Порог оценки изоляции в режиме `ScoringMode::Absolute`
+    score_threshold: Float,
 }
 
 impl AnomalyDetector {
-    pub fn new(contamination: f64) -> Self {
+    /// Алгоритмы, доступные через `settings.features.anomaly_backend`
+    pub const BACKENDS: &'static [&'static str] = &["isolation_forest"];
+
+    pub fn new(contamination: Float) -> Self {
         Self {
             isolation_forest: None,
             contamination,
+            n_trees: None,
+            sample_size: SampleSize::default(),
+            max_depth: None,
+            seed: None,
             is_trained: false,
+            trained_at: None,
+            training_samples: 0,
+            window: None,
+            memory_limit: None,
+            prune_correlation: None,
+            score_threshold: DEFAULT_SCORE_THRESHOLD,
+        }
+    }
+
+    pub fn builder() -> AnomalyDetectorBuilder {
+        AnomalyDetectorBuilder::default()
+    }
+
+    /// Лес без деревьев для `rows` записей обучения: заданные или подобранные
+    /// по размеру данных число деревьев, выборка и глубина
+    fn forest_for(&self, rows: usize) -> IsolationForest {
+        let subsample = self.sample_size.resolve(rows);
+        let forest = IsolationForest::new(
+            self.n_trees
+                .unwrap_or_else(|| auto_tree_count(rows, subsample)),
+            subsample,
+            self.max_depth.unwrap_or_else(|| auto_max_depth(subsample)),
+        );
+        match self.seed {
+            Some(seed) => forest.with_seed(seed),
+            None => forest,
         }
     }
 
     pub fn train(&mut self, entries: &[TimesheetEntry]) -> Result<(), String> {
-        if entries.len() < 20 {
-            return Err("Need at least 20 entries for training".to_string());
+        self.train_with_cancellation(entries, &CancellationToken::new())
+    }
+
+    /// Обучение с возможностью кооперативной отмены; при отмене
+    /// текущий лес не заменяется
+    pub fn train_with_cancellation(
+        &mut self,
+        entries: &[TimesheetEntry],
+        token: &CancellationToken,
+    ) -> Result<(), String> {
+        self.train_with_progress(entries, token, &no_progress)
+    }
+
+    /// Обучение с отменой и отчетами о построении деревьев
+    pub fn train_with_progress(
+        &mut self,
+        entries: &[TimesheetEntry],
+        token: &CancellationToken,
+        progress: &dyn Fn(TrainingProgress),
+    ) -> Result<(), String> {
+        if entries.len() < MIN_TRAINING_ENTRIES {
+            return Err(format!(
+                "Need at least {} entries for training",
+                MIN_TRAINING_ENTRIES
+            ));
         }
 
         let features = FeatureEngineer::extract_anomaly_features(entries);
+        self.train_on_features(&features, token, progress)
+    }
 
-        let max_samples = (entries.len() as f64 * 0.8) as usize;
-        let mut forest = IsolationForest::new(100, max_samples, 10);
-        forest.fit(&features);
+    /// Обучение на уже извлеченных признаках записей
+    /// (`FeatureEngineer::extract_anomaly_features`, например из кэша признаков)
+    pub fn train_on_features(
+        &mut self,
+        features: &Array2<Float>,
+        token: &CancellationToken,
+        progress: &dyn Fn(TrainingProgress),
+    ) -> Result<(), String> {
+        if features.nrows() < MIN_TRAINING_ENTRIES {
+            return Err(format!(
+                "Need at least {} entries for training",
+                MIN_TRAINING_ENTRIES
+            ));
+        }
+        if features.ncols() != ANOMALY_FEATURES.len() {
+            return Err("Feature layout mismatch".to_string());
+        }
+
+        let mut forest = self.forest_for(features.nrows());
+        forest.fit_with_progress(features, token, progress)?;
+        if let Some(threshold) = self.prune_correlation {
+            forest.prune_correlated(features, threshold);
+        }
+        if let Some(limit) = self.memory_limit {
+            forest.truncate_to_memory(limit);
+        }
 
         self.isolation_forest = Some(forest);
         self.is_trained = true;
+        self.trained_at = Some(chrono::Utc::now().to_rfc3339());
+        self.training_samples = features.nrows();
 
         Ok(())
     }
 
+    /// Режим скользящего окна: детектор хранит последние `capacity` записей, а
+    /// `update_window` перестраивает на них долю `refresh_fraction` деревьев.
+    /// Записи окна восстановленного детектора сохраняются (не больше `capacity`)
+    pub fn with_rolling_window(
+        mut self,
+        capacity: usize,
+        refresh_fraction: Float,
+    ) -> Result<Self, String> {
+        if capacity < MIN_TRAINING_ENTRIES {
+            return Err(format!(
+                "Rolling window must hold at least {} entries, got {}",
+                MIN_TRAINING_ENTRIES, capacity
+            ));
+        }
+        if !(refresh_fraction > 0.0 && refresh_fraction <= 1.0) {
+            return Err(format!("Invalid refresh fraction: {}", refresh_fraction));
+        }
+
+        let mut window = self.window.take().unwrap_or(RollingWindow {
+            capacity,
+            refresh_fraction,
+            entries: VecDeque::new(),
+            next_tree: 0,
+        });
+        window.capacity = capacity;
+        window.refresh_fraction = refresh_fraction;
+        while window.entries.len() > capacity {
+            window.entries.pop_front();
+        }
+        self.window = Some(window);
+        Ok(self)
+    }
+
+    /// Пределы размера леса: `memory_limit` - оценка памяти в байтах (лишние
+    /// деревья отбрасываются, в том числе у уже обученного детектора),
+    /// `prune_correlation` - порог корреляции в (0, 1] для отбора избыточных
+    /// деревьев после обучения (`IsolationForest::prune_correlated`)
+    pub fn with_limits(
+        mut self,
+        memory_limit: Option<usize>,
+        prune_correlation: Option<Float>,
+    ) -> Result<Self, String> {
+        if memory_limit == Some(0) {
+            return Err("Memory limit must be positive".to_string());
+        }
+        if let Some(threshold) = prune_correlation {
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                return Err(format!("Invalid prune correlation: {}", threshold));
+            }
+        }
+        self.memory_limit = memory_limit;
+        self.prune_correlation = prune_correlation;
+        if let (Some(limit), Some(forest)) = (memory_limit, self.isolation_forest.as_mut()) {
+            forest.truncate_to_memory(limit);
+        }
+        Ok(self)
+    }
+
+    /// Отключает режим скользящего окна; записи окна отбрасываются
+    pub fn without_rolling_window(mut self) -> Self {
+        self.window = None;
+        self
+    }
+
+    /// Записей в скользящем окне; `None` без режима окна
+    pub fn window_len(&self) -> Option<usize> {
+        self.window.as_ref().map(|w| w.entries.len())
+    }
+
+    /// Добавляет записи в скользящее окно (запись с тем же `id` заменяется,
+    /// старые вытесняются) и перестраивает на окне самые старые
+    /// `refresh_fraction` деревьев. Необученный детектор обучается на окне
+    /// целиком, как только в нем наберется `MIN_TRAINING_ENTRIES` записей.
+    /// Возвращает число перестроенных деревьев: 0 без новых записей или
+    /// пока окно мало
+    pub fn update_window(
+        &mut self,
+        entries: &[TimesheetEntry],
+        token: &CancellationToken,
+        progress: &dyn Fn(TrainingProgress),
+    ) -> Result<usize, String> {
+        let window = self
+            .window
+            .as_mut()
+            .ok_or("Rolling window is not enabled")?;
+        let mut positions: HashMap<i32, usize> = window
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, e)| (e.id, index))
+            .collect();
+        let mut changed = false;
+        for entry in entries {
+            match positions.get(&entry.id) {
+                Some(&index) => {
+                    let existing = &mut window.entries[index];
+                    changed |= existing.duration != entry.duration || existing.begin != entry.begin;
+                    *existing = entry.clone();
+                }
+                None => {
+                    positions.insert(entry.id, window.entries.len());
+                    window.entries.push_back(entry.clone());
+                    changed = true;
+                }
+            }
+        }
+        while window.entries.len() > window.capacity {
+            window.entries.pop_front();
+        }
+        if !changed || window.entries.len() < MIN_TRAINING_ENTRIES {
+            return Ok(0);
+        }
+
+        let window_entries: Vec<TimesheetEntry> = window.entries.iter().cloned().collect();
+        let refit = match self.isolation_forest.as_mut() {
+            Some(forest) if forest.tree_count() > 0 => forest,
+            _ => {
+                self.train_with_progress(&window_entries, token, progress)?;
+                return Ok(self
+                    .isolation_forest
+                    .as_ref()
+                    .map_or(0, IsolationForest::tree_count));
+            }
+        };
+
+        let size = refit.tree_count();
+        let count = ((size as Float * window.refresh_fraction).ceil() as usize).clamp(1, size);
+        let start = window.next_tree % size;
+        let features = FeatureEngineer::extract_anomaly_features(&window_entries);
+        refit.max_samples = self.sample_size.resolve(window_entries.len());
+        refit.refit_trees(&features, start, count, token, progress)?;
+        if let Some(limit) = self.memory_limit {
+            refit.truncate_to_memory(limit);
+        }
+
+        window.next_tree = (start + count) % refit.tree_count();
+        self.trained_at = Some(chrono::Utc::now().to_rfc3339());
+        self.training_samples = window_entries.len();
+        Ok(count)
+    }
+
+    /// Сведения об обученной модели; `None` до обучения
+    pub fn model_info(&self) -> Option<ModelInfo> {
+        if !self.is_trained {
+            return None;
+        }
+        Some(ModelInfo {
+            model_version: format!(
+                "{}+anomaly.v{}",
+                env!("CARGO_PKG_VERSION"),
+                ANOMALY_SNAPSHOT_VERSION
+            ),
+            trained_at: self.trained_at.clone(),
+            training_samples: self.training_samples,
+            algorithms: vec!["isolation_forest".to_string()],
+            correction_factor: None,
+            size: self.isolation_forest.as_ref().map(IsolationForest::size),
+        })
+    }
+
     pub fn detect(&self, entries: &[TimesheetEntry]) -> Result<Vec<AnomalyOutput>, String> {
+        self.detect_with_thresholds(entries, &HashMap::new())
+    }
+
+    /// Поиск аномалий со сдвигом порога `contamination` для отдельных типов
+    /// (например, `LearningModule::threshold_shifts` по отзывам пользователей)
+    pub fn detect_with_thresholds(
+        &self,
+        entries: &[TimesheetEntry],
+        threshold_shifts: &HashMap<String, f64>,
+    ) -> Result<Vec<AnomalyOutput>, String> {
+        self.detect_with_mode(entries, threshold_shifts, ScoringMode::Batch)
+    }
+
+    /// Поиск аномалий с выбранным способом оценки; сдвиги порога по типам
+    /// действуют в обоих режимах
+    pub fn detect_with_mode(
+        &self,
+        entries: &[TimesheetEntry],
+        threshold_shifts: &HashMap<String, f64>,
+        mode: ScoringMode,
+    ) -> Result<Vec<AnomalyOutput>, String> {
         if !self.is_trained {
             return Err("Detector not trained".to_string());
         }
@@ -181,49 +944,69 @@ impl AnomalyDetector {
         }
 
         let features = FeatureEngineer::extract_anomaly_features(entries);
-        let forest = self
-            .isolation_forest
-            .as_ref()
-            .ok_or("Forest not available")?;
+        self.detect_with_features(entries, &features, threshold_shifts, mode)
+    }
+
+    /// Поиск аномалий по уже извлеченным признакам `entries` (строка на запись)
+    pub fn detect_with_features(
+        &self,
+        entries: &[TimesheetEntry],
+        features: &Array2<Float>,
+        threshold_shifts: &HashMap<String, f64>,
+        mode: ScoringMode,
+    ) -> Result<Vec<AnomalyOutput>, String> {
+        if !self.is_trained {
+            return Err("Detector not trained".to_string());
+        }
+        if features.nrows() != entries.len() {
+            return Err(format!(
+                "Feature rows ({}) do not match entries ({})",
+                features.nrows(),
+                entries.len()
+            ));
+        }
 
-        let scores = forest.predict(&features);
+        if entries.is_empty() {
+            return Ok(Vec::new());
+        }
 
-        // Нормализация scores к [0, 1]
-        let min_score = scores.iter().copied().fold(f64::INFINITY, f64::min);
-        let max_score = scores.iter().copied().fold(f64::NEG_INFINITY, f64::max);
-        let score_range = max_score - min_score;
+        let (normalized_scores, base_threshold) = self.entry_scores(features, mode)?;
 
-        let normalized_scores: Vec<f64> = if score_range.abs() < 1e-12 {
-            // All scores equal — treat as non-anomalous (uniform)
-            scores.iter().map(|_| 0.0).collect()
-        } else {
-            scores
-                .iter()
-                .map(|s| {
-                    let v = 1.0 - (s - min_score) / score_range;
-                    // clamp
-                    v.clamp(0.0, 1.0)
-                })
-                .collect()
-        };
+        // Недельные окна, не похожие ни на одно другое: дополнительный признак
+        // аномалий паттерна работы
+        let discords = discord_days(entries, DEFAULT_PATTERN_WINDOW, PATTERN_DISCORDS);
 
         let mut anomalies = Vec::new();
 
         for (i, entry) in entries.iter().enumerate() {
             let score = normalized_scores[i];
+            let anomaly_type = self.classify_anomaly_type(entry);
 
-            // Порог для аномалии (на основе contamination)
-            if score > self.contamination {
-                let severity = self.determine_severity(entry, score);
-                let anomaly_type = self.classify_anomaly_type(entry);
-                let reason = self.generate_reason(entry, score);
+            // Порог для аномалии: граница доли contamination или абсолютный порог
+            let shift = threshold_shifts.get(&anomaly_type).copied().unwrap_or(0.0);
+            let threshold = base_threshold + shift as Float;
+            if score > threshold {
+                let discord = entry
+                    .begin
+                    .get(..10)
+                    .and_then(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                    .and_then(|d| discords.get(&d))
+                    .filter(|_| anomaly_type == "pattern");
+                let severity_score = score + discord.map_or(0.0, |s| *s as Float * 0.2);
+                let severity = self.determine_severity(entry, severity_score);
+                let mut reason = self.generate_reason(entry, score);
+                if discord.is_some() {
+                    reason.push_str("; Нетипичная последовательность рабочих дней");
+                }
 
                 anomalies.push(AnomalyOutput {
                     entry_id: entry.id,
                     r#type: anomaly_type,
                     severity,
                     reason,
-                    score,
+                    score: to_f64(score),
+                    period: None,
+                    context: None,
                 });
             }
         }
@@ -231,12 +1014,45 @@ impl AnomalyDetector {
         Ok(anomalies)
     }
 
-    fn determine_severity(&self, entry: &TimesheetEntry, score: f64) -> String {
+    /// Оценки записей (строк `features`) и порог до сдвигов по типам аномалий:
+    /// граница доли `contamination` или абсолютный порог
+    pub fn entry_scores(
+        &self,
+        features: &Array2<Float>,
+        mode: ScoringMode,
+    ) -> Result<(Vec<Float>, Float), String> {
+        let forest = self
+            .isolation_forest
+            .as_ref()
+            .ok_or("Forest not available")?;
+
+        let (scores, threshold) = match mode {
+            ScoringMode::Batch => {
+                let scores = batch_scores(&forest.predict(features));
+                let cutoff = contamination_cutoff(&scores, self.contamination);
+                (scores, cutoff)
+            }
+            ScoringMode::Absolute => (forest.score_samples(features), self.score_threshold),
+        };
+        debug_assert!(
+            scores.iter().all(|s| s.is_finite()),
+            "non-finite anomaly scores"
+        );
+        Ok((scores, threshold))
+    }
+
+    /// Доли признаков в изоляции записи лесом (сумма 1); `None` без обученного леса
+    pub fn feature_contributions(&self, sample: &ndarray::Array1<Float>) -> Option<Vec<Float>> {
+        let forest = self.isolation_forest.as_ref()?;
+        Some(forest.feature_contributions(sample))
+    }
+
+    fn determine_severity(&self, entry: &TimesheetEntry, score: Float) -> String {
         let mut severity_score = score;
 
-        if entry.duration > 10 * 60 {
+        if entry.duration > VERY_LONG_SESSION_MINUTES {
             severity_score += 0.2;
-        } else if entry.duration < 5 {
+        } else if entry.duration < SHORT_SESSION_MINUTES {
             severity_score += 0.1;
         }
 
@@ -253,8 +1069,9 @@ impl AnomalyDetector {
         }
     }
 
-    fn classify_anomaly_type(&self, entry: &TimesheetEntry) -> String {
-        if entry.duration > 8 * 60 || entry.duration < 5 {
+    /// Тип аномалии записи (`duration`, `time`, `pattern`): по нему сдвигается порог
+    pub fn classify_anomaly_type(&self, entry: &TimesheetEntry) -> String {
+        if entry.duration > LONG_SESSION_MINUTES || entry.duration < SHORT_SESSION_MINUTES {
             "duration".to_string()
         } else if entry.hour_of_day < 6 || entry.hour_of_day > 23 {
             "time".to_string()
@@ -263,15 +1080,15 @@ impl AnomalyDetector {
         }
     }
 
-    fn generate_reason(&self, entry: &TimesheetEntry, score: f64) -> String {
+    fn generate_reason(&self, entry: &TimesheetEntry, score: Float) -> String {
         let mut reasons = Vec::new();
 
-        if entry.duration > 8 * 60 {
+        if entry.duration > LONG_SESSION_MINUTES {
             reasons.push(format!(
                 "Очень длинная сессия: {:.1} часов",
-                entry.duration as f64 / 60.0
+                entry.duration as Float / 60.0
             ));
-        } else if entry.duration < 5 {
+        } else if entry.duration < SHORT_SESSION_MINUTES {
             reasons.push(format!("Очень короткая сессия: {} минут", entry.duration));
         }
 
@@ -293,8 +1110,459 @@ impl AnomalyDetector {
     }
 }
 
+/// Сохраняемое состояние обученного детектора
+#[derive(Serialize, Deserialize)]
+struct AnomalySnapshot {
+    version: u32,
+    layout: FeatureLayout,
+    contamination: Float,
+    isolation_forest: IsolationForest,
+    #[serde(default)]
+    trained_at: Option<String>,
+    #[serde(default)]
+    training_samples: usize,
+    #[serde(default)]
+    window: Option<RollingWindow>,
+}
+
+impl AnomalyDetector {
+    /// Сериализация обученного детектора в JSON
+    pub fn to_json(&self) -> Result<String, String> {
+        let forest = self
+            .isolation_forest
+            .as_ref()
+            .ok_or("Detector not trained")?;
+
+        #[derive(Serialize)]
+        struct SnapshotRef<'a> {
+            version: u32,
+            layout: FeatureLayout,
+            contamination: Float,
+            isolation_forest: &'a IsolationForest,
+            trained_at: &'a Option<String>,
+            training_samples: usize,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            window: &'a Option<RollingWindow>,
+        }
+
+        serde_json::to_string(&SnapshotRef {
+            version: ANOMALY_SNAPSHOT_VERSION,
+            layout: FeatureEngineer::anomaly_layout(),
+            contamination: self.contamination,
+            isolation_forest: forest,
+            trained_at: &self.trained_at,
+            training_samples: self.training_samples,
+            window: &self.window,
+        })
+        .map_err(|e| format!("Serialization error: {}", e))
+    }
+
+    /// Восстановление детектора из JSON с проверкой совместимости
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let snapshot: AnomalySnapshot =
+            serde_json::from_str(json).map_err(|e| format!("Deserialization error: {}", e))?;
+        Self::from_snapshot(snapshot)
+    }
+
+    /// Обученный детектор в компактном двоичном формате (`compact`): деревья
+    /// с порогами `f32`, без записей скользящего окна
+    pub fn to_compact(&self) -> Result<Vec<u8>, String> {
+        let forest = self
+            .isolation_forest
+            .as_ref()
+            .ok_or("Detector not trained")?;
+        compact::encode(
+            "anomaly",
+            &CompactAnomalySnapshot {
+                version: ANOMALY_SNAPSHOT_VERSION,
+                layout: FeatureEngineer::anomaly_layout(),
+                contamination: self.contamination,
+                n_trees: forest.n_trees,
+                max_samples: forest.max_samples,
+                max_depth: forest.max_depth,
+                trees: forest.trees.iter().map(encode_tree).collect(),
+                trained_at: self.trained_at.clone(),
+                training_samples: self.training_samples,
+            },
+        )
+    }
+
+    /// Восстановление детектора из компактного снимка; режим окна задается
+    /// заново (`with_rolling_window`)
+    pub fn from_compact(data: &[u8]) -> Result<Self, String> {
+        let snapshot: CompactAnomalySnapshot = compact::decode("anomaly", data)?;
+        let trees = snapshot
+            .trees
+            .iter()
+            .map(|tree| decode_tree(&mut tree.as_slice(), snapshot.layout.features.len(), 0))
+            .collect::<Result<Vec<_>, String>>()?;
+        Self::from_snapshot(AnomalySnapshot {
+            version: snapshot.version,
+            layout: snapshot.layout,
+            contamination: snapshot.contamination,
+            isolation_forest: IsolationForest {
+                trees,
+                ..IsolationForest::new(snapshot.n_trees, snapshot.max_samples, snapshot.max_depth)
+            },
+            trained_at: snapshot.trained_at,
+            training_samples: snapshot.training_samples,
+            window: None,
+        })
+    }
+
+    fn from_snapshot(snapshot: AnomalySnapshot) -> Result<Self, String> {
+        if snapshot.version != ANOMALY_SNAPSHOT_VERSION {
+            return Err(format!(
+                "Unsupported anomaly snapshot version: {} (expected {})",
+                snapshot.version, ANOMALY_SNAPSHOT_VERSION
+            ));
+        }
+        if snapshot.layout != FeatureEngineer::anomaly_layout() {
+            return Err("Feature layout mismatch".to_string());
+        }
+
+        Ok(Self {
+            n_trees: Some(snapshot.isolation_forest.n_trees),
+            max_depth: Some(snapshot.isolation_forest.max_depth),
+            isolation_forest: Some(snapshot.isolation_forest),
+            is_trained: true,
+            trained_at: snapshot.trained_at,
+            training_samples: snapshot.training_samples,
+            window: snapshot.window,
+            ..Self::new(snapshot.contamination)
+        })
+    }
+}
+
+/// Компактный снимок детектора: деревья в раскладке `encode_tree`
+#[derive(Serialize, Deserialize)]
+struct CompactAnomalySnapshot {
+    version: u32,
+    layout: FeatureLayout,
+    contamination: Float,
+    n_trees: usize,
+    max_samples: usize,
+    max_depth: usize,
+    trees: Vec<Vec<u8>>,
+    trained_at: Option<String>,
+    training_samples: usize,
+}
+
+/// Глубже дерево из снимка не читается (защита стека от поврежденных данных)
+const MAX_DECODED_DEPTH: usize = 256;
+
+/// Дерево в прямом порядке обхода: узел - номер признака + 1 (varint, 0 -
+/// лист, число признаков + 1 - лист из нескольких строк и затем их число),
+/// у разбиения - порог `f32` и затем левое и правое поддеревья
+#[allow(clippy::unnecessary_cast)]
+fn encode_tree(tree: &IsolationTree) -> Vec<u8> {
+    fn encode(node: &IsolationTree, out: &mut Vec<u8>) {
+        match node {
+            IsolationTree::Leaf => out.push(0),
+            IsolationTree::Bucket { size } => {
+                compact::write_varint(ANOMALY_FEATURES.len() as u64 + 1, out);
+                compact::write_varint(*size as u64, out);
+            }
+            IsolationTree::Split {
+                feature,
+                threshold,
+                left,
+                right,
+            } => {
+                compact::write_varint(*feature as u64 + 1, out);
+                out.extend_from_slice(&(*threshold as f32).to_le_bytes());
+                encode(left, out);
+                encode(right, out);
+            }
+        }
+    }
+    let mut out = Vec::new();
+    encode(tree, &mut out);
+    out
+}
+
+fn decode_tree(data: &mut &[u8], features: usize, depth: usize) -> Result<IsolationTree, String> {
+    if depth > MAX_DECODED_DEPTH {
+        return Err("Compact tree is too deep".to_string());
+    }
+    let feature = compact::read_varint(data)?;
+    if feature == 0 {
+        return Ok(IsolationTree::Leaf);
+    }
+    if feature == features as u64 + 1 {
+        let size = compact::read_varint(data)? as usize;
+        return Ok(leaf(size));
+    }
+    if feature > features as u64 {
+        return Err(format!(
+            "Compact tree splits on unknown feature {}",
+            feature - 1
+        ));
+    }
+    let (threshold, rest) = data
+        .split_first_chunk::<4>()
+        .ok_or("Truncated compact tree")?;
+    *data = rest;
+    Ok(IsolationTree::Split {
+        feature: (feature - 1) as usize,
+        threshold: f32::from_le_bytes(*threshold) as Float,
+        left: Box::new(decode_tree(data, features, depth + 1)?),
+        right: Box::new(decode_tree(data, features, depth + 1)?),
+    })
+}
+
 impl Default for AnomalyDetector {
     fn default() -> Self {
         Self::new(0.1)
     }
 }
+
+/// Построитель `AnomalyDetector`
+#[derive(Debug, Clone)]
+pub struct AnomalyDetectorBuilder {
+    contamination: Float,
+    n_trees: Option<usize>,
+    sample_size: SampleSize,
+    max_depth: Option<usize>,
+    seed: Option<u64>,
+    rolling_window: Option<(usize, Float)>,
+    memory_limit: Option<usize>,
+    prune_correlation: Option<Float>,
+    score_threshold: Float,
+}
+
+impl Default for AnomalyDetectorBuilder {
+    fn default() -> Self {
+        Self {
+            contamination: 0.1,
+            n_trees: None,
+            sample_size: SampleSize::default(),
+            max_depth: None,
+            seed: None,
+            rolling_window: None,
+            memory_limit: None,
+            prune_correlation: None,
+            score_threshold: DEFAULT_SCORE_THRESHOLD,
+        }
+    }
+}
+
+impl AnomalyDetectorBuilder {
+    /// Ожидаемая доля аномалий: в пакетном режиме отмечаются записи с наибольшими
+    /// оценками в этой доле
+    pub fn contamination(mut self, contamination: Float) -> Self {
+        self.contamination = contamination;
+        self
+    }
+
+    /// Постоянное число деревьев вместо подбора по размеру данных
+    pub fn trees(mut self, n_trees: usize) -> Self {
+        self.n_trees = Some(n_trees);
+        self
+    }
+
+    /// Доля записей (0, 1] в выборке каждого дерева вместо `DEFAULT_MAX_SAMPLES`
+    pub fn sample_ratio(mut self, ratio: Float) -> Self {
+        self.sample_size = SampleSize::Ratio(ratio);
+        self
+    }
+
+    /// Не больше `count` записей в выборке каждого дерева
+    pub fn max_samples(mut self, count: usize) -> Self {
+        self.sample_size = SampleSize::Count(count);
+        self
+    }
+
+    /// Постоянный предел глубины вместо ceil(log2 ψ)
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Скользящее окно из `capacity` записей с перестройкой доли
+    /// `refresh_fraction` деревьев (см. `AnomalyDetector::with_rolling_window`)
+    pub fn rolling_window(mut self, capacity: usize, refresh_fraction: Float) -> Self {
+        self.rolling_window = Some((capacity, refresh_fraction));
+        self
+    }
+
+    /// Предел оценки памяти леса в байтах (см. `AnomalyDetector::with_limits`)
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Отбор избыточных деревьев после обучения с порогом корреляции оценок
+    pub fn prune_correlation(mut self, threshold: Float) -> Self {
+        self.prune_correlation = Some(threshold);
+        self
+    }
+
+    /// Порог оценки изоляции в (0, 1) для `ScoringMode::Absolute`
+    pub fn score_threshold(mut self, threshold: Float) -> Self {
+        self.score_threshold = threshold;
+        self
+    }
+
+    pub fn build(self) -> Result<AnomalyDetector, String> {
+        if !(0.0..=1.0).contains(&self.contamination) {
+            return Err(format!("Invalid contamination: {}", self.contamination));
+        }
+        if self.n_trees == Some(0) {
+            return Err("Number of trees must be positive".to_string());
+        }
+        match self.sample_size {
+            SampleSize::Ratio(ratio) if !(ratio > 0.0 && ratio <= 1.0) => {
+                return Err(format!("Invalid sample ratio: {}", ratio));
+            }
+            SampleSize::Count(0) => return Err("Sample size must be positive".to_string()),
+            _ => {}
+        }
+        if !(self.score_threshold > 0.0 && self.score_threshold < 1.0) {
+            return Err(format!("Invalid score threshold: {}", self.score_threshold));
+        }
+
+        let detector = AnomalyDetector {
+            n_trees: self.n_trees,
+            sample_size: self.sample_size,
+            max_depth: self.max_depth,
+            seed: self.seed,
+            score_threshold: self.score_threshold,
+            ..AnomalyDetector::new(self.contamination)
+        }
+        .with_limits(self.memory_limit, self.prune_correlation)?;
+        match self.rolling_window {
+            Some((capacity, fraction)) => detector.with_rolling_window(capacity, fraction),
+            None => Ok(detector),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_parameters_for_small_input() {
+        let forest = AnomalyDetector::new(0.1).forest_for(50);
+        assert_eq!(forest.max_samples, 50);
+        assert_eq!(forest.n_trees, MIN_AUTO_TREES);
+        assert_eq!(forest.max_depth, 6);
+    }
+
+    #[test]
+    fn auto_parameters_for_medium_input() {
+        let forest = AnomalyDetector::new(0.1).forest_for(10_000);
+        assert_eq!(forest.max_samples, DEFAULT_MAX_SAMPLES);
+        assert_eq!(forest.n_trees, 118);
+        assert_eq!(forest.max_depth, 8);
+    }
+
+    #[test]
+    fn auto_parameters_for_large_input() {
+        let forest = AnomalyDetector::new(0.1).forest_for(1_000_000);
+        assert_eq!(forest.max_samples, DEFAULT_MAX_SAMPLES);
+        assert_eq!(forest.n_trees, MAX_AUTO_TREES);
+        assert_eq!(forest.max_depth, 8);
+    }
+
+    #[test]
+    fn explicit_parameters_override_auto() {
+        let detector = AnomalyDetector::builder()
+            .trees(7)
+            .max_samples(32)
+            .build()
+            .unwrap();
+        let forest = detector.forest_for(10_000);
+        assert_eq!(forest.n_trees, 7);
+        assert_eq!(forest.max_samples, 32);
+        assert_eq!(forest.max_depth, 5);
+    }
+
+    fn flagged(scores: &[Float], contamination: Float) -> Vec<bool> {
+        let cutoff = contamination_cutoff(scores, contamination);
+        scores.iter().map(|s| *s > cutoff).collect()
+    }
+
+    #[test]
+    fn cutoff_flags_contamination_share_of_distinct_scores() {
+        for n in [10, 37, 200] {
+            let raw: Vec<Float> = (0..n).map(|i| ((i * 7919) % n) as Float).collect();
+            let scores = batch_scores(&raw);
+            for contamination in [0.0, 0.05, 0.1, 0.25, 1.0] {
+                let expected = (contamination * n as Float).round() as usize;
+                let count = flagged(&scores, contamination)
+                    .iter()
+                    .filter(|f| **f)
+                    .count();
+                assert_eq!(
+                    count, expected,
+                    "n = {}, contamination = {}",
+                    n, contamination
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cutoff_gives_tied_scores_one_verdict() {
+        // Три равные наибольшие оценки при квоте в две записи
+        let scores = [1.0, 1.0, 1.0, 0.5, 0.4, 0.3, 0.2, 0.1, 0.05, 0.0];
+        let verdicts = flagged(&scores, 0.2);
+        assert!(verdicts[..3].iter().all(|v| *v == verdicts[0]));
+        assert!(verdicts.iter().filter(|v| **v).count() <= 2);
+
+        // Равные оценки ниже границы квоты не отмечаются
+        let scores = [1.0, 0.9, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5];
+        assert_eq!(
+            flagged(&scores, 0.2),
+            [true, true, false, false, false, false, false, false, false, false]
+        );
+    }
+
+    #[test]
+    fn equal_scores_flag_nothing() {
+        let scores = batch_scores(&[0.7; 20]);
+        assert!(scores.iter().all(|s| *s == 0.0));
+        assert!(flagged(&scores, 0.1).iter().all(|f| !*f));
+    }
+
+    #[test]
+    fn detector_flags_contamination_share() {
+        let data = crate::synthetic::generate(crate::synthetic::Profile::BurstyAgency, 26, 7);
+        let entries = &data.input.timesheets;
+        let detector = {
+            let mut detector = AnomalyDetector::builder()
+                .contamination(0.1)
+                .seed(7)
+                .build()
+                .unwrap();
+            detector.train(entries).unwrap();
+            detector
+        };
+        let features = crate::preprocessing::FeatureEngineer::extract_anomaly_features(entries);
+        let (scores, cutoff) = detector
+            .entry_scores(&features, ScoringMode::Batch)
+            .unwrap();
+        let count = scores.iter().filter(|s| **s > cutoff).count();
+        let expected = (0.1 * entries.len() as Float).round() as usize;
+        // Равные оценки на границе могут только уменьшить число отмеченных
+        assert!(count <= expected, "{} > {}", count, expected);
+        assert!(count * 10 >= expected * 9, "{} of {}", count, expected);
+    }
+
+    #[test]
+    fn fit_on_large_input_builds_all_trees() {
+        let rows = 200_000;
+        let features = Array2::from_shape_fn((rows, 3), |(i, j)| ((i * 31 + j * 7) % 97) as Float);
+        let mut forest = IsolationForest::new(MAX_AUTO_TREES, DEFAULT_MAX_SAMPLES, 8).with_seed(1);
+        forest.fit(&features);
+        assert_eq!(forest.tree_count(), MAX_AUTO_TREES);
+        assert_eq!(forest.subsample, DEFAULT_MAX_SAMPLES);
+    }
+}
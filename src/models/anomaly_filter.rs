@@ -0,0 +1,307 @@
+//! Фильтрация и постраничная выдача найденных аномалий (`/api/detect-anomalies`)
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{Datelike, NaiveDate};
+use serde_json::Value as JsonValue;
+
+use crate::models::forecasting::{series_trend, TREND_WEEKS};
+use crate::types::{
+    AnomalyOutput, AnomalyPage, AnomalySummary, AnomalyWeekRate, ProjectAnomalies, TimesheetEntry,
+};
+
+/// Сколько проектов приводится в `AnomalySummary::top_projects`
+pub const TOP_ANOMALY_PROJECTS: usize = 5;
+
+/// Фильтр и страница из `options`: `severity`, `type` (строка или массив),
+/// `project_id` (число или массив), `from`/`to` (`YYYY-MM-DD` включительно),
+/// `page` (с 1) и `page_size`
+#[derive(Debug, Clone, Default)]
+pub struct AnomalyFilter {
+    severities: Option<HashSet<String>>,
+    types: Option<HashSet<String>>,
+    projects: Option<HashSet<i32>>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    page: usize,
+    page_size: Option<usize>,
+}
+
+impl AnomalyFilter {
+    pub fn from_options(options: Option<&JsonValue>) -> Result<Self, String> {
+        let get = |key: &str| options.and_then(|o| o.get(key));
+
+        let projects = match get("project_id") {
+            Some(value) => Some(
+                one_or_many(value)
+                    .iter()
+                    .map(|v| {
+                        v.as_i64()
+                            .and_then(|id| i32::try_from(id).ok())
+                            .ok_or_else(|| format!("Invalid project_id: {}", v))
+                    })
+                    .collect::<Result<HashSet<i32>, String>>()?,
+            ),
+            None => None,
+        };
+
+        let page = match get("page") {
+            Some(value) => value
+                .as_u64()
+                .filter(|p| *p >= 1)
+                .ok_or_else(|| format!("Invalid page: {}", value))?
+                as usize,
+            None => 1,
+        };
+        let page_size = match get("page_size") {
+            Some(value) => Some(
+                value
+                    .as_u64()
+                    .filter(|s| *s >= 1)
+                    .ok_or_else(|| format!("Invalid page_size: {}", value))?
+                    as usize,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            severities: get("severity").map(strings).transpose()?,
+            types: get("type").map(strings).transpose()?,
+            projects,
+            from: get("from").map(date).transpose()?,
+            to: get("to").map(date).transpose()?,
+            page,
+            page_size,
+        })
+    }
+
+    /// Отфильтрованные аномалии текущей страницы и итоги по всем отфильтрованным.
+    ///
+    /// Аномалии недели целиком (`period`) не относятся к проекту и отсеиваются
+    /// фильтром по проекту; по датам сравнивается понедельник недели. При
+    /// постраничной выдаче аномалии упорядочены по убыванию `score`
+    pub fn apply(
+        &self,
+        anomalies: Vec<AnomalyOutput>,
+        entries: &[TimesheetEntry],
+    ) -> (Vec<AnomalyOutput>, AnomalyPage) {
+        let by_id: HashMap<i32, &TimesheetEntry> = entries.iter().map(|e| (e.id, e)).collect();
+        let mut matched: Vec<AnomalyOutput> = anomalies
+            .into_iter()
+            .filter(|a| self.matches(a, by_id.get(&a.entry_id).copied()))
+            .collect();
+
+        let mut by_severity = BTreeMap::new();
+        let mut by_type = BTreeMap::new();
+        for anomaly in &matched {
+            *by_severity.entry(anomaly.severity.clone()).or_insert(0) += 1;
+            *by_type.entry(anomaly.r#type.clone()).or_insert(0) += 1;
+        }
+        let total = matched.len();
+
+        let page_size = self.page_size.unwrap_or(total.max(1));
+        if self.page_size.is_some() {
+            matched.sort_by(|a, b| {
+                b.score
+                    .total_cmp(&a.score)
+                    .then_with(|| a.entry_id.cmp(&b.entry_id))
+            });
+        }
+        let page: Vec<AnomalyOutput> = matched
+            .into_iter()
+            .skip((self.page - 1) * page_size)
+            .take(page_size)
+            .collect();
+
+        let summary = AnomalyPage {
+            total,
+            page: self.page,
+            page_size,
+            pages: total.div_ceil(page_size),
+            by_severity,
+            by_type,
+        };
+        (page, summary)
+    }
+
+    /// Сводка по всем отфильтрованным аномалиям: число по типам и важности,
+    /// доля отмеченных записей по неделям и ее тренд, самые затронутые проекты.
+    /// Недели и проекты считаются по записям `entries`
+    pub fn summary(
+        &self,
+        anomalies: &[AnomalyOutput],
+        entries: &[TimesheetEntry],
+    ) -> AnomalySummary {
+        let by_id: HashMap<i32, &TimesheetEntry> = entries.iter().map(|e| (e.id, e)).collect();
+        let matched: Vec<&AnomalyOutput> = anomalies
+            .iter()
+            .filter(|a| self.matches(a, by_id.get(&a.entry_id).copied()))
+            .collect();
+
+        let mut summary = AnomalySummary {
+            total: matched.len(),
+            ..AnomalySummary::default()
+        };
+        for anomaly in &matched {
+            *summary
+                .by_severity
+                .entry(anomaly.severity.clone())
+                .or_insert(0) += 1;
+            *summary.by_type.entry(anomaly.r#type.clone()).or_insert(0) += 1;
+        }
+
+        // Неделя -> (записей, отмеченных записей, аномалий); проект -> (записей, отмеченных)
+        let flagged: HashSet<i32> = matched
+            .iter()
+            .filter(|a| by_id.contains_key(&a.entry_id))
+            .map(|a| a.entry_id)
+            .collect();
+        let mut weeks: BTreeMap<(i32, u32), (usize, usize, usize)> = BTreeMap::new();
+        let mut projects: HashMap<i32, (&str, usize, usize)> = HashMap::new();
+        for entry in entries {
+            let is_flagged = flagged.contains(&entry.id) as usize;
+            if let Some(week) = entry_date(entry).map(iso_week) {
+                let counts = weeks.entry(week).or_default();
+                counts.0 += 1;
+                counts.1 += is_flagged;
+            }
+            if let Some(project_id) = entry.project_id {
+                let counts =
+                    projects
+                        .entry(project_id)
+                        .or_insert((entry.project_name.as_str(), 0, 0));
+                counts.1 += 1;
+                counts.2 += is_flagged;
+            }
+        }
+        for anomaly in &matched {
+            if let Some(day) = anomaly_date(anomaly, by_id.get(&anomaly.entry_id).copied()) {
+                weeks.entry(iso_week(day)).or_default().2 += 1;
+            }
+        }
+
+        summary.weekly = weeks
+            .into_iter()
+            .map(
+                |((year, week), (entries, flagged, anomalies))| AnomalyWeekRate {
+                    period: format!("{}-W{:02}", year, week),
+                    entries,
+                    anomalies,
+                    rate: if entries > 0 {
+                        flagged as f64 / entries as f64
+                    } else {
+                        0.0
+                    },
+                },
+            )
+            .collect();
+        let rates: Vec<f64> = summary.weekly[summary.weekly.len().saturating_sub(TREND_WEEKS)..]
+            .iter()
+            .filter(|w| w.entries > 0)
+            .map(|w| w.rate)
+            .collect();
+        summary.trend = series_trend(&rates).direction.to_string();
+
+        let mut top: Vec<ProjectAnomalies> = projects
+            .into_iter()
+            .filter(|(_, (_, _, flagged))| *flagged > 0)
+            .map(|(project_id, (name, entries, flagged))| ProjectAnomalies {
+                project_id,
+                project_name: name.to_string(),
+                anomalies: flagged,
+                entries,
+                rate: flagged as f64 / entries as f64,
+            })
+            .collect();
+        top.sort_by(|a, b| {
+            b.anomalies
+                .cmp(&a.anomalies)
+                .then_with(|| b.rate.total_cmp(&a.rate))
+                .then_with(|| a.project_id.cmp(&b.project_id))
+        });
+        top.truncate(TOP_ANOMALY_PROJECTS);
+        summary.top_projects = top;
+        summary
+    }
+
+    fn matches(&self, anomaly: &AnomalyOutput, entry: Option<&TimesheetEntry>) -> bool {
+        if self
+            .severities
+            .as_ref()
+            .is_some_and(|s| !s.contains(&anomaly.severity))
+        {
+            return false;
+        }
+        if self
+            .types
+            .as_ref()
+            .is_some_and(|t| !t.contains(&anomaly.r#type))
+        {
+            return false;
+        }
+        if let Some(projects) = &self.projects {
+            if !entry
+                .and_then(|e| e.project_id)
+                .is_some_and(|id| projects.contains(&id))
+            {
+                return false;
+            }
+        }
+        if self.from.is_some() || self.to.is_some() {
+            let Some(day) = anomaly_date(anomaly, entry) else {
+                return false;
+            };
+            if self.from.is_some_and(|from| day < from) || self.to.is_some_and(|to| day > to) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Дата записи или понедельник недели для аномалий недели целиком
+fn anomaly_date(anomaly: &AnomalyOutput, entry: Option<&TimesheetEntry>) -> Option<NaiveDate> {
+    if let Some(period) = &anomaly.period {
+        let (year, week) = period.split_once("-W")?;
+        return NaiveDate::from_isoywd_opt(
+            year.parse().ok()?,
+            week.parse().ok()?,
+            chrono::Weekday::Mon,
+        );
+    }
+    entry_date(entry?)
+}
+
+fn entry_date(entry: &TimesheetEntry) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(entry.begin.get(..10)?, "%Y-%m-%d").ok()
+}
+
+fn iso_week(day: NaiveDate) -> (i32, u32) {
+    let week = day.iso_week();
+    (week.year(), week.week())
+}
+
+fn one_or_many(value: &JsonValue) -> Vec<&JsonValue> {
+    match value.as_array() {
+        Some(values) => values.iter().collect(),
+        None => vec![value],
+    }
+}
+
+fn strings(value: &JsonValue) -> Result<HashSet<String>, String> {
+    one_or_many(value)
+        .into_iter()
+        .map(|v| {
+            v.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("Expected a string, got {}", v))
+        })
+        .collect()
+}
+
+fn date(value: &JsonValue) -> Result<NaiveDate, String> {
+    value
+        .as_str()
+        .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+        .ok_or_else(|| format!("Invalid date (expected YYYY-MM-DD): {}", value))
+}
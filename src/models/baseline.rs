@@ -0,0 +1,222 @@
+//! Базовый профиль пользователя для оценки аномалий между запросами.
+//!
+//! Профиль накапливает типичное время работы (день недели × час) и длительности
+//! записей по проектам и активностям. Новые записи оцениваются относительно него,
+//! поэтому результат не зависит от того, сколько записей пришло в одном запросе
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::{AnomalyOutput, TimesheetEntry};
+
+/// Версия формата сохраненного профиля
+pub const BASELINE_SNAPSHOT_VERSION: u32 = 1;
+
+/// Меньше записей в профиле (или в группе длительностей) - оценка не проводится
+pub const MIN_BASELINE_SAMPLES: usize = 20;
+
+/// Длительность дальше стольких стандартных отклонений (в логарифмах) аномальна
+const DURATION_Z_THRESHOLD: f64 = 3.0;
+
+/// Час недели с меньшей долей записей профиля считается нетипичным
+const RARE_SLOT_SHARE: f64 = 0.01;
+
+/// Среднее и дисперсия по Велфорду
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RunningStats {
+    count: usize,
+    mean: f64,
+    m2: f64,
+}
+
+impl RunningStats {
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    fn std(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        (self.m2 / (self.count - 1) as f64).sqrt()
+    }
+}
+
+/// Профиль одного арендатора
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineProfile {
+    version: u32,
+    /// Число записей по дню недели (0 - воскресенье) и часу начала
+    slots: Vec<[usize; 24]>,
+    /// Логарифм длительности по ключам `project:<id>` и `activity:<id>`
+    durations: HashMap<String, RunningStats>,
+    entries_seen: usize,
+    /// Наибольший учтенный `id` записи: повторно присланные записи не учитываются
+    last_entry_id: i32,
+    updated_at: Option<String>,
+}
+
+impl BaselineProfile {
+    pub fn new() -> Self {
+        Self {
+            version: BASELINE_SNAPSHOT_VERSION,
+            slots: vec![[0; 24]; 7],
+            durations: HashMap::new(),
+            entries_seen: 0,
+            last_entry_id: i32::MIN,
+            updated_at: None,
+        }
+    }
+
+    /// Имя профиля арендатора в хранилище моделей
+    pub fn storage_name(tenant: &str) -> String {
+        format!("baseline:{}", tenant)
+    }
+
+    pub fn entries_seen(&self) -> usize {
+        self.entries_seen
+    }
+
+    /// Учесть записи с `id` больше ранее учтенных; возвращает число новых записей
+    pub fn update(&mut self, entries: &[TimesheetEntry]) -> usize {
+        let watermark = self.last_entry_id;
+        let mut added = 0;
+        for entry in entries.iter().filter(|e| e.id > watermark) {
+            if let Some(slot) = slot(entry) {
+                self.slots[slot.0][slot.1] += 1;
+            }
+            if entry.duration > 0 {
+                let value = (entry.duration as f64).ln();
+                for key in duration_keys(entry) {
+                    self.durations.entry(key).or_default().push(value);
+                }
+            }
+            self.last_entry_id = self.last_entry_id.max(entry.id);
+            self.entries_seen += 1;
+            added += 1;
+        }
+        if added > 0 {
+            self.updated_at = Some(chrono::Utc::now().to_rfc3339());
+        }
+        added
+    }
+
+    /// Аномалии записей относительно профиля: длительность, нетипичная для
+    /// проекта (иначе активности), и время начала в редкий для пользователя час
+    pub fn score(&self, entries: &[TimesheetEntry]) -> Vec<AnomalyOutput> {
+        if self.entries_seen < MIN_BASELINE_SAMPLES {
+            return Vec::new();
+        }
+        let slot_total: usize = self.slots.iter().flatten().sum();
+
+        let mut anomalies = Vec::new();
+        for entry in entries {
+            if let Some(z) = self.duration_z(entry) {
+                if z.abs() > DURATION_Z_THRESHOLD {
+                    let severity = match z.abs() {
+                        z if z > 5.0 => "high",
+                        z if z > 4.0 => "medium",
+                        _ => "low",
+                    };
+                    let direction = if z > 0.0 {
+                        "дольше"
+                    } else {
+                        "короче"
+                    };
+                    anomalies.push(AnomalyOutput {
+                        entry_id: entry.id,
+                        r#type: "duration".to_string(),
+                        severity: severity.to_string(),
+                        reason: format!(
+                            "Запись ({:.1} ч) намного {} обычных для проекта (активности)",
+                            entry.duration as f64 / 60.0,
+                            direction
+                        ),
+                        score: z.abs() / (z.abs() + DURATION_Z_THRESHOLD),
+                        period: None,
+                        context: None,
+                    });
+                    continue;
+                }
+            }
+
+            if let Some((day, hour)) = slot(entry).filter(|_| slot_total > 0) {
+                let share = self.slots[day][hour] as f64 / slot_total as f64;
+                if share < RARE_SLOT_SHARE {
+                    anomalies.push(AnomalyOutput {
+                        entry_id: entry.id,
+                        r#type: "time".to_string(),
+                        severity: if share == 0.0 { "medium" } else { "low" }.to_string(),
+                        reason: format!(
+                            "Работа в {}:00 в этот день недели для пользователя нетипична ({:.1}% записей)",
+                            hour,
+                            share * 100.0
+                        ),
+                        score: 0.5 + 0.5 * (1.0 - share / RARE_SLOT_SHARE),
+                        period: None,
+                        context: None,
+                    });
+                }
+            }
+        }
+        anomalies
+    }
+
+    fn duration_z(&self, entry: &TimesheetEntry) -> Option<f64> {
+        if entry.duration <= 0 {
+            return None;
+        }
+        let stats = duration_keys(entry)
+            .into_iter()
+            .filter_map(|key| self.durations.get(&key))
+            .find(|s| s.count >= MIN_BASELINE_SAMPLES && s.std() > 0.0)?;
+        Some(((entry.duration as f64).ln() - stats.mean) / stats.std())
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Serialization error: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let profile: Self =
+            serde_json::from_str(json).map_err(|e| format!("Deserialization error: {}", e))?;
+        if profile.version != BASELINE_SNAPSHOT_VERSION {
+            return Err(format!(
+                "Unsupported baseline snapshot version: {} (expected {})",
+                profile.version, BASELINE_SNAPSHOT_VERSION
+            ));
+        }
+        if profile.slots.len() != 7 {
+            return Err("Baseline weekday layout mismatch".to_string());
+        }
+        Ok(profile)
+    }
+}
+
+impl Default for BaselineProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn slot(entry: &TimesheetEntry) -> Option<(usize, usize)> {
+    let day = usize::try_from(entry.day_of_week).ok().filter(|d| *d < 7)?;
+    let hour = usize::try_from(entry.hour_of_day)
+        .ok()
+        .filter(|h| *h < 24)?;
+    Some((day, hour))
+}
+
+/// Группы длительностей записи в порядке предпочтения
+fn duration_keys(entry: &TimesheetEntry) -> Vec<String> {
+    entry
+        .project_id
+        .map(|id| format!("project:{}", id))
+        .into_iter()
+        .chain(entry.activity_id.map(|id| format!("activity:{}", id)))
+        .collect()
+}
@@ -0,0 +1,74 @@
+//! Согласованность выручки и часов: сумма недели против минут × ставка проекта
+
+use crate::types::{AnomalyOutput, Settings, WeekData};
+
+/// Допустимое относительное расхождение суммы недели с ожидаемой по умолчанию
+pub const DEFAULT_BILLING_TOLERANCE: f64 = 0.1;
+
+/// Тип аномалий расхождения выручки и часов
+pub const BILLING_ANOMALY: &str = "billing";
+
+/// Недели, где записанная сумма расходится с часами по проектам × ставки
+/// (`ProjectSettings::rate_per_minute`, иначе `Settings::rate_per_minute`)
+/// больше чем на `tolerance`: неверная ставка или неоплаченное время.
+///
+/// Аномалия относится к неделе целиком: `entry_id` равен 0, неделя - в `period`.
+/// Недели без часов или с нулевой ожидаемой суммой не проверяются
+pub fn billing_anomalies(
+    weeks: &[WeekData],
+    settings: &Settings,
+    tolerance: f64,
+) -> Vec<AnomalyOutput> {
+    weeks
+        .iter()
+        .filter_map(|week| {
+            let expected: f64 = week
+                .project_stats
+                .iter()
+                .map(|s| s.minutes as f64 * settings.project_rate(s.project_id))
+                .sum();
+            if expected <= 0.0 {
+                return None;
+            }
+
+            let divergence = (week.total_amount - expected) / expected;
+            if divergence.abs() <= tolerance {
+                return None;
+            }
+
+            let reason = if week.total_amount <= 0.0 {
+                format!("Время не оплачено: ожидалось {:.2}", expected)
+            } else if divergence < 0.0 {
+                format!(
+                    "Сумма {:.2} меньше ожидаемой {:.2} на {:.0}%: часть времени не оплачена \
+                     или применена заниженная ставка",
+                    week.total_amount,
+                    expected,
+                    -divergence * 100.0
+                )
+            } else {
+                format!(
+                    "Сумма {:.2} больше ожидаемой {:.2} на {:.0}%: применена завышенная ставка",
+                    week.total_amount,
+                    expected,
+                    divergence * 100.0
+                )
+            };
+            let severity = match divergence.abs() {
+                d if d > 0.5 => "high",
+                d if d > 0.25 => "medium",
+                _ => "low",
+            };
+
+            Some(AnomalyOutput {
+                entry_id: 0,
+                r#type: BILLING_ANOMALY.to_string(),
+                severity: severity.to_string(),
+                reason,
+                score: divergence.abs().min(1.0),
+                period: Some(format!("{}-W{:02}", week.year, week.week)),
+                context: None,
+            })
+        })
+        .collect()
+}
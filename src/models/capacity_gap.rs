@@ -0,0 +1,153 @@
+//! Нехватка емкости команды (`/api/team/capacity-gap`).
+//!
+//! Часы участника на горизонте прогноза - среднее последних `CONTRACT_WEEKS`
+//! недель со значимым трендом (`detect_trend`), но не больше его договорной
+//! емкости: переработки не считаются емкостью, на которую можно брать
+//! обязательства. Разрыв - обязательства перед клиентами минус часы команды;
+//! сигнал `capacity_shortfall` выдается, только если разрыв держится на большей
+//! части горизонта, чтобы решение о найме не принималось по одной тяжелой неделе
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::contract::{capacity_hours, DEFAULT_CAPACITY_HOURS};
+use crate::models::forecasting::{detect_trend, next_iso_week, series_trend};
+use crate::models::team::{member_weeks, recent_weekly_hours};
+use crate::types::{CapacityGap, CapacityShortfall, CapacityWeek, TeamInput};
+
+/// Горизонт прогноза по умолчанию, недель (`options.horizon_weeks`)
+pub const DEFAULT_HORIZON_WEEKS: usize = 12;
+
+/// Самый длинный горизонт прогноза, недель
+pub const MAX_HORIZON_WEEKS: usize = 52;
+
+/// Недель истории команды в ответе и в тренде разрыва
+pub const GAP_HISTORY_WEEKS: usize = 12;
+
+/// Разрыв меньше этого, часов в неделю, - не нехватка
+const MIN_GAP_HOURS: f64 = 4.0;
+
+/// Доля недель горизонта с нехваткой, начиная с которой разрыв устойчив
+const PERSISTENT_SHARE: f64 = 0.75;
+
+/// Разрыв команды `team` и обязательств `commitments` (часов в неделю по
+/// проектам) на `horizon_weeks` недель вперед
+pub fn capacity_gap(
+    team: &TeamInput,
+    commitments: &HashMap<i32, f64>,
+    horizon_weeks: usize,
+) -> CapacityGap {
+    let committed_hours: f64 = commitments.values().sum();
+    let mut capacity = 0.0;
+    let mut by_week: BTreeMap<(i32, i32), f64> = BTreeMap::new();
+    // Часы участника в неделю: база и наклон значимого тренда
+    let mut members = Vec::new();
+    for member in &team.members {
+        let weeks = member_weeks(member);
+        let member_capacity = capacity_hours(&member.data.settings);
+        capacity += member_capacity;
+        for week in weeks.iter() {
+            *by_week.entry((week.year, week.week)).or_default() +=
+                week.total_hours.clamp(0.0, member_capacity);
+        }
+        let trend = detect_trend(&weeks);
+        let slope = if trend.direction == "stable" {
+            0.0
+        } else {
+            trend.slope
+        };
+        members.push((recent_weekly_hours(&weeks), slope, member_capacity));
+    }
+
+    let history: Vec<CapacityWeek> = by_week
+        .iter()
+        .skip(by_week.len().saturating_sub(GAP_HISTORY_WEEKS))
+        .map(|(&(year, week), &hours)| CapacityWeek {
+            period: format!("{}-W{:02}", year, week),
+            hours,
+            gap_hours: committed_hours - hours,
+        })
+        .collect();
+
+    let mut forecast = Vec::with_capacity(horizon_weeks);
+    // Без истории прогноз начинается со следующей недели
+    let mut period = by_week
+        .keys()
+        .next_back()
+        .map(|&(year, week)| (year, week.max(1) as u32))
+        .unwrap_or_else(current_iso_week);
+    for step in 1..=horizon_weeks {
+        period = next_iso_week(period.0, period.1);
+        let hours: f64 = members
+            .iter()
+            .map(|(base, slope, capacity)| (base + slope * step as f64).clamp(0.0, *capacity))
+            .sum();
+        forecast.push(CapacityWeek {
+            period: format!("{}-W{:02}", period.0, period.1),
+            hours,
+            gap_hours: committed_hours - hours,
+        });
+    }
+
+    let average_gap_hours = if forecast.is_empty() {
+        0.0
+    } else {
+        forecast.iter().map(|w| w.gap_hours).sum::<f64>() / forecast.len() as f64
+    };
+    let gaps: Vec<f64> = history.iter().map(|w| w.gap_hours).collect();
+    let trend = series_trend(&gaps);
+    let shortfall = shortfall(&forecast, trend.direction);
+    CapacityGap {
+        committed_hours,
+        capacity_hours: capacity,
+        history,
+        forecast,
+        average_gap_hours,
+        trend: trend.direction.to_string(),
+        trend_slope: trend.slope,
+        shortfall,
+    }
+}
+
+fn current_iso_week() -> (i32, u32) {
+    use chrono::Datelike;
+    let week = chrono::Utc::now().date_naive().iso_week();
+    (week.year(), week.week())
+}
+
+/// Сигнал, если разрыв не меньше `MIN_GAP_HOURS` на `PERSISTENT_SHARE` недель горизонта
+fn shortfall(forecast: &[CapacityWeek], trend: &str) -> Option<CapacityShortfall> {
+    let short: Vec<&CapacityWeek> = forecast
+        .iter()
+        .filter(|w| w.gap_hours >= MIN_GAP_HOURS)
+        .collect();
+    if short.is_empty() || (short.len() as f64) < PERSISTENT_SHARE * forecast.len() as f64 {
+        return None;
+    }
+    let gap_hours = short.iter().map(|w| w.gap_hours).sum::<f64>() / short.len() as f64;
+    let fte = gap_hours / DEFAULT_CAPACITY_HOURS;
+    let severity = if fte >= 1.0 {
+        "high"
+    } else if fte >= 0.5 {
+        "medium"
+    } else {
+        "low"
+    };
+    Some(CapacityShortfall {
+        signal: "capacity_shortfall".to_string(),
+        severity: severity.to_string(),
+        gap_hours,
+        fte,
+        weeks_short: short.len(),
+        horizon_weeks: forecast.len(),
+        first_week: short[0].period.clone(),
+        trend: trend.to_string(),
+        message: format!(
+            "Обязательства превышают емкость команды на {:.1} ч/нед. ({:.1} ставки) \
+             в {} из {} недель прогноза",
+            gap_hours,
+            fte,
+            short.len(),
+            forecast.len()
+        ),
+    })
+}
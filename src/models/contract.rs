@@ -0,0 +1,152 @@
+//! Договорные часы (`UserPreferences::contracted_weekly_hours`).
+//!
+//! Договорная неделя - точка отсчета для переработок, риска выгорания, емкости
+//! плана действий и недоучтенных недель. Праздники `settings.holiday_country`
+//! в будни уменьшают договорные часы недели на 1/5 за день. Без договорных часов
+//! переработки и недоучет не считаются: у фрилансера нет нормы, с которой
+//! их сравнивать
+
+use crate::holidays;
+use crate::types::{AnomalyOutput, ContractUtilization, Settings, WeekData};
+
+/// Тип аномалий недоучтенного времени
+pub const UNDER_TRACKING_ANOMALY: &str = "under_tracking";
+
+/// Доля договорных часов, которой может не хватать до недоучтенной недели, по умолчанию
+pub const DEFAULT_UNDER_TRACKING_TOLERANCE: f64 = 0.2;
+
+/// Сколько последних недель входят в сводку переработок
+pub const CONTRACT_WEEKS: usize = 8;
+
+/// Устойчивая нагрузка относительно договорной недели (45 ч при 40-часовом договоре)
+pub const SUSTAINABLE_CONTRACT_SHARE: f64 = 1.125;
+
+/// Устойчивая нагрузка без договорных часов
+pub const DEFAULT_SUSTAINABLE_HOURS: f64 = 45.0;
+
+/// Емкость без договорных часов: обычная рабочая неделя
+pub const DEFAULT_CAPACITY_HOURS: f64 = 40.0;
+
+/// Договорные часы в неделю; `None`, если не заданы или не положительны
+pub fn contracted_hours(settings: &Settings) -> Option<f64> {
+    settings
+        .user_preferences
+        .as_ref()
+        .and_then(|prefs| prefs.contracted_weekly_hours)
+        .filter(|hours| *hours > 0.0 && hours.is_finite())
+}
+
+/// Договорные часы ISO-недели за вычетом праздников в будни
+pub fn expected_hours(settings: &Settings, year: i32, week: u32) -> Option<f64> {
+    Some(contracted_hours(settings)? * workday_share(settings, year, week))
+}
+
+/// Емкость: договорные часы в неделю, без них - `DEFAULT_CAPACITY_HOURS`
+pub fn capacity_hours(settings: &Settings) -> f64 {
+    contracted_hours(settings).unwrap_or(DEFAULT_CAPACITY_HOURS)
+}
+
+/// Емкость ISO-недели за вычетом праздников в будни
+pub fn available_hours(settings: &Settings, year: i32, week: u32) -> f64 {
+    capacity_hours(settings) * workday_share(settings, year, week)
+}
+
+/// Доля будней ISO-недели без праздников `settings.holiday_country`
+fn workday_share(settings: &Settings, year: i32, week: u32) -> f64 {
+    let holidays = settings
+        .holiday_country
+        .as_deref()
+        .and_then(|country| holidays::weekday_holidays(country, year, week).ok())
+        .map_or(0, |dates| dates.len().min(5));
+    (5 - holidays) as f64 / 5.0
+}
+
+/// Недельная нагрузка, выше которой растет риск выгорания
+pub fn sustainable_hours(settings: &Settings) -> f64 {
+    contracted_hours(settings).map_or(DEFAULT_SUSTAINABLE_HOURS, |hours| {
+        hours * SUSTAINABLE_CONTRACT_SHARE
+    })
+}
+
+/// Переработки и недоучет за последние `CONTRACT_WEEKS` недель
+pub fn utilization(weeks: &[WeekData], settings: &Settings) -> Option<ContractUtilization> {
+    let contracted_weekly_hours = contracted_hours(settings)?;
+    let recent = &weeks[weeks.len().saturating_sub(CONTRACT_WEEKS)..];
+    if recent.is_empty() {
+        return None;
+    }
+    let mut expected_total = 0.0;
+    let mut overtime_hours = 0.0;
+    let mut overtime_weeks = 0;
+    let mut under_tracked_weeks = 0;
+    for week in recent {
+        let expected = expected_hours(settings, week.year, week.week.max(1) as u32)
+            .unwrap_or(contracted_weekly_hours);
+        expected_total += expected;
+        if week.total_hours > expected {
+            overtime_hours += week.total_hours - expected;
+            overtime_weeks += 1;
+        } else if week.total_hours < expected * (1.0 - DEFAULT_UNDER_TRACKING_TOLERANCE) {
+            under_tracked_weeks += 1;
+        }
+    }
+    let tracked: f64 = recent.iter().map(|w| w.total_hours).sum();
+    Some(ContractUtilization {
+        contracted_weekly_hours,
+        weeks: recent.len(),
+        average_hours: tracked / recent.len() as f64,
+        utilization: if expected_total > 0.0 {
+            tracked / expected_total
+        } else {
+            0.0
+        },
+        overtime_hours,
+        overtime_weeks,
+        under_tracked_weeks,
+    })
+}
+
+/// Недели, учтенных часов которых меньше договорных больше чем на `tolerance`:
+/// время, вероятно, отработано, но не записано.
+///
+/// Последняя неделя не проверяется - она может быть еще не закончена. Аномалия
+/// относится к неделе целиком: `entry_id` равен 0, неделя - в `period`
+pub fn under_tracking_anomalies(
+    weeks: &[WeekData],
+    settings: &Settings,
+    tolerance: f64,
+) -> Vec<AnomalyOutput> {
+    let complete = &weeks[..weeks.len().saturating_sub(1)];
+    complete
+        .iter()
+        .filter_map(|week| {
+            let expected = expected_hours(settings, week.year, week.week.max(1) as u32)?;
+            if expected <= 0.0 {
+                return None;
+            }
+            let shortfall = (expected - week.total_hours) / expected;
+            if shortfall <= tolerance {
+                return None;
+            }
+            let severity = match shortfall {
+                s if s > 0.5 => "high",
+                s if s > 0.35 => "medium",
+                _ => "low",
+            };
+            Some(AnomalyOutput {
+                entry_id: 0,
+                r#type: UNDER_TRACKING_ANOMALY.to_string(),
+                severity: severity.to_string(),
+                reason: format!(
+                    "Учтено {:.1} ч из {:.1} договорных: не записано {:.0}% времени",
+                    week.total_hours,
+                    expected,
+                    shortfall * 100.0
+                ),
+                score: shortfall.min(1.0),
+                period: Some(format!("{}-W{:02}", week.year, week.week)),
+                context: None,
+            })
+        })
+        .collect()
+}
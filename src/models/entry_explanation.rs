@@ -0,0 +1,242 @@
+//! Объяснение одной записи (`/api/explain-entry`): признаки детектора против
+//! обычных значений, оценки всех детекторов, вклад признаков в оценку леса и
+//! похожие записи для сравнения
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use ndarray::Array2;
+
+use crate::float::{to_f64, Float};
+use crate::models::anomaly_context::ContextBaselines;
+use crate::models::anomaly_detection::{AnomalyDetector, ScoringMode};
+use crate::models::baseline::BaselineProfile;
+use crate::models::validation::invalid_entries;
+use crate::preprocessing::feature_engineering::ANOMALY_FEATURES;
+use crate::preprocessing::FeatureEngineer;
+use crate::types::{
+    AnomalyContext, DetectorVerdict, EntryExplanation, EntryFeature, FeatureContribution,
+    SimilarEntry, TimesheetEntry,
+};
+
+/// Сколько похожих записей приводится
+pub const SIMILAR_ENTRIES: usize = 5;
+
+/// Объяснение записи `entry_id` из `entries`. Детектор и профиль только
+/// оценивают запись (пакетный режим по всем `entries`), но не обучаются
+pub fn explain_entry(
+    entry_id: i32,
+    entries: &[TimesheetEntry],
+    detector: &AnomalyDetector,
+    baseline: &BaselineProfile,
+    threshold_shifts: &HashMap<String, f64>,
+    now: DateTime<Utc>,
+) -> Result<EntryExplanation, String> {
+    let row = entries
+        .iter()
+        .position(|e| e.id == entry_id)
+        .ok_or_else(|| format!("Entry {} not found", entry_id))?;
+    let entry = &entries[row];
+    let features = FeatureEngineer::extract_anomaly_features(entries);
+
+    // Оценки леса по всем записям: нужны и для похожих записей
+    let forest = detector
+        .model_info()
+        .and_then(|_| detector.entry_scores(&features, ScoringMode::Batch).ok());
+    let mut detectors = Vec::new();
+    let mut flagged = vec![false; entries.len()];
+    if let Some((scores, base_threshold)) = &forest {
+        for (i, e) in entries.iter().enumerate() {
+            flagged[i] = scores[i] > threshold(detector, e, *base_threshold, threshold_shifts);
+        }
+        let fired = flagged[row];
+        let reason = fired
+            .then(|| {
+                detector
+                    .detect_with_features(entries, &features, threshold_shifts, ScoringMode::Batch)
+                    .ok()?
+                    .into_iter()
+                    .find(|a| a.entry_id == entry_id)
+                    .map(|a| a.reason)
+            })
+            .flatten();
+        detectors.push(DetectorVerdict {
+            detector: "isolation_forest".to_string(),
+            fired,
+            score: Some(to_f64(scores[row])),
+            threshold: Some(to_f64(threshold(
+                detector,
+                entry,
+                *base_threshold,
+                threshold_shifts,
+            ))),
+            reason,
+        });
+    }
+    let baseline_anomaly = baseline.score(std::slice::from_ref(entry)).pop();
+    detectors.push(DetectorVerdict {
+        detector: "baseline".to_string(),
+        fired: baseline_anomaly.is_some(),
+        score: baseline_anomaly.as_ref().map(|a| a.score),
+        threshold: None,
+        reason: baseline_anomaly.map(|a| a.reason),
+    });
+    let invalid = invalid_entries(std::slice::from_ref(entry), now).pop();
+    detectors.push(DetectorVerdict {
+        detector: "validation".to_string(),
+        fired: invalid.is_some(),
+        score: invalid.as_ref().map(|a| a.score),
+        threshold: None,
+        reason: invalid.map(|a| a.reason),
+    });
+
+    let sample = features.row(row).to_owned();
+    let mut contributions: Vec<FeatureContribution> = detector
+        .feature_contributions(&sample)
+        .unwrap_or_default()
+        .into_iter()
+        .zip(ANOMALY_FEATURES)
+        .map(|(share, name)| FeatureContribution {
+            feature: name.to_string(),
+            share: to_f64(share),
+        })
+        .collect();
+    contributions.sort_by(|a, b| b.share.total_cmp(&a.share));
+
+    let context = ContextBaselines::from_entries(entries).context(entry);
+    let features_out = feature_values(&features, row);
+    let similar = similar_entries(entries, &features, row, &flagged);
+    let summary = summary(entry, &detectors, &contributions, &context);
+    Ok(EntryExplanation {
+        entry_id,
+        features: features_out,
+        context,
+        detectors,
+        contributions,
+        similar,
+        summary,
+    })
+}
+
+/// Порог леса для записи со сдвигом по ее типу аномалии
+fn threshold(
+    detector: &AnomalyDetector,
+    entry: &TimesheetEntry,
+    base: Float,
+    threshold_shifts: &HashMap<String, f64>,
+) -> Float {
+    let shift = threshold_shifts
+        .get(&detector.classify_anomaly_type(entry))
+        .copied()
+        .unwrap_or(0.0);
+    base + shift as Float
+}
+
+fn feature_values(features: &Array2<Float>, row: usize) -> Vec<EntryFeature> {
+    ANOMALY_FEATURES
+        .iter()
+        .enumerate()
+        .map(|(column, name)| {
+            let mut values: Vec<f64> = features.column(column).iter().map(|v| to_f64(*v)).collect();
+            let value = values[row];
+            let below = values.iter().filter(|v| **v < value).count();
+            values.sort_by(f64::total_cmp);
+            let mid = values.len() / 2;
+            let median = if values.len().is_multiple_of(2) {
+                (values[mid - 1] + values[mid]) / 2.0
+            } else {
+                values[mid]
+            };
+            EntryFeature {
+                name: name.to_string(),
+                value,
+                median,
+                percentile: below as f64 / values.len() as f64,
+            }
+        })
+        .collect()
+}
+
+/// Ближайшие записи по признакам детектора, кроме самой записи
+fn similar_entries(
+    entries: &[TimesheetEntry],
+    features: &Array2<Float>,
+    row: usize,
+    flagged: &[bool],
+) -> Vec<SimilarEntry> {
+    let target = features.row(row);
+    let mut neighbours: Vec<(usize, f64)> = features
+        .rows()
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| *i != row)
+        .map(|(i, other)| {
+            let distance = target
+                .iter()
+                .zip(other.iter())
+                .map(|(a, b)| to_f64(*a - *b).powi(2))
+                .sum::<f64>()
+                .sqrt();
+            (i, distance)
+        })
+        .collect();
+    neighbours.sort_by(|a, b| a.1.total_cmp(&b.1));
+    neighbours
+        .into_iter()
+        .take(SIMILAR_ENTRIES)
+        .map(|(i, distance)| {
+            let entry = &entries[i];
+            SimilarEntry {
+                entry_id: entry.id,
+                begin: entry.begin.clone(),
+                duration: entry.duration,
+                project_id: entry.project_id,
+                distance,
+                anomalous: flagged[i],
+            }
+        })
+        .collect()
+}
+
+fn summary(
+    entry: &TimesheetEntry,
+    detectors: &[DetectorVerdict],
+    contributions: &[FeatureContribution],
+    context: &AnomalyContext,
+) -> String {
+    let fired: Vec<&str> = detectors
+        .iter()
+        .filter(|d| d.fired)
+        .map(|d| d.detector.as_str())
+        .collect();
+    let mut parts = vec![if fired.is_empty() {
+        format!("Запись {} не отмечена ни одним детектором", entry.id)
+    } else {
+        format!("Запись {} отмечена: {}", entry.id, fired.join(", "))
+    }];
+    if let Some(top) = contributions.first().filter(|c| c.share > 0.0) {
+        parts.push(format!(
+            "сильнее всего на оценку леса влияет {} ({:.0}%)",
+            top.feature,
+            top.share * 100.0
+        ));
+    }
+    if let Some(ratio) = context.project_duration_ratio {
+        parts.push(format!(
+            "длительность {} мин - {:.1} обычной записи проекта",
+            entry.duration, ratio
+        ));
+    }
+    if let Some(difference) = context.start_hour_difference.filter(|d| d.abs() >= 2.0) {
+        parts.push(format!(
+            "начало на {:.0} ч {} обычного",
+            difference.abs(),
+            if difference > 0.0 {
+                "позже"
+            } else {
+                "раньше"
+            }
+        ));
+    }
+    parts.join("; ")
+}
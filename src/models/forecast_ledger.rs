@@ -0,0 +1,308 @@
+//! Журнал выданных прогнозов арендатора и их сверка с фактом.
+//!
+//! Прогноз недельных часов записывается на неделю, для которой выдан (`YYYY-Www`);
+//! повторный прогноз той же недели заменяет прежний, пока факт неизвестен. Факт
+//! приходит из недель следующих запросов прогноза (`sync`; последняя неделя
+//! запроса может быть неполной и не учитывается) или из `/api/learn` с
+//! `period` (`learn`). Журнал превращает цикл обучения на ошибках в видимую
+//! историю точности.
+//!
+//! Кроме последнего прогноза недели журнал хранит ее снимки: прогноз недельных
+//! часов - это и уровень ближайших `SNAPSHOT_HORIZON_WEEKS` недель, поэтому
+//! каждый прогноз записывается на них с горизонтом в неделях. Снимки одной
+//! недели показывают, как прогноз сходился к факту
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::forecasting::next_iso_week;
+use crate::types::{
+    ForecastAccuracy, ForecastHistory, ForecastRecord, ForecastRevision, ForecastRevisions,
+    WeekData,
+};
+
+/// Сколько недель журнала хранится
+pub const LEDGER_RETENTION_WEEKS: usize = 104;
+
+/// Сколько последних проверенных прогнозов входят в `rolling_mape`
+pub const ROLLING_ACCURACY_WINDOW: usize = 4;
+
+/// Ошибка, в пределах которой прогноз считается попаданием
+pub const HIT_THRESHOLD: f64 = 0.1;
+
+/// На сколько недель вперед записываются снимки прогноза
+pub const SNAPSHOT_HORIZON_WEEKS: u32 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LedgerEntry {
+    issued_at: DateTime<Utc>,
+    predicted_hours: f64,
+    confidence: f64,
+    model_version: Option<String>,
+    actual: Option<(f64, String)>,
+}
+
+/// Снимок прогноза недели, выданный за `horizon` недель до нее
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    issued_at: DateTime<Utc>,
+    horizon: u32,
+    predicted_hours: f64,
+    confidence: f64,
+    model_version: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct WeekSnapshots {
+    /// По убыванию горизонта: от ранних к поздним
+    snapshots: Vec<Snapshot>,
+    actual: Option<(f64, String)>,
+}
+
+/// Прогнозы по неделям
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ForecastLedger {
+    entries: BTreeMap<String, LedgerEntry>,
+    /// Журналы до снимков их не содержат
+    #[serde(default)]
+    snapshots: BTreeMap<String, WeekSnapshots>,
+}
+
+impl ForecastLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Имя журнала арендатора в хранилище моделей
+    pub fn storage_name(tenant: &str) -> String {
+        format!("forecasts:{}", tenant)
+    }
+
+    /// Неделя в формате журнала
+    pub fn period(year: i32, week: u32) -> String {
+        format!("{}-W{:02}", year, week)
+    }
+
+    /// Записывает прогноз недели `period`; прогноз недели с известным фактом не меняется
+    pub fn record_forecast(
+        &mut self,
+        period: String,
+        predicted_hours: f64,
+        confidence: f64,
+        model_version: Option<String>,
+        now: DateTime<Utc>,
+    ) {
+        if self
+            .entries
+            .get(&period)
+            .is_some_and(|entry| entry.actual.is_some())
+        {
+            return;
+        }
+        self.entries.insert(
+            period,
+            LedgerEntry {
+                issued_at: now,
+                predicted_hours,
+                confidence,
+                model_version,
+                actual: None,
+            },
+        );
+        while self.entries.len() > LEDGER_RETENTION_WEEKS {
+            self.entries.pop_first();
+        }
+    }
+
+    /// Снимки прогноза `predicted_hours` на `SNAPSHOT_HORIZON_WEEKS` недель,
+    /// начиная с ISO-недели `year`-`week` (горизонт 1). Снимок того же горизонта
+    /// заменяется: повторные запросы одной недели дают один снимок; недели с
+    /// известным фактом не меняются
+    pub fn record_snapshots(
+        &mut self,
+        year: i32,
+        week: u32,
+        predicted_hours: f64,
+        confidence: f64,
+        model_version: Option<String>,
+        now: DateTime<Utc>,
+    ) {
+        let mut target = (year, week);
+        for horizon in 1..=SNAPSHOT_HORIZON_WEEKS {
+            let period = Self::period(target.0, target.1);
+            target = next_iso_week(target.0, target.1);
+            let week = self.snapshots.entry(period).or_default();
+            if week.actual.is_some() {
+                continue;
+            }
+            week.snapshots.retain(|s| s.horizon != horizon);
+            let position = week
+                .snapshots
+                .iter()
+                .position(|s| s.horizon < horizon)
+                .unwrap_or(week.snapshots.len());
+            week.snapshots.insert(
+                position,
+                Snapshot {
+                    issued_at: now,
+                    horizon,
+                    predicted_hours,
+                    confidence,
+                    model_version: model_version.clone(),
+                },
+            );
+        }
+        while self.snapshots.len() > LEDGER_RETENTION_WEEKS {
+            self.snapshots.pop_first();
+        }
+    }
+
+    /// Факт недели `period`; `false`, если прогноза этой недели нет
+    pub fn record_actual(&mut self, period: &str, actual_hours: f64, source: &str) -> bool {
+        if !actual_hours.is_finite() {
+            return false;
+        }
+        let actual = Some((actual_hours, source.to_string()));
+        let snapshots = match self.snapshots.get_mut(period) {
+            Some(week) => {
+                week.actual = actual.clone();
+                true
+            }
+            None => false,
+        };
+        match self.entries.get_mut(period) {
+            Some(entry) => {
+                entry.actual = actual;
+                true
+            }
+            None => snapshots,
+        }
+    }
+
+    /// Снимки прогноза недели `period` от ранних к поздним с ошибками против
+    /// факта; `None`, если снимков недели нет
+    pub fn revisions(&self, period: &str) -> Option<ForecastRevisions> {
+        let week = self.snapshots.get(period)?;
+        let actual_hours = week.actual.as_ref().map(|(hours, _)| *hours);
+        let mut previous: Option<f64> = None;
+        let revisions: Vec<ForecastRevision> = week
+            .snapshots
+            .iter()
+            .map(|snapshot| {
+                let error = actual_hours.map(|actual| snapshot.predicted_hours - actual);
+                let revision = ForecastRevision {
+                    issued_at: snapshot.issued_at.to_rfc3339(),
+                    horizon_weeks: snapshot.horizon,
+                    predicted_hours: snapshot.predicted_hours,
+                    confidence: snapshot.confidence,
+                    model_version: snapshot.model_version.clone(),
+                    change: previous.map(|hours| snapshot.predicted_hours - hours),
+                    error,
+                    absolute_percentage_error: actual_hours
+                        .zip(error)
+                        .filter(|(actual, _)| *actual > 0.0)
+                        .map(|(actual, error)| error.abs() / actual),
+                };
+                previous = Some(snapshot.predicted_hours);
+                revision
+            })
+            .collect();
+        let errors: Vec<f64> = revisions.iter().filter_map(|r| r.error).collect();
+        let converged = match (errors.first(), errors.last()) {
+            (Some(first), Some(last)) if errors.len() > 1 => Some(last.abs() <= first.abs()),
+            _ => None,
+        };
+        Some(ForecastRevisions {
+            period: period.to_string(),
+            actual_hours,
+            actual_source: week.actual.as_ref().map(|(_, source)| source.clone()),
+            revisions,
+            converged,
+        })
+    }
+
+    /// Факт из недель запроса, кроме последней (она может быть не закончена);
+    /// возвращает число сверенных прогнозов
+    pub fn sync_actuals(&mut self, weeks: &[WeekData]) -> usize {
+        let complete = &weeks[..weeks.len().saturating_sub(1)];
+        complete
+            .iter()
+            .filter(|w| w.week > 0)
+            .filter(|w| {
+                self.record_actual(&Self::period(w.year, w.week as u32), w.total_hours, "sync")
+            })
+            .count()
+    }
+
+    /// Последние `limit` недель журнала с ошибками и итоговая точность по ним
+    pub fn history(&self, limit: usize) -> ForecastHistory {
+        let mut records: Vec<ForecastRecord> = Vec::with_capacity(self.entries.len());
+        let mut recent_errors: Vec<f64> = Vec::new();
+        for (period, entry) in &self.entries {
+            let actual_hours = entry.actual.as_ref().map(|(hours, _)| *hours);
+            let error = actual_hours.map(|actual| entry.predicted_hours - actual);
+            let absolute_percentage_error = actual_hours
+                .zip(error)
+                .filter(|(actual, _)| *actual > 0.0)
+                .map(|(actual, error)| error.abs() / actual);
+            if let Some(ape) = absolute_percentage_error {
+                recent_errors.push(ape);
+            }
+            let window =
+                &recent_errors[recent_errors.len().saturating_sub(ROLLING_ACCURACY_WINDOW)..];
+            records.push(ForecastRecord {
+                period: period.clone(),
+                issued_at: entry.issued_at.to_rfc3339(),
+                predicted_hours: entry.predicted_hours,
+                confidence: entry.confidence,
+                model_version: entry.model_version.clone(),
+                actual_hours,
+                actual_source: entry.actual.as_ref().map(|(_, source)| source.clone()),
+                error,
+                absolute_percentage_error,
+                rolling_mape: (absolute_percentage_error.is_some())
+                    .then(|| window.iter().sum::<f64>() / window.len() as f64),
+            });
+        }
+        let records = records.split_off(records.len().saturating_sub(limit));
+
+        let evaluated: Vec<&ForecastRecord> =
+            records.iter().filter(|r| r.error.is_some()).collect();
+        let mean = |values: Vec<f64>| {
+            (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+        };
+        let apes: Vec<f64> = evaluated
+            .iter()
+            .filter_map(|r| r.absolute_percentage_error)
+            .collect();
+        let accuracy = ForecastAccuracy {
+            forecasts: records.len(),
+            evaluated: evaluated.len(),
+            mae: mean(
+                evaluated
+                    .iter()
+                    .filter_map(|r| r.error)
+                    .map(f64::abs)
+                    .collect(),
+            ),
+            bias: mean(evaluated.iter().filter_map(|r| r.error).collect()),
+            hit_rate: mean(
+                apes.iter()
+                    .map(|ape| if *ape <= HIT_THRESHOLD { 1.0 } else { 0.0 })
+                    .collect(),
+            ),
+            mape: mean(apes),
+        };
+        ForecastHistory { records, accuracy }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Serialization error: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Deserialization error: {}", e))
+    }
+}
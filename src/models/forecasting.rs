@@ -2,20 +2,46 @@
 
 #![allow(non_snake_case)]
 
-use crate::preprocessing::{DataNormalizer, FeatureEngineer};
-use crate::types::{ForecastingOutput, WeekData};
-use ndarray::{s, Array1, Array2};
+use crate::cancellation::CancellationToken;
+use crate::compact;
+use crate::float::{to_f64, Float};
+use crate::preprocessing::{
+    sanitize_weeks, DataNormalizer, FeatureEngineer, FeatureSelector, NonFinitePolicy,
+    PreprocessingState, SelectionCriterion, Winsorizer, PREPROCESSING_VERSION,
+};
+use crate::progress::{no_progress, TrainingProgress};
+use crate::types::{
+    DataSufficiency, ForecastExplanation, ForecastingOutput, GoalAssessment, ModelInfo, WeekData,
+};
+use chrono::Datelike;
+use ndarray::{s, Array1, Array2, Axis};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Версия формата сохраненной модели прогнозирования
+pub const FORECASTING_SNAPSHOT_VERSION: u32 = 1;
+
+/// Предел итераций байесовской оценки alpha
+const BAYESIAN_ITERATIONS: usize = 300;
+
+/// Границы автоматически выбранного alpha
+const MIN_RIDGE_ALPHA: Float = 1e-6;
+const MAX_RIDGE_ALPHA: Float = 1e6;
 
 /// Упрощенная Ridge Regression
+#[derive(Clone, Serialize, Deserialize)]
 struct SimpleRidge {
-    alpha: f64,
-    weights: Option<Array1<f64>>,
-    bias: Option<f64>,
+    alpha: Float,
+    weights: Option<Array1<Float>>,
+    bias: Option<Float>,
 }
 
 impl SimpleRidge {
-    fn new(alpha: f64) -> Self {
+    fn new(alpha: Float) -> Self {
         Self {
             alpha,
             weights: None,
@@ -23,63 +49,113 @@ impl SimpleRidge {
         }
     }
 
-    fn fit(&mut self, X: &Array2<f64>, y: &Array1<f64>) -> Result<(), String> {
-        let n_samples = X.nrows();
-        let n_features = X.ncols();
-
-        if n_samples == 0 || n_features == 0 {
+    /// Байесовская гребневая регрессия: alpha = точность весов / точность шума,
+    /// обе оцениваются максимизацией обоснованности (evidence) по MacKay.
+    /// Регуляризация подстраивается под масштаб и шум данных пользователя.
+    /// Веса наблюдений нормируются к среднему 1, так что их сумма остается
+    /// числом наблюдений в формулах обоснованности
+    fn fit_bayesian(
+        X: &Array2<Float>,
+        y: &Array1<Float>,
+        sample_weights: &Array1<Float>,
+    ) -> Result<Self, String> {
+        let n = X.nrows() as Float;
+        if X.nrows() == 0 || X.ncols() == 0 {
             return Err("Empty dataset".to_string());
         }
 
-        // Ridge Regression: (X^T X + αI)^(-1) X^T y
-        // Упрощенная версия через нормальные уравнения
+        let (x_mean, y_mean) = weighted_means(X, y, sample_weights)?;
+        let sqrt_weights = (sample_weights * (n / sample_weights.sum())).mapv(Float::sqrt);
+        let Xc = (X - &x_mean) * sqrt_weights.view().insert_axis(Axis(1));
+        let yc = (y - y_mean) * &sqrt_weights;
+        let xtx = Xc.t().dot(&Xc);
+        let xty = Xc.t().dot(&yc);
+        let eigenvalues = symmetric_eigenvalues(&xtx);
+
+        let y_variance = yc.mapv(|v| v * v).sum() / n;
+        if y_variance <= Float::EPSILON {
+            let mut model = SimpleRidge::new(1.0);
+            model.fit(X, y, sample_weights)?;
+            return Ok(model);
+        }
+
+        // Точности весов (alpha) и шума (beta)
+        let mut alpha: Float = 1.0;
+        let mut beta: Float = 1.0 / y_variance;
+        let solver = SimpleRidge::new(1.0);
+        for _ in 0..BAYESIAN_ITERATIONS {
+            let ridge = (alpha / beta).clamp(MIN_RIDGE_ALPHA, MAX_RIDGE_ALPHA);
+            let a = &xtx + &(Array2::<Float>::eye(X.ncols()) * ridge);
+            let weights = solver.solve_linear_system(&a, &xty)?;
+
+            // Эффективное число параметров
+            let gamma: Float = eigenvalues
+                .iter()
+                .map(|&l| beta * l.max(0.0) / (beta * l.max(0.0) + alpha))
+                .sum();
+            let rss = (&yc - &Xc.dot(&weights)).mapv(|r| r * r).sum();
+            let norm = weights.dot(&weights);
+            if norm <= Float::EPSILON || rss <= Float::EPSILON || n - gamma <= 0.0 {
+                break;
+            }
 
-        // X^T X
-        let mut xtx = Array2::zeros((n_features, n_features));
-        for i in 0..n_features {
-            for j in 0..n_features {
-                let mut sum = 0.0;
-                for k in 0..n_samples {
-                    sum += X[[k, i]] * X[[k, j]];
-                }
-                xtx[[i, j]] = sum;
+            let next_alpha = gamma / norm;
+            let next_beta = (n - gamma) / rss;
+            let next_ridge = (next_alpha / next_beta).clamp(MIN_RIDGE_ALPHA, MAX_RIDGE_ALPHA);
+            alpha = next_alpha;
+            beta = next_beta;
+            if ((next_ridge - ridge) / ridge).abs() < 1e-4 {
+                break;
             }
         }
 
-        // Добавляем регуляризацию (αI)
-        for i in 0..n_features {
-            xtx[[i, i]] += self.alpha;
-        }
+        let mut model = SimpleRidge::new((alpha / beta).clamp(MIN_RIDGE_ALPHA, MAX_RIDGE_ALPHA));
+        model.fit(X, y, sample_weights)?;
+        Ok(model)
+    }
 
-        // X^T y
-        let mut xty = Array1::zeros(n_features);
-        for i in 0..n_features {
-            let mut sum = 0.0;
-            for k in 0..n_samples {
-                sum += X[[k, i]] * y[k];
-            }
-            xty[i] = sum;
+    /// Взвешенные наименьшие квадраты с гребневым штрафом
+    fn fit(
+        &mut self,
+        X: &Array2<Float>,
+        y: &Array1<Float>,
+        sample_weights: &Array1<Float>,
+    ) -> Result<(), String> {
+        let n_samples = X.nrows();
+        let n_features = X.ncols();
+
+        if n_samples == 0 || n_features == 0 {
+            return Err("Empty dataset".to_string());
+        }
+        if sample_weights.len() != n_samples {
+            return Err("Sample weight count mismatch".to_string());
         }
 
+        // Ridge Regression: (X^T W X + αI)^(-1) X^T W y
+        // Нормальные уравнения через матричное умножение
+        let Xw = X * &sample_weights.view().insert_axis(Axis(1));
+        let xtx = Xw.t().dot(X) + Array2::<Float>::eye(n_features) * self.alpha;
+        let xty = Xw.t().dot(y);
+
         // Решение через упрощенный метод (для небольших матриц)
         // В реальности нужна более сложная инверсия, но для простоты используем приближение
         self.weights = Some(self.solve_linear_system(&xtx, &xty)?);
 
-        // Bias (среднее значение y минус среднее предсказание)
-        let y_mean = y.mean().unwrap_or(0.0);
-        let x_mean: Array1<f64> = (0..n_features)
-            .map(|j| (0..n_samples).map(|i| X[[i, j]]).sum::<f64>() / n_samples as f64)
-            .collect();
+        // Bias (взвешенное среднее y минус среднее предсказание)
+        let (x_mean, y_mean) = weighted_means(X, y, sample_weights)?;
 
         if let Some(ref weights) = self.weights {
-            let pred_mean: f64 = x_mean.iter().zip(weights.iter()).map(|(x, w)| x * w).sum();
-            self.bias = Some(y_mean - pred_mean);
+            self.bias = Some(y_mean - x_mean.dot(weights));
         }
 
         Ok(())
     }
 
-    fn solve_linear_system(&self, A: &Array2<f64>, b: &Array1<f64>) -> Result<Array1<f64>, String> {
+    fn solve_linear_system(
+        &self,
+        A: &Array2<Float>,
+        b: &Array1<Float>,
+    ) -> Result<Array1<Float>, String> {
         // Упрощенное решение через метод Гаусса (для небольших систем)
         let n = A.nrows();
         let mut augmented = Array2::zeros((n, n + 1));
@@ -139,37 +215,124 @@ impl SimpleRidge {
         Ok(x)
     }
 
-    fn predict(&self, X: &Array2<f64>) -> Result<Array1<f64>, String> {
+    fn predict(&self, X: &Array2<Float>) -> Result<Array1<Float>, String> {
         let weights = self.weights.as_ref().ok_or("Model not trained")?;
         let bias = self.bias.unwrap_or(0.0);
 
-        let mut predictions = Array1::zeros(X.nrows());
-        for i in 0..X.nrows() {
-            let mut pred = bias;
-            for j in 0..X.ncols() {
-                pred += X[[i, j]] * weights[j];
+        if weights.len() != X.ncols() {
+            return Err("Feature count mismatch".to_string());
+        }
+
+        Ok(X.dot(weights) + bias)
+    }
+}
+
+/// Взвешенные средние признаков и целевой переменной
+fn weighted_means(
+    X: &Array2<Float>,
+    y: &Array1<Float>,
+    sample_weights: &Array1<Float>,
+) -> Result<(Array1<Float>, Float), String> {
+    let total = sample_weights.sum();
+    if total <= 0.0 || !total.is_finite() {
+        return Err("Sample weights must have a positive sum".to_string());
+    }
+    Ok((sample_weights.dot(X) / total, sample_weights.dot(y) / total))
+}
+
+/// Веса наблюдений, убывающие вдвое каждые `half_life` наблюдений от последнего;
+/// `None` - все наблюдения равноправны
+fn recency_weights(n: usize, half_life: Option<Float>) -> Array1<Float> {
+    match half_life {
+        Some(half_life) => {
+            Array1::from_shape_fn(n, |i| (0.5 as Float).powf((n - 1 - i) as Float / half_life))
+        }
+        None => Array1::ones(n),
+    }
+}
+
+/// Собственные значения симметричной матрицы (метод вращений Якоби)
+fn symmetric_eigenvalues(matrix: &Array2<Float>) -> Vec<Float> {
+    let n = matrix.nrows();
+    let mut a = matrix.clone();
+    for _ in 0..100 {
+        let off_diagonal: Float = (0..n)
+            .flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j)))
+            .map(|(i, j)| a[[i, j]] * a[[i, j]])
+            .sum();
+        if off_diagonal < 1e-12 {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[[p, q]].abs() < 1e-12 {
+                    continue;
+                }
+                let theta = (a[[q, q]] - a[[p, p]]) / (2.0 * a[[p, q]]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for k in 0..n {
+                    let (akp, akq) = (a[[k, p]], a[[k, q]]);
+                    a[[k, p]] = c * akp - s * akq;
+                    a[[k, q]] = s * akp + c * akq;
+                }
+                for k in 0..n {
+                    let (apk, aqk) = (a[[p, k]], a[[q, k]]);
+                    a[[p, k]] = c * apk - s * aqk;
+                    a[[q, k]] = s * apk + c * aqk;
+                }
             }
-            predictions[i] = pred;
         }
+    }
+    (0..n).map(|i| a[[i, i]]).collect()
+}
+
+/// Обучающая выборка дерева с весами наблюдений
+struct WeightedSamples<'a> {
+    X: &'a Array2<Float>,
+    y: &'a Array1<Float>,
+    weights: &'a Array1<Float>,
+}
+
+impl WeightedSamples<'_> {
+    fn mean(&self, indices: &[usize]) -> Float {
+        let total: Float = indices.iter().map(|&i| self.weights[i]).sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        indices
+            .iter()
+            .map(|&i| self.weights[i] * self.y[i])
+            .sum::<Float>()
+            / total
+    }
 
-        Ok(predictions)
+    fn squared_error(&self, indices: &[usize]) -> Float {
+        let mean = self.mean(indices);
+        indices
+            .iter()
+            .map(|&i| self.weights[i] * (self.y[i] - mean).powi(2))
+            .sum()
     }
 }
 
 /// Упрощенный Decision Tree (регрессия)
+#[derive(Clone, Serialize, Deserialize)]
 struct SimpleTree {
     max_depth: usize,
     min_samples_split: usize,
     root: Option<TreeNode>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 enum TreeNode {
     Leaf {
-        value: f64,
+        value: Float,
     },
     Split {
         feature: usize,
-        threshold: f64,
+        threshold: Float,
         left: Box<TreeNode>,
         right: Box<TreeNode>,
     },
@@ -184,37 +347,57 @@ impl SimpleTree {
         }
     }
 
-    fn fit(&mut self, X: &Array2<f64>, y: &Array1<f64>) -> Result<(), String> {
+    fn fit(
+        &mut self,
+        X: &Array2<Float>,
+        y: &Array1<Float>,
+        sample_weights: &Array1<Float>,
+        rng: &mut StdRng,
+        token: &CancellationToken,
+    ) -> Result<(), String> {
         if X.nrows() == 0 {
             return Err("Empty dataset".to_string());
         }
+        if sample_weights.len() != X.nrows() {
+            return Err("Sample weight count mismatch".to_string());
+        }
 
-        self.root = Some(self.build_tree(X, y, 0, (0..X.nrows()).collect()));
+        let data = WeightedSamples {
+            X,
+            y,
+            weights: sample_weights,
+        };
+        self.root = Some(self.build_tree(&data, 0, (0..X.nrows()).collect(), rng, token)?);
         Ok(())
     }
 
     fn build_tree(
         &self,
-        X: &Array2<f64>,
-        y: &Array1<f64>,
+        data: &WeightedSamples,
         depth: usize,
         indices: Vec<usize>,
-    ) -> TreeNode {
+        rng: &mut StdRng,
+        token: &CancellationToken,
+    ) -> Result<TreeNode, String> {
+        token.check()?;
+        let X = data.X;
+
         if depth >= self.max_depth || indices.len() < self.min_samples_split {
-            // Лист: среднее значение
-            let mean = indices.iter().map(|&i| y[i]).sum::<f64>() / indices.len() as f64;
-            return TreeNode::Leaf { value: mean };
+            // Лист: взвешенное среднее значение
+            return Ok(TreeNode::Leaf {
+                value: data.mean(&indices),
+            });
         }
 
         // Поиск лучшего разделения
         let mut best_feature = 0;
         let mut best_threshold = 0.0;
-        let mut best_score = f64::INFINITY;
+        let mut best_score = Float::INFINITY;
 
         for feature in 0..X.ncols() {
-            let values: Vec<f64> = indices.iter().map(|&i| X[[i, feature]]).collect();
-            let min_val = values.iter().copied().fold(f64::INFINITY, f64::min);
-            let max_val = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            let values: Vec<Float> = indices.iter().map(|&i| X[[i, feature]]).collect();
+            let min_val = values.iter().copied().fold(Float::INFINITY, Float::min);
+            let max_val = values.iter().copied().fold(Float::NEG_INFINITY, Float::max);
 
             if (max_val - min_val).abs() < 1e-10 {
                 continue;
@@ -222,8 +405,6 @@ impl SimpleTree {
 
             // Пробуем несколько порогов
             for _ in 0..10 {
-                use rand::Rng;
-                let mut rng = rand::thread_rng();
                 let threshold = rng.gen_range(min_val..=max_val);
 
                 let (left_indices, right_indices): (Vec<usize>, Vec<usize>) =
@@ -233,21 +414,9 @@ impl SimpleTree {
                     continue;
                 }
 
-                // Вычисляем MSE
-                let left_mean =
-                    left_indices.iter().map(|&i| y[i]).sum::<f64>() / left_indices.len() as f64;
-                let right_mean =
-                    right_indices.iter().map(|&i| y[i]).sum::<f64>() / right_indices.len() as f64;
-
-                let left_mse: f64 = left_indices
-                    .iter()
-                    .map(|&i| (y[i] - left_mean).powi(2))
-                    .sum();
-                let right_mse: f64 = right_indices
-                    .iter()
-                    .map(|&i| (y[i] - right_mean).powi(2))
-                    .sum();
-                let total_mse = left_mse + right_mse;
+                // Вычисляем взвешенную сумму квадратов отклонений
+                let total_mse =
+                    data.squared_error(&left_indices) + data.squared_error(&right_indices);
 
                 if total_mse < best_score {
                     best_score = total_mse;
@@ -257,10 +426,11 @@ impl SimpleTree {
             }
         }
 
-        if best_score == f64::INFINITY {
+        if best_score == Float::INFINITY {
             // Не удалось найти хорошее разделение
-            let mean = indices.iter().map(|&i| y[i]).sum::<f64>() / indices.len() as f64;
-            return TreeNode::Leaf { value: mean };
+            return Ok(TreeNode::Leaf {
+                value: data.mean(&indices),
+            });
         }
 
         // Разделение
@@ -268,15 +438,15 @@ impl SimpleTree {
             .iter()
             .partition(|&&i| X[[i, best_feature]] < best_threshold);
 
-        TreeNode::Split {
+        Ok(TreeNode::Split {
             feature: best_feature,
             threshold: best_threshold,
-            left: Box::new(self.build_tree(X, y, depth + 1, left_indices)),
-            right: Box::new(self.build_tree(X, y, depth + 1, right_indices)),
-        }
+            left: Box::new(self.build_tree(data, depth + 1, left_indices, rng, token)?),
+            right: Box::new(self.build_tree(data, depth + 1, right_indices, rng, token)?),
+        })
     }
 
-    fn predict(&self, X: &Array2<f64>) -> Result<Array1<f64>, String> {
+    fn predict(&self, X: &Array2<Float>) -> Result<Array1<Float>, String> {
         let root = self.root.as_ref().ok_or("Model not trained")?;
         let mut predictions = Array1::zeros(X.nrows());
 
@@ -287,7 +457,7 @@ impl SimpleTree {
         Ok(predictions)
     }
 
-    fn predict_single(&self, node: &TreeNode, sample: &Array1<f64>) -> f64 {
+    fn predict_single(&self, node: &TreeNode, sample: &Array1<Float>) -> Float {
         match node {
             TreeNode::Leaf { value } => *value,
             TreeNode::Split {
@@ -306,171 +476,612 @@ impl SimpleTree {
     }
 }
 
-pub struct ForecastingModel {
-    tree_model: Option<SimpleTree>,
-    linear_model: Option<SimpleRidge>,
-    normalizer: DataNormalizer,
-    is_trained: bool,
+/// Прогноз по аналогам: среднее часов недель, следовавших за `k` ближайшими
+/// к текущей историческими неделями в пространстве нормализованных признаков.
+/// Подходит пользователям, чьи недели не следуют тренду
+#[derive(Clone, Serialize, Deserialize)]
+struct KnnForecaster {
+    k: usize,
+    samples: Option<Array2<Float>>,
+    /// Часы недели, следующей за каждым образцом
+    outcomes: Option<Array1<Float>>,
 }
 
-impl ForecastingModel {
-    pub fn new() -> Self {
+impl KnnForecaster {
+    fn new(k: usize) -> Self {
         Self {
-            tree_model: None,
-            linear_model: None,
-            normalizer: DataNormalizer::new(),
-            is_trained: false,
+            k,
+            samples: None,
+            outcomes: None,
         }
     }
 
-    pub fn train(&mut self, weeks: &[WeekData]) -> Result<(), String> {
-        if weeks.len() < 8 {
-            return Err("Need at least 8 weeks of data for training".to_string());
+    fn fit(&mut self, X: &Array2<Float>, next_hours: &Array1<Float>) -> Result<(), String> {
+        if X.nrows() == 0 {
+            return Err("Empty dataset".to_string());
         }
+        if X.nrows() != next_hours.len() {
+            return Err("Sample count mismatch".to_string());
+        }
+        self.samples = Some(X.clone());
+        self.outcomes = Some(next_hours.clone());
+        Ok(())
+    }
 
-        // Извлечение признаков
-        let (X, y) = FeatureEngineer::extract_temporal_features(weeks)?;
-
-        // Разделение на train/test (80/20)
-        let split_idx = (X.nrows() as f64 * 0.8) as usize;
-        let X_train = X.slice(s![..split_idx, ..]).to_owned();
-        let X_test = X.slice(s![split_idx.., ..]).to_owned();
-        let y_train = y.slice(s![..split_idx]).to_owned();
-        let y_test = y.slice(s![split_idx..]).to_owned();
+    fn predict(&self, X: &Array2<Float>) -> Result<Array1<Float>, String> {
+        let samples = self.samples.as_ref().ok_or("Model not trained")?;
+        let outcomes = self.outcomes.as_ref().ok_or("Model not trained")?;
+        if samples.ncols() != X.ncols() {
+            return Err("Feature count mismatch".to_string());
+        }
 
-        // Нормализация
-        let X_train_scaled = self.normalizer.fit_transform(&X_train)?;
-        let X_test_scaled = self.normalizer.transform(&X_test)?;
+        let k = self.k.clamp(1, samples.nrows());
+        let mut predictions = Array1::zeros(X.nrows());
+        for (i, query) in X.rows().into_iter().enumerate() {
+            let mut distances: Vec<(Float, usize)> = samples
+                .rows()
+                .into_iter()
+                .enumerate()
+                .map(|(j, sample)| {
+                    let distance: Float = sample
+                        .iter()
+                        .zip(query.iter())
+                        .map(|(a, b)| (a - b).powi(2))
+                        .sum();
+                    (distance, j)
+                })
+                .collect();
+            distances.sort_by(|a, b| a.0.total_cmp(&b.0));
+            predictions[i] = distances[..k]
+                .iter()
+                .map(|&(_, j)| outcomes[j])
+                .sum::<Float>()
+                / k as Float;
+        }
+        Ok(predictions)
+    }
+}
 
-        // Обучение Decision Tree
-        let mut tree = SimpleTree::new(10, 5);
-        tree.fit(&X_train_scaled, &y_train)?;
-        self.tree_model = Some(tree);
+/// Длина года в неделях для годовой сезонности
+const WEEKS_PER_YEAR: f64 = 52.1775;
+
+/// Модель в духе Prophet: кусочно-линейный тренд с точками излома, годовая
+/// сезонность рядом Фурье по неделе года и эффект праздничных недель.
+/// Выражает смену темпа ("в марте пришел новый клиент"), которую не видят
+/// признаки скользящих средних. Недельные данные не содержат внутринедельной
+/// сезонности, поэтому она не моделируется
+#[derive(Clone, Serialize, Deserialize)]
+struct ProphetModel {
+    changepoints: usize,
+    fourier_order: usize,
+    changepoint_penalty: f64,
+    /// Положения точек излома на шкале времени [0, 1] обучающей истории
+    changepoint_positions: Vec<f64>,
+    coefficients: Option<Vec<f64>>,
+    /// Масштаб часов (максимум истории)
+    y_scale: f64,
+    n_weeks: usize,
+    /// Праздничные недели (ISO год, неделя)
+    holiday_weeks: Vec<(i32, u32)>,
+}
 
-        // Обучение Linear Model (Ridge)
-        let mut linear = SimpleRidge::new(1.0);
-        linear.fit(&X_train_scaled, &y_train)?;
-        self.linear_model = Some(linear);
+impl ProphetModel {
+    fn new(changepoints: usize, fourier_order: usize, changepoint_penalty: f64) -> Self {
+        Self {
+            changepoints,
+            fourier_order,
+            changepoint_penalty,
+            changepoint_positions: Vec::new(),
+            coefficients: None,
+            y_scale: 1.0,
+            n_weeks: 0,
+            holiday_weeks: Vec::new(),
+        }
+    }
 
-        self.is_trained = true;
+    /// Обучение на всей истории; `holidays` - даты `YYYY-MM-DD`
+    fn fit(&mut self, weeks: &[WeekData], holidays: &[String]) -> Result<(), String> {
+        if weeks.len() < 2 {
+            return Err("Need at least 2 weeks for trend model".to_string());
+        }
+        let mut holiday_weeks: Vec<(i32, u32)> = holidays
+            .iter()
+            .filter_map(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .map(|d| (d.iso_week().year(), d.iso_week().week()))
+            .collect();
+        holiday_weeks.sort();
+        holiday_weeks.dedup();
 
-        // Оценка качества (опционально, для логирования)
-        if let (Some(ref tree), Some(ref linear)) = (&self.tree_model, &self.linear_model) {
-            let tree_pred = tree.predict(&X_test_scaled)?;
-            let linear_pred = linear.predict(&X_test_scaled)?;
+        let n = weeks.len();
+        let y_scale = weeks
+            .iter()
+            .map(|w| w.total_hours.abs())
+            .fold(0.0, f64::max)
+            .max(1.0);
+        // Точки излома равномерно в первых 80% истории, как в Prophet
+        let changepoints = self.changepoints.min(n.saturating_sub(2));
+        let changepoint_positions: Vec<f64> = (1..=changepoints)
+            .map(|j| 0.8 * j as f64 / (changepoints + 1) as f64)
+            .collect();
 
-            // Ensemble
-            let ensemble_pred: Array1<f64> = tree_pred * 0.7 + linear_pred * 0.3;
+        let fitted = Self {
+            changepoint_positions,
+            y_scale,
+            n_weeks: n,
+            holiday_weeks,
+            ..self.clone()
+        };
+        let rows: Vec<Vec<f64>> = weeks
+            .iter()
+            .enumerate()
+            .map(|(i, w)| fitted.design_row(i, w.year, w.week as u32))
+            .collect();
+        let n_columns = rows[0].len();
+
+        // Штрафы: изломы тренда - сильнее, сезонность и праздники - слабо, база и наклон - нет
+        let mut penalties = vec![1.0; n_columns];
+        penalties[0] = 0.0;
+        penalties[1] = 0.0;
+        for penalty in &mut penalties[2..2 + fitted.changepoint_positions.len()] {
+            *penalty = self.changepoint_penalty;
+        }
 
-            // MAE
-            let mae = (ensemble_pred - y_test)
-                .mapv(|x| x.abs())
-                .mean()
-                .unwrap_or(0.0);
-            tracing::info!("Forecasting model trained. MAE: {:.2}", mae);
+        let mut ata = Array2::<Float>::zeros((n_columns, n_columns));
+        let mut aty = Array1::<Float>::zeros(n_columns);
+        for (row, week) in rows.iter().zip(weeks) {
+            let target = week.total_hours / y_scale;
+            for a in 0..n_columns {
+                aty[a] += (row[a] * target) as Float;
+                for b in 0..n_columns {
+                    ata[[a, b]] += (row[a] * row[b]) as Float;
+                }
+            }
         }
+        for (a, penalty) in penalties.iter().enumerate() {
+            ata[[a, a]] += *penalty as Float;
+        }
+        let coefficients = SimpleRidge::new(0.0).solve_linear_system(&ata, &aty)?;
 
+        *self = Self {
+            coefficients: Some(coefficients.iter().map(|&c| to_f64(c)).collect()),
+            ..fitted
+        };
         Ok(())
     }
 
+    /// Прогноз на неделю после последней из `weeks`
+    fn predict_next(&self, weeks: &[WeekData]) -> Result<f64, String> {
+        let coefficients = self.coefficients.as_ref().ok_or("Model not trained")?;
+        let last = weeks.last().ok_or("No weeks provided")?;
+        let (year, week) = next_iso_week(last.year, last.week as u32);
+        // Время продолжает шкалу обучения: история могла вырасти после него
+        let index = self.n_weeks.max(weeks.len());
+        let row = self.design_row(index, year, week);
+        let value: f64 = row.iter().zip(coefficients).map(|(x, c)| x * c).sum();
+        Ok(value * self.y_scale)
+    }
+
+    /// Столбцы: база, наклон, изломы, пары Фурье, праздник
+    fn design_row(&self, index: usize, year: i32, week: u32) -> Vec<f64> {
+        let t = index as f64 / (self.n_weeks.max(2) - 1) as f64;
+        let mut row = vec![1.0, t];
+        row.extend(self.changepoint_positions.iter().map(|&c| (t - c).max(0.0)));
+        for k in 1..=self.fourier_order {
+            let angle = 2.0 * std::f64::consts::PI * k as f64 * week as f64 / WEEKS_PER_YEAR;
+            row.push(angle.sin());
+            row.push(angle.cos());
+        }
+        row.push(self.holiday_weeks.contains(&(year, week)) as u8 as f64);
+        row
+    }
+}
+
+/// Следующая ISO неделя; для некорректной недели - просто следующий номер
+pub fn next_iso_week(year: i32, week: u32) -> (i32, u32) {
+    match chrono::NaiveDate::from_isoywd_opt(year, week, chrono::Weekday::Mon) {
+        Some(monday) => {
+            let next = (monday + chrono::Duration::days(7)).iso_week();
+            (next.year(), next.week())
+        }
+        None => (year, week + 1),
+    }
+}
+
+/// Недели для моделей: без NaN и бесконечностей (`sanitize_weeks`), по порядку
+/// и без повторов (`FeatureEngineer::canonical_weeks`)
+fn prepare_weeks(
+    weeks: &[WeekData],
+    policy: NonFinitePolicy,
+) -> Result<Cow<'_, [WeekData]>, String> {
+    let weeks = sanitize_weeks(weeks, policy)?;
+    let (canonical, report) = FeatureEngineer::canonical_weeks(&weeks);
+    if report.merged.is_empty() && !report.reordered && report.skipped.is_empty() {
+        return Ok(weeks);
+    }
+    Ok(Cow::Owned(canonical.into_owned()))
+}
+
+/// Прогнозы отдельных моделей для недели, следующей за историей
+struct ComponentPredictions {
+    tree: Option<f64>,
+    linear: Option<f64>,
+    knn: Option<f64>,
+    prophet: Option<f64>,
+}
+
+/// Гиперпараметры обучения по умолчанию; `options` запроса их переопределяют
+#[derive(Debug, Clone)]
+struct ForecastingParams {
+    /// `None` - alpha выбирается байесовской гребневой регрессией
+    linear_alpha: Option<Float>,
+    tree_max_depth: usize,
+    min_samples_split: usize,
+    feature_selection: Option<SelectionCriterion>,
+    feature_min_score: Float,
+    clip_outliers: bool,
+    clip_lower_quantile: Float,
+    clip_upper_quantile: Float,
+    knn_neighbors: usize,
+    changepoints: usize,
+    fourier_order: usize,
+    changepoint_penalty: f64,
+    pooling_strength: f64,
+    /// Период полураспада веса недели при обучении (в неделях); `None` - без затухания
+    recency_half_life: Option<Float>,
+    seed: Option<u64>,
+    /// NaN и бесконечности в неделях при обучении и прогнозе
+    non_finite: NonFinitePolicy,
+}
+
+impl Default for ForecastingParams {
+    fn default() -> Self {
+        Self {
+            linear_alpha: None,
+            tree_max_depth: 10,
+            min_samples_split: 5,
+            feature_selection: Some(SelectionCriterion::Correlation),
+            feature_min_score: 0.05,
+            clip_outliers: true,
+            clip_lower_quantile: 0.05,
+            clip_upper_quantile: 0.95,
+            knn_neighbors: 5,
+            changepoints: 5,
+            fourier_order: 3,
+            changepoint_penalty: 10.0,
+            pooling_strength: 4.0,
+            recency_half_life: Some(26.0),
+            seed: None,
+            non_finite: NonFinitePolicy::default(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ForecastingModel {
+    params: ForecastingParams,
+    tree_model: Option<SimpleTree>,
+    linear_model: Option<SimpleRidge>,
+    knn_model: Option<KnnForecaster>,
+    prophet_model: Option<ProphetModel>,
+    feature_selector: Option<FeatureSelector>,
+    clipper: Option<Winsorizer>,
+    normalizer: DataNormalizer,
+    is_trained: bool,
+    trained_at: Option<String>,
+    training_samples: usize,
+}
+
+impl ForecastingModel {
+    pub fn new() -> Self {
+        Self::with_params(ForecastingParams::default())
+    }
+
+    pub fn builder() -> ForecastingModelBuilder {
+        ForecastingModelBuilder::default()
+    }
+
+    fn with_params(params: ForecastingParams) -> Self {
+        Self {
+            params,
+            tree_model: None,
+            linear_model: None,
+            knn_model: None,
+            prophet_model: None,
+            feature_selector: None,
+            clipper: None,
+            normalizer: DataNormalizer::new(),
+            is_trained: false,
+            trained_at: None,
+            training_samples: 0,
+        }
+    }
+
+    pub fn train(&mut self, weeks: &[WeekData]) -> Result<(), String> {
+        self.train_with_options(weeks, None)
+    }
+
     /// Train with optional JSON options (hyperparameters)
     pub fn train_with_options(
         &mut self,
         weeks: &[WeekData],
         options: Option<&JsonValue>,
     ) -> Result<(), String> {
-        if weeks.len() < 8 {
-            return Err("Need at least 8 weeks of data for training".to_string());
+        self.train_with_cancellation(weeks, options, &CancellationToken::new())
+    }
+
+    /// Обучение с возможностью кооперативной отмены.
+    ///
+    /// Токен проверяется при построении дерева; при отмене возвращается
+    /// `TRAINING_CANCELLED`, а ранее обученное состояние модели не меняется.
+    pub fn train_with_cancellation(
+        &mut self,
+        weeks: &[WeekData],
+        options: Option<&JsonValue>,
+        token: &CancellationToken,
+    ) -> Result<(), String> {
+        self.train_with_progress(weeks, options, token, &no_progress)
+    }
+
+    /// Обучение с отменой и отчетами о ходе (стадии конвейера)
+    pub fn train_with_progress(
+        &mut self,
+        weeks: &[WeekData],
+        options: Option<&JsonValue>,
+        token: &CancellationToken,
+        progress: &dyn Fn(TrainingProgress),
+    ) -> Result<(), String> {
+        const STEPS: usize = 6;
+
+        if weeks.len() < DataSufficiency::FULL_WEEKS {
+            return Err(format!(
+                "Need at least {} weeks of data for training",
+                DataSufficiency::FULL_WEEKS
+            ));
         }
 
-        // parse hyperparameters
+        // parse hyperparameters (defaults come from the builder)
+        let params = &self.params;
         let linear_alpha = options
             .and_then(|o| o.get("linear_alpha"))
             .and_then(|v| v.as_f64())
-            .unwrap_or(1.0);
+            .map(|v| v as Float)
+            .or(params.linear_alpha);
 
         let tree_max_depth = options
             .and_then(|o| o.get("tree_max_depth"))
             .and_then(|v| v.as_u64())
             .map(|v| v as usize)
-            .unwrap_or(10);
+            .unwrap_or(params.tree_max_depth);
 
         let min_samples_split = options
             .and_then(|o| o.get("min_samples_split"))
             .and_then(|v| v.as_u64())
             .map(|v| v as usize)
-            .unwrap_or(5);
+            .unwrap_or(params.min_samples_split);
+
+        // "correlation" (default) | "mutual_information" | "none"
+        let feature_selection = match options
+            .and_then(|o| o.get("feature_selection"))
+            .and_then(|v| v.as_str())
+        {
+            Some(name) => SelectionCriterion::from_name(name),
+            None => params.feature_selection,
+        };
+
+        let feature_min_score = options
+            .and_then(|o| o.get("feature_min_score"))
+            .and_then(|v| v.as_f64())
+            .map(|v| v as Float)
+            .unwrap_or(params.feature_min_score);
+
+        // Квантили обрезки выбросов; "clip_outliers": false отключает обрезку
+        let clip_outliers = options
+            .and_then(|o| o.get("clip_outliers"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(params.clip_outliers);
+
+        let clip_lower = options
+            .and_then(|o| o.get("clip_lower_quantile"))
+            .and_then(|v| v.as_f64())
+            .map(|v| v as Float)
+            .unwrap_or(params.clip_lower_quantile);
+
+        let clip_upper = options
+            .and_then(|o| o.get("clip_upper_quantile"))
+            .and_then(|v| v.as_f64())
+            .map(|v| v as Float)
+            .unwrap_or(params.clip_upper_quantile);
+
+        let knn_neighbors = options
+            .and_then(|o| o.get("knn_neighbors"))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(params.knn_neighbors);
+        if knn_neighbors == 0 {
+            return Err("knn_neighbors must be positive".to_string());
+        }
+
+        // Модель тренда с изломами: число изломов, порядок ряда Фурье, штраф изломов
+        // и даты праздников `YYYY-MM-DD`
+        let changepoints = options
+            .and_then(|o| o.get("changepoints"))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(params.changepoints);
+
+        let fourier_order = options
+            .and_then(|o| o.get("fourier_order"))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+            .unwrap_or(params.fourier_order);
+
+        let changepoint_penalty = options
+            .and_then(|o| o.get("changepoint_penalty"))
+            .and_then(|v| v.as_f64())
+            .unwrap_or(params.changepoint_penalty);
+        if !(changepoint_penalty > 0.0 && changepoint_penalty.is_finite()) {
+            return Err(format!(
+                "Invalid changepoint_penalty: {}",
+                changepoint_penalty
+            ));
+        }
+
+        let holidays: Vec<String> = options
+            .and_then(|o| o.get("holidays"))
+            .and_then(|v| v.as_array())
+            .map(|dates| {
+                dates
+                    .iter()
+                    .filter_map(|d| d.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // Затухание веса старых недель; 0 отключает затухание
+        let recency_half_life = match options.and_then(|o| o.get("recency_half_life")) {
+            Some(value) => match value.as_f64() {
+                Some(0.0) => None,
+                Some(half_life) if half_life > 0.0 && half_life.is_finite() => {
+                    Some(half_life as Float)
+                }
+                _ => return Err(format!("Invalid recency_half_life: {}", value)),
+            },
+            None => params.recency_half_life,
+        };
+
+        // Зерно генератора для воспроизводимого обучения
+        let seed = options
+            .and_then(|o| o.get("seed"))
+            .and_then(|v| v.as_u64())
+            .or(params.seed);
+
+        // NaN и бесконечности в неделях: "impute" (по умолчанию) | "reject"
+        let non_finite = NonFinitePolicy::from_options(options)?.unwrap_or(params.non_finite);
+        let weeks = prepare_weeks(weeks, non_finite)?;
+        let weeks: &[WeekData] = &weeks;
 
         // Извлечение признаков
         let (X, y) = FeatureEngineer::extract_temporal_features(weeks)?;
 
         // Разделение на train/test (80/20)
-        let split_idx = (X.nrows() as f64 * 0.8) as usize;
+        let split_idx = (X.nrows() as Float * 0.8) as usize;
         let X_train = X.slice(s![..split_idx, ..]).to_owned();
         let X_test = X.slice(s![split_idx.., ..]).to_owned();
         let y_train = y.slice(s![..split_idx]).to_owned();
         let y_test = y.slice(s![split_idx..]).to_owned();
+        progress(TrainingProgress::new("features", 1, STEPS));
+
+        // Отбор признаков
+        let (feature_selector, X_train, X_test) = match feature_selection {
+            Some(criterion) => {
+                let mut selector = FeatureSelector::new(criterion, feature_min_score);
+                let X_train = selector.fit_transform(&X_train, &y_train)?;
+                let X_test = selector.transform(&X_test)?;
+                (Some(selector), X_train, X_test)
+            }
+            None => (None, X_train, X_test),
+        };
+
+        // Обрезка выбросов
+        let (clipper, X_train, X_test) = if clip_outliers {
+            let mut clipper = Winsorizer::new(clip_lower, clip_upper)?;
+            let X_train = clipper.fit_transform(&X_train)?;
+            let X_test = clipper.transform(&X_test)?;
+            (Some(clipper), X_train, X_test)
+        } else {
+            (None, X_train, X_test)
+        };
 
         // Нормализация
-        let X_train_scaled = self.normalizer.fit_transform(&X_train)?;
-        let X_test_scaled = self.normalizer.transform(&X_test)?;
+        let mut normalizer = DataNormalizer::new();
+        let X_train_scaled = normalizer.fit_transform(&X_train)?;
+        let X_test_scaled = normalizer.transform(&X_test)?;
+        progress(TrainingProgress::new("preprocessing", 2, STEPS));
 
         // Обучение Decision Tree with parameters
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let sample_weights = recency_weights(X_train_scaled.nrows(), recency_half_life);
         let mut tree = SimpleTree::new(tree_max_depth, min_samples_split);
-        tree.fit(&X_train_scaled, &y_train)?;
-        self.tree_model = Some(tree);
+        tree.fit(&X_train_scaled, &y_train, &sample_weights, &mut rng, token)?;
+        progress(TrainingProgress::new("tree", 3, STEPS));
 
         // Обучение Linear Model (Ridge) with alpha
-        let mut linear = SimpleRidge::new(linear_alpha);
-        linear.fit(&X_train_scaled, &y_train)?;
-        self.linear_model = Some(linear);
-
-        self.is_trained = true;
+        token.check()?;
+        let linear = match linear_alpha {
+            Some(alpha) => {
+                let mut linear = SimpleRidge::new(alpha);
+                linear.fit(&X_train_scaled, &y_train, &sample_weights)?;
+                linear
+            }
+            None => SimpleRidge::fit_bayesian(&X_train_scaled, &y_train, &sample_weights)?,
+        };
+        progress(TrainingProgress::new("linear", 4, STEPS));
+
+        // Аналоги: каждая неделя, кроме последней, и часы следующей за ней недели
+        token.check()?;
+        let X_scaled =
+            ndarray::concatenate(Axis(0), &[X_train_scaled.view(), X_test_scaled.view()])
+                .map_err(|e| format!("Failed to join samples: {}", e))?;
+        let last = X_scaled.nrows() - 1;
+        let mut knn = KnnForecaster::new(knn_neighbors);
+        knn.fit(
+            &X_scaled.slice(s![..last, ..]).to_owned(),
+            &y.slice(s![1..]).to_owned(),
+        )?;
+        progress(TrainingProgress::new("knn", 5, STEPS));
+
+        token.check()?;
+        let mut prophet = ProphetModel::new(changepoints, fourier_order, changepoint_penalty);
+        prophet.fit(weeks, &holidays)?;
+        progress(TrainingProgress::new("prophet", 6, STEPS));
 
         // Оценка качества (опционально, для логирования)
-        if let (Some(ref tree), Some(ref linear)) = (&self.tree_model, &self.linear_model) {
-            let tree_pred = tree.predict(&X_test_scaled)?;
-            let linear_pred = linear.predict(&X_test_scaled)?;
-
-            // Ensemble
-            let ensemble_pred: Array1<f64> = tree_pred * 0.7 + linear_pred * 0.3;
+        let tree_pred = tree.predict(&X_test_scaled)?;
+        let linear_pred = linear.predict(&X_test_scaled)?;
 
-            // MAE
-            let mae = (ensemble_pred - y_test)
-                .mapv(|x| x.abs())
-                .mean()
-                .unwrap_or(0.0);
-            tracing::info!("Forecasting model trained (opts: linear_alpha={}, tree_max_depth={}, min_samples_split={}). MAE: {:.2}", linear_alpha, tree_max_depth, min_samples_split, mae);
-        }
+        // Ensemble
+        let ensemble_pred: Array1<Float> = tree_pred * 0.7 + linear_pred * 0.3;
+
+        // MAE
+        let mae = (ensemble_pred - y_test)
+            .mapv(|x| x.abs())
+            .mean()
+            .unwrap_or(0.0);
+        tracing::info!("Forecasting model trained (opts: linear_alpha={}, tree_max_depth={}, min_samples_split={}). MAE: {:.2}", linear.alpha, tree_max_depth, min_samples_split, mae);
+
+        // Фиксируем состояние только после успешного обучения
+        self.feature_selector = feature_selector;
+        self.clipper = clipper;
+        self.normalizer = normalizer;
+        self.tree_model = Some(tree);
+        self.linear_model = Some(linear);
+        self.knn_model = Some(knn);
+        self.prophet_model = Some(prophet);
+        self.is_trained = true;
+        self.trained_at = Some(chrono::Utc::now().to_rfc3339());
+        self.training_samples = weeks.len();
 
         Ok(())
     }
 
     pub fn predict(&self, weeks: &[WeekData]) -> Result<ForecastingOutput, String> {
+        let weeks = prepare_weeks(weeks, self.params.non_finite)?;
+        let weeks: &[WeekData] = &weeks;
+        if DataSufficiency::from_weeks(weeks.len()) != DataSufficiency::Full {
+            return Ok(Self::fallback_forecast(weeks));
+        }
         if !self.is_trained {
             return Err("Model not trained".to_string());
         }
 
-        if weeks.len() < 4 {
-            // Если недостаточно данных, используем среднее
-            let avg_hours = if weeks.is_empty() {
-                0.0
-            } else {
-                weeks.iter().map(|w| w.total_hours).sum::<f64>() / weeks.len() as f64
-            };
-            return Ok(ForecastingOutput {
-                weekly_hours: avg_hours,
-                weekly_hours_by_project: std::collections::HashMap::new(),
-                monthly_hours: avg_hours * 4.0,
-                confidence: 0.3,
-                trend: "stable".to_string(),
-            });
-        }
-
         // Извлечение признаков для последней недели
         let (features, _) = FeatureEngineer::extract_temporal_features(weeks)?;
         let last_idx = features.nrows() - 1;
         let last_week_features = features.slice(s![last_idx..last_idx + 1, ..]).to_owned();
+        let last_week_features = self.apply_transformers(last_week_features)?;
 
         // Нормализация
         let X_scaled = self.normalizer.transform(&last_week_features)?;
@@ -478,107 +1089,146 @@ impl ForecastingModel {
         // Предсказания
         let tree_pred = if let Some(ref tree) = self.tree_model {
             let pred = tree.predict(&X_scaled)?;
-            pred[0]
+            to_f64(pred[0])
         } else {
             return Err("Tree model not available".to_string());
         };
 
         let linear_pred = if let Some(ref linear) = self.linear_model {
             let pred = linear.predict(&X_scaled)?;
-            pred[0]
+            to_f64(pred[0])
         } else {
             return Err("Linear model not available".to_string());
         };
 
         // Ensemble
         let ensemble_pred = tree_pred * 0.7 + linear_pred * 0.3;
+        debug_assert!(
+            ensemble_pred.is_finite(),
+            "non-finite forecast: {}",
+            ensemble_pred
+        );
 
         // Confidence на основе разброса предсказаний
         let pred_std = (tree_pred - linear_pred).abs();
         let confidence = (1.0 / (1.0 + pred_std)).min(1.0);
 
         // Определение тренда
-        let trend = if weeks.len() >= 2 {
-            let recent_trend =
-                weeks[weeks.len() - 1].total_hours - weeks[weeks.len() - 2].total_hours;
-            if recent_trend > 2.0 {
-                "increasing"
-            } else if recent_trend < -2.0 {
-                "decreasing"
-            } else {
-                "stable"
-            }
-        } else {
-            "stable"
-        };
-
-        // Прогноз по проектам с учетом целей пользователя
-        let mut weekly_hours_by_project = std::collections::HashMap::new();
-        if let Some(last_week) = weeks.last() {
-            let total_current = last_week.total_hours;
+        let trend = detect_trend(weeks);
 
-            if total_current > 0.0 {
-                // Без целей - пропорционально текущему распределению
-                for stat in &last_week.project_stats {
-                    let ratio = stat.hours / total_current;
-                    weekly_hours_by_project.insert(stat.project_id, ensemble_pred * ratio);
-                }
-            }
-        }
+        // Прогноз по проектам: доли проектов, сглаженные к типичным для пользователя
+        let weekly_hours_by_project = project_shares(weeks, self.params.pooling_strength)
+            .into_iter()
+            .map(|(project_id, share)| (project_id, ensemble_pred * share))
+            .collect();
 
         Ok(ForecastingOutput {
             weekly_hours: ensemble_pred,
             weekly_hours_by_project,
             monthly_hours: ensemble_pred * 4.0,
             confidence,
-            trend: trend.to_string(),
+            trend: trend.direction.to_string(),
+            trend_strength: trend.strength,
+            model_info: Some(self.info_for(&["decision_tree", "ridge"])),
+            billable_hours: None,
+            data_sufficiency: DataSufficiency::Full,
+            explanation: None,
+            goal_assessments: Vec::new(),
+            clamped: false,
+            warnings: Vec::new(),
         })
     }
 
+    /// Прогноз без моделей для истории короче `DataSufficiency::FULL_WEEKS`:
+    /// среднее часов с уверенностью, трендом и долями проектов по уровню истории.
+    /// Обученная модель для такой истории не нужна
+    pub fn fallback_forecast(weeks: &[WeekData]) -> ForecastingOutput {
+        let sufficiency = DataSufficiency::from_weeks(weeks.len());
+        let avg_hours = if weeks.is_empty() {
+            0.0
+        } else {
+            weeks.iter().map(|w| w.total_hours).sum::<f64>() / weeks.len() as f64
+        };
+        let confidence = if sufficiency == DataSufficiency::None {
+            0.0
+        } else {
+            0.3
+        };
+        let (trend, trend_strength, weekly_hours_by_project) =
+            if sufficiency >= DataSufficiency::Limited {
+                let trend = detect_trend(weeks);
+                let shares = project_shares(weeks, ForecastingParams::default().pooling_strength);
+                (
+                    trend.direction,
+                    trend.strength,
+                    shares
+                        .into_iter()
+                        .map(|(project_id, share)| (project_id, avg_hours * share))
+                        .collect(),
+                )
+            } else {
+                ("stable", 0.0, HashMap::new())
+            };
+        ForecastingOutput {
+            weekly_hours: avg_hours,
+            weekly_hours_by_project,
+            monthly_hours: avg_hours * 4.0,
+            confidence,
+            trend: trend.to_string(),
+            trend_strength,
+            model_info: Some(ModelInfo::baseline(weeks.len())),
+            billable_hours: None,
+            data_sufficiency: sufficiency,
+            explanation: None,
+            goal_assessments: Vec::new(),
+            clamped: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Индексы признаков, оставленных при последнем обучении
+    pub fn selected_features(&self) -> Option<&[usize]> {
+        self.feature_selector
+            .as_ref()
+            .and_then(|selector| selector.selected_features())
+    }
+
+    /// Отбор признаков и обрезка выбросов с параметрами последнего обучения
+    fn apply_transformers(&self, X: Array2<Float>) -> Result<Array2<Float>, String> {
+        let X = match self.feature_selector {
+            Some(ref selector) => selector.transform(&X)?,
+            None => X,
+        };
+        match self.clipper {
+            Some(ref clipper) => clipper.transform(&X),
+            None => Ok(X),
+        }
+    }
+
     /// Predict with optional model choice. If `choice` is Some("linear") will use linear model only,
-    /// if Some("tree") will use tree only, otherwise ensemble (default).
+    /// if Some("tree") will use tree only, if Some("knn") - analog weeks only,
+    /// if Some("prophet") - piecewise trend with seasonality, otherwise ensemble (default).
     pub fn predict_with_choice(
         &self,
         weeks: &[WeekData],
         choice: Option<&str>,
     ) -> Result<ForecastingOutput, String> {
+        let weeks = prepare_weeks(weeks, self.params.non_finite)?;
+        let weeks: &[WeekData] = &weeks;
+        if DataSufficiency::from_weeks(weeks.len()) != DataSufficiency::Full {
+            return Ok(Self::fallback_forecast(weeks));
+        }
         if !self.is_trained {
             return Err("Model not trained".to_string());
         }
 
-        if weeks.len() < 4 {
-            let avg_hours = if weeks.is_empty() {
-                0.0
-            } else {
-                weeks.iter().map(|w| w.total_hours).sum::<f64>() / weeks.len() as f64
-            };
-            return Ok(ForecastingOutput {
-                weekly_hours: avg_hours,
-                weekly_hours_by_project: std::collections::HashMap::new(),
-                monthly_hours: avg_hours * 4.0,
-                confidence: 0.3,
-                trend: "stable".to_string(),
-            });
-        }
-
-        // extract features for last week
-        let (features, _) = FeatureEngineer::extract_temporal_features(weeks)?;
-        let last_idx = features.nrows() - 1;
-        let last_week_features = features.slice(s![last_idx..last_idx + 1, ..]).to_owned();
-        let X_scaled = self.normalizer.transform(&last_week_features)?;
-
         // obtain predictions according to choice
-        // obtain first-element predictions (f64) to avoid moving large Array1 values
-        let tree_pred_opt: Option<f64> = if let Some(ref tree) = self.tree_model {
-            Some(tree.predict(&X_scaled)?[0])
-        } else {
-            None
-        };
-        let linear_pred_opt: Option<f64> = if let Some(ref linear) = self.linear_model {
-            Some(linear.predict(&X_scaled)?[0])
-        } else {
-            None
-        };
+        let ComponentPredictions {
+            tree: tree_pred_opt,
+            linear: linear_pred_opt,
+            knn: knn_pred_opt,
+            prophet: prophet_pred_opt,
+        } = self.component_predictions(weeks)?;
 
         let ensemble_pred = match choice.unwrap_or("auto") {
             "linear" => {
@@ -595,6 +1245,8 @@ impl ForecastingModel {
                     return Err("Tree model not available".to_string());
                 }
             }
+            "knn" => knn_pred_opt.ok_or_else(|| "k-NN model not available".to_string())?,
+            "prophet" => prophet_pred_opt.ok_or_else(|| "Trend model not available".to_string())?,
             _ => {
                 // default ensemble weighting: tree 0.7, linear 0.3
                 let tp = tree_pred_opt.ok_or_else(|| "Tree model not available".to_string())?;
@@ -603,6 +1255,12 @@ impl ForecastingModel {
             }
         };
 
+        debug_assert!(
+            ensemble_pred.is_finite(),
+            "non-finite forecast: {}",
+            ensemble_pred
+        );
+
         // compute confidence similarly
         let pred_std = match (tree_pred_opt, linear_pred_opt) {
             (Some(tp), Some(lp)) => (tp - lp).abs(),
@@ -611,37 +1269,232 @@ impl ForecastingModel {
         let confidence = (1.0 / (1.0 + pred_std)).min(1.0);
 
         // determine trend
-        let trend = if weeks.len() >= 2 {
-            let recent_trend =
-                weeks[weeks.len() - 1].total_hours - weeks[weeks.len() - 2].total_hours;
-            if recent_trend > 2.0 {
-                "increasing"
-            } else if recent_trend < -2.0 {
-                "decreasing"
-            } else {
-                "stable"
-            }
-        } else {
-            "stable"
-        };
+        let trend = detect_trend(weeks);
 
-        let mut weekly_hours_by_project = std::collections::HashMap::new();
-        if let Some(last_week) = weeks.last() {
-            let total_current = last_week.total_hours;
-            if total_current > 0.0 {
-                for stat in &last_week.project_stats {
-                    let ratio = stat.hours / total_current;
-                    weekly_hours_by_project.insert(stat.project_id, ensemble_pred * ratio);
-                }
-            }
-        }
+        let weekly_hours_by_project = project_shares(weeks, self.params.pooling_strength)
+            .into_iter()
+            .map(|(project_id, share)| (project_id, ensemble_pred * share))
+            .collect();
+
+        let algorithms: &[&str] = match choice.unwrap_or("auto") {
+            "linear" => &["ridge"],
+            "tree" => &["decision_tree"],
+            "knn" => &["knn"],
+            "prophet" => &["prophet"],
+            _ => &["decision_tree", "ridge"],
+        };
 
         Ok(ForecastingOutput {
             weekly_hours: ensemble_pred,
             weekly_hours_by_project,
             monthly_hours: ensemble_pred * 4.0,
             confidence,
-            trend: trend.to_string(),
+            trend: trend.direction.to_string(),
+            trend_strength: trend.strength,
+            model_info: Some(self.info_for(algorithms)),
+            billable_hours: None,
+            data_sufficiency: DataSufficiency::Full,
+            explanation: None,
+            goal_assessments: Vec::new(),
+            clamped: false,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Составляющие прогноза `predict_with_choice` для тех же недель и выбора модели;
+    /// `correction_factor` заполняет вызывающий
+    pub fn explain(
+        &self,
+        weeks: &[WeekData],
+        choice: Option<&str>,
+    ) -> Result<ForecastExplanation, String> {
+        let weeks = prepare_weeks(weeks, self.params.non_finite)?;
+        let weeks: &[WeekData] = &weeks;
+        let mut explanation = ForecastExplanation::from_history(weeks);
+        if !self.is_trained || DataSufficiency::from_weeks(weeks.len()) != DataSufficiency::Full {
+            return Ok(explanation);
+        }
+
+        let components = self.component_predictions(weeks)?;
+        let (tree_weight, linear_weight, knn_weight, prophet_weight) =
+            match choice.unwrap_or("auto") {
+                "linear" => (0.0, 1.0, 0.0, 0.0),
+                "tree" => (1.0, 0.0, 0.0, 0.0),
+                "knn" => (0.0, 0.0, 1.0, 0.0),
+                "prophet" => (0.0, 0.0, 0.0, 1.0),
+                _ => (0.7, 0.3, 0.0, 0.0),
+            };
+        explanation.tree_hours = components.tree;
+        explanation.linear_hours = components.linear;
+        explanation.knn_hours = components.knn;
+        explanation.prophet_hours = components.prophet;
+        explanation.tree_weight = tree_weight;
+        explanation.linear_weight = linear_weight;
+        explanation.knn_weight = knn_weight;
+        explanation.prophet_weight = prophet_weight;
+        Ok(explanation)
+    }
+
+    /// Прогнозы дерева, гребневой регрессии, аналогов и модели тренда
+    /// для недели, следующей за `weeks`
+    fn component_predictions(&self, weeks: &[WeekData]) -> Result<ComponentPredictions, String> {
+        // extract features for last week
+        let (features, _) = FeatureEngineer::extract_temporal_features(weeks)?;
+        let last_idx = features.nrows() - 1;
+        let last_week_features = features.slice(s![last_idx..last_idx + 1, ..]).to_owned();
+        let last_week_features = self.apply_transformers(last_week_features)?;
+        let X_scaled = self.normalizer.transform(&last_week_features)?;
+
+        // obtain first-element predictions (f64) to avoid moving large Array1 values
+        let tree_pred = match self.tree_model {
+            Some(ref tree) => Some(to_f64(tree.predict(&X_scaled)?[0])),
+            None => None,
+        };
+        let linear_pred = match self.linear_model {
+            Some(ref linear) => Some(to_f64(linear.predict(&X_scaled)?[0])),
+            None => None,
+        };
+        let knn_pred = match self.knn_model {
+            Some(ref knn) => Some(to_f64(knn.predict(&X_scaled)?[0])),
+            None => None,
+        };
+        let prophet_pred = match self.prophet_model {
+            Some(ref prophet) => Some(prophet.predict_next(weeks)?),
+            None => None,
+        };
+        Ok(ComponentPredictions {
+            tree: tree_pred,
+            linear: linear_pred,
+            knn: knn_pred,
+            prophet: prophet_pred,
+        })
+    }
+
+    /// Сведения об обученной модели; `None` до обучения
+    pub fn model_info(&self) -> Option<ModelInfo> {
+        self.is_trained
+            .then(|| self.info_for(&["decision_tree", "ridge"]))
+    }
+
+    fn info_for(&self, algorithms: &[&str]) -> ModelInfo {
+        ModelInfo {
+            model_version: format!(
+                "{}+forecasting.v{}",
+                env!("CARGO_PKG_VERSION"),
+                FORECASTING_SNAPSHOT_VERSION
+            ),
+            trained_at: self.trained_at.clone(),
+            training_samples: self.training_samples,
+            algorithms: algorithms.iter().map(|a| a.to_string()).collect(),
+            correction_factor: None,
+            size: None,
+        }
+    }
+}
+
+/// Сохраняемое состояние обученной модели вместе с конвейером предобработки
+#[derive(Serialize, Deserialize)]
+struct ForecastingSnapshot {
+    version: u32,
+    preprocessing: PreprocessingState,
+    tree_model: Option<SimpleTree>,
+    linear_model: Option<SimpleRidge>,
+    #[serde(default)]
+    knn_model: Option<KnnForecaster>,
+    #[serde(default)]
+    prophet_model: Option<ProphetModel>,
+    #[serde(default)]
+    trained_at: Option<String>,
+    #[serde(default)]
+    training_samples: usize,
+}
+
+/// `ForecastingSnapshot` без копирования моделей
+#[derive(Serialize)]
+struct ForecastingSnapshotRef<'a> {
+    version: u32,
+    preprocessing: PreprocessingState,
+    tree_model: &'a Option<SimpleTree>,
+    linear_model: &'a Option<SimpleRidge>,
+    knn_model: &'a Option<KnnForecaster>,
+    prophet_model: &'a Option<ProphetModel>,
+    trained_at: &'a Option<String>,
+    training_samples: usize,
+}
+
+impl ForecastingModel {
+    /// Состояние преобразователей, использованных при последнем обучении
+    pub fn preprocessing_state(&self) -> PreprocessingState {
+        PreprocessingState {
+            version: PREPROCESSING_VERSION,
+            layout: FeatureEngineer::temporal_layout(),
+            selector: self.feature_selector.clone(),
+            clipper: self.clipper.clone(),
+            normalizer: self.normalizer.clone(),
+        }
+    }
+
+    fn snapshot(&self) -> Result<ForecastingSnapshotRef<'_>, String> {
+        if !self.is_trained {
+            return Err("Model not trained".to_string());
+        }
+        Ok(ForecastingSnapshotRef {
+            version: FORECASTING_SNAPSHOT_VERSION,
+            preprocessing: self.preprocessing_state(),
+            tree_model: &self.tree_model,
+            linear_model: &self.linear_model,
+            knn_model: &self.knn_model,
+            prophet_model: &self.prophet_model,
+            trained_at: &self.trained_at,
+            training_samples: self.training_samples,
+        })
+    }
+
+    /// Сериализация обученной модели в JSON
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(&self.snapshot()?).map_err(|e| format!("Serialization error: {}", e))
+    }
+
+    /// Восстановление модели из JSON с проверкой совместимости
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let snapshot: ForecastingSnapshot =
+            serde_json::from_str(json).map_err(|e| format!("Deserialization error: {}", e))?;
+        Self::from_snapshot(snapshot)
+    }
+
+    /// Обученная модель в компактном двоичном формате (`compact`)
+    pub fn to_compact(&self) -> Result<Vec<u8>, String> {
+        compact::encode("forecasting", &self.snapshot()?)
+    }
+
+    /// Восстановление модели из компактного снимка
+    pub fn from_compact(data: &[u8]) -> Result<Self, String> {
+        Self::from_snapshot(compact::decode("forecasting", data)?)
+    }
+
+    fn from_snapshot(snapshot: ForecastingSnapshot) -> Result<Self, String> {
+        if snapshot.version != FORECASTING_SNAPSHOT_VERSION {
+            return Err(format!(
+                "Unsupported forecasting snapshot version: {} (expected {})",
+                snapshot.version, FORECASTING_SNAPSHOT_VERSION
+            ));
+        }
+        snapshot
+            .preprocessing
+            .check_compatible(&FeatureEngineer::temporal_layout())?;
+
+        Ok(Self {
+            params: ForecastingParams::default(),
+            tree_model: snapshot.tree_model,
+            linear_model: snapshot.linear_model,
+            knn_model: snapshot.knn_model,
+            prophet_model: snapshot.prophet_model,
+            feature_selector: snapshot.preprocessing.selector,
+            clipper: snapshot.preprocessing.clipper,
+            normalizer: snapshot.preprocessing.normalizer,
+            is_trained: true,
+            trained_at: snapshot.trained_at,
+            training_samples: snapshot.training_samples,
         })
     }
 }
@@ -651,3 +1504,595 @@ impl Default for ForecastingModel {
         Self::new()
     }
 }
+
+/// Построитель `ForecastingModel` с гиперпараметрами по умолчанию
+#[derive(Debug, Clone, Default)]
+pub struct ForecastingModelBuilder {
+    params: ForecastingParams,
+}
+
+impl ForecastingModelBuilder {
+    /// Фиксированный alpha гребневой регрессии вместо байесовской оценки
+    pub fn linear_alpha(mut self, alpha: Float) -> Self {
+        self.params.linear_alpha = Some(alpha);
+        self
+    }
+
+    pub fn tree_max_depth(mut self, depth: usize) -> Self {
+        self.params.tree_max_depth = depth;
+        self
+    }
+
+    pub fn min_samples_split(mut self, samples: usize) -> Self {
+        self.params.min_samples_split = samples;
+        self
+    }
+
+    /// `None` отключает отбор признаков
+    pub fn feature_selection(mut self, criterion: Option<SelectionCriterion>) -> Self {
+        self.params.feature_selection = criterion;
+        self
+    }
+
+    pub fn feature_min_score(mut self, min_score: Float) -> Self {
+        self.params.feature_min_score = min_score;
+        self
+    }
+
+    /// Квантили обрезки выбросов
+    pub fn clip_outliers(mut self, lower_quantile: Float, upper_quantile: Float) -> Self {
+        self.params.clip_outliers = true;
+        self.params.clip_lower_quantile = lower_quantile;
+        self.params.clip_upper_quantile = upper_quantile;
+        self
+    }
+
+    pub fn no_clipping(mut self) -> Self {
+        self.params.clip_outliers = false;
+        self
+    }
+
+    /// Сколько недель-аналогов усредняет k-NN прогноз
+    pub fn knn_neighbors(mut self, k: usize) -> Self {
+        self.params.knn_neighbors = k;
+        self
+    }
+
+    /// Число точек излома тренда модели `prophet`
+    pub fn changepoints(mut self, changepoints: usize) -> Self {
+        self.params.changepoints = changepoints;
+        self
+    }
+
+    /// Порядок ряда Фурье годовой сезонности модели `prophet`
+    pub fn fourier_order(mut self, order: usize) -> Self {
+        self.params.fourier_order = order;
+        self
+    }
+
+    /// Штраф изменения наклона в точках излома: чем больше, тем глаже тренд
+    pub fn changepoint_penalty(mut self, penalty: f64) -> Self {
+        self.params.changepoint_penalty = penalty;
+        self
+    }
+
+    /// Вес (в неделях наблюдений) типичной для пользователя доли проекта того же
+    /// возраста при прогнозе по проектам; 0 - только история самого проекта
+    pub fn pooling_strength(mut self, strength: f64) -> Self {
+        self.params.pooling_strength = strength;
+        self
+    }
+
+    /// Период полураспада веса недели при обучении (в неделях)
+    pub fn recency_half_life(mut self, half_life: Float) -> Self {
+        self.params.recency_half_life = Some(half_life);
+        self
+    }
+
+    /// Все недели истории обучаются с одинаковым весом
+    pub fn no_recency_weighting(mut self) -> Self {
+        self.params.recency_half_life = None;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.params.seed = Some(seed);
+        self
+    }
+
+    /// Замена NaN и бесконечностей в неделях (по умолчанию) или ошибка
+    pub fn non_finite(mut self, policy: NonFinitePolicy) -> Self {
+        self.params.non_finite = policy;
+        self
+    }
+
+    pub fn build(self) -> Result<ForecastingModel, String> {
+        let params = self.params;
+        if let Some(alpha) = params.linear_alpha {
+            if alpha.is_nan() || alpha < 0.0 {
+                return Err(format!("Invalid linear_alpha: {}", alpha));
+            }
+        }
+        if params.tree_max_depth == 0 {
+            return Err("tree_max_depth must be positive".to_string());
+        }
+        if params.knn_neighbors == 0 {
+            return Err("knn_neighbors must be positive".to_string());
+        }
+        if let Some(half_life) = params.recency_half_life {
+            if !(half_life > 0.0 && half_life.is_finite()) {
+                return Err(format!("Invalid recency_half_life: {}", half_life));
+            }
+        }
+        if !(params.pooling_strength >= 0.0 && params.pooling_strength.is_finite()) {
+            return Err(format!(
+                "Invalid pooling_strength: {}",
+                params.pooling_strength
+            ));
+        }
+        if !(params.changepoint_penalty > 0.0 && params.changepoint_penalty.is_finite()) {
+            return Err(format!(
+                "Invalid changepoint_penalty: {}",
+                params.changepoint_penalty
+            ));
+        }
+        if params.clip_outliers {
+            // Проверка квантилей
+            Winsorizer::new(params.clip_lower_quantile, params.clip_upper_quantile)?;
+        }
+        Ok(ForecastingModel::with_params(params))
+    }
+}
+
+/// Сколько последних недель проекта определяют его собственную долю
+const PROJECT_SHARE_WEEKS: usize = 8;
+
+/// Доли проектов последней недели в прогнозе следующей (в сумме 1).
+///
+/// Частичное объединение: собственная средняя доля проекта за последние
+/// `PROJECT_SHARE_WEEKS` недель сжимается к типичной доле других проектов
+/// пользователя того же возраста (недель с первой записи) с весом `strength`
+/// недель. Новый проект с одной-двумя неделями истории получает типичный для
+/// пользователя разгон, проект с долгой историей - почти собственную долю
+pub fn project_shares(weeks: &[WeekData], strength: f64) -> HashMap<i32, f64> {
+    let Some(last_week) = weeks.last().filter(|w| w.total_hours > 0.0) else {
+        return HashMap::new();
+    };
+
+    // Доли проектов по неделям с первой активной недели проекта
+    let mut first_week: HashMap<i32, usize> = HashMap::new();
+    for (index, week) in weeks.iter().enumerate() {
+        for stat in week.project_stats.iter().filter(|s| s.hours > 0.0) {
+            first_week.entry(stat.project_id).or_insert(index);
+        }
+    }
+    let share = |week: &WeekData, project_id: i32| -> f64 {
+        if week.total_hours <= 0.0 {
+            return 0.0;
+        }
+        week.project_stats
+            .iter()
+            .filter(|s| s.project_id == project_id)
+            .map(|s| s.hours)
+            .sum::<f64>()
+            / week.total_hours
+    };
+    let history: HashMap<i32, Vec<f64>> = first_week
+        .iter()
+        .map(|(&project_id, &first)| {
+            let shares = weeks[first..]
+                .iter()
+                .map(|w| share(w, project_id))
+                .collect();
+            (project_id, shares)
+        })
+        .collect();
+
+    let active: Vec<i32> = last_week
+        .project_stats
+        .iter()
+        .filter(|s| s.hours > 0.0)
+        .map(|s| s.project_id)
+        .collect();
+    let mut shares: HashMap<i32, f64> = HashMap::new();
+    for &project_id in &active {
+        let own = &history[&project_id];
+        let recent = &own[own.len().saturating_sub(PROJECT_SHARE_WEEKS)..];
+        let own_mean = recent.iter().sum::<f64>() / recent.len() as f64;
+        let age = own.len() - 1;
+
+        // Типичная доля других проектов в том же возрасте; без них - равная доля
+        let peers: Vec<f64> = history
+            .iter()
+            .filter(|(&other, shares)| other != project_id && shares.len() > age)
+            .map(|(_, shares)| shares[age])
+            .collect();
+        let prior = if peers.is_empty() {
+            1.0 / active.len() as f64
+        } else {
+            peers.iter().sum::<f64>() / peers.len() as f64
+        };
+
+        let n = recent.len() as f64;
+        shares.insert(
+            project_id,
+            (n * own_mean + strength * prior) / (n + strength),
+        );
+    }
+
+    let total: f64 = shares.values().sum();
+    if total > 0.0 {
+        for value in shares.values_mut() {
+            *value /= total;
+        }
+    }
+    shares
+}
+
+/// Сколько последних недель проекта определяют вероятность достижения цели
+const GOAL_HISTORY_WEEKS: usize = 8;
+
+/// Вероятность достижения, ниже которой цель считается нереалистичной
+pub const GOAL_REALISTIC_PROBABILITY: f64 = 0.2;
+
+/// Смешивает прогноз по проектам с целями пользователя.
+///
+/// Часы проекта за последние `GOAL_HISTORY_WEEKS` недель (включая недели без
+/// работы) дают нормальное приближение недельных часов; вероятность недели не
+/// ниже цели - вес цели в прогнозе, остальное - прогноз по истории
+/// (`by_project`, без него - среднее). Проекты без целей не меняются
+pub fn blend_with_goals(
+    weeks: &[WeekData],
+    by_project: &mut HashMap<i32, f64>,
+    goals: &HashMap<i32, f64>,
+) -> Vec<GoalAssessment> {
+    let recent = &weeks[weeks.len().saturating_sub(GOAL_HISTORY_WEEKS)..];
+    let mut assessments: Vec<GoalAssessment> = goals
+        .iter()
+        .filter(|(_, &goal)| goal > 0.0 && goal.is_finite())
+        .map(|(&project_id, &goal_hours)| {
+            let hours: Vec<f64> = recent
+                .iter()
+                .map(|w| {
+                    w.project_stats
+                        .iter()
+                        .filter(|s| s.project_id == project_id)
+                        .map(|s| s.hours)
+                        .sum()
+                })
+                .collect();
+            let n = hours.len().max(1) as f64;
+            let mean = hours.iter().sum::<f64>() / n;
+            let variance = hours.iter().map(|h| (h - mean).powi(2)).sum::<f64>() / n;
+            // Нижняя граница разброса: несколько одинаковых недель не дают уверенности
+            let std = variance.sqrt().max(0.1 * mean.max(goal_hours)).max(0.5);
+
+            let attainment_probability = if hours.is_empty() {
+                0.0
+            } else {
+                1.0 - normal_cdf((goal_hours - mean) / std)
+            };
+            let historical_hours = by_project.get(&project_id).copied().unwrap_or(mean);
+            let blended_hours = attainment_probability * goal_hours
+                + (1.0 - attainment_probability) * historical_hours;
+            by_project.insert(project_id, blended_hours);
+
+            GoalAssessment {
+                project_id,
+                goal_hours,
+                historical_hours,
+                attainment_probability,
+                blended_hours,
+                realistic: attainment_probability >= GOAL_REALISTIC_PROBABILITY,
+            }
+        })
+        .collect();
+    assessments.sort_by_key(|a| a.project_id);
+    assessments
+}
+
+/// Часов в неделе: верхняя граница прогноза по умолчанию
+pub const HOURS_PER_WEEK: f64 = 168.0;
+
+/// Физические границы прогноза недельных часов (`options.min_weekly_hours`,
+/// `options.max_weekly_hours`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForecastBounds {
+    pub min_weekly_hours: f64,
+    pub max_weekly_hours: f64,
+}
+
+impl Default for ForecastBounds {
+    fn default() -> Self {
+        Self {
+            min_weekly_hours: 0.0,
+            max_weekly_hours: HOURS_PER_WEEK,
+        }
+    }
+}
+
+impl ForecastBounds {
+    /// Границы из `options` запроса; незаданные - по умолчанию
+    pub fn from_options(options: Option<&JsonValue>) -> Result<Self, String> {
+        let bound = |name: &str, default: f64| {
+            options
+                .and_then(|o| o.get(name))
+                .and_then(|v| v.as_f64())
+                .unwrap_or(default)
+        };
+        let defaults = Self::default();
+        let bounds = Self {
+            min_weekly_hours: bound("min_weekly_hours", defaults.min_weekly_hours),
+            max_weekly_hours: bound("max_weekly_hours", defaults.max_weekly_hours),
+        };
+        if !(bounds.min_weekly_hours >= 0.0
+            && bounds.min_weekly_hours <= bounds.max_weekly_hours
+            && bounds.max_weekly_hours <= HOURS_PER_WEEK)
+        {
+            return Err(format!(
+                "Invalid forecast bounds: {}..={} hours per week (allowed 0..={})",
+                bounds.min_weekly_hours, bounds.max_weekly_hours, HOURS_PER_WEEK
+            ));
+        }
+        Ok(bounds)
+    }
+
+    /// Прижимает прогноз к границам: недельные часы (и месячные в той же
+    /// пропорции), часы проектов - к 0 (и NaN, и бесконечные) и в сумме не больше
+    /// недели, оплачиваемые -
+    /// не больше недели. `ForecastingOutput::clamped` отмечает, что прогноз менялся
+    pub fn apply(&self, forecast: &mut ForecastingOutput) -> bool {
+        let mut clamped = false;
+        let weekly = if forecast.weekly_hours.is_nan() {
+            self.min_weekly_hours
+        } else {
+            forecast
+                .weekly_hours
+                .clamp(self.min_weekly_hours, self.max_weekly_hours)
+        };
+        if forecast.weekly_hours.is_nan() || weekly != forecast.weekly_hours {
+            // Месячный прогноз - в той же пропорции к недельному; из NaN - по неделе
+            forecast.monthly_hours = if forecast.weekly_hours.is_finite()
+                && forecast.weekly_hours != 0.0
+                && forecast.monthly_hours.is_finite()
+            {
+                forecast.monthly_hours * weekly / forecast.weekly_hours
+            } else {
+                weekly * 4.0
+            };
+            forecast.weekly_hours = weekly;
+            clamped = true;
+        }
+
+        // Бесконечные часы проекта обнулили бы остальные при масштабировании
+        for hours in forecast.weekly_hours_by_project.values_mut() {
+            if !hours.is_finite() || *hours < 0.0 {
+                *hours = 0.0;
+                clamped = true;
+            }
+        }
+        let projects_total: f64 = forecast.weekly_hours_by_project.values().sum();
+        if projects_total > weekly {
+            let scale = weekly / projects_total;
+            for hours in forecast.weekly_hours_by_project.values_mut() {
+                *hours *= scale;
+            }
+            clamped = true;
+        }
+
+        if let Some(billable) = forecast.billable_hours.as_mut() {
+            let bounded = if billable.is_nan() {
+                0.0
+            } else {
+                billable.clamp(0.0, weekly)
+            };
+            if bounded != *billable {
+                *billable = bounded;
+                clamped = true;
+            }
+        }
+
+        forecast.clamped |= clamped;
+        clamped
+    }
+}
+
+/// Сколько последних недель участвует в оценке тренда
+pub const TREND_WEEKS: usize = 8;
+
+/// Уровень значимости теста Манна-Кендалла для тренда
+const TREND_SIGNIFICANCE: f64 = 0.05;
+
+/// Тренд недельных часов
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendEstimate {
+    /// "increasing" | "decreasing" | "stable"
+    pub direction: &'static str,
+    /// Наклон Тейла-Сена, часов в неделю
+    pub slope: f64,
+    /// |тау Кендалла| от 0 (нет монотонности) до 1 (строго монотонный ряд)
+    pub strength: f64,
+    /// Двусторонний p-value теста Манна-Кендалла
+    pub p_value: f64,
+}
+
+/// Тренд по последним `TREND_WEEKS` неделям: наклон Тейла-Сена (медиана наклонов
+/// между всеми парами недель) устойчив к отдельным выбросам; направление
+/// задается, только если тест Манна-Кендалла значим на уровне 5%
+pub fn detect_trend(weeks: &[WeekData]) -> TrendEstimate {
+    let hours: Vec<f64> = weeks[weeks.len().saturating_sub(TREND_WEEKS)..]
+        .iter()
+        .map(|w| w.total_hours)
+        .collect();
+    series_trend(&hours)
+}
+
+/// Тренд ряда по неделям (наклон в единицах ряда за неделю) тем же тестом,
+/// что и `detect_trend`; нечисловые значения пропускаются
+pub fn series_trend(values: &[f64]) -> TrendEstimate {
+    let hours: Vec<f64> = values.iter().copied().filter(|h| h.is_finite()).collect();
+    let n = hours.len();
+    let mut trend = TrendEstimate {
+        direction: "stable",
+        slope: 0.0,
+        strength: 0.0,
+        p_value: 1.0,
+    };
+    if n < 3 {
+        return trend;
+    }
+
+    let mut slopes = Vec::with_capacity(n * (n - 1) / 2);
+    let mut s = 0i64;
+    for i in 0..n {
+        for j in i + 1..n {
+            let diff = hours[j] - hours[i];
+            slopes.push(diff / (j - i) as f64);
+            s += diff.partial_cmp(&0.0).map_or(0, |o| o as i64);
+        }
+    }
+    slopes.sort_by(|a, b| a.total_cmp(b));
+    let mid = slopes.len() / 2;
+    trend.slope = if slopes.len().is_multiple_of(2) {
+        (slopes[mid - 1] + slopes[mid]) / 2.0
+    } else {
+        slopes[mid]
+    };
+    trend.strength = (s as f64 / slopes.len() as f64).abs();
+
+    // Дисперсия статистики S с поправкой на совпадающие значения
+    let mut sorted = hours.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let tie_term: f64 = sorted
+        .chunk_by(|a, b| a == b)
+        .map(|tie| {
+            let t = tie.len() as f64;
+            t * (t - 1.0) * (2.0 * t + 5.0)
+        })
+        .sum();
+    let n = n as f64;
+    let variance = (n * (n - 1.0) * (2.0 * n + 5.0) - tie_term) / 18.0;
+    if variance <= 0.0 {
+        return trend;
+    }
+    // Поправка на непрерывность
+    let z = (s.abs() - 1).max(0) as f64 / variance.sqrt();
+    trend.p_value = (2.0 * (1.0 - normal_cdf(z))).clamp(0.0, 1.0);
+
+    if trend.p_value < TREND_SIGNIFICANCE && trend.slope != 0.0 {
+        trend.direction = if trend.slope > 0.0 {
+            "increasing"
+        } else {
+            "decreasing"
+        };
+    }
+    trend
+}
+
+/// Функция распределения стандартного нормального закона
+/// (приближение erf Абрамовица-Стиган 7.1.26, погрешность до 1.5e-7)
+pub(crate) fn normal_cdf(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+    if z >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forecast(weekly_hours: f64, projects: &[(i32, f64)]) -> ForecastingOutput {
+        ForecastingOutput {
+            weekly_hours,
+            weekly_hours_by_project: projects.iter().copied().collect(),
+            monthly_hours: weekly_hours * 4.0,
+            ..ForecastingModel::fallback_forecast(&[])
+        }
+    }
+
+    #[test]
+    fn forecast_within_bounds_is_not_clamped() {
+        let mut output = forecast(40.0, &[(1, 30.0), (2, 10.0)]);
+        output.billable_hours = Some(35.0);
+        assert!(!ForecastBounds::default().apply(&mut output));
+        assert!(!output.clamped);
+        assert_eq!(output.weekly_hours, 40.0);
+        assert_eq!(output.monthly_hours, 160.0);
+        assert_eq!(output.weekly_hours_by_project[&1], 30.0);
+        assert_eq!(output.billable_hours, Some(35.0));
+    }
+
+    #[test]
+    fn weekly_hours_are_clamped_to_the_week() {
+        let bounds = ForecastBounds::default();
+        for (weekly, expected) in [
+            (-5.0, 0.0),
+            (200.0, HOURS_PER_WEEK),
+            (f64::NAN, 0.0),
+            (f64::INFINITY, HOURS_PER_WEEK),
+        ] {
+            let mut output = forecast(weekly, &[]);
+            assert!(bounds.apply(&mut output), "{}", weekly);
+            assert!(output.clamped);
+            assert_eq!(output.weekly_hours, expected, "{}", weekly);
+            assert_eq!(output.monthly_hours, expected * 4.0, "{}", weekly);
+        }
+
+        let bounds = ForecastBounds::from_options(Some(&serde_json::json!({
+            "min_weekly_hours": 10.0,
+            "max_weekly_hours": 50.0,
+        })))
+        .unwrap();
+        let mut output = forecast(5.0, &[]);
+        assert!(bounds.apply(&mut output));
+        assert_eq!((output.weekly_hours, output.monthly_hours), (10.0, 40.0));
+        assert!(ForecastBounds::from_options(Some(&serde_json::json!({
+            "max_weekly_hours": 200.0,
+        })))
+        .is_err());
+    }
+
+    #[test]
+    fn project_hours_fit_into_the_weekly_total() {
+        let bounds = ForecastBounds::default();
+        let mut output = forecast(40.0, &[(1, 30.0), (2, 50.0)]);
+        assert!(bounds.apply(&mut output));
+        assert_eq!(output.weekly_hours, 40.0);
+        assert!((output.weekly_hours_by_project[&1] - 15.0).abs() < 1e-9);
+        assert!((output.weekly_hours_by_project[&2] - 25.0).abs() < 1e-9);
+
+        // Отрицательные, NaN и бесконечные часы проектов обнуляются, не
+        // затрагивая остальные
+        let mut output = forecast(
+            40.0,
+            &[(1, 20.0), (2, -3.0), (3, f64::NAN), (4, f64::INFINITY)],
+        );
+        assert!(bounds.apply(&mut output));
+        assert!(output.clamped);
+        let hours = &output.weekly_hours_by_project;
+        assert_eq!(
+            (hours[&1], hours[&2], hours[&3], hours[&4]),
+            (20.0, 0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn billable_hours_stay_within_the_week() {
+        let bounds = ForecastBounds::default();
+        for (billable, expected) in [(60.0, 40.0), (-1.0, 0.0), (f64::NAN, 0.0)] {
+            let mut output = forecast(40.0, &[]);
+            output.billable_hours = Some(billable);
+            assert!(bounds.apply(&mut output), "{}", billable);
+            assert_eq!(output.billable_hours, Some(expected), "{}", billable);
+        }
+    }
+}
@@ -3,6 +3,48 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::types::AnomalyFeedback;
+
+/// Наибольшее отклонение корректирующего фактора от 1
+pub const MAX_CORRECTION: f64 = 0.2;
+
+/// Доля крайних ошибок, отбрасываемая с каждой стороны при оценке разброса
+const TRIM_SHARE: f64 = 0.1;
+
+/// Прогноз считается точным, если ошибка не больше этой доли факта
+pub const ACCURACY_TOLERANCE: f64 = 0.1;
+
+/// Число интервалов уверенности в диаграмме надежности
+const CALIBRATION_BUCKETS: usize = 10;
+
+/// Интервалы с меньшим числом прогнозов не участвуют в перекалибровке
+const MIN_CALIBRATION_SAMPLES: usize = 5;
+
+/// Горизонт прогноза по умолчанию: на неделю вперед
+pub const DEFAULT_HORIZON: u32 = 1;
+
+/// Наибольший сдвиг порога детектора аномалий по отзывам
+pub const MAX_THRESHOLD_SHIFT: f64 = 0.2;
+
+/// Порог типа аномалии сдвигается, только если по нему есть столько отзывов
+const MIN_FEEDBACK: usize = 5;
+
+/// Тип аномалии для отзывов, в которых он не указан
+pub const UNTYPED_ANOMALY: &str = "unknown";
+
+/// Модель важности обучается, только если столько отзывов о найденных аномалиях
+/// содержат `score` и среди них есть и подтвержденные, и отклоненные
+pub const MIN_SEVERITY_FEEDBACK: usize = 10;
+
+/// Итерации градиентного спуска при обучении логистической модели важности
+const SEVERITY_ITERATIONS: usize = 2000;
+
+/// Шаг градиентного спуска модели важности
+const SEVERITY_LEARNING_RATE: f64 = 1.0;
+
+/// L2-регуляризация логистической модели важности
+const SEVERITY_L2: f64 = 0.1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PredictionError {
     pub prediction_type: String,
@@ -10,21 +52,244 @@ pub struct PredictionError {
     pub actual_value: f64,
     pub error: f64,
     pub context: serde_json::Value,
+    /// На сколько недель вперед был прогноз; поправки считаются отдельно для каждого горизонта
+    #[serde(default = "default_horizon")]
+    pub horizon: u32,
+    /// Уверенность, с которой был выдан прогноз (0-1)
+    #[serde(default)]
+    pub confidence: Option<f64>,
+    /// Время записи (RFC 3339) для сроков хранения
+    #[serde(default)]
+    pub recorded_at: Option<String>,
+    /// Арендатор, заполняется сервером по `X-Tenant-Id`
+    #[serde(default)]
+    pub tenant: String,
+}
+
+impl PredictionError {
+    /// Ошибка не больше `ACCURACY_TOLERANCE` от факта
+    pub fn is_accurate(&self) -> bool {
+        if self.actual_value.abs() > f64::EPSILON {
+            (self.error / self.actual_value).abs() <= ACCURACY_TOLERANCE
+        } else {
+            self.error.abs() <= f64::EPSILON
+        }
+    }
+}
+
+/// Интервал диаграммы надежности: заявленная уверенность и фактическая точность
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalibrationBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: usize,
+    pub mean_confidence: f64,
+    /// Доля точных прогнозов (`PredictionError::is_accurate`)
+    pub accuracy: f64,
+}
+
+fn default_horizon() -> u32 {
+    DEFAULT_HORIZON
+}
+
+/// Исход классификации записи: решение детектора против вердикта пользователя
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalyVerdict {
+    pub anomaly_type: String,
+    /// Детектор отметил запись как аномалию
+    pub predicted: bool,
+    /// Пользователь считает запись аномалией
+    pub actual: bool,
+    /// `score` найденной аномалии, если клиент его передал
+    #[serde(default)]
+    pub score: Option<f64>,
 }
 
+impl From<&AnomalyFeedback> for AnomalyVerdict {
+    fn from(feedback: &AnomalyFeedback) -> Self {
+        Self {
+            anomaly_type: feedback
+                .anomaly_type
+                .clone()
+                .unwrap_or_else(|| UNTYPED_ANOMALY.to_string()),
+            predicted: feedback.detected,
+            actual: feedback.is_anomaly,
+            score: feedback.score,
+        }
+    }
+}
+
+/// Логистическая модель вероятности того, что пользователь подтвердит найденную
+/// аномалию: признаки - `score` детектора и тип аномалии (one-hot)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeverityModel {
+    /// Типы с собственным коэффициентом; остальные используют только `score`
+    pub anomaly_types: Vec<String>,
+    pub intercept: f64,
+    pub score_weight: f64,
+    pub type_weights: Vec<f64>,
+    pub samples: usize,
+}
+
+impl SeverityModel {
+    /// Обучение регуляризованной логистической регрессии градиентным спуском
+    fn fit(samples: &[(&str, f64, bool)]) -> Option<Self> {
+        let confirmed = samples.iter().filter(|(_, _, actual)| *actual).count();
+        if samples.len() < MIN_SEVERITY_FEEDBACK || confirmed == 0 || confirmed == samples.len() {
+            return None;
+        }
+
+        let mut anomaly_types: Vec<String> =
+            samples.iter().map(|(t, _, _)| t.to_string()).collect();
+        anomaly_types.sort();
+        anomaly_types.dedup();
+        let mut model = Self {
+            type_weights: vec![0.0; anomaly_types.len()],
+            anomaly_types,
+            intercept: 0.0,
+            score_weight: 0.0,
+            samples: samples.len(),
+        };
+
+        let dim = 2 + model.anomaly_types.len();
+        let features: Vec<Vec<f64>> = samples
+            .iter()
+            .map(|(anomaly_type, score, _)| model.features(anomaly_type, *score))
+            .collect();
+        let n = samples.len() as f64;
+        let mut weights = vec![0.0; dim];
+        for _ in 0..SEVERITY_ITERATIONS {
+            let mut gradient = vec![0.0; dim];
+            for (x, (_, _, actual)) in features.iter().zip(samples) {
+                let z: f64 = x.iter().zip(&weights).map(|(a, b)| a * b).sum();
+                let residual = sigmoid(z) - if *actual { 1.0 } else { 0.0 };
+                for (g, xi) in gradient.iter_mut().zip(x) {
+                    *g += residual * xi / n;
+                }
+            }
+            // Свободный член не штрафуется
+            for (g, w) in gradient.iter_mut().zip(&weights).skip(1) {
+                *g += SEVERITY_L2 * w / n;
+            }
+
+            let mut change: f64 = 0.0;
+            for (w, g) in weights.iter_mut().zip(&gradient) {
+                *w -= SEVERITY_LEARNING_RATE * g;
+                change = change.max(g.abs());
+            }
+            if change < 1e-6 {
+                break;
+            }
+        }
+
+        model.intercept = weights[0];
+        model.score_weight = weights[1];
+        model.type_weights = weights[2..].to_vec();
+        Some(model)
+    }
+
+    fn features(&self, anomaly_type: &str, score: f64) -> Vec<f64> {
+        let mut x = vec![1.0, score];
+        x.extend(
+            self.anomaly_types
+                .iter()
+                .map(|t| if t == anomaly_type { 1.0 } else { 0.0 }),
+        );
+        x
+    }
+
+    /// Вероятность того, что пользователь подтвердит аномалию
+    pub fn confirmation_probability(&self, anomaly_type: &str, score: f64) -> f64 {
+        let type_weight = self
+            .anomaly_types
+            .iter()
+            .position(|t| t == anomaly_type)
+            .map_or(0.0, |i| self.type_weights[i]);
+        sigmoid(self.intercept + self.score_weight * score + type_weight)
+    }
+
+    /// Важность по вероятности подтверждения
+    pub fn severity(&self, anomaly_type: &str, score: f64) -> &'static str {
+        match self.confirmation_probability(anomaly_type, score) {
+            p if p >= 0.8 => "high",
+            p if p >= 0.5 => "medium",
+            _ => "low",
+        }
+    }
+}
+
+fn sigmoid(z: f64) -> f64 {
+    1.0 / (1.0 + (-z).exp())
+}
+
+/// Точность и полнота детектора по одному типу аномалий
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassificationStats {
+    pub anomaly_type: String,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub true_negatives: usize,
+    /// `None`, если отзывов о найденных аномалиях нет
+    pub precision: Option<f64>,
+    /// `None`, если пользователи не подтвердили ни одной аномалии
+    pub recall: Option<f64>,
+    /// Сдвиг порога детектора для этого типа (`LearningModule::threshold_shift`)
+    pub threshold_shift: f64,
+}
+
+#[derive(Clone)]
 pub struct LearningModule {
     errors: Vec<PredictionError>,
+    verdicts: Vec<AnomalyVerdict>,
     max_errors: usize,
+    retrain: RetrainPolicy,
+}
+
+/// Когда ошибки прогнозов требуют переобучения модели
+#[derive(Debug, Clone)]
+struct RetrainPolicy {
+    /// Порог скользящей MAPE (доля)
+    mape_threshold: f64,
+    /// Сколько ошибок подряд скользящая MAPE должна быть выше порога
+    consecutive: usize,
+    /// Размер окна скользящей MAPE
+    window: usize,
+}
+
+impl Default for RetrainPolicy {
+    fn default() -> Self {
+        Self {
+            mape_threshold: 0.25,
+            consecutive: 3,
+            window: 20,
+        }
+    }
+}
+
+/// Сигнал переобучения: скользящая MAPE выше порога `consecutive` ошибок подряд
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetrainSignal {
+    pub prediction_type: String,
+    pub horizon: u32,
+    pub rolling_mape: f64,
+    pub consecutive: usize,
 }
 
 impl LearningModule {
     pub fn new(max_errors: usize) -> Self {
         Self {
             errors: Vec::new(),
+            verdicts: Vec::new(),
             max_errors,
+            retrain: RetrainPolicy::default(),
         }
     }
 
+    pub fn builder() -> LearningModuleBuilder {
+        LearningModuleBuilder::default()
+    }
+
     pub fn record_error(&mut self, error: PredictionError) {
         self.errors.push(error);
         if self.errors.len() > self.max_errors {
@@ -32,51 +297,160 @@ impl LearningModule {
         }
     }
 
-    pub fn get_correction_factor(&self, prediction_type: &str) -> f64 {
-        let relevant_errors: Vec<&PredictionError> = self
-            .errors
+    /// Заменяет историю ошибок (например, загруженной из общего хранилища);
+    /// сохраняются последние `max_errors`
+    pub fn load_errors(&mut self, mut errors: Vec<PredictionError>) {
+        if errors.len() > self.max_errors {
+            errors.drain(..errors.len() - self.max_errors);
+        }
+        self.errors = errors;
+    }
+
+    pub fn max_errors(&self) -> usize {
+        self.max_errors
+    }
+
+    /// Записывает отзыв об аномалии; хранятся последние `max_errors` отзывов
+    pub fn record_verdict(&mut self, verdict: AnomalyVerdict) {
+        self.verdicts.push(verdict);
+        if self.verdicts.len() > self.max_errors {
+            self.verdicts.remove(0);
+        }
+    }
+
+    /// Заменяет историю отзывов об аномалиях; сохраняются последние `max_errors`
+    pub fn load_verdicts(&mut self, mut verdicts: Vec<AnomalyVerdict>) {
+        if verdicts.len() > self.max_errors {
+            verdicts.drain(..verdicts.len() - self.max_errors);
+        }
+        self.verdicts = verdicts;
+    }
+
+    /// Типы аномалий, по которым есть отзывы
+    pub fn anomaly_types(&self) -> Vec<String> {
+        let mut types: Vec<String> = self
+            .verdicts
             .iter()
-            .filter(|e| e.prediction_type == prediction_type)
+            .map(|v| v.anomaly_type.clone())
             .collect();
+        types.sort();
+        types.dedup();
+        types
+    }
 
-        if relevant_errors.is_empty() {
-            return 1.0;
+    pub fn classification_stats(&self, anomaly_type: &str) -> ClassificationStats {
+        let mut stats = ClassificationStats {
+            anomaly_type: anomaly_type.to_string(),
+            true_positives: 0,
+            false_positives: 0,
+            false_negatives: 0,
+            true_negatives: 0,
+            precision: None,
+            recall: None,
+            threshold_shift: 0.0,
+        };
+        for verdict in self
+            .verdicts
+            .iter()
+            .filter(|v| v.anomaly_type == anomaly_type)
+        {
+            match (verdict.predicted, verdict.actual) {
+                (true, true) => stats.true_positives += 1,
+                (true, false) => stats.false_positives += 1,
+                (false, true) => stats.false_negatives += 1,
+                (false, false) => stats.true_negatives += 1,
+            }
         }
 
-        // Вычисляем среднюю ошибку
-        let avg_error: f64 = relevant_errors.iter().map(|e| e.error.abs()).sum::<f64>()
-            / relevant_errors.len() as f64;
+        let predicted = stats.true_positives + stats.false_positives;
+        let actual = stats.true_positives + stats.false_negatives;
+        stats.precision = (predicted > 0).then(|| stats.true_positives as f64 / predicted as f64);
+        stats.recall = (actual > 0).then(|| stats.true_positives as f64 / actual as f64);
+
+        // Ложные срабатывания поднимают порог, пропущенные аномалии - опускают
+        let total = predicted + stats.false_negatives + stats.true_negatives;
+        if total >= MIN_FEEDBACK {
+            let shift = (stats.recall.unwrap_or(1.0) - stats.precision.unwrap_or(1.0))
+                * MAX_THRESHOLD_SHIFT;
+            stats.threshold_shift = shift.clamp(-MAX_THRESHOLD_SHIFT, MAX_THRESHOLD_SHIFT);
+        }
+        stats
+    }
+
+    /// Сдвиг порога аномальности для типа `anomaly_type`: положительный, если
+    /// пользователи чаще отклоняют найденные аномалии, чем сообщают о пропущенных
+    pub fn threshold_shift(&self, anomaly_type: &str) -> f64 {
+        self.classification_stats(anomaly_type).threshold_shift
+    }
 
-        // Вычисляем средний процент ошибки
-        let avg_percent_error: f64 = relevant_errors
+    /// Ненулевые сдвиги порогов по типам аномалий для `AnomalyDetector::detect_with_thresholds`
+    pub fn threshold_shifts(&self) -> HashMap<String, f64> {
+        self.anomaly_types()
+            .into_iter()
+            .filter(|t| t != UNTYPED_ANOMALY)
+            .map(|t| {
+                let shift = self.threshold_shift(&t);
+                (t, shift)
+            })
+            .filter(|(_, shift)| *shift != 0.0)
+            .collect()
+    }
+
+    /// Модель важности по отзывам о найденных аномалиях с `score`;
+    /// `None`, пока отзывов меньше `MIN_SEVERITY_FEEDBACK` или все они одинаковы
+    pub fn severity_model(&self) -> Option<SeverityModel> {
+        let samples: Vec<(&str, f64, bool)> = self
+            .verdicts
             .iter()
-            .filter(|e| e.actual_value != 0.0)
-            .map(|e| (e.error / e.actual_value).abs())
-            .sum::<f64>()
-            / relevant_errors
-                .iter()
-                .filter(|e| e.actual_value != 0.0)
-                .count() as f64;
+            .filter(|v| v.predicted)
+            .filter_map(|v| Some((v.anomaly_type.as_str(), v.score?, v.actual)))
+            .collect();
+        SeverityModel::fit(&samples)
+    }
+
+    /// Корректирующий фактор для прогнозов на `DEFAULT_HORIZON`
+    pub fn get_correction_factor(&self, prediction_type: &str) -> f64 {
+        self.get_correction_factor_for_horizon(prediction_type, DEFAULT_HORIZON)
+    }
+
+    pub fn get_correction_factor_for_horizon(&self, prediction_type: &str, horizon: u32) -> f64 {
+        // Относительные ошибки (>0 - прогноз завышен); нулевые факты не дают процента
+        let mut percent_errors: Vec<f64> = self
+            .relevant_errors(prediction_type, horizon)
+            .iter()
+            .filter(|e| e.actual_value.abs() > f64::EPSILON)
+            .map(|e| e.error / e.actual_value)
+            .filter(|pe| pe.is_finite())
+            .collect();
+
+        let Some(bias) = median(&mut percent_errors) else {
+            return 1.0;
+        };
 
-        // Корректирующий фактор: если предсказания завышены, уменьшаем, если занижены - увеличиваем
-        let bias: f64 =
-            relevant_errors.iter().map(|e| e.error).sum::<f64>() / relevant_errors.len() as f64;
+        // Типичная величина ошибки без крайних значений
+        let mut abs_errors: Vec<f64> = percent_errors.iter().map(|pe| pe.abs()).collect();
+        let typical_error = trimmed_mean(&mut abs_errors, TRIM_SHARE);
 
-        // Если есть систематическая ошибка (bias), корректируем
-        if bias.abs() > avg_error * 0.1 {
-            // Корректируем на основе bias
-            1.0 - (bias / avg_error).signum() * avg_percent_error.min(0.2)
+        // Корректируем только систематическую ошибку: медиана заметна на фоне разброса.
+        // Если предсказания завышены, уменьшаем, если занижены - увеличиваем
+        if bias.abs() > typical_error * 0.1 {
+            (1.0 - bias).clamp(1.0 - MAX_CORRECTION, 1.0 + MAX_CORRECTION)
         } else {
             1.0
         }
     }
 
+    /// Поправка уверенности для прогнозов на `DEFAULT_HORIZON`
     pub fn get_confidence_adjustment(&self, prediction_type: &str) -> f64 {
-        let relevant_errors: Vec<&PredictionError> = self
-            .errors
-            .iter()
-            .filter(|e| e.prediction_type == prediction_type)
-            .collect();
+        self.get_confidence_adjustment_for_horizon(prediction_type, DEFAULT_HORIZON)
+    }
+
+    pub fn get_confidence_adjustment_for_horizon(
+        &self,
+        prediction_type: &str,
+        horizon: u32,
+    ) -> f64 {
+        let relevant_errors = self.relevant_errors(prediction_type, horizon);
 
         if relevant_errors.is_empty() {
             return 1.0;
@@ -108,12 +482,137 @@ impl LearningModule {
         }
     }
 
+    /// Диаграмма надежности по ошибкам с записанной уверенностью; только непустые интервалы
+    pub fn calibration_curve(&self, prediction_type: &str, horizon: u32) -> Vec<CalibrationBucket> {
+        let mut buckets: Vec<(usize, f64, usize)> = vec![(0, 0.0, 0); CALIBRATION_BUCKETS];
+        for error in self.relevant_errors(prediction_type, horizon) {
+            let Some(confidence) = error.confidence.filter(|c| c.is_finite()) else {
+                continue;
+            };
+            let confidence = confidence.clamp(0.0, 1.0);
+            let index =
+                ((confidence * CALIBRATION_BUCKETS as f64) as usize).min(CALIBRATION_BUCKETS - 1);
+            let bucket = &mut buckets[index];
+            bucket.0 += 1;
+            bucket.1 += confidence;
+            bucket.2 += error.is_accurate() as usize;
+        }
+
+        buckets
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (count, _, _))| *count > 0)
+            .map(
+                |(index, (count, confidence_sum, accurate))| CalibrationBucket {
+                    lower: index as f64 / CALIBRATION_BUCKETS as f64,
+                    upper: (index + 1) as f64 / CALIBRATION_BUCKETS as f64,
+                    count,
+                    mean_confidence: confidence_sum / count as f64,
+                    accuracy: accurate as f64 / count as f64,
+                },
+            )
+            .collect()
+    }
+
+    /// Перекалибровка уверенности: линейная интерполяция по точкам
+    /// (средняя уверенность, точность) интервалов с достаточным числом прогнозов.
+    /// Меньше двух таких интервалов - уверенность не меняется
+    pub fn calibrate_confidence(
+        &self,
+        prediction_type: &str,
+        horizon: u32,
+        confidence: f64,
+    ) -> f64 {
+        let points: Vec<(f64, f64)> = self
+            .calibration_curve(prediction_type, horizon)
+            .into_iter()
+            .filter(|b| b.count >= MIN_CALIBRATION_SAMPLES)
+            .map(|b| (b.mean_confidence, b.accuracy))
+            .collect();
+        if points.len() < 2 || !confidence.is_finite() {
+            return confidence;
+        }
+
+        let calibrated = match points.iter().position(|(x, _)| *x >= confidence) {
+            Some(0) => points[0].1,
+            None => points[points.len() - 1].1,
+            Some(i) => {
+                let ((x0, y0), (x1, y1)) = (points[i - 1], points[i]);
+                if x1 - x0 > f64::EPSILON {
+                    y0 + (y1 - y0) * (confidence - x0) / (x1 - x0)
+                } else {
+                    y1
+                }
+            }
+        };
+        calibrated.clamp(0.0, 1.0)
+    }
+
+    /// MAPE последних ошибок (окно политики переобучения); `None` без ошибок
+    /// с ненулевым фактом
+    pub fn rolling_mape(&self, prediction_type: &str, horizon: u32) -> Option<f64> {
+        let percent_errors = self.absolute_percent_errors(prediction_type, horizon);
+        let window = &percent_errors[percent_errors.len().saturating_sub(self.retrain.window)..];
+        (!window.is_empty()).then(|| window.iter().sum::<f64>() / window.len() as f64)
+    }
+
+    /// Сигнал переобучения, если после каждой из последних `consecutive` ошибок
+    /// скользящая MAPE была выше порога
+    pub fn retrain_signal(&self, prediction_type: &str, horizon: u32) -> Option<RetrainSignal> {
+        let percent_errors = self.absolute_percent_errors(prediction_type, horizon);
+        let consecutive = self.retrain.consecutive;
+        if consecutive == 0 || percent_errors.len() < consecutive {
+            return None;
+        }
+
+        let mape_at = |end: usize| -> f64 {
+            let window = &percent_errors[end.saturating_sub(self.retrain.window)..end];
+            window.iter().sum::<f64>() / window.len() as f64
+        };
+        let len = percent_errors.len();
+        let above =
+            (len - consecutive + 1..=len).all(|end| mape_at(end) > self.retrain.mape_threshold);
+        above.then(|| RetrainSignal {
+            prediction_type: prediction_type.to_string(),
+            horizon,
+            rolling_mape: mape_at(len),
+            consecutive,
+        })
+    }
+
+    /// |ошибка / факт| по порядку записи; нулевые факты пропускаются
+    fn absolute_percent_errors(&self, prediction_type: &str, horizon: u32) -> Vec<f64> {
+        self.relevant_errors(prediction_type, horizon)
+            .iter()
+            .filter(|e| e.actual_value.abs() > f64::EPSILON)
+            .map(|e| (e.error / e.actual_value).abs())
+            .filter(|pe| pe.is_finite())
+            .collect()
+    }
+
+    /// Пары (тип прогноза, горизонт), по которым есть ошибки
+    pub fn tracked(&self) -> Vec<(String, u32)> {
+        let mut tracked: Vec<(String, u32)> = self
+            .errors
+            .iter()
+            .map(|e| (e.prediction_type.clone(), e.horizon))
+            .collect();
+        tracked.sort();
+        tracked.dedup();
+        tracked
+    }
+
+    /// Число ошибок с конечными значениями для пары (тип, горизонт)
+    pub fn error_count(&self, prediction_type: &str, horizon: u32) -> usize {
+        self.relevant_errors(prediction_type, horizon).len()
+    }
+
     pub fn analyze_patterns(&self) -> HashMap<String, f64> {
         let mut patterns = HashMap::new();
 
         // Анализ ошибок по типам
         let mut errors_by_type: HashMap<String, Vec<f64>> = HashMap::new();
-        for error in &self.errors {
+        for error in self.errors.iter().filter(|e| e.error.is_finite()) {
             errors_by_type
                 .entry(error.prediction_type.clone())
                 .or_default()
@@ -129,6 +628,43 @@ impl LearningModule {
 
         patterns
     }
+
+    /// Ошибки прогнозов типа `prediction_type` на `horizon` недель с конечными значениями
+    fn relevant_errors(&self, prediction_type: &str, horizon: u32) -> Vec<&PredictionError> {
+        self.errors
+            .iter()
+            .filter(|e| e.prediction_type == prediction_type && e.horizon == horizon)
+            .filter(|e| {
+                e.error.is_finite() && e.predicted_value.is_finite() && e.actual_value.is_finite()
+            })
+            .collect()
+    }
+}
+
+/// Медиана; `None` для пустого набора
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
+}
+
+/// Среднее без доли `trim` наименьших и наибольших значений
+fn trimmed_mean(values: &mut [f64], trim: f64) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    // Хотя бы одно значение с каждой стороны, если их достаточно
+    let cut = ((values.len() as f64 * trim).ceil() as usize).min((values.len() - 1) / 2);
+    let kept = &values[cut..values.len() - cut];
+    kept.iter().sum::<f64>() / kept.len() as f64
 }
 
 impl Default for LearningModule {
@@ -136,3 +672,158 @@ impl Default for LearningModule {
         Self::new(1000)
     }
 }
+
+/// Построитель `LearningModule`
+#[derive(Debug, Clone)]
+pub struct LearningModuleBuilder {
+    max_errors: usize,
+    retrain: RetrainPolicy,
+}
+
+impl Default for LearningModuleBuilder {
+    fn default() -> Self {
+        Self {
+            max_errors: 1000,
+            retrain: RetrainPolicy::default(),
+        }
+    }
+}
+
+impl LearningModuleBuilder {
+    /// Сколько последних ошибок хранить
+    pub fn max_errors(mut self, max_errors: usize) -> Self {
+        self.max_errors = max_errors;
+        self
+    }
+
+    /// Порог скользящей MAPE для сигнала переобучения (0.25 - 25%)
+    pub fn retrain_mape_threshold(mut self, threshold: f64) -> Self {
+        self.retrain.mape_threshold = threshold;
+        self
+    }
+
+    /// Сколько ошибок подряд MAPE должна превышать порог
+    pub fn retrain_after(mut self, consecutive: usize) -> Self {
+        self.retrain.consecutive = consecutive;
+        self
+    }
+
+    /// Окно скользящей MAPE
+    pub fn mape_window(mut self, window: usize) -> Self {
+        self.retrain.window = window;
+        self
+    }
+
+    pub fn build(self) -> Result<LearningModule, String> {
+        if self.max_errors == 0 {
+            return Err("max_errors must be positive".to_string());
+        }
+        if !(self.retrain.mape_threshold > 0.0 && self.retrain.mape_threshold.is_finite()) {
+            return Err(format!(
+                "Invalid retrain MAPE threshold: {}",
+                self.retrain.mape_threshold
+            ));
+        }
+        if self.retrain.window == 0 {
+            return Err("mape_window must be positive".to_string());
+        }
+        Ok(LearningModule {
+            retrain: self.retrain,
+            ..LearningModule::new(self.max_errors)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPE: &str = "weekly_hours";
+
+    fn error(predicted: f64, actual: f64, horizon: u32) -> PredictionError {
+        PredictionError {
+            prediction_type: TYPE.to_string(),
+            predicted_value: predicted,
+            actual_value: actual,
+            error: predicted - actual,
+            context: serde_json::Value::Null,
+            horizon,
+            confidence: None,
+            recorded_at: None,
+            tenant: String::new(),
+        }
+    }
+
+    fn module(errors: Vec<PredictionError>) -> LearningModule {
+        let mut module = LearningModule::new(1000);
+        module.load_errors(errors);
+        module
+    }
+
+    /// Фактор конечен и не выходит за `MAX_CORRECTION`
+    fn assert_bounded(factor: f64) {
+        assert!(factor.is_finite(), "factor {}", factor);
+        assert!(
+            (1.0 - MAX_CORRECTION..=1.0 + MAX_CORRECTION).contains(&factor),
+            "factor {}",
+            factor
+        );
+    }
+
+    #[test]
+    fn zero_actuals_give_no_correction() {
+        let module = module((0..10).map(|_| error(8.0, 0.0, 1)).collect());
+        let factor = module.get_correction_factor(TYPE);
+        assert_bounded(factor);
+        assert_eq!(factor, 1.0);
+    }
+
+    #[test]
+    fn non_finite_errors_are_ignored() {
+        let mut errors: Vec<PredictionError> = (0..5).map(|_| error(11.0, 10.0, 1)).collect();
+        errors.push(error(f64::NAN, 10.0, 1));
+        errors.push(error(f64::INFINITY, 10.0, 1));
+        errors.push(error(10.0, f64::NEG_INFINITY, 1));
+        errors.push(PredictionError {
+            error: f64::NAN,
+            ..error(11.0, 10.0, 1)
+        });
+        // Конечные значения, но относительная ошибка переполняется
+        errors.push(error(f64::MAX, 1e-300, 1));
+        let factor = module(errors).get_correction_factor(TYPE);
+        assert_bounded(factor);
+        assert!((factor - 0.9).abs() < 1e-9, "factor {}", factor);
+    }
+
+    #[test]
+    fn single_outlier_does_not_move_median() {
+        let mut errors: Vec<PredictionError> = (0..9).map(|_| error(11.0, 10.0, 1)).collect();
+        errors.push(error(1e12, 10.0, 1));
+        let factor = module(errors).get_correction_factor(TYPE);
+        assert_bounded(factor);
+        assert!((factor - 0.9).abs() < 1e-9, "factor {}", factor);
+    }
+
+    #[test]
+    fn lone_extreme_error_is_clamped() {
+        let factor = module(vec![error(1e12, 10.0, 1)]).get_correction_factor(TYPE);
+        assert_bounded(factor);
+        assert_eq!(factor, 1.0 - MAX_CORRECTION);
+
+        let factor = module(vec![error(0.0, 10.0, 1)]).get_correction_factor(TYPE);
+        assert_bounded(factor);
+        assert_eq!(factor, 1.0 + MAX_CORRECTION);
+    }
+
+    #[test]
+    fn empty_horizon_bucket_gives_no_correction() {
+        let module = module((0..10).map(|_| error(12.0, 10.0, 1)).collect());
+        assert!(module.get_correction_factor_for_horizon(TYPE, 1) < 1.0);
+
+        let factor = module.get_correction_factor_for_horizon(TYPE, 4);
+        assert_bounded(factor);
+        assert_eq!(factor, 1.0);
+        assert_eq!(module.get_correction_factor("monthly_hours"), 1.0);
+        assert_eq!(LearningModule::default().get_correction_factor(TYPE), 1.0);
+    }
+}
@@ -0,0 +1,250 @@
+//! Матричный профиль ряда часов по дням: повторяющиеся мотивы и диссонансы
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use crate::types::{PatternDiagnostics, PatternDiscord, PatternMotif, TimesheetEntry};
+
+/// Окно по умолчанию: неделя
+pub const DEFAULT_PATTERN_WINDOW: usize = 7;
+
+/// Сколько мотивов и диссонансов возвращается по умолчанию
+const DEFAULT_TOP: usize = 3;
+
+/// Диссонансы слабее этого (нормированное расстояние) не отмечают дни в `discord_days`
+const MIN_DISCORD_STRENGTH: f64 = 0.25;
+
+/// Часы по календарным дням от первой до последней записи; дни без записей - нули
+#[derive(Debug, Clone)]
+pub struct DailySeries {
+    pub start: NaiveDate,
+    pub hours: Vec<f64>,
+}
+
+impl DailySeries {
+    pub fn from_entries(entries: &[TimesheetEntry]) -> Option<Self> {
+        let mut daily: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+        for entry in entries {
+            let Some(date) = entry
+                .begin
+                .get(..10)
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            else {
+                continue;
+            };
+            *daily.entry(date).or_default() += entry.duration as f64 / 60.0;
+        }
+
+        let (&start, _) = daily.first_key_value()?;
+        let (&end, _) = daily.last_key_value()?;
+        let hours = start
+            .iter_days()
+            .take_while(|d| *d <= end)
+            .map(|d| daily.get(&d).copied().unwrap_or(0.0))
+            .collect();
+        Some(Self { start, hours })
+    }
+
+    pub fn date(&self, index: usize) -> NaiveDate {
+        self.start + chrono::Duration::days(index as i64)
+    }
+}
+
+/// Расстояние от каждого окна до ближайшего непересекающегося окна
+/// (z-нормализованное евклидово) и индекс этого окна
+#[derive(Debug, Clone)]
+pub struct MatrixProfile {
+    pub window: usize,
+    pub profile: Vec<f64>,
+    pub index: Vec<usize>,
+}
+
+impl MatrixProfile {
+    /// Прямой расчет за O(n^2 * window); окна ближе `window / 2` к текущему
+    /// (тривиальные совпадения) не учитываются
+    pub fn compute(series: &[f64], window: usize) -> Result<Self, String> {
+        if window < 2 {
+            return Err("Pattern window must be at least 2".to_string());
+        }
+        if series.len() < window * 2 {
+            return Err(format!(
+                "Need at least {} days for window {}",
+                window * 2,
+                window
+            ));
+        }
+
+        let n_windows = series.len() - window + 1;
+        let stats: Vec<(f64, f64)> = (0..n_windows)
+            .map(|i| {
+                let values = &series[i..i + window];
+                let mean = values.iter().sum::<f64>() / window as f64;
+                let std =
+                    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / window as f64).sqrt();
+                (mean, std)
+            })
+            .collect();
+        let exclusion = window.div_ceil(2);
+
+        let mut profile = vec![f64::INFINITY; n_windows];
+        let mut index = vec![0; n_windows];
+        for i in 0..n_windows {
+            for j in i + exclusion..n_windows {
+                let distance = z_distance(series, window, (i, stats[i]), (j, stats[j]));
+                if distance < profile[i] {
+                    profile[i] = distance;
+                    index[i] = j;
+                }
+                if distance < profile[j] {
+                    profile[j] = distance;
+                    index[j] = i;
+                }
+            }
+        }
+
+        Ok(Self {
+            window,
+            profile,
+            index,
+        })
+    }
+
+    /// Мотивы: пары самых похожих окон по возрастанию расстояния, без пересечений
+    pub fn motifs(&self, top: usize) -> Vec<(usize, usize, f64)> {
+        let mut used: Vec<usize> = Vec::new();
+        let mut motifs = Vec::new();
+        for i in self.order(false) {
+            if motifs.len() >= top {
+                break;
+            }
+            let j = self.index[i];
+            if used
+                .iter()
+                .any(|&u| u.abs_diff(i) < self.window || u.abs_diff(j) < self.window)
+            {
+                continue;
+            }
+            used.extend([i, j]);
+            motifs.push((i, j, self.profile[i]));
+        }
+        motifs
+    }
+
+    /// Диссонансы: окна, дальше всего стоящие от ближайшего соседа, без пересечений
+    pub fn discords(&self, top: usize) -> Vec<(usize, f64)> {
+        let mut discords: Vec<(usize, f64)> = Vec::new();
+        for i in self.order(true) {
+            if discords.len() >= top {
+                break;
+            }
+            if discords.iter().any(|&(d, _)| d.abs_diff(i) < self.window) {
+                continue;
+            }
+            discords.push((i, self.profile[i]));
+        }
+        discords
+    }
+
+    /// Расстояние, нормированное на наибольшее возможное (2 * sqrt(window)), 0-1
+    pub fn normalized(&self, distance: f64) -> f64 {
+        (distance / (2.0 * (self.window as f64).sqrt())).clamp(0.0, 1.0)
+    }
+
+    fn order(&self, descending: bool) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.profile.len())
+            .filter(|&i| self.profile[i].is_finite())
+            .collect();
+        order.sort_by(|&a, &b| self.profile[a].total_cmp(&self.profile[b]));
+        if descending {
+            order.reverse();
+        }
+        order
+    }
+}
+
+/// Z-нормализованное евклидово расстояние между окнами; постоянное окно
+/// совпадает только с постоянным
+fn z_distance(
+    series: &[f64],
+    window: usize,
+    (i, (mean_i, std_i)): (usize, (f64, f64)),
+    (j, (mean_j, std_j)): (usize, (f64, f64)),
+) -> f64 {
+    const FLAT: f64 = 1e-8;
+    match (std_i < FLAT, std_j < FLAT) {
+        (true, true) => 0.0,
+        (true, false) | (false, true) => (window as f64).sqrt(),
+        (false, false) => {
+            let dot: f64 = (0..window).map(|k| series[i + k] * series[j + k]).sum();
+            let correlation =
+                (dot - window as f64 * mean_i * mean_j) / (window as f64 * std_i * std_j);
+            (2.0 * window as f64 * (1.0 - correlation.clamp(-1.0, 1.0))).sqrt()
+        }
+    }
+}
+
+/// Дни, попавшие в окна `top` диссонансов, с нормированной силой диссонанса;
+/// слабые диссонансы (`MIN_DISCORD_STRENGTH`) пропускаются
+pub fn discord_days(
+    entries: &[TimesheetEntry],
+    window: usize,
+    top: usize,
+) -> BTreeMap<NaiveDate, f64> {
+    let mut days = BTreeMap::new();
+    let Some(series) = DailySeries::from_entries(entries) else {
+        return days;
+    };
+    let Ok(profile) = MatrixProfile::compute(&series.hours, window) else {
+        return days;
+    };
+    for (start, distance) in profile.discords(top) {
+        let strength = profile.normalized(distance);
+        if strength < MIN_DISCORD_STRENGTH {
+            continue;
+        }
+        for offset in 0..window {
+            let day = days.entry(series.date(start + offset)).or_insert(0.0);
+            *day = f64::max(*day, strength);
+        }
+    }
+    days
+}
+
+/// Мотивы и диссонансы ряда часов по дням; `window` и `top` - по умолчанию 7 и 3
+pub fn diagnose(
+    entries: &[TimesheetEntry],
+    window: Option<usize>,
+    top: Option<usize>,
+) -> Result<PatternDiagnostics, String> {
+    let window = window.unwrap_or(DEFAULT_PATTERN_WINDOW);
+    let top = top.unwrap_or(DEFAULT_TOP);
+    let series = DailySeries::from_entries(entries).ok_or("No dated entries provided")?;
+    let profile = MatrixProfile::compute(&series.hours, window)?;
+    let date = |i: usize| series.date(i).to_string();
+
+    Ok(PatternDiagnostics {
+        window,
+        start_date: date(0),
+        days: series.hours.len(),
+        motifs: profile
+            .motifs(top)
+            .into_iter()
+            .map(|(first, second, distance)| PatternMotif {
+                first_start: date(first),
+                second_start: date(second),
+                distance,
+            })
+            .collect(),
+        discords: profile
+            .discords(top)
+            .into_iter()
+            .map(|(start, distance)| PatternDiscord {
+                start: date(start),
+                distance,
+                strength: profile.normalized(distance),
+            })
+            .collect(),
+        profile: profile.profile,
+    })
+}
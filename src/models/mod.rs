@@ -1,13 +1,35 @@
 //! ML модели
 
+pub mod absence;
+pub mod anomaly_context;
 pub mod anomaly_detection;
+pub mod anomaly_filter;
+pub mod baseline;
+pub mod billing;
+pub mod capacity_gap;
+pub mod contract;
+pub mod entry_explanation;
+pub mod forecast_ledger;
 pub mod forecasting;
 pub mod learning;
+pub mod matrix_profile;
 pub mod productivity;
+pub mod recommendation_templates;
 pub mod recommendations;
+pub mod schedule;
+pub mod seasonality;
+pub mod survival;
+pub mod team;
+pub mod unit_economics;
+pub mod utilization;
+pub mod validation;
+pub mod workload_balance;
 
-pub use anomaly_detection::AnomalyDetector;
-pub use forecasting::ForecastingModel;
-pub use learning::{LearningModule, PredictionError};
+pub use anomaly_detection::{AnomalyDetector, AnomalyDetectorBuilder};
+pub use forecasting::{ForecastingModel, ForecastingModelBuilder};
+pub use learning::{
+    AnomalyVerdict, CalibrationBucket, ClassificationStats, LearningModule, LearningModuleBuilder,
+    PredictionError, RetrainSignal, SeverityModel,
+};
 pub use productivity::ProductivityAnalyzer;
 pub use recommendations::RecommendationEngine;
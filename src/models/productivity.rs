@@ -1,16 +1,71 @@
 //! Анализ продуктивности
 
-use chrono::DateTime;
-use std::collections::HashMap;
+use chrono::{DateTime, Datelike, Timelike, Weekday};
+use std::collections::{BTreeMap, HashMap};
 
+use crate::text;
 use crate::types::{
-    BreakRecommendations, EfficiencyPoint, OptimalWorkHours, ProductivityOutput, TimesheetEntry,
-    UserPreferences,
+    AnalyzerConfig, BillableShare, BreakRecommendations, DayClassification, DayEnergy,
+    DayTypeAnalysis, EfficiencyMetric, EfficiencyPoint, EnergyProfile, InterruptedProject,
+    MeetingLoad, MetricStanding, OptimalWorkHours, ProductivityOutput, SessionStatistics,
+    TimesheetEntry, UserPreferences, WeekendPolicy, WeeklyBillableShare, WeeklyComparison,
+    WeeklyDayMix, WeeklyMeetingShare, WeeklyScheduleConflicts,
 };
 
+/// Длительность записи сверх суток (минуты) при раскладке по часам не учитывается
+const MAX_ENTRY_MINUTES: i32 = 24 * 60;
+
+/// Сколько последних недель сравнивается с текущей
+const COMPARISON_WEEKS: usize = 52;
+
+/// Сессия от этой длительности (минуты) считается сосредоточенной работой
+const FOCUSED_SESSION_MINUTES: i32 = 60;
+
+/// Меньше сессий активности - для нее используется общая рекомендация по перерывам
+const MIN_ACTIVITY_SESSIONS: usize = 5;
+
+/// Подстроки названия активности или тега (в описании - начала слов), по
+/// которым запись считается встречей
+pub const DEFAULT_MEETING_PATTERNS: &[&str] = &[
+    "meeting",
+    "call",
+    "standup",
+    "sync",
+    "встреча",
+    "созвон",
+    "митинг",
+    "планерка",
+];
+
+/// Сколько последних недель дают текущую долю оплачиваемой работы
+pub const BILLABLE_RECENT_WEEKS: usize = 4;
+
+/// Архетипы дней и начальные центры кластеров для признаков дня:
+/// доля встреч, доля сосредоточенной работы, раздробленность, доля коротких записей
+const DAY_TYPES: [(&str, [f64; 4]); 4] = [
+    ("focus", [0.1, 0.8, 0.2, 0.1]),
+    ("meeting_heavy", [0.6, 0.2, 0.4, 0.2]),
+    ("admin", [0.1, 0.1, 0.6, 0.7]),
+    ("split", [0.3, 0.3, 0.8, 0.3]),
+];
+
+/// Записи короче (минуты) считаются административными
+const SHORT_ENTRY_MINUTES: i32 = 15;
+
+/// Пауза (минуты) после записи, которая считается перерывом в профиле энергии
+const ENERGY_BREAK_MINUTES: i64 = 15;
+
+/// Сессия этой длительности (минуты) и дольше считается полностью непрерывной
+const CONTINUOUS_SESSION_MINUTES: f64 = 120.0;
+
 #[derive(Default)]
 pub struct ProductivityAnalyzer {
     preferences: Option<UserPreferences>,
+    meeting_patterns: Vec<String>,
+    billable_tags: Vec<String>,
+    config: AnalyzerConfig,
+    default_rate: f64,
+    project_rates: HashMap<i32, f64>,
 }
 
 impl ProductivityAnalyzer {
@@ -18,8 +73,242 @@ impl ProductivityAnalyzer {
         Self::default()
     }
 
-    pub fn with_preferences(preferences: Option<UserPreferences>) -> Self {
-        Self { preferences }
+    pub fn with_preferences(preferences: Option<UserPreferences>, config: AnalyzerConfig) -> Self {
+        Self {
+            preferences,
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Ставки для `EfficiencyMetric::OutputDensity`: `default_rate` для проектов
+    /// без своей ставки в `project_rates`
+    pub fn rates(mut self, default_rate: f64, project_rates: HashMap<i32, f64>) -> Self {
+        self.default_rate = default_rate;
+        self.project_rates = project_rates;
+        self
+    }
+
+    /// Шаблоны встреч (`settings.meeting_patterns`); пустой список -
+    /// `DEFAULT_MEETING_PATTERNS`
+    pub fn meeting_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.meeting_patterns = patterns.into_iter().map(|p| p.to_lowercase()).collect();
+        self
+    }
+
+    /// Теги оплачиваемых записей (`settings.billable_tags`)
+    pub fn billable_tags(mut self, tags: Vec<String>) -> Self {
+        self.billable_tags = tags.into_iter().map(|t| t.to_lowercase()).collect();
+        self
+    }
+
+    /// Оплачиваемая ли запись: флаг `billable`, иначе наличие тега из
+    /// `billable_tags`; `None` без флага и без заданных тегов
+    pub fn is_billable(&self, entry: &TimesheetEntry) -> Option<bool> {
+        entry.billable.or_else(|| {
+            (!self.billable_tags.is_empty()).then(|| {
+                entry
+                    .tags
+                    .iter()
+                    .any(|t| self.billable_tags.contains(&t.to_lowercase()))
+            })
+        })
+    }
+
+    /// Доля оплачиваемой работы по неделям; `None`, если ни одна запись не
+    /// классифицирована
+    pub fn billable_share(&self, entries: &[TimesheetEntry]) -> Option<BillableShare> {
+        // (оплачиваемые, неоплачиваемые) минуты по неделям
+        let mut weeks: BTreeMap<(i32, i32), (i64, i64)> = BTreeMap::new();
+        let mut unclassified_minutes = 0i64;
+        for entry in entries {
+            let minutes = entry.duration as i64;
+            match self.is_billable(entry) {
+                Some(true) => weeks.entry(entry.iso_week()).or_default().0 += minutes,
+                Some(false) => weeks.entry(entry.iso_week()).or_default().1 += minutes,
+                None => unclassified_minutes += minutes,
+            }
+        }
+        if weeks.is_empty() {
+            return None;
+        }
+
+        let share = |billable: i64, non_billable: i64| {
+            if billable + non_billable > 0 {
+                billable as f64 / (billable + non_billable) as f64
+            } else {
+                0.0
+            }
+        };
+        let recent = weeks
+            .values()
+            .rev()
+            .take(BILLABLE_RECENT_WEEKS)
+            .fold((0, 0), |(b, n), &(billable, non_billable)| {
+                (b + billable, n + non_billable)
+            });
+        let weeks: Vec<WeeklyBillableShare> = weeks
+            .into_iter()
+            .map(
+                |((year, week), (billable, non_billable))| WeeklyBillableShare {
+                    year,
+                    week,
+                    billable_hours: billable as f64 / 60.0,
+                    non_billable_hours: non_billable as f64 / 60.0,
+                    billable_share: share(billable, non_billable),
+                },
+            )
+            .collect();
+        Some(BillableShare {
+            average_share: weeks.iter().map(|w| w.billable_share).sum::<f64>() / weeks.len() as f64,
+            recent_share: share(recent.0, recent.1),
+            unclassified_hours: unclassified_minutes as f64 / 60.0,
+            weeks,
+        })
+    }
+
+    /// Запись - встреча, если название активности или тег содержит шаблон
+    /// либо описание упоминает его (`text::mentions`)
+    pub fn is_meeting(&self, entry: &TimesheetEntry) -> bool {
+        let patterns: Vec<&str> = if self.meeting_patterns.is_empty() {
+            DEFAULT_MEETING_PATTERNS.to_vec()
+        } else {
+            self.meeting_patterns.iter().map(String::as_str).collect()
+        };
+        let matches = |text: &str| {
+            let text = text.to_lowercase();
+            patterns.iter().any(|p| text.contains(p))
+        };
+        if matches(&entry.activity_name) || entry.tags.iter().any(|tag| matches(tag)) {
+            return true;
+        }
+        // Описание - свободный текст на двух языках: шаблон ищется по словам
+        entry.description.as_deref().is_some_and(|description| {
+            let terms = text::tokenize(description);
+            patterns.iter().any(|p| text::mentions(&terms, p))
+        })
+    }
+
+    /// Доля встреч по неделям и ее связь с глубокой работой (часы в сессиях
+    /// без встреч от `FOCUSED_SESSION_MINUTES` минут); `None` без записей
+    pub fn meeting_load(&self, entries: &[TimesheetEntry]) -> Option<MeetingLoad> {
+        let mut weeks: BTreeMap<(i32, i32), Vec<&TimesheetEntry>> = BTreeMap::new();
+        for entry in entries {
+            weeks.entry(entry.iso_week()).or_default().push(entry);
+        }
+        if weeks.is_empty() {
+            return None;
+        }
+
+        let weeks: Vec<WeeklyMeetingShare> = weeks
+            .into_iter()
+            .map(|((year, week), week_entries)| {
+                let total: i32 = week_entries.iter().map(|e| e.duration).sum();
+                let (meetings, work): (Vec<&TimesheetEntry>, Vec<&TimesheetEntry>) =
+                    week_entries.into_iter().partition(|e| self.is_meeting(e));
+                let meeting_minutes: i32 = meetings.iter().map(|e| e.duration).sum();
+                let deep_work_minutes: i32 = self
+                    .extract_sessions(work)
+                    .iter()
+                    .filter(|s| s.duration >= FOCUSED_SESSION_MINUTES)
+                    .map(|s| s.duration)
+                    .sum();
+                WeeklyMeetingShare {
+                    year,
+                    week,
+                    total_hours: total as f64 / 60.0,
+                    meeting_hours: meeting_minutes as f64 / 60.0,
+                    meeting_share: if total > 0 {
+                        meeting_minutes as f64 / total as f64
+                    } else {
+                        0.0
+                    },
+                    deep_work_hours: deep_work_minutes as f64 / 60.0,
+                }
+            })
+            .collect();
+
+        let shares: Vec<f64> = weeks.iter().map(|w| w.meeting_share).collect();
+        let deep_work: Vec<f64> = weeks.iter().map(|w| w.deep_work_hours).collect();
+        Some(MeetingLoad {
+            average_share: shares.iter().sum::<f64>() / shares.len() as f64,
+            deep_work_correlation: correlation(&shares, &deep_work),
+            weeks,
+        })
+    }
+
+    /// Работа по неделям во время сна, перед сном и в выходные относительно
+    /// `UserPreferences`; пусто без предпочтений. Записи раскладываются по минутам
+    /// от начала (местное время записи), так что запись через полночь попадает
+    /// в оба дня
+    pub fn schedule_conflicts(&self, entries: &[TimesheetEntry]) -> Vec<WeeklyScheduleConflicts> {
+        let Some(prefs) = self.preferences.as_ref() else {
+            return Vec::new();
+        };
+        let sleep_start = prefs.sleep_start_hour.rem_euclid(24);
+        let sleep_end = prefs.sleep_end_hour.rem_euclid(24);
+        let buffer_start =
+            (sleep_start - prefs.no_work_before_sleep_hours.clamp(0, 24)).rem_euclid(24);
+        let work_on_weekends = self.works_on_weekends();
+
+        // Час в полуинтервале [start, end) по кругу суток
+        let within = |hour: i32, start: i32, end: i32| {
+            if start <= end {
+                hour >= start && hour < end
+            } else {
+                hour >= start || hour < end
+            }
+        };
+
+        // (всего, сон, перед сном, выходные) в минутах
+        let mut weeks: BTreeMap<(i32, i32), [i32; 4]> = BTreeMap::new();
+        for entry in entries.iter().filter(|e| e.duration > 0) {
+            let Ok(begin) = DateTime::parse_from_rfc3339(&entry.begin) else {
+                continue;
+            };
+            let counts = weeks.entry(entry.iso_week()).or_default();
+            let begin = begin.naive_local();
+            for minute in 0..entry.duration.min(MAX_ENTRY_MINUTES) {
+                let at = begin + chrono::Duration::minutes(minute as i64);
+                let hour = at.hour() as i32;
+                counts[0] += 1;
+                if within(hour, sleep_start, sleep_end) {
+                    counts[1] += 1;
+                } else if within(hour, buffer_start, sleep_start) {
+                    counts[2] += 1;
+                }
+                if !work_on_weekends && matches!(at.weekday(), Weekday::Sat | Weekday::Sun) {
+                    counts[3] += 1;
+                }
+            }
+        }
+
+        weeks
+            .into_iter()
+            .map(
+                |((year, week), [total, sleep, buffer, weekend])| WeeklyScheduleConflicts {
+                    year,
+                    week,
+                    total_hours: total as f64 / 60.0,
+                    sleep_hours: sleep as f64 / 60.0,
+                    buffer_hours: buffer as f64 / 60.0,
+                    weekend_hours: weekend as f64 / 60.0,
+                },
+            )
+            .collect()
+    }
+
+    /// Планируется ли работа в выходные по `weekend_policy` и предпочтениям
+    fn works_on_weekends(&self) -> bool {
+        match self.config.weekend_policy {
+            WeekendPolicy::Preferences => self
+                .preferences
+                .as_ref()
+                .map(|p| p.work_on_weekends)
+                .unwrap_or(false),
+            WeekendPolicy::Include => true,
+            WeekendPolicy::Exclude => false,
+        }
     }
 
     pub fn analyze(&self, entries: &[TimesheetEntry]) -> ProductivityOutput {
@@ -32,17 +321,352 @@ impl ProductivityAnalyzer {
         // 3. Определение оптимальных часов
         let optimal_hours = self.find_optimal_hours(&hourly_efficiency, &daily_efficiency);
 
-        // 4. Рекомендации по перерывам
+        // 4. Рекомендации по перерывам, в том числе по активностям
         let break_recommendations = self.analyze_breaks(entries);
+        let break_recommendations_by_activity =
+            self.analyze_activity_breaks(entries, &break_recommendations);
+
+        // 5. Последняя неделя относительно истории
+        let weekly_comparison = self.compare_weeks(entries);
+
+        // 6. Раздробленность сессий и перерывы
+        let session_stats = self.analyze_sessions(entries);
+
+        // 7. Нагрузка встречами
+        let meeting_load = self.meeting_load(entries);
+
+        // 8. Доля оплачиваемой работы
+        let billable = self.billable_share(entries);
+
+        // 9. Типы дней
+        let day_types = self.classify_days(entries);
+
+        // 10. Профиль энергии по дням недели
+        let energy_profile = self.energy_profile(entries);
 
         ProductivityOutput {
             optimal_work_hours: optimal_hours,
             efficiency_by_time: hourly_efficiency,
             break_recommendations,
+            break_recommendations_by_activity,
+            weekly_comparison,
+            session_stats,
+            meeting_load,
+            billable,
+            day_types,
+            energy_profile,
+            // Считается по неделям запроса (`contract::utilization`)
+            contract: None,
+            // По недельной емкости из настроек (`utilization::analyze`)
+            utilization: None,
+            // По неделям запроса (`unit_economics::profitability`)
+            profitability: None,
+        }
+    }
+
+    /// Сессии, их раздробленность и перерывы между записями
+    pub fn analyze_sessions(&self, entries: &[TimesheetEntry]) -> Option<SessionStatistics> {
+        let sessions = self.extract_sessions(entries);
+        if sessions.is_empty() {
+            return None;
+        }
+        let total_minutes: i32 = sessions.iter().map(|s| s.duration).sum();
+
+        // Паузы между соседними записями дня; прерванным считается проект
+        // записи перед паузой
+        let mut interruption_minutes = Vec::new();
+        let mut interrupted: HashMap<i32, (String, usize)> = HashMap::new();
+        for day_entries in group_by_day(entries).into_values() {
+            for pair in day_entries.windows(2) {
+                let (current, next) = (pair[0], pair[1]);
+                let Some(gap) = gap_minutes(current, next) else {
+                    continue;
+                };
+                if gap <= 0 {
+                    continue;
+                }
+                interruption_minutes.push(gap);
+                if let Some(project_id) = current.project_id {
+                    interrupted
+                        .entry(project_id)
+                        .or_insert_with(|| (current.project_name.clone(), 0))
+                        .1 += 1;
+                }
+            }
+        }
+
+        let mut most_interrupted_projects: Vec<InterruptedProject> = interrupted
+            .into_iter()
+            .map(
+                |(project_id, (project_name, interruptions))| InterruptedProject {
+                    project_id,
+                    project_name,
+                    interruptions,
+                },
+            )
+            .collect();
+        most_interrupted_projects.sort_by(|a, b| {
+            b.interruptions
+                .cmp(&a.interruptions)
+                .then(a.project_id.cmp(&b.project_id))
+        });
+        most_interrupted_projects.truncate(5);
+
+        Some(SessionStatistics {
+            sessions: sessions.len(),
+            avg_session_minutes: total_minutes as f64 / sessions.len() as f64,
+            fragmentation_index: fragmentation_index(sessions.len(), total_minutes),
+            interruptions: interruption_minutes.len(),
+            avg_interruption_minutes: if interruption_minutes.is_empty() {
+                0.0
+            } else {
+                interruption_minutes.iter().sum::<i64>() as f64 / interruption_minutes.len() as f64
+            },
+            most_interrupted_projects,
+        })
+    }
+
+    /// Типы дней (`DAY_TYPES`): k-средних по признакам дней с центрами,
+    /// начинающимися с архетипов, поэтому номер кластера остается типом дня
+    pub fn classify_days(&self, entries: &[TimesheetEntry]) -> Option<DayTypeAnalysis> {
+        let days: Vec<(String, (i32, i32), [f64; 4])> = group_by_day(entries)
+            .into_iter()
+            .map(|(date, day_entries)| {
+                let week = day_entries[0].iso_week();
+                (date, week, self.day_features(&day_entries))
+            })
+            .collect();
+        if days.is_empty() {
+            return None;
+        }
+
+        let mut centroids: Vec<[f64; 4]> = DAY_TYPES.iter().map(|(_, c)| *c).collect();
+        let mut labels = vec![0usize; days.len()];
+        for _ in 0..20 {
+            let mut changed = false;
+            for (label, (_, _, features)) in labels.iter_mut().zip(&days) {
+                let nearest = nearest_centroid(features, &centroids);
+                changed |= nearest != *label;
+                *label = nearest;
+            }
+            for (cluster, centroid) in centroids.iter_mut().enumerate() {
+                let members: Vec<&[f64; 4]> = labels
+                    .iter()
+                    .zip(&days)
+                    .filter(|(label, _)| **label == cluster)
+                    .map(|(_, (_, _, features))| features)
+                    .collect();
+                // Пустой кластер сохраняет прежний центр
+                if members.is_empty() {
+                    continue;
+                }
+                for (i, value) in centroid.iter_mut().enumerate() {
+                    *value = members.iter().map(|f| f[i]).sum::<f64>() / members.len() as f64;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut weekly: BTreeMap<(i32, i32), [usize; 4]> = BTreeMap::new();
+        let mut classified: Vec<DayClassification> = days
+            .iter()
+            .zip(&labels)
+            .map(|((date, week, _), &label)| {
+                weekly.entry(*week).or_default()[label] += 1;
+                DayClassification {
+                    date: date.clone(),
+                    day_type: DAY_TYPES[label].0.to_string(),
+                }
+            })
+            .collect();
+        classified.sort_by(|a, b| a.date.cmp(&b.date));
+
+        let weekly_mix: Vec<WeeklyDayMix> = weekly
+            .into_iter()
+            .map(|((year, week), counts)| WeeklyDayMix {
+                year,
+                week,
+                focus: counts[0],
+                meeting_heavy: counts[1],
+                admin: counts[2],
+                split: counts[3],
+            })
+            .collect();
+
+        // Тренд: дни сосредоточенной работы за последние 4 недели против предыдущих 4
+        let focus: Vec<f64> = weekly_mix.iter().map(|w| w.focus as f64).collect();
+        let focus_trend = if focus.len() >= 2 {
+            let window = (focus.len() / 2).min(4);
+            let recent = &focus[focus.len() - window..];
+            let previous = &focus[focus.len() - 2 * window..focus.len() - window];
+            let delta = recent.iter().sum::<f64>() / window as f64
+                - previous.iter().sum::<f64>() / window as f64;
+            if delta > 0.5 {
+                "increasing"
+            } else if delta < -0.5 {
+                "decreasing"
+            } else {
+                "stable"
+            }
+        } else {
+            "stable"
+        };
+
+        Some(DayTypeAnalysis {
+            days: classified,
+            weekly_mix,
+            focus_trend: focus_trend.to_string(),
+        })
+    }
+
+    /// Часы каждого дня недели по уровню энергии. Энергия часа - средняя занятость
+    /// этого часа в такие дни, уменьшенная долей записей, после которых был перерыв;
+    /// часы с работой делятся на трети (high/medium/low)
+    pub fn energy_profile(&self, entries: &[TimesheetEntry]) -> Option<EnergyProfile> {
+        // (день недели, час) -> (минуты, записи, записи перед перерывом)
+        let mut slots: HashMap<(i32, i32), (i32, usize, usize)> = HashMap::new();
+        let mut dates: HashMap<i32, std::collections::HashSet<String>> = HashMap::new();
+        for day_entries in group_by_day(entries).into_values() {
+            for (i, entry) in day_entries.iter().enumerate() {
+                let followed_by_break = day_entries
+                    .get(i + 1)
+                    .and_then(|next| gap_minutes(entry, next))
+                    .is_some_and(|gap| gap >= ENERGY_BREAK_MINUTES);
+                let slot = slots
+                    .entry((entry.day_of_week, entry.hour_of_day))
+                    .or_default();
+                slot.0 += entry.duration;
+                slot.1 += 1;
+                slot.2 += followed_by_break as usize;
+                if let Some(date) = entry.begin.split('T').next() {
+                    dates
+                        .entry(entry.day_of_week)
+                        .or_default()
+                        .insert(date.to_string());
+                }
+            }
+        }
+        if slots.is_empty() {
+            return None;
         }
+
+        let mut days = Vec::new();
+        for day_of_week in 0..7 {
+            let n_dates = dates.get(&day_of_week).map(|d| d.len()).unwrap_or(0);
+            if n_dates == 0 {
+                continue;
+            }
+            let mut hours: Vec<(i32, f64)> = (0..24)
+                .filter_map(|hour| {
+                    let &(minutes, count, breaks) = slots.get(&(day_of_week, hour))?;
+                    let occupancy = (minutes as f64 / (n_dates as f64 * 60.0)).min(1.0);
+                    let break_rate = breaks as f64 / count as f64;
+                    Some((hour, occupancy * (1.0 - break_rate / 2.0)))
+                })
+                .collect();
+            hours.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            let third = hours.len().div_ceil(3);
+            let band = |from: usize, to: usize| -> Vec<i32> {
+                let (from, to) = (from.min(hours.len()), to.min(hours.len()));
+                let mut band: Vec<i32> = hours[from..to].iter().map(|(hour, _)| *hour).collect();
+                band.sort_unstable();
+                band
+            };
+            days.push(DayEnergy {
+                day_of_week,
+                high: band(0, third),
+                medium: band(third, 2 * third),
+                low: band(2 * third, hours.len()),
+            });
+        }
+
+        Some(EnergyProfile { days })
+    }
+
+    /// Признаки дня в диапазоне 0-1 в порядке центров `DAY_TYPES`
+    fn day_features(&self, day_entries: &[&TimesheetEntry]) -> [f64; 4] {
+        let total: i32 = day_entries.iter().map(|e| e.duration).sum();
+        if total <= 0 {
+            return [0.0; 4];
+        }
+        let (meetings, work): (Vec<&TimesheetEntry>, Vec<&TimesheetEntry>) =
+            day_entries.iter().partition(|e| self.is_meeting(e));
+        let meeting_minutes: i32 = meetings.iter().map(|e| e.duration).sum();
+        let sessions = self.extract_sessions(work);
+        let focused_minutes: i32 = sessions
+            .iter()
+            .filter(|s| s.duration >= FOCUSED_SESSION_MINUTES)
+            .map(|s| s.duration)
+            .sum();
+        let short_entries = day_entries
+            .iter()
+            .filter(|e| e.duration < SHORT_ENTRY_MINUTES)
+            .count();
+        [
+            meeting_minutes as f64 / total as f64,
+            focused_minutes as f64 / total as f64,
+            // Больше двух сессий в час - предельно раздробленный день
+            (fragmentation_index(sessions.len(), total) / 2.0).min(1.0),
+            short_entries as f64 / day_entries.len() as f64,
+        ]
+    }
+
+    /// Часы и доля сосредоточенной работы последней недели в распределении
+    /// по последним `COMPARISON_WEEKS` неделям; `None`, если неделя одна
+    fn compare_weeks(&self, entries: &[TimesheetEntry]) -> Option<WeeklyComparison> {
+        let mut weeks: BTreeMap<(i32, i32), Vec<&TimesheetEntry>> = BTreeMap::new();
+        for entry in entries {
+            weeks.entry(entry.iso_week()).or_default().push(entry);
+        }
+        if weeks.len() < 2 {
+            return None;
+        }
+
+        let skip = weeks.len().saturating_sub(COMPARISON_WEEKS);
+        let stats: Vec<((i32, i32), f64, f64, f64)> = weeks
+            .into_iter()
+            .skip(skip)
+            .map(|(key, week_entries)| {
+                let sessions = self.extract_sessions(week_entries);
+                let total: i32 = sessions.iter().map(|s| s.duration).sum();
+                let focused: i32 = sessions
+                    .iter()
+                    .filter(|s| s.duration >= FOCUSED_SESSION_MINUTES)
+                    .map(|s| s.duration)
+                    .sum();
+                let score = if total > 0 {
+                    focused as f64 / total as f64
+                } else {
+                    0.0
+                };
+                let fragmentation = fragmentation_index(sessions.len(), total);
+                (key, total as f64 / 60.0, score, fragmentation)
+            })
+            .collect();
+
+        let hours: Vec<f64> = stats.iter().map(|s| s.1).collect();
+        let scores: Vec<f64> = stats.iter().map(|s| s.2).collect();
+        let fragmentation: Vec<f64> = stats.iter().map(|s| s.3).collect();
+        let &((year, week), current_hours, current_score, current_fragmentation) = stats.last()?;
+
+        Some(WeeklyComparison {
+            year,
+            week,
+            weeks_compared: stats.len(),
+            hours: MetricStanding::new(current_hours, &hours),
+            productivity_score: MetricStanding::new(current_score, &scores),
+            fragmentation: MetricStanding::new(current_fragmentation, &fragmentation),
+        })
     }
 
     fn analyze_hourly_efficiency(&self, entries: &[TimesheetEntry]) -> Vec<EfficiencyPoint> {
+        if self.config.efficiency_metric == EfficiencyMetric::OutputDensity {
+            return self.analyze_output_density(entries);
+        }
+
         let mut hourly_data: HashMap<i32, (i32, i32)> = HashMap::new(); // (work, total)
 
         for entry in entries {
@@ -72,6 +696,55 @@ impl ProductivityAnalyzer {
         efficiency
     }
 
+    /// Занятость часа, где каждая минута взвешена качеством работы:
+    /// 0.4 - непрерывность сессии, 0.3 - ставка проекта относительно наибольшей,
+    /// 0.3 - отсутствие дробления сессии на короткие записи
+    fn analyze_output_density(&self, entries: &[TimesheetEntry]) -> Vec<EfficiencyPoint> {
+        let rate = |project_id: Option<i32>| -> f64 {
+            project_id
+                .and_then(|id| self.project_rates.get(&id).copied())
+                .unwrap_or(self.default_rate)
+        };
+        let max_rate = self
+            .project_rates
+            .values()
+            .copied()
+            .fold(self.default_rate, f64::max);
+
+        // час -> (взвешенные минуты, доступные минуты)
+        let mut hourly_data: HashMap<i32, (f64, i32)> = HashMap::new();
+        for session in self.extract_sessions(entries) {
+            let continuity = (session.duration as f64 / CONTINUOUS_SESSION_MINUTES).min(1.0);
+            let low_fragmentation =
+                1.0 - (fragmentation_index(session.entries.len(), session.duration) / 2.0).min(1.0);
+            for entry in &session.entries {
+                let value = if max_rate > 0.0 {
+                    rate(entry.project_id) / max_rate
+                } else {
+                    1.0
+                };
+                let quality = 0.4 * continuity + 0.3 * value + 0.3 * low_fragmentation;
+                let (weighted, total) = hourly_data.entry(entry.hour_of_day).or_insert((0.0, 0));
+                *weighted += entry.duration as f64 * quality;
+                *total += 60;
+            }
+        }
+
+        (0..24)
+            .map(|hour| {
+                let (weighted, total) = hourly_data.get(&hour).copied().unwrap_or((0.0, 0));
+                EfficiencyPoint {
+                    hour,
+                    efficiency: if total > 0 {
+                        weighted / total as f64
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect()
+    }
+
     fn analyze_daily_efficiency(&self, entries: &[TimesheetEntry]) -> HashMap<i32, f64> {
         let mut daily_data: HashMap<i32, (i32, std::collections::HashSet<String>)> = HashMap::new();
 
@@ -108,7 +781,7 @@ impl ProductivityAnalyzer {
         let sleep_start = prefs.map(|p| p.sleep_start_hour).unwrap_or(0);
         let sleep_end = prefs.map(|p| p.sleep_end_hour).unwrap_or(8);
         let no_work_before_sleep = prefs.map(|p| p.no_work_before_sleep_hours).unwrap_or(2);
-        let work_on_weekends = prefs.map(|p| p.work_on_weekends).unwrap_or(false);
+        let work_on_weekends = self.works_on_weekends();
 
         // Фильтруем часы с учетом предпочтений пользователя
         let mut filtered_efficiency: Vec<_> = hourly_efficiency
@@ -140,10 +813,10 @@ impl ProductivityAnalyzer {
                 .unwrap_or(std::cmp::Ordering::Equal)
         });
 
-        // Топ-8 часов
+        // Топ-N часов
         let top_hours: Vec<i32> = filtered_efficiency
             .iter()
-            .take(8)
+            .take(self.config.top_hours)
             .filter(|e| e.efficiency > 0.0)
             .map(|e| e.hour)
             .collect();
@@ -182,52 +855,45 @@ impl ProductivityAnalyzer {
     }
 
     fn analyze_breaks(&self, entries: &[TimesheetEntry]) -> BreakRecommendations {
-        let sessions = self.extract_sessions(entries);
-
-        if sessions.is_empty() {
-            return BreakRecommendations {
-                optimal_break_duration: 15,
-                break_frequency: 2.0,
-            };
-        }
-
-        let avg_session_duration =
-            sessions.iter().map(|s| s.duration).sum::<i32>() as f64 / sessions.len() as f64;
-
-        // Рекомендации на основе средней длительности сессии
-        let (break_duration, break_frequency) = if avg_session_duration > 120.0 {
-            (15, 2.0) // каждые 2 часа
-        } else if avg_session_duration > 60.0 {
-            (10, 1.5)
-        } else {
-            (5, 1.0)
-        };
-
-        BreakRecommendations {
-            optimal_break_duration: break_duration,
-            break_frequency,
-        }
+        breaks_for_sessions(&self.extract_sessions(entries))
     }
 
-    fn extract_sessions(&self, entries: &[TimesheetEntry]) -> Vec<Session> {
-        // Группировка по дням
-        let mut daily_entries: HashMap<String, Vec<&TimesheetEntry>> = HashMap::new();
+    /// Перерывы по сессиям каждой активности; при малом числе сессий -
+    /// общая рекомендация `global`
+    fn analyze_activity_breaks(
+        &self,
+        entries: &[TimesheetEntry],
+        global: &BreakRecommendations,
+    ) -> HashMap<String, BreakRecommendations> {
+        let mut by_activity: HashMap<&str, Vec<&TimesheetEntry>> = HashMap::new();
         for entry in entries {
-            if let Some(date_key) = entry.begin.split('T').next() {
-                daily_entries
-                    .entry(date_key.to_string())
-                    .or_default()
-                    .push(entry);
-            }
+            by_activity
+                .entry(entry.activity_name.as_str())
+                .or_default()
+                .push(entry);
         }
 
-        let mut sessions = Vec::new();
+        by_activity
+            .into_iter()
+            .map(|(activity, activity_entries)| {
+                let sessions = self.extract_sessions(activity_entries);
+                let breaks = if sessions.len() >= MIN_ACTIVITY_SESSIONS {
+                    breaks_for_sessions(&sessions)
+                } else {
+                    global.clone()
+                };
+                (activity.to_string(), breaks)
+            })
+            .collect()
+    }
 
-        for (_, day_entries) in daily_entries {
-            // Сортировка по времени начала
-            let mut sorted: Vec<_> = day_entries.iter().collect();
-            sorted.sort_by_key(|e| &e.begin);
+    fn extract_sessions<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a TimesheetEntry>,
+    ) -> Vec<Session<'a>> {
+        let mut sessions = Vec::new();
 
+        for sorted in group_by_day(entries).into_values() {
             // Объединение близких записей в сессии
             let mut current_session = Session {
                 start: sorted[0].begin.clone(),
@@ -236,26 +902,29 @@ impl ProductivityAnalyzer {
                     .clone()
                     .unwrap_or_else(|| sorted[0].begin.clone()),
                 duration: sorted[0].duration,
+                entries: vec![sorted[0]],
             };
 
             for entry in sorted.iter().skip(1) {
-                // Если перерыв < 30 минут, считаем продолжением сессии
+                // Короткий перерыв - продолжение сессии
                 if let (Ok(current_end), Ok(next_start)) = (
                     DateTime::parse_from_rfc3339(&current_session.end),
                     DateTime::parse_from_rfc3339(&entry.begin),
                 ) {
                     let gap = (next_start - current_end).num_minutes();
 
-                    if gap < 30 {
+                    if gap < self.config.session_gap_minutes {
                         current_session.end =
                             entry.end.clone().unwrap_or_else(|| entry.begin.clone());
                         current_session.duration += entry.duration;
+                        current_session.entries.push(entry);
                     } else {
                         sessions.push(current_session);
                         current_session = Session {
                             start: entry.begin.clone(),
                             end: entry.end.clone().unwrap_or_else(|| entry.begin.clone()),
                             duration: entry.duration,
+                            entries: vec![entry],
                         };
                     }
                 }
@@ -264,13 +933,150 @@ impl ProductivityAnalyzer {
             sessions.push(current_session);
         }
 
+        sessions.retain(|s| s.duration >= self.config.min_session_minutes);
         sessions
     }
 }
 
-struct Session {
+/// Записи по дням, в каждом дне - по времени начала
+fn group_by_day<'a>(
+    entries: impl IntoIterator<Item = &'a TimesheetEntry>,
+) -> HashMap<String, Vec<&'a TimesheetEntry>> {
+    let mut daily_entries: HashMap<String, Vec<&TimesheetEntry>> = HashMap::new();
+    for entry in entries {
+        if let Some(date_key) = entry.begin.split('T').next() {
+            daily_entries
+                .entry(date_key.to_string())
+                .or_default()
+                .push(entry);
+        }
+    }
+    for day_entries in daily_entries.values_mut() {
+        day_entries.sort_by_key(|e| &e.begin);
+    }
+    daily_entries
+}
+
+/// Минуты между концом `current` и началом `next`; `None`, если время не разобрать
+fn gap_minutes(current: &TimesheetEntry, next: &TimesheetEntry) -> Option<i64> {
+    let end = DateTime::parse_from_rfc3339(current.end.as_deref()?).ok()?;
+    let start = DateTime::parse_from_rfc3339(&next.begin).ok()?;
+    Some((start - end).num_minutes())
+}
+
+/// Перерывы по средней длительности сессии
+fn breaks_for_sessions(sessions: &[Session]) -> BreakRecommendations {
+    if sessions.is_empty() {
+        return BreakRecommendations {
+            optimal_break_duration: 15,
+            break_frequency: 2.0,
+        };
+    }
+
+    let avg_session_duration =
+        sessions.iter().map(|s| s.duration).sum::<i32>() as f64 / sessions.len() as f64;
+
+    // Рекомендации на основе средней длительности сессии
+    let (break_duration, break_frequency) = if avg_session_duration > 120.0 {
+        (15, 2.0) // каждые 2 часа
+    } else if avg_session_duration > 60.0 {
+        (10, 1.5)
+    } else {
+        (5, 1.0)
+    };
+
+    BreakRecommendations {
+        optimal_break_duration: break_duration,
+        break_frequency,
+    }
+}
+
+/// Коэффициент корреляции Пирсона; `None` меньше чем для трех точек или без разброса
+fn correlation(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    if xs.len() < 3 || xs.len() != ys.len() {
+        return None;
+    }
+    let n = xs.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    if var_x <= 0.0 || var_y <= 0.0 {
+        return None;
+    }
+    Some(cov / (var_x * var_y).sqrt())
+}
+
+fn nearest_centroid(features: &[f64; 4], centroids: &[[f64; 4]]) -> usize {
+    let distance =
+        |c: &[f64; 4]| -> f64 { features.iter().zip(c).map(|(a, b)| (a - b).powi(2)).sum() };
+    (0..centroids.len())
+        .min_by(|&a, &b| {
+            distance(&centroids[a])
+                .partial_cmp(&distance(&centroids[b]))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0)
+}
+
+/// Сессий на час учтенного времени
+fn fragmentation_index(sessions: usize, minutes: i32) -> f64 {
+    if minutes > 0 {
+        sessions as f64 / (minutes as f64 / 60.0)
+    } else {
+        0.0
+    }
+}
+
+struct Session<'a> {
     #[allow(dead_code)]
     start: String,
     end: String,
     duration: i32,
+    entries: Vec<&'a TimesheetEntry>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(activity: &str, description: Option<&str>) -> TimesheetEntry {
+        TimesheetEntry {
+            id: 1,
+            begin: "2024-03-04T10:00:00+00:00".to_string(),
+            end: None,
+            duration: 60,
+            project_id: Some(1),
+            project_name: "Project".to_string(),
+            activity_id: Some(1),
+            activity_name: activity.to_string(),
+            description: description.map(str::to_string),
+            tags: Vec::new(),
+            day_of_week: 0,
+            hour_of_day: 10,
+            week_of_year: 10,
+            month: 3,
+            year: 2024,
+            billable: None,
+        }
+    }
+
+    #[test]
+    fn meeting_detected_by_description_words() {
+        let analyzer = ProductivityAnalyzer::new();
+        assert!(analyzer.is_meeting(&entry("Development", Some("Созвоны с клиентом по API"))));
+        assert!(analyzer.is_meeting(&entry("Development", Some("Weekly SYNC with QA"))));
+        // Шаблон - начало слова, а не любая подстрока
+        assert!(!analyzer.is_meeting(&entry("Development", Some("recall bug in async worker"))));
+        assert!(!analyzer.is_meeting(&entry("Development", None)));
+        assert!(analyzer.is_meeting(&entry("Team call", None)));
+
+        let custom = ProductivityAnalyzer::new().meeting_patterns(vec!["Демо".to_string()]);
+        assert!(custom.is_meeting(&entry("Development", Some("демо для заказчика"))));
+        assert!(!custom.is_meeting(&entry("Development", Some("созвон"))));
+    }
 }
@@ -0,0 +1,201 @@
+//! Формулировки и пороги рекомендаций из файла конфигурации.
+//!
+//! Файл JSON задает пороги (`thresholds`) и шаблоны Handlebars по типам
+//! рекомендаций (`templates`):
+//!
+//! ```json
+//! {
+//!   "thresholds": { "meeting_load": 0.5 },
+//!   "templates": {
+//!     "meeting_load": {
+//!       "title": "Too many meetings",
+//!       "description": "Meetings took {{percent metrics.meeting_share}}% of the week",
+//!       "action_items": ["Batch meetings into one block"]
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! В шаблоне доступны поля рекомендации (`title`, `description`, `action_items`,
+//! `expected_impact`, `metrics`, ...) со стандартными формулировками и
+//! `project_name`. Помощники: `percent` (доля в проценты без дробной части) и
+//! `fixed` (число с `digits` знаками, по умолчанию 1)
+
+use std::collections::HashMap;
+
+use handlebars::{handlebars_helper, Handlebars};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+
+use crate::models::recommendations::RecommendationThresholds;
+use crate::types::RecommendationOutput;
+
+handlebars_helper!(percent: |value: f64| format!("{:.0}", value * 100.0));
+handlebars_helper!(fixed: |value: f64, {digits: u64 = 1}| format!("{:.*}", digits as usize, value));
+
+/// Переопределения порогов; отсутствующие остаются по умолчанию
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThresholdOverrides {
+    meeting_load: Option<f64>,
+    dormancy_risk: Option<f64>,
+    schedule_conflict_share: Option<f64>,
+    value_decline: Option<f64>,
+    drop_value_share: Option<f64>,
+    billable_share: Option<f64>,
+    low_margin_share: Option<f64>,
+}
+
+/// Шаблоны одного типа рекомендаций; отсутствующие поля не меняются
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TypeTemplates {
+    title: Option<String>,
+    description: Option<String>,
+    action_items: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateFile {
+    #[serde(default)]
+    thresholds: ThresholdOverrides,
+    #[serde(default)]
+    templates: HashMap<String, TypeTemplates>,
+}
+
+/// Скомпилированные шаблоны и пороги
+pub struct RecommendationTemplates {
+    registry: Handlebars<'static>,
+    thresholds: RecommendationThresholds,
+    /// Число шаблонов пунктов действий по типам
+    action_items: HashMap<String, usize>,
+}
+
+impl RecommendationTemplates {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read recommendation templates {}: {}", path, e))?;
+        Self::from_json(&json)
+    }
+
+    /// Разбор и компиляция файла; ошибки шаблонов и порогов вне (0, 1] - `Err`
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let file: TemplateFile = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid recommendation templates: {}", e))?;
+
+        let defaults = RecommendationThresholds::default();
+        let overrides = &file.thresholds;
+        let thresholds = RecommendationThresholds {
+            meeting_load: overrides.meeting_load.unwrap_or(defaults.meeting_load),
+            dormancy_risk: overrides.dormancy_risk.unwrap_or(defaults.dormancy_risk),
+            schedule_conflict_share: overrides
+                .schedule_conflict_share
+                .unwrap_or(defaults.schedule_conflict_share),
+            value_decline: overrides.value_decline.unwrap_or(defaults.value_decline),
+            drop_value_share: overrides
+                .drop_value_share
+                .unwrap_or(defaults.drop_value_share),
+            billable_share: overrides.billable_share.unwrap_or(defaults.billable_share),
+            low_margin_share: overrides
+                .low_margin_share
+                .unwrap_or(defaults.low_margin_share),
+        };
+        thresholds.validate()?;
+
+        let mut registry = Handlebars::new();
+        // Опечатка в имени показателя - ошибка отрисовки, а не пустая строка
+        registry.set_strict_mode(true);
+        registry.register_escape_fn(handlebars::no_escape);
+        registry.register_helper("percent", Box::new(percent));
+        registry.register_helper("fixed", Box::new(fixed));
+
+        let mut action_items = HashMap::new();
+        for (kind, templates) in &file.templates {
+            let mut register = |name: String, template: &str| {
+                registry
+                    .register_template_string(&name, template)
+                    .map_err(|e| format!("Invalid template {}: {}", name, e))
+            };
+            if let Some(title) = &templates.title {
+                register(format!("{}.title", kind), title)?;
+            }
+            if let Some(description) = &templates.description {
+                register(format!("{}.description", kind), description)?;
+            }
+            if let Some(items) = &templates.action_items {
+                for (index, item) in items.iter().enumerate() {
+                    register(format!("{}.action_items.{}", kind, index), item)?;
+                }
+                action_items.insert(kind.clone(), items.len());
+            }
+        }
+
+        Ok(Self {
+            registry,
+            thresholds,
+            action_items,
+        })
+    }
+
+    pub fn thresholds(&self) -> &RecommendationThresholds {
+        &self.thresholds
+    }
+
+    /// Переписывает формулировки рекомендации по шаблонам ее типа. При ошибке
+    /// отрисовки рекомендация остается со стандартными формулировками
+    pub fn apply(&self, recommendation: &mut RecommendationOutput, project_name: Option<String>) {
+        if let Err(e) = self.render(recommendation, project_name) {
+            tracing::warn!(
+                "Failed to render {} recommendation template: {}",
+                recommendation.r#type,
+                e
+            );
+        }
+    }
+
+    fn render(
+        &self,
+        recommendation: &mut RecommendationOutput,
+        project_name: Option<String>,
+    ) -> Result<(), String> {
+        let kind = recommendation.r#type.clone();
+        let mut context = serde_json::to_value(&*recommendation).map_err(|e| e.to_string())?;
+        if let (JsonValue::Object(fields), Some(name)) = (&mut context, project_name) {
+            fields.insert("project_name".to_string(), JsonValue::String(name));
+        }
+        let render = |name: String| -> Result<Option<String>, String> {
+            if !self.registry.has_template(&name) {
+                return Ok(None);
+            }
+            self.registry
+                .render(&name, &context)
+                .map(Some)
+                .map_err(|e| e.to_string())
+        };
+
+        // Сначала все поля, затем запись: рекомендация не остается наполовину переписанной
+        let title = render(format!("{}.title", kind))?;
+        let description = render(format!("{}.description", kind))?;
+        let action_items = match self.action_items.get(&kind) {
+            Some(&count) => Some(
+                (0..count)
+                    .map(|index| render(format!("{}.action_items.{}", kind, index)))
+                    .collect::<Result<Option<Vec<String>>, String>>()?
+                    .unwrap_or_default(),
+            ),
+            None => None,
+        };
+
+        if let Some(title) = title {
+            recommendation.title = title;
+        }
+        if let Some(description) = description {
+            recommendation.description = description;
+        }
+        if let Some(action_items) = action_items {
+            recommendation.action_items = action_items;
+        }
+        Ok(())
+    }
+}
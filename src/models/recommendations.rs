@@ -1,16 +1,164 @@
 //! Генератор рекомендаций по оптимизации
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-use crate::types::{MLInputData, Project, RecommendationOutput};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::holidays;
+use crate::models::forecasting::next_iso_week;
+use crate::models::productivity::BILLABLE_RECENT_WEEKS;
+use crate::models::recommendation_templates::RecommendationTemplates;
+use crate::models::survival::{project_lifetime, DEFAULT_DORMANCY_HORIZON};
+use crate::models::unit_economics::{
+    profitability, ValueMatrix, VALUE_DECLINE_THRESHOLD, VALUE_TREND_WEEKS,
+};
+use crate::models::ProductivityAnalyzer;
+use crate::types::{
+    ActionPlan, ActionStep, ExpectedImpact, MLInputData, Project, RecommendationAction,
+    RecommendationFeedback, RecommendationOutput, WeeklyScheduleConflicts,
+};
+
+/// Доля встреч за последнюю неделю, после которой нужна рекомендация
+const MEETING_LOAD_THRESHOLD: f64 = 0.4;
+
+/// Доля оплачиваемой работы последних недель, ниже которой нужна рекомендация
+const BILLABLE_SHARE_THRESHOLD: f64 = 0.6;
+
+/// Падение доли оплачиваемой работы к средней, при котором рекомендация важная
+const BILLABLE_SHARE_DROP: f64 = 0.1;
+
+/// Сколько последних недель записей проверяется на нарушения предпочтений
+const SCHEDULE_CONFLICT_WEEKS: usize = 4;
+
+/// Доля работы вне заявленного времени, после которой нужна рекомендация
+const SCHEDULE_CONFLICT_SHARE: f64 = 0.05;
+
+/// Часы недели одного вида нарушений
+type ConflictHours = fn(&WeeklyScheduleConflicts) -> f64;
+
+/// Вероятность засыпания проекта в ближайшие недели, после которой нужна рекомендация
+const DORMANCY_RISK_THRESHOLD: f64 = 0.5;
+
+/// Доля недельных часов, которую рекомендации предлагают перенести между проектами
+const SHIFT_SHARE: f64 = 0.2;
+
+/// Рекомендации, не выдававшиеся столько недель, забываются трекером
+const TRACKING_RETENTION_WEEKS: i64 = 12;
+
+/// На сколько недель откладывается рекомендация без `snooze_weeks`
+pub const DEFAULT_SNOOZE_WEEKS: u32 = 2;
+
+/// Доля медианного дохода за час других проектов, ниже которой от проекта
+/// рекомендуется отказаться, а не пересматривать ставку
+const DROP_VALUE_SHARE: f64 = 0.5;
+
+/// Доля маржи в доходе проекта, ниже которой при падающей марже нужна рекомендация
+const LOW_MARGIN_SHARE: f64 = 0.2;
+
+/// Доля прогноза рабочих часов, которую план действий отводит на шаги
+const ACTION_PLAN_CAPACITY_SHARE: f64 = 0.1;
+
+/// Наибольшее число шагов плана действий
+const MAX_ACTION_STEPS: usize = 5;
+
+/// Оценка времени на выполнение рекомендации по типу, часов
+const ACTION_TIME_COST: &[(&str, f64)] = &[
+    ("time_allocation", 0.5),
+    ("project_priority", 0.5),
+    ("schedule_optimization", 0.25),
+    ("meeting_load", 1.0),
+    ("billable_share", 1.0),
+    ("schedule_conflict", 0.25),
+    ("idle_project", 2.0),
+    ("renegotiate_project", 1.5),
+    ("drop_project", 2.0),
+    ("unprofitable_project", 1.5),
+    ("low_margin_project", 1.0),
+];
+
+/// Время на рекомендацию неизвестного типа, часов
+const DEFAULT_ACTION_TIME_COST: f64 = 0.5;
+
+/// Наибольшая уверенность рекомендации после поправки на отзывы
+const MAX_CONFIDENCE: f64 = 0.95;
+
+/// Пороги, после которых выдаются рекомендации (переопределяются файлом шаблонов)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecommendationThresholds {
+    /// Доля встреч за последнюю неделю
+    pub meeting_load: f64,
+    /// Вероятность засыпания проекта в ближайшие недели
+    pub dormancy_risk: f64,
+    /// Доля работы вне заявленного в предпочтениях времени
+    pub schedule_conflict_share: f64,
+    /// Падение дохода за час проекта на окне тренда
+    pub value_decline: f64,
+    /// Доля медианного дохода за час других проектов для отказа от проекта
+    pub drop_value_share: f64,
+    /// Доля оплачиваемой работы последних недель
+    pub billable_share: f64,
+    /// Доля маржи в доходе проекта с падающей маржой
+    pub low_margin_share: f64,
+}
+
+impl RecommendationThresholds {
+    /// Все пороги - доли в (0, 1]
+    pub fn validate(&self) -> Result<(), String> {
+        let thresholds = [
+            ("meeting_load", self.meeting_load),
+            ("dormancy_risk", self.dormancy_risk),
+            ("schedule_conflict_share", self.schedule_conflict_share),
+            ("value_decline", self.value_decline),
+            ("drop_value_share", self.drop_value_share),
+            ("billable_share", self.billable_share),
+            ("low_margin_share", self.low_margin_share),
+        ];
+        for (name, value) in thresholds {
+            if !(value > 0.0 && value <= 1.0) {
+                return Err(format!("{} must be in (0, 1], got {}", name, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for RecommendationThresholds {
+    fn default() -> Self {
+        Self {
+            meeting_load: MEETING_LOAD_THRESHOLD,
+            dormancy_risk: DORMANCY_RISK_THRESHOLD,
+            schedule_conflict_share: SCHEDULE_CONFLICT_SHARE,
+            value_decline: VALUE_DECLINE_THRESHOLD,
+            drop_value_share: DROP_VALUE_SHARE,
+            billable_share: BILLABLE_SHARE_THRESHOLD,
+            low_margin_share: LOW_MARGIN_SHARE,
+        }
+    }
+}
 
 pub struct RecommendationEngine {
     // KMeans не используется, используем простую эвристику
+    thresholds: RecommendationThresholds,
+    /// Формулировки развертывания вместо стандартных
+    templates: Option<RecommendationTemplates>,
 }
 
 impl RecommendationEngine {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            thresholds: RecommendationThresholds::default(),
+            templates: None,
+        }
+    }
+
+    /// Пороги и формулировки из файла шаблонов
+    pub fn with_templates(templates: RecommendationTemplates) -> Self {
+        Self {
+            thresholds: templates.thresholds().clone(),
+            templates: Some(templates),
+        }
     }
 
     pub fn generate_recommendations(&mut self, data: &MLInputData) -> Vec<RecommendationOutput> {
@@ -25,24 +173,64 @@ impl RecommendationEngine {
         // 3. Анализ распределения времени
         let time_distribution = self.analyze_time_distribution(&data.weeks);
 
-        // 4. Генерация рекомендаций
-        recommendations.extend(self.recommend_time_allocation(
-            &project_efficiency,
-            &time_distribution,
-            data,
-        ));
-        recommendations.extend(self.recommend_project_priority(&project_efficiency, data));
-        recommendations.extend(self.recommend_schedule_optimization(data));
+        // 4. Генерация рекомендаций (виды можно отключить в settings.features)
+        let features = &data.settings;
+        if features.feature_enabled("time_allocation_recommendations", true) {
+            recommendations.extend(self.recommend_time_allocation(
+                &project_efficiency,
+                &time_distribution,
+                data,
+            ));
+        }
+        if features.feature_enabled("project_priority_recommendations", true) {
+            recommendations.extend(self.recommend_project_priority(
+                &project_efficiency,
+                &time_distribution,
+                data,
+            ));
+        }
+        if features.feature_enabled("schedule_recommendations", true) {
+            recommendations.extend(self.recommend_schedule_optimization(data));
+        }
+        if features.feature_enabled("meeting_recommendations", true) {
+            recommendations.extend(self.recommend_meeting_load(data));
+        }
+        if features.feature_enabled("billable_recommendations", true) {
+            recommendations.extend(self.recommend_billable_share(data));
+        }
+        if features.feature_enabled("schedule_conflict_recommendations", true) {
+            recommendations.extend(self.recommend_schedule_conflicts(data));
+        }
+        if features.feature_enabled("idle_project_recommendations", true) {
+            recommendations.extend(self.recommend_idle_projects(data));
+        }
+        if features.feature_enabled("project_value_recommendations", true) {
+            recommendations.extend(self.recommend_project_value(data));
+        }
+        if features.feature_enabled("profitability_recommendations", true) {
+            recommendations.extend(self.recommend_profitability(data));
+        }
 
+        // id - по стандартному заголовку, чтобы не меняться вместе с шаблонами
+        for recommendation in &mut recommendations {
+            recommendation.id = recommendation_id(recommendation);
+            if let Some(templates) = &self.templates {
+                let project_name = recommendation
+                    .project_id
+                    .map(|id| self.get_project_name(data, id));
+                templates.apply(recommendation, project_name);
+            }
+        }
         recommendations
     }
 
+    /// Доход за час по проектам: ставка проекта или общая ставка
     fn calculate_project_efficiency(&self, data: &MLInputData) -> HashMap<i32, f64> {
         let mut efficiency = HashMap::new();
-        let rate_per_hour = data.settings.rate_per_minute * 60.0;
 
         for project in &data.projects {
             if project.total_hours > 0.0 {
+                let rate_per_hour = data.settings.project_rate(project.id) * 60.0;
                 let total_amount = project.total_hours * rate_per_hour;
                 efficiency.insert(project.id, total_amount / project.total_hours);
             } else {
@@ -150,6 +338,12 @@ impl RecommendationEngine {
 
                 if current_hours < *goal_hours * 0.9 {
                     recommendations.push(RecommendationOutput {
+                        id: String::new(),
+                        project_id: Some(*project_id),
+                        period: None,
+                        status: None,
+                        first_seen: None,
+                        last_seen: None,
                         r#type: "time_allocation".to_string(),
                         priority: "high".to_string(),
                         title: format!("Увеличьте время на проект '{}'", project_name),
@@ -161,7 +355,19 @@ impl RecommendationEngine {
                             format!("Распределите {:.1} часов равномерно по рабочим дням", goal_hours),
                             "Используйте оптимальные часы работы для этого проекта".to_string(),
                         ],
-                        expected_impact: format!("Достижение цели по проекту '{}'", project_name),
+                        expected_impact: ExpectedImpact {
+                            metric: "project_hours".to_string(),
+                            estimated_delta: Some(goal_hours - current_hours),
+                            unit: "hours_per_week".to_string(),
+                            assumptions: vec![
+                                format!(
+                                    "Текущее время - среднее за {} нед. истории",
+                                    data.weeks.len()
+                                ),
+                                "Цель - из предпочтений пользователя".to_string(),
+                            ],
+                        },
+                        metrics: recommendation_metrics(&[("current_hours", current_hours), ("goal_hours", *goal_hours)]),
                         confidence: 0.8,
                     });
                 }
@@ -179,11 +385,33 @@ impl RecommendationEngine {
             if efficiency_val > 0.0 {
                 let current_hours = distribution.get(&top_project_id).copied().unwrap_or(0.0);
 
-                if current_hours > 0.0 {
-                    let recommended_hours = current_hours * 1.2;
+                // Средняя ставка остальных проектов, взвешенная по их часам
+                let (other_hours, other_amount) = distribution
+                    .iter()
+                    .filter(|(id, _)| **id != top_project_id)
+                    .filter_map(|(id, hours)| efficiency.get(id).map(|rate| (*hours, hours * rate)))
+                    .fold((0.0, 0.0), |acc, (hours, amount)| {
+                        (acc.0 + hours, acc.1 + amount)
+                    });
+                let rate_gap = if other_hours > 0.0 {
+                    efficiency_val - other_amount / other_hours
+                } else {
+                    0.0
+                };
+
+                // Перенос времени выгоден, только если ставка проекта выше остальных
+                if current_hours > 0.0 && rate_gap > 0.0 {
+                    let shifted_hours = (current_hours * SHIFT_SHARE).min(other_hours);
+                    let recommended_hours = current_hours + shifted_hours;
                     let project_name = self.get_project_name(data, top_project_id);
 
                     recommendations.push(RecommendationOutput {
+                        id: String::new(),
+                        project_id: Some(top_project_id),
+                        period: None,
+                        status: None,
+                        first_seen: None,
+                        last_seen: None,
                         r#type: "time_allocation".to_string(),
                         priority: "high".to_string(),
                         title: "Увеличьте время на высокоэффективные проекты".to_string(),
@@ -199,7 +427,27 @@ impl RecommendationEngine {
                             "Перераспределите 15-20% времени с менее эффективных проектов"
                                 .to_string(),
                         ],
-                        expected_impact: "Потенциальное увеличение дохода на 10-15%".to_string(),
+                        expected_impact: ExpectedImpact {
+                            metric: "revenue".to_string(),
+                            estimated_delta: Some(shifted_hours * rate_gap),
+                            unit: "amount_per_week".to_string(),
+                            assumptions: vec![
+                                format!(
+                                    "{:.1} ч/нед. переносятся с других проектов (их средняя ставка \
+                                     ниже на {:.2} за час)",
+                                    shifted_hours, rate_gap
+                                ),
+                                "Объем работы на проекте растет вместе с выделенным временем"
+                                    .to_string(),
+                            ],
+                        },
+                        metrics: recommendation_metrics(&[
+                            ("current_hours", current_hours),
+                            ("recommended_hours", recommended_hours),
+                            ("rate", efficiency_val),
+                            ("rate_gap", rate_gap),
+                            ("shifted_hours", shifted_hours),
+                        ]),
                         confidence: 0.75,
                     });
                 }
@@ -212,6 +460,7 @@ impl RecommendationEngine {
     fn recommend_project_priority(
         &self,
         efficiency: &HashMap<i32, f64>,
+        distribution: &HashMap<i32, f64>,
         data: &MLInputData,
     ) -> Vec<RecommendationOutput> {
         let mut recommendations = Vec::new();
@@ -229,10 +478,23 @@ impl RecommendationEngine {
             .filter(|(_, &eff)| eff > 0.0)
             .collect();
 
-        if let Some((&project_id, _)) = low_efficiency.first() {
+        let best_rate = sorted.last().map(|(_, &rate)| rate).unwrap_or(0.0);
+        if let Some((&project_id, &rate)) = low_efficiency.first() {
+            let weekly_hours = distribution.get(&project_id).copied().unwrap_or(0.0);
+            // При равных ставках перераспределение не меняет доход
+            if best_rate <= rate || weekly_hours <= 0.0 {
+                return recommendations;
+            }
+            let shifted_hours = weekly_hours * SHIFT_SHARE;
             let project_name = self.get_project_name(data, project_id);
 
             recommendations.push(RecommendationOutput {
+                id: String::new(),
+                project_id: Some(project_id),
+                period: None,
+                status: None,
+                first_seen: None,
+                last_seen: None,
                 r#type: "project_priority".to_string(),
                 priority: "medium".to_string(),
                 title: "Пересмотрите приоритеты проектов".to_string(),
@@ -241,7 +503,25 @@ impl RecommendationEngine {
                     format!("Проанализируйте проект '{}'", project_name),
                     "Рассмотрите возможность перераспределения времени".to_string(),
                 ],
-                expected_impact: "Оптимизация использования времени".to_string(),
+                expected_impact: ExpectedImpact {
+                    metric: "revenue".to_string(),
+                    estimated_delta: Some(shifted_hours * (best_rate - rate)),
+                    unit: "amount_per_week".to_string(),
+                    assumptions: vec![
+                        format!(
+                            "{:.1} ч/нед. переносятся на проект с самой высокой ставкой \
+                             ({:.2} против {:.2} за час)",
+                            shifted_hours, best_rate, rate
+                        ),
+                        format!("Среднее время на проекте - {:.1} ч/нед.", weekly_hours),
+                    ],
+                },
+                metrics: recommendation_metrics(&[
+                    ("weekly_hours", weekly_hours),
+                    ("rate", rate),
+                    ("best_rate", best_rate),
+                    ("shifted_hours", shifted_hours),
+                ]),
                 confidence: 0.6,
             });
         }
@@ -267,8 +547,15 @@ impl RecommendationEngine {
             sorted.sort_by(|a, b| b.1.cmp(a.1));
             let top_hours: Vec<String> =
                 sorted.iter().take(3).map(|(&h, _)| h.to_string()).collect();
+            let peak_minutes: i32 = sorted.iter().take(3).map(|(_, &m)| m).sum();
 
             recommendations.push(RecommendationOutput {
+                id: String::new(),
+                project_id: None,
+                period: None,
+                status: None,
+                first_seen: None,
+                last_seen: None,
                 r#type: "schedule_optimization".to_string(),
                 priority: "medium".to_string(),
                 title: "Оптимизируйте расписание работы".to_string(),
@@ -277,14 +564,765 @@ impl RecommendationEngine {
                     format!("Планируйте важные задачи на {}:00", top_hours[0]),
                     "Используйте менее продуктивные часы для рутинных задач".to_string(),
                 ],
-                expected_impact: "Улучшение продуктивности на 10-15%".to_string(),
+                expected_impact: ExpectedImpact {
+                    metric: "peak_hours".to_string(),
+                    estimated_delta: (!data.weeks.is_empty())
+                        .then(|| peak_minutes as f64 / 60.0 / data.weeks.len() as f64),
+                    unit: "hours_per_week".to_string(),
+                    assumptions: vec![
+                        "Пиковые часы - три часа дня с наибольшим объемом работы".to_string(),
+                        "Оценка - часы в пиковое время в среднем за неделю, которые можно \
+                         отдать важным задачам"
+                            .to_string(),
+                    ],
+                },
+                metrics: recommendation_metrics(&[
+                    ("top_hour", *sorted[0].0 as f64),
+                    ("peak_minutes", peak_minutes as f64),
+                ]),
                 confidence: 0.7,
             });
         }
 
+        let analyzer = productivity_analyzer(data);
+
+        // Сложная работа - проект с самыми длинными записями - в часы высокой энергии
+        if let (Some(profile), Some(project_name)) = (
+            analyzer.energy_profile(&data.timesheets),
+            self.most_demanding_project(data),
+        ) {
+            let slots: Vec<String> = profile
+                .days
+                .iter()
+                .filter(|d| !d.high.is_empty())
+                .map(|d| {
+                    let hours: Vec<String> = d.high.iter().map(|h| format!("{}:00", h)).collect();
+                    format!("{}: {}", weekday_name(d.day_of_week), hours.join(", "))
+                })
+                .collect();
+            if !slots.is_empty() {
+                let high_energy_days = slots.len();
+                recommendations.push(RecommendationOutput {
+                    id: String::new(),
+                    project_id: None,
+                    period: None,
+                    status: None,
+                    first_seen: None,
+                    last_seen: None,
+                    r#type: "schedule_optimization".to_string(),
+                    priority: "medium".to_string(),
+                    title: "Планируйте сложную работу на часы высокой энергии".to_string(),
+                    description: format!(
+                        "Работу над '{}' лучше ставить на часы высокой энергии",
+                        project_name
+                    ),
+                    action_items: slots,
+                    expected_impact: ExpectedImpact {
+                        metric: "focus_hours".to_string(),
+                        estimated_delta: None,
+                        unit: "hours_per_week".to_string(),
+                        assumptions: vec![
+                            "Сложная работа - проект с самыми длинными записями".to_string(),
+                            "Эффект зависит от того, сколько сложной работы сейчас приходится \
+                             на часы низкой энергии"
+                                .to_string(),
+                        ],
+                    },
+                    metrics: recommendation_metrics(&[(
+                        "high_energy_days",
+                        high_energy_days as f64,
+                    )]),
+                    confidence: 0.6,
+                });
+            }
+        }
+
+        // Дни сосредоточенной работы за последнюю неделю
+        let min_focus_days = data
+            .settings
+            .user_preferences
+            .as_ref()
+            .map(|p| p.min_focus_days_per_week)
+            .unwrap_or(2);
+        if let Some(day_types) = analyzer.classify_days(&data.timesheets) {
+            let focus_days = day_types.weekly_mix.last().map(|w| w.focus).unwrap_or(0);
+            if focus_days < min_focus_days {
+                recommendations.push(RecommendationOutput {
+                    id: String::new(),
+                    project_id: None,
+                    period: last_week_period(data),
+                    status: None,
+                    first_seen: None,
+                    last_seen: None,
+                    r#type: "schedule_optimization".to_string(),
+                    priority: if focus_days == 0 {
+                        "high".to_string()
+                    } else {
+                        "medium".to_string()
+                    },
+                    title: "Защитите дни для сосредоточенной работы".to_string(),
+                    description: format!(
+                        "На прошлой неделе дней сосредоточенной работы: {} из желаемых {}",
+                        focus_days, min_focus_days
+                    ),
+                    action_items: vec![
+                        format!(
+                            "Отведите не меньше {} дн. в неделю без встреч и мелких задач",
+                            min_focus_days
+                        ),
+                        "Переносите встречи и административные задачи в другие дни".to_string(),
+                    ],
+                    expected_impact: ExpectedImpact {
+                        metric: "focus_days".to_string(),
+                        estimated_delta: Some((min_focus_days - focus_days) as f64),
+                        unit: "days_per_week".to_string(),
+                        assumptions: vec![format!(
+                            "Желаемое число дней сосредоточенной работы - {} в неделю",
+                            min_focus_days
+                        )],
+                    },
+                    metrics: recommendation_metrics(&[
+                        ("focus_days", focus_days as f64),
+                        ("min_focus_days", min_focus_days as f64),
+                    ]),
+                    confidence: if day_types.weekly_mix.len() >= 4 {
+                        0.7
+                    } else {
+                        0.5
+                    },
+                });
+            }
+        }
+
         recommendations
     }
 
+    fn recommend_meeting_load(&self, data: &MLInputData) -> Vec<RecommendationOutput> {
+        let analyzer = productivity_analyzer(data);
+        let Some(load) = analyzer.meeting_load(&data.timesheets) else {
+            return Vec::new();
+        };
+        let Some(last) = load.weeks.last() else {
+            return Vec::new();
+        };
+        let threshold = self.thresholds.meeting_load;
+        if last.meeting_share < threshold {
+            return Vec::new();
+        }
+
+        let mut description = format!(
+            "Встречи заняли {:.0}% времени за неделю ({:.1} ч из {:.1} ч), в среднем - {:.0}%",
+            last.meeting_share * 100.0,
+            last.meeting_hours,
+            last.total_hours,
+            load.average_share * 100.0
+        );
+        if let Some(correlation) = load.deep_work_correlation.filter(|c| *c < -0.3) {
+            description.push_str(&format!(
+                ". В неделях с большим числом встреч меньше глубокой работы (корреляция {:.2})",
+                correlation
+            ));
+        }
+
+        let mut metrics = recommendation_metrics(&[
+            ("meeting_share", last.meeting_share),
+            ("meeting_hours", last.meeting_hours),
+            ("total_hours", last.total_hours),
+            ("average_share", load.average_share),
+            ("threshold", threshold),
+        ]);
+        if let Some(correlation) = load.deep_work_correlation {
+            metrics.insert("deep_work_correlation".to_string(), correlation);
+        }
+
+        vec![RecommendationOutput {
+            id: String::new(),
+            project_id: None,
+            period: last_week_period(data),
+            status: None,
+            first_seen: None,
+            last_seen: None,
+            r#type: "meeting_load".to_string(),
+            priority: if last.meeting_share >= 0.6 {
+                "high".to_string()
+            } else {
+                "medium".to_string()
+            },
+            title: "Сократите нагрузку встречами".to_string(),
+            description,
+            action_items: vec![
+                "Объедините встречи в один-два блока в день".to_string(),
+                "Откажитесь от встреч без повестки или замените их перепиской".to_string(),
+                "Зарезервируйте в календаре время для работы без встреч".to_string(),
+            ],
+            // Часы сверх порога, которые можно вернуть сосредоточенной работе
+            expected_impact: ExpectedImpact {
+                metric: "focus_hours".to_string(),
+                estimated_delta: Some(last.meeting_hours - last.total_hours * threshold),
+                unit: "hours_per_week".to_string(),
+                assumptions: vec![format!(
+                    "Встречи сокращаются до {:.0}% рабочего времени",
+                    threshold * 100.0
+                )],
+            },
+            metrics,
+            confidence: if load.weeks.len() >= 4 { 0.75 } else { 0.6 },
+        }]
+    }
+
+    /// Неоплачиваемая работа вытесняет оплачиваемую: доля оплачиваемых часов
+    /// последних `BILLABLE_RECENT_WEEKS` недель ниже порога
+    fn recommend_billable_share(&self, data: &MLInputData) -> Vec<RecommendationOutput> {
+        let analyzer = productivity_analyzer(data);
+        let Some(share) = analyzer.billable_share(&data.timesheets) else {
+            return Vec::new();
+        };
+        let threshold = self.thresholds.billable_share;
+        if share.recent_share >= threshold {
+            return Vec::new();
+        }
+
+        let recent = &share.weeks[share.weeks.len().saturating_sub(BILLABLE_RECENT_WEEKS)..];
+        let weeks = recent.len() as f64;
+        let billable_hours = recent.iter().map(|w| w.billable_hours).sum::<f64>() / weeks;
+        let non_billable_hours = recent.iter().map(|w| w.non_billable_hours).sum::<f64>() / weeks;
+        // Часы в неделю, которые нужно вернуть оплачиваемой работе до порога
+        let shift_hours = (billable_hours + non_billable_hours) * threshold - billable_hours;
+
+        // Средняя ставка оплачиваемых записей, за час
+        let (minutes, amount) = data
+            .timesheets
+            .iter()
+            .filter(|e| analyzer.is_billable(e) == Some(true))
+            .fold((0.0, 0.0), |(minutes, amount), e| {
+                let rate = match e.project_id {
+                    Some(id) => data.settings.project_rate(id),
+                    None => data.settings.rate_per_minute,
+                };
+                (
+                    minutes + e.duration as f64,
+                    amount + e.duration as f64 * rate,
+                )
+            });
+        let rate_per_hour = if minutes > 0.0 {
+            amount / minutes * 60.0
+        } else {
+            data.settings.rate_per_minute * 60.0
+        };
+
+        let declining = share.average_share - share.recent_share >= BILLABLE_SHARE_DROP;
+        let mut description = format!(
+            "Оплачиваемая работа заняла {:.0}% времени за последние {} нед. ({:.1} ч из {:.1} ч в неделю), \
+             в среднем - {:.0}%",
+            share.recent_share * 100.0,
+            recent.len(),
+            billable_hours,
+            billable_hours + non_billable_hours,
+            share.average_share * 100.0
+        );
+        if declining {
+            description.push_str(". Внутренние задачи вытесняют работу для клиентов");
+        }
+
+        vec![RecommendationOutput {
+            id: String::new(),
+            project_id: None,
+            period: last_week_period(data),
+            status: None,
+            first_seen: None,
+            last_seen: None,
+            r#type: "billable_share".to_string(),
+            priority: if declining {
+                "high".to_string()
+            } else {
+                "medium".to_string()
+            },
+            title: "Увеличьте долю оплачиваемой работы".to_string(),
+            description,
+            action_items: vec![
+                "Ограничьте время на внутренние задачи и администрирование".to_string(),
+                "Перенесите внутреннюю работу на часы низкой энергии".to_string(),
+                "Проверьте, не осталась ли работа для клиентов неоплаченной".to_string(),
+            ],
+            expected_impact: ExpectedImpact {
+                metric: "revenue".to_string(),
+                estimated_delta: Some(shift_hours * rate_per_hour),
+                unit: "amount_per_week".to_string(),
+                assumptions: vec![format!(
+                    "Доля оплачиваемой работы растет до {:.0}% при тех же часах",
+                    threshold * 100.0
+                )],
+            },
+            metrics: recommendation_metrics(&[
+                ("billable_share", share.recent_share),
+                ("average_share", share.average_share),
+                ("billable_hours", billable_hours),
+                ("non_billable_hours", non_billable_hours),
+                ("shift_hours", shift_hours),
+                ("threshold", threshold),
+            ]),
+            confidence: if recent.len() >= BILLABLE_RECENT_WEEKS {
+                0.75
+            } else {
+                0.6
+            },
+        }]
+    }
+
+    /// Работа во время сна, перед сном и в нерабочие выходные за последние
+    /// `SCHEDULE_CONFLICT_WEEKS` недель; часы по неделям - в пунктах действий
+    fn recommend_schedule_conflicts(&self, data: &MLInputData) -> Vec<RecommendationOutput> {
+        let conflicts = productivity_analyzer(data).schedule_conflicts(&data.timesheets);
+        let recent = &conflicts[conflicts.len().saturating_sub(SCHEDULE_CONFLICT_WEEKS)..];
+        let total_hours: f64 = recent.iter().map(|w| w.total_hours).sum();
+        let Some(last) = recent.last().filter(|_| total_hours > 0.0) else {
+            return Vec::new();
+        };
+        let period = format!("{}-W{:02}", last.year, last.week);
+
+        let kinds: [(&str, &str, &str, ConflictHours); 3] = [
+            (
+                "sleep",
+                "Не работайте во время сна",
+                "пришлось на заявленное время сна",
+                |w| w.sleep_hours,
+            ),
+            (
+                "buffer",
+                "Заканчивайте работу заранее до сна",
+                "пришлось на часы перед сном, свободные от работы",
+                |w| w.buffer_hours,
+            ),
+            (
+                "weekend",
+                "Освободите выходные от работы",
+                "пришлось на выходные",
+                |w| w.weekend_hours,
+            ),
+        ];
+
+        kinds
+            .iter()
+            .filter_map(|&(kind, title, place, hours_of)| {
+                let hours: f64 = recent.iter().map(hours_of).sum();
+                let share = hours / total_hours;
+                if share < self.thresholds.schedule_conflict_share {
+                    return None;
+                }
+                let kind_hours = format!("{}_hours", kind);
+
+                let mut action_items: Vec<String> = recent
+                    .iter()
+                    .filter(|w| hours_of(w) > 0.0)
+                    .map(|w| {
+                        format!(
+                            "{}-W{:02}: {:.1} ч из {:.1} ч ({:.0}%)",
+                            w.year,
+                            w.week,
+                            hours_of(w),
+                            w.total_hours,
+                            if w.total_hours > 0.0 {
+                                hours_of(w) / w.total_hours * 100.0
+                            } else {
+                                0.0
+                            }
+                        )
+                    })
+                    .collect();
+                action_items.push(
+                    "Перенесите эту работу в оптимальные часы или обновите предпочтения, \
+                     если режим изменился"
+                        .to_string(),
+                );
+
+                Some(RecommendationOutput {
+                    id: String::new(),
+                    project_id: None,
+                    period: Some(period.clone()),
+                    status: None,
+                    first_seen: None,
+                    last_seen: None,
+                    r#type: "schedule_conflict".to_string(),
+                    priority: if share >= 0.15 {
+                        "high".to_string()
+                    } else {
+                        "medium".to_string()
+                    },
+                    title: title.to_string(),
+                    description: format!(
+                        "{:.0}% работы за последние {} нед. {} ({:.1} ч из {:.1} ч)",
+                        share * 100.0,
+                        recent.len(),
+                        place,
+                        hours,
+                        total_hours
+                    ),
+                    action_items,
+                    expected_impact: ExpectedImpact {
+                        metric: "conflict_hours".to_string(),
+                        estimated_delta: Some(-hours / recent.len() as f64),
+                        unit: "hours_per_week".to_string(),
+                        assumptions: vec![format!(
+                            "Нарушение ({}) полностью переносится в рабочее время",
+                            kind
+                        )],
+                    },
+                    metrics: recommendation_metrics(&[
+                        (kind_hours.as_str(), hours),
+                        ("share", share),
+                        ("total_hours", total_hours),
+                        ("weeks", recent.len() as f64),
+                    ]),
+                    confidence: if recent.len() >= 2 { 0.8 } else { 0.6 },
+                })
+            })
+            .collect()
+    }
+
+    /// Проекты, у которых падает реализованный доход за час: пересмотреть ставку
+    /// или, если доход намного ниже других проектов, отказаться от проекта
+    fn recommend_project_value(&self, data: &MLInputData) -> Vec<RecommendationOutput> {
+        let matrix = ValueMatrix::build(&data.weeks, &data.settings);
+
+        matrix
+            .trends()
+            .into_iter()
+            .filter(|trend| trend.change <= -self.thresholds.value_decline)
+            .map(|trend| {
+                let project_name = self.get_project_name(data, trend.project_id);
+                // Медиана последнего дохода за час остальных проектов
+                let mut others: Vec<f64> = matrix
+                    .project_ids
+                    .iter()
+                    .filter(|&&id| id != trend.project_id)
+                    .filter_map(|&id| matrix.latest_value(id))
+                    .collect();
+                others.sort_by(|a, b| a.total_cmp(b));
+                let others_median = others.get(others.len() / 2).copied();
+                let drop = others_median
+                    .is_some_and(|median| {
+                        trend.current_value < median * self.thresholds.drop_value_share
+                    });
+                let mut metrics = recommendation_metrics(&[
+                    ("start_value", trend.start_value),
+                    ("current_value", trend.current_value),
+                    ("slope", trend.slope),
+                    ("change", trend.change),
+                    ("weekly_hours", trend.weekly_hours),
+                    ("weeks", trend.weeks as f64),
+                ]);
+                if let Some(median) = others_median {
+                    metrics.insert("others_median".to_string(), median);
+                }
+
+                let description = format!(
+                    "Доход за час по проекту '{}' снизился на {:.0}% за {} нед. работы: с {:.2} до {:.2} \
+                     ({:+.2} в неделю){}",
+                    project_name,
+                    -trend.change * 100.0,
+                    trend.weeks,
+                    trend.start_value,
+                    trend.current_value,
+                    trend.slope,
+                    others_median
+                        .map(|median| format!(". Медиана других проектов - {:.2}", median))
+                        .unwrap_or_default()
+                );
+                let (r#type, title, action_items, expected_impact) = match others_median {
+                    Some(median) if drop => (
+                        "drop_project",
+                        format!("Рассмотрите отказ от проекта '{}'", project_name),
+                        vec![
+                            "Оцените, окупает ли проект свое время с учетом неоплаченной работы"
+                                .to_string(),
+                            "Завершите текущие обязательства и не берите новые задачи по проекту"
+                                .to_string(),
+                            "Займите освободившееся время более доходными проектами".to_string(),
+                        ],
+                        ExpectedImpact {
+                            metric: "revenue".to_string(),
+                            estimated_delta: Some(
+                                trend.weekly_hours * (median - trend.current_value),
+                            ),
+                            unit: "amount_per_week".to_string(),
+                            assumptions: vec![
+                                format!(
+                                    "{:.1} ч/нед. проекта заняты работой с медианным доходом \
+                                     за час других проектов",
+                                    trend.weekly_hours
+                                ),
+                                "Доход недели делится между проектами по часам × ставке"
+                                    .to_string(),
+                            ],
+                        },
+                    ),
+                    _ => (
+                        "renegotiate_project",
+                        format!("Пересмотрите условия проекта '{}'", project_name),
+                        vec![
+                            "Сверьте выставленные суммы с отработанным временем".to_string(),
+                            "Обсудите с клиентом повышение ставки или оплату доп. работ"
+                                .to_string(),
+                            "Ограничьте работу, которая не оплачивается".to_string(),
+                        ],
+                        ExpectedImpact {
+                            metric: "revenue".to_string(),
+                            estimated_delta: Some(
+                                trend.weekly_hours * (trend.start_value - trend.current_value),
+                            ),
+                            unit: "amount_per_week".to_string(),
+                            assumptions: vec![
+                                format!(
+                                    "Доход за час возвращается к {:.2} при {:.1} ч/нед.",
+                                    trend.start_value, trend.weekly_hours
+                                ),
+                                "Доход недели делится между проектами по часам × ставке"
+                                    .to_string(),
+                            ],
+                        },
+                    ),
+                };
+
+                RecommendationOutput {
+                    id: String::new(),
+                    project_id: Some(trend.project_id),
+                    period: last_week_period(data),
+                    status: None,
+                    first_seen: None,
+                    last_seen: None,
+                    r#type: r#type.to_string(),
+                    priority: if drop || trend.change <= -0.3 {
+                        "high".to_string()
+                    } else {
+                        "medium".to_string()
+                    },
+                    title,
+                    description,
+                    action_items,
+                    expected_impact,
+                    metrics,
+                    confidence: 0.6 + 0.2 * (trend.weeks as f64 / VALUE_TREND_WEEKS as f64).min(1.0),
+                }
+            })
+            .collect()
+    }
+
+    /// Убыточные проекты и проекты с низкой и падающей маржой
+    /// (`settings.cost_per_minute`)
+    fn recommend_profitability(&self, data: &MLInputData) -> Vec<RecommendationOutput> {
+        let Some(profitability) = profitability(&data.weeks, &data.settings) else {
+            return Vec::new();
+        };
+        let weeks = profitability.weeks as f64;
+
+        profitability
+            .projects
+            .iter()
+            .filter_map(|project| {
+                let unprofitable = project.margin < 0.0;
+                let low_margin = project
+                    .margin_share
+                    .is_some_and(|share| share < self.thresholds.low_margin_share)
+                    && project.margin_trend == "decreasing";
+                if !unprofitable && !low_margin {
+                    return None;
+                }
+                let project_name = self.get_project_name(data, project.project_id);
+                let revenue_per_hour = if project.hours > 0.0 {
+                    project.revenue / project.hours
+                } else {
+                    0.0
+                };
+                let metrics = recommendation_metrics(&[
+                    ("revenue", project.revenue),
+                    ("cost", project.cost),
+                    ("margin", project.margin),
+                    ("margin_share", project.margin_share.unwrap_or(0.0)),
+                    ("margin_per_hour", project.margin_per_hour),
+                    ("margin_trend_slope", project.margin_trend_slope),
+                    ("hours", project.hours),
+                    ("weeks", weeks),
+                ]);
+                let (r#type, priority, title, description, action_items, estimated_delta) =
+                    if unprofitable {
+                        (
+                            "unprofitable_project",
+                            "high",
+                            format!("Проект '{}' убыточен", project_name),
+                            format!(
+                                "За {} нед. доход по проекту {:.2}, затраты {:.2}: убыток {:.2}",
+                                profitability.weeks, project.revenue, project.cost, -project.margin
+                            ),
+                            vec![
+                                format!(
+                                    "Пересмотрите ставку: доход за час {:.2} не покрывает \
+                                     затраты {:.2}",
+                                    revenue_per_hour, profitability.cost_per_hour
+                                ),
+                                "Сократите неоплачиваемую работу по проекту".to_string(),
+                                "Если условия не изменить, завершите обязательства и не берите \
+                                 новые задачи"
+                                    .to_string(),
+                            ],
+                            -project.margin / weeks,
+                        )
+                    } else {
+                        // Доход, при котором доля маржи вернется к порогу
+                        let target_revenue =
+                            project.cost / (1.0 - self.thresholds.low_margin_share);
+                        (
+                            "low_margin_project",
+                            "medium",
+                            format!("Маржа проекта '{}' снижается", project_name),
+                            format!(
+                                "Маржа проекта - {:.0}% дохода и падает на {:.2} за час в неделю",
+                                project.margin_share.unwrap_or(0.0) * 100.0,
+                                -project.margin_trend_slope
+                            ),
+                            vec![
+                                "Сверьте выставленные суммы с отработанным временем".to_string(),
+                                "Обсудите с клиентом повышение ставки".to_string(),
+                            ],
+                            (target_revenue - project.revenue).max(0.0) / weeks,
+                        )
+                    };
+
+                Some(RecommendationOutput {
+                    id: String::new(),
+                    project_id: Some(project.project_id),
+                    period: None,
+                    status: None,
+                    first_seen: None,
+                    last_seen: None,
+                    r#type: r#type.to_string(),
+                    priority: priority.to_string(),
+                    title,
+                    description,
+                    action_items,
+                    expected_impact: ExpectedImpact {
+                        metric: "margin".to_string(),
+                        estimated_delta: Some(estimated_delta),
+                        unit: "amount_per_week".to_string(),
+                        assumptions: vec![
+                            format!(
+                                "Затраты - {:.2} за час работы над проектом",
+                                profitability.cost_per_hour
+                            ),
+                            "Доход недели делится между проектами по часам × ставке".to_string(),
+                        ],
+                    },
+                    metrics,
+                    confidence: 0.6 + 0.2 * (weeks / VALUE_TREND_WEEKS as f64).min(1.0),
+                })
+            })
+            .collect()
+    }
+
+    /// Активные проекты, которые по кривой выживаемости проектов пользователя
+    /// скорее всего уснут в ближайшие недели
+    fn recommend_idle_projects(&self, data: &MLInputData) -> Vec<RecommendationOutput> {
+        let lifetime = project_lifetime(&data.weeks, DEFAULT_DORMANCY_HORIZON);
+        // Без завершившихся периодов кривая ничего не знает о сроке жизни
+        if lifetime.events == 0 {
+            return Vec::new();
+        }
+
+        lifetime
+            .active_projects
+            .iter()
+            .filter(|p| p.dormancy_probability >= self.thresholds.dormancy_risk)
+            .map(|risk| {
+                let project_name = self.get_project_name(data, risk.project_id);
+                let mut metrics = recommendation_metrics(&[
+                    ("active_weeks", risk.active_weeks as f64),
+                    ("dormancy_probability", risk.dormancy_probability),
+                    ("horizon_weeks", lifetime.horizon_weeks as f64),
+                ]);
+                if let Some(median) = lifetime.median_lifetime_weeks {
+                    metrics.insert("median_lifetime_weeks".to_string(), median as f64);
+                }
+                let mut description = format!(
+                    "Проект '{}' активен {} нед.; вероятность, что он уснет в ближайшие {} нед., - {:.0}%",
+                    project_name,
+                    risk.active_weeks,
+                    lifetime.horizon_weeks,
+                    risk.dormancy_probability * 100.0
+                );
+                if let Some(median) = lifetime.median_lifetime_weeks {
+                    description.push_str(&format!(
+                        ". Обычно ваши проекты активны {} нед.",
+                        median
+                    ));
+                }
+                RecommendationOutput {
+                    id: String::new(),
+                    project_id: Some(risk.project_id),
+                    period: None,
+                    status: None,
+                    first_seen: None,
+                    last_seen: None,
+                    r#type: "idle_project".to_string(),
+                    priority: if risk.dormancy_probability >= 0.75 {
+                        "high".to_string()
+                    } else {
+                        "medium".to_string()
+                    },
+                    title: format!("Спланируйте завершение проекта '{}'", project_name),
+                    description,
+                    action_items: vec![
+                        "Закройте открытые задачи и выставьте счет за выполненную работу"
+                            .to_string(),
+                        "Обсудите с клиентом продолжение или следующий этап".to_string(),
+                        "Запланируйте, чем заполнить освобождающееся время".to_string(),
+                    ],
+                    // Доход проекта, который нужно будет чем-то заменить, если он уснет
+                    expected_impact: ExpectedImpact {
+                        metric: "revenue".to_string(),
+                        estimated_delta: Some(
+                            -risk.dormancy_probability
+                                * recent_weekly_hours(data, risk.project_id)
+                                * data.settings.project_rate(risk.project_id)
+                                * 60.0
+                                * lifetime.horizon_weeks as f64,
+                        ),
+                        unit: "amount".to_string(),
+                        assumptions: vec![
+                            format!(
+                                "Ожидаемая потеря дохода за {} нед. без замены проекта",
+                                lifetime.horizon_weeks
+                            ),
+                            "Часы проекта - среднее за последние 4 недели".to_string(),
+                        ],
+                    },
+                    metrics,
+                    confidence: (0.5 + 0.05 * lifetime.events as f64).min(0.85),
+                }
+            })
+            .collect()
+    }
+
+    /// Проект с наибольшей средней длительностью записи (без встреч)
+    fn most_demanding_project(&self, data: &MLInputData) -> Option<String> {
+        let analyzer = productivity_analyzer(data);
+        let mut durations: HashMap<i32, (i32, i32)> = HashMap::new();
+        for entry in data.timesheets.iter().filter(|e| !analyzer.is_meeting(e)) {
+            if let Some(project_id) = entry.project_id {
+                let (minutes, count) = durations.entry(project_id).or_insert((0, 0));
+                *minutes += entry.duration;
+                *count += 1;
+            }
+        }
+        durations
+            .into_iter()
+            .map(|(id, (minutes, count))| (id, minutes as f64 / count as f64))
+            .max_by(|a, b| {
+                a.1.partial_cmp(&b.1)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(b.0.cmp(&a.0))
+            })
+            .map(|(id, _)| self.get_project_name(data, id))
+    }
+
     fn get_project_name(&self, data: &MLInputData, project_id: i32) -> String {
         data.projects
             .iter()
@@ -294,6 +1332,333 @@ impl RecommendationEngine {
     }
 }
 
+/// Анализатор продуктивности с настройками запроса
+fn productivity_analyzer(data: &MLInputData) -> ProductivityAnalyzer {
+    ProductivityAnalyzer::with_preferences(
+        data.settings.user_preferences.clone(),
+        data.settings.analyzer.clone(),
+    )
+    .meeting_patterns(data.settings.meeting_patterns.clone())
+    .billable_tags(data.settings.billable_tags.clone())
+}
+
+fn weekday_name(day_of_week: i32) -> &'static str {
+    match day_of_week {
+        0 => "Вс",
+        1 => "Пн",
+        2 => "Вт",
+        3 => "Ср",
+        4 => "Чт",
+        5 => "Пт",
+        _ => "Сб",
+    }
+}
+
+/// Стабильный идентификатор: одна и та же рекомендация (тип, проект, неделя и
+/// заголовок) получает тот же `id` в следующих запросах
+pub(crate) fn recommendation_id(recommendation: &RecommendationOutput) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(recommendation.r#type.as_bytes());
+    hasher.update([0u8]);
+    if let Some(project_id) = recommendation.project_id {
+        hasher.update(project_id.to_le_bytes());
+    }
+    hasher.update([0u8]);
+    if let Some(period) = &recommendation.period {
+        hasher.update(period.as_bytes());
+    }
+    hasher.update([0u8]);
+    hasher.update(recommendation.title.as_bytes());
+    format!("{:x}", hasher.finalize())[..16].to_string()
+}
+
+/// Средние часы проекта в неделю за последние 4 недели
+fn recent_weekly_hours(data: &MLInputData, project_id: i32) -> f64 {
+    let recent = &data.weeks[data.weeks.len().saturating_sub(4)..];
+    if recent.is_empty() {
+        return 0.0;
+    }
+    recent
+        .iter()
+        .flat_map(|w| &w.project_stats)
+        .filter(|s| s.project_id == project_id)
+        .map(|s| s.hours)
+        .sum::<f64>()
+        / recent.len() as f64
+}
+
+/// План действий на неделю после последней недели данных.
+///
+/// Рекомендации упорядочиваются по приоритету, затем по уверенности, и
+/// добавляются в план, пока их время укладывается в `ACTION_PLAN_CAPACITY_SHARE`
+/// от `capacity_hours` (первая - всегда), но не больше `MAX_ACTION_STEPS`.
+/// `capacity_hours` уменьшается на долю будней недели, выпадающих на праздники
+/// `settings.holiday_country`
+pub fn action_plan(
+    recommendations: &[RecommendationOutput],
+    data: &MLInputData,
+    capacity_hours: f64,
+    capacity_source: &str,
+) -> ActionPlan {
+    let priority_rank = |priority: &str| match priority {
+        "high" => 0,
+        "medium" => 1,
+        _ => 2,
+    };
+    let mut ranked: Vec<&RecommendationOutput> = recommendations.iter().collect();
+    ranked.sort_by(|a, b| {
+        priority_rank(&a.priority)
+            .cmp(&priority_rank(&b.priority))
+            .then(b.confidence.total_cmp(&a.confidence))
+    });
+
+    // Праздники в будни недели плана сокращают рабочее время
+    let plan_week = data
+        .weeks
+        .last()
+        .map(|week| next_iso_week(week.year, week.week.max(1) as u32));
+    let holidays = match (&data.settings.holiday_country, plan_week) {
+        (Some(country), Some((year, week))) => {
+            holidays::weekday_holidays(country, year, week).unwrap_or_default()
+        }
+        _ => Vec::new(),
+    };
+    let capacity_hours = capacity_hours.max(0.0) * (5 - holidays.len().min(5)) as f64 / 5.0;
+    let budget_hours = capacity_hours * ACTION_PLAN_CAPACITY_SHARE;
+    let mut planned_hours = 0.0;
+    let mut steps: Vec<ActionStep> = Vec::new();
+    for recommendation in ranked {
+        if steps.len() >= MAX_ACTION_STEPS {
+            break;
+        }
+        let time_cost_hours = ACTION_TIME_COST
+            .iter()
+            .find(|(kind, _)| *kind == recommendation.r#type)
+            .map(|(_, hours)| *hours)
+            .unwrap_or(DEFAULT_ACTION_TIME_COST);
+        if !steps.is_empty() && planned_hours + time_cost_hours > budget_hours {
+            continue;
+        }
+        planned_hours += time_cost_hours;
+        steps.push(ActionStep {
+            order: steps.len() + 1,
+            recommendation_id: recommendation.id.clone(),
+            recommendation_type: recommendation.r#type.clone(),
+            priority: recommendation.priority.clone(),
+            title: recommendation.title.clone(),
+            checklist: recommendation.action_items.clone(),
+            time_cost_hours,
+            expected_benefit: recommendation.expected_impact.clone(),
+        });
+    }
+
+    ActionPlan {
+        period: plan_week.map(|(year, number)| format!("{}-W{:02}", year, number)),
+        capacity_hours,
+        capacity_source: capacity_source.to_string(),
+        budget_hours,
+        planned_hours,
+        steps,
+        holidays: holidays.iter().map(|d| d.to_string()).collect(),
+    }
+}
+
+/// Показатели рекомендации для шаблонов и клиентов
+fn recommendation_metrics(values: &[(&str, f64)]) -> BTreeMap<String, f64> {
+    values
+        .iter()
+        .filter(|(_, value)| value.is_finite())
+        .map(|(name, value)| (name.to_string(), *value))
+        .collect()
+}
+
+/// ISO-неделя последней недели данных (`2024-W05`) для рекомендаций по ней
+fn last_week_period(data: &MLInputData) -> Option<String> {
+    data.weeks
+        .last()
+        .map(|week| format!("{}-W{:02}", week.year, week.week))
+}
+
+/// Отзывы о рекомендациях одного типа
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecommendationTypeStats {
+    pub recommendation_type: String,
+    pub accepted: usize,
+    pub dismissed: usize,
+    pub snoozed: usize,
+    /// Доля принятых со сглаживанием Лапласа: 0.5 без отзывов
+    pub acceptance_rate: f64,
+}
+
+/// История отзывов арендатора: что больше не показывать и каким типам доверять
+#[derive(Debug, Clone, Default)]
+pub struct RecommendationHistory {
+    dismissed: HashSet<String>,
+    snoozed_until: HashMap<String, DateTime<Utc>>,
+    by_type: HashMap<String, RecommendationTypeStats>,
+}
+
+impl RecommendationHistory {
+    /// Отзывы в порядке поступления; более поздний отзыв о рекомендации
+    /// отменяет ранний (принятие после отказа возвращает ее)
+    pub fn from_feedback(feedback: &[RecommendationFeedback]) -> Self {
+        let mut history = Self::default();
+        for item in feedback {
+            let id = &item.recommendation_id;
+            history.dismissed.remove(id);
+            history.snoozed_until.remove(id);
+            match item.action {
+                RecommendationAction::Accepted => {}
+                RecommendationAction::Dismissed => {
+                    history.dismissed.insert(id.clone());
+                }
+                RecommendationAction::Snoozed => {
+                    let recorded_at = DateTime::parse_from_rfc3339(&item.recorded_at)
+                        .map(|t| t.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now());
+                    let weeks = item.snooze_weeks.unwrap_or(DEFAULT_SNOOZE_WEEKS);
+                    history
+                        .snoozed_until
+                        .insert(id.clone(), recorded_at + Duration::weeks(weeks as i64));
+                }
+            }
+
+            let stats = history
+                .by_type
+                .entry(item.recommendation_type.clone())
+                .or_insert_with(|| RecommendationTypeStats {
+                    recommendation_type: item.recommendation_type.clone(),
+                    ..RecommendationTypeStats::default()
+                });
+            match item.action {
+                RecommendationAction::Accepted => stats.accepted += 1,
+                RecommendationAction::Dismissed => stats.dismissed += 1,
+                RecommendationAction::Snoozed => stats.snoozed += 1,
+            }
+        }
+        for stats in history.by_type.values_mut() {
+            let total = stats.accepted + stats.dismissed + stats.snoozed;
+            stats.acceptance_rate = (stats.accepted as f64 + 1.0) / (total as f64 + 2.0);
+        }
+        history
+    }
+
+    pub fn type_stats(&self, recommendation_type: &str) -> RecommendationTypeStats {
+        self.by_type
+            .get(recommendation_type)
+            .cloned()
+            .unwrap_or_else(|| RecommendationTypeStats {
+                recommendation_type: recommendation_type.to_string(),
+                acceptance_rate: 0.5,
+                ..RecommendationTypeStats::default()
+            })
+    }
+
+    /// Убирает отклоненные и отложенные рекомендации, масштабирует уверенность
+    /// на долю принятых рекомендаций того же типа (0.5 - без изменений) и
+    /// упорядочивает по уверенности
+    pub fn apply(
+        &self,
+        recommendations: Vec<RecommendationOutput>,
+        now: DateTime<Utc>,
+    ) -> Vec<RecommendationOutput> {
+        if self.by_type.is_empty() {
+            return recommendations;
+        }
+
+        let mut recommendations: Vec<RecommendationOutput> = recommendations
+            .into_iter()
+            .filter(|r| !self.dismissed.contains(&r.id))
+            .filter(|r| {
+                self.snoozed_until
+                    .get(&r.id)
+                    .is_none_or(|until| *until <= now)
+            })
+            .map(|mut r| {
+                let rate = self.type_stats(&r.r#type).acceptance_rate;
+                r.confidence = (r.confidence * 2.0 * rate).min(MAX_CONFIDENCE);
+                r
+            })
+            .collect();
+        recommendations.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        recommendations
+    }
+}
+
+/// Когда рекомендация впервые и последний раз выдавалась арендатору
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SeenRecommendation {
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+}
+
+/// История выдачи рекомендаций арендатору: отличает новые от еще открытых и
+/// находит решенные - выданные прошлым запросом, но не выданные этим
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecommendationTracker {
+    seen: HashMap<String, SeenRecommendation>,
+    /// Рекомендации, выданные последним запросом
+    open: HashSet<String>,
+}
+
+impl RecommendationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Имя трекера арендатора в хранилище моделей
+    pub fn storage_name(tenant: &str) -> String {
+        format!("recommendations:{}", tenant)
+    }
+
+    /// Отмечает выдачу `recommendations` (`status`, `first_seen`, `last_seen`);
+    /// возвращает `id` решенных рекомендаций в порядке сортировки
+    pub fn track(
+        &mut self,
+        recommendations: &mut [RecommendationOutput],
+        now: DateTime<Utc>,
+    ) -> Vec<String> {
+        let current: HashSet<String> = recommendations.iter().map(|r| r.id.clone()).collect();
+        for recommendation in recommendations.iter_mut() {
+            let status = if self.open.contains(&recommendation.id) {
+                "open"
+            } else {
+                "new"
+            };
+            let seen = self
+                .seen
+                .entry(recommendation.id.clone())
+                .or_insert(SeenRecommendation {
+                    first_seen: now,
+                    last_seen: now,
+                });
+            if status == "new" {
+                // Вернувшаяся после решения рекомендация считается новой
+                seen.first_seen = now;
+            }
+            seen.last_seen = now;
+            recommendation.status = Some(status.to_string());
+            recommendation.first_seen = Some(seen.first_seen.to_rfc3339());
+            recommendation.last_seen = Some(seen.last_seen.to_rfc3339());
+        }
+
+        let mut resolved: Vec<String> = self.open.difference(&current).cloned().collect();
+        resolved.sort();
+        self.open = current;
+        let cutoff = now - Duration::weeks(TRACKING_RETENTION_WEEKS);
+        self.seen.retain(|_, seen| seen.last_seen >= cutoff);
+        resolved
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Serialization error: {}", e))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Deserialization error: {}", e))
+    }
+}
+
 impl Default for RecommendationEngine {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,171 @@
+//! Моделирование «что если» для недельных распределений часов.
+//!
+//! Фактические часы проекта при плане считаются нормально распределенными вокруг
+//! плана с разбросом недельных часов проекта за последние `SCHEDULE_HISTORY_WEEKS`
+//! недель - как в оценке целей прогноза. Из этого следуют вероятность выполнить
+//! цели `user_preferences.project_goals` и риск выгорания - вероятность недели
+//! дольше устойчивой нагрузки (`contract::sustainable_hours`: от договорных
+//! часов, без них 45 ч). Выручка - план по ставкам проектов
+
+use std::collections::{BTreeSet, HashMap};
+
+use super::contract;
+use super::forecasting::normal_cdf;
+use crate::types::{
+    ProposedSchedule, ScheduleComparison, ScheduleDifference, ScheduleGoal, ScheduleOutcome,
+    Settings, WeekData,
+};
+
+/// Сколько последних недель определяют разброс часов
+pub const SCHEDULE_HISTORY_WEEKS: usize = 8;
+
+/// Разброс недельных часов по последним неделям истории
+pub struct ScheduleSimulator<'a> {
+    settings: &'a Settings,
+    /// Стандартное отклонение часов по проектам и всей недели
+    project_std: HashMap<i32, f64>,
+    total_std: f64,
+    baseline_hours: f64,
+    sustainable_hours: f64,
+}
+
+impl<'a> ScheduleSimulator<'a> {
+    pub fn new(weeks: &[WeekData], settings: &'a Settings) -> Self {
+        let recent = &weeks[weeks.len().saturating_sub(SCHEDULE_HISTORY_WEEKS)..];
+        let project_ids: BTreeSet<i32> = recent
+            .iter()
+            .flat_map(|w| w.project_stats.iter().map(|s| s.project_id))
+            .collect();
+        // Недели без работы по проекту входят в разброс с нулем часов
+        let project_std = project_ids
+            .into_iter()
+            .map(|project_id| {
+                let hours: Vec<f64> = recent
+                    .iter()
+                    .map(|w| {
+                        w.project_stats
+                            .iter()
+                            .filter(|s| s.project_id == project_id)
+                            .map(|s| s.hours)
+                            .sum()
+                    })
+                    .collect();
+                (project_id, mean_std(&hours).1)
+            })
+            .collect();
+        let totals: Vec<f64> = recent.iter().map(|w| w.total_hours).collect();
+        let (baseline_hours, total_std) = mean_std(&totals);
+        Self {
+            settings,
+            project_std,
+            total_std,
+            baseline_hours,
+            sustainable_hours: contract::sustainable_hours(settings),
+        }
+    }
+
+    /// Риск недели дольше устойчивой нагрузки при плане `total_hours`
+    pub fn burnout_risk(&self, total_hours: f64) -> f64 {
+        let std = spread(self.total_std, total_hours);
+        1.0 - normal_cdf((self.sustainable_hours - total_hours) / std)
+    }
+
+    pub fn simulate(&self, schedule: &ProposedSchedule) -> ScheduleOutcome {
+        let planned = |project_id: i32| schedule.allocations.get(&project_id).copied();
+        let total_hours: f64 = schedule.allocations.values().sum();
+        let revenue = schedule
+            .allocations
+            .iter()
+            .map(|(&project_id, &hours)| hours * 60.0 * self.settings.project_rate(project_id))
+            .sum();
+
+        let mut goals: Vec<ScheduleGoal> = self
+            .settings
+            .user_preferences
+            .iter()
+            .flat_map(|prefs| &prefs.project_goals)
+            .filter(|(_, &goal)| goal > 0.0 && goal.is_finite())
+            .map(|(&project_id, &goal_hours)| {
+                let planned_hours = planned(project_id).unwrap_or(0.0);
+                // Без истории разброс - только нижняя граница
+                let std = spread(
+                    self.project_std.get(&project_id).copied().unwrap_or(0.0),
+                    planned_hours.max(goal_hours),
+                );
+                ScheduleGoal {
+                    project_id,
+                    goal_hours,
+                    planned_hours,
+                    attainment_probability: 1.0 - normal_cdf((goal_hours - planned_hours) / std),
+                }
+            })
+            .collect();
+        goals.sort_by_key(|g| g.project_id);
+
+        let burnout_risk = self.burnout_risk(total_hours);
+        ScheduleOutcome {
+            name: schedule.name.clone(),
+            total_hours,
+            revenue,
+            goal_attainment_probability: (!goals.is_empty())
+                .then(|| goals.iter().map(|g| g.attainment_probability).product()),
+            goals,
+            burnout_risk,
+            burnout_risk_delta: burnout_risk - self.burnout_risk(self.baseline_hours),
+        }
+    }
+
+    /// Результаты двух распределений и разница второго с первым
+    pub fn compare(
+        &self,
+        first: &ProposedSchedule,
+        second: &ProposedSchedule,
+    ) -> ScheduleComparison {
+        let first = self.simulate(first);
+        let second = self.simulate(second);
+        let difference = ScheduleDifference {
+            total_hours: second.total_hours - first.total_hours,
+            revenue: second.revenue - first.revenue,
+            goal_attainment_probability: first
+                .goal_attainment_probability
+                .zip(second.goal_attainment_probability)
+                .map(|(a, b)| b - a),
+            burnout_risk: second.burnout_risk - first.burnout_risk,
+        };
+        ScheduleComparison {
+            baseline_hours: self.baseline_hours,
+            baseline_burnout_risk: self.burnout_risk(self.baseline_hours),
+            sustainable_hours: self.sustainable_hours,
+            schedules: vec![first, second],
+            difference,
+        }
+    }
+}
+
+/// Проверка распределения из запроса: часы конечные и неотрицательные
+pub fn validate(schedule: &ProposedSchedule) -> Result<(), String> {
+    match schedule
+        .allocations
+        .iter()
+        .find(|(_, &hours)| !hours.is_finite() || hours < 0.0)
+    {
+        Some((project_id, hours)) => Err(format!(
+            "Schedule {}: invalid hours {} for project {}",
+            schedule.name, hours, project_id
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Нижняя граница разброса, как в оценке целей: несколько одинаковых недель не
+/// дают уверенности
+fn spread(std: f64, hours: f64) -> f64 {
+    std.max(0.1 * hours).max(0.5)
+}
+
+fn mean_std(values: &[f64]) -> (f64, f64) {
+    let n = values.len().max(1) as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
@@ -0,0 +1,137 @@
+//! Диагностика автокорреляции и сезонности недельных часов
+
+use crate::types::{SeasonalPeriod, SeasonalityDiagnostics, WeekData};
+
+/// Минимум недель для диагностики
+pub const MIN_SEASONALITY_WEEKS: usize = 8;
+
+/// Наибольший лаг по умолчанию: полгода
+const DEFAULT_MAX_LAG: usize = 26;
+
+/// Автокорреляция, частная автокорреляция и периоды, на которых автокорреляция
+/// значима и образует локальный максимум. Лаг не больше половины истории,
+/// чтобы каждая фаза периода встречалась хотя бы дважды
+pub fn diagnose(
+    weeks: &[WeekData],
+    max_lag: Option<usize>,
+) -> Result<SeasonalityDiagnostics, String> {
+    let hours: Vec<f64> = weeks.iter().map(|w| w.total_hours).collect();
+    if hours.len() < MIN_SEASONALITY_WEEKS {
+        return Err(format!(
+            "Need at least {} weeks for seasonality diagnostics",
+            MIN_SEASONALITY_WEEKS
+        ));
+    }
+    if hours.iter().any(|h| !h.is_finite()) {
+        return Err("Weekly hours must be finite".to_string());
+    }
+
+    let n = hours.len();
+    let max_lag = max_lag.unwrap_or(DEFAULT_MAX_LAG).clamp(1, n / 2);
+    let acf = autocorrelation(&hours, max_lag);
+    let pacf = partial_autocorrelation(&acf);
+    let confidence_bound = 1.96 / (n as f64).sqrt();
+
+    let mut periods: Vec<SeasonalPeriod> = (2..=max_lag)
+        .filter(|&lag| {
+            let value = acf[lag - 1];
+            value > confidence_bound
+                && value >= acf[lag - 2]
+                && acf.get(lag).is_none_or(|next| value >= *next)
+        })
+        .map(|period| SeasonalPeriod {
+            period,
+            label: period_label(period).map(str::to_string),
+            autocorrelation: acf[period - 1],
+            strength: seasonal_strength(&hours, period),
+        })
+        .collect();
+    periods.sort_by(|a, b| b.strength.total_cmp(&a.strength));
+    let seasonal_strength = periods.first().map_or(0.0, |p| p.strength);
+
+    Ok(SeasonalityDiagnostics {
+        weeks: n,
+        acf,
+        pacf,
+        confidence_bound,
+        periods,
+        seasonal_strength,
+    })
+}
+
+/// Выборочная автокорреляция для лагов 1..=`max_lag`; постоянный ряд дает нули
+fn autocorrelation(values: &[f64], max_lag: usize) -> Vec<f64> {
+    let n = values.len();
+    let mean = values.iter().sum::<f64>() / n as f64;
+    let variance: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    (1..=max_lag)
+        .map(|lag| {
+            if variance <= f64::EPSILON {
+                return 0.0;
+            }
+            (lag..n)
+                .map(|t| (values[t] - mean) * (values[t - lag] - mean))
+                .sum::<f64>()
+                / variance
+        })
+        .collect()
+}
+
+/// Частная автокорреляция по рекурсии Дурбина-Левинсона
+fn partial_autocorrelation(acf: &[f64]) -> Vec<f64> {
+    let mut pacf = Vec::with_capacity(acf.len());
+    let mut phi: Vec<f64> = Vec::new();
+    for k in 0..acf.len() {
+        let numerator = acf[k] - (0..k).map(|j| phi[j] * acf[k - 1 - j]).sum::<f64>();
+        let denominator = 1.0 - (0..k).map(|j| phi[j] * acf[j]).sum::<f64>();
+        let phi_kk = if denominator.abs() > f64::EPSILON {
+            (numerator / denominator).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+        let mut next: Vec<f64> = (0..k).map(|j| phi[j] - phi_kk * phi[k - 1 - j]).collect();
+        next.push(phi_kk);
+        phi = next;
+        pacf.push(phi_kk);
+    }
+    pacf
+}
+
+/// 1 - Var(остаток) / Var(ряд), где остаток - ряд без средних по фазе периода;
+/// дисперсии поправлены на число степеней свободы, иначе длинные периоды
+/// объясняли бы шум
+fn seasonal_strength(values: &[f64], period: usize) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let total: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    if total <= f64::EPSILON {
+        return 0.0;
+    }
+
+    let mut sums = vec![(0.0, 0usize); period];
+    for (t, v) in values.iter().enumerate() {
+        sums[t % period].0 += v;
+        sums[t % period].1 += 1;
+    }
+    let residual: f64 = values
+        .iter()
+        .enumerate()
+        .map(|(t, v)| {
+            let (sum, count) = sums[t % period];
+            (v - sum / count as f64).powi(2)
+        })
+        .sum();
+    let residual_df = values.len().saturating_sub(period).max(1) as f64;
+    (1.0 - (residual / residual_df) / (total / (n - 1.0))).clamp(0.0, 1.0)
+}
+
+/// Календарное название периода в неделях
+fn period_label(period: usize) -> Option<&'static str> {
+    match period {
+        4 | 5 => Some("monthly"),
+        12..=14 => Some("quarterly"),
+        25..=27 => Some("half_yearly"),
+        51..=53 => Some("yearly"),
+        _ => None,
+    }
+}
@@ -0,0 +1,155 @@
+//! Анализ выживаемости проектов: как долго проекты остаются активными
+
+use std::collections::BTreeMap;
+
+use crate::types::{ProjectDormancyRisk, ProjectLifetime, SurvivalPoint, WeekData};
+
+/// Столько недель без записей подряд - и проект считается уснувшим
+pub const DORMANCY_WEEKS: usize = 4;
+
+/// Горизонт прогноза засыпания по умолчанию
+pub const DEFAULT_DORMANCY_HORIZON: usize = 4;
+
+/// Период активности проекта в неделях истории
+#[derive(Debug, Clone, PartialEq)]
+struct ActivitySpan {
+    project_id: i32,
+    /// Недели от первой до последней активной недели включительно
+    length: usize,
+    /// Проект уснул после периода; иначе период продолжается (цензурирован)
+    ended: bool,
+}
+
+/// Кривая выживаемости Каплана-Мейера по периодам активности проектов
+#[derive(Debug, Clone)]
+pub struct KaplanMeier {
+    /// (недели, доля периодов, длящихся дольше, в риске, завершилось)
+    steps: Vec<(usize, f64, usize, usize)>,
+}
+
+impl KaplanMeier {
+    fn fit(spans: &[ActivitySpan]) -> Self {
+        let mut by_length: BTreeMap<usize, (usize, usize)> = BTreeMap::new();
+        for span in spans {
+            let (events, censored) = by_length.entry(span.length).or_default();
+            if span.ended {
+                *events += 1;
+            } else {
+                *censored += 1;
+            }
+        }
+
+        let mut at_risk = spans.len();
+        let mut survival = 1.0;
+        let mut steps = Vec::new();
+        for (length, (events, censored)) in by_length {
+            if events > 0 && at_risk > 0 {
+                survival *= 1.0 - events as f64 / at_risk as f64;
+                steps.push((length, survival, at_risk, events));
+            }
+            at_risk -= events + censored;
+        }
+        Self { steps }
+    }
+
+    /// Доля периодов активности длиннее `weeks` недель
+    pub fn survival(&self, weeks: usize) -> f64 {
+        self.steps
+            .iter()
+            .take_while(|(length, ..)| *length <= weeks)
+            .last()
+            .map_or(1.0, |(_, survival, ..)| *survival)
+    }
+
+    /// Наименьшая длина, после которой активны не больше половины проектов
+    pub fn median(&self) -> Option<usize> {
+        self.steps
+            .iter()
+            .find(|(_, survival, ..)| *survival <= 0.5)
+            .map(|(length, ..)| *length)
+    }
+
+    /// Вероятность, что период возрастом `age` недель закончится в ближайшие `horizon` недель
+    pub fn ending_probability(&self, age: usize, horizon: usize) -> f64 {
+        let current = self.survival(age);
+        if current <= f64::EPSILON {
+            return 1.0;
+        }
+        (1.0 - self.survival(age + horizon) / current).clamp(0.0, 1.0)
+    }
+
+    pub fn events(&self) -> usize {
+        self.steps.iter().map(|(.., events)| events).sum()
+    }
+}
+
+/// Периоды активности по неделям (в порядке истории): перерыв в `DORMANCY_WEEKS`
+/// недель завершает период; период, идущий в конце истории, цензурирован
+fn activity_spans(weeks: &[WeekData]) -> Vec<ActivitySpan> {
+    let mut active_weeks: BTreeMap<i32, Vec<usize>> = BTreeMap::new();
+    for (index, week) in weeks.iter().enumerate() {
+        for stat in week.project_stats.iter().filter(|s| s.hours > 0.0) {
+            active_weeks.entry(stat.project_id).or_default().push(index);
+        }
+    }
+
+    let mut spans = Vec::new();
+    for (project_id, mut indices) in active_weeks {
+        indices.dedup();
+        let mut start = indices[0];
+        for pair in indices.windows(2) {
+            if pair[1] - pair[0] > DORMANCY_WEEKS {
+                spans.push(ActivitySpan {
+                    project_id,
+                    length: pair[0] - start + 1,
+                    ended: true,
+                });
+                start = pair[1];
+            }
+        }
+        let last = indices[indices.len() - 1];
+        spans.push(ActivitySpan {
+            project_id,
+            length: last - start + 1,
+            ended: weeks.len() - 1 - last >= DORMANCY_WEEKS,
+        });
+    }
+    spans
+}
+
+/// Кривая выживаемости проектов и вероятность засыпания активных сейчас проектов
+/// в ближайшие `horizon` недель (по убыванию вероятности)
+pub fn project_lifetime(weeks: &[WeekData], horizon: usize) -> ProjectLifetime {
+    let spans = activity_spans(weeks);
+    let curve = KaplanMeier::fit(&spans);
+
+    let mut at_risk: Vec<ProjectDormancyRisk> = spans
+        .iter()
+        .filter(|s| !s.ended)
+        .map(|s| ProjectDormancyRisk {
+            project_id: s.project_id,
+            active_weeks: s.length,
+            dormancy_probability: curve.ending_probability(s.length, horizon),
+        })
+        .collect();
+    at_risk.sort_by(|a, b| b.dormancy_probability.total_cmp(&a.dormancy_probability));
+
+    ProjectLifetime {
+        dormancy_weeks: DORMANCY_WEEKS,
+        horizon_weeks: horizon,
+        spans: spans.len(),
+        events: curve.events(),
+        median_lifetime_weeks: curve.median(),
+        curve: curve
+            .steps
+            .iter()
+            .map(|&(weeks, survival, at_risk, events)| SurvivalPoint {
+                weeks,
+                survival,
+                at_risk,
+                events,
+            })
+            .collect(),
+        active_projects: at_risk,
+    }
+}
@@ -0,0 +1,169 @@
+//! Аналитика команды (`/api/team/*`): участники сравниваются группами, чтобы
+//! руководитель видел системные проблемы, а не отдельных людей
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::ingest::WeeklyAggregator;
+use crate::models::contract::{sustainable_hours, CONTRACT_WEEKS};
+use crate::models::forecasting::blend_with_goals;
+use crate::models::productivity::ProductivityAnalyzer;
+use crate::preprocessing::FeatureEngineer;
+use crate::types::{Cohort, CohortAnalysis, CohortMetrics, TeamInput, TeamMember, WeekData};
+
+/// Ключ метаданных когорт по умолчанию
+pub const DEFAULT_COHORT_KEY: &str = "role";
+
+/// Когорты меньше этого не раскрывают показатели (`options.min_cohort_size`)
+pub const DEFAULT_MIN_COHORT_SIZE: usize = 3;
+
+/// Когорта участников без ключа метаданных
+pub const UNASSIGNED_COHORT: &str = "unassigned";
+
+/// Раздробленность когорты во столько раз выше командной - сигнал `fragmentation`
+const FRAGMENTATION_RATIO: f64 = 1.5;
+
+/// Вероятность выполнить цели ниже этой - сигнал `goals_at_risk`
+const GOAL_RISK_PROBABILITY: f64 = 0.5;
+
+/// Недели участника по порядку: из `weeks`, без них - из его записей
+pub fn member_weeks(member: &TeamMember) -> Cow<'_, [WeekData]> {
+    if member.data.weeks.is_empty() {
+        let mut aggregator = WeeklyAggregator::new(member.data.settings.rate_per_minute);
+        for entry in &member.data.timesheets {
+            aggregator.add(entry);
+        }
+        return Cow::Owned(aggregator.finish());
+    }
+    match FeatureEngineer::canonical_weeks(&member.data.weeks).0 {
+        Cow::Borrowed(_) => Cow::Borrowed(&member.data.weeks),
+        Cow::Owned(weeks) => Cow::Owned(weeks),
+    }
+}
+
+/// Средние часы участника в неделю за последние `CONTRACT_WEEKS` недель
+pub fn recent_weekly_hours(weeks: &[WeekData]) -> f64 {
+    let recent = &weeks[weeks.len().saturating_sub(CONTRACT_WEEKS)..];
+    if recent.is_empty() {
+        return 0.0;
+    }
+    recent
+        .iter()
+        .map(|w| w.total_hours)
+        .filter(|h| h.is_finite())
+        .sum::<f64>()
+        / recent.len() as f64
+}
+
+fn member_metrics(member: &TeamMember) -> CohortMetrics {
+    let settings = &member.data.settings;
+    let weeks = member_weeks(member);
+    let analyzer = ProductivityAnalyzer::with_preferences(
+        settings.user_preferences.clone(),
+        settings.analyzer.clone(),
+    );
+    let goals = settings
+        .user_preferences
+        .as_ref()
+        .map(|p| p.project_goals.clone())
+        .unwrap_or_default();
+    let assessments = blend_with_goals(&weeks, &mut HashMap::new(), &goals);
+    CohortMetrics {
+        avg_weekly_hours: recent_weekly_hours(&weeks),
+        sustainable_hours: sustainable_hours(settings),
+        fragmentation_index: analyzer
+            .analyze_sessions(&member.data.timesheets)
+            .map(|s| s.fragmentation_index),
+        goal_attainment: mean(assessments.iter().map(|a| a.attainment_probability)),
+    }
+}
+
+/// Средние показатели группы участников
+fn group_metrics(metrics: &[&CohortMetrics]) -> CohortMetrics {
+    CohortMetrics {
+        avg_weekly_hours: mean(metrics.iter().map(|m| m.avg_weekly_hours)).unwrap_or(0.0),
+        sustainable_hours: mean(metrics.iter().map(|m| m.sustainable_hours)).unwrap_or(0.0),
+        fragmentation_index: mean(metrics.iter().filter_map(|m| m.fragmentation_index)),
+        goal_attainment: mean(metrics.iter().filter_map(|m| m.goal_attainment)),
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0usize), |(sum, count), v| (sum + v, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+/// Когорты по ключу `cohort_by` метаданных участников. Когорты меньше
+/// `min_cohort_size` скрываются; если скрыта ровно одна, скрывается и
+/// наименьшая из остальных, иначе ее средние восстанавливаются из итогов команды
+pub fn cohort_analysis(
+    team: &TeamInput,
+    cohort_by: &str,
+    min_cohort_size: usize,
+) -> CohortAnalysis {
+    let metrics: Vec<CohortMetrics> = team.members.iter().map(member_metrics).collect();
+    let mut groups: BTreeMap<&str, Vec<&CohortMetrics>> = BTreeMap::new();
+    for (member, metrics) in team.members.iter().zip(&metrics) {
+        let name = member
+            .metadata
+            .get(cohort_by)
+            .map_or(UNASSIGNED_COHORT, String::as_str);
+        groups.entry(name).or_default().push(metrics);
+    }
+
+    let mut cohorts: Vec<Cohort> = groups
+        .iter()
+        .map(|(name, members)| Cohort {
+            name: name.to_string(),
+            members: members.len(),
+            suppressed: members.len() < min_cohort_size,
+            metrics: None,
+            signals: Vec::new(),
+        })
+        .collect();
+    if cohorts.len() > 1 && cohorts.iter().filter(|c| c.suppressed).count() == 1 {
+        if let Some(smallest) = cohorts
+            .iter_mut()
+            .filter(|c| !c.suppressed)
+            .min_by_key(|c| c.members)
+        {
+            smallest.suppressed = true;
+        }
+    }
+
+    let all: Vec<&CohortMetrics> = metrics.iter().collect();
+    let team_metrics = (all.len() >= min_cohort_size).then(|| group_metrics(&all));
+    for cohort in cohorts.iter_mut().filter(|c| !c.suppressed) {
+        let cohort_metrics = group_metrics(&groups[cohort.name.as_str()]);
+        cohort.signals = signals(&cohort_metrics, team_metrics.as_ref());
+        cohort.metrics = Some(cohort_metrics);
+    }
+
+    CohortAnalysis {
+        cohort_by: cohort_by.to_string(),
+        min_cohort_size,
+        members: team.members.len(),
+        team: team_metrics,
+        cohorts,
+    }
+}
+
+fn signals(cohort: &CohortMetrics, team: Option<&CohortMetrics>) -> Vec<String> {
+    let mut signals = Vec::new();
+    if cohort.sustainable_hours > 0.0 && cohort.avg_weekly_hours > cohort.sustainable_hours {
+        signals.push("overload".to_string());
+    }
+    let team_fragmentation = team.and_then(|t| t.fragmentation_index);
+    if let (Some(cohort), Some(team)) = (cohort.fragmentation_index, team_fragmentation) {
+        if team > 0.0 && cohort >= FRAGMENTATION_RATIO * team {
+            signals.push("fragmentation".to_string());
+        }
+    }
+    if cohort
+        .goal_attainment
+        .is_some_and(|p| p < GOAL_RISK_PROBABILITY)
+    {
+        signals.push("goals_at_risk".to_string());
+    }
+    signals
+}
@@ -0,0 +1,225 @@
+//! Юнит-экономика проектов: реализованный доход за час по неделям и маржа -
+//! доход минус внутренние затраты (`Settings::cost_per_minute`)
+
+use crate::models::forecasting::series_trend;
+use crate::types::{Profitability, ProjectMargin, Settings, WeekData};
+
+/// Сколько последних активных недель проекта определяют тренд дохода за час
+pub const VALUE_TREND_WEEKS: usize = 12;
+
+/// Меньше активных недель - тренд проекта не оценивается
+const MIN_VALUE_WEEKS: usize = 4;
+
+/// Падение дохода за час на окне тренда, после которого проект считается теряющим ценность
+pub const VALUE_DECLINE_THRESHOLD: f64 = 0.15;
+
+/// Матрица проект × неделя реализованного дохода за час
+#[derive(Debug, Clone, Default)]
+pub struct ValueMatrix {
+    /// Недели в хронологическом порядке (`YYYY-Www`)
+    pub periods: Vec<String>,
+    /// Проекты по возрастанию id
+    pub project_ids: Vec<i32>,
+    /// `values[проект][неделя]`; `None` - в неделю не было часов проекта
+    pub values: Vec<Vec<Option<f64>>>,
+    /// Часы `hours[проект][неделя]`
+    pub hours: Vec<Vec<f64>>,
+}
+
+/// Тренд дохода за час одного проекта
+#[derive(Debug, Clone)]
+pub struct ValueTrend {
+    pub project_id: i32,
+    /// Число активных недель в окне
+    pub weeks: usize,
+    /// Значения линии тренда в начале и в конце окна
+    pub start_value: f64,
+    pub current_value: f64,
+    /// Наклон Тейла-Сена, дохода за час в неделю
+    pub slope: f64,
+    /// `current_value / start_value - 1`
+    pub change: f64,
+    /// Средние часы проекта в неделю за окно
+    pub weekly_hours: f64,
+}
+
+impl ValueMatrix {
+    /// Доход недели делится между проектами пропорционально часам × ставке
+    /// (`Settings::project_rate`): доход за час проекта - его ставка, умноженная на
+    /// долю ожидаемой суммы недели, которая действительно записана в `total_amount`.
+    /// Если суммы не записаны ни в одной неделе, доход за час равен ставке
+    pub fn build(weeks: &[WeekData], settings: &Settings) -> Self {
+        let mut project_ids: Vec<i32> = weeks
+            .iter()
+            .flat_map(|w| &w.project_stats)
+            .filter(|s| s.hours > 0.0)
+            .map(|s| s.project_id)
+            .collect();
+        project_ids.sort_unstable();
+        project_ids.dedup();
+
+        let amounts_recorded = weeks.iter().any(|w| w.total_amount > 0.0);
+        let mut matrix = ValueMatrix {
+            periods: weeks
+                .iter()
+                .map(|w| format!("{}-W{:02}", w.year, w.week))
+                .collect(),
+            values: vec![vec![None; weeks.len()]; project_ids.len()],
+            hours: vec![vec![0.0; weeks.len()]; project_ids.len()],
+            project_ids,
+        };
+
+        for (column, week) in weeks.iter().enumerate() {
+            let expected: f64 = week
+                .project_stats
+                .iter()
+                .map(|s| s.hours * settings.project_rate(s.project_id) * 60.0)
+                .sum();
+            if expected <= 0.0 {
+                continue;
+            }
+            let realization = if amounts_recorded {
+                week.total_amount.max(0.0) / expected
+            } else {
+                1.0
+            };
+
+            for stat in week.project_stats.iter().filter(|s| s.hours > 0.0) {
+                let Ok(row) = matrix.project_ids.binary_search(&stat.project_id) else {
+                    continue;
+                };
+                matrix.hours[row][column] += stat.hours;
+                matrix.values[row][column] =
+                    Some(settings.project_rate(stat.project_id) * 60.0 * realization);
+            }
+        }
+        matrix
+    }
+
+    /// Доход за час проекта в последнюю неделю его работы
+    pub fn latest_value(&self, project_id: i32) -> Option<f64> {
+        let row = self.project_ids.binary_search(&project_id).ok()?;
+        self.values[row].iter().rev().find_map(|v| *v)
+    }
+
+    /// Тренды проектов, работавших в последнюю неделю, по последним
+    /// `VALUE_TREND_WEEKS` активным неделям (наклон Тейла-Сена по номеру недели)
+    pub fn trends(&self) -> Vec<ValueTrend> {
+        let Some(last) = self.periods.len().checked_sub(1) else {
+            return Vec::new();
+        };
+
+        self.project_ids
+            .iter()
+            .enumerate()
+            .filter(|(row, _)| self.values[*row][last].is_some())
+            .filter_map(|(row, &project_id)| {
+                let points: Vec<(f64, f64)> = self.values[row]
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(column, v)| v.map(|v| (column as f64, v)))
+                    .collect();
+                let points = &points[points.len().saturating_sub(VALUE_TREND_WEEKS)..];
+                if points.len() < MIN_VALUE_WEEKS {
+                    return None;
+                }
+
+                let mut slopes = Vec::new();
+                for (i, a) in points.iter().enumerate() {
+                    for b in &points[i + 1..] {
+                        slopes.push((b.1 - a.1) / (b.0 - a.0));
+                    }
+                }
+                slopes.sort_by(|a, b| a.total_cmp(b));
+                let slope = slopes[slopes.len() / 2];
+
+                // Линия через медиану точек с наклоном Тейла-Сена
+                let mut residuals: Vec<f64> = points.iter().map(|(x, y)| y - slope * x).collect();
+                residuals.sort_by(|a, b| a.total_cmp(b));
+                let intercept = residuals[residuals.len() / 2];
+                let first_week = points[0].0;
+                let start_value = (intercept + slope * first_week).max(0.0);
+                let current_value = (intercept + slope * last as f64).max(0.0);
+
+                let window_hours: f64 = self.hours[row][first_week as usize..].iter().sum();
+                Some(ValueTrend {
+                    project_id,
+                    weeks: points.len(),
+                    start_value,
+                    current_value,
+                    slope,
+                    change: if start_value > 0.0 {
+                        current_value / start_value - 1.0
+                    } else {
+                        0.0
+                    },
+                    weekly_hours: window_hours / (last as f64 - first_week + 1.0),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Маржа проектов за последние `VALUE_TREND_WEEKS` недель: реализованный доход
+/// (`ValueMatrix`) минус затраты `cost_per_minute` за часы проекта. `None` без
+/// ставки затрат (или с отрицательной) и без недель
+pub fn profitability(weeks: &[WeekData], settings: &Settings) -> Option<Profitability> {
+    let cost_per_hour = settings
+        .cost_per_minute
+        .filter(|cost| cost.is_finite() && *cost >= 0.0)?
+        * 60.0;
+    let weeks = &weeks[weeks.len().saturating_sub(VALUE_TREND_WEEKS)..];
+    if weeks.is_empty() {
+        return None;
+    }
+    let matrix = ValueMatrix::build(weeks, settings);
+
+    let mut projects: Vec<ProjectMargin> = matrix
+        .project_ids
+        .iter()
+        .enumerate()
+        .map(|(row, &project_id)| {
+            let hours: f64 = matrix.hours[row].iter().sum();
+            let revenue: f64 = matrix.values[row]
+                .iter()
+                .zip(&matrix.hours[row])
+                .filter_map(|(value, hours)| value.map(|v| v * hours))
+                .sum();
+            let cost = hours * cost_per_hour;
+            // Маржа за час по активным неделям
+            let margins: Vec<f64> = matrix.values[row]
+                .iter()
+                .filter_map(|v| v.map(|v| v - cost_per_hour))
+                .collect();
+            let trend = series_trend(&margins);
+            ProjectMargin {
+                project_id,
+                hours,
+                revenue,
+                cost,
+                margin: revenue - cost,
+                margin_share: share(revenue - cost, revenue),
+                margin_per_hour: margins.last().copied().unwrap_or(0.0),
+                margin_trend: trend.direction.to_string(),
+                margin_trend_slope: trend.slope,
+            }
+        })
+        .collect();
+    projects.sort_by(|a, b| a.margin.total_cmp(&b.margin));
+
+    let revenue: f64 = projects.iter().map(|p| p.revenue).sum();
+    let cost: f64 = projects.iter().map(|p| p.cost).sum();
+    Some(Profitability {
+        weeks: weeks.len(),
+        cost_per_hour,
+        revenue,
+        cost,
+        margin: revenue - cost,
+        margin_share: share(revenue - cost, revenue),
+        projects,
+    })
+}
+
+fn share(margin: f64, revenue: f64) -> Option<f64> {
+    (revenue > 0.0).then(|| margin / revenue)
+}
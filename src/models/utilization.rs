@@ -0,0 +1,179 @@
+//! Загрузка оплачиваемой работой: оплачиваемые часы недели (`billable_share`)
+//! против доступных (`contract::available_hours`: договорные часы или 40, за
+//! вычетом праздников).
+//!
+//! Прогноз на `UTILIZATION_FORECAST_WEEKS` недель - средние оплачиваемые часы
+//! последних `CONTRACT_WEEKS` недель со значимым трендом против доступных часов
+//! будущих недель, поэтому праздники снижают доступные часы и в прогнозе
+
+use std::collections::BTreeMap;
+
+use crate::models::contract::{available_hours, CONTRACT_WEEKS};
+use crate::models::forecasting::{next_iso_week, series_trend};
+use crate::models::productivity::ProductivityAnalyzer;
+use crate::types::{
+    BillableShare, MemberUtilization, Settings, TeamInput, TeamUtilization, UtilizationAnalysis,
+    UtilizationThresholds, WeeklyUtilization,
+};
+
+/// Недель прогноза загрузки
+pub const UTILIZATION_FORECAST_WEEKS: usize = 4;
+
+/// Загрузка по неделям `billable` и прогноз; `None` без недель
+pub fn analyze(billable: &BillableShare, settings: &Settings) -> Option<UtilizationAnalysis> {
+    let thresholds = settings.utilization;
+    let weeks: Vec<WeeklyUtilization> = billable
+        .weeks
+        .iter()
+        .map(|w| {
+            let available = available_hours(settings, w.year, w.week.max(1) as u32);
+            week(w.year, w.week, w.billable_hours, available, thresholds)
+        })
+        .collect();
+    let last = weeks.last()?;
+
+    let recent = &weeks[weeks.len().saturating_sub(CONTRACT_WEEKS)..];
+    let hours: Vec<f64> = recent.iter().map(|w| w.billable_hours).collect();
+    let base = hours.iter().sum::<f64>() / hours.len() as f64;
+    let trend = series_trend(&hours);
+    let slope = if trend.direction == "stable" {
+        0.0
+    } else {
+        trend.slope
+    };
+    let mut period = (last.year, last.week.max(1) as u32);
+    let forecast = (1..=UTILIZATION_FORECAST_WEEKS)
+        .map(|step| {
+            period = next_iso_week(period.0, period.1);
+            let billable = (base + slope * step as f64).max(0.0);
+            let available = available_hours(settings, period.0, period.1);
+            week(period.0, period.1 as i32, billable, available, thresholds)
+        })
+        .collect();
+
+    Some(summary(weeks, forecast, thresholds))
+}
+
+/// Загрузка участников по их настройкам и команды по порогам `thresholds`.
+/// Неделя команды - суммы участников, у которых в эту неделю есть
+/// классифицированные записи
+pub fn team_utilization(team: &TeamInput, thresholds: UtilizationThresholds) -> TeamUtilization {
+    let members: Vec<MemberUtilization> = team
+        .members
+        .iter()
+        .map(|member| {
+            let settings = &member.data.settings;
+            let analyzer = ProductivityAnalyzer::with_preferences(
+                settings.user_preferences.clone(),
+                settings.analyzer.clone(),
+            )
+            .billable_tags(settings.billable_tags.clone());
+            MemberUtilization {
+                id: member.id.clone(),
+                utilization: analyzer
+                    .billable_share(&member.data.timesheets)
+                    .and_then(|billable| analyze(&billable, settings)),
+            }
+        })
+        .collect();
+
+    // (оплачиваемые, доступные) часы команды по неделям
+    let mut weeks: BTreeMap<(i32, i32), (f64, f64)> = BTreeMap::new();
+    let mut forecast: BTreeMap<(i32, i32), (f64, f64)> = BTreeMap::new();
+    for analysis in members.iter().filter_map(|m| m.utilization.as_ref()) {
+        for (target, source) in [
+            (&mut weeks, &analysis.weeks),
+            (&mut forecast, &analysis.forecast),
+        ] {
+            for w in source {
+                let total = target.entry((w.year, w.week)).or_default();
+                total.0 += w.billable_hours;
+                total.1 += w.available_hours;
+            }
+        }
+    }
+    let to_weeks = |totals: BTreeMap<(i32, i32), (f64, f64)>| -> Vec<WeeklyUtilization> {
+        totals
+            .into_iter()
+            .map(|((year, week_number), (billable, available))| {
+                week(year, week_number, billable, available, thresholds)
+            })
+            .collect()
+    };
+    let weeks = to_weeks(weeks);
+    let forecast = to_weeks(forecast);
+    TeamUtilization {
+        team: (!weeks.is_empty()).then(|| summary(weeks, forecast, thresholds)),
+        members,
+    }
+}
+
+fn week(
+    year: i32,
+    week: i32,
+    billable_hours: f64,
+    available_hours: f64,
+    thresholds: UtilizationThresholds,
+) -> WeeklyUtilization {
+    let utilization = ratio(billable_hours, available_hours);
+    WeeklyUtilization {
+        year,
+        week,
+        billable_hours,
+        available_hours,
+        utilization,
+        status: status(utilization, thresholds).to_string(),
+    }
+}
+
+/// Загрузка последних `CONTRACT_WEEKS` недель - отношение сумм, а не среднее
+/// отношений: неделя с праздниками весит меньше
+fn summary(
+    weeks: Vec<WeeklyUtilization>,
+    forecast: Vec<WeeklyUtilization>,
+    thresholds: UtilizationThresholds,
+) -> UtilizationAnalysis {
+    let recent = &weeks[weeks.len().saturating_sub(CONTRACT_WEEKS)..];
+    let recent_utilization = ratio(
+        recent.iter().map(|w| w.billable_hours).sum(),
+        recent.iter().map(|w| w.available_hours).sum(),
+    );
+    UtilizationAnalysis {
+        recent_utilization,
+        status: status(recent_utilization, thresholds).to_string(),
+        weeks,
+        forecast,
+        low_threshold: thresholds.low,
+        high_threshold: thresholds.high,
+    }
+}
+
+fn ratio(billable: f64, available: f64) -> f64 {
+    if available > 0.0 {
+        billable / available
+    } else {
+        0.0
+    }
+}
+
+fn status(utilization: f64, thresholds: UtilizationThresholds) -> &'static str {
+    if utilization < thresholds.low {
+        "low"
+    } else if utilization > thresholds.high {
+        "high"
+    } else {
+        "on_target"
+    }
+}
+
+/// Проверка порогов из запроса: конечные, неотрицательные, `low` не выше `high`
+pub fn validate(thresholds: &UtilizationThresholds) -> Result<(), String> {
+    let valid = |v: f64| v.is_finite() && v >= 0.0;
+    if !valid(thresholds.low) || !valid(thresholds.high) || thresholds.low > thresholds.high {
+        return Err(format!(
+            "Invalid utilization thresholds: low {}, high {}",
+            thresholds.low, thresholds.high
+        ));
+    }
+    Ok(())
+}
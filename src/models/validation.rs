@@ -0,0 +1,91 @@
+//! Детерминированная проверка записей на физически невозможные значения
+
+use chrono::{DateTime, Timelike, Utc};
+
+use crate::types::{AnomalyOutput, TimesheetEntry};
+
+/// Тип аномалий невозможных записей
+pub const INVALID_ANOMALY: &str = "invalid";
+
+/// Самая длинная допустимая запись (минуты)
+pub const MAX_ENTRY_MINUTES: i32 = 24 * 60;
+
+/// Допустимое расхождение длительности с интервалом начала-конца (минуты)
+const DURATION_TOLERANCE_MINUTES: i64 = 1;
+
+/// Невозможные записи: конец раньше начала, длительность не совпадает с
+/// интервалом или превышает сутки, запись из будущего, `hour_of_day` не
+/// совпадает с часом начала. Каждая запись дает одну аномалию `invalid`
+/// с перечислением всех нарушений
+pub fn invalid_entries(entries: &[TimesheetEntry], now: DateTime<Utc>) -> Vec<AnomalyOutput> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let violations = violations(entry, now);
+            (!violations.is_empty()).then(|| AnomalyOutput {
+                entry_id: entry.id,
+                r#type: INVALID_ANOMALY.to_string(),
+                severity: "high".to_string(),
+                reason: violations.join("; "),
+                score: 1.0,
+                period: None,
+                context: None,
+            })
+        })
+        .collect()
+}
+
+fn violations(entry: &TimesheetEntry, now: DateTime<Utc>) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    if entry.duration < 0 {
+        violations.push(format!(
+            "Отрицательная длительность: {} мин",
+            entry.duration
+        ));
+    } else if entry.duration > MAX_ENTRY_MINUTES {
+        violations.push(format!(
+            "Длительность больше суток: {:.1} ч",
+            entry.duration as f64 / 60.0
+        ));
+    }
+
+    let Ok(begin) = DateTime::parse_from_rfc3339(&entry.begin) else {
+        violations.push(format!("Некорректное время начала: {}", entry.begin));
+        return violations;
+    };
+    if begin > now {
+        violations.push(format!("Запись из будущего: начало {}", entry.begin));
+    }
+    if begin.hour() as i32 != entry.hour_of_day {
+        violations.push(format!(
+            "Час записи {} не совпадает с началом в {}:00",
+            entry.hour_of_day,
+            begin.hour()
+        ));
+    }
+
+    match entry.end.as_deref().map(DateTime::parse_from_rfc3339) {
+        Some(Ok(end)) => {
+            let interval = (end - begin).num_minutes();
+            if end < begin {
+                violations.push(format!("Конец {} раньше начала {}", end, begin));
+            } else if (interval - entry.duration as i64).abs() > DURATION_TOLERANCE_MINUTES {
+                violations.push(format!(
+                    "Длительность {} мин не совпадает с интервалом {} мин",
+                    entry.duration, interval
+                ));
+            }
+            if end > now {
+                violations.push(format!("Запись из будущего: конец {}", end));
+            }
+        }
+        Some(Err(_)) => violations.push(format!(
+            "Некорректное время конца: {}",
+            entry.end.as_deref().unwrap_or_default()
+        )),
+        None => {}
+    }
+
+    violations
+}
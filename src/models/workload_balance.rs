@@ -0,0 +1,385 @@
+//! Балансировка нагрузки команды (`/api/team/balance`): жадный перенос недельных
+//! часов проектов от перегруженных участников к недогруженным
+//!
+//! Нагрузка участника - прогноз недельных часов моделью прогноза, обученной на
+//! его истории. Перегруженный участник по очереди отдает свои крупнейшие проекты участнику с
+//! наибольшим запасом емкости. По умолчанию получатели - только те, кто уже
+//! работал над проектом: передача незнакомого проекта дороже, чем подсказывает
+//! разница часов
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::contract::{capacity_hours, sustainable_hours, CONTRACT_WEEKS};
+use crate::models::forecasting::{ForecastBounds, ForecastingModel};
+use crate::models::recommendations::recommendation_id;
+use crate::models::team::{member_weeks, recent_weekly_hours};
+use crate::types::{
+    DataSufficiency, ExpectedImpact, HourTransfer, MemberLoad, RecommendationOutput, TeamBalance,
+    TeamInput, TeamMember, WeekData,
+};
+
+/// Переносы меньше этого не предлагаются; перегрузка и недогрузка меньше этого
+/// не считаются
+pub const MIN_TRANSFER_HOURS: f64 = 1.0;
+
+/// Шаг переносимых часов
+const TRANSFER_STEP_HOURS: f64 = 0.5;
+
+/// Уверенность рекомендации при полной истории последних `CONTRACT_WEEKS` недель
+const FULL_HISTORY_CONFIDENCE: f64 = 0.8;
+
+struct MemberState {
+    /// Прогноз часов в неделю (`forecast_hours`)
+    forecast: f64,
+    capacity: f64,
+    load: f64,
+    /// Часы проектов в неделю за последние `CONTRACT_WEEKS` недель
+    projects: BTreeMap<i32, f64>,
+    history_weeks: usize,
+}
+
+impl MemberState {
+    fn excess(&self) -> f64 {
+        self.load - self.capacity
+    }
+}
+
+/// Переносы часов между участниками `team`. С `allow_new_projects` получателем
+/// может быть и участник, не работавший над проектом
+pub fn balance_workload(team: &TeamInput, allow_new_projects: bool) -> TeamBalance {
+    let mut states: Vec<MemberState> = team.members.iter().map(member_state).collect();
+    let project_names = project_names(team);
+
+    let mut donors: Vec<usize> = (0..states.len())
+        .filter(|&i| states[i].excess() >= MIN_TRANSFER_HOURS)
+        .collect();
+    donors.sort_by(|&a, &b| states[b].excess().total_cmp(&states[a].excess()));
+
+    let mut transfers = Vec::new();
+    for &donor in &donors {
+        let mut projects: Vec<(i32, f64)> = states[donor]
+            .projects
+            .iter()
+            .map(|(&id, &hours)| (id, hours))
+            .collect();
+        projects.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (project_id, mut movable) in projects {
+            loop {
+                let excess = states[donor].excess();
+                let Some((recipient, familiar)) =
+                    pick_recipient(&states, donor, project_id, allow_new_projects)
+                else {
+                    break;
+                };
+                let spare = -states[recipient].excess();
+                let hours = round_down(excess.min(movable).min(spare));
+                if hours < MIN_TRANSFER_HOURS {
+                    break;
+                }
+                movable -= hours;
+                states[donor].load -= hours;
+                states[recipient].load += hours;
+                transfers.push(HourTransfer {
+                    project_id,
+                    project_name: project_names
+                        .get(&project_id)
+                        .cloned()
+                        .unwrap_or_else(|| format!("Проект {}", project_id)),
+                    from: team.members[donor].id.clone(),
+                    to: team.members[recipient].id.clone(),
+                    hours,
+                    familiar,
+                });
+            }
+        }
+    }
+
+    let recommendations = donors
+        .iter()
+        .filter_map(|&donor| recommendation(team, &states, donor, &transfers))
+        .collect();
+    let members = team
+        .members
+        .iter()
+        .zip(&states)
+        .map(|(member, state)| MemberLoad {
+            id: member.id.clone(),
+            forecast_hours: state.forecast,
+            capacity_hours: state.capacity,
+            balanced_hours: state.load,
+            status: status(state.forecast - state.capacity).to_string(),
+        })
+        .collect();
+    TeamBalance {
+        members,
+        transfers,
+        overload_hours_before: total(&states, |s| s.forecast - s.capacity),
+        overload_hours_after: total(&states, |s| s.load - s.capacity),
+        idle_hours_before: total(&states, |s| s.capacity - s.forecast),
+        idle_hours_after: total(&states, |s| s.capacity - s.load),
+        recommendations,
+    }
+}
+
+fn member_state(member: &TeamMember) -> MemberState {
+    let weeks = member_weeks(member);
+    let recent = &weeks[weeks.len().saturating_sub(CONTRACT_WEEKS)..];
+    let mut projects: BTreeMap<i32, f64> = BTreeMap::new();
+    for stats in recent.iter().flat_map(|w| &w.project_stats) {
+        if stats.hours.is_finite() && stats.hours > 0.0 {
+            *projects.entry(stats.project_id).or_default() += stats.hours / recent.len() as f64;
+        }
+    }
+    let forecast = forecast_hours(member, &weeks);
+    MemberState {
+        forecast,
+        capacity: capacity_hours(&member.data.settings),
+        load: forecast,
+        projects,
+        history_weeks: recent.len(),
+    }
+}
+
+/// Недельные часы участника по модели прогноза: с `FULL_WEEKS` недель - обученной
+/// на его истории (`options` участника), с меньшей - по ее запасному прогнозу.
+/// Ошибка обучения - среднее последних `CONTRACT_WEEKS` недель
+fn forecast_hours(member: &TeamMember, weeks: &[WeekData]) -> f64 {
+    let mut model = ForecastingModel::new();
+    let forecast = if DataSufficiency::from_weeks(weeks.len()) == DataSufficiency::Full {
+        model
+            .train_with_options(weeks, member.data.options.as_ref())
+            .and_then(|_| model.predict(weeks))
+    } else {
+        model.predict(weeks)
+    };
+    match forecast {
+        Ok(mut forecast) => {
+            ForecastBounds::default().apply(&mut forecast);
+            forecast.weekly_hours
+        }
+        Err(e) => {
+            tracing::warn!("Forecast for team member {} failed: {}", member.id, e);
+            recent_weekly_hours(weeks)
+        }
+    }
+}
+
+fn project_names(team: &TeamInput) -> HashMap<i32, String> {
+    team.members
+        .iter()
+        .flat_map(|m| &m.data.projects)
+        .map(|p| (p.id, p.name.clone()))
+        .collect()
+}
+
+/// Получатель с наибольшим запасом емкости; знакомые с проектом - первыми
+fn pick_recipient(
+    states: &[MemberState],
+    donor: usize,
+    project_id: i32,
+    allow_new_projects: bool,
+) -> Option<(usize, bool)> {
+    (0..states.len())
+        .filter(|&i| i != donor && -states[i].excess() >= MIN_TRANSFER_HOURS)
+        .map(|i| (i, states[i].projects.contains_key(&project_id)))
+        .filter(|&(_, familiar)| familiar || allow_new_projects)
+        .max_by(|a, b| {
+            a.1.cmp(&b.1)
+                .then((-states[a.0].excess()).total_cmp(&-states[b.0].excess()))
+                .then(b.0.cmp(&a.0))
+        })
+}
+
+/// Вниз до шага; погрешность прогноза (19.999999 ч) шаг не теряет
+fn round_down(hours: f64) -> f64 {
+    ((hours + 1e-3) / TRANSFER_STEP_HOURS).floor() * TRANSFER_STEP_HOURS
+}
+
+fn status(excess: f64) -> &'static str {
+    if excess >= MIN_TRANSFER_HOURS {
+        "overloaded"
+    } else if -excess >= MIN_TRANSFER_HOURS {
+        "idle"
+    } else {
+        "balanced"
+    }
+}
+
+/// Сумма положительных значений `value` по участникам
+fn total(states: &[MemberState], value: impl Fn(&MemberState) -> f64) -> f64 {
+    states.iter().map(|s| value(s).max(0.0)).sum()
+}
+
+/// Рекомендация перегруженному участнику; без переносов от него - `None`
+fn recommendation(
+    team: &TeamInput,
+    states: &[MemberState],
+    donor: usize,
+    transfers: &[HourTransfer],
+) -> Option<RecommendationOutput> {
+    let member = &team.members[donor];
+    let state = &states[donor];
+    let own: Vec<&HourTransfer> = transfers.iter().filter(|t| t.from == member.id).collect();
+    if own.is_empty() {
+        return None;
+    }
+    let moved: f64 = own.iter().map(|t| t.hours).sum();
+    let priority = if state.forecast > sustainable_hours(&member.data.settings) {
+        "high"
+    } else {
+        "medium"
+    };
+    let mut recommendation = RecommendationOutput {
+        id: String::new(),
+        project_id: None,
+        period: None,
+        status: None,
+        first_seen: None,
+        last_seen: None,
+        r#type: "workload_balance".to_string(),
+        priority: priority.to_string(),
+        title: format!("Разгрузите участника {}", member.id),
+        description: format!(
+            "Прогноз {:.1} ч/нед. при емкости {:.1} ч",
+            state.forecast, state.capacity
+        ),
+        action_items: own
+            .iter()
+            .map(|t| {
+                format!(
+                    "Передайте {:.1} ч/нед. проекта '{}' участнику {}",
+                    t.hours, t.project_name, t.to
+                )
+            })
+            .collect(),
+        expected_impact: ExpectedImpact {
+            metric: "overload_hours".to_string(),
+            estimated_delta: Some(-moved),
+            unit: "hours_per_week".to_string(),
+            assumptions: vec![
+                "Прогноз нагрузки - модель прогноза по истории участника".to_string(),
+                format!(
+                    "Часы проектов - средние последних {} недель",
+                    CONTRACT_WEEKS
+                ),
+                "Часы проекта переходят к получателю без потерь на передачу дел".to_string(),
+            ],
+        },
+        metrics: BTreeMap::from([
+            ("forecast_hours".to_string(), state.forecast),
+            ("capacity_hours".to_string(), state.capacity),
+            ("moved_hours".to_string(), moved),
+        ]),
+        confidence: FULL_HISTORY_CONFIDENCE * state.history_weeks as f64 / CONTRACT_WEEKS as f64,
+    };
+    recommendation.id = recommendation_id(&recommendation);
+    Some(recommendation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Участник с 12 одинаковыми неделями: `projects` - часы проектов в неделю
+    fn member(id: &str, projects: &[(i32, f64)]) -> serde_json::Value {
+        let hours: f64 = projects.iter().map(|(_, h)| h).sum();
+        let weeks: Vec<serde_json::Value> = (1..=12)
+            .map(|week| {
+                serde_json::json!({
+                    "year": 2024,
+                    "week": week,
+                    "total_minutes": (hours * 60.0) as i32,
+                    "total_hours": hours,
+                    "total_amount": 0.0,
+                    "project_stats": projects
+                        .iter()
+                        .map(|(id, h)| serde_json::json!({
+                            "project_id": id,
+                            "minutes": (h * 60.0) as i32,
+                            "hours": h,
+                        }))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "id": id,
+            "weeks": weeks,
+            "timesheets": [],
+            "projects": [{
+                "id": 1,
+                "name": "Портал",
+                "total_hours": 0.0,
+                "avg_hours_per_week": 0.0,
+                "weeks_count": 0,
+            }],
+            "settings": {"rate_per_minute": 1.0, "project_settings": {}},
+        })
+    }
+
+    fn team() -> TeamInput {
+        // Емкость всех - 40 ч: alice перегружена на 30 ч, bob знаком с проектом 1
+        // и свободен на 20 ч, carol проекта 1 не знает и свободна на 10 ч
+        serde_json::from_value(serde_json::json!({
+            "members": [
+                member("alice", &[(1, 50.0), (2, 20.0)]),
+                member("bob", &[(1, 20.0)]),
+                member("carol", &[(3, 30.0)]),
+            ],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn overload_goes_to_familiar_members_first() {
+        let balance = balance_workload(&team(), false);
+        let loads: Vec<(&str, &str)> = balance
+            .members
+            .iter()
+            .map(|m| (m.id.as_str(), m.status.as_str()))
+            .collect();
+        assert_eq!(
+            loads,
+            vec![("alice", "overloaded"), ("bob", "idle"), ("carol", "idle")]
+        );
+        assert!((balance.members[0].forecast_hours - 70.0).abs() < 1.0);
+
+        // Незнакомой carol проект не передается: перегрузка остается
+        assert_eq!(balance.transfers.len(), 1);
+        let transfer = &balance.transfers[0];
+        assert_eq!(
+            (transfer.from.as_str(), transfer.to.as_str()),
+            ("alice", "bob")
+        );
+        assert_eq!(
+            (transfer.project_id, transfer.project_name.as_str()),
+            (1, "Портал")
+        );
+        assert!(transfer.familiar);
+        assert!((transfer.hours - 20.0).abs() <= 1.0, "{}", transfer.hours);
+        assert!(balance.overload_hours_after > 9.0);
+        assert!(balance.overload_hours_after < balance.overload_hours_before);
+
+        assert_eq!(balance.recommendations.len(), 1);
+        let recommendation = &balance.recommendations[0];
+        assert_eq!(recommendation.r#type, "workload_balance");
+        assert_eq!(recommendation.priority, "high");
+        assert_eq!(recommendation.action_items.len(), 1);
+    }
+
+    #[test]
+    fn new_projects_reach_unfamiliar_members_when_allowed() {
+        let balance = balance_workload(&team(), true);
+        let transfers: Vec<(&str, i32, bool)> = balance
+            .transfers
+            .iter()
+            .map(|t| (t.to.as_str(), t.project_id, t.familiar))
+            .collect();
+        // Сначала знакомый bob, остаток - carol
+        assert_eq!(transfers, vec![("bob", 1, true), ("carol", 1, false)]);
+        let moved: f64 = balance.transfers.iter().map(|t| t.hours).sum();
+        assert!((moved - 30.0).abs() <= 1.0, "{}", moved);
+        assert!(balance.overload_hours_after < 1.0);
+        assert!(balance.idle_hours_after < balance.idle_hours_before);
+    }
+}
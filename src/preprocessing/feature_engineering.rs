@@ -1,74 +1,332 @@
 //! Feature engineering для ML моделей
 
+use chrono::{Datelike, NaiveDate, Weekday};
 use ndarray::{Array1, Array2};
-use std::f64::consts::PI;
+use serde::{Deserialize, Serialize};
+use std::borrow::{Borrow, Cow};
+use std::collections::HashMap;
 
-use crate::types::{TimesheetEntry, WeekData};
+use crate::float::{consts::PI, Float};
+use crate::types::{ProjectStats, TimesheetEntry, WeekData};
+
+/// Версия набора признаков; увеличивается при любом изменении порядка или смысла столбцов
+pub const FEATURE_LAYOUT_VERSION: u32 = 3;
+
+/// Столбцы матрицы `extract_temporal_features`. Календарные признаки берутся по
+/// четвергу ISO-недели (он определяет ее год); `week_index` - сквозной номер
+/// недели, непрерывный через границы лет, `week_sin`/`week_cos` - доля года
+/// (годы с 53 неделями кодируются так же, как остальные). Сырого номера недели
+/// нет: на стыке лет он скачет с 52-53 на 1
+pub const TEMPORAL_FEATURES: [&str; 12] = [
+    "week_index",
+    "month",
+    "week_sin",
+    "week_cos",
+    "month_sin",
+    "month_cos",
+    "lag_1",
+    "rolling_mean_4",
+    "rolling_mean_8",
+    "trend_4",
+    "volatility_4",
+    "reserved",
+];
+
+/// Столбцы матрицы `extract_anomaly_features`
+pub const ANOMALY_FEATURES: [&str; 5] = [
+    "duration_norm",
+    "hour_norm",
+    "day_of_week_norm",
+    "project_duration_ratio",
+    "tag_count",
+];
+
+/// Четверг ISO-недели; номер недели вне года ограничивается его неделями.
+/// Ошибка - год вне диапазона дат
+fn week_thursday(year: i32, week: i32) -> Result<NaiveDate, String> {
+    let invalid = || format!("Invalid ISO week {}-W{:02}", year, week);
+    let weeks_in_year = NaiveDate::from_ymd_opt(year, 12, 28)
+        .ok_or_else(invalid)?
+        .iso_week()
+        .week();
+    NaiveDate::from_isoywd_opt(
+        year,
+        week.clamp(1, weeks_in_year as i32) as u32,
+        Weekday::Thu,
+    )
+    .ok_or_else(invalid)
+}
+
+/// Описание раскладки признаков, сохраняемое вместе с моделью
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureLayout {
+    pub version: u32,
+    pub features: Vec<String>,
+}
+
+impl FeatureLayout {
+    fn from_names(names: &[&str]) -> Self {
+        Self {
+            version: FEATURE_LAYOUT_VERSION,
+            features: names.iter().map(|n| n.to_string()).collect(),
+        }
+    }
+}
+
+/// Что исправлено в неделях перед извлечением признаков
+/// (`FeatureEngineer::canonical_weeks`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WeekOrderReport {
+    /// Недели пришли не по порядку ISO-года и недели
+    pub reordered: bool,
+    /// Недели (`2024-W05`), присланные несколько раз: точные повторы
+    /// отбрасываются, различающиеся части недели складываются
+    pub merged: Vec<String>,
+    /// Пропуски: неделя перед пропуском и число отсутствующих недель
+    pub gaps: Vec<(String, i64)>,
+    /// Недели с годом вне диапазона дат: в признаки не попадают
+    #[serde(default)]
+    pub skipped: Vec<String>,
+}
+
+impl WeekOrderReport {
+    pub fn is_clean(&self) -> bool {
+        !self.reordered && self.merged.is_empty() && self.gaps.is_empty() && self.skipped.is_empty()
+    }
+
+    /// Предупреждения для ответа API
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.reordered {
+            warnings.push("weeks were not in chronological order and have been sorted".to_string());
+        }
+        if !self.merged.is_empty() {
+            warnings.push(format!(
+                "duplicate weeks merged: {}",
+                self.merged.join(", ")
+            ));
+        }
+        for (week, missing) in &self.gaps {
+            warnings.push(format!("{} week(s) missing after {}", missing, week));
+        }
+        if !self.skipped.is_empty() {
+            warnings.push(format!(
+                "weeks with invalid dates skipped: {}",
+                self.skipped.join(", ")
+            ));
+        }
+        warnings
+    }
+}
+
+fn week_label(week: &WeekData) -> String {
+    format!("{}-W{:02}", week.year, week.week)
+}
+
+/// Одинаковые ли итоги и проекты у двух записей одной недели
+fn same_totals(a: &WeekData, b: &WeekData) -> bool {
+    let stats = |w: &WeekData| {
+        let mut stats: Vec<(i32, i32, u64)> = w
+            .project_stats
+            .iter()
+            .map(|s| (s.project_id, s.minutes, s.hours.to_bits()))
+            .collect();
+        stats.sort_unstable();
+        stats
+    };
+    a.total_minutes == b.total_minutes
+        && a.total_hours.to_bits() == b.total_hours.to_bits()
+        && a.total_amount.to_bits() == b.total_amount.to_bits()
+        && stats(a) == stats(b)
+}
+
+/// Складывает вторую часть недели в первую; проекты объединяются по `project_id`
+fn merge_week(into: &mut WeekData, part: &WeekData) {
+    into.total_minutes += part.total_minutes;
+    into.total_hours += part.total_hours;
+    into.total_amount += part.total_amount;
+    for stats in &part.project_stats {
+        match into
+            .project_stats
+            .iter_mut()
+            .find(|s| s.project_id == stats.project_id)
+        {
+            Some(existing) => {
+                existing.minutes += stats.minutes;
+                existing.hours += stats.hours;
+            }
+            None => into.project_stats.push(ProjectStats {
+                project_id: stats.project_id,
+                minutes: stats.minutes,
+                hours: stats.hours,
+            }),
+        }
+    }
+}
 
 pub struct FeatureEngineer;
 
 impl FeatureEngineer {
     //! Извлечение временных признаков из недель
+
+    /// Раскладка признаков прогнозирования
+    pub fn temporal_layout() -> FeatureLayout {
+        FeatureLayout::from_names(&TEMPORAL_FEATURES)
+    }
+
+    /// Раскладка признаков обнаружения аномалий
+    pub fn anomaly_layout() -> FeatureLayout {
+        FeatureLayout::from_names(&ANOMALY_FEATURES)
+    }
+
+    /// Недели по порядку ISO-года и недели без повторов; лаги и скользящие
+    /// средние считаются по соседним строкам и без этого теряют смысл.
+    /// Недели с годом вне диапазона дат отбрасываются (`WeekOrderReport::skipped`).
+    /// Упорядоченные недели без повторов возвращаются без копирования
+    pub fn canonical_weeks(weeks: &[WeekData]) -> (Cow<'_, [WeekData]>, WeekOrderReport) {
+        let mut report = WeekOrderReport::default();
+        // Четверг каждой недели - ключ порядка и повторов
+        let mut dated: Vec<(NaiveDate, &WeekData)> = Vec::with_capacity(weeks.len());
+        for week in weeks {
+            match week_thursday(week.year, week.week) {
+                Ok(thursday) => dated.push((thursday, week)),
+                Err(_) => report.skipped.push(week_label(week)),
+            }
+        }
+        report.reordered = dated.windows(2).any(|pair| pair[0].0 > pair[1].0);
+        let has_duplicates = dated.windows(2).any(|pair| pair[0].0 == pair[1].0);
+
+        let (weeks, dates): (Cow<'_, [WeekData]>, Vec<NaiveDate>) =
+            if report.reordered || has_duplicates || !report.skipped.is_empty() {
+                // Сортировка устойчива: из повторов первой остается присланная раньше
+                dated.sort_by_key(|&(thursday, _)| thursday);
+                let mut canonical: Vec<WeekData> = Vec::with_capacity(dated.len());
+                let mut dates: Vec<NaiveDate> = Vec::with_capacity(dated.len());
+                // Повторы недели, уже учтенные в ней: точная копия любого из них отбрасывается
+                let mut parts: Vec<&WeekData> = Vec::new();
+                for (thursday, week) in dated {
+                    match canonical.last_mut() {
+                        Some(last) if dates.last() == Some(&thursday) => {
+                            let label = week_label(week);
+                            if !report.merged.contains(&label) {
+                                report.merged.push(label);
+                            }
+                            if !parts.iter().any(|part| same_totals(part, week)) {
+                                merge_week(last, week);
+                                parts.push(week);
+                            }
+                        }
+                        _ => {
+                            parts = vec![week];
+                            canonical.push(week.clone());
+                            dates.push(thursday);
+                        }
+                    }
+                }
+                (Cow::Owned(canonical), dates)
+            } else {
+                (
+                    Cow::Borrowed(weeks),
+                    dated.into_iter().map(|(thursday, _)| thursday).collect(),
+                )
+            };
+
+        for (pair, week) in dates.windows(2).zip(weeks.iter()) {
+            let missing = (pair[1] - pair[0]).num_days() / 7 - 1;
+            if missing > 0 {
+                report.gaps.push((week_label(week), missing));
+            }
+        }
+        (weeks, report)
+    }
+
     pub fn extract_temporal_features(
         weeks: &[WeekData],
-    ) -> Result<(Array2<f64>, Array1<f64>), String> {
+    ) -> Result<(Array2<Float>, Array1<Float>), String> {
+        Self::extract_temporal_features_with_report(weeks)
+            .map(|(features, targets, _)| (features, targets))
+    }
+
+    /// Признаки недель после `canonical_weeks` и отчет о том, что в неделях исправлено
+    pub fn extract_temporal_features_with_report(
+        weeks: &[WeekData],
+    ) -> Result<(Array2<Float>, Array1<Float>, WeekOrderReport), String> {
         if weeks.is_empty() {
             return Err("No weeks provided".to_string());
         }
+        let (weeks, report) = Self::canonical_weeks(weeks);
+        if weeks.is_empty() {
+            return Err(format!(
+                "No valid weeks provided: {}",
+                report.skipped.join(", ")
+            ));
+        }
 
         let n_samples = weeks.len();
-        let n_features = 13; // Количество признаков
+        let n_features = TEMPORAL_FEATURES.len(); // Количество признаков
 
         let mut features = Array2::zeros((n_samples, n_features));
         let mut targets = Array1::zeros(n_samples);
 
         for (i, week) in weeks.iter().enumerate() {
             let mut feature_idx = 0;
+            let thursday = week_thursday(week.year, week.week)?;
 
             // Базовые временные признаки
-            features[[i, feature_idx]] = week.week as f64;
-            feature_idx += 1;
-            features[[i, feature_idx]] = week.year as f64;
+            // Недели с 1970-01-01 (`NaiveDate::default`)
+            let days = (thursday - NaiveDate::default()).num_days();
+            features[[i, feature_idx]] = days.div_euclid(7) as Float;
             feature_idx += 1;
-
-            // Месяц (приблизительно из недели)
-            let month = ((week.week - 1) / 4) + 1;
-            features[[i, feature_idx]] = month as f64;
+            let month = thursday.month0();
+            features[[i, feature_idx]] = (month + 1) as Float;
             feature_idx += 1;
 
-            // Циклические признаки
-            features[[i, feature_idx]] = (2.0 * PI * week.week as f64 / 52.0).sin();
+            // Циклические признаки: доля года по дню четверга и месяц
+            let days_in_year = if thursday.leap_year() { 366.0 } else { 365.0 };
+            let year_phase = thursday.ordinal0() as Float / days_in_year;
+            features[[i, feature_idx]] = (2.0 * PI * year_phase).sin();
             feature_idx += 1;
-            features[[i, feature_idx]] = (2.0 * PI * week.week as f64 / 52.0).cos();
+            features[[i, feature_idx]] = (2.0 * PI * year_phase).cos();
             feature_idx += 1;
-            features[[i, feature_idx]] = (2.0 * PI * month as f64 / 12.0).sin();
+            features[[i, feature_idx]] = (2.0 * PI * month as Float / 12.0).sin();
             feature_idx += 1;
-            features[[i, feature_idx]] = (2.0 * PI * month as f64 / 12.0).cos();
+            features[[i, feature_idx]] = (2.0 * PI * month as Float / 12.0).cos();
             feature_idx += 1;
 
             // Исторические признаки
             if i > 0 {
-                features[[i, feature_idx]] = weeks[i - 1].total_hours;
+                features[[i, feature_idx]] = weeks[i - 1].total_hours as Float;
             }
             feature_idx += 1;
 
             if i >= 4 {
-                let avg: f64 = weeks[i - 4..i].iter().map(|w| w.total_hours).sum::<f64>() / 4.0;
+                let avg: Float = weeks[i - 4..i]
+                    .iter()
+                    .map(|w| w.total_hours as Float)
+                    .sum::<Float>()
+                    / 4.0;
                 features[[i, feature_idx]] = avg;
             }
             feature_idx += 1;
 
             if i >= 8 {
-                let avg: f64 = weeks[i - 8..i].iter().map(|w| w.total_hours).sum::<f64>() / 8.0;
+                let avg: Float = weeks[i - 8..i]
+                    .iter()
+                    .map(|w| w.total_hours as Float)
+                    .sum::<Float>()
+                    / 8.0;
                 features[[i, feature_idx]] = avg;
             }
             feature_idx += 1;
 
             // Тренд (упрощенный)
             if i >= 4 {
-                let recent: Vec<f64> = weeks[i - 4..i].iter().map(|w| w.total_hours).collect();
+                let recent: Vec<Float> = weeks[i - 4..i]
+                    .iter()
+                    .map(|w| w.total_hours as Float)
+                    .collect();
                 if recent.len() >= 2 {
-                    let trend = (recent[recent.len() - 1] - recent[0]) / recent.len() as f64;
+                    let trend = (recent[recent.len() - 1] - recent[0]) / recent.len() as Float;
                     features[[i, feature_idx]] = trend;
                 }
             }
@@ -76,76 +334,255 @@ impl FeatureEngineer {
 
             // Волатильность
             if i >= 4 {
-                let values: Vec<f64> = weeks[i - 4..i].iter().map(|w| w.total_hours).collect();
-                let mean = values.iter().sum::<f64>() / values.len() as f64;
-                let variance =
-                    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+                let values: Vec<Float> = weeks[i - 4..i]
+                    .iter()
+                    .map(|w| w.total_hours as Float)
+                    .collect();
+                let mean = values.iter().sum::<Float>() / values.len() as Float;
+                let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<Float>()
+                    / values.len() as Float;
                 features[[i, feature_idx]] = variance.sqrt();
             }
 
             // Целевая переменная
-            targets[i] = week.total_hours;
+            targets[i] = week.total_hours as Float;
         }
 
-        Ok((features, targets))
+        Ok((features, targets, report))
     }
 
     /// Извлечение признаков для обнаружения аномалий
-    pub fn extract_anomaly_features(entries: &[TimesheetEntry]) -> Array2<f64> {
-        if entries.is_empty() {
-            return Array2::zeros((0, 5));
-        }
+    pub fn extract_anomaly_features(entries: &[TimesheetEntry]) -> Array2<Float> {
+        Self::extract_anomaly_features_iter(entries)
+    }
+
+    /// Потоковое извлечение признаков аномалий.
+    ///
+    /// Записи читаются за один проход и не накапливаются: в памяти остается
+    /// только итоговая матрица и суммы длительностей по проектам, поэтому
+    /// очень большие выгрузки можно подавать прямо из десериализатора.
+    pub fn extract_anomaly_features_iter<I>(entries: I) -> Array2<Float>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<TimesheetEntry>,
+    {
+        let entries = entries.into_iter();
+        let n_features = ANOMALY_FEATURES.len();
+        let (capacity, _) = entries.size_hint();
+
+        let mut data: Vec<Float> = Vec::with_capacity(capacity * n_features);
+        let mut row_projects: Vec<Option<i32>> = Vec::with_capacity(capacity);
+        // project_id -> (сумма длительностей, количество записей)
+        let mut project_totals: HashMap<i32, (Float, usize)> = HashMap::new();
 
-        // Вычисляем среднюю длительность по проектам
-        use std::collections::HashMap;
-        let mut project_durations: HashMap<i32, Vec<i32>> = HashMap::new();
         for entry in entries {
+            let entry = entry.borrow();
+            let duration = entry.duration as Float;
+
             if let Some(project_id) = entry.project_id {
-                project_durations
-                    .entry(project_id)
-                    .or_default()
-                    .push(entry.duration);
+                let totals = project_totals.entry(project_id).or_insert((0.0, 0));
+                totals.0 += duration;
+                totals.1 += 1;
             }
-        }
 
-        let mut project_avg: HashMap<i32, f64> = HashMap::new();
-        for (project_id, durations) in project_durations {
-            let avg = durations.iter().sum::<i32>() as f64 / durations.len() as f64;
-            project_avg.insert(project_id, avg);
+            data.extend_from_slice(&[
+                // Нормализованная длительность (0-1, нормализация к 8 часам)
+                (duration / (8.0 * 60.0)).min(1.0),
+                // Время дня (0-1)
+                entry.hour_of_day as Float / 23.0,
+                // День недели (0-1)
+                entry.day_of_week as Float / 6.0,
+                // Сырая длительность; заменяется отношением к среднему по проекту ниже
+                duration,
+                // Количество тегов
+                entry.tags.len() as Float,
+            ]);
+            row_projects.push(entry.project_id);
         }
 
-        let n_samples = entries.len();
-        let n_features = 5;
-        let mut features = Array2::zeros((n_samples, n_features));
-
-        for (i, entry) in entries.iter().enumerate() {
-            // Нормализованная длительность (0-1, нормализация к 8 часам)
-            let duration_norm = (entry.duration as f64 / (8.0 * 60.0)).min(1.0);
-            features[[i, 0]] = duration_norm;
-
-            // Время дня (0-1)
-            features[[i, 1]] = entry.hour_of_day as f64 / 23.0;
-
-            // День недели (0-1)
-            features[[i, 2]] = entry.day_of_week as f64 / 6.0;
-
-            // Отношение к среднему по проекту
-            let project_avg_val = entry
-                .project_id
-                .and_then(|id| project_avg.get(&id))
-                .copied()
-                .unwrap_or(entry.duration as f64);
-            let duration_ratio = if project_avg_val > 0.0 {
-                (entry.duration as f64 / project_avg_val).min(5.0)
+        // Отношение к среднему по проекту (проход по готовой матрице, а не по записям)
+        for (row, project_id) in data.chunks_exact_mut(n_features).zip(&row_projects) {
+            let duration = row[3];
+            let project_avg_val = project_id
+                .and_then(|id| project_totals.get(&id))
+                .map(|&(sum, count)| sum / count as Float)
+                .unwrap_or(duration);
+            row[3] = if project_avg_val > 0.0 {
+                (duration / project_avg_val).min(5.0)
             } else {
                 1.0
             };
-            features[[i, 3]] = duration_ratio;
+        }
+
+        Array2::from_shape_vec((row_projects.len(), n_features), data)
+            .unwrap_or_else(|_| Array2::zeros((0, n_features)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            // Количество тегов
-            features[[i, 4]] = entry.tags.len() as f64;
+    fn week(year: i32, week: i32, hours: f64) -> WeekData {
+        WeekData {
+            year,
+            week,
+            total_minutes: (hours * 60.0) as i32,
+            total_hours: hours,
+            total_amount: 0.0,
+            project_stats: Vec::new(),
         }
+    }
+
+    fn column(name: &str) -> usize {
+        TEMPORAL_FEATURES.iter().position(|f| *f == name).unwrap()
+    }
+
+    /// Признаки недель подряд через стык лет: сквозной номер растет на 1,
+    /// точка (sin, cos) сдвигается на неделю, а не на год
+    fn assert_continuous(weeks: &[WeekData]) {
+        let (features, _) = FeatureEngineer::extract_temporal_features(weeks).unwrap();
+        assert_eq!(features.nrows(), weeks.len());
+        let (index, sin, cos) = (column("week_index"), column("week_sin"), column("week_cos"));
+        // Хорда между соседними неделями на единичной окружности ~ 2π·7/365
+        let step = 2.0 * PI * 7.0 / 365.0;
+        for i in 1..features.nrows() {
+            assert_eq!(features[[i, index]] - features[[i - 1, index]], 1.0);
+            let distance = ((features[[i, sin]] - features[[i - 1, sin]]).powi(2)
+                + (features[[i, cos]] - features[[i - 1, cos]]).powi(2))
+            .sqrt();
+            assert!(distance < step * 1.5, "week {} jumped by {}", i, distance);
+        }
+    }
+
+    #[test]
+    fn layout_has_no_raw_week_number() {
+        assert!(!TEMPORAL_FEATURES.contains(&"week"));
+        assert_eq!(
+            FeatureEngineer::temporal_layout().version,
+            FEATURE_LAYOUT_VERSION
+        );
+    }
+
+    #[test]
+    fn features_continuous_across_53_week_year() {
+        // 2020 - год с 53 неделями
+        assert_eq!(
+            week_thursday(2020, 53).unwrap(),
+            NaiveDate::from_ymd_opt(2020, 12, 31).unwrap()
+        );
+        assert_continuous(&[
+            week(2020, 51, 30.0),
+            week(2020, 52, 31.0),
+            week(2020, 53, 32.0),
+            week(2021, 1, 33.0),
+            week(2021, 2, 34.0),
+        ]);
+    }
+
+    #[test]
+    fn features_continuous_across_52_week_year() {
+        assert_continuous(&[
+            week(2023, 51, 30.0),
+            week(2023, 52, 31.0),
+            week(2024, 1, 32.0),
+            week(2024, 2, 33.0),
+        ]);
+    }
+
+    #[test]
+    fn week_starting_in_december_belongs_to_january() {
+        // 2025-W01 начинается в понедельник 2024-12-30
+        let (features, _) = FeatureEngineer::extract_temporal_features(&[
+            week(2024, 52, 30.0),
+            week(2025, 1, 31.0),
+        ])
+        .unwrap();
+        assert_eq!(features[[0, column("month")]], 12.0);
+        assert_eq!(features[[1, column("month")]], 1.0);
+        assert_eq!(
+            features[[1, column("week_index")]] - features[[0, column("week_index")]],
+            1.0
+        );
+    }
+
+    #[test]
+    fn week_53_in_52_week_year_is_clamped() {
+        assert_eq!(week_thursday(2023, 53), week_thursday(2023, 52));
+        let input = [week(2023, 53, 30.0), week(2024, 1, 31.0)];
+        let (weeks, report) = FeatureEngineer::canonical_weeks(&input);
+        assert_eq!(weeks.len(), 2);
+        assert!(report.gaps.is_empty());
+    }
+
+    #[test]
+    fn weeks_outside_date_range_are_skipped() {
+        assert!(week_thursday(i32::MAX, 1).is_err());
+        let input = [
+            week(2024, 1, 30.0),
+            week(i32::MAX, 1, 99.0),
+            week(2024, 2, 31.0),
+        ];
+        let (weeks, report) = FeatureEngineer::canonical_weeks(&input);
+        assert_eq!(weeks.len(), 2);
+        assert_eq!(report.skipped, vec![format!("{}-W01", i32::MAX)]);
+        assert!(report.gaps.is_empty());
+        assert!(!report.is_clean());
+
+        let error = FeatureEngineer::extract_temporal_features(&[week(i32::MIN, 10, 30.0)]);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn unsorted_duplicate_and_missing_weeks_are_canonicalized() {
+        let mut part = week(2024, 3, 5.0);
+        part.total_amount = 50.0;
+        part.project_stats = vec![ProjectStats {
+            project_id: 7,
+            minutes: 300,
+            hours: 5.0,
+        }];
+        let input = [
+            week(2024, 3, 30.0),
+            week(2024, 1, 10.0),
+            // Точный повтор отбрасывается, другая часть недели складывается
+            week(2024, 3, 30.0),
+            part,
+            week(2024, 6, 20.0),
+        ];
+
+        let (weeks, report) = FeatureEngineer::canonical_weeks(&input);
+        let order: Vec<(i32, f64)> = weeks.iter().map(|w| (w.week, w.total_hours)).collect();
+        assert_eq!(order, vec![(1, 10.0), (3, 35.0), (6, 20.0)]);
+        assert_eq!(weeks[1].total_minutes, 35 * 60);
+        assert_eq!(weeks[1].total_amount, 50.0);
+        assert_eq!(weeks[1].project_stats.len(), 1);
+        assert_eq!(
+            report,
+            WeekOrderReport {
+                reordered: true,
+                merged: vec!["2024-W03".to_string()],
+                gaps: vec![("2024-W01".to_string(), 1), ("2024-W03".to_string(), 2)],
+                skipped: Vec::new(),
+            }
+        );
+        assert_eq!(
+            report.warnings(),
+            vec![
+                "weeks were not in chronological order and have been sorted",
+                "duplicate weeks merged: 2024-W03",
+                "1 week(s) missing after 2024-W01",
+                "2 week(s) missing after 2024-W03",
+            ]
+        );
 
-        features
+        // Лаг берется из предыдущей недели по порядку, а не по входу
+        let (features, targets, with_report) =
+            FeatureEngineer::extract_temporal_features_with_report(&input).unwrap();
+        assert_eq!(with_report, report);
+        assert_eq!(features.nrows(), 3);
+        assert_eq!(features[[1, column("lag_1")]], 10.0);
+        assert_eq!(features[[2, column("lag_1")]], 35.0);
+        assert_eq!(targets.to_vec(), vec![10.0, 35.0, 20.0]);
     }
 }
@@ -0,0 +1,215 @@
+//! Отбор признаков
+
+#![allow(non_snake_case)]
+
+use ndarray::{Array1, Array2, ArrayView1, Axis};
+use serde::{Deserialize, Serialize};
+
+use crate::float::Float;
+
+/// Критерий одномерной оценки признака
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectionCriterion {
+    /// Модуль коэффициента корреляции Пирсона с целевой переменной
+    Correlation,
+    /// Взаимная информация (оценка по гистограмме), нормированная к [0, 1]
+    MutualInformation,
+}
+
+impl SelectionCriterion {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "correlation" => Some(Self::Correlation),
+            "mutual_information" | "mi" => Some(Self::MutualInformation),
+            _ => None,
+        }
+    }
+}
+
+/// Одномерный отбор признаков: признаки со слабой связью с целевой
+/// переменной (или постоянные) отбрасываются.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeatureSelector {
+    criterion: SelectionCriterion,
+    min_score: Float,
+    scores: Option<Vec<Float>>,
+    selected: Option<Vec<usize>>,
+}
+
+impl FeatureSelector {
+    pub fn new(criterion: SelectionCriterion, min_score: Float) -> Self {
+        Self {
+            criterion,
+            min_score,
+            scores: None,
+            selected: None,
+        }
+    }
+
+    pub fn fit(&mut self, X: &Array2<Float>, y: &Array1<Float>) -> Result<(), String> {
+        if X.nrows() == 0 || X.ncols() == 0 {
+            return Err("Empty dataset".to_string());
+        }
+        if X.nrows() != y.len() {
+            return Err("Features and targets have different lengths".to_string());
+        }
+
+        let scores: Vec<Float> = X
+            .axis_iter(Axis(1))
+            .map(|column| match self.criterion {
+                SelectionCriterion::Correlation => correlation(column, y.view()).abs(),
+                SelectionCriterion::MutualInformation => mutual_information(column, y.view()),
+            })
+            .collect();
+
+        let mut selected: Vec<usize> = scores
+            .iter()
+            .enumerate()
+            .filter(|(_, &score)| score >= self.min_score)
+            .map(|(i, _)| i)
+            .collect();
+
+        // Оставляем хотя бы один признак — лучший по оценке
+        if selected.is_empty() {
+            let best = scores
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            selected.push(best);
+        }
+
+        self.scores = Some(scores);
+        self.selected = Some(selected);
+        Ok(())
+    }
+
+    pub fn transform(&self, X: &Array2<Float>) -> Result<Array2<Float>, String> {
+        let selected = self.selected.as_ref().ok_or("Selector not fitted")?;
+        if let Some(&max_idx) = selected.iter().max() {
+            if max_idx >= X.ncols() {
+                return Err("Feature count mismatch".to_string());
+            }
+        }
+        Ok(X.select(Axis(1), selected))
+    }
+
+    pub fn fit_transform(
+        &mut self,
+        X: &Array2<Float>,
+        y: &Array1<Float>,
+    ) -> Result<Array2<Float>, String> {
+        self.fit(X, y)?;
+        self.transform(X)
+    }
+
+    /// Индексы оставленных признаков (после `fit`)
+    pub fn selected_features(&self) -> Option<&[usize]> {
+        self.selected.as_deref()
+    }
+
+    /// Оценки всех признаков (после `fit`)
+    pub fn scores(&self) -> Option<&[Float]> {
+        self.scores.as_deref()
+    }
+}
+
+impl Default for FeatureSelector {
+    fn default() -> Self {
+        Self::new(SelectionCriterion::Correlation, 0.05)
+    }
+}
+
+fn correlation(x: ArrayView1<Float>, y: ArrayView1<Float>) -> Float {
+    let n = x.len() as Float;
+    if n < 2.0 {
+        return 0.0;
+    }
+    let x_mean = x.sum() / n;
+    let y_mean = y.sum() / n;
+
+    let mut cov = 0.0;
+    let mut x_var = 0.0;
+    let mut y_var = 0.0;
+    for (xi, yi) in x.iter().zip(y.iter()) {
+        let dx = xi - x_mean;
+        let dy = yi - y_mean;
+        cov += dx * dy;
+        x_var += dx * dx;
+        y_var += dy * dy;
+    }
+
+    // Постоянный признак не несет информации
+    if x_var < 1e-10 || y_var < 1e-10 {
+        return 0.0;
+    }
+    cov / (x_var.sqrt() * y_var.sqrt())
+}
+
+fn mutual_information(x: ArrayView1<Float>, y: ArrayView1<Float>) -> Float {
+    let n = x.len();
+    if n < 2 {
+        return 0.0;
+    }
+    // Правило квадратного корня для числа корзин
+    let bins = ((n as Float).sqrt().round() as usize).clamp(2, 16);
+
+    let x_bins = discretize(x, bins);
+    let y_bins = discretize(y, bins);
+    let (Some(x_bins), Some(y_bins)) = (x_bins, y_bins) else {
+        return 0.0;
+    };
+
+    let mut joint = vec![0.0; bins * bins];
+    let mut px = vec![0.0; bins];
+    let mut py = vec![0.0; bins];
+    for (&bx, &by) in x_bins.iter().zip(y_bins.iter()) {
+        joint[bx * bins + by] += 1.0;
+        px[bx] += 1.0;
+        py[by] += 1.0;
+    }
+
+    let total = n as Float;
+    let mut mi = 0.0;
+    for bx in 0..bins {
+        for by in 0..bins {
+            let pxy = joint[bx * bins + by] / total;
+            if pxy > 0.0 {
+                mi += pxy * (pxy / ((px[bx] / total) * (py[by] / total))).ln();
+            }
+        }
+    }
+
+    // Нормировка на энтропию целевой переменной
+    let hy: Float = py
+        .iter()
+        .filter(|&&c| c > 0.0)
+        .map(|&c| {
+            let p = c / total;
+            -p * p.ln()
+        })
+        .sum();
+    if hy > 0.0 {
+        (mi / hy).clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// Равномерное разбиение на корзины; `None` для постоянного столбца
+fn discretize(values: ArrayView1<Float>, bins: usize) -> Option<Vec<usize>> {
+    let min_val = values.iter().copied().fold(Float::INFINITY, Float::min);
+    let max_val = values.iter().copied().fold(Float::NEG_INFINITY, Float::max);
+    let range = max_val - min_val;
+    if range.abs() < 1e-10 {
+        return None;
+    }
+    Some(
+        values
+            .iter()
+            .map(|v| (((v - min_val) / range * bins as Float) as usize).min(bins - 1))
+            .collect(),
+    )
+}
@@ -1,7 +1,15 @@
 //! Модуль предобработки данных
 
 pub mod feature_engineering;
+pub mod feature_selection;
+pub mod non_finite;
 pub mod normalization;
+pub mod pipeline;
+pub mod winsorization;
 
-pub use feature_engineering::FeatureEngineer;
+pub use feature_engineering::{FeatureEngineer, FeatureLayout, WeekOrderReport};
+pub use feature_selection::{FeatureSelector, SelectionCriterion};
+pub use non_finite::{sanitize_weeks, NonFinitePolicy};
 pub use normalization::DataNormalizer;
+pub use pipeline::{PreprocessingState, PREPROCESSING_VERSION};
+pub use winsorization::Winsorizer;
@@ -0,0 +1,174 @@
+//! Пропуски (NaN) и бесконечности в недельных данных
+//!
+//! Одно нечисловое `total_hours` проходит через веса недель и признаки в каждый
+//! прогноз модели, поэтому недели проверяются до извлечения признаков
+
+use std::borrow::Cow;
+
+use serde_json::Value as JsonValue;
+
+use crate::types::WeekData;
+
+/// Что делать с недельными значениями NaN и ±Inf (`options.non_finite`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    /// Ошибка с перечнем недель
+    Reject,
+    /// Часы - из минут недели или проекта, иначе (и для выручки) - медиана
+    /// конечных значений остальных недель
+    #[default]
+    Impute,
+}
+
+impl NonFinitePolicy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "reject" => Some(Self::Reject),
+            "impute" => Some(Self::Impute),
+            _ => None,
+        }
+    }
+
+    /// Политика из `options.non_finite` запроса; `None`, если не задана
+    pub fn from_options(options: Option<&JsonValue>) -> Result<Option<Self>, String> {
+        match options
+            .and_then(|o| o.get("non_finite"))
+            .and_then(|v| v.as_str())
+        {
+            Some(name) => Self::from_name(name)
+                .map(Some)
+                .ok_or_else(|| format!("Unknown non_finite policy: {}", name)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Недели без NaN и бесконечностей в часах и выручке. Без таких значений
+/// возвращаются исходные недели без копирования
+pub fn sanitize_weeks(
+    weeks: &[WeekData],
+    policy: NonFinitePolicy,
+) -> Result<Cow<'_, [WeekData]>, String> {
+    let broken: Vec<String> = weeks
+        .iter()
+        .filter(|w| !is_finite_week(w))
+        .map(|w| format!("{}-W{:02}", w.year, w.week))
+        .collect();
+    if broken.is_empty() {
+        return Ok(Cow::Borrowed(weeks));
+    }
+    if policy == NonFinitePolicy::Reject {
+        return Err(format!(
+            "Non-finite hours or amount in weeks: {}",
+            broken.join(", ")
+        ));
+    }
+
+    let median_hours = median(weeks.iter().map(|w| w.total_hours));
+    let median_amount = median(weeks.iter().map(|w| w.total_amount));
+    let mut weeks = weeks.to_vec();
+    for week in weeks.iter_mut().filter(|w| !is_finite_week(w)) {
+        if !week.total_hours.is_finite() {
+            week.total_hours = if week.total_minutes > 0 {
+                week.total_minutes as f64 / 60.0
+            } else {
+                median_hours
+            };
+        }
+        if !week.total_amount.is_finite() {
+            week.total_amount = median_amount;
+        }
+        for stats in week.project_stats.iter_mut() {
+            if !stats.hours.is_finite() {
+                stats.hours = stats.minutes.max(0) as f64 / 60.0;
+            }
+        }
+    }
+    Ok(Cow::Owned(weeks))
+}
+
+fn is_finite_week(week: &WeekData) -> bool {
+    week.total_hours.is_finite()
+        && week.total_amount.is_finite()
+        && week.project_stats.iter().all(|s| s.hours.is_finite())
+}
+
+/// Медиана конечных значений; без них - 0
+fn median(values: impl Iterator<Item = f64>) -> f64 {
+    let mut values: Vec<f64> = values.filter(|v| v.is_finite()).collect();
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn week(week: i32, total_hours: f64, total_minutes: i32) -> WeekData {
+        WeekData {
+            year: 2024,
+            week,
+            total_minutes,
+            total_hours,
+            total_amount: 100.0,
+            project_stats: Vec::new(),
+        }
+    }
+
+    fn weeks() -> Vec<WeekData> {
+        vec![
+            week(1, 30.0, 1800),
+            week(2, f64::NAN, 0),
+            week(3, 40.0, 2400),
+            week(4, f64::INFINITY, 2700),
+            week(5, f64::NEG_INFINITY, 0),
+            week(6, 50.0, 3000),
+        ]
+    }
+
+    #[test]
+    fn policy_comes_from_options() {
+        let options = |value: JsonValue| NonFinitePolicy::from_options(Some(&value));
+        assert_eq!(NonFinitePolicy::from_options(None), Ok(None));
+        assert_eq!(options(serde_json::json!({})), Ok(None));
+        assert_eq!(
+            options(serde_json::json!({"non_finite": "reject"})),
+            Ok(Some(NonFinitePolicy::Reject))
+        );
+        assert_eq!(
+            options(serde_json::json!({"non_finite": "impute"})),
+            Ok(Some(NonFinitePolicy::Impute))
+        );
+        assert!(options(serde_json::json!({"non_finite": "drop"})).is_err());
+    }
+
+    #[test]
+    fn impute_replaces_nan_and_infinite_hours() {
+        let weeks = weeks();
+        let sanitized = sanitize_weeks(&weeks, NonFinitePolicy::Impute).unwrap();
+        let hours: Vec<f64> = sanitized.iter().map(|w| w.total_hours).collect();
+        // Из минут недели, иначе медиана конечных недель (30, 40, 50)
+        assert_eq!(hours, vec![30.0, 40.0, 40.0, 45.0, 40.0, 50.0]);
+
+        let finite = vec![week(1, 30.0, 1800)];
+        assert!(matches!(
+            sanitize_weeks(&finite, NonFinitePolicy::Impute),
+            Ok(Cow::Borrowed(_))
+        ));
+    }
+
+    #[test]
+    fn reject_lists_weeks_with_nan_and_infinite_hours() {
+        let error = sanitize_weeks(&weeks(), NonFinitePolicy::Reject).unwrap_err();
+        assert!(error.contains("2024-W02, 2024-W04, 2024-W05"), "{}", error);
+        assert!(!error.contains("2024-W01"), "{}", error);
+    }
+}
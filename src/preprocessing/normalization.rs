@@ -3,10 +3,14 @@
 #![allow(non_snake_case)]
 
 use ndarray::{Array1, Array2, Axis};
+use serde::{Deserialize, Serialize};
 
+use crate::float::Float;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DataNormalizer {
-    mean: Option<Array1<f64>>,
-    std: Option<Array1<f64>>,
+    mean: Option<Array1<Float>>,
+    std: Option<Array1<Float>>,
     is_fitted: bool,
 }
 
@@ -19,7 +23,7 @@ impl DataNormalizer {
         }
     }
 
-    pub fn fit(&mut self, X: &Array2<f64>) -> Result<(), String> {
+    pub fn fit(&mut self, X: &Array2<Float>) -> Result<(), String> {
         if X.nrows() == 0 {
             return Err("Empty dataset".to_string());
         }
@@ -41,7 +45,7 @@ impl DataNormalizer {
         Ok(())
     }
 
-    pub fn transform(&self, X: &Array2<f64>) -> Result<Array2<f64>, String> {
+    pub fn transform(&self, X: &Array2<Float>) -> Result<Array2<Float>, String> {
         if !self.is_fitted {
             return Err("Normalizer not fitted".to_string());
         }
@@ -60,7 +64,7 @@ impl DataNormalizer {
         Ok(normalized)
     }
 
-    pub fn fit_transform(&mut self, X: &Array2<f64>) -> Result<Array2<f64>, String> {
+    pub fn fit_transform(&mut self, X: &Array2<Float>) -> Result<Array2<Float>, String> {
         self.fit(X)?;
         self.transform(X)
     }
@@ -0,0 +1,40 @@
+//! Сохраняемое состояние конвейера предобработки
+
+use serde::{Deserialize, Serialize};
+
+use super::{DataNormalizer, FeatureLayout, FeatureSelector, Winsorizer};
+
+/// Версия формата `PreprocessingState`
+pub const PREPROCESSING_VERSION: u32 = 1;
+
+/// Параметры всех преобразователей, необходимые для воспроизведения входа модели
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreprocessingState {
+    pub version: u32,
+    pub layout: FeatureLayout,
+    pub selector: Option<FeatureSelector>,
+    pub clipper: Option<Winsorizer>,
+    pub normalizer: DataNormalizer,
+}
+
+impl PreprocessingState {
+    /// Проверка совместимости с текущей версией кода
+    pub fn check_compatible(&self, expected_layout: &FeatureLayout) -> Result<(), String> {
+        if self.version != PREPROCESSING_VERSION {
+            return Err(format!(
+                "Unsupported preprocessing version: {} (expected {})",
+                self.version, PREPROCESSING_VERSION
+            ));
+        }
+        if &self.layout != expected_layout {
+            return Err(format!(
+                "Feature layout mismatch: saved v{} with {} features, expected v{} with {} features",
+                self.layout.version,
+                self.layout.features.len(),
+                expected_layout.version,
+                expected_layout.features.len()
+            ));
+        }
+        Ok(())
+    }
+}
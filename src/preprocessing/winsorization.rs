@@ -0,0 +1,105 @@
+//! Ограничение выбросов (винзоризация)
+
+#![allow(non_snake_case)]
+
+use ndarray::{Array2, Axis};
+use serde::{Deserialize, Serialize};
+
+use crate::float::Float;
+
+/// Обрезает значения каждого признака по перцентилям обучающей выборки,
+/// чтобы единичные экстремальные недели не искажали масштаб нормализации.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Winsorizer {
+    lower_quantile: Float,
+    upper_quantile: Float,
+    bounds: Option<Vec<(Float, Float)>>,
+}
+
+impl Winsorizer {
+    /// `lower_quantile` и `upper_quantile` задаются долями в диапазоне [0, 1]
+    pub fn new(lower_quantile: Float, upper_quantile: Float) -> Result<Self, String> {
+        if !(0.0..=1.0).contains(&lower_quantile)
+            || !(0.0..=1.0).contains(&upper_quantile)
+            || lower_quantile >= upper_quantile
+        {
+            return Err(format!(
+                "Invalid clipping quantiles: {} .. {}",
+                lower_quantile, upper_quantile
+            ));
+        }
+
+        Ok(Self {
+            lower_quantile,
+            upper_quantile,
+            bounds: None,
+        })
+    }
+
+    pub fn fit(&mut self, X: &Array2<Float>) -> Result<(), String> {
+        if X.nrows() == 0 {
+            return Err("Empty dataset".to_string());
+        }
+
+        let bounds = X
+            .axis_iter(Axis(1))
+            .map(|column| {
+                let mut values: Vec<Float> = column.iter().copied().collect();
+                values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                (
+                    quantile(&values, self.lower_quantile),
+                    quantile(&values, self.upper_quantile),
+                )
+            })
+            .collect();
+
+        self.bounds = Some(bounds);
+        Ok(())
+    }
+
+    pub fn transform(&self, X: &Array2<Float>) -> Result<Array2<Float>, String> {
+        let bounds = self.bounds.as_ref().ok_or("Winsorizer not fitted")?;
+        if bounds.len() != X.ncols() {
+            return Err("Feature count mismatch".to_string());
+        }
+
+        let mut clipped = X.clone();
+        for mut row in clipped.rows_mut() {
+            for (i, val) in row.iter_mut().enumerate() {
+                let (low, high) = bounds[i];
+                *val = val.clamp(low, high);
+            }
+        }
+
+        Ok(clipped)
+    }
+
+    pub fn fit_transform(&mut self, X: &Array2<Float>) -> Result<Array2<Float>, String> {
+        self.fit(X)?;
+        self.transform(X)
+    }
+
+    /// Границы обрезки по каждому признаку (после `fit`)
+    pub fn bounds(&self) -> Option<&[(Float, Float)]> {
+        self.bounds.as_deref()
+    }
+}
+
+impl Default for Winsorizer {
+    fn default() -> Self {
+        Self {
+            lower_quantile: 0.05,
+            upper_quantile: 0.95,
+            bounds: None,
+        }
+    }
+}
+
+/// Квантиль отсортированного массива с линейной интерполяцией
+fn quantile(sorted: &[Float], q: Float) -> Float {
+    let pos = q * (sorted.len() - 1) as Float;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    let frac = pos - lower as Float;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * frac
+}
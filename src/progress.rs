@@ -0,0 +1,24 @@
+//! Отчеты о ходе обучения моделей
+
+use serde::Serialize;
+
+/// Шаг обучения: `completed` из `total` в рамках стадии `stage`
+#[derive(Debug, Clone, Serialize)]
+pub struct TrainingProgress {
+    pub stage: String,
+    pub completed: usize,
+    pub total: usize,
+}
+
+impl TrainingProgress {
+    pub fn new(stage: &str, completed: usize, total: usize) -> Self {
+        Self {
+            stage: stage.to_string(),
+            completed,
+            total,
+        }
+    }
+}
+
+/// Колбэк без действия для обучения без отчетов
+pub fn no_progress(_: TrainingProgress) {}
@@ -0,0 +1,73 @@
+//! Отбор полей ответа (`?fields=forecasting.weekly_hours,productivity.optimal_work_hours`).
+//!
+//! Путь - имена полей через точку. Массивы на пути проходятся поэлементно
+//! (`anomalies.entry_id` оставляет у каждой аномалии только `entry_id`), путь к
+//! полю целиком поглощает пути к его вложенным полям
+
+use std::collections::BTreeMap;
+
+use serde_json::Value as JsonValue;
+
+/// Дерево выбранных полей; `None` у поля - поле целиком
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldProjection {
+    fields: BTreeMap<String, Option<FieldProjection>>,
+}
+
+impl FieldProjection {
+    /// Разбор списка путей через запятую; пустой список и пустые имена полей - `Err`
+    pub fn parse(paths: &str) -> Result<Self, String> {
+        let mut projection = Self::default();
+        for path in paths.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let segments: Vec<&str> = path.split('.').collect();
+            if segments.iter().any(|s| s.is_empty()) {
+                return Err(format!("Invalid field path: {}", path));
+            }
+            projection.insert(&segments);
+        }
+        if projection.fields.is_empty() {
+            return Err("No fields selected".to_string());
+        }
+        Ok(projection)
+    }
+
+    fn insert(&mut self, segments: &[&str]) {
+        let Some((first, rest)) = segments.split_first() else {
+            return;
+        };
+        if rest.is_empty() {
+            self.fields.insert(first.to_string(), None);
+            return;
+        }
+        // `None` - поле уже выбрано целиком
+        if let Some(nested) = self
+            .fields
+            .entry(first.to_string())
+            .or_insert_with(|| Some(Self::default()))
+        {
+            nested.insert(rest);
+        }
+    }
+
+    /// Оставляет в значении только выбранные поля; отсутствующие в нем пропускаются
+    pub fn apply(&self, value: JsonValue) -> JsonValue {
+        match value {
+            JsonValue::Object(mut object) => JsonValue::Object(
+                self.fields
+                    .iter()
+                    .filter_map(|(name, nested)| {
+                        let value = object.remove(name)?;
+                        Some(match nested {
+                            Some(nested) => (name.clone(), nested.apply(value)),
+                            None => (name.clone(), value),
+                        })
+                    })
+                    .collect(),
+            ),
+            JsonValue::Array(items) => {
+                JsonValue::Array(items.into_iter().map(|item| self.apply(item)).collect())
+            }
+            value => value,
+        }
+    }
+}
@@ -0,0 +1,469 @@
+//! Реестр моделей: версионированные артефакты обученных моделей и их
+//! продвижение между окружениями (например, `staging` → `production`).
+//!
+//! Артефакты хранятся в каталоге на диске или в S3-совместимом хранилище
+//! (feature `s3`):
+//!
+//! ```text
+//! models/{name}/versions/{version}/model.json
+//! models/{name}/versions/{version}/metadata.json
+//! models/{name}/environments/{environment}.json
+//! ```
+//!
+//! Снимок версии в компактном формате (`compact`) хранится в `model.bin`.
+//! Подпись снимка (`signing::SnapshotSigner`) записывается в метаданные версии и
+//! проверяется при загрузке
+
+#[cfg(feature = "s3")]
+pub mod s3;
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::compact::SizeReport;
+use crate::retention::is_expired;
+use crate::signing::SnapshotSigner;
+use crate::types::ModelInfo;
+
+#[cfg(feature = "s3")]
+pub use self::s3::S3ArtifactStore;
+
+/// Хранилище артефактов с путями вида `a/b/c.json`
+#[async_trait]
+pub trait ArtifactStore: Send + Sync {
+    async fn put(&self, path: &str, data: Vec<u8>) -> Result<(), String>;
+
+    /// `None`, если артефакта нет
+    async fn get(&self, path: &str) -> Result<Option<Vec<u8>>, String>;
+
+    /// Имена непосредственных подкаталогов `prefix`
+    async fn list_dirs(&self, prefix: &str) -> Result<Vec<String>, String>;
+
+    /// Имена файлов непосредственно в `prefix`
+    async fn list_files(&self, prefix: &str) -> Result<Vec<String>, String>;
+
+    /// Удаляет артефакт; отсутствующий - не ошибка
+    async fn delete(&self, path: &str) -> Result<(), String>;
+}
+
+/// Артефакты в каталоге локальной файловой системы
+pub struct LocalArtifactStore {
+    root: PathBuf,
+}
+
+impl LocalArtifactStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        if path.split('/').any(|part| part.is_empty() || part == "..") {
+            return Err(format!("Invalid artifact path: {}", path));
+        }
+        Ok(self.root.join(path))
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for LocalArtifactStore {
+    async fn put(&self, path: &str, data: Vec<u8>) -> Result<(), String> {
+        let target = self.resolve(path)?;
+        if let Some(parent) = target.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        // Запись через временный файл, чтобы читатели не увидели половину артефакта
+        let tmp = target.with_extension("tmp");
+        tokio::fs::write(&tmp, data)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        tokio::fs::rename(&tmp, &target)
+            .await
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+    }
+
+    async fn get(&self, path: &str) -> Result<Option<Vec<u8>>, String> {
+        let target = self.resolve(path)?;
+        match tokio::fs::read(&target).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read {}: {}", target.display(), e)),
+        }
+    }
+
+    async fn list_dirs(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let dir = self.resolve(prefix)?;
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to list {}: {}", dir.display(), e)),
+        };
+
+        let mut dirs = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?
+        {
+            if entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false) {
+                dirs.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(dirs)
+    }
+
+    async fn list_files(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let dir = self.resolve(prefix)?;
+        let mut entries = match tokio::fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to list {}: {}", dir.display(), e)),
+        };
+
+        let mut files = Vec::new();
+        while let Some(entry) = entries
+            .next_entry()
+            .await
+            .map_err(|e| format!("Failed to list {}: {}", dir.display(), e))?
+        {
+            if entry
+                .file_type()
+                .await
+                .map(|t| t.is_file())
+                .unwrap_or(false)
+            {
+                files.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(files)
+    }
+
+    /// Опустевший каталог артефакта удаляется вместе с ним
+    async fn delete(&self, path: &str) -> Result<(), String> {
+        let target = self.resolve(path)?;
+        match tokio::fs::remove_file(&target).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Failed to delete {}: {}", target.display(), e)),
+        }
+        if let Some(parent) = target.parent() {
+            // Непустой каталог не удаляется
+            let _ = tokio::fs::remove_dir(parent).await;
+        }
+        Ok(())
+    }
+}
+
+/// Формат снимка модели в реестре
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactFormat {
+    /// `to_json` моделей
+    #[default]
+    Json,
+    /// `to_compact` моделей
+    Compact,
+}
+
+impl ArtifactFormat {
+    fn file_name(self) -> &'static str {
+        match self {
+            Self::Json => "model.json",
+            Self::Compact => "model.bin",
+        }
+    }
+}
+
+/// Снимок модели в одном из форматов
+#[derive(Debug, Clone)]
+pub enum Artifact {
+    Json(String),
+    Compact(Vec<u8>),
+}
+
+impl Artifact {
+    pub fn format(&self) -> ArtifactFormat {
+        match self {
+            Self::Json(_) => ArtifactFormat::Json,
+            Self::Compact(_) => ArtifactFormat::Compact,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Json(json) => json.as_bytes(),
+            Self::Compact(data) => data,
+        }
+    }
+}
+
+/// Сведения о зарегистрированной версии модели
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactVersion {
+    pub name: String,
+    pub version: String,
+    pub registered_at: String,
+    #[serde(default)]
+    pub model_info: Option<ModelInfo>,
+    #[serde(default)]
+    pub format: ArtifactFormat,
+    /// Размер сохраненного снимка
+    #[serde(default)]
+    pub bytes: usize,
+    /// Размеры снимка в обоих форматах на момент регистрации
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<SizeReport>,
+    /// Подпись или контрольная сумма снимка (`SnapshotSigner::sign`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Указатель окружения на версию модели
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Promotion {
+    pub environment: String,
+    pub version: String,
+    pub promoted_at: String,
+}
+
+pub struct ModelRegistry {
+    store: Box<dyn ArtifactStore>,
+    signer: SnapshotSigner,
+}
+
+impl ModelRegistry {
+    pub fn new(store: Box<dyn ArtifactStore>) -> Self {
+        Self {
+            store,
+            signer: SnapshotSigner::default(),
+        }
+    }
+
+    /// Подпись регистрируемых и проверка загружаемых снимков
+    pub fn with_signer(mut self, signer: SnapshotSigner) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Сохраняет снимок модели (`to_json` или `to_compact`) как новую версию
+    pub async fn register(
+        &self,
+        name: &str,
+        artifact: &Artifact,
+        model_info: Option<ModelInfo>,
+        size: Option<SizeReport>,
+    ) -> Result<ArtifactVersion, String> {
+        validate_name(name)?;
+        let now = chrono::Utc::now();
+        let version = ArtifactVersion {
+            name: name.to_string(),
+            // Лексикографический порядок версий совпадает с хронологическим
+            version: format!(
+                "{}-{:08x}",
+                now.format("%Y%m%dT%H%M%S%.3fZ"),
+                rand::random::<u32>()
+            ),
+            registered_at: now.to_rfc3339(),
+            model_info,
+            format: artifact.format(),
+            bytes: artifact.len(),
+            size,
+            signature: Some(self.signer.sign(name, artifact.as_bytes())),
+        };
+
+        let dir = format!("models/{}/versions/{}", name, version.version);
+        self.store
+            .put(
+                &format!("{}/{}", dir, version.format.file_name()),
+                artifact.as_bytes().to_vec(),
+            )
+            .await?;
+        // Метаданные пишутся последними: версия без них не считается зарегистрированной
+        self.store
+            .put(&format!("{}/metadata.json", dir), to_json(&version)?)
+            .await?;
+        Ok(version)
+    }
+
+    /// Зарегистрированные версии от старых к новым
+    pub async fn versions(&self, name: &str) -> Result<Vec<ArtifactVersion>, String> {
+        validate_name(name)?;
+        let mut ids = self
+            .store
+            .list_dirs(&format!("models/{}/versions", name))
+            .await?;
+        ids.sort();
+
+        let mut versions = Vec::new();
+        for id in ids {
+            let path = format!("models/{}/versions/{}/metadata.json", name, id);
+            if let Some(data) = self.store.get(&path).await? {
+                versions.push(from_json(&data)?);
+            }
+        }
+        Ok(versions)
+    }
+
+    /// Назначает версию окружению (`production`, `staging`, ...)
+    pub async fn promote(
+        &self,
+        name: &str,
+        version: &str,
+        environment: &str,
+    ) -> Result<Promotion, String> {
+        validate_name(name)?;
+        validate_name(environment)?;
+        let metadata = format!("models/{}/versions/{}/metadata.json", name, version);
+        if validate_name(version).is_err() || self.store.get(&metadata).await?.is_none() {
+            return Err(format!("Unknown version {} of model {}", version, name));
+        }
+
+        let promotion = Promotion {
+            environment: environment.to_string(),
+            version: version.to_string(),
+            promoted_at: chrono::Utc::now().to_rfc3339(),
+        };
+        self.store
+            .put(
+                &format!("models/{}/environments/{}.json", name, environment),
+                to_json(&promotion)?,
+            )
+            .await?;
+        Ok(promotion)
+    }
+
+    /// Удаляет версии моделей, зарегистрированные раньше `before`. Последняя
+    /// версия модели и версии, назначенные окружениям, сохраняются. Возвращает
+    /// удаленные версии
+    pub async fn prune(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<ArtifactVersion>, String> {
+        let mut removed = Vec::new();
+        for name in self.store.list_dirs("models").await? {
+            if validate_name(&name).is_err() {
+                continue;
+            }
+            let mut promoted = Vec::new();
+            let environments = format!("models/{}/environments", name);
+            for file in self.store.list_files(&environments).await? {
+                if !file.ends_with(".json") {
+                    continue;
+                }
+                let path = format!("{}/{}", environments, file);
+                if let Some(data) = self.store.get(&path).await? {
+                    promoted.push(from_json::<Promotion>(&data)?.version);
+                }
+            }
+
+            let mut versions = self.versions(&name).await?;
+            versions.pop();
+            for version in versions {
+                if promoted.contains(&version.version)
+                    || !is_expired(&version.registered_at, before)
+                {
+                    continue;
+                }
+                let dir = format!("models/{}/versions/{}", name, version.version);
+                // Метаданные удаляются первыми: версия без них не считается
+                // зарегистрированной, даже если снимок удалить не удалось
+                self.store.delete(&format!("{}/metadata.json", dir)).await?;
+                self.store
+                    .delete(&format!("{}/{}", dir, version.format.file_name()))
+                    .await?;
+                removed.push(version);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Удаляет все версии модели и ее назначения окружениям; возвращает
+    /// удаленные версии
+    pub async fn delete(&self, name: &str) -> Result<Vec<ArtifactVersion>, String> {
+        validate_name(name)?;
+        // Назначения удаляются первыми: окружение не указывает на удаленную версию
+        let environments = format!("models/{}/environments", name);
+        for file in self.store.list_files(&environments).await? {
+            self.store
+                .delete(&format!("{}/{}", environments, file))
+                .await?;
+        }
+        let versions = self.versions(name).await?;
+        for version in &versions {
+            let dir = format!("models/{}/versions/{}", name, version.version);
+            self.store.delete(&format!("{}/metadata.json", dir)).await?;
+            self.store
+                .delete(&format!("{}/{}", dir, version.format.file_name()))
+                .await?;
+        }
+        Ok(versions)
+    }
+
+    /// Снимок модели, назначенной окружению; `None`, если назначения нет.
+    /// Снимок с неверной подписью - ошибка
+    pub async fn load(&self, name: &str, environment: &str) -> Result<Option<Artifact>, String> {
+        validate_name(name)?;
+        validate_name(environment)?;
+        let pointer = format!("models/{}/environments/{}.json", name, environment);
+        let Some(data) = self.store.get(&pointer).await? else {
+            return Ok(None);
+        };
+        let promotion: Promotion = from_json(&data)?;
+
+        let dir = format!("models/{}/versions/{}", name, promotion.version);
+        let (format, signature) = match self.store.get(&format!("{}/metadata.json", dir)).await? {
+            Some(data) => {
+                let metadata: ArtifactVersion = from_json(&data)?;
+                (metadata.format, metadata.signature)
+            }
+            None => (ArtifactFormat::Json, None),
+        };
+        let path = format!("{}/{}", dir, format.file_name());
+        let Some(data) = self.store.get(&path).await? else {
+            return Err(format!("Artifact {} is missing", path));
+        };
+        self.signer
+            .verify(name, &data, signature.as_deref())
+            .map_err(|e| format!("Artifact {} failed integrity check: {}", path, e))?;
+        match format {
+            ArtifactFormat::Json => String::from_utf8(data)
+                .map(|json| Some(Artifact::Json(json)))
+                .map_err(|e| format!("Invalid artifact {}: {}", path, e)),
+            ArtifactFormat::Compact => Ok(Some(Artifact::Compact(data))),
+        }
+    }
+}
+
+/// Имена моделей, версий и окружений становятся частями пути; `:` отделяет
+/// арендатора в имени модели (`forecasting:acme`)
+fn validate_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+        && !name.starts_with('.');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid name: {}", name))
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    serde_json::to_vec_pretty(value).map_err(|e| format!("Serialization error: {}", e))
+}
+
+fn from_json<T: for<'de> Deserialize<'de>>(data: &[u8]) -> Result<T, String> {
+    serde_json::from_slice(data).map_err(|e| format!("Deserialization error: {}", e))
+}
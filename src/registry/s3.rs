@@ -0,0 +1,105 @@
+//! Артефакты в S3-совместимом хранилище (AWS S3, MinIO)
+
+use async_trait::async_trait;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path;
+use object_store::ObjectStore;
+
+use super::ArtifactStore;
+
+pub struct S3ArtifactStore {
+    store: AmazonS3,
+    prefix: Option<Path>,
+}
+
+impl S3ArtifactStore {
+    /// Подключение по URL вида `s3://bucket/prefix`.
+    ///
+    /// Учетные данные и адрес берутся из переменных окружения `AWS_*`
+    /// (`AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_REGION`, `AWS_ENDPOINT`
+    /// и `AWS_ALLOW_HTTP=true` для MinIO).
+    pub fn from_url(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("s3://")
+            .ok_or_else(|| format!("Not an s3:// URL: {}", url))?;
+        let (bucket, prefix) = match rest.split_once('/') {
+            Some((bucket, prefix)) => (bucket, prefix.trim_matches('/')),
+            None => (rest, ""),
+        };
+
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()
+            .map_err(store_error)?;
+        Ok(Self {
+            store,
+            prefix: (!prefix.is_empty()).then(|| Path::from(prefix)),
+        })
+    }
+
+    fn location(&self, path: &str) -> Path {
+        match &self.prefix {
+            Some(prefix) => Path::from(format!("{}/{}", prefix, path)),
+            None => Path::from(path),
+        }
+    }
+}
+
+#[async_trait]
+impl ArtifactStore for S3ArtifactStore {
+    async fn put(&self, path: &str, data: Vec<u8>) -> Result<(), String> {
+        self.store
+            .put(&self.location(path), data.into())
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    async fn get(&self, path: &str) -> Result<Option<Vec<u8>>, String> {
+        match self.store.get(&self.location(path)).await {
+            Ok(result) => {
+                let bytes = result.bytes().await.map_err(store_error)?;
+                Ok(Some(bytes.to_vec()))
+            }
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(store_error(e)),
+        }
+    }
+
+    async fn list_dirs(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let listing = self
+            .store
+            .list_with_delimiter(Some(&self.location(prefix)))
+            .await
+            .map_err(store_error)?;
+        Ok(listing
+            .common_prefixes
+            .iter()
+            .filter_map(|p| p.filename().map(|name| name.to_string()))
+            .collect())
+    }
+
+    async fn list_files(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let listing = self
+            .store
+            .list_with_delimiter(Some(&self.location(prefix)))
+            .await
+            .map_err(store_error)?;
+        Ok(listing
+            .objects
+            .iter()
+            .filter_map(|object| object.location.filename().map(|name| name.to_string()))
+            .collect())
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), String> {
+        match self.store.delete(&self.location(path)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(store_error(e)),
+        }
+    }
+}
+
+fn store_error(e: object_store::Error) -> String {
+    format!("Object storage error: {}", e)
+}
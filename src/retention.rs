@@ -0,0 +1,146 @@
+//! Сроки хранения данных и удаление данных арендатора.
+//!
+//! Сроки задаются переменными окружения в днях; незаданный срок - данные
+//! хранятся без ограничения:
+//!
+//! - `RETENTION_LEARNING_ERRORS_DAYS` - ошибки прогнозов (`/api/learn`)
+//! - `RETENTION_CAPTURES_DAYS` - записанные запросы (`CAPTURE_PATH`)
+//! - `RETENTION_AUDIT_DAYS` - журнал аудита
+//! - `RETENTION_ARTIFACTS_DAYS` - версии моделей в реестре
+//!
+//! Устаревшие данные удаляет фоновая очистка раз в `RETENTION_INTERVAL_SECS`
+//! секунд (3600)
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Период фоновой очистки по умолчанию
+pub const DEFAULT_CLEANUP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Сроки хранения в днях; `None` - без ограничения
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    pub learning_errors_days: Option<u32>,
+    pub captures_days: Option<u32>,
+    pub audit_days: Option<u32>,
+    pub artifacts_days: Option<u32>,
+    /// Период фоновой очистки
+    pub interval: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            learning_errors_days: None,
+            captures_days: None,
+            audit_days: None,
+            artifacts_days: None,
+            interval: DEFAULT_CLEANUP_INTERVAL,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Сроки из переменных окружения `RETENTION_*`; ошибка - нечисловое или
+    /// нулевое значение
+    pub fn from_env() -> Result<Self, String> {
+        let days = |name: &str| -> Result<Option<u32>, String> {
+            match std::env::var(name).ok().filter(|v| !v.is_empty()) {
+                Some(value) => match value.parse::<u32>() {
+                    Ok(days) if days > 0 => Ok(Some(days)),
+                    _ => Err(format!(
+                        "{} must be a positive number of days, got {}",
+                        name, value
+                    )),
+                },
+                None => Ok(None),
+            }
+        };
+        let interval = match std::env::var("RETENTION_INTERVAL_SECS")
+            .ok()
+            .filter(|v| !v.is_empty())
+        {
+            Some(value) => match value.parse::<u64>() {
+                Ok(secs) if secs > 0 => Duration::from_secs(secs),
+                _ => {
+                    return Err(format!(
+                        "RETENTION_INTERVAL_SECS must be a positive number, got {}",
+                        value
+                    ))
+                }
+            },
+            None => DEFAULT_CLEANUP_INTERVAL,
+        };
+        Ok(Self {
+            learning_errors_days: days("RETENTION_LEARNING_ERRORS_DAYS")?,
+            captures_days: days("RETENTION_CAPTURES_DAYS")?,
+            audit_days: days("RETENTION_AUDIT_DAYS")?,
+            artifacts_days: days("RETENTION_ARTIFACTS_DAYS")?,
+            interval,
+        })
+    }
+
+    /// Задан хотя бы один срок: фоновая очистка нужна
+    pub fn is_enabled(&self) -> bool {
+        self.learning_errors_days.is_some()
+            || self.captures_days.is_some()
+            || self.audit_days.is_some()
+            || self.artifacts_days.is_some()
+    }
+}
+
+/// Граница хранения: данные старше нее удаляются
+pub fn cutoff(days: u32, now: DateTime<Utc>) -> DateTime<Utc> {
+    now - chrono::Duration::days(days as i64)
+}
+
+/// Метка времени RFC 3339 раньше `before`; некорректная метка - нет
+pub fn is_expired(timestamp: &str, before: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(timestamp).is_ok_and(|t| t < before)
+}
+
+/// Замена файла журнала (аудит, записанные запросы) через временный файл,
+/// чтобы читатели не увидели его половину
+pub(crate) async fn replace_file(path: &std::path::Path, content: String) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, content)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Итог очистки по срокам хранения: удалено записей и версий
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CleanupReport {
+    pub learning_errors: usize,
+    pub captures: usize,
+    pub audit_records: usize,
+    /// Версии моделей реестра
+    pub artifacts: usize,
+}
+
+impl CleanupReport {
+    pub fn total(&self) -> usize {
+        self.learning_errors + self.captures + self.audit_records + self.artifacts
+    }
+}
+
+/// Итог удаления данных арендатора (`DELETE /api/tenants/{id}/data`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TenantDataDeletion {
+    pub tenant: String,
+    /// Удаленные снимки арендатора (модели, профиль, трекер рекомендаций,
+    /// журнал прогнозов)
+    pub snapshots: Vec<String>,
+    /// Удаленные версии моделей арендатора в реестре
+    pub model_versions: usize,
+    pub learning_errors: usize,
+    pub anomaly_feedback: usize,
+    pub recommendation_feedback: usize,
+    pub captures: usize,
+    pub audit_records: usize,
+}
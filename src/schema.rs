@@ -0,0 +1,74 @@
+//! JSON Schema тел запросов и ответов API (`GET /api/schema`).
+//!
+//! Схемы строятся из типов `types` при запуске, поэтому не расходятся с тем,
+//! что сервис принимает и отдает. Клиенты (плагин Kimai, генераторы кода)
+//! проверяют по ним данные до отправки
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Schema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
+use serde_json::Value as JsonValue;
+
+use crate::types::{AnomalyFeedback, MLInputData, MLOutputData, RecommendationFeedback};
+
+/// Версия контракта схем; увеличивается при несовместимых изменениях полей
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Имена схем в ответе `/api/schema` и в пути `/api/schema/{name}`
+pub const SCHEMA_NAMES: [&str; 4] = [
+    "input",
+    "output",
+    "recommendation_feedback",
+    "anomaly_feedback",
+];
+
+/// Схема по имени из `SCHEMA_NAMES`
+pub fn schema(name: &str) -> Option<JsonValue> {
+    match name {
+        "input" => Some(schema_for::<MLInputData>()),
+        "output" => Some(schema_for::<MLOutputData>()),
+        "recommendation_feedback" => Some(schema_for::<RecommendationFeedback>()),
+        "anomaly_feedback" => Some(schema_for::<AnomalyFeedback>()),
+        _ => None,
+    }
+}
+
+/// Все схемы с версией контракта и сервиса
+pub fn api_schema() -> JsonValue {
+    let schemas: serde_json::Map<String, JsonValue> = SCHEMA_NAMES
+        .iter()
+        .filter_map(|name| Some((name.to_string(), schema(name)?)))
+        .collect();
+    serde_json::json!({
+        "schema_version": SCHEMA_VERSION,
+        "service_version": env!("CARGO_PKG_VERSION"),
+        "schemas": schemas,
+    })
+}
+
+fn schema_for<T: JsonSchema>() -> JsonValue {
+    let generator = SchemaGenerator::new(SchemaSettings::draft07());
+    serde_json::to_value(generator.into_root_schema_for::<T>()).unwrap_or_default()
+}
+
+/// `TimesheetEntry::duration` на входе: число в единицах `settings.duration_unit`
+/// или строка ISO-8601 (`PT1H30M`)
+pub fn duration_schema(_: &mut SchemaGenerator) -> Schema {
+    let number = SchemaObject {
+        instance_type: Some(InstanceType::Number.into()),
+        ..Default::default()
+    };
+    let iso8601 = SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        format: Some("duration".to_string()),
+        ..Default::default()
+    };
+    SchemaObject {
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![number.into(), iso8601.into()]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
@@ -0,0 +1,107 @@
+//! Сегментация анализа по тегам записей (`settings.segment_by_tags`).
+//!
+//! Сегмент - записи с тегом (без учета регистра). Недели сегмента собираются
+//! из его записей на тех же неделях, что и общие данные: неделя без записей
+//! сегмента дает нулевые часы, а не пропуск. Запись с несколькими тегами
+//! входит в каждый из их сегментов
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::ingest::WeeklyAggregator;
+use crate::types::{MLInputData, Project, WeekData};
+
+/// Входные данные сегментов `settings.segment_by_tags` в порядке настроек
+pub fn segment_inputs(data: &MLInputData) -> Vec<(String, MLInputData)> {
+    let mut seen = HashSet::new();
+    data.settings
+        .segment_by_tags
+        .iter()
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.to_lowercase()))
+        .map(|tag| (tag.clone(), segment_input(data, tag)))
+        .collect()
+}
+
+/// Данные сегмента `tag`: его записи, проекты и недели; настройки - общие
+/// без `segment_by_tags`
+pub fn segment_input(data: &MLInputData, tag: &str) -> MLInputData {
+    let tag = tag.to_lowercase();
+    let timesheets: Vec<_> = data
+        .timesheets
+        .iter()
+        .filter(|e| e.tags.iter().any(|t| t.to_lowercase() == tag))
+        .cloned()
+        .collect();
+
+    let mut aggregator = WeeklyAggregator::new(data.settings.rate_per_minute);
+    for entry in &timesheets {
+        aggregator.add(entry);
+    }
+    let mut weeks: BTreeMap<(i32, i32), WeekData> = aggregator
+        .finish()
+        .into_iter()
+        .map(|week| ((week.year, week.week), week))
+        .collect();
+    for week in weeks.values_mut() {
+        // Выручка по ставкам проектов, как в данных Kimai
+        let project_minutes: i32 = week.project_stats.iter().map(|s| s.minutes).sum();
+        week.total_amount = week
+            .project_stats
+            .iter()
+            .map(|s| s.minutes as f64 * data.settings.project_rate(s.project_id))
+            .sum::<f64>()
+            + (week.total_minutes - project_minutes) as f64 * data.settings.rate_per_minute;
+    }
+    let weeks = if data.weeks.is_empty() {
+        weeks.into_values().collect()
+    } else {
+        data.weeks
+            .iter()
+            .map(|w| {
+                weeks.remove(&(w.year, w.week)).unwrap_or(WeekData {
+                    year: w.year,
+                    week: w.week,
+                    total_minutes: 0,
+                    total_hours: 0.0,
+                    total_amount: 0.0,
+                    project_stats: Vec::new(),
+                })
+            })
+            .collect()
+    };
+
+    // Проекты с записями сегмента; часы и недели - по записям сегмента
+    let mut project_weeks: BTreeMap<i32, (i64, HashSet<(i32, i32)>)> = BTreeMap::new();
+    for entry in &timesheets {
+        if let Some(project_id) = entry.project_id {
+            let (minutes, weeks) = project_weeks.entry(project_id).or_default();
+            *minutes += entry.duration as i64;
+            weeks.insert(entry.iso_week());
+        }
+    }
+    let projects = data
+        .projects
+        .iter()
+        .filter_map(|project| {
+            let (minutes, weeks) = project_weeks.get(&project.id)?;
+            let total_hours = *minutes as f64 / 60.0;
+            Some(Project {
+                id: project.id,
+                name: project.name.clone(),
+                total_hours,
+                avg_hours_per_week: total_hours / weeks.len().max(1) as f64,
+                weeks_count: weeks.len() as i32,
+            })
+        })
+        .collect();
+
+    let mut settings = data.settings.clone();
+    settings.segment_by_tags.clear();
+    MLInputData {
+        timesheets,
+        projects,
+        weeks,
+        settings,
+        context: data.context.clone(),
+        options: data.options.clone(),
+    }
+}
@@ -0,0 +1,92 @@
+//! Самопроверка сервиса (`/ready` и старт сервера): контрольные суммы снимков
+//! моделей и пробные прогноз и поиск аномалий на синтетических данных
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::models::anomaly_detection::AnomalyDetector;
+use crate::models::forecasting::ForecastingModel;
+use crate::synthetic::{self, Profile};
+
+/// Недель в синтетических данных: минимум для обучения прогноза (8) с запасом
+const SYNTHETIC_WEEKS: usize = 12;
+
+/// Зерно синтетических данных самопроверки
+const SYNTHETIC_SEED: u64 = 1;
+
+/// Результат одной проверки
+#[derive(Debug, Clone, Serialize)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl ReadinessCheck {
+    pub fn new(name: &str, result: Result<String, String>) -> Self {
+        let (ok, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        Self {
+            name: name.to_string(),
+            ok,
+            detail,
+        }
+    }
+}
+
+/// SHA-256 снимка модели (`to_json`)
+pub fn snapshot_checksum(json: &str) -> String {
+    format!("{:x}", Sha256::digest(json.as_bytes()))
+}
+
+/// Снимок модели совпадает с тем, что был загружен или сохранен последним
+pub fn verify_checksum(
+    snapshot: Result<String, String>,
+    expected: Option<&str>,
+) -> Result<String, String> {
+    let checksum = snapshot_checksum(&snapshot?);
+    match expected {
+        Some(expected) if expected != checksum => Err(format!(
+            "Model checksum {} does not match loaded snapshot {}",
+            checksum, expected
+        )),
+        _ => Ok(checksum),
+    }
+}
+
+/// Прогноз на синтетических неделях. Необученная модель сначала обучается на них
+/// (копия), так проверяется и обучение
+pub fn smoke_forecast(model: &ForecastingModel) -> Result<String, String> {
+    let weeks = synthetic::generate(Profile::SeasonalFreelancer, SYNTHETIC_WEEKS, SYNTHETIC_SEED)
+        .input
+        .weeks;
+    let mut model = model.clone();
+    if model.model_info().is_none() {
+        model.train(&weeks)?;
+    }
+    let forecast = model.predict(&weeks)?;
+    if !forecast.weekly_hours.is_finite() || forecast.weekly_hours < 0.0 {
+        return Err(format!("Invalid smoke forecast: {}", forecast.weekly_hours));
+    }
+    Ok(format!("{:.1} h/week", forecast.weekly_hours))
+}
+
+/// Поиск аномалий в синтетических записях; необученный детектор сначала
+/// обучается на них (копия)
+pub fn smoke_anomalies(detector: &AnomalyDetector) -> Result<String, String> {
+    let entries = synthetic::generate(Profile::SeasonalFreelancer, SYNTHETIC_WEEKS, SYNTHETIC_SEED)
+        .input
+        .timesheets;
+    let mut detector = detector.clone();
+    if detector.model_info().is_none() {
+        detector.train(&entries)?;
+    }
+    let anomalies = detector.detect(&entries)?;
+    Ok(format!(
+        "{} anomalies in {} entries",
+        anomalies.len(),
+        entries.len()
+    ))
+}
@@ -0,0 +1,285 @@
+//! Целостность и подпись снимков моделей.
+//!
+//! Снимки в хранилище и артефакты реестра сопровождаются подписью: HMAC-SHA256
+//! имени и содержимого снимка с ключом `MODEL_SIGNING_KEY` (снимок нельзя
+//! выдать за модель другого арендатора) или, без ключа, контрольной суммой SHA-256,
+//! которая ловит только повреждение. С ключом снимки без подписи или с одной
+//! контрольной суммой отвергаются: загруженная модель напрямую определяет
+//! прогнозы пользователей. Снимок хранилища, не прошедший проверку, копируется
+//! под именем `quarantine:<name>` для разбора и не загружается
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::jobs::JobInfo;
+use crate::models::PredictionError;
+use crate::storage::Storage;
+use crate::types::{AnomalyFeedback, RecommendationFeedback};
+
+/// Первая строка подписанного снимка в хранилище: `#kimai-ml-signature <подпись>`
+pub const SIGNATURE_HEADER: &str = "#kimai-ml-signature ";
+
+/// Префикс имени, под которым сохраняются отвергнутые снимки
+pub const QUARANTINE_PREFIX: &str = "quarantine:";
+
+const HMAC_SCHEME: &str = "hmac-sha256-v2:";
+/// Подпись прежних версий без имени снимка: подходит к снимку под любым именем
+const LEGACY_HMAC_SCHEME: &str = "hmac-sha256:";
+const CHECKSUM_SCHEME: &str = "sha256:";
+
+/// Подпись и проверка снимков
+#[derive(Clone, Default)]
+pub struct SnapshotSigner {
+    key: Option<Vec<u8>>,
+}
+
+impl SnapshotSigner {
+    /// Без ключа снимки только получают и проверяют контрольную сумму
+    pub fn new(key: Option<&[u8]>) -> Self {
+        Self {
+            key: key.filter(|k| !k.is_empty()).map(<[u8]>::to_vec),
+        }
+    }
+
+    pub fn has_key(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// `hmac-sha256-v2:<hex>` от `name`, нулевого байта и `data` с ключом,
+    /// иначе `sha256:<hex>` от `data`
+    pub fn sign(&self, name: &str, data: &[u8]) -> String {
+        match &self.key {
+            Some(key) => format!("{}{}", HMAC_SCHEME, hex(&hmac(key, name, data))),
+            None => format!("{}{}", CHECKSUM_SCHEME, hex(&Sha256::digest(data))),
+        }
+    }
+
+    /// Проверяет подпись снимка `data` под именем `name`. Снимки без подписи
+    /// (сохраненные до появления подписей) принимаются только без ключа
+    pub fn verify(&self, name: &str, data: &[u8], signature: Option<&str>) -> Result<(), String> {
+        let Some(signature) = signature else {
+            return match self.key {
+                Some(_) => Err("snapshot is not signed".to_string()),
+                None => Ok(()),
+            };
+        };
+        let valid = if let Some(expected) = signature.strip_prefix(HMAC_SCHEME) {
+            let key = self
+                .key
+                .as_ref()
+                .ok_or("snapshot is signed, but no signing key is configured")?;
+            constant_time_eq(hex(&hmac(key, name, data)).as_bytes(), expected.as_bytes())
+        } else if signature.starts_with(LEGACY_HMAC_SCHEME) {
+            return Err("snapshot is signed without its name".to_string());
+        } else if let Some(expected) = signature.strip_prefix(CHECKSUM_SCHEME) {
+            if self.key.is_some() {
+                return Err("snapshot has a checksum, but no signature".to_string());
+            }
+            hex(&Sha256::digest(data)) == expected
+        } else {
+            return Err(format!("unknown signature scheme: {}", signature));
+        };
+        if valid {
+            Ok(())
+        } else if self.key.is_some() {
+            Err("signature does not match: snapshot was modified, renamed or signed with another key".to_string())
+        } else {
+            Err("checksum does not match: snapshot is corrupted".to_string())
+        }
+    }
+
+    /// Снимок `name` для хранилища: строка подписи и сам снимок
+    pub fn seal(&self, name: &str, snapshot: &str) -> String {
+        format!(
+            "{}{}\n{}",
+            SIGNATURE_HEADER,
+            self.sign(name, snapshot.as_bytes()),
+            snapshot
+        )
+    }
+
+    /// Снимок из `seal` после проверки подписи; снимок без строки подписи
+    /// проверяется как неподписанный. Снимок, запечатанный под другим именем,
+    /// отвергается
+    pub fn open(&self, name: &str, sealed: &str) -> Result<String, String> {
+        let Some(rest) = sealed.strip_prefix(SIGNATURE_HEADER) else {
+            self.verify(name, sealed.as_bytes(), None)?;
+            return Ok(sealed.to_string());
+        };
+        let (signature, snapshot) = rest.split_once('\n').ok_or("truncated signed snapshot")?;
+        self.verify(name, snapshot.as_bytes(), Some(signature))?;
+        Ok(snapshot.to_string())
+    }
+}
+
+fn hmac(key: &[u8], name: &str, data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(name.as_bytes());
+    mac.update(&[0]);
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Хранилище, подписывающее сохраняемые снимки моделей и проверяющее
+/// загружаемые; остальные данные передаются без изменений
+pub struct SignedStorage {
+    inner: Arc<dyn Storage>,
+    signer: SnapshotSigner,
+}
+
+impl SignedStorage {
+    pub fn new(inner: Arc<dyn Storage>, signer: SnapshotSigner) -> Self {
+        Self { inner, signer }
+    }
+}
+
+#[async_trait]
+impl Storage for SignedStorage {
+    async fn save_model(&self, name: &str, snapshot: &str) -> Result<(), String> {
+        self.inner
+            .save_model(name, &self.signer.seal(name, snapshot))
+            .await
+    }
+
+    /// Снимок, не прошедший проверку, копируется в `quarantine:<name>`, а
+    /// загрузка завершается ошибкой
+    async fn load_model(&self, name: &str) -> Result<Option<String>, String> {
+        let Some(sealed) = self.inner.load_model(name).await? else {
+            return Ok(None);
+        };
+        match self.signer.open(name, &sealed) {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(e) => {
+                let quarantine = format!("{}{}", QUARANTINE_PREFIX, name);
+                if let Err(save_error) = self.inner.save_model(&quarantine, &sealed).await {
+                    tracing::warn!("Failed to quarantine {} model: {}", name, save_error);
+                }
+                Err(format!(
+                    "Model {} failed integrity check ({}); moved to {}",
+                    name, e, quarantine
+                ))
+            }
+        }
+    }
+
+    /// Удаляет и карантинную копию снимка
+    async fn delete_model(&self, name: &str) -> Result<bool, String> {
+        let quarantine = format!("{}{}", QUARANTINE_PREFIX, name);
+        let quarantined = self.inner.delete_model(&quarantine).await?;
+        Ok(self.inner.delete_model(name).await? || quarantined)
+    }
+
+    async fn record_learning_error(&self, error: &PredictionError) -> Result<(), String> {
+        self.inner.record_learning_error(error).await
+    }
+
+    async fn learning_errors(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<PredictionError>, String> {
+        self.inner.learning_errors(tenant, limit).await
+    }
+
+    async fn prune_learning_errors(&self, before: DateTime<Utc>) -> Result<usize, String> {
+        self.inner.prune_learning_errors(before).await
+    }
+
+    async fn delete_learning_errors(&self, tenant: &str) -> Result<usize, String> {
+        self.inner.delete_learning_errors(tenant).await
+    }
+
+    async fn record_anomaly_feedback(&self, feedback: &AnomalyFeedback) -> Result<(), String> {
+        self.inner.record_anomaly_feedback(feedback).await
+    }
+
+    async fn anomaly_feedback(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<AnomalyFeedback>, String> {
+        self.inner.anomaly_feedback(tenant, limit).await
+    }
+
+    async fn delete_anomaly_feedback(&self, tenant: &str) -> Result<usize, String> {
+        self.inner.delete_anomaly_feedback(tenant).await
+    }
+
+    async fn record_recommendation_feedback(
+        &self,
+        feedback: &RecommendationFeedback,
+    ) -> Result<(), String> {
+        self.inner.record_recommendation_feedback(feedback).await
+    }
+
+    async fn recommendation_feedback(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<RecommendationFeedback>, String> {
+        self.inner.recommendation_feedback(tenant, limit).await
+    }
+
+    async fn delete_recommendation_feedback(&self, tenant: &str) -> Result<usize, String> {
+        self.inner.delete_recommendation_feedback(tenant).await
+    }
+
+    async fn save_job(&self, job: &JobInfo) -> Result<(), String> {
+        self.inner.save_job(job).await
+    }
+
+    async fn remove_job(&self, tenant: &str, id: &str) -> Result<(), String> {
+        self.inner.remove_job(tenant, id).await
+    }
+
+    async fn jobs(&self, tenant: &str) -> Result<Vec<JobInfo>, String> {
+        self.inner.jobs(tenant).await
+    }
+
+    async fn model_updates(&self) -> Result<Option<BoxStream<'static, String>>, String> {
+        self.inner.model_updates().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_is_bound_to_its_name() {
+        let signer = SnapshotSigner::new(Some(b"key"));
+        let sealed = signer.seal("forecasting:acme", "{}");
+        assert_eq!(signer.open("forecasting:acme", &sealed).unwrap(), "{}");
+        // Снимок арендатора, скопированный под имя другого, не загружается
+        assert!(signer.open("forecasting:globex", &sealed).is_err());
+        assert!(SnapshotSigner::new(Some(b"other"))
+            .open("forecasting:acme", &sealed)
+            .is_err());
+
+        let legacy = format!("{}{}", LEGACY_HMAC_SCHEME, hex(&[0; 32]));
+        assert!(signer
+            .verify("forecasting:acme", b"{}", Some(&legacy))
+            .is_err());
+
+        // Без ключа проверяется только целостность
+        let checksum = SnapshotSigner::default();
+        let sealed = checksum.seal("forecasting:acme", "{}");
+        assert!(checksum.open("forecasting:globex", &sealed).is_ok());
+        assert!(checksum
+            .open("forecasting:acme", &sealed.replace("{}", "[]"))
+            .is_err());
+    }
+}
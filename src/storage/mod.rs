@@ -0,0 +1,262 @@
+//! Хранилище состояния сервера: снимки моделей, ошибки прогнозов,
+//! отзывы об аномалиях и рекомендациях и выполняющиеся задачи.
+//!
+//! По умолчанию состояние живет в памяти процесса. Несколько реплик за
+//! балансировщиком разделяют его через общее хранилище (features `postgres`, `redis`).
+
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "redis")]
+pub mod redis;
+
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+
+use crate::jobs::JobInfo;
+use crate::models::PredictionError;
+use crate::retention::is_expired;
+use crate::types::{AnomalyFeedback, RecommendationFeedback};
+
+#[cfg(feature = "redis")]
+pub use self::redis::RedisStorage;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStorage;
+
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Сохраняет сериализованную модель (`to_json`) под именем, например `forecasting`
+    async fn save_model(&self, name: &str, snapshot: &str) -> Result<(), String>;
+
+    async fn load_model(&self, name: &str) -> Result<Option<String>, String>;
+
+    /// `false`, если модели с таким именем нет
+    async fn delete_model(&self, name: &str) -> Result<bool, String>;
+
+    async fn record_learning_error(&self, error: &PredictionError) -> Result<(), String>;
+
+    /// Последние `limit` ошибок арендатора в порядке поступления
+    async fn learning_errors(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<PredictionError>, String>;
+
+    /// Удаляет ошибки, записанные раньше `before`; ошибки без времени записи
+    /// (сохраненные до сроков хранения) - тоже. Возвращает число удаленных
+    async fn prune_learning_errors(&self, before: DateTime<Utc>) -> Result<usize, String>;
+
+    /// Удаляет все ошибки арендатора; возвращает их число
+    async fn delete_learning_errors(&self, tenant: &str) -> Result<usize, String>;
+
+    async fn record_anomaly_feedback(&self, feedback: &AnomalyFeedback) -> Result<(), String>;
+
+    /// Последние `limit` отзывов арендатора об аномалиях в порядке поступления
+    async fn anomaly_feedback(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<AnomalyFeedback>, String>;
+
+    /// Удаляет все отзывы арендатора об аномалиях; возвращает их число
+    async fn delete_anomaly_feedback(&self, tenant: &str) -> Result<usize, String>;
+
+    async fn record_recommendation_feedback(
+        &self,
+        feedback: &RecommendationFeedback,
+    ) -> Result<(), String>;
+
+    /// Последние `limit` отзывов арендатора о рекомендациях в порядке поступления
+    async fn recommendation_feedback(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<RecommendationFeedback>, String>;
+
+    /// Удаляет все отзывы арендатора о рекомендациях; возвращает их число
+    async fn delete_recommendation_feedback(&self, tenant: &str) -> Result<usize, String>;
+
+    async fn save_job(&self, job: &JobInfo) -> Result<(), String>;
+
+    async fn remove_job(&self, tenant: &str, id: &str) -> Result<(), String>;
+
+    /// Выполняющиеся задачи арендатора на всех репликах
+    async fn jobs(&self, tenant: &str) -> Result<Vec<JobInfo>, String>;
+
+    /// Имена моделей, сохраненных другими репликами после переобучения;
+    /// `None`, если хранилище не рассылает оповещения
+    async fn model_updates(&self) -> Result<Option<BoxStream<'static, String>>, String> {
+        Ok(None)
+    }
+}
+
+/// Хранилище в памяти процесса (одна реплика)
+#[derive(Default)]
+pub struct MemoryStorage {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    models: std::collections::HashMap<String, String>,
+    learning_errors: Vec<PredictionError>,
+    anomaly_feedback: Vec<AnomalyFeedback>,
+    recommendation_feedback: Vec<RecommendationFeedback>,
+    jobs: Vec<JobInfo>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn save_model(&self, name: &str, snapshot: &str) -> Result<(), String> {
+        self.lock()
+            .models
+            .insert(name.to_string(), snapshot.to_string());
+        Ok(())
+    }
+
+    async fn load_model(&self, name: &str) -> Result<Option<String>, String> {
+        Ok(self.lock().models.get(name).cloned())
+    }
+
+    async fn delete_model(&self, name: &str) -> Result<bool, String> {
+        Ok(self.lock().models.remove(name).is_some())
+    }
+
+    async fn record_learning_error(&self, error: &PredictionError) -> Result<(), String> {
+        self.lock().learning_errors.push(error.clone());
+        Ok(())
+    }
+
+    async fn learning_errors(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<PredictionError>, String> {
+        let tenant_errors: Vec<PredictionError> = self
+            .lock()
+            .learning_errors
+            .iter()
+            .filter(|e| e.tenant == tenant)
+            .cloned()
+            .collect();
+        Ok(last(&tenant_errors, limit))
+    }
+
+    async fn prune_learning_errors(&self, before: DateTime<Utc>) -> Result<usize, String> {
+        let mut state = self.lock();
+        let count = state.learning_errors.len();
+        state.learning_errors.retain(|error| {
+            error
+                .recorded_at
+                .as_deref()
+                .is_some_and(|at| !is_expired(at, before))
+        });
+        Ok(count - state.learning_errors.len())
+    }
+
+    async fn delete_learning_errors(&self, tenant: &str) -> Result<usize, String> {
+        let mut state = self.lock();
+        let count = state.learning_errors.len();
+        state.learning_errors.retain(|e| e.tenant != tenant);
+        Ok(count - state.learning_errors.len())
+    }
+
+    async fn record_anomaly_feedback(&self, feedback: &AnomalyFeedback) -> Result<(), String> {
+        self.lock().anomaly_feedback.push(feedback.clone());
+        Ok(())
+    }
+
+    async fn anomaly_feedback(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<AnomalyFeedback>, String> {
+        let tenant_feedback: Vec<AnomalyFeedback> = self
+            .lock()
+            .anomaly_feedback
+            .iter()
+            .filter(|f| f.tenant == tenant)
+            .cloned()
+            .collect();
+        Ok(last(&tenant_feedback, limit))
+    }
+
+    async fn delete_anomaly_feedback(&self, tenant: &str) -> Result<usize, String> {
+        let mut state = self.lock();
+        let count = state.anomaly_feedback.len();
+        state.anomaly_feedback.retain(|f| f.tenant != tenant);
+        Ok(count - state.anomaly_feedback.len())
+    }
+
+    async fn record_recommendation_feedback(
+        &self,
+        feedback: &RecommendationFeedback,
+    ) -> Result<(), String> {
+        self.lock().recommendation_feedback.push(feedback.clone());
+        Ok(())
+    }
+
+    async fn recommendation_feedback(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<RecommendationFeedback>, String> {
+        let tenant_feedback: Vec<RecommendationFeedback> = self
+            .lock()
+            .recommendation_feedback
+            .iter()
+            .filter(|f| f.tenant == tenant)
+            .cloned()
+            .collect();
+        Ok(last(&tenant_feedback, limit))
+    }
+
+    async fn delete_recommendation_feedback(&self, tenant: &str) -> Result<usize, String> {
+        let mut state = self.lock();
+        let count = state.recommendation_feedback.len();
+        state.recommendation_feedback.retain(|f| f.tenant != tenant);
+        Ok(count - state.recommendation_feedback.len())
+    }
+
+    async fn save_job(&self, job: &JobInfo) -> Result<(), String> {
+        let mut state = self.lock();
+        state
+            .jobs
+            .retain(|j| j.id != job.id || j.tenant != job.tenant);
+        state.jobs.push(job.clone());
+        Ok(())
+    }
+
+    async fn remove_job(&self, tenant: &str, id: &str) -> Result<(), String> {
+        self.lock()
+            .jobs
+            .retain(|j| j.id != id || j.tenant != tenant);
+        Ok(())
+    }
+
+    async fn jobs(&self, tenant: &str) -> Result<Vec<JobInfo>, String> {
+        Ok(self
+            .lock()
+            .jobs
+            .iter()
+            .filter(|j| j.tenant == tenant)
+            .cloned()
+            .collect())
+    }
+}
+
+fn last<T: Clone>(items: &[T], limit: usize) -> Vec<T> {
+    items[items.len().saturating_sub(limit)..].to_vec()
+}
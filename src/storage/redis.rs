@@ -0,0 +1,309 @@
+//! Хранилище в Redis: снимки моделей и оповещения реплик о переобучении
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{BoxStream, StreamExt};
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+
+use super::Storage;
+use crate::cache::redis::redis_error;
+use crate::jobs::JobInfo;
+use crate::models::PredictionError;
+use crate::retention::is_expired;
+use crate::types::{AnomalyFeedback, RecommendationFeedback};
+
+const KEY_PREFIX: &str = "kimai-ml";
+/// Канал оповещений о сохранении новых снимков моделей
+const MODEL_UPDATES_CHANNEL: &str = "kimai-ml:model-updates";
+/// Сколько последних записей хранится в списках ошибок и отзывов
+const MAX_RECORDS: isize = 10_000;
+
+#[derive(Serialize, Deserialize)]
+struct ModelUpdate {
+    instance: String,
+    model: String,
+}
+
+pub struct RedisStorage {
+    client: redis::Client,
+    connection: ConnectionManager,
+    /// Идентификатор реплики, чтобы не реагировать на собственные оповещения
+    instance: String,
+}
+
+impl RedisStorage {
+    pub async fn connect(url: &str) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(redis_error)?;
+        let connection = client.get_connection_manager().await.map_err(redis_error)?;
+        Ok(Self {
+            client,
+            connection,
+            instance: format!("{:016x}", rand::random::<u64>()),
+        })
+    }
+
+    fn key(name: &str) -> String {
+        format!("{}:{}", KEY_PREFIX, name)
+    }
+
+    async fn push_record<T: Serialize + Sync>(&self, list: &str, record: &T) -> Result<(), String> {
+        let json =
+            serde_json::to_string(record).map_err(|e| format!("Serialization error: {}", e))?;
+        let mut connection = self.connection.clone();
+        redis::pipe()
+            .rpush(Self::key(list), json)
+            .ignore()
+            .ltrim(Self::key(list), -MAX_RECORDS, -1)
+            .ignore()
+            .query_async(&mut connection)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn records<T: for<'de> Deserialize<'de>>(
+        &self,
+        list: &str,
+        limit: usize,
+    ) -> Result<Vec<T>, String> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        let items: Vec<String> = self
+            .connection
+            .clone()
+            .lrange(Self::key(list), -(limit as isize), -1)
+            .await
+            .map_err(redis_error)?;
+        items
+            .iter()
+            .map(|item| {
+                serde_json::from_str(item).map_err(|e| format!("Deserialization error: {}", e))
+            })
+            .collect()
+    }
+
+    /// Удаляет список; возвращает число его записей
+    async fn delete_list(&self, list: &str) -> Result<usize, String> {
+        let list = Self::key(list);
+        let mut connection = self.connection.clone();
+        let (count, _): (usize, usize) = redis::pipe()
+            .atomic()
+            .llen(&list)
+            .del(&list)
+            .query_async(&mut connection)
+            .await
+            .map_err(redis_error)?;
+        Ok(count)
+    }
+
+    /// Списки арендаторов `<list>:<арендатор>` (имена без префикса ключей)
+    async fn tenant_lists(&self, list: &str) -> Result<Vec<String>, String> {
+        let mut connection = self.connection.clone();
+        let mut keys: redis::AsyncIter<'_, String> = connection
+            .scan_match(format!("{}:*", Self::key(list)))
+            .await
+            .map_err(redis_error)?;
+        let prefix = format!("{}:", KEY_PREFIX);
+        let mut lists = Vec::new();
+        while let Some(key) = keys.next_item().await {
+            if let Some(name) = key.strip_prefix(&prefix) {
+                lists.push(name.to_string());
+            }
+        }
+        Ok(lists)
+    }
+}
+
+/// Поле задачи в хэше `jobs`: идентификаторы задач уникальны в пределах арендатора
+fn job_field(tenant: &str, id: &str) -> String {
+    serde_json::json!([tenant, id]).to_string()
+}
+
+#[async_trait]
+impl Storage for RedisStorage {
+    async fn save_model(&self, name: &str, snapshot: &str) -> Result<(), String> {
+        let update = serde_json::to_string(&ModelUpdate {
+            instance: self.instance.clone(),
+            model: name.to_string(),
+        })
+        .map_err(|e| format!("Serialization error: {}", e))?;
+
+        let mut connection = self.connection.clone();
+        redis::pipe()
+            .set(Self::key(&format!("model:{}", name)), snapshot)
+            .ignore()
+            .publish(MODEL_UPDATES_CHANNEL, update)
+            .ignore()
+            .query_async(&mut connection)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn load_model(&self, name: &str) -> Result<Option<String>, String> {
+        self.connection
+            .clone()
+            .get(Self::key(&format!("model:{}", name)))
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn delete_model(&self, name: &str) -> Result<bool, String> {
+        let deleted: usize = self
+            .connection
+            .clone()
+            .del(Self::key(&format!("model:{}", name)))
+            .await
+            .map_err(redis_error)?;
+        Ok(deleted > 0)
+    }
+
+    async fn record_learning_error(&self, error: &PredictionError) -> Result<(), String> {
+        let list = format!("learning-errors:{}", error.tenant);
+        self.push_record(&list, error).await
+    }
+
+    async fn learning_errors(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<PredictionError>, String> {
+        let list = format!("learning-errors:{}", tenant);
+        self.records(&list, limit).await
+    }
+
+    async fn prune_learning_errors(&self, before: DateTime<Utc>) -> Result<usize, String> {
+        let mut pruned = 0;
+        for list in self.tenant_lists("learning-errors").await? {
+            // Список упорядочен по времени записи: удаляется его начало, а ошибки,
+            // добавленные другими репликами во время очистки, попадают в конец
+            let errors: Vec<PredictionError> = self.records(&list, MAX_RECORDS as usize).await?;
+            let expired = errors
+                .iter()
+                .take_while(|error| {
+                    error
+                        .recorded_at
+                        .as_deref()
+                        .is_none_or(|at| is_expired(at, before))
+                })
+                .count();
+            if expired > 0 {
+                self.connection
+                    .clone()
+                    .ltrim::<_, ()>(Self::key(&list), expired as isize, -1)
+                    .await
+                    .map_err(redis_error)?;
+            }
+            pruned += expired;
+        }
+        Ok(pruned)
+    }
+
+    async fn delete_learning_errors(&self, tenant: &str) -> Result<usize, String> {
+        self.delete_list(&format!("learning-errors:{}", tenant))
+            .await
+    }
+
+    async fn record_anomaly_feedback(&self, feedback: &AnomalyFeedback) -> Result<(), String> {
+        let list = format!("anomaly-feedback:{}", feedback.tenant);
+        self.push_record(&list, feedback).await
+    }
+
+    async fn anomaly_feedback(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<AnomalyFeedback>, String> {
+        let list = format!("anomaly-feedback:{}", tenant);
+        self.records(&list, limit).await
+    }
+
+    async fn delete_anomaly_feedback(&self, tenant: &str) -> Result<usize, String> {
+        self.delete_list(&format!("anomaly-feedback:{}", tenant))
+            .await
+    }
+
+    async fn record_recommendation_feedback(
+        &self,
+        feedback: &RecommendationFeedback,
+    ) -> Result<(), String> {
+        let list = format!("recommendation-feedback:{}", feedback.tenant);
+        self.push_record(&list, feedback).await
+    }
+
+    async fn recommendation_feedback(
+        &self,
+        tenant: &str,
+        limit: usize,
+    ) -> Result<Vec<RecommendationFeedback>, String> {
+        let list = format!("recommendation-feedback:{}", tenant);
+        self.records(&list, limit).await
+    }
+
+    async fn delete_recommendation_feedback(&self, tenant: &str) -> Result<usize, String> {
+        self.delete_list(&format!("recommendation-feedback:{}", tenant))
+            .await
+    }
+
+    async fn save_job(&self, job: &JobInfo) -> Result<(), String> {
+        let json = serde_json::to_string(job).map_err(|e| format!("Serialization error: {}", e))?;
+        self.connection
+            .clone()
+            .hset(Self::key("jobs"), job_field(&job.tenant, &job.id), json)
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn remove_job(&self, tenant: &str, id: &str) -> Result<(), String> {
+        self.connection
+            .clone()
+            .hdel(Self::key("jobs"), job_field(tenant, id))
+            .await
+            .map_err(redis_error)
+    }
+
+    async fn jobs(&self, tenant: &str) -> Result<Vec<JobInfo>, String> {
+        let items: Vec<String> = self
+            .connection
+            .clone()
+            .hvals(Self::key("jobs"))
+            .await
+            .map_err(redis_error)?;
+        let mut jobs = items
+            .iter()
+            .map(|item| {
+                serde_json::from_str::<JobInfo>(item)
+                    .map_err(|e| format!("Deserialization error: {}", e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        jobs.retain(|job| job.tenant == tenant);
+        jobs.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        Ok(jobs)
+    }
+
+    async fn model_updates(&self) -> Result<Option<BoxStream<'static, String>>, String> {
+        let mut pubsub = self
+            .client
+            .get_async_connection()
+            .await
+            .map_err(redis_error)?
+            .into_pubsub();
+        pubsub
+            .subscribe(MODEL_UPDATES_CHANNEL)
+            .await
+            .map_err(redis_error)?;
+
+        let instance = self.instance.clone();
+        let updates = pubsub.into_on_message().filter_map(move |message| {
+            let update = message
+                .get_payload::<String>()
+                .ok()
+                .and_then(|payload| serde_json::from_str::<ModelUpdate>(&payload).ok())
+                .filter(|update| update.instance != instance)
+                .map(|update| update.model);
+            async move { update }
+        });
+        Ok(Some(updates.boxed()))
+    }
+}
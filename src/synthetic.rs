@@ -0,0 +1,273 @@
+//! Синтетические записи времени для демонстраций, предпросмотра аналитики и
+//! нагрузочного тестирования.
+//!
+//! `generate(profile, weeks, seed)` детерминированно строит записи, недельные
+//! агрегаты и проекты одного из профилей пользователей (`Profile`). В каждый набор
+//! заложены две аномалии - 14-часовая сессия и запись в необычный для профиля час
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike, Weekday};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::ingest::WeeklyAggregator;
+use crate::types::{MLInputData, Project, Settings, TimesheetEntry};
+
+/// Ставка синтетических данных, за минуту
+pub const SYNTHETIC_RATE_PER_MINUTE: f64 = 1.0;
+
+/// Профиль пользователя синтетических данных
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    /// Будни с 9 до 18, нагрузка меняется по сезонам (летний спад)
+    SeasonalFreelancer,
+    /// Много проектов и встреч, недели авралов чередуются со спокойными
+    BurstyAgency,
+    /// Работа поздним вечером и ночью, в том числе в выходные
+    NightOwl,
+}
+
+impl Profile {
+    pub const ALL: [Profile; 3] = [
+        Profile::SeasonalFreelancer,
+        Profile::BurstyAgency,
+        Profile::NightOwl,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Profile::SeasonalFreelancer => "seasonal_freelancer",
+            Profile::BurstyAgency => "bursty_agency",
+            Profile::NightOwl => "night_owl",
+        }
+    }
+}
+
+/// Сгенерированные данные
+#[derive(Debug, Clone)]
+pub struct SyntheticData {
+    /// Записи (`timesheets`), недели (`weeks`) и проекты с настройками по умолчанию
+    pub input: MLInputData,
+    /// `id` записей с заложенными аномалиями
+    pub injected_anomalies: Vec<i32>,
+}
+
+/// Данные профиля за `weeks` недель начиная с понедельника 1 января 2024
+pub fn generate(profile: Profile, weeks: usize, seed: u64) -> SyntheticData {
+    let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap_or_default();
+    generate_from(profile, weeks, seed, start)
+}
+
+/// Данные профиля за `weeks` недель начиная с недели `start` (с ее понедельника).
+/// Одинаковые профиль, число недель, зерно и неделя начала дают одинаковые данные
+pub fn generate_from(profile: Profile, weeks: usize, seed: u64, start: NaiveDate) -> SyntheticData {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let project_names: &[&str] = match profile {
+        Profile::SeasonalFreelancer => &["Website redesign", "Mobile app", "Support retainer"],
+        Profile::BurstyAgency => &[
+            "Brand campaign",
+            "E-commerce launch",
+            "Annual report",
+            "Internal tools",
+            "Pitch deck",
+        ],
+        Profile::NightOwl => &["Open source", "Game prototype"],
+    };
+    let first_day = start.week(Weekday::Mon).first_day();
+
+    let mut entries = Vec::new();
+    for week in 0..weeks {
+        let monday = first_day + Duration::weeks(week as i64);
+        let sessions = week_sessions(profile, monday, &mut rng);
+        for (day, day_sessions) in sessions.into_iter().enumerate() {
+            let date = monday + Duration::days(day as i64);
+            for (start, minutes, project, activity) in day_sessions {
+                let begin =
+                    date.and_hms_opt(0, 0, 0).unwrap_or_default() + Duration::minutes(start);
+                entries.push(entry(
+                    entries.len() as i32 + 1,
+                    begin,
+                    minutes,
+                    project,
+                    project_names[project],
+                    activity,
+                ));
+            }
+        }
+    }
+
+    // Заложенные аномалии: 14-часовая запись и запись в необычный для профиля час
+    let mut injected_anomalies = Vec::new();
+    let odd_hour = match profile {
+        Profile::NightOwl => 7 * 60,
+        _ => 3 * 60,
+    };
+    let injected = [(weeks / 3, 8 * 60, 14 * 60), (weeks * 2 / 3, odd_hour, 45)];
+    for (week, start, minutes) in injected.into_iter().filter(|_| weeks > 0) {
+        let date = first_day + Duration::days((week * 7 + 2) as i64);
+        let begin = date.and_hms_opt(0, 0, 0).unwrap_or_default() + Duration::minutes(start);
+        let id = entries.len() as i32 + 1;
+        entries.push(entry(
+            id,
+            begin,
+            minutes,
+            0,
+            project_names[0],
+            "Development",
+        ));
+        injected_anomalies.push(id);
+    }
+    entries.sort_by(|a, b| a.begin.cmp(&b.begin));
+
+    let mut aggregator = WeeklyAggregator::new(SYNTHETIC_RATE_PER_MINUTE);
+    for entry in &entries {
+        aggregator.add(entry);
+    }
+    let week_data = aggregator.finish();
+    let projects = project_names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let id = index as i32 + 1;
+            let hours: Vec<f64> = week_data
+                .iter()
+                .filter_map(|w| w.project_stats.iter().find(|s| s.project_id == id))
+                .map(|s| s.hours)
+                .collect();
+            let total_hours: f64 = hours.iter().sum();
+            Project {
+                id,
+                name: name.to_string(),
+                total_hours,
+                avg_hours_per_week: total_hours / hours.len().max(1) as f64,
+                weeks_count: hours.len() as i32,
+            }
+        })
+        .collect();
+
+    let input = MLInputData {
+        timesheets: entries,
+        projects,
+        weeks: week_data,
+        settings: Settings {
+            rate_per_minute: SYNTHETIC_RATE_PER_MINUTE,
+            cost_per_minute: None,
+            project_settings: Default::default(),
+            user_preferences: None,
+            features: Default::default(),
+            meeting_patterns: Vec::new(),
+            analyzer: Default::default(),
+            duration_unit: Default::default(),
+            segment_by_tags: Vec::new(),
+            billable_tags: Vec::new(),
+            holiday_country: None,
+            utilization: Default::default(),
+        },
+        context: None,
+        options: None,
+    };
+    SyntheticData {
+        input,
+        injected_anomalies,
+    }
+}
+
+/// Рабочий отрезок дня: начало (минуты от полуночи), длительность, проект, активность
+type Session = (i64, i64, usize, &'static str);
+
+/// Отрезки каждого дня недели, начинающейся в понедельник `monday`
+fn week_sessions(profile: Profile, monday: NaiveDate, rng: &mut StdRng) -> [Vec<Session>; 7] {
+    let mut days: [Vec<Session>; 7] = Default::default();
+    match profile {
+        Profile::SeasonalFreelancer => {
+            // Годовой цикл с минимумом в середине лета (неделя 30)
+            let week = monday.iso_week().week0() as f64;
+            let phase = 2.0 * std::f64::consts::PI * (week - 4.0) / 52.0;
+            let weekly_hours = 32.0 + 6.0 * phase.cos() + rng.gen_range(-2.0..2.0);
+            for day in days.iter_mut().take(5) {
+                let mut left = (weekly_hours / 5.0 * 60.0) as i64;
+                let mut start = 9 * 60 + rng.gen_range(0..4) * 15;
+                while left > 20 {
+                    let minutes = left.min(rng.gen_range(90..=180));
+                    let project = rng.gen_range(0..3);
+                    let activity = if rng.gen_bool(0.1) {
+                        "Client call"
+                    } else if project == 2 {
+                        "Support"
+                    } else {
+                        "Development"
+                    };
+                    day.push((start, minutes, project, activity));
+                    start += minutes + if start < 13 * 60 { 15 } else { 60 };
+                    left -= minutes;
+                }
+            }
+        }
+        Profile::BurstyAgency => {
+            let crunch = rng.gen_bool(0.3);
+            for day in days.iter_mut().take(5) {
+                let mut start = 8 * 60 + rng.gen_range(0..4) * 15;
+                let end = if crunch { 20 * 60 } else { 14 * 60 + 30 };
+                day.push((start, 15, 3, "Standup"));
+                start += 30;
+                while start < end {
+                    let minutes = rng.gen_range(2..=8) * 15;
+                    let (project, activity) = if rng.gen_bool(0.25) {
+                        (rng.gen_range(0..5), "Meeting")
+                    } else {
+                        (rng.gen_range(0..5), "Production")
+                    };
+                    day.push((start, minutes, project, activity));
+                    start += minutes + rng.gen_range(0..3) * 15;
+                }
+            }
+        }
+        Profile::NightOwl => {
+            for day in days.iter_mut() {
+                if rng.gen_bool(0.3) {
+                    continue;
+                }
+                let start = (21 * 60 + rng.gen_range(0..12) * 15) as i64;
+                let minutes = rng.gen_range(90..=240);
+                let project = rng.gen_range(0..2);
+                let activity = if rng.gen_bool(0.3) {
+                    "Research"
+                } else {
+                    "Development"
+                };
+                day.push((start, minutes, project, activity));
+            }
+        }
+    }
+    days
+}
+
+fn entry(
+    id: i32,
+    begin: NaiveDateTime,
+    minutes: i64,
+    project: usize,
+    project_name: &str,
+    activity: &str,
+) -> TimesheetEntry {
+    let end = begin + Duration::minutes(minutes);
+    TimesheetEntry {
+        id,
+        begin: begin.and_utc().to_rfc3339(),
+        end: Some(end.and_utc().to_rfc3339()),
+        duration: minutes as i32,
+        project_id: Some(project as i32 + 1),
+        project_name: project_name.to_string(),
+        activity_id: None,
+        activity_name: activity.to_string(),
+        description: None,
+        tags: Vec::new(),
+        day_of_week: begin.weekday().num_days_from_sunday() as i32,
+        hour_of_day: begin.hour() as i32,
+        week_of_year: begin.iso_week().week() as i32,
+        month: begin.month() as i32,
+        year: begin.year(),
+        billable: None,
+    }
+}
@@ -0,0 +1,205 @@
+//! Состояние арендаторов в памяти сервера.
+//!
+//! Модели прогноза и аномалий, модуль обучения и данные переобучения у каждого
+//! арендатора свои: обучение одного арендатора не меняет ответы другому.
+//! Снимки моделей хранятся под именами арендаторов (`forecasting:acme`)
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Арендаторов в памяти по умолчанию (`TENANT_STATE_CAPACITY`)
+pub const DEFAULT_TENANT_CAPACITY: usize = 1000;
+
+/// Значения по арендаторам, каждое под своей асинхронной блокировкой: запросы
+/// разных арендаторов не ждут друг друга. Арендаторов не больше `capacity`:
+/// новый вытесняет дольше всех не обращавшегося, его модели потом загружаются
+/// из снимков заново
+pub struct TenantMap<T> {
+    capacity: usize,
+    slots: Mutex<HashMap<String, Slot<T>>>,
+}
+
+struct Slot<T> {
+    used_at: Instant,
+    value: Arc<tokio::sync::Mutex<T>>,
+}
+
+impl<T> Default for TenantMap<T> {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_TENANT_CAPACITY)
+    }
+}
+
+impl<T> TenantMap<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Не больше `capacity` арендаторов (не меньше одного)
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Slot<T>>> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Значение арендатора, если оно уже создано
+    pub fn get(&self, tenant: &str) -> Option<Arc<tokio::sync::Mutex<T>>> {
+        let mut slots = self.lock();
+        let slot = slots.get_mut(tenant)?;
+        slot.used_at = Instant::now();
+        Some(slot.value.clone())
+    }
+
+    /// Значение арендатора; при первом обращении - `init()`. Если значение
+    /// параллельно создал другой запрос, остается оно
+    pub fn get_or_insert_with(
+        &self,
+        tenant: &str,
+        init: impl FnOnce() -> T,
+    ) -> Arc<tokio::sync::Mutex<T>> {
+        let mut slots = self.lock();
+        if !slots.contains_key(tenant) && slots.len() >= self.capacity {
+            // Значения, которые сейчас используют запросы, не вытесняются:
+            // иначе их изменения не увидел бы следующий запрос
+            let oldest = slots
+                .iter()
+                .filter(|(_, slot)| Arc::strong_count(&slot.value) == 1)
+                .min_by_key(|(_, slot)| slot.used_at)
+                .map(|(t, _)| t.clone());
+            if let Some(oldest) = oldest {
+                slots.remove(&oldest);
+            }
+        }
+        let slot = slots.entry(tenant.to_string()).or_insert_with(|| Slot {
+            used_at: Instant::now(),
+            value: Arc::new(tokio::sync::Mutex::new(init())),
+        });
+        slot.used_at = Instant::now();
+        slot.value.clone()
+    }
+
+    /// `false`, если значения арендатора нет
+    pub fn remove(&self, tenant: &str) -> bool {
+        self.lock().remove(tenant).is_some()
+    }
+
+    /// Арендаторы с созданными значениями
+    pub fn tenants(&self) -> Vec<String> {
+        let mut tenants: Vec<String> = self.lock().keys().cloned().collect();
+        tenants.sort();
+        tenants
+    }
+}
+
+/// Имя снимка модели `model` арендатора в хранилище и реестре
+pub fn model_name(model: &str, tenant: &str) -> String {
+    format!("{}:{}", model, tenant)
+}
+
+/// Модель и арендатор из имени снимка `model_name`
+pub fn parse_model_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once(':')
+        .filter(|(model, tenant)| !model.is_empty() && !tenant.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::forecasting::ForecastingModel;
+    use crate::types::WeekData;
+
+    fn weeks(hours: f64) -> Vec<WeekData> {
+        (1..=20)
+            .map(|week| WeekData {
+                year: 2024,
+                week,
+                total_minutes: (hours * 60.0) as i32,
+                total_hours: hours + (week % 3) as f64,
+                total_amount: 0.0,
+                project_stats: Vec::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn model_names_round_trip() {
+        let name = model_name("forecasting", "acme:eu");
+        assert_eq!(name, "forecasting:acme:eu");
+        assert_eq!(parse_model_name(&name), Some(("forecasting", "acme:eu")));
+        assert_eq!(parse_model_name("forecasting"), None);
+    }
+
+    #[tokio::test]
+    async fn tenants_train_and_predict_independently() {
+        let models: TenantMap<ForecastingModel> = TenantMap::new();
+        let (acme, globex) = (weeks(40.0), weeks(10.0));
+
+        models
+            .get_or_insert_with("acme", ForecastingModel::new)
+            .lock()
+            .await
+            .train(&acme)
+            .unwrap();
+        let acme_before = models
+            .get("acme")
+            .unwrap()
+            .lock()
+            .await
+            .predict(&acme)
+            .unwrap()
+            .weekly_hours;
+        // У нового арендатора модель не обучена
+        let globex_model = models.get_or_insert_with("globex", ForecastingModel::new);
+        assert!(globex_model.lock().await.model_info().is_none());
+
+        globex_model.lock().await.train(&globex).unwrap();
+        let globex_hours = globex_model
+            .lock()
+            .await
+            .predict(&globex)
+            .unwrap()
+            .weekly_hours;
+        let acme_after = models
+            .get("acme")
+            .unwrap()
+            .lock()
+            .await
+            .predict(&acme)
+            .unwrap()
+            .weekly_hours;
+
+        assert_eq!(acme_before, acme_after);
+        assert!((acme_after - 41.0).abs() < 5.0, "acme {}", acme_after);
+        assert!((globex_hours - 11.0).abs() < 5.0, "globex {}", globex_hours);
+        assert_eq!(models.tenants(), vec!["acme", "globex"]);
+
+        assert!(models.remove("globex"));
+        assert!(models.get("globex").is_none());
+        assert!(!models.remove("globex"));
+    }
+
+    #[test]
+    fn least_recently_used_idle_tenant_is_evicted() {
+        let values: TenantMap<u32> = TenantMap::with_capacity(2);
+        values.get_or_insert_with("acme", || 1);
+        values.get_or_insert_with("globex", || 2);
+        // acme использован позже globex
+        assert!(values.get("acme").is_some());
+
+        values.get_or_insert_with("initech", || 3);
+        assert_eq!(values.tenants(), vec!["acme", "initech"]);
+
+        // Используемое запросом значение не вытесняется даже самым старым
+        let held = values.get("acme").unwrap();
+        values.get("initech");
+        values.get_or_insert_with("umbrella", || 4);
+        assert_eq!(values.tenants(), vec!["acme", "umbrella"]);
+        drop(held);
+    }
+}
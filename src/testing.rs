@@ -0,0 +1,234 @@
+//! Эталонные наборы данных для регрессионной проверки моделей.
+//!
+//! Три профиля пользователей (`Profile`) генерируются `synthetic::generate` по
+//! `GOLDEN_SEED`: сезонный фрилансер, агентство с авралами и «сова». Эталонные
+//! показатели (`ExpectedMetrics`) - прогноз часов, тренд и число аномалий -
+//! получены моделями с зерном `GOLDEN_SEED` и проверяются с допуском; изменение
+//! поведения моделей обновляет их вместе с кодом.
+//!
+//! `GoldenDataset::verify` обучает модели этой сборки с фиксированными зернами и
+//! сравнивает результат с эталоном. Ответы развернутого сервера на `input` набора
+//! проверяются `check_forecast` и `check_anomalies`
+
+use serde::Serialize;
+
+use crate::models::anomaly_detection::AnomalyDetector;
+use crate::models::forecasting::ForecastingModel;
+use crate::synthetic::{self, SyntheticData};
+use crate::types::{AnomalyOutput, ForecastingOutput, MLInputData};
+
+pub use crate::synthetic::Profile;
+
+/// Зерно генерации эталонных наборов и обучения моделей
+pub const GOLDEN_SEED: u64 = 42;
+
+/// Недель истории в эталонных наборах
+pub const GOLDEN_WEEKS: usize = 26;
+
+/// Допустимое отклонение прогноза от эталона. Эталон получен в f64; в f32
+/// округление меняет решения ridge-регрессии, прогноз расходится сильнее
+#[cfg(not(feature = "f32"))]
+pub const WEEKLY_HOURS_TOLERANCE: f64 = 0.05;
+#[cfg(feature = "f32")]
+pub const WEEKLY_HOURS_TOLERANCE: f64 = 0.1;
+
+/// Эталонные показатели набора
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpectedMetrics {
+    /// Прогноз часов на следующую неделю
+    pub weekly_hours: f64,
+    /// Допустимое относительное отклонение прогноза
+    pub weekly_hours_tolerance: f64,
+    /// `ForecastingOutput::trend`
+    pub trend: &'static str,
+    /// Число найденных аномалий
+    pub anomalies: usize,
+    /// Допустимое отклонение числа аномалий, доля записей
+    pub anomaly_share_tolerance: f64,
+}
+
+/// Результат сравнения одного показателя с эталоном
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricCheck {
+    pub metric: String,
+    pub expected: String,
+    pub actual: String,
+    pub ok: bool,
+}
+
+/// Проверки набора
+#[derive(Debug, Clone, Serialize)]
+pub struct GoldenReport {
+    pub profile: Profile,
+    pub checks: Vec<MetricCheck>,
+}
+
+impl GoldenReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.ok)
+    }
+
+    /// Паника со списком не прошедших проверок (для тестов интеграторов)
+    pub fn assert_passed(&self) {
+        let failed: Vec<String> = self
+            .checks
+            .iter()
+            .filter(|check| !check.ok)
+            .map(|check| {
+                format!(
+                    "{}: expected {}, got {}",
+                    check.metric, check.expected, check.actual
+                )
+            })
+            .collect();
+        assert!(
+            failed.is_empty(),
+            "Golden dataset {} failed: {}",
+            self.profile.name(),
+            failed.join("; ")
+        );
+    }
+}
+
+/// Эталонный набор: входные данные и ожидаемые показатели
+#[derive(Debug, Clone)]
+pub struct GoldenDataset {
+    pub profile: Profile,
+    pub input: MLInputData,
+    pub expected: ExpectedMetrics,
+    /// Записи с заложенными аномалиями (14-часовая сессия и запись в
+    /// необычный для профиля час)
+    pub injected_anomalies: Vec<i32>,
+}
+
+impl GoldenDataset {
+    pub fn new(profile: Profile) -> Self {
+        let SyntheticData {
+            input,
+            injected_anomalies,
+        } = synthetic::generate(profile, GOLDEN_WEEKS, GOLDEN_SEED);
+        let (weekly_hours, trend, anomalies) = match profile {
+            Profile::SeasonalFreelancer => (34.00, "decreasing", 45),
+            Profile::BurstyAgency => (29.31, "stable", 84),
+            Profile::NightOwl => (12.62, "stable", 13),
+        };
+        Self {
+            profile,
+            input,
+            expected: ExpectedMetrics {
+                weekly_hours,
+                weekly_hours_tolerance: WEEKLY_HOURS_TOLERANCE,
+                trend,
+                anomalies,
+                anomaly_share_tolerance: 0.02,
+            },
+            injected_anomalies,
+        }
+    }
+
+    /// Наборы всех профилей
+    pub fn all() -> Vec<Self> {
+        Profile::ALL.into_iter().map(Self::new).collect()
+    }
+
+    pub fn check_forecast(&self, forecast: &ForecastingOutput) -> Vec<MetricCheck> {
+        let expected = &self.expected;
+        let deviation = (forecast.weekly_hours - expected.weekly_hours).abs();
+        vec![
+            MetricCheck {
+                metric: "weekly_hours".to_string(),
+                expected: format!(
+                    "{:.1} ± {:.0}%",
+                    expected.weekly_hours,
+                    expected.weekly_hours_tolerance * 100.0
+                ),
+                actual: format!("{:.1}", forecast.weekly_hours),
+                ok: deviation <= expected.weekly_hours * expected.weekly_hours_tolerance,
+            },
+            MetricCheck {
+                metric: "trend".to_string(),
+                expected: expected.trend.to_string(),
+                actual: forecast.trend.clone(),
+                ok: forecast.trend == expected.trend,
+            },
+        ]
+    }
+
+    pub fn check_anomalies(&self, anomalies: &[AnomalyOutput]) -> Vec<MetricCheck> {
+        let expected = &self.expected;
+        let tolerance = expected.anomaly_share_tolerance * self.input.timesheets.len() as f64;
+        // Внесенные аномалии должны быть среди отмеченных: число отмеченных
+        // записей само по себе не ловит перевернутые оценки
+        let injected = self.injected_anomalies.len();
+        let found = self
+            .injected_anomalies
+            .iter()
+            .filter(|id| anomalies.iter().any(|a| a.entry_id == **id))
+            .count();
+        vec![
+            MetricCheck {
+                metric: "anomalies".to_string(),
+                expected: format!("{} ± {:.0}", expected.anomalies, tolerance),
+                actual: anomalies.len().to_string(),
+                ok: (anomalies.len() as f64 - expected.anomalies as f64).abs() <= tolerance,
+            },
+            MetricCheck {
+                metric: "injected_anomalies".to_string(),
+                expected: format!(">= {} of {}", injected.div_ceil(2), injected),
+                actual: found.to_string(),
+                ok: found >= injected.div_ceil(2),
+            },
+        ]
+    }
+
+    /// Обучает модели этой сборки на наборе (зерно `GOLDEN_SEED`) и сравнивает
+    /// прогноз и аномалии с эталоном
+    pub fn verify(&self) -> Result<GoldenReport, String> {
+        let mut model = ForecastingModel::builder().seed(GOLDEN_SEED).build()?;
+        model.train(&self.input.weeks)?;
+        let forecast = model.predict(&self.input.weeks)?;
+
+        let mut detector = AnomalyDetector::builder()
+            .contamination(0.1)
+            .seed(GOLDEN_SEED)
+            .build()?;
+        detector.train(&self.input.timesheets)?;
+        let anomalies = detector.detect(&self.input.timesheets)?;
+
+        let mut checks = self.check_forecast(&forecast);
+        checks.extend(self.check_anomalies(&anomalies));
+        Ok(GoldenReport {
+            profile: self.profile,
+            checks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seasonal_freelancer_matches_golden_metrics() {
+        GoldenDataset::new(Profile::SeasonalFreelancer)
+            .verify()
+            .unwrap()
+            .assert_passed();
+    }
+
+    #[test]
+    fn bursty_agency_matches_golden_metrics() {
+        GoldenDataset::new(Profile::BurstyAgency)
+            .verify()
+            .unwrap()
+            .assert_passed();
+    }
+
+    #[test]
+    fn night_owl_matches_golden_metrics() {
+        GoldenDataset::new(Profile::NightOwl)
+            .verify()
+            .unwrap()
+            .assert_passed();
+    }
+}
@@ -0,0 +1,196 @@
+//! Разбор описаний записей на русском и английском.
+//!
+//! Описания смешивают языки, часто в одной записи («созвон по API release»).
+//! Язык определяется по алфавиту каждого слова, так что стоп-слова и
+//! нормализация применяются свои для каждого слова, а не одни на все описание.
+//! Термы несут язык и сравниваются по паре язык-терм, не смешивая, например,
+//! английское `on` с русским `он`. По термам описаний записи относятся к
+//! встречам (`ProductivityAnalyzer::is_meeting`)
+
+use serde::{Deserialize, Serialize};
+
+/// Язык слова
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    Russian,
+    English,
+    /// Без букв (числа, коды задач)
+    Unknown,
+}
+
+/// Слова короче не становятся термами
+pub const MIN_TERM_CHARS: usize = 2;
+
+const RUSSIAN_STOPWORDS: &[&str] = &[
+    "а",
+    "без",
+    "бы",
+    "в",
+    "во",
+    "вот",
+    "все",
+    "да",
+    "для",
+    "до",
+    "его",
+    "ее",
+    "если",
+    "же",
+    "за",
+    "и",
+    "из",
+    "или",
+    "им",
+    "их",
+    "к",
+    "как",
+    "ко",
+    "ли",
+    "на",
+    "над",
+    "не",
+    "нет",
+    "ни",
+    "но",
+    "о",
+    "об",
+    "от",
+    "по",
+    "под",
+    "при",
+    "про",
+    "с",
+    "со",
+    "так",
+    "там",
+    "то",
+    "тоже",
+    "только",
+    "у",
+    "уже",
+    "чем",
+    "что",
+    "это",
+    "эти",
+    "этот",
+    "я",
+    "мы",
+    "вы",
+    "он",
+    "она",
+    "они",
+];
+
+const ENGLISH_STOPWORDS: &[&str] = &[
+    "a", "about", "after", "an", "and", "are", "as", "at", "be", "before", "but", "by", "for",
+    "from", "has", "have", "in", "into", "is", "it", "its", "of", "on", "or", "over", "the",
+    "this", "that", "to", "up", "was", "were", "with", "we", "our", "via",
+];
+
+/// Слово описания после нормализации
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Term {
+    pub language: Language,
+    pub text: String,
+}
+
+/// Язык слова по алфавиту: кириллица - русский, латиница - английский
+pub fn word_language(word: &str) -> Language {
+    let (mut cyrillic, mut latin) = (0, 0);
+    for c in word.chars().filter(|c| c.is_alphabetic()) {
+        if matches!(c, '\u{0400}'..='\u{04FF}') {
+            cyrillic += 1;
+        } else if c.is_ascii_alphabetic() {
+            latin += 1;
+        }
+    }
+    match (cyrillic, latin) {
+        (0, 0) => Language::Unknown,
+        (c, l) if c >= l => Language::Russian,
+        _ => Language::English,
+    }
+}
+
+/// Термы текста: слова в нижнем регистре (`ё` - как `е`) без стоп-слов своего
+/// языка и слов короче `MIN_TERM_CHARS`; числа и коды задач (`API-42`) остаются
+/// с языком `Unknown` или языком букв
+pub fn tokenize(text: &str) -> Vec<Term> {
+    words(text)
+        .filter_map(|word| {
+            let text = word.to_lowercase().replace('ё', "е");
+            let language = word_language(&text);
+            let stopwords = match language {
+                Language::Russian => RUSSIAN_STOPWORDS,
+                Language::English => ENGLISH_STOPWORDS,
+                _ => &[],
+            };
+            (text.chars().count() >= MIN_TERM_CHARS && !stopwords.contains(&text.as_str()))
+                .then_some(Term { language, text })
+        })
+        .collect()
+}
+
+/// Все термы фразы есть среди `terms`: с тем же языком и как начало терма,
+/// чтобы `созвоны` находились по `созвон`, а `recall` не находился по `call`.
+/// Фраза без термов не находится
+pub fn mentions(terms: &[Term], phrase: &str) -> bool {
+    let phrase = tokenize(phrase);
+    !phrase.is_empty()
+        && phrase.iter().all(|wanted| {
+            terms
+                .iter()
+                .any(|term| term.language == wanted.language && term.text.starts_with(&wanted.text))
+        })
+}
+
+/// Слова: буквы и цифры, внутри слова допустимы `-` и `_` (`API-42`, `follow-up`)
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+        .map(|word| word.trim_matches(|c| c == '-' || c == '_'))
+        .filter(|word| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(terms: &[Term]) -> Vec<(Language, &str)> {
+        terms
+            .iter()
+            .map(|t| (t.language, t.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn tokenize_drops_stopwords_of_each_language() {
+        let terms = tokenize("Созвон по API release и on-call на проде, ёлка API-42");
+        assert_eq!(
+            texts(&terms),
+            vec![
+                (Language::Russian, "созвон"),
+                (Language::English, "api"),
+                (Language::English, "release"),
+                (Language::English, "on-call"),
+                (Language::Russian, "проде"),
+                (Language::Russian, "елка"),
+                (Language::English, "api-42"),
+            ]
+        );
+        // `он` - русское стоп-слово, `on` - английское
+        assert!(tokenize("он on").is_empty());
+        assert_eq!(texts(&tokenize("42 x")), vec![(Language::Unknown, "42")]);
+    }
+
+    #[test]
+    fn mentions_matches_word_starts_of_same_language() {
+        let terms = tokenize("Созвоны с клиентом, recall bug, weekly sync-up");
+        assert!(mentions(&terms, "созвон"));
+        assert!(mentions(&terms, "Sync"));
+        assert!(mentions(&terms, "weekly sync"));
+        assert!(!mentions(&terms, "call"));
+        assert!(!mentions(&terms, "daily sync"));
+        assert!(!mentions(&terms, "на"));
+        assert!(!mentions(&[], "созвон"));
+    }
+}
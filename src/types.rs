@@ -1,14 +1,18 @@
 //! Типы данных для ML модуля
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TimesheetEntry {
     pub id: i32,
     pub begin: String,
     pub end: Option<String>,
-    pub duration: i32, // минуты
+    /// Минуты; секунды и ISO-8601 приводятся к ним при разборе запроса
+    /// (`Settings::duration_unit`)
+    #[schemars(schema_with = "crate::schema::duration_schema")]
+    pub duration: i32,
     pub project_id: Option<i32>,
     pub project_name: String,
     pub activity_id: Option<i32>,
@@ -20,9 +24,26 @@ pub struct TimesheetEntry {
     pub week_of_year: i32,
     pub month: i32,
     pub year: i32,
+    /// Оплачиваемая ли запись (поле `billable` Kimai); без него - по
+    /// `Settings::billable_tags`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billable: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TimesheetEntry {
+    /// ISO-неделя записи `(год, неделя)`: первая неделя может начинаться в декабре,
+    /// последняя - заканчиваться в январе
+    pub fn iso_week(&self) -> (i32, i32) {
+        let year = match (self.week_of_year, self.month) {
+            (1, 12) => self.year + 1,
+            (52 | 53, 1) => self.year - 1,
+            _ => self.year,
+        };
+        (year, self.week_of_year)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Project {
     pub id: i32,
     pub name: String,
@@ -31,14 +52,14 @@ pub struct Project {
     pub weeks_count: i32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProjectStats {
     pub project_id: i32,
     pub minutes: i32,
     pub hours: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WeekData {
     pub year: i32,
     pub week: i32,
@@ -48,14 +69,46 @@ pub struct WeekData {
     pub project_stats: Vec<ProjectStats>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProjectSettings {
     pub enabled: bool,
     pub weekly_goal_hours: Option<f64>,
     pub payment_period_weeks: Option<i32>,
+    /// Ставка проекта; по умолчанию `Settings::rate_per_minute`
+    #[serde(default)]
+    pub rate_per_minute: Option<f64>,
+    /// Проект завершен и не учитывается ни одной моделью
+    #[serde(default)]
+    pub archived: bool,
+    /// Часы проекта не входят в прогноз
+    #[serde(default)]
+    pub exclude_from_forecasting: bool,
+    /// Записи проекта не проверяются на аномалии
+    #[serde(default)]
+    pub exclude_from_anomalies: bool,
+}
+
+/// Назначение данных запроса: от него зависит, какие проекты исключаются
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectUsage {
+    Forecasting,
+    Anomalies,
+    /// Рекомендации и продуктивность: исключаются только архивные проекты
+    Analysis,
+}
+
+impl ProjectSettings {
+    pub fn excluded_from(&self, usage: ProjectUsage) -> bool {
+        self.archived
+            || match usage {
+                ProjectUsage::Forecasting => self.exclude_from_forecasting,
+                ProjectUsage::Anomalies => self.exclude_from_anomalies,
+                ProjectUsage::Analysis => false,
+            }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MLInputData {
     pub timesheets: Vec<TimesheetEntry>,
     pub projects: Vec<Project>,
@@ -66,16 +119,199 @@ pub struct MLInputData {
     pub options: Option<JsonValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl MLInputData {
+    /// Убирает проекты, исключенные для `usage`: их записи, сводки и доли недель.
+    /// Часы и минуты недель уменьшаются на часы проекта, выручка - на часы по
+    /// ставке проекта
+    pub fn exclude_projects(&mut self, usage: ProjectUsage) {
+        let excluded: std::collections::HashSet<i32> = self
+            .settings
+            .project_settings
+            .iter()
+            .filter(|(_, s)| s.excluded_from(usage))
+            .map(|(id, _)| *id)
+            .collect();
+        if excluded.is_empty() {
+            return;
+        }
+        let is_excluded = |id: Option<i32>| id.is_some_and(|id| excluded.contains(&id));
+
+        self.timesheets.retain(|e| !is_excluded(e.project_id));
+        self.projects.retain(|p| !excluded.contains(&p.id));
+        for week in &mut self.weeks {
+            for stats in week
+                .project_stats
+                .iter()
+                .filter(|s| excluded.contains(&s.project_id))
+            {
+                week.total_minutes = (week.total_minutes - stats.minutes).max(0);
+                week.total_hours = (week.total_hours - stats.hours).max(0.0);
+                week.total_amount = (week.total_amount
+                    - stats.minutes as f64 * self.settings.project_rate(stats.project_id))
+                .max(0.0);
+            }
+            week.project_stats
+                .retain(|s| !excluded.contains(&s.project_id));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Settings {
     pub rate_per_minute: f64,
+    /// Внутренние затраты на минуту работы пользователя (оплата труда и накладные);
+    /// без них маржа проектов не считается
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_per_minute: Option<f64>,
     #[serde(default)]
     pub project_settings: std::collections::HashMap<i32, ProjectSettings>,
     #[serde(default)]
     pub user_preferences: Option<UserPreferences>,
+    /// Переключатели возможностей для запроса; неизвестные ключи игнорируются.
+    ///
+    /// Известные: `schedule_recommendations`, `time_allocation_recommendations`,
+    /// `project_priority_recommendations`, `meeting_recommendations`,
+    /// `schedule_conflict_recommendations`, `idle_project_recommendations`,
+    /// `project_value_recommendations`, `billable_recommendations`,
+    /// `profitability_recommendations`, `action_plan`,
+    /// `billing_anomalies` (по умолчанию `true`), `explanations` (по умолчанию `false`),
+    /// `insufficient_data` (по умолчанию `false`), `anomaly_backend` (`"isolation_forest"`).
+    #[serde(default)]
+    pub features: std::collections::HashMap<String, JsonValue>,
+    /// Подстроки названия активности или тега, отмечающие встречи
+    /// (без учета регистра); пустой список - встроенные шаблоны
+    #[serde(default)]
+    pub meeting_patterns: Vec<String>,
+    /// Настройки анализа продуктивности
+    #[serde(default)]
+    pub analyzer: AnalyzerConfig,
+    /// Единицы числовых `timesheets[].duration` во входных данных; внутри
+    /// длительности всегда в минутах (см. `duration::normalize_input`)
+    #[serde(default)]
+    pub duration_unit: DurationUnit,
+    /// Теги, по которым прогноз, продуктивность и рекомендации считаются еще и
+    /// отдельно (`MLOutputData::segments`, см. `segments`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segment_by_tags: Vec<String>,
+    /// Теги оплачиваемых записей (без учета регистра) для записей без `billable`;
+    /// пустой список - такие записи не классифицируются
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub billable_tags: Vec<String>,
+    /// Страна встроенного календаря праздников (ISO 3166-1 alpha-2, `holidays`)
+    #[serde(
+        default,
+        deserialize_with = "crate::holidays::deserialize_country",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub holiday_country: Option<String>,
+    /// Пороги загрузки оплачиваемой работой (`ProductivityOutput::utilization`)
+    #[serde(default)]
+    pub utilization: UtilizationThresholds,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Загрузка ниже `low` - недогрузка, выше `high` - перегрузка; незаданные поля -
+/// значения по умолчанию
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct UtilizationThresholds {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Default for UtilizationThresholds {
+    fn default() -> Self {
+        Self {
+            low: 0.6,
+            high: 0.9,
+        }
+    }
+}
+
+/// Единицы длительности записей во входных данных
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DurationUnit {
+    #[default]
+    Minutes,
+    /// Как в API Kimai
+    Seconds,
+    /// По совпадению длительности с интервалом `begin`-`end` записи
+    Auto,
+}
+
+impl Settings {
+    /// Булев флаг из `features`; `default`, если он не задан или не булев
+    pub fn feature_enabled(&self, name: &str, default: bool) -> bool {
+        self.features
+            .get(name)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(default)
+    }
+
+    /// Ставка за минуту по проекту: своя ставка проекта или общая
+    pub fn project_rate(&self, project_id: i32) -> f64 {
+        self.project_settings
+            .get(&project_id)
+            .and_then(|s| s.rate_per_minute)
+            .unwrap_or(self.rate_per_minute)
+    }
+
+    /// Строковое значение из `features` (например, выбор алгоритма)
+    pub fn feature_str(&self, name: &str) -> Option<&str> {
+        self.features.get(name).and_then(|v| v.as_str())
+    }
+}
+
+/// Настройки `ProductivityAnalyzer`; незаданные поля - значения по умолчанию
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+pub struct AnalyzerConfig {
+    pub efficiency_metric: EfficiencyMetric,
+    /// Записи с перерывом меньше этого (минуты) объединяются в одну сессию
+    pub session_gap_minutes: i64,
+    /// Более короткие сессии (минуты) не учитываются
+    pub min_session_minutes: i32,
+    /// Сколько самых эффективных часов определяют оптимальное время работы
+    pub top_hours: usize,
+    pub weekend_policy: WeekendPolicy,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            efficiency_metric: EfficiencyMetric::default(),
+            session_gap_minutes: 30,
+            min_session_minutes: 0,
+            top_hours: 8,
+            weekend_policy: WeekendPolicy::default(),
+        }
+    }
+}
+
+/// Входят ли выходные в оптимальные дни работы
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WeekendPolicy {
+    /// По `UserPreferences::work_on_weekends`
+    #[default]
+    Preferences,
+    Include,
+    Exclude,
+}
+
+/// Что означает `EfficiencyPoint::efficiency`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EfficiencyMetric {
+    /// Доля часа, занятая записями
+    #[default]
+    Occupancy,
+    /// Занятость, взвешенная непрерывностью сессий, ставкой проекта и
+    /// отсутствием дробления на короткие записи
+    OutputDensity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UserPreferences {
     #[serde(default = "default_sleep_start")]
     pub sleep_start_hour: i32, // 0-23
@@ -87,6 +323,12 @@ pub struct UserPreferences {
     pub work_on_weekends: bool,
     #[serde(default)]
     pub project_goals: std::collections::HashMap<i32, f64>, // project_id -> weekly_goal_hours
+    #[serde(default = "default_min_focus_days")]
+    pub min_focus_days_per_week: usize,
+    /// Договорные часы в неделю (неполная занятость - меньше 40); точка отсчета
+    /// переработок, риска выгорания, емкости плана и недоучета (`models::contract`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contracted_weekly_hours: Option<f64>,
 }
 
 fn default_sleep_start() -> i32 {
@@ -101,15 +343,125 @@ fn default_no_work_hours() -> i32 {
 fn default_work_on_weekends() -> bool {
     false
 }
+fn default_min_focus_days() -> usize {
+    2
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Context {
     pub target_week: Option<i32>,
     pub target_year: Option<i32>,
     pub target_project_id: Option<i32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Сведения о модели, построившей результат
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ModelInfo {
+    pub model_version: String,
+    pub trained_at: Option<String>, // RFC 3339
+    pub training_samples: usize,
+    pub algorithms: Vec<String>,
+    #[serde(default)]
+    pub correction_factor: Option<f64>,
+    /// Размер модели в памяти (для моделей из деревьев)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<ModelSize>,
+}
+
+/// Размер ансамбля деревьев: число деревьев и узлов, глубина листьев и оценка
+/// занимаемой памяти
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ModelSize {
+    pub trees: usize,
+    pub nodes: usize,
+    pub max_depth: usize,
+    pub mean_depth: f64,
+    pub memory_bytes: usize,
+}
+
+impl ModelInfo {
+    /// Результат без обученной модели (среднее по истории)
+    pub fn baseline(samples: usize) -> Self {
+        Self {
+            model_version: env!("CARGO_PKG_VERSION").to_string(),
+            trained_at: None,
+            training_samples: samples,
+            algorithms: vec!["mean_baseline".to_string()],
+            correction_factor: None,
+            size: None,
+        }
+    }
+}
+
+/// Достаточность истории для прогноза. Чем меньше недель, тем проще прогноз:
+/// модели обучаются только на `Full`, остальные уровни - среднее по истории
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[serde(rename_all = "snake_case")]
+pub enum DataSufficiency {
+    /// Недель нет: прогноз нулевой с нулевой уверенностью
+    None,
+    /// Меньше `LIMITED_WEEKS`: среднее без тренда и разбивки по проектам
+    Minimal,
+    /// Меньше `FULL_WEEKS`: среднее с трендом и долями проектов
+    Limited,
+    /// Ансамбль обученных моделей
+    #[default]
+    Full,
+}
+
+impl DataSufficiency {
+    /// Недель для уровня `Limited`
+    pub const LIMITED_WEEKS: usize = 4;
+    /// Недель для обучения моделей (`Full`)
+    pub const FULL_WEEKS: usize = 8;
+
+    pub fn from_weeks(weeks: usize) -> Self {
+        match weeks {
+            0 => Self::None,
+            w if w < Self::LIMITED_WEEKS => Self::Minimal,
+            w if w < Self::FULL_WEEKS => Self::Limited,
+            _ => Self::Full,
+        }
+    }
+}
+
+/// Раздел ответа, для которого истории мало (`settings.features.insufficient_data`):
+/// вместо результата с низкой уверенностью - сколько данных нужно и сколько есть
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct InsufficientData {
+    /// `forecasting` или `anomalies`
+    pub section: String,
+    /// `weeks` или `entries`
+    pub unit: String,
+    pub needed: usize,
+    pub have: usize,
+}
+
+impl InsufficientData {
+    /// Прогноз из истории короче `DataSufficiency::FULL_WEEKS`
+    pub fn forecasting(weeks: usize) -> Option<Self> {
+        Self::check("forecasting", "weeks", DataSufficiency::FULL_WEEKS, weeks)
+    }
+
+    /// `None`, если `have` не меньше `needed`
+    pub fn check(section: &str, unit: &str, needed: usize, have: usize) -> Option<Self> {
+        (have < needed).then(|| Self {
+            section: section.to_string(),
+            unit: unit.to_string(),
+            needed,
+            have,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct ForecastingOutput {
     pub weekly_hours: f64,
     #[serde(default)]
@@ -117,58 +469,1256 @@ pub struct ForecastingOutput {
     pub monthly_hours: f64,
     pub confidence: f64,
     pub trend: String, // "increasing" | "decreasing" | "stable"
+    /// Сила тренда последних недель (|тау Кендалла|, 0-1); `trend` отличен от
+    /// "stable", только если тренд статистически значим
+    #[serde(default)]
+    pub trend_strength: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model_info: Option<ModelInfo>,
+    /// Прогноз оплачиваемых часов: `weekly_hours` × доля оплачиваемой работы
+    /// последних недель (`BillableShare::recent_share`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billable_hours: Option<f64>,
+    /// Уровень истории, по которому построен прогноз
+    #[serde(default)]
+    pub data_sufficiency: DataSufficiency,
+    /// Составляющие прогноза (`settings.features.explanations`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<ForecastExplanation>,
+    /// Оценка целей по проектам (`user_preferences.project_goals`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub goal_assessments: Vec<GoalAssessment>,
+    /// Прогноз вышел за физические границы и прижат к ним
+    /// (`models::forecasting::ForecastBounds`)
+    #[serde(default)]
+    pub clamped: bool,
+    /// Исправления входных недель: порядок, повторы, пропуски
+    /// (`preprocessing::WeekOrderReport`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Цель по проекту на фоне истории пользователя
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct GoalAssessment {
+    pub project_id: i32,
+    pub goal_hours: f64,
+    /// Прогноз часов проекта без учета цели
+    pub historical_hours: f64,
+    /// Вероятность недели с часами не ниже цели по недавней истории проекта
+    pub attainment_probability: f64,
+    /// Прогноз, смешанный с целью с весом `attainment_probability`
+    pub blended_hours: f64,
+    /// `false`, если история почти исключает достижение цели
+    pub realistic: bool,
+}
+
+/// Из чего сложился прогноз
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ForecastExplanation {
+    /// Прогнозы дерева решений, гребневой регрессии, недель-аналогов (k-NN) и
+    /// кусочно-линейного тренда с сезонностью (`prophet`) до смешивания;
+    /// `None`, если модель не участвовала (средний базовый прогноз)
+    pub tree_hours: Option<f64>,
+    pub linear_hours: Option<f64>,
+    #[serde(default)]
+    pub knn_hours: Option<f64>,
+    #[serde(default)]
+    pub prophet_hours: Option<f64>,
+    /// Веса моделей в итоговом прогнозе
+    pub tree_weight: f64,
+    pub linear_weight: f64,
+    #[serde(default)]
+    pub knn_weight: f64,
+    #[serde(default)]
+    pub prophet_weight: f64,
+    /// Множитель из обучения на ошибках (`/api/learn`)
+    pub correction_factor: f64,
+    /// Часы последней недели и их изменение к предыдущей
+    pub last_week_hours: f64,
+    pub trend_delta: f64,
+}
+
+impl ForecastExplanation {
+    /// Объяснение без моделей: только история недель
+    pub fn from_history(weeks: &[WeekData]) -> Self {
+        let last_week_hours = weeks.last().map(|w| w.total_hours).unwrap_or(0.0);
+        let trend_delta = match weeks {
+            [.., previous, last] => last.total_hours - previous.total_hours,
+            _ => 0.0,
+        };
+        Self {
+            correction_factor: 1.0,
+            last_week_hours,
+            trend_delta,
+            ..Self::default()
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct AnomalyOutput {
     pub entry_id: i32,
-    pub r#type: String,   // "duration" | "time" | "pattern" | "project"
+    pub r#type: String, // "duration" | "time" | "pattern" | "project" | "billing" | "under_tracking"
     pub severity: String, // "low" | "medium" | "high"
     pub reason: String,
     pub score: f64,
+    /// ISO-неделя (`2024-W05`) для аномалий недели целиком; `entry_id` у них 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<String>,
+    /// Обычные значения, с которыми сравнивается запись
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<AnomalyContext>,
+}
+
+/// Запись на фоне обычных для проекта, активности и пользователя значений
+/// (по записям запроса, см. `models::anomaly_context`); обычное значение группы
+/// меньше чем из трех записей - `None`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct AnomalyContext {
+    pub duration_minutes: f64,
+    /// Медианная длительность записи проекта, минуты
+    pub project_median_duration: Option<f64>,
+    /// `duration_minutes / project_median_duration`
+    pub project_duration_ratio: Option<f64>,
+    /// Записей проекта в запросе
+    pub project_entries: usize,
+    pub activity_median_duration: Option<f64>,
+    pub activity_duration_ratio: Option<f64>,
+    /// Медианная длительность всех записей пользователя
+    pub user_median_duration: Option<f64>,
+    pub user_duration_ratio: Option<f64>,
+    pub start_hour: i32,
+    /// Медианный час начала рабочего дня (первой записи дня)
+    pub typical_start_hour: Option<f64>,
+    /// Час начала записи минус обычный, по кругу суток (-12..12)
+    pub start_hour_difference: Option<f64>,
+}
+
+/// Отзыв пользователя о найденной (или пропущенной) аномалии
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AnomalyFeedback {
+    pub entry_id: i32,
+    pub is_anomaly: bool,
+    #[serde(default)]
+    pub anomaly_type: Option<String>,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Была ли запись среди найденных детектором аномалий (`false` - пропущенная аномалия)
+    #[serde(default = "default_detected")]
+    pub detected: bool,
+    /// `score` найденной аномалии; по таким отзывам обучается модель важности
+    #[serde(default)]
+    pub score: Option<f64>,
+    /// Арендатор, заполняется сервером по `X-Tenant-Id`
+    #[serde(default)]
+    pub tenant: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_detected() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct RecommendationOutput {
-    pub r#type: String, // "time_allocation" | "project_priority" | "schedule_optimization"
+    /// Стабильный идентификатор рекомендации (тип, проект, неделя, заголовок)
+    #[serde(default)]
+    pub id: String,
+    /// Проект, к которому относится рекомендация
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<i32>,
+    /// Неделя (`2024-W05`), по которой дана рекомендация, если она про одну неделю
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub period: Option<String>,
+    /// `new` - впервые в этом запросе, `open` - выдавалась и раньше
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Когда рекомендация впервые и последний раз выдавалась арендатору (RFC 3339)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<String>,
+    pub r#type: String, // "time_allocation" | "project_priority" | "schedule_optimization" | "workload_balance"
     pub priority: String, // "low" | "medium" | "high"
     pub title: String,
     pub description: String,
     pub action_items: Vec<String>,
-    pub expected_impact: String,
+    pub expected_impact: ExpectedImpact,
+    /// Рассчитанные показатели рекомендации (доступны шаблонам формулировок)
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub metrics: std::collections::BTreeMap<String, f64>,
     pub confidence: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Оценка эффекта рекомендации, рассчитанная по данным пользователя
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ExpectedImpact {
+    /// `revenue` | `project_hours` | `focus_hours` | `focus_days` | `peak_hours` |
+    /// `conflict_hours` | `overload_hours` | `margin`
+    pub metric: String,
+    /// Ожидаемое изменение метрики; `None`, если по данным его не оценить
+    pub estimated_delta: Option<f64>,
+    /// `amount_per_week` (в валюте ставок) | `hours_per_week` | `days_per_week` | `amount`
+    pub unit: String,
+    /// Допущения, на которых построена оценка
+    pub assumptions: Vec<String>,
+}
+
+/// Что пользователь сделал с рекомендацией
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RecommendationAction {
+    Accepted,
+    Dismissed,
+    Snoozed,
+}
+
+impl RecommendationAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::Dismissed => "dismissed",
+            Self::Snoozed => "snoozed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "accepted" => Some(Self::Accepted),
+            "dismissed" => Some(Self::Dismissed),
+            "snoozed" => Some(Self::Snoozed),
+            _ => None,
+        }
+    }
+}
+
+/// Отзыв пользователя о рекомендации (`POST /api/recommendations/feedback`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RecommendationFeedback {
+    pub recommendation_id: String,
+    /// Тип рекомендации (`RecommendationOutput::type`)
+    pub recommendation_type: String,
+    pub action: RecommendationAction,
+    /// На сколько недель отложить рекомендацию (`snoozed`)
+    #[serde(default)]
+    pub snooze_weeks: Option<u32>,
+    /// Арендатор, заполняется сервером по `X-Tenant-Id`
+    #[serde(default)]
+    pub tenant: String,
+    /// Время отзыва (RFC 3339), заполняется сервером
+    #[serde(default)]
+    pub recorded_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct OptimalWorkHours {
     pub start: i32,
     pub end: i32,
     pub days: Vec<i32>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct BreakRecommendations {
     pub optimal_break_duration: i32,
     pub break_frequency: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct ProductivityOutput {
     pub optimal_work_hours: OptimalWorkHours,
     pub efficiency_by_time: Vec<EfficiencyPoint>,
     pub break_recommendations: BreakRecommendations,
+    /// Перерывы по активностям (`activity_name`); при малом числе сессий
+    /// активности - значение `break_recommendations`
+    #[serde(default)]
+    pub break_recommendations_by_activity: std::collections::HashMap<String, BreakRecommendations>,
+    /// Последняя неделя на фоне собственной истории (до 52 недель)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekly_comparison: Option<WeeklyComparison>,
+    /// Сессии и перерывы, на которых основаны `break_recommendations`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_stats: Option<SessionStatistics>,
+    /// Доля встреч по неделям (`settings.meeting_patterns`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meeting_load: Option<MeetingLoad>,
+    /// Доля оплачиваемой работы по неделям; `None`, если ни одна запись не
+    /// классифицирована (`billable`, `settings.billable_tags`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub billable: Option<BillableShare>,
+    /// Типы дней: сосредоточенная работа, встречи, административные задачи, раздробленный день
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day_types: Option<DayTypeAnalysis>,
+    /// Часы высокой, средней и низкой энергии по дням недели
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy_profile: Option<EnergyProfile>,
+    /// Переработки и недоучет относительно договорных часов
+    /// (`user_preferences.contracted_weekly_hours`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract: Option<ContractUtilization>,
+    /// Загрузка оплачиваемой работой по неделям и ее прогноз; `None` без
+    /// классифицированных записей
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utilization: Option<UtilizationAnalysis>,
+    /// Маржа проектов (`settings.cost_per_minute`); `None` без ставки затрат
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profitability: Option<Profitability>,
+}
+
+/// Доход минус внутренние затраты за последние 12 недель
+/// (`unit_economics::profitability`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct Profitability {
+    pub weeks: usize,
+    pub cost_per_hour: f64,
+    pub revenue: f64,
+    pub cost: f64,
+    pub margin: f64,
+    /// `margin / revenue`; `None` без дохода
+    pub margin_share: Option<f64>,
+    /// Сначала наименее прибыльные
+    pub projects: Vec<ProjectMargin>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ProjectMargin {
+    pub project_id: i32,
+    pub hours: f64,
+    /// Реализованный доход (доля `total_amount` недель по часам × ставке)
+    pub revenue: f64,
+    pub cost: f64,
+    pub margin: f64,
+    pub margin_share: Option<f64>,
+    /// Маржа за час в последнюю неделю работы над проектом
+    pub margin_per_hour: f64,
+    /// Тренд маржи за час по активным неделям: "increasing" | "decreasing" | "stable"
+    pub margin_trend: String,
+    /// Наклон Тейла-Сена, маржи за час в неделю
+    pub margin_trend_slope: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Загрузка: оплачиваемые часы / доступные часы недели (емкость за вычетом
+/// праздников, `contract::available_hours`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct UtilizationAnalysis {
+    /// Недели с классифицированными записями в хронологическом порядке
+    pub weeks: Vec<WeeklyUtilization>,
+    /// Загрузка за последние 8 недель
+    pub recent_utilization: f64,
+    /// `low` | `on_target` | `high` по `recent_utilization`
+    pub status: String,
+    /// Прогноз на следующие недели
+    pub forecast: Vec<WeeklyUtilization>,
+    pub low_threshold: f64,
+    pub high_threshold: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct WeeklyUtilization {
+    pub year: i32,
+    pub week: i32,
+    pub billable_hours: f64,
+    pub available_hours: f64,
+    pub utilization: f64,
+    /// `low` | `on_target` | `high`
+    pub status: String,
+}
+
+/// Учтенные часы последних недель против договорных
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ContractUtilization {
+    pub contracted_weekly_hours: f64,
+    pub weeks: usize,
+    pub average_hours: f64,
+    /// Учтенные часы / договорные за вычетом праздников
+    pub utilization: f64,
+    /// Часы сверх договорных, сумма по неделям
+    pub overtime_hours: f64,
+    pub overtime_weeks: usize,
+    /// Недели, где учтено меньше 80% договорных часов
+    pub under_tracked_weeks: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct EnergyProfile {
+    /// Только дни недели, в которые была работа
+    pub days: Vec<DayEnergy>,
+}
+
+/// Часы дня недели по уровню энергии (по возрастанию)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DayEnergy {
+    pub day_of_week: i32, // 0 = воскресенье
+    pub high: Vec<i32>,
+    pub medium: Vec<i32>,
+    pub low: Vec<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DayTypeAnalysis {
+    /// Дни в хронологическом порядке
+    pub days: Vec<DayClassification>,
+    pub weekly_mix: Vec<WeeklyDayMix>,
+    /// Число дней сосредоточенной работы в неделю: "increasing" | "decreasing" | "stable"
+    pub focus_trend: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DayClassification {
+    pub date: String,     // YYYY-MM-DD
+    pub day_type: String, // "focus" | "meeting_heavy" | "admin" | "split"
+}
+
+/// Число дней каждого типа за неделю
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct WeeklyDayMix {
+    pub year: i32,
+    pub week: i32,
+    pub focus: usize,
+    pub meeting_heavy: usize,
+    pub admin: usize,
+    pub split: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct MeetingLoad {
+    /// Недели в хронологическом порядке
+    pub weeks: Vec<WeeklyMeetingShare>,
+    pub average_share: f64,
+    /// Корреляция доли встреч и часов глубокой работы по неделям
+    /// (`None` меньше чем для трех недель)
+    pub deep_work_correlation: Option<f64>,
+}
+
+/// Работа недели вне заявленного в `UserPreferences` времени
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct WeeklyScheduleConflicts {
+    pub year: i32,
+    pub week: i32,
+    pub total_hours: f64,
+    /// Часы в окне сна `sleep_start_hour..sleep_end_hour`
+    pub sleep_hours: f64,
+    /// Часы за `no_work_before_sleep_hours` до сна
+    pub buffer_hours: f64,
+    /// Часы в субботу и воскресенье, если работа в выходные не планируется
+    pub weekend_hours: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct WeeklyMeetingShare {
+    pub year: i32,
+    pub week: i32,
+    pub total_hours: f64,
+    pub meeting_hours: f64,
+    /// `meeting_hours / total_hours`
+    pub meeting_share: f64,
+    /// Часы в сессиях без встреч от часа и дольше
+    pub deep_work_hours: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct BillableShare {
+    /// Недели в хронологическом порядке
+    pub weeks: Vec<WeeklyBillableShare>,
+    pub average_share: f64,
+    /// Доля за последние недели (`BILLABLE_RECENT_WEEKS`)
+    pub recent_share: f64,
+    /// Часы записей, не отмеченных ни флагом, ни тегом; в доли не входят
+    pub unclassified_hours: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct WeeklyBillableShare {
+    pub year: i32,
+    pub week: i32,
+    pub billable_hours: f64,
+    pub non_billable_hours: f64,
+    /// `billable_hours / (billable_hours + non_billable_hours)`
+    pub billable_share: f64,
+}
+
+/// Раздробленность работы: сессии (записи с перерывами меньше
+/// `AnalyzerConfig::session_gap_minutes`)
+/// и паузы между записями внутри дня
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct SessionStatistics {
+    pub sessions: usize,
+    pub avg_session_minutes: f64,
+    /// Сессий на час учтенного времени: чем больше, тем раздробленнее работа
+    pub fragmentation_index: f64,
+    /// Паузы между соседними записями одного дня
+    pub interruptions: usize,
+    pub avg_interruption_minutes: f64,
+    /// Проекты, работа над которыми чаще всего прерывалась (до 5)
+    pub most_interrupted_projects: Vec<InterruptedProject>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct InterruptedProject {
+    pub project_id: i32,
+    pub project_name: String,
+    pub interruptions: usize,
+}
+
+/// Сравнение последней недели с предыдущими неделями того же пользователя
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct WeeklyComparison {
+    pub year: i32,
+    pub week: i32,
+    /// Сколько недель (включая текущую) участвует в сравнении
+    pub weeks_compared: usize,
+    pub hours: MetricStanding,
+    /// Доля времени в сессиях от часа и дольше (0-1)
+    pub productivity_score: MetricStanding,
+    /// Сессий на час учтенного времени
+    pub fragmentation: MetricStanding,
+}
+
+/// Значение метрики за неделю и его место в истории
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct MetricStanding {
+    pub value: f64,
+    pub mean: f64,
+    /// Доля недель со значением не больше текущего, 0-100
+    pub percentile: f64,
+    pub z_score: f64,
+    /// Место среди недель по убыванию значения (1 - наибольшее)
+    pub rank: usize,
+}
+
+impl MetricStanding {
+    /// `history` включает текущее значение `value`
+    pub fn new(value: f64, history: &[f64]) -> Self {
+        let n = history.len().max(1) as f64;
+        let mean = history.iter().sum::<f64>() / n;
+        let variance = history.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        let std_dev = variance.sqrt();
+        Self {
+            value,
+            mean,
+            percentile: history.iter().filter(|&&v| v <= value).count() as f64 / n * 100.0,
+            z_score: if std_dev > 0.0 {
+                (value - mean) / std_dev
+            } else {
+                0.0
+            },
+            rank: 1 + history.iter().filter(|&&v| v > value).count(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 pub struct EfficiencyPoint {
     pub hour: i32,
     pub efficiency: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct MLOutputData {
     pub forecasting: Option<ForecastingOutput>,
     pub anomalies: Option<Vec<AnomalyOutput>>,
     pub recommendations: Option<Vec<RecommendationOutput>>,
     pub productivity: Option<ProductivityOutput>,
+    /// Модель, построившая `anomalies`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anomaly_model_info: Option<ModelInfo>,
+    /// Диагностика запроса с `options.dry_run: true`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dry_run: Option<DryRunReport>,
+    /// Итоги и страница `anomalies` после фильтров запроса
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anomaly_page: Option<AnomalyPage>,
+    /// Сводка по всем аномалиям после фильтров (не только по странице)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anomaly_summary: Option<AnomalySummary>,
+    /// `id` рекомендаций, выданных прошлым запросом и больше не актуальных
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_recommendations: Option<Vec<String>>,
+    /// План действий на следующую неделю по лучшим рекомендациям
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_plan: Option<ActionPlan>,
+    /// Результаты по сегментам `settings.segment_by_tags`; общие результаты -
+    /// в полях выше
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segments: Option<Vec<SegmentOutput>>,
+    /// Разделы без результата из-за короткой истории
+    /// (`settings.features.insufficient_data`); сами разделы в них - `None`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insufficient_data: Option<Vec<InsufficientData>>,
+}
+
+/// Результаты анализа записей с тегом
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SegmentOutput {
+    pub tag: String,
+    /// Записей с тегом
+    pub entries: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forecasting: Option<ForecastingOutput>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recommendations: Option<Vec<RecommendationOutput>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub productivity: Option<ProductivityOutput>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insufficient_data: Option<Vec<InsufficientData>>,
+}
+
+/// Упорядоченные шаги на неделю, укладывающиеся в прогноз рабочего времени
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ActionPlan {
+    /// Неделя плана (`YYYY-Www`), следующая за последней неделей данных
+    pub period: Option<String>,
+    /// Прогноз рабочих часов недели
+    pub capacity_hours: f64,
+    /// `contract` - договорные часы, `forecast` - обученная модель прогноза,
+    /// `history` - среднее последних недель
+    pub capacity_source: String,
+    /// Часы, которые план отводит на шаги (доля `capacity_hours`)
+    pub budget_hours: f64,
+    /// Сумма `time_cost_hours` шагов
+    pub planned_hours: f64,
+    pub steps: Vec<ActionStep>,
+    /// Праздники недели плана в будни (`settings.holiday_country`), `YYYY-MM-DD`;
+    /// `capacity_hours` уменьшен пропорционально
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub holidays: Vec<String>,
+}
+
+/// Шаг плана - одна рекомендация
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ActionStep {
+    /// Порядковый номер, с 1
+    pub order: usize,
+    pub recommendation_id: String,
+    pub recommendation_type: String,
+    pub priority: String,
+    pub title: String,
+    /// Пункты для отметки в чек-листе
+    pub checklist: Vec<String>,
+    /// Оценка времени на выполнение шага
+    pub time_cost_hours: f64,
+    pub expected_benefit: ExpectedImpact,
+}
+
+/// Итоги по отфильтрованным аномалиям и номер выданной страницы
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct AnomalyPage {
+    /// Число аномалий после фильтров (на всех страницах)
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+    pub pages: usize,
+    pub by_severity: std::collections::BTreeMap<String, usize>,
+    pub by_type: std::collections::BTreeMap<String, usize>,
+}
+
+/// Сводка по аномалиям после фильтров запроса для обзорных виджетов
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct AnomalySummary {
+    pub total: usize,
+    pub by_type: std::collections::BTreeMap<String, usize>,
+    pub by_severity: std::collections::BTreeMap<String, usize>,
+    /// Аномалии по неделям записей, от ранних к поздним
+    pub weekly: Vec<AnomalyWeekRate>,
+    /// Тренд доли отмеченных записей по последним 8 неделям:
+    /// "increasing" | "decreasing" | "stable"
+    pub trend: String,
+    /// Проекты с наибольшим числом отмеченных записей
+    pub top_projects: Vec<ProjectAnomalies>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AnomalyWeekRate {
+    /// Неделя `YYYY-Www`
+    pub period: String,
+    pub entries: usize,
+    /// Аномалии недели, включая аномалии недели целиком
+    pub anomalies: usize,
+    /// Доля отмеченных записей недели
+    pub rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectAnomalies {
+    pub project_id: i32,
+    pub project_name: String,
+    /// Отмеченных записей проекта
+    pub anomalies: usize,
+    pub entries: usize,
+    pub rate: f64,
+}
+
+/// Что изменил бы обычный запрос вместо пробного (`options.dry_run`).
+///
+/// Пробный запрос выполняет весь конвейер на копиях моделей: общие модели,
+/// хранилище, кэш и история ошибок не меняются.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct DryRunReport {
+    /// Пропущенные изменения состояния, например `train forecasting`
+    pub skipped: Vec<String>,
+    /// Сколько недель, записей или проектов дошло до модели после фильтров из `options`
+    pub samples: usize,
+    /// Ответ на такой же обычный запрос уже есть в кэше
+    pub cache_hit: bool,
+    /// Ошибка обучения копии: обычный запрос использовал бы прежнюю модель
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub training_error: Option<String>,
+}
+
+impl DryRunReport {
+    pub fn skip(&mut self, mutations: &[&str]) {
+        self.skipped
+            .extend(mutations.iter().map(|mutation| mutation.to_string()));
+    }
+}
+
+/// Автокорреляция и сезонность недельных часов (`/api/diagnostics/seasonality`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SeasonalityDiagnostics {
+    pub weeks: usize,
+    /// Автокорреляция для лагов 1..=`acf.len()` недель
+    pub acf: Vec<f64>,
+    /// Частная автокорреляция для тех же лагов
+    pub pacf: Vec<f64>,
+    /// Граница значимости 95% (1.96 / sqrt(weeks))
+    pub confidence_bound: f64,
+    /// Найденные периоды по убыванию силы
+    pub periods: Vec<SeasonalPeriod>,
+    /// Сила самого выраженного периода (0, если периодов нет)
+    pub seasonal_strength: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SeasonalPeriod {
+    /// Период в неделях
+    pub period: usize,
+    /// "monthly" | "quarterly" | "half_yearly" | "yearly", если период близок к календарному
+    pub label: Option<String>,
+    pub autocorrelation: f64,
+    /// Доля дисперсии, объясненная средними по фазе периода (0-1)
+    pub strength: f64,
+}
+
+/// Мотивы и диссонансы часов по дням (`/api/diagnostics/patterns`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PatternDiagnostics {
+    /// Длина окна в днях
+    pub window: usize,
+    /// Первый день ряда; `profile[i]` относится к окну, начинающемуся через `i` дней
+    pub start_date: String,
+    pub days: usize,
+    /// Расстояние каждого окна до ближайшего похожего (z-нормализованное)
+    pub profile: Vec<f64>,
+    pub motifs: Vec<PatternMotif>,
+    pub discords: Vec<PatternDiscord>,
+}
+
+/// Пара повторяющихся отрезков
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PatternMotif {
+    pub first_start: String,
+    pub second_start: String,
+    pub distance: f64,
+}
+
+/// Отрезок, не похожий ни на какой другой
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PatternDiscord {
+    pub start: String,
+    pub distance: f64,
+    /// Расстояние, нормированное на наибольшее возможное (0-1)
+    pub strength: f64,
+}
+
+/// Продолжительность активности проектов (`/api/diagnostics/project-lifetime`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectLifetime {
+    /// Сколько недель без записей означают, что проект уснул
+    pub dormancy_weeks: usize,
+    pub horizon_weeks: usize,
+    /// Число периодов активности и завершившихся из них
+    pub spans: usize,
+    pub events: usize,
+    /// `None`, если больше половины периодов еще не завершились
+    pub median_lifetime_weeks: Option<usize>,
+    /// Кривая Каплана-Мейера: доля периодов активности длиннее `weeks`
+    pub curve: Vec<SurvivalPoint>,
+    pub active_projects: Vec<ProjectDormancyRisk>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SurvivalPoint {
+    pub weeks: usize,
+    pub survival: f64,
+    pub at_risk: usize,
+    pub events: usize,
+}
+
+/// Вероятность, что активный проект уснет в ближайшие `horizon_weeks` недель
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectDormancyRisk {
+    pub project_id: i32,
+    /// Длина текущего периода активности
+    pub active_weeks: usize,
+    pub dormancy_probability: f64,
+}
+
+/// Предлагаемое недельное распределение часов (`/api/compare-schedules`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProposedSchedule {
+    pub name: String,
+    /// project_id -> часов в неделю
+    pub allocations: std::collections::HashMap<i32, f64>,
+}
+
+/// Прогнозируемый результат недели по распределению
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduleOutcome {
+    pub name: String,
+    pub total_hours: f64,
+    /// Выручка по ставкам проектов
+    pub revenue: f64,
+    /// Вероятность выполнить все цели проектов за неделю; `None` без целей
+    pub goal_attainment_probability: Option<f64>,
+    pub goals: Vec<ScheduleGoal>,
+    /// Вероятность, что неделя превысит устойчивую нагрузку
+    pub burnout_risk: f64,
+    /// Изменение риска выгорания относительно средней недели истории
+    pub burnout_risk_delta: f64,
+}
+
+/// Цель проекта при распределении
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduleGoal {
+    pub project_id: i32,
+    pub goal_hours: f64,
+    pub planned_hours: f64,
+    pub attainment_probability: f64,
+}
+
+/// Сравнение двух распределений: результаты и разница второго с первым
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduleComparison {
+    /// Средние часы недели за последние недели истории
+    pub baseline_hours: f64,
+    pub baseline_burnout_risk: f64,
+    /// Недельная нагрузка, выше которой растет риск выгорания
+    pub sustainable_hours: f64,
+    pub schedules: Vec<ScheduleOutcome>,
+    pub difference: ScheduleDifference,
+}
+
+/// Второе распределение минус первое
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduleDifference {
+    pub total_hours: f64,
+    pub revenue: f64,
+    /// `None`, если у пользователя нет целей проектов
+    pub goal_attainment_probability: Option<f64>,
+    pub burnout_risk: f64,
+}
+
+/// Плановое отсутствие (`options.absence`), даты `YYYY-MM-DD` включительно
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PlannedAbsence {
+    pub from: String,
+    pub to: String,
+}
+
+/// Влияние отсутствия на недели и проекты (`/api/simulate-absence`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AbsenceImpact {
+    pub from: String,
+    pub to: String,
+    /// Рабочих дней отсутствия: будни без праздников `settings.holiday_country`
+    pub absent_days: usize,
+    pub weeks: Vec<AbsenceWeek>,
+    pub projects: Vec<ProjectAbsenceImpact>,
+    /// Недель перед отсутствием, за которые часть работы можно сделать заранее
+    pub preparation_weeks: usize,
+    /// Часов, которые можно отработать заранее без недель дольше устойчивой нагрузки
+    pub frontload_capacity_hours: f64,
+    /// Что отработать заранее и что передать на время отсутствия
+    pub suggestions: Vec<String>,
+}
+
+/// Неделя, задетая отсутствием
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AbsenceWeek {
+    /// ISO-неделя `2024-W27`
+    pub period: String,
+    pub absent_days: usize,
+    /// Доля обычной недели: рабочие дни, оставшиеся после праздников и отсутствия, из пяти
+    pub available_share: f64,
+    /// Средние часы недели за последние недели истории
+    pub baseline_hours: f64,
+    pub expected_hours: f64,
+    /// Вероятность выполнить все цели проектов за неделю; `None` без целей
+    pub goal_attainment_probability: Option<f64>,
+}
+
+/// Проект при отсутствии
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ProjectAbsenceImpact {
+    pub project_id: i32,
+    pub project_name: String,
+    /// Средние часы проекта в неделю за последние недели истории
+    pub baseline_weekly_hours: f64,
+    /// Часы проекта, выпадающие за все отсутствие
+    pub lost_hours: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub goal_hours: Option<f64>,
+    /// Вероятность выполнить цель в обычную неделю и в худшую неделю отсутствия
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub baseline_attainment: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub absence_attainment: Option<f64>,
+    /// Часов отработать заранее и передать коллегам
+    pub frontload_hours: f64,
+    pub handover_hours: f64,
+}
+
+/// Выданный прогноз недели и фактические часы (`/api/forecasts/history`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ForecastRecord {
+    /// Неделя прогноза (`YYYY-Www`)
+    pub period: String,
+    /// Когда выдан последний прогноз недели (RFC 3339)
+    pub issued_at: String,
+    pub predicted_hours: f64,
+    pub confidence: f64,
+    pub model_version: Option<String>,
+    #[serde(default)]
+    pub actual_hours: Option<f64>,
+    /// `sync` - из недель следующих запросов, `learn` - из `/api/learn`
+    #[serde(default)]
+    pub actual_source: Option<String>,
+    /// Прогноз минус факт
+    #[serde(default)]
+    pub error: Option<f64>,
+    /// |ошибка| / факт; `None` для недели без часов
+    #[serde(default)]
+    pub absolute_percentage_error: Option<f64>,
+    /// Средняя `absolute_percentage_error` этой и предыдущих проверенных
+    /// прогнозов в окне `ROLLING_ACCURACY_WINDOW`
+    #[serde(default)]
+    pub rolling_mape: Option<f64>,
+}
+
+/// Как менялся прогноз одной недели (`/api/forecasts/revisions`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ForecastRevisions {
+    /// Неделя прогноза (`YYYY-Www`)
+    pub period: String,
+    #[serde(default)]
+    pub actual_hours: Option<f64>,
+    #[serde(default)]
+    pub actual_source: Option<String>,
+    /// Снимки от ранних (наибольший горизонт) к поздним
+    pub revisions: Vec<ForecastRevision>,
+    /// Ошибка последнего снимка не больше ошибки первого; `None` без факта
+    /// или с одним снимком
+    #[serde(default)]
+    pub converged: Option<bool>,
+}
+
+/// Снимок прогноза недели
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ForecastRevision {
+    /// Когда выдан (RFC 3339)
+    pub issued_at: String,
+    /// За сколько недель до недели прогноза
+    pub horizon_weeks: u32,
+    pub predicted_hours: f64,
+    pub confidence: f64,
+    pub model_version: Option<String>,
+    /// Изменение к предыдущему снимку
+    #[serde(default)]
+    pub change: Option<f64>,
+    /// Прогноз минус факт
+    #[serde(default)]
+    pub error: Option<f64>,
+    #[serde(default)]
+    pub absolute_percentage_error: Option<f64>,
+}
+
+/// Журнал прогнозов с фактом и итоговая точность
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ForecastHistory {
+    /// Прогнозы по неделям, от ранних к поздним
+    pub records: Vec<ForecastRecord>,
+    pub accuracy: ForecastAccuracy,
+}
+
+/// Точность прогнозов, для которых известен факт
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ForecastAccuracy {
+    pub forecasts: usize,
+    pub evaluated: usize,
+    /// Средняя абсолютная ошибка, часов
+    pub mae: Option<f64>,
+    pub mape: Option<f64>,
+    /// Средняя ошибка со знаком: больше нуля - прогнозы завышены
+    pub bias: Option<f64>,
+    /// Доля прогнозов с ошибкой не больше 10%
+    pub hit_rate: Option<f64>,
+}
+
+/// Подробное объяснение одной записи (`/api/explain-entry`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EntryExplanation {
+    pub entry_id: i32,
+    /// Признаки детектора против обычных значений по записям запроса
+    pub features: Vec<EntryFeature>,
+    pub context: AnomalyContext,
+    /// Детекторы, оценившие запись, сработавшие и нет
+    pub detectors: Vec<DetectorVerdict>,
+    /// Доли признаков в оценке леса, по убыванию; пусто без обученного детектора
+    pub contributions: Vec<FeatureContribution>,
+    /// Ближайшие по признакам записи запроса
+    pub similar: Vec<SimilarEntry>,
+    /// Объяснение одним абзацем
+    pub summary: String,
+}
+
+/// Значение признака записи
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EntryFeature {
+    pub name: String,
+    pub value: f64,
+    /// Медиана признака по записям запроса
+    pub median: f64,
+    /// Доля записей запроса с меньшим значением, 0..1
+    pub percentile: f64,
+}
+
+/// Оценка записи одним детектором (`isolation_forest`, `baseline`, `validation`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DetectorVerdict {
+    pub detector: String,
+    pub fired: bool,
+    pub score: Option<f64>,
+    /// Порог оценки с учетом сдвига по отзывам; только у леса
+    pub threshold: Option<f64>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct FeatureContribution {
+    pub feature: String,
+    /// Доля в изоляции записи лесом, сумма по признакам - 1
+    pub share: f64,
+}
+
+/// Похожая запись для сравнения
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SimilarEntry {
+    pub entry_id: i32,
+    pub begin: String,
+    pub duration: i32,
+    pub project_id: Option<i32>,
+    /// Евклидово расстояние по признакам детектора
+    pub distance: f64,
+    /// Лес отметил эту запись как аномалию
+    pub anomalous: bool,
+}
+
+/// Данные команды (`/api/team/*`): запрос анализа каждого участника и его
+/// метаданные (роль, грейд, ...)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TeamInput {
+    pub members: Vec<TeamMember>,
+    #[serde(default)]
+    pub options: Option<JsonValue>,
+}
+
+/// Участник команды: идентификатор, метаданные и те же поля, что у `MLInputData`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TeamMember {
+    pub id: String,
+    /// Атрибуты для группировки, например `{"role": "developer", "seniority": "senior"}`
+    #[serde(default)]
+    pub metadata: std::collections::BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub data: MLInputData,
+}
+
+/// Сравнение когорт команды (`/api/team/cohorts`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CohortAnalysis {
+    /// Ключ метаданных, по которому участники разбиты на когорты
+    pub cohort_by: String,
+    /// Когорты меньше этого скрыты
+    pub min_cohort_size: usize,
+    pub members: usize,
+    /// Показатели всей команды; `None`, если в ней меньше `min_cohort_size` участников
+    pub team: Option<CohortMetrics>,
+    pub cohorts: Vec<Cohort>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Cohort {
+    /// Значение ключа метаданных; `unassigned` - участники без него
+    pub name: String,
+    pub members: usize,
+    /// Показатели скрыты: когорта меньше `min_cohort_size` или ее значения
+    /// вычислялись бы из итогов команды и остальных когорт
+    pub suppressed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<CohortMetrics>,
+    /// Системные проблемы когорты: `overload`, `fragmentation`, `goals_at_risk`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signals: Vec<String>,
+}
+
+/// Средние по участникам
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct CohortMetrics {
+    /// Часы в неделю за последние 8 недель
+    pub avg_weekly_hours: f64,
+    /// Устойчивая нагрузка участников (`contract::sustainable_hours`)
+    pub sustainable_hours: f64,
+    /// Сессий на час работы; `None` - ни у кого нет записей
+    pub fragmentation_index: Option<f64>,
+    /// Вероятность выполнить недельные цели проектов; `None` - целей нет
+    pub goal_attainment: Option<f64>,
+}
+
+/// Перераспределение часов проектов в команде (`/api/team/balance`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TeamBalance {
+    pub members: Vec<MemberLoad>,
+    pub transfers: Vec<HourTransfer>,
+    /// Часы сверх емкости и недобор до нее по команде до и после переноса
+    pub overload_hours_before: f64,
+    pub overload_hours_after: f64,
+    pub idle_hours_before: f64,
+    pub idle_hours_after: f64,
+    /// По рекомендации `workload_balance` на каждого перегруженного участника
+    pub recommendations: Vec<RecommendationOutput>,
+}
+
+/// Разрыв между обязательствами перед клиентами и емкостью команды
+/// (`/api/team/capacity-gap`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CapacityGap {
+    /// Обязательства перед клиентами, часов в неделю (`options.commitments`)
+    pub committed_hours: f64,
+    /// Договорная емкость команды, часов в неделю
+    pub capacity_hours: f64,
+    /// Прошлые недели: отработанные часы в пределах емкости участников
+    pub history: Vec<CapacityWeek>,
+    /// Прогноз на `options.horizon_weeks` недель
+    pub forecast: Vec<CapacityWeek>,
+    /// Средний разрыв прогноза, часов в неделю; отрицательный - запас
+    pub average_gap_hours: f64,
+    /// Тренд разрыва по истории: "increasing" | "decreasing" | "stable"
+    pub trend: String,
+    /// Наклон тренда, часов разрыва за неделю
+    pub trend_slope: f64,
+    /// Сигнал нехватки емкости, если разрыв устойчив на горизонте прогноза
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shortfall: Option<CapacityShortfall>,
+}
+
+/// Неделя команды против обязательств
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CapacityWeek {
+    /// ISO-неделя `2024-W27`
+    pub period: String,
+    pub hours: f64,
+    /// Обязательства минус часы; отрицательный - запас
+    pub gap_hours: f64,
+}
+
+/// Сигнал `capacity_shortfall`: сколько людей не хватает и как давно
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CapacityShortfall {
+    pub signal: String,
+    /// "low" | "medium" | "high"
+    pub severity: String,
+    /// Средний разрыв на неделях прогноза с нехваткой, часов в неделю
+    pub gap_hours: f64,
+    /// Разрыв в ставках полной занятости (40 ч)
+    pub fte: f64,
+    /// Недель прогноза с нехваткой из `horizon_weeks`
+    pub weeks_short: usize,
+    pub horizon_weeks: usize,
+    /// Первая неделя прогноза с нехваткой
+    pub first_week: String,
+    /// "increasing" | "decreasing" | "stable"
+    pub trend: String,
+    pub message: String,
+}
+
+/// Загрузка команды оплачиваемой работой (`/api/team/utilization`)
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TeamUtilization {
+    /// Суммы оплачиваемых и доступных часов участников по неделям и прогноз;
+    /// `None`, если ни у кого нет классифицированных записей
+    pub team: Option<UtilizationAnalysis>,
+    pub members: Vec<MemberUtilization>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MemberUtilization {
+    pub id: String,
+    /// По порогам `settings.utilization` участника
+    pub utilization: Option<UtilizationAnalysis>,
+}
+
+/// Нагрузка участника до и после переноса
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MemberLoad {
+    pub id: String,
+    /// Прогноз часов в неделю моделью прогноза по истории участника
+    pub forecast_hours: f64,
+    /// Договорные часы в неделю, без них - 40
+    pub capacity_hours: f64,
+    pub balanced_hours: f64,
+    /// `overloaded` | `idle` | `balanced` до переноса
+    pub status: String,
+}
+
+/// Перенос недельных часов проекта от одного участника другому
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HourTransfer {
+    pub project_id: i32,
+    pub project_name: String,
+    pub from: String,
+    pub to: String,
+    /// Часов в неделю
+    pub hours: f64,
+    /// Получатель уже работал над проектом последние 8 недель
+    pub familiar: bool,
 }
@@ -0,0 +1,290 @@
+//! Учет потребления арендаторов и квоты.
+//!
+//! Для каждого арендатора считаются запросы и время обучения моделей за текущие
+//! сутки (UTC) и размер сохраненных снимков. Квоты задаются файлом JSON
+//! (`TENANT_QUOTAS`):
+//!
+//! ```json
+//! {
+//!   "default": { "requests_per_day": 10000, "training_seconds_per_day": 600 },
+//!   "tenants": { "acme": { "model_bytes": 50000000 } }
+//! }
+//! ```
+//!
+//! Поля арендатора дополняют `default`; отсутствующая квота не ограничена.
+//! Учет ведется в памяти процесса
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Квоты арендатора; `None` - без ограничения
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UsageLimits {
+    pub requests_per_day: Option<u64>,
+    pub training_seconds_per_day: Option<f64>,
+    pub model_bytes: Option<u64>,
+}
+
+impl UsageLimits {
+    /// Заданные поля `self`, остальные - из `fallback`
+    fn or(self, fallback: UsageLimits) -> UsageLimits {
+        UsageLimits {
+            requests_per_day: self.requests_per_day.or(fallback.requests_per_day),
+            training_seconds_per_day: self
+                .training_seconds_per_day
+                .or(fallback.training_seconds_per_day),
+            model_bytes: self.model_bytes.or(fallback.model_bytes),
+        }
+    }
+}
+
+/// Квоты по умолчанию и по арендаторам
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaConfig {
+    #[serde(default)]
+    pub default: UsageLimits,
+    #[serde(default)]
+    pub tenants: HashMap<String, UsageLimits>,
+}
+
+impl QuotaConfig {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tenant quotas {}: {}", path, e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid tenant quotas {}: {}", path, e))
+    }
+
+    pub fn limits(&self, tenant: &str) -> UsageLimits {
+        match self.tenants.get(tenant) {
+            Some(limits) => limits.or(self.default),
+            None => self.default,
+        }
+    }
+}
+
+/// Потребление арендатора
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TenantUsage {
+    /// Запросы за текущие сутки
+    pub requests: u64,
+    /// Время обучения моделей за текущие сутки, секунды
+    pub training_seconds: f64,
+    /// Размер последних сохраненных снимков арендатора
+    pub model_bytes: u64,
+    /// Размер по именам снимков
+    pub snapshots: BTreeMap<String, u64>,
+}
+
+/// Исчерпанная квота
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaExceeded {
+    pub quota: &'static str,
+    pub limit: f64,
+    pub used: f64,
+    /// Квота суточная и восстановится в полночь UTC
+    pub daily: bool,
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Quota {} exceeded: {} of {}",
+            self.quota, self.used, self.limit
+        )
+    }
+}
+
+/// Потребление и квоты арендатора в отчете
+#[derive(Debug, Clone, Serialize)]
+pub struct TenantUsageReport {
+    #[serde(flatten)]
+    pub usage: TenantUsage,
+    pub limits: UsageLimits,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageReport {
+    /// Текущие сутки (UTC), за которые считаются запросы и обучение
+    pub day: NaiveDate,
+    pub tenants: BTreeMap<String, TenantUsageReport>,
+}
+
+struct UsageState {
+    day: NaiveDate,
+    tenants: HashMap<String, TenantUsage>,
+}
+
+pub struct UsageTracker {
+    quotas: QuotaConfig,
+    state: Mutex<UsageState>,
+}
+
+impl UsageTracker {
+    pub fn new(quotas: QuotaConfig) -> Self {
+        Self {
+            quotas,
+            state: Mutex::new(UsageState {
+                day: Utc::now().date_naive(),
+                tenants: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Состояние с обнуленными суточными счетчиками, если начались новые сутки
+    fn lock(&self) -> std::sync::MutexGuard<'_, UsageState> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let today = Utc::now().date_naive();
+        if state.day != today {
+            state.day = today;
+            for usage in state.tenants.values_mut() {
+                usage.requests = 0;
+                usage.training_seconds = 0.0;
+            }
+        }
+        state
+    }
+
+    /// Учитывает запрос арендатора. `trains` - запрос может обучать модели и
+    /// сохранять снимки, для него проверяются и квоты обучения и хранения.
+    /// Отклоненный запрос не учитывается
+    pub fn admit(&self, tenant: &str, trains: bool) -> Result<(), QuotaExceeded> {
+        let limits = self.quotas.limits(tenant);
+        let mut state = self.lock();
+        let usage = state.tenants.entry(tenant.to_string()).or_default();
+
+        if let Some(limit) = limits.requests_per_day {
+            if usage.requests >= limit {
+                return Err(QuotaExceeded {
+                    quota: "requests_per_day",
+                    limit: limit as f64,
+                    used: usage.requests as f64,
+                    daily: true,
+                });
+            }
+        }
+        if trains {
+            if let Some(limit) = limits.training_seconds_per_day {
+                if usage.training_seconds >= limit {
+                    return Err(QuotaExceeded {
+                        quota: "training_seconds_per_day",
+                        limit,
+                        used: usage.training_seconds,
+                        daily: true,
+                    });
+                }
+            }
+            if let Some(limit) = limits.model_bytes {
+                if usage.model_bytes >= limit {
+                    return Err(QuotaExceeded {
+                        quota: "model_bytes",
+                        limit: limit as f64,
+                        used: usage.model_bytes as f64,
+                        daily: false,
+                    });
+                }
+            }
+        }
+        usage.requests += 1;
+        Ok(())
+    }
+
+    pub fn record_training(&self, tenant: &str, elapsed: Duration) {
+        let mut state = self.lock();
+        let usage = state.tenants.entry(tenant.to_string()).or_default();
+        usage.training_seconds += elapsed.as_secs_f64();
+    }
+
+    /// Снимок `name` арендатора (`forecasting:acme`) сохранен или загружен из
+    /// хранилища; прежний размер снимка с тем же именем заменяется
+    pub fn record_snapshot(&self, tenant: &str, name: &str, bytes: u64) {
+        let mut state = self.lock();
+        let usage = state.tenants.entry(tenant.to_string()).or_default();
+        let previous = usage.snapshots.insert(name.to_string(), bytes).unwrap_or(0);
+        usage.model_bytes = usage.model_bytes - previous + bytes;
+    }
+
+    /// Данные арендатора удалены: снимки больше не учитываются. Запросы и
+    /// обучение за сутки остаются до полуночи UTC, иначе удаление данных
+    /// сбрасывало бы суточные квоты
+    pub fn remove_snapshots(&self, tenant: &str) {
+        let mut state = self.lock();
+        if let Some(usage) = state.tenants.get_mut(tenant) {
+            usage.snapshots.clear();
+            usage.model_bytes = 0;
+        }
+    }
+
+    /// Снимок `name` удален из хранилища: его размер больше не учитывается
+    pub fn remove_snapshot(&self, name: &str) {
+        let mut state = self.lock();
+        for usage in state.tenants.values_mut() {
+            if let Some(bytes) = usage.snapshots.remove(name) {
+                usage.model_bytes -= bytes;
+            }
+        }
+    }
+
+    pub fn report(&self) -> UsageReport {
+        let state = self.lock();
+        UsageReport {
+            day: state.day,
+            tenants: state
+                .tenants
+                .iter()
+                .map(|(tenant, usage)| {
+                    (
+                        tenant.clone(),
+                        TenantUsageReport {
+                            usage: usage.clone(),
+                            limits: self.quotas.limits(tenant),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self::new(QuotaConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model_bytes(tracker: &UsageTracker, tenant: &str) -> u64 {
+        tracker.report().tenants[tenant].usage.model_bytes
+    }
+
+    #[test]
+    fn snapshots_are_charged_per_tenant_and_replaced() {
+        let tracker = UsageTracker::default();
+        tracker.record_snapshot("acme", "forecasting:acme", 100);
+        tracker.record_snapshot("acme", "anomaly:acme", 50);
+        tracker.record_snapshot("globex", "forecasting:globex", 70);
+        assert_eq!(model_bytes(&tracker, "acme"), 150);
+        assert_eq!(model_bytes(&tracker, "globex"), 70);
+
+        // Переобучение заменяет снимок: учитывается только новый размер
+        tracker.record_snapshot("acme", "forecasting:acme", 40);
+        assert_eq!(model_bytes(&tracker, "acme"), 90);
+        assert_eq!(model_bytes(&tracker, "globex"), 70);
+
+        tracker.remove_snapshot("anomaly:acme");
+        assert_eq!(model_bytes(&tracker, "acme"), 40);
+        assert_eq!(
+            tracker.report().tenants["acme"].usage.snapshots,
+            BTreeMap::from([("forecasting:acme".to_string(), 40)])
+        );
+    }
+}